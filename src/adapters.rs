@@ -0,0 +1,229 @@
+//! Hook formats of other agent CLIs.
+//!
+//! `hook --format <name>` accepts another agent's approval callback, turns it
+//! into Claude Code's PermissionRequest input, and turns our answer back into
+//! what that agent expects. Tool names are mapped onto Claude Code's (`Bash`,
+//! `Edit`, `Write`, ...) so always-allow lists, policies and approval rules
+//! cover every agent on the machine alike.
+
+use serde_json::{json, Map, Value};
+
+/// Hook formats accepted by `hook --format`.
+pub const FORMATS: [&str; 4] = ["claude", "codex", "gemini", "aider"];
+
+/// Translate another agent's approval request into Claude Code hook input.
+pub fn to_claude_input(format: &str, input: &str) -> Result<String, serde_json::Error> {
+    if format == "claude" {
+        return Ok(input.to_string());
+    }
+
+    let raw: Value = serde_json::from_str(input)?;
+    let (tool_name, tool_input) = match format {
+        "codex" => codex_tool(&raw),
+        "gemini" => gemini_tool(&raw),
+        _ => aider_tool(&raw),
+    };
+    let claude = json!({
+        "hook_event_name": "PermissionRequest",
+        "tool_name": tool_name,
+        "tool_input": tool_input,
+        "cwd": raw.get("cwd").and_then(Value::as_str).unwrap_or_default(),
+    });
+    serde_json::to_string(&claude)
+}
+
+/// Translate our Claude Code hook output into the agent's answer.
+pub fn from_claude_output(format: &str, output: &str) -> Result<String, serde_json::Error> {
+    if format == "claude" {
+        return Ok(output.to_string());
+    }
+
+    let output: Value = serde_json::from_str(output)?;
+    let decision = output.pointer("/hookSpecificOutput/decision");
+    let allowed = decision
+        .and_then(|d| d.get("behavior"))
+        .and_then(Value::as_str)
+        == Some("allow");
+    let reason = decision
+        .and_then(|d| d.get("message"))
+        .and_then(Value::as_str);
+
+    let answer = match format {
+        "codex" => json!({"decision": if allowed { "approved" } else { "denied" }}),
+        "gemini" => {
+            let mut answer = json!({"decision": if allowed { "allow" } else { "deny" }});
+            if let Some(reason) = reason.filter(|_| !allowed) {
+                answer["reason"] = json!(reason);
+            }
+            answer
+        }
+        _ => json!({"answer": if allowed { "yes" } else { "no" }}),
+    };
+    serde_json::to_string(&answer)
+}
+
+/// Codex exec and apply-patch approval requests.
+fn codex_tool(raw: &Value) -> (String, Value) {
+    let reason = raw.get("reason").cloned().unwrap_or(Value::Null);
+    match raw.get("type").and_then(Value::as_str) {
+        Some("apply_patch_approval_request") => {
+            let changes = raw.get("changes").cloned().unwrap_or_else(|| json!({}));
+            let files: Vec<&str> = changes
+                .as_object()
+                .map(|files| files.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            (
+                "Edit".to_string(),
+                json!({"file_path": files.join(", "), "changes": changes, "description": reason}),
+            )
+        }
+        _ => {
+            let command = match raw.get("command") {
+                Some(Value::Array(args)) => join_command(args),
+                Some(Value::String(command)) => command.clone(),
+                _ => String::new(),
+            };
+            (
+                "Bash".to_string(),
+                json!({"command": command, "description": reason}),
+            )
+        }
+    }
+}
+
+/// Gemini CLI `BeforeTool` hook input.
+fn gemini_tool(raw: &Value) -> (String, Value) {
+    let name = raw
+        .get("tool_name")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown");
+    let input = raw.get("tool_input").cloned().unwrap_or_else(|| json!({}));
+    let tool_name = match name {
+        "run_shell_command" => "Bash",
+        "write_file" => "Write",
+        "replace" => "Edit",
+        "read_file" => "Read",
+        "web_fetch" => "WebFetch",
+        "google_web_search" => "WebSearch",
+        other => other,
+    };
+    (tool_name.to_string(), input)
+}
+
+/// Aider confirmation prompt, e.g. `{"question": "Run shell command?", "subject": "make"}`.
+fn aider_tool(raw: &Value) -> (String, Value) {
+    let question = raw.get("question").and_then(Value::as_str).unwrap_or("");
+    let subject = raw.get("subject").and_then(Value::as_str).unwrap_or("");
+    let lower = question.to_ascii_lowercase();
+    if lower.contains("shell command") {
+        ("Bash".to_string(), json!({"command": subject}))
+    } else if lower.contains("create new file") {
+        ("Write".to_string(), json!({"file_path": subject}))
+    } else if lower.contains("edit") || (lower.contains("add") && lower.contains("file")) {
+        ("Edit".to_string(), json!({"file_path": subject}))
+    } else {
+        let mut input = Map::new();
+        input.insert("question".to_string(), json!(question));
+        input.insert("subject".to_string(), json!(subject));
+        ("AiderConfirm".to_string(), Value::Object(input))
+    }
+}
+
+/// Render an argv array as one shell command line.
+///
+/// `bash -lc <script>` style wrappers are unwrapped to the script itself.
+fn join_command(args: &[Value]) -> String {
+    let args: Vec<&str> = args.iter().filter_map(Value::as_str).collect();
+    if let [shell, flag, script] = args.as_slice() {
+        if shell.ends_with("sh") && flag.starts_with('-') && flag.ends_with('c') {
+            return script.to_string();
+        }
+    }
+    args.iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "/._-=:,+@%".contains(c))
+            {
+                arg.to_string()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claude_input(format: &str, input: Value) -> Value {
+        let translated = to_claude_input(format, &input.to_string()).unwrap();
+        serde_json::from_str(&translated).unwrap()
+    }
+
+    #[test]
+    fn test_codex_requests() {
+        let exec = claude_input(
+            "codex",
+            json!({"type": "exec_approval_request", "command": ["bash", "-lc", "git push"], "cwd": "/repo"}),
+        );
+        assert_eq!(exec["tool_name"], "Bash");
+        assert_eq!(exec["tool_input"]["command"], "git push");
+        assert_eq!(exec["cwd"], "/repo");
+
+        let argv = claude_input(
+            "codex",
+            json!({"type": "exec_approval_request", "command": ["rm", "-rf", "my dir"]}),
+        );
+        assert_eq!(argv["tool_input"]["command"], "rm -rf 'my dir'");
+
+        let patch = claude_input(
+            "codex",
+            json!({"type": "apply_patch_approval_request", "changes": {"src/lib.rs": {"update": {}}}}),
+        );
+        assert_eq!(patch["tool_name"], "Edit");
+        assert_eq!(patch["tool_input"]["file_path"], "src/lib.rs");
+    }
+
+    #[test]
+    fn test_gemini_and_aider_requests() {
+        let gemini = claude_input(
+            "gemini",
+            json!({"hook_event_name": "BeforeTool", "tool_name": "run_shell_command", "tool_input": {"command": "ls"}}),
+        );
+        assert_eq!(gemini["tool_name"], "Bash");
+        assert_eq!(gemini["tool_input"]["command"], "ls");
+
+        let aider = claude_input(
+            "aider",
+            json!({"question": "Run shell command?", "subject": "make test"}),
+        );
+        assert_eq!(aider["tool_name"], "Bash");
+        assert_eq!(aider["tool_input"]["command"], "make test");
+    }
+
+    #[test]
+    fn test_answers() {
+        let deny = json!({"hookSpecificOutput": {"hookEventName": "PermissionRequest",
+            "decision": {"behavior": "deny", "message": "Denied by policy"}}})
+        .to_string();
+        let allow = json!({"hookSpecificOutput": {"decision": {"behavior": "allow"}}}).to_string();
+
+        assert_eq!(
+            from_claude_output("codex", &allow).unwrap(),
+            r#"{"decision":"approved"}"#
+        );
+        assert_eq!(
+            from_claude_output("gemini", &deny).unwrap(),
+            r#"{"decision":"deny","reason":"Denied by policy"}"#
+        );
+        assert_eq!(
+            from_claude_output("aider", &deny).unwrap(),
+            r#"{"answer":"no"}"#
+        );
+        assert_eq!(from_claude_output("claude", &deny).unwrap(), deny);
+    }
+}
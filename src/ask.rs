@@ -0,0 +1,36 @@
+//! Yes/no approvals for shell scripts.
+//!
+//! `ask` sends a question with Allow/Deny buttons through the first available
+//! messenger and reports the answer through the exit status.
+
+use crate::config::{Config, Severity};
+use crate::messenger::{available_messengers, Decision};
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+
+/// Ask a question and wait for the answer.
+///
+/// Returns `true` when approved and `false` when denied or timed out.
+pub async fn run(
+    config_path: Option<&Path>,
+    question: &str,
+    timeout_seconds: Option<u64>,
+) -> Result<bool> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
+    let timeout = Duration::from_secs(timeout_seconds.unwrap_or(config.timeout_seconds));
+
+    let Some(messenger) = available_messengers(&config, Severity::Normal)
+        .await
+        .into_iter()
+        .next()
+    else {
+        anyhow::bail!("No messenger configured");
+    };
+
+    let decision = messenger
+        .send_question(question, &config.host_label, timeout)
+        .await?;
+
+    Ok(decision != Decision::Deny)
+}
@@ -0,0 +1,351 @@
+//! Tamper-evident audit log of permission requests and decisions.
+//!
+//! Each record is one JSON line carrying the SHA-256 hash of the record
+//! before it, so editing, reordering or deleting a line breaks the chain.
+//! The newest sequence number and hash are also kept in a `.head` file next
+//! to the log, which lets `audit verify` notice lines cut from the end.
+
+use crate::clock::now_secs;
+use crate::config::{AuditLogConfig, Config};
+use crate::lockfile::FileLock;
+use crate::messenger::dry_run;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// `prev_hash` of the first record in a log.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// How far from the end of the log to look for the last record.
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// Kind of audited event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    Request,
+    Decision,
+}
+
+/// What happened, independent of its position in the chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub event: AuditEvent,
+    pub request_id: String,
+    /// Messenger the request went through
+    pub platform: String,
+    /// Host the request came from
+    pub host: String,
+    /// One-line description of the tool call (already redacted)
+    pub tool: String,
+    /// Who answered, when the platform reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub responder: Option<String>,
+    /// "allow", "deny", "deny_block", "snooze", "auto_allow", "blocked: ..."
+    /// or "error: ..." for decisions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<String>,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Position in the log, starting at 0
+    pub seq: u64,
+    /// Unix time of the event
+    pub time: u64,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    /// Hash of the previous record, or `GENESIS_HASH` for the first
+    pub prev_hash: String,
+    /// SHA-256 of this record serialized without the `hash` field
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Hash this record's content, ignoring any stored hash.
+    fn compute_hash(&self) -> io::Result<String> {
+        let unhashed = AuditRecord {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let digest = Sha256::digest(serde_json::to_vec(&unhashed)?);
+        Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+}
+
+/// Result of checking an audit log.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// Every record chains correctly and matches the head file
+    Intact { records: u64 },
+    /// The first problem found; `line` is `None` for head file mismatches
+    Broken { line: Option<usize>, reason: String },
+}
+
+/// Path of the head file kept next to `log`.
+fn head_path(log: &Path) -> PathBuf {
+    let mut name = OsString::from(log.as_os_str());
+    name.push(".head");
+    PathBuf::from(name)
+}
+
+/// Read the last record of a log, if it has any.
+fn last_record(path: &Path) -> io::Result<Option<AuditRecord>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let tail = String::from_utf8_lossy(&tail);
+    let Some(line) = tail.lines().rev().find(|line| !line.trim().is_empty()) else {
+        return Ok(None);
+    };
+    serde_json::from_str(line).map(Some).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("last audit record is malformed: {}", e),
+        )
+    })
+}
+
+/// Append an entry to the log, chaining it to the previous record.
+pub fn append(path: &Path, entry: AuditEntry) -> io::Result<AuditRecord> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _lock = FileLock::acquire(path)?;
+
+    let (seq, prev_hash) = match last_record(path)? {
+        Some(last) => (last.seq + 1, last.hash),
+        None => (0, GENESIS_HASH.to_string()),
+    };
+    let mut record = AuditRecord {
+        seq,
+        time: now_secs(),
+        entry,
+        prev_hash,
+        hash: String::new(),
+    };
+    record.hash = record.compute_hash()?;
+
+    let mut line = serde_json::to_string(&record)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    fs::write(head_path(path), format!("{} {}\n", record.seq, record.hash))?;
+
+    Ok(record)
+}
+
+/// Record an entry in the configured audit log, if enabled.
+///
+/// Failures are logged and otherwise ignored so auditing never breaks a hook.
+/// Nothing is recorded in dry-run mode.
+pub fn record(config: &AuditLogConfig, entry: AuditEntry) {
+    if !config.enabled || dry_run() {
+        return;
+    }
+    if let Err(e) = append(&config.path(), entry) {
+        tracing::warn!("Failed to write audit log: {}", e);
+    }
+}
+
+/// Check the hash chain of a log and compare its end with the head file.
+pub fn verify(path: &Path) -> io::Result<Verification> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let broken = |line: usize, reason: String| {
+        Ok(Verification::Broken {
+            line: Some(line),
+            reason,
+        })
+    };
+
+    let mut last: Option<AuditRecord> = None;
+    for (index, line) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let record: AuditRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(e) => return broken(line_no, format!("malformed record: {}", e)),
+        };
+
+        let expected_seq = last.as_ref().map_or(0, |r| r.seq + 1);
+        if record.seq != expected_seq {
+            return broken(
+                line_no,
+                format!(
+                    "expected record {}, found {} (records missing)",
+                    expected_seq, record.seq
+                ),
+            );
+        }
+        let expected_prev = last.as_ref().map_or(GENESIS_HASH, |r| r.hash.as_str());
+        if record.prev_hash != expected_prev {
+            return broken(line_no, "does not chain to the previous record".to_string());
+        }
+        if record.compute_hash()? != record.hash {
+            return broken(line_no, "hash mismatch (record was modified)".to_string());
+        }
+        last = Some(record);
+    }
+
+    let head = match fs::read_to_string(head_path(path)) {
+        Ok(head) => Some(head.trim().to_string()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    let tail = last.as_ref().map(|r| format!("{} {}", r.seq, r.hash));
+    if head != tail {
+        let reason = match (head, tail) {
+            (None, _) => "head file is missing".to_string(),
+            (Some(head), None) => format!("log is empty but head file records {}", head),
+            (Some(head), Some(tail)) => format!(
+                "log ends at {} but head file records {} (log was truncated)",
+                tail, head
+            ),
+        };
+        return Ok(Verification::Broken { line: None, reason });
+    }
+
+    Ok(Verification::Intact {
+        records: last.map_or(0, |r| r.seq + 1),
+    })
+}
+
+/// Verify an audit log and print the result. Returns whether it is intact.
+///
+/// Without an explicit path, the log configured in `config_path` is checked.
+pub fn run_verify(config_path: Option<&Path>, path: Option<&Path>) -> Result<bool> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => Config::load(config_path.map(Path::to_path_buf))
+            .map(|config| config.audit_log.path())
+            .unwrap_or_else(|_| AuditLogConfig::default().path()),
+    };
+
+    let verification =
+        verify(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    match verification {
+        Verification::Intact { records } => {
+            println!(
+                "✅ Audit log intact: {} record(s) in {}",
+                records,
+                path.display()
+            );
+            Ok(true)
+        }
+        Verification::Broken { line, reason } => {
+            match line {
+                Some(line) => println!("❌ {} line {}: {}", path.display(), line, reason),
+                None => println!("❌ {}: {}", path.display(), reason),
+            }
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(request_id: &str, outcome: Option<&str>) -> AuditEntry {
+        AuditEntry {
+            event: if outcome.is_some() {
+                AuditEvent::Decision
+            } else {
+                AuditEvent::Request
+            },
+            request_id: request_id.to_string(),
+            platform: "Telegram".to_string(),
+            host: "build-01".to_string(),
+            tool: "Bash `ls`".to_string(),
+            responder: outcome.map(|_| "@alice (42)".to_string()),
+            outcome: outcome.map(str::to_string),
+        }
+    }
+
+    fn write_sample(path: &Path) {
+        append(path, entry("a", None)).unwrap();
+        append(path, entry("a", Some("allow"))).unwrap();
+        append(path, entry("b", None)).unwrap();
+        append(path, entry("b", Some("deny"))).unwrap();
+    }
+
+    #[test]
+    fn test_append_chains_records() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let first = append(&path, entry("a", None)).unwrap();
+        let second = append(&path, entry("a", Some("allow"))).unwrap();
+
+        assert_eq!(first.seq, 0);
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+        assert_eq!(second.seq, 1);
+        assert_eq!(second.prev_hash, first.hash);
+        assert_eq!(verify(&path).unwrap(), Verification::Intact { records: 2 });
+        assert_eq!(
+            verify(&dir.path().join("missing.jsonl")).unwrap(),
+            Verification::Intact { records: 0 }
+        );
+    }
+
+    #[test]
+    fn test_verify_detects_modification() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        write_sample(&path);
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, content.replacen("\"deny\"", "\"allow\"", 1)).unwrap();
+
+        assert_eq!(
+            verify(&path).unwrap(),
+            Verification::Broken {
+                line: Some(4),
+                reason: "hash mismatch (record was modified)".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_verify_detects_deleted_and_truncated_records() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        write_sample(&path);
+        let lines: Vec<String> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| format!("{}\n", l))
+            .collect();
+
+        fs::write(&path, [lines[0].as_str(), &lines[2], &lines[3]].concat()).unwrap();
+        assert!(matches!(
+            verify(&path).unwrap(),
+            Verification::Broken { line: Some(2), .. }
+        ));
+
+        fs::write(&path, lines[..3].concat()).unwrap();
+        assert!(matches!(
+            verify(&path).unwrap(),
+            Verification::Broken { line: None, reason } if reason.contains("truncated")
+        ));
+    }
+}
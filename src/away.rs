@@ -0,0 +1,284 @@
+//! Delegation and vacation mode, set from chat.
+//!
+//! `/delegate <id> 48h` in the bot lets another user answer requests until
+//! the time is up, as if they were on the `authorized_responders` list of
+//! that platform. Delegates are kept by user ID only, since usernames can be
+//! changed and taken by someone else. `/vacation` has the hook decide every request it would
+//! otherwise ask about by `vacation_fallback`, until `/vacation off`. Both are
+//! kept in a small file in the state directory, read by the hook and the
+//! messengers on each request or button press.
+
+use crate::clock::now_secs;
+use crate::config::default_away_path;
+use crate::lockfile::{write_atomic, FileLock};
+use crate::messenger::is_authorized;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Someone allowed to answer requests for a while.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delegate {
+    /// Messenger the delegation applies to, e.g. `telegram`
+    pub platform: String,
+    /// User ID
+    pub user: String,
+    /// How the user was named when delegated, e.g. `@bob`, for display
+    #[serde(default)]
+    pub name: String,
+    /// Who delegated
+    pub by: String,
+    /// Unix time the delegation ends
+    pub until: u64,
+}
+
+impl Delegate {
+    /// Whether the delegate is this user.
+    fn is(&self, platform: &str, id: &str) -> bool {
+        self.platform == platform && self.user == id
+    }
+
+    /// Whether the delegate is named by a user ID or the name it was given
+    /// under (ignoring case).
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    fn named(&self, platform: &str, user: &str) -> bool {
+        self.platform == platform
+            && (self.user == user
+                || (!self.name.is_empty() && self.name.eq_ignore_ascii_case(user)))
+    }
+}
+
+impl fmt::Display for Delegate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let left = self.until.saturating_sub(now_secs());
+        let who = if self.name.is_empty() {
+            self.user.clone()
+        } else {
+            format!("{} ({})", self.name, self.user)
+        };
+        write!(f, "{} for {}h (by {})", who, left.div_ceil(3600), self.by)
+    }
+}
+
+/// Vacation mode, while it is on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vacation {
+    /// Who turned it on
+    pub by: String,
+    /// Unix time it was turned on
+    #[serde(default)]
+    pub since: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AwayData {
+    #[serde(default)]
+    delegates: Vec<Delegate>,
+    #[serde(default)]
+    vacation: Option<Vacation>,
+}
+
+/// Delegations and vacation mode.
+#[derive(Debug, Clone)]
+pub struct Away {
+    storage_path: PathBuf,
+}
+
+impl Away {
+    /// Create the state kept at the given path, or the default state file.
+    pub fn new(storage_path: Option<PathBuf>) -> Self {
+        Self {
+            storage_path: storage_path.unwrap_or_else(default_away_path),
+        }
+    }
+
+    fn load(&self) -> AwayData {
+        fs::read_to_string(&self.storage_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Read, change and write the state while holding its lock.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    fn update<T>(&self, change: impl FnOnce(&mut AwayData) -> T) -> io::Result<T> {
+        if let Some(parent) = self.storage_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _lock = FileLock::acquire(&self.storage_path)?;
+        let mut data = self.load();
+        let result = change(&mut data);
+        write_atomic(&self.storage_path, &serde_json::to_string_pretty(&data)?)?;
+        Ok(result)
+    }
+
+    /// Delegations on a platform that have not ended.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn delegates(&self, platform: &str) -> Vec<Delegate> {
+        self.delegates_at(platform, now_secs())
+    }
+
+    fn delegates_at(&self, platform: &str, now: u64) -> Vec<Delegate> {
+        self.load()
+            .delegates
+            .into_iter()
+            .filter(|delegate| delegate.platform == platform && delegate.until > now)
+            .collect()
+    }
+
+    /// Whether a user, by ID, is a delegate on a platform.
+    #[cfg_attr(not(any(feature = "telegram", unix, windows)), allow(dead_code))]
+    pub fn is_delegate(&self, platform: &str, id: &str) -> bool {
+        self.delegates_at(platform, now_secs())
+            .iter()
+            .any(|delegate| delegate.is(platform, id))
+    }
+
+    /// Let a user, by ID, answer requests until `until`, replacing an earlier
+    /// delegation. `name` is only shown in the list of delegates.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn delegate(
+        &self,
+        platform: &str,
+        user: &str,
+        name: &str,
+        by: &str,
+        until: u64,
+    ) -> io::Result<()> {
+        let now = now_secs();
+        self.update(|data| {
+            data.delegates.retain(|delegate| {
+                delegate.until > now && !(delegate.platform == platform && delegate.user == user)
+            });
+            data.delegates.push(Delegate {
+                platform: platform.to_string(),
+                user: user.to_string(),
+                name: name.to_string(),
+                by: by.to_string(),
+                until,
+            });
+        })
+    }
+
+    /// End a delegation early, by user ID or the name it was given under.
+    /// Returns false if there was none.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn revoke(&self, platform: &str, user: &str) -> io::Result<bool> {
+        self.update(|data| {
+            let before = data.delegates.len();
+            data.delegates
+                .retain(|delegate| !delegate.named(platform, user));
+            data.delegates.len() < before
+        })
+    }
+
+    /// Vacation mode, if it is on.
+    pub fn vacation(&self) -> Option<Vacation> {
+        self.load().vacation
+    }
+
+    /// Turn vacation mode on. Returns false if it already was.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn start_vacation(&self, by: &str) -> io::Result<bool> {
+        let since = now_secs();
+        self.update(|data| {
+            if data.vacation.is_some() {
+                return false;
+            }
+            data.vacation = Some(Vacation {
+                by: by.to_string(),
+                since,
+            });
+            true
+        })
+    }
+
+    /// Turn vacation mode off, returning how it was turned on.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn end_vacation(&self) -> io::Result<Option<Vacation>> {
+        self.update(|data| data.vacation.take())
+    }
+}
+
+/// A delegate as given in `/delegate`, as its user ID and display name.
+///
+/// A numeric ID is taken as is. A `@username` is resolved once to the ID of
+/// `replied_to`, the author (ID and username) of the message the command
+/// replies to, and only if that author has the username.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn parse_user(text: &str, replied_to: Option<(u64, Option<&str>)>) -> Option<(String, String)> {
+    match text.strip_prefix('@') {
+        Some(name) => {
+            let (id, username) = replied_to?;
+            let username = username.filter(|username| username.eq_ignore_ascii_case(name))?;
+            Some((id.to_string(), format!("@{}", username)))
+        }
+        None => (!text.is_empty() && text.chars().all(|c| c.is_ascii_digit()))
+            .then(|| (text.to_string(), String::new())),
+    }
+}
+
+/// Whether a Telegram user may answer requests: on the allowlist, or a
+/// delegate.
+#[cfg_attr(not(any(feature = "telegram", unix, windows)), allow(dead_code))]
+pub fn telegram_responder(allowed: &[u64], id: u64) -> bool {
+    is_authorized(allowed, &id) || Away::new(None).is_delegate("telegram", &id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_delegates_and_vacation() {
+        let dir = tempdir().unwrap();
+        let away = Away::new(Some(dir.path().join("away.json")));
+        let now = now_secs();
+        assert!(!away.is_delegate("telegram", "42"));
+
+        away.delegate("telegram", "42", "@Bob", "@alice", now + 3600)
+            .unwrap();
+        away.delegate("telegram", "77", "", "@alice", now - 1)
+            .unwrap();
+        assert!(away.is_delegate("telegram", "42"));
+        assert!(!away.is_delegate("signal", "42"));
+        // Ended delegations no longer count
+        assert!(!away.is_delegate("telegram", "77"));
+        assert_eq!(away.delegates("telegram").len(), 1);
+        assert_eq!(
+            away.delegates("telegram")[0].to_string(),
+            "@Bob (42) for 1h (by @alice)"
+        );
+
+        assert!(away.revoke("telegram", "@bob").unwrap());
+        assert!(!away.revoke("telegram", "42").unwrap());
+        assert!(!away.is_delegate("telegram", "42"));
+
+        assert_eq!(away.vacation(), None);
+        assert!(away.start_vacation("@alice").unwrap());
+        assert!(!away.start_vacation("@bob").unwrap());
+        assert_eq!(away.vacation().unwrap().by, "@alice");
+        assert_eq!(away.end_vacation().unwrap().unwrap().by, "@alice");
+        assert_eq!(away.vacation(), None);
+    }
+
+    #[test]
+    fn test_parse_user_resolves_usernames_by_reply() {
+        assert_eq!(
+            parse_user("123456", None),
+            Some(("123456".to_string(), String::new()))
+        );
+        assert_eq!(parse_user("bob", None), None);
+        // A username alone is never trusted
+        assert_eq!(parse_user("@bob", None), None);
+        assert_eq!(
+            parse_user("@bob", Some((42, Some("Bob")))),
+            Some(("42".to_string(), "@Bob".to_string()))
+        );
+        assert_eq!(parse_user("@bob", Some((43, Some("eve")))), None);
+        assert_eq!(parse_user("@bob", Some((43, None))), None);
+    }
+}
@@ -1,11 +1,52 @@
 //! Long-running Telegram bot for /start, /help, /status commands.
+//!
+//! Updates are read through the shared spool in `messenger::updates`, so the
+//! bot can run alongside hook processes without stealing their button presses.
+//! `/allow_all` and `/deny_all` decide pending requests through the shared
+//! request ledger, which the waiting hook processes check. `/pause` and
+//! `/resume` edit the list of paused hosts and sessions the hook reads, and
+//! `/delegate` and `/vacation` the delegations and vacation mode. `/cat`
+//! and `/diff` show files in the configured projects, and `/git` the state
+//! of a session's working tree. `/yolo` opens a confirmed, time-boxed window
+//! in which a session's requests are allowed without asking.
+//! With rule suggestions enabled, the bot also offers always-allow entries
+//! from the decision history now and then, each added with one tap.
 
-use crate::config::Config;
+use crate::always_allow::AlwaysAllowManager;
+use crate::away::{self, Away};
+use crate::clock::now_secs;
+use crate::config::{default_claude_projects_path, Config, VacationFallback};
+use crate::history::{self, EventKind, HistoryEntry};
+use crate::launcher;
+use crate::lockfile::off_reactor;
+use crate::messenger::clients;
+use crate::messenger::ledger::{self, Resolution};
+use crate::messenger::telegram::{
+    answer_unclaimed_press, escape_code, may_answer, responder_name, send_with_retry,
+};
+use crate::messenger::updates::UpdateInbox;
+use crate::messenger::{is_authorized, new_request_id, notify_primary};
+use crate::observer::is_observer_chat;
+use crate::pause::PauseList;
+use crate::peek;
+use crate::redact::Redactor;
+use crate::stop_handler;
+use crate::suggestions;
 use crate::telegram::escape_markdown;
+use crate::tmux;
+use crate::yolo::{self, YoloWindows};
 use anyhow::Result;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use teloxide::prelude::*;
-use teloxide::types::ParseMode;
+use teloxide::types::{
+    CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode,
+    ThreadId, UpdateKind, User,
+};
 use teloxide::utils::command::BotCommands;
+use tokio::time::{interval, interval_at, Instant, Interval};
 
 /// Available bot commands.
 #[derive(BotCommands, Clone)]
@@ -17,6 +58,55 @@ pub enum Command {
     Help,
     #[command(description = "Check bot status")]
     Status,
+    #[command(description = "Start Claude in a project: /run <project> <prompt>")]
+    Run(String),
+    #[command(description = "List Claude sessions running in tmux")]
+    Sessions,
+    #[command(description = "Interrupt a session: /interrupt <id>")]
+    Interrupt(String),
+    #[command(description = "Type a prompt into a session: /send <id> <text>")]
+    Send(String),
+    #[command(description = "Show a file in a project: /cat <path>")]
+    Cat(String),
+    #[command(description = "Show a file's uncommitted changes: /diff <path>")]
+    Diff(String),
+    #[command(description = "Show git status of a session or host: /git <session|host>")]
+    Git(String),
+    #[command(
+        rename = "allow_all",
+        description = "Allow every pending request: /allow_all [host|session]"
+    )]
+    AllowAll(String),
+    #[command(
+        rename = "deny_all",
+        description = "Deny every pending request: /deny_all [host|session]"
+    )]
+    DenyAll(String),
+    #[command(description = "List requests waiting for an answer: /pending [host|session]")]
+    Pending(String),
+    #[command(description = "Deny a host's or session's requests: /pause <host|session>")]
+    Pause(String),
+    #[command(description = "Stop denying them: /resume [host|session]")]
+    Resume(String),
+    #[command(description = "Let someone answer for a while: /delegate <id|@user> <48h|off>")]
+    Delegate(String),
+    #[command(description = "Decide requests by vacation_fallback: /vacation [off]")]
+    Vacation(String),
+    #[command(description = "Allow a session's requests for a while: /yolo <session> <10m|off>")]
+    Yolo(String),
+}
+
+/// A bulk decision shown for confirmation.
+struct BulkDecision {
+    allow: bool,
+    /// The requests listed in the confirmation, the only ones it decides
+    request_ids: Vec<String>,
+}
+
+/// A yolo window shown for confirmation.
+struct YoloRequest {
+    session: String,
+    length: Duration,
 }
 
 /// Handle the /start command.
@@ -36,9 +126,11 @@ async fn start_handler(bot: Bot, msg: Message) -> ResponseResult<()> {
         chat_id, chat_id
     );
 
-    bot.send_message(chat_id, text)
-        .parse_mode(ParseMode::MarkdownV2)
-        .await?;
+    send_with_retry(
+        bot.send_message(chat_id, text)
+            .parse_mode(ParseMode::MarkdownV2),
+    )
+    .await?;
 
     Ok(())
 }
@@ -78,66 +170,1222 @@ This bot integrates with Claude Code to handle permission requests remotely\.
 *Commands:*
 /start \- Show your chat ID
 /help \- Show this help
-/status \- Check bot status"#;
+/status \- Check bot status
+/run \- Start Claude in a configured project
+/sessions \- List Claude sessions in tmux
+/interrupt \- Interrupt a session
+/send \- Type a prompt into a session
+/cat \- Show a file in a configured project
+/diff \- Show a file's uncommitted changes
+/git \- Show git status and diff stat of a session or this host's sessions
+/pending \- List requests waiting for an answer
+/allow\_all \- Allow every pending request, optionally for one host or session
+/deny\_all \- Deny every pending request, optionally for one host or session
+/pause \- Deny all requests from a host or session until resumed
+/resume \- Resume a paused host or session, or all of them
+/delegate \- Let someone else answer requests for a while, e\.g\. `/delegate 123456789 48h`, or `/delegate @bob 48h` in reply to their message
+/vacation \- Decide requests without asking until `/vacation off`
+/yolo \- Allow every request from a session for a few minutes, e\.g\. `/yolo 1a2b3c4d 10m`"#;
 
-    bot.send_message(msg.chat.id, text)
-        .parse_mode(ParseMode::MarkdownV2)
-        .await?;
+    send_with_retry(
+        bot.send_message(msg.chat.id, text)
+            .parse_mode(ParseMode::MarkdownV2),
+    )
+    .await?;
 
     Ok(())
 }
 
 /// Handle the /status command.
 async fn status_handler(bot: Bot, msg: Message, config: &Config) -> ResponseResult<()> {
+    let window = Duration::from_secs(history::STATS_DAYS * 86400);
+    let stats = off_reactor(move || history::decision_stats(window)).await;
     let text = format!(
         "✅ *Bot Status: Online*\n\n\
         🖥️ *Host:* `{}`\n\
-        💬 *Chat ID:* `{}`",
-        escape_markdown(&config.hostname),
-        msg.chat.id
+        💬 *Chat ID:* `{}`\n\n\
+        ⏱️ *Decisions \\(last {} days\\):*\n{}",
+        escape_markdown(&config.host_label),
+        msg.chat.id,
+        history::STATS_DAYS,
+        escape_markdown(&stats.lines().join("\n"))
     );
 
-    bot.send_message(msg.chat.id, text)
-        .parse_mode(ParseMode::MarkdownV2)
+    send_with_retry(
+        bot.send_message(msg.chat.id, text)
+            .parse_mode(ParseMode::MarkdownV2),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Check that a command controlling this host comes from an authorized
+/// responder in the configured chat, telling the sender if not.
+async fn authorize_host_control(bot: &Bot, msg: &Message, config: &Config) -> ResponseResult<bool> {
+    let responders = &config.authorized_responders.telegram;
+    let sender = msg.from.as_ref().map(|user| user.id.0);
+    let from_home_chat = config
+        .telegram
+        .as_ref()
+        .is_some_and(|telegram| telegram.chat_id.0 == msg.chat.id.0);
+    let authorized = config.remote_runs.enabled
+        && from_home_chat
+        && !responders.is_empty()
+        && sender.is_some_and(|id| responders.contains(&id));
+    if !authorized {
+        tracing::warn!(
+            "Refused {:?} from {:?} in chat {}",
+            msg.text(),
+            sender,
+            msg.chat.id
+        );
+        send_with_retry(
+            bot.send_message(
+                msg.chat.id,
+                "⛔ Needs `remote_runs\\.enabled`, `authorized_responders\\.telegram` \
+                 and the configured chat",
+            )
+            .parse_mode(ParseMode::MarkdownV2),
+        )
+        .await?;
+    }
+    Ok(authorized)
+}
+
+/// Refuse a command sent in the observer chat, which never controls anything.
+async fn refuse_in_observer_chat(bot: &Bot, msg: &Message) -> ResponseResult<()> {
+    tracing::warn!("Refused {:?} in observer chat {}", msg.text(), msg.chat.id);
+    send_with_retry(bot.send_message(
+        msg.chat.id,
+        "👀 This chat only observes; send commands in the approval chat",
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Check that a user may answer permission requests, like pressing their
+/// buttons: in the configured chat and on the responder allowlist, if any,
+/// or a delegate.
+fn is_responder(config: &Config, chat_id: ChatId, user: Option<&User>) -> bool {
+    let from_home_chat = config
+        .telegram
+        .as_ref()
+        .is_some_and(|telegram| telegram.chat_id.0 == chat_id.0);
+    from_home_chat
+        && user.is_some_and(|user| may_answer(&config.authorized_responders.telegram, user))
+}
+
+/// Check that a user is on the responder allowlist itself, not a delegate,
+/// and in the configured chat.
+fn is_allowlisted(config: &Config, chat_id: ChatId, user: Option<&User>) -> bool {
+    let from_home_chat = config
+        .telegram
+        .as_ref()
+        .is_some_and(|telegram| telegram.chat_id.0 == chat_id.0);
+    from_home_chat
+        && user
+            .is_some_and(|user| is_authorized(&config.authorized_responders.telegram, &user.id.0))
+}
+
+/// Format a number of seconds as e.g. `45s` or `3m`.
+fn short_duration(secs: u64) -> String {
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m", secs / 60)
+    }
+}
+
+/// Handle /pending by listing waiting requests, with how long they have left.
+async fn pending_handler(
+    bot: Bot,
+    msg: Message,
+    config: &Config,
+    target: &str,
+) -> ResponseResult<()> {
+    let text = if !is_responder(config, msg.chat.id, msg.from.as_ref()) {
+        escape_markdown("⛔ Only authorized responders in the configured chat can list requests")
+    } else {
+        let pending = off_reactor(ledger::pending).await;
+        let now = now_secs();
+        let lines: Vec<String> = ledger::matching(pending, target.trim())
+            .iter()
+            .map(|request| {
+                format!(
+                    "• `{}` {}: {} _\\({} ago, {} left\\)_",
+                    request.request_id,
+                    escape_markdown(&request.details.host),
+                    escape_markdown(&request.details.summary),
+                    short_duration(now.saturating_sub(request.sent_at)),
+                    short_duration(request.expires_at.saturating_sub(now))
+                )
+            })
+            .collect();
+        if lines.is_empty() {
+            "No pending requests".to_string()
+        } else {
+            format!(
+                "⏳ *{} pending request\\(s\\)*\n{}",
+                lines.len(),
+                lines.join("\n")
+            )
+        }
+    };
+
+    send_with_retry(
+        bot.send_message(msg.chat.id, text)
+            .parse_mode(ParseMode::MarkdownV2),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Handle /allow_all and /deny_all by listing what they would decide.
+///
+/// Nothing is decided until the list is confirmed, and requests needing
+/// several approvals are left out of /allow_all.
+async fn bulk_handler(
+    bot: Bot,
+    msg: Message,
+    config: &Config,
+    allow: bool,
+    target: &str,
+) -> ResponseResult<Option<(String, BulkDecision)>> {
+    let reply = |text: String, keyboard: Option<InlineKeyboardMarkup>| {
+        let mut request = bot
+            .send_message(msg.chat.id, text)
+            .parse_mode(ParseMode::MarkdownV2);
+        if let Some(keyboard) = keyboard {
+            request = request.reply_markup(keyboard);
+        }
+        send_with_retry(request)
+    };
+    if !is_responder(config, msg.chat.id, msg.from.as_ref()) {
+        reply(
+            "⛔ Only authorized responders in the configured chat can decide requests".to_string(),
+            None,
+        )
         .await?;
+        return Ok(None);
+    }
+
+    let pending = off_reactor(ledger::pending).await;
+    let (requests, quorum): (Vec<_>, Vec<_>) = ledger::matching(pending, target.trim())
+        .into_iter()
+        .partition(|request| !allow || request.details.required_approvals <= 1);
+    let skipped = if quorum.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "\n\n_{} request\\(s\\) needing several approvals left out_",
+            quorum.len()
+        )
+    };
+    if requests.is_empty() {
+        reply(format!("No pending requests{}", skipped), None).await?;
+        return Ok(None);
+    }
+
+    let lines: Vec<String> = requests
+        .iter()
+        .map(|request| {
+            format!(
+                "• `{}` {}: {}",
+                request.request_id,
+                escape_markdown(&request.details.host),
+                escape_markdown(&request.details.summary)
+            )
+        })
+        .collect();
+    let (icon, verb) = if allow {
+        ("✅", "Allow")
+    } else {
+        ("❌", "Deny")
+    };
+    let token = new_request_id();
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(
+            format!("{} {}", icon, verb),
+            format!("{}:confirm_bulk", token),
+        ),
+        InlineKeyboardButton::callback("Cancel", format!("{}:cancel_bulk", token)),
+    ]]);
+    let text = format!(
+        "*{} {} pending request\\(s\\)?*\n{}{}",
+        verb,
+        requests.len(),
+        lines.join("\n"),
+        skipped
+    );
+    reply(text, Some(keyboard)).await?;
 
+    let request_ids = requests
+        .into_iter()
+        .map(|request| request.request_id)
+        .collect();
+    Ok(Some((token, BulkDecision { allow, request_ids })))
+}
+
+/// Parse a press on a bulk confirmation: its token and whether it confirms.
+fn parse_bulk_callback(data: &str) -> Option<(&str, bool)> {
+    let (token, action) = data.split_once(':')?;
+    match action {
+        "confirm_bulk" => Some((token, true)),
+        "cancel_bulk" => Some((token, false)),
+        _ => None,
+    }
+}
+
+/// Carry out or cancel a bulk decision once its confirmation is pressed.
+async fn confirm_bulk(
+    bot: &Bot,
+    query: &CallbackQuery,
+    bulk: BulkDecision,
+    confirmed: bool,
+) -> ResponseResult<()> {
+    let text = if confirmed {
+        let command = if bulk.allow { "allow_all" } else { "deny_all" };
+        let by = format!("{} via /{}", responder_name(&query.from), command);
+        let mut decided = 0;
+        for request_id in &bulk.request_ids {
+            let resolution = Resolution {
+                allow: bulk.allow,
+                by: by.clone(),
+            };
+            if ledger::resolve(request_id, resolution).await {
+                decided += 1;
+            }
+        }
+        tracing::info!("{} decided {} pending request(s)", by, decided);
+        let verb = if bulk.allow {
+            "✅ Allowed"
+        } else {
+            "❌ Denied"
+        };
+        format!(
+            "{} {} of {} request(s)",
+            verb,
+            decided,
+            bulk.request_ids.len()
+        )
+    } else {
+        "Cancelled".to_string()
+    };
+
+    send_with_retry(bot.answer_callback_query(&query.id)).await?;
+    if let Some(msg) = &query.message {
+        send_with_retry(bot.edit_message_text(msg.chat().id, msg.id(), text)).await?;
+    }
+    Ok(())
+}
+
+/// Offer always-allow suggestions not offered before, remembering each
+/// button's token and entry in `offered`.
+async fn offer_suggestions(
+    bot: &Bot,
+    config: &Config,
+    offered: &mut HashMap<String, String>,
+) -> ResponseResult<()> {
+    let Some(telegram) = &config.telegram else {
+        return Ok(());
+    };
+    let fresh: Vec<_> = suggestions::current(config)
+        .into_iter()
+        .filter(|suggestion| !offered.values().any(|entry| *entry == suggestion.entry))
+        .collect();
+    if fresh.is_empty() {
+        return Ok(());
+    }
+
+    let mut lines = vec![format!(
+        "💡 Always-allow suggestions for {}:",
+        config.host_label
+    )];
+    let mut buttons = Vec::new();
+    for suggestion in fresh {
+        lines.push(format!("• {}", suggestion.text()));
+        let token = new_request_id();
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("✅ Allow {}", suggestion.entry),
+            format!("{}:allow_rule", token),
+        )]);
+        offered.insert(token, suggestion.entry);
+    }
+    let mut request = bot
+        .send_message(ChatId(telegram.chat_id.0), lines.join("\n"))
+        .reply_markup(InlineKeyboardMarkup::new(buttons));
+    if let Some(topic_id) = telegram.topic_id {
+        request = request.message_thread_id(ThreadId(MessageId(topic_id)));
+    }
+    send_with_retry(request).await?;
+    Ok(())
+}
+
+/// Parse a press on a suggestion's button into its token.
+fn parse_rule_callback(data: &str) -> Option<&str> {
+    data.strip_suffix(":allow_rule")
+}
+
+/// Add a suggested entry to the always-allow list once its button is pressed.
+async fn allow_suggested(
+    bot: &Bot,
+    config: &Config,
+    query: &CallbackQuery,
+    entry: &str,
+) -> ResponseResult<()> {
+    let chat_id = query.message.as_ref().map(|msg| msg.chat().id);
+    let allowed = chat_id.is_some_and(|chat_id| is_responder(config, chat_id, Some(&query.from)));
+    let text = if allowed {
+        let owned = entry.to_string();
+        match off_reactor(move || AlwaysAllowManager::new(None).add_tool(&owned)).await {
+            Ok(()) => {
+                tracing::info!(
+                    "{} added {} to always-allow",
+                    responder_name(&query.from),
+                    entry
+                );
+                format!("🔓 {} is now always allowed", entry)
+            }
+            Err(e) => format!("Failed to add {}: {}", entry, e),
+        }
+    } else {
+        "You are not authorized to change always-allow rules".to_string()
+    };
+    send_with_retry(
+        bot.answer_callback_query(&query.id)
+            .text(text)
+            .show_alert(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Wait for the next suggestion message, or forever when they are off.
+async fn next_suggestions(timer: &mut Option<Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Answer `/pause` and `/resume`, or nothing for other commands.
+fn pause_reply(pauses: &PauseList, by: &str, cmd: Command) -> Option<String> {
+    let text = match cmd {
+        Command::Pause(target) if target.trim().is_empty() => {
+            let lines: Vec<String> = pauses
+                .list()
+                .iter()
+                .map(|pause| format!("• {}", pause))
+                .collect();
+            if lines.is_empty() {
+                "Nothing is paused. Usage: /pause <host|session>".to_string()
+            } else {
+                format!("⏸️ Paused:\n{}", lines.join("\n"))
+            }
+        }
+        Command::Pause(target) => match pauses.pause(target.trim(), by) {
+            Ok(true) => {
+                tracing::info!("{} paused {}", by, target.trim());
+                format!(
+                    "⏸️ Denying requests from {} until /resume {}",
+                    target.trim(),
+                    target.trim()
+                )
+            }
+            Ok(false) => format!("{} is already paused", target.trim()),
+            Err(e) => format!("❌ Failed to pause: {}", e),
+        },
+        Command::Resume(target) => match pauses.resume(target.trim()) {
+            Ok(lifted) if lifted.is_empty() => "Nothing to resume".to_string(),
+            Ok(lifted) => {
+                let targets: Vec<&str> = lifted.iter().map(|pause| pause.target.as_str()).collect();
+                tracing::info!("{} resumed {}", by, targets.join(", "));
+                format!("▶️ Resumed {}", targets.join(", "))
+            }
+            Err(e) => format!("❌ Failed to resume: {}", e),
+        },
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// Handle the /pause and /resume commands.
+///
+/// `/pause` without a target lists what is paused, `/resume` without one
+/// resumes everything.
+async fn pause_handler(
+    bot: Bot,
+    msg: Message,
+    config: &Config,
+    cmd: Command,
+) -> ResponseResult<()> {
+    let text = if !is_responder(config, msg.chat.id, msg.from.as_ref()) {
+        "⛔ Only authorized responders in the configured chat can pause requests".to_string()
+    } else {
+        let by = msg.from.as_ref().map(responder_name).unwrap_or_default();
+        let reply = off_reactor(move || pause_reply(&PauseList::new(None), &by, cmd)).await;
+        match reply {
+            Some(text) => text,
+            None => return Ok(()),
+        }
+    };
+
+    send_with_retry(bot.send_message(msg.chat.id, text)).await?;
+    Ok(())
+}
+
+/// Answer `/delegate`: list delegates, add one, or end one with `off`.
+///
+/// A `@username` is only taken from the author of the message the command
+/// replies to, and the delegate is kept by that user's ID.
+fn delegate_reply(
+    config: &Config,
+    away: &Away,
+    by: &str,
+    args: &str,
+    replied_to: Option<&User>,
+) -> String {
+    let usage = "Usage: /delegate <id|@user> <48h|off>, with @user in a reply to their message";
+    let mut args = args.split_whitespace();
+    let (Some(user), Some(length)) = (args.next(), args.next()) else {
+        let lines: Vec<String> = away
+            .delegates("telegram")
+            .iter()
+            .map(|delegate| format!("• {}", delegate))
+            .collect();
+        return if lines.is_empty() {
+            format!("Nobody is standing in. {}", usage)
+        } else {
+            format!("🤝 Delegates:\n{}", lines.join("\n"))
+        };
+    };
+
+    if length == "off" {
+        return match away.revoke("telegram", user) {
+            Ok(true) => {
+                tracing::info!("{} revoked the delegation to {}", by, user);
+                format!("{} can no longer answer requests", user)
+            }
+            Ok(false) => format!("{} is not a delegate", user),
+            Err(e) => format!("❌ Failed to revoke: {}", e),
+        };
+    }
+    let replied_to = replied_to.map(|user| (user.id.0, user.username.as_deref()));
+    let Some((id, name)) = away::parse_user(user, replied_to) else {
+        return format!(
+            "{} is not a user ID or the author of the message this replies to. {}",
+            user, usage
+        );
+    };
+    let user = if name.is_empty() {
+        id.clone()
+    } else {
+        format!("{} ({})", name, id)
+    };
+    if config.authorized_responders.telegram.is_empty() {
+        return "Everyone in this chat can already answer; list responders in \
+                authorized_responders.telegram to delegate"
+            .to_string();
+    }
+    let until = match history::parse_age(length) {
+        Ok(length) if !length.is_zero() => {
+            let now = now_secs();
+            now + length.as_secs()
+        }
+        _ => return format!("Expected a time like 48h or 2d. {}", usage),
+    };
+    match away.delegate("telegram", &id, &name, by, until) {
+        Ok(()) => {
+            tracing::info!("{} delegated to {} for {}", by, user, length);
+            format!(
+                "🤝 {} can answer requests for {}, or until /delegate {} off",
+                user, length, id
+            )
+        }
+        Err(e) => format!("❌ Failed to delegate: {}", e),
+    }
+}
+
+/// Answer `/vacation`: turn vacation mode on, or off with `off`.
+fn vacation_reply(config: &Config, away: &Away, by: &str, args: &str) -> String {
+    match args.trim() {
+        "" | "on" => match away.start_vacation(by) {
+            Ok(true) => {
+                tracing::info!("{} turned vacation mode on", by);
+                let decided = match config.vacation_fallback {
+                    VacationFallback::Deny => "denied",
+                    VacationFallback::Allow => "allowed",
+                };
+                format!(
+                    "🏖️ Vacation mode on: requests are {} without asking until /vacation off",
+                    decided
+                )
+            }
+            Ok(false) => "Vacation mode is already on; end it with /vacation off".to_string(),
+            Err(e) => format!("❌ Failed to turn vacation mode on: {}", e),
+        },
+        "off" => match away.end_vacation() {
+            Ok(Some(_)) => {
+                tracing::info!("{} turned vacation mode off", by);
+                "👋 Vacation mode off: requests are asked again".to_string()
+            }
+            Ok(None) => "Vacation mode is not on".to_string(),
+            Err(e) => format!("❌ Failed to turn vacation mode off: {}", e),
+        },
+        _ => "Usage: /vacation [off]".to_string(),
+    }
+}
+
+/// Handle the /delegate and /vacation commands.
+///
+/// Only responders on the allowlist may use them, so delegates can't pass
+/// their access on.
+async fn away_handler(bot: Bot, msg: Message, config: &Config, cmd: Command) -> ResponseResult<()> {
+    let text = if !is_allowlisted(config, msg.chat.id, msg.from.as_ref()) {
+        "⛔ Only authorized responders in the configured chat can delegate or go on vacation"
+            .to_string()
+    } else {
+        let by = msg.from.as_ref().map(responder_name).unwrap_or_default();
+        let replied_to = msg.reply_to_message().and_then(|reply| reply.from.clone());
+        let config = config.clone();
+        let reply = off_reactor(move || {
+            let away = Away::new(None);
+            match cmd {
+                Command::Delegate(args) => Some(delegate_reply(
+                    &config,
+                    &away,
+                    &by,
+                    &args,
+                    replied_to.as_ref(),
+                )),
+                Command::Vacation(args) => Some(vacation_reply(&config, &away, &by, &args)),
+                _ => None,
+            }
+        })
+        .await;
+        match reply {
+            Some(text) => text,
+            None => return Ok(()),
+        }
+    };
+
+    send_with_retry(bot.send_message(msg.chat.id, text)).await?;
+    Ok(())
+}
+
+/// Answer `/yolo off`, or `/yolo <session> off`, by closing windows early.
+fn close_yolo(windows: &YoloWindows, by: &str, session: &str) -> String {
+    match windows.close(session) {
+        Ok(closed) if closed.is_empty() => "No yolo window is open".to_string(),
+        Ok(closed) => {
+            let sessions: Vec<&str> = closed
+                .iter()
+                .map(|window| window.session.as_str())
+                .collect();
+            let detail = format!("{} closed the yolo window of {}", by, sessions.join(", "));
+            tracing::warn!("{}", detail);
+            history::record(HistoryEntry::new(
+                EventKind::Notification,
+                Some("telegram"),
+                detail,
+            ));
+            format!("🛑 Requests from {} are asked again", sessions.join(", "))
+        }
+        Err(e) => format!("❌ Failed to close the yolo window: {}", e),
+    }
+}
+
+/// Handle the /yolo command by asking to confirm the window it would open.
+///
+/// Only responders on the allowlist may open or close windows, and nothing
+/// is allowed until the confirmation is pressed.
+async fn yolo_handler(
+    bot: Bot,
+    msg: Message,
+    config: &Config,
+    args: &str,
+) -> ResponseResult<Option<(String, YoloRequest)>> {
+    let usage = "Usage: /yolo <session> <10m|off>";
+    let reply = |text: String, keyboard: Option<InlineKeyboardMarkup>| {
+        let mut request = bot.send_message(msg.chat.id, text);
+        if let Some(keyboard) = keyboard {
+            request = request.reply_markup(keyboard);
+        }
+        send_with_retry(request)
+    };
+    if !is_allowlisted(config, msg.chat.id, msg.from.as_ref()) {
+        reply(
+            "⛔ Only authorized responders in the configured chat can open yolo windows"
+                .to_string(),
+            None,
+        )
+        .await?;
+        return Ok(None);
+    }
+
+    let by = msg.from.as_ref().map(responder_name).unwrap_or_default();
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let (session, length) = match args.as_slice() {
+        [] => {
+            let windows = off_reactor(|| YoloWindows::new(None).list()).await;
+            let lines: Vec<String> = windows
+                .iter()
+                .map(|window| format!("• {}", window))
+                .collect();
+            let text = if lines.is_empty() {
+                format!("No yolo window is open. {}", usage)
+            } else {
+                format!("🤠 Allowing every request from:\n{}", lines.join("\n"))
+            };
+            reply(text, None).await?;
+            return Ok(None);
+        }
+        ["off"] => {
+            let text = off_reactor(move || close_yolo(&YoloWindows::new(None), &by, "")).await;
+            reply(text, None).await?;
+            return Ok(None);
+        }
+        [session, "off"] => {
+            let session = session.to_string();
+            let text =
+                off_reactor(move || close_yolo(&YoloWindows::new(None), &by, &session)).await;
+            reply(text, None).await?;
+            return Ok(None);
+        }
+        [session, length] => (*session, *length),
+        _ => {
+            reply(usage.to_string(), None).await?;
+            return Ok(None);
+        }
+    };
+    let max_minutes = yolo::MAX_WINDOW.as_secs() / 60;
+    let length = match history::parse_age(length) {
+        Ok(length) if !length.is_zero() && length <= yolo::MAX_WINDOW => length,
+        _ => {
+            reply(
+                format!(
+                    "Expected a time like 10m, up to {}m. {}",
+                    max_minutes, usage
+                ),
+                None,
+            )
+            .await?;
+            return Ok(None);
+        }
+    };
+
+    // The window covers one full session ID, so a short prefix can't
+    // reach sessions started later
+    let prefix = session.to_string();
+    let found =
+        off_reactor(move || peek::find_session(&default_claude_projects_path(), &prefix)).await;
+    let session = match found {
+        Ok((session, _)) => session,
+        Err(e) => {
+            reply(format!("❌ {}", e), None).await?;
+            return Ok(None);
+        }
+    };
+
+    let token = new_request_id();
+    let short: String = session.chars().take(8).collect();
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(
+            format!("⚠️ Allow everything from {}", short),
+            format!("{}:confirm_yolo", token),
+        ),
+        InlineKeyboardButton::callback("Cancel", format!("{}:cancel_yolo", token)),
+    ]]);
+    let text = format!(
+        "⚠️ Allow EVERY request from session {} for {}m without asking?\n\n\
+         Requests needing several approvals and blocked calls are still asked about. \
+         The window ends on its own, or early with /yolo {} off.",
+        session,
+        length.as_secs().div_ceil(60),
+        session
+    );
+    reply(text, Some(keyboard)).await?;
+    Ok(Some((token, YoloRequest { session, length })))
+}
+
+/// Parse a press on a yolo confirmation: its token and whether it confirms.
+fn parse_yolo_callback(data: &str) -> Option<(&str, bool)> {
+    let (token, action) = data.split_once(':')?;
+    match action {
+        "confirm_yolo" => Some((token, true)),
+        "cancel_yolo" => Some((token, false)),
+        _ => None,
+    }
+}
+
+/// Open or cancel a yolo window once its confirmation is pressed.
+async fn confirm_yolo(
+    bot: &Bot,
+    query: &CallbackQuery,
+    request: YoloRequest,
+    confirmed: bool,
+) -> ResponseResult<()> {
+    let text = if confirmed {
+        let by = responder_name(&query.from);
+        let (session, length) = (request.session.clone(), request.length);
+        let opened = off_reactor(move || {
+            let window = YoloWindows::new(None).open(&session, &by, length)?;
+            let detail = format!("{} opened a yolo window for {}", by, window);
+            tracing::warn!("{}", detail);
+            history::record(HistoryEntry::new(
+                EventKind::Notification,
+                Some("telegram"),
+                detail,
+            ));
+            io::Result::Ok(window)
+        })
+        .await;
+        match opened {
+            Ok(window) => {
+                format!(
+                    "🤠 Allowing every request from {} for {}m, or until /yolo {} off",
+                    window.session,
+                    request.length.as_secs().div_ceil(60),
+                    window.session
+                )
+            }
+            Err(e) => format!("❌ Failed to open the yolo window: {}", e),
+        }
+    } else {
+        "Cancelled".to_string()
+    };
+
+    send_with_retry(bot.answer_callback_query(&query.id)).await?;
+    if let Some(msg) = &query.message {
+        send_with_retry(bot.edit_message_text(msg.chat().id, msg.id(), text)).await?;
+    }
+    Ok(())
+}
+
+/// Handle the /run command.
+///
+/// Only authorized responders may start runs, and only from the configured
+/// chat, since a run executes on this host.
+async fn run_handler(bot: Bot, msg: Message, config: &Config, args: &str) -> ResponseResult<()> {
+    if !authorize_host_control(&bot, &msg, config).await? {
+        return Ok(());
+    }
+    let reply = |text: String| {
+        send_with_retry(
+            bot.send_message(msg.chat.id, text)
+                .parse_mode(ParseMode::MarkdownV2),
+        )
+    };
+
+    let Some((project, prompt)) = launcher::parse_run_args(args) else {
+        reply("Usage: `/run <project> <prompt>`".to_string()).await?;
+        return Ok(());
+    };
+    let started = launcher::plan(&config.remote_runs, project)
+        .and_then(|run| launcher::start(&run, &config.remote_runs, prompt).map(|_| run));
+    let run = match started {
+        Ok(run) => run,
+        Err(e) => {
+            reply(format!("❌ {}", escape_markdown(&e.to_string()))).await?;
+            return Ok(());
+        }
+    };
+
+    tracing::info!("Started run {} in {}", run.session_id, run.cwd.display());
+    reply(format!(
+        "🚀 *Started* in `{}` on `{}`\n\n*Session:* `{}`\n*Attach:* `tmux attach \\-t {}`",
+        escape_markdown(&run.project),
+        escape_markdown(&config.host_label),
+        run.session_id,
+        escape_markdown(&run.tmux_session),
+    ))
+    .await?;
+
+    let config = config.for_project(&run.cwd.to_string_lossy());
+    tokio::spawn(async move {
+        let result = match launcher::wait_for_exit(&run).await {
+            Some(event) => stop_handler::send_summary(&config, &event)
+                .await
+                .map_err(|e| e.to_string()),
+            None => notify_primary(
+                &config,
+                &format!(
+                    "⚠️ Run {} in {} ended without a transcript",
+                    run.session_id, run.project
+                ),
+            )
+            .await
+            .map_err(|e| e.to_string()),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to report run {}: {}", run.session_id, e);
+        }
+    });
+
+    Ok(())
+}
+
+/// Handle the /sessions, /interrupt and /send commands.
+async fn steer_handler(
+    bot: Bot,
+    msg: Message,
+    config: &Config,
+    cmd: Command,
+) -> ResponseResult<()> {
+    if !authorize_host_control(&bot, &msg, config).await? {
+        return Ok(());
+    }
+
+    let text = match cmd {
+        Command::Sessions => {
+            let panes = tmux::list_claude_panes();
+            if panes.is_empty() {
+                "No Claude sessions running in tmux".to_string()
+            } else {
+                let lines: Vec<String> = panes
+                    .iter()
+                    .map(|pane| {
+                        format!(
+                            "`{}` {} \\({}\\)",
+                            pane.id,
+                            escape_markdown(&pane.project()),
+                            escape_markdown(&pane.target)
+                        )
+                    })
+                    .collect();
+                format!("*Claude sessions:*\n{}", lines.join("\n"))
+            }
+        }
+        Command::Interrupt(id) => match tmux::interrupt(&id) {
+            Ok(pane) => format!("⏸️ Interrupted {}", escape_markdown(&pane.project())),
+            Err(e) => format!("❌ {}", escape_markdown(&e.to_string())),
+        },
+        Command::Send(args) => {
+            let sent = args
+                .trim()
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| anyhow::anyhow!("Usage: /send <id> <text>"))
+                .and_then(|(id, text)| tmux::send_prompt(id, text.trim()));
+            match sent {
+                Ok(pane) => format!("📨 Sent to {}", escape_markdown(&pane.project())),
+                Err(e) => format!("❌ {}", escape_markdown(&e.to_string())),
+            }
+        }
+        _ => return Ok(()),
+    };
+
+    send_with_retry(
+        bot.send_message(msg.chat.id, text)
+            .parse_mode(ParseMode::MarkdownV2),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Handle the /cat and /diff commands.
+///
+/// Like /run, they need host control, and only show files inside the
+/// `remote_runs` projects.
+async fn peek_handler(bot: Bot, msg: Message, config: &Config, cmd: Command) -> ResponseResult<()> {
+    if !authorize_host_control(&bot, &msg, config).await? {
+        return Ok(());
+    }
+
+    let (path, diff) = match &cmd {
+        Command::Cat(path) => (path.trim(), false),
+        Command::Diff(path) => (path.trim(), true),
+        _ => return Ok(()),
+    };
+    let text = if path.is_empty() {
+        "Usage: `/cat <path>` or `/diff <path>`".to_string()
+    } else {
+        // Files like `.env` are why requests are redacted in the first place
+        let redactor = Redactor::new(&config.redaction);
+        let (projects, wanted) = (config.remote_runs.projects.clone(), path.to_string());
+        let shown = off_reactor(move || {
+            peek::resolve(&projects, &wanted).and_then(|file| {
+                if diff {
+                    peek::diff(&file)
+                } else {
+                    peek::cat(&file)
+                }
+            })
+        })
+        .await;
+        match shown {
+            Ok(content) => format!(
+                "📄 `{}`\n```{}\n{}\n```",
+                escape_code(path),
+                if diff { "diff" } else { "" },
+                peek::fit(&redactor.redact(&content), escape_code)
+            ),
+            Err(e) => format!("❌ {}", escape_markdown(&e.to_string())),
+        }
+    };
+
+    send_with_retry(
+        bot.send_message(msg.chat.id, text)
+            .parse_mode(ParseMode::MarkdownV2),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Working directories a `/git` target stands for: this host's Claude
+/// sessions in tmux, or one session. Only directories inside the
+/// `remote_runs` projects are included.
+fn git_dirs(config: &Config, target: &str) -> Result<Vec<PathBuf>> {
+    let projects = &config.remote_runs.projects;
+    if target == config.hostname || target == config.host_label {
+        let mut dirs: Vec<PathBuf> = tmux::list_claude_panes()
+            .into_iter()
+            .filter_map(|pane| peek::inside_projects(projects, &pane.cwd).ok())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        return Ok(dirs);
+    }
+    Ok(vec![peek::session_cwd(
+        &default_claude_projects_path(),
+        projects,
+        target,
+    )?])
+}
+
+/// Answer a `/git` target with the git summary of each of its directories.
+fn git_text(config: &Config, target: &str) -> String {
+    match git_dirs(config, target) {
+        Ok(dirs) if dirs.is_empty() => {
+            "No Claude sessions running in tmux inside the configured projects".to_string()
+        }
+        Ok(dirs) => dirs
+            .iter()
+            .map(|dir| match peek::git_summary(dir) {
+                Ok(summary) => format!(
+                    "📁 `{}`\n```\n{}\n```",
+                    escape_code(&dir.display().to_string()),
+                    peek::fit(&summary, escape_code)
+                ),
+                Err(e) => format!(
+                    "📁 `{}`\n❌ {}",
+                    escape_code(&dir.display().to_string()),
+                    escape_markdown(&e.to_string())
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Err(e) => format!("❌ {}", escape_markdown(&e.to_string())),
+    }
+}
+
+/// Handle the /git command.
+///
+/// Needs host control like /run, since it runs git on this host.
+async fn git_handler(bot: Bot, msg: Message, config: &Config, target: &str) -> ResponseResult<()> {
+    if !authorize_host_control(&bot, &msg, config).await? {
+        return Ok(());
+    }
+
+    let target = target.trim();
+    let text = if target.is_empty() {
+        "Usage: `/git <session|host>`".to_string()
+    } else {
+        let (config, target) = (config.clone(), target.to_string());
+        off_reactor(move || git_text(&config, &target)).await
+    };
+
+    send_with_retry(
+        bot.send_message(msg.chat.id, text)
+            .parse_mode(ParseMode::MarkdownV2),
+    )
+    .await?;
     Ok(())
 }
 
 /// Main entry point for the bot.
-pub async fn run() -> Result<()> {
-    let config = Config::load(None)?;
+pub async fn run(config_path: Option<&Path>) -> Result<()> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
 
     let telegram_config = config
         .telegram
         .as_ref()
         .ok_or_else(|| anyhow::anyhow!("Telegram configuration required for bot command"))?;
 
-    let bot = Bot::new(&telegram_config.bot_token);
+    let bot = clients::shared().telegram_bot(&telegram_config.bot_token);
 
     tracing::info!("Starting Claude Code Telegram Bot...");
 
-    let handler = Update::filter_message()
-        .filter_command::<Command>()
-        .endpoint({
-            let config = config.clone();
-            move |bot: Bot, msg: Message, cmd: Command| {
-                let config = config.clone();
-                async move {
-                    match cmd {
-                        Command::Start => start_handler(bot, msg).await,
-                        Command::Help => help_handler(bot, msg).await,
-                        Command::Status => status_handler(bot, msg, &config).await,
-                    }
+    let me = bot.get_me().await?;
+    tokio::select! {
+        _ = poll_commands(&bot, me.username(), &config) => {}
+        _ = tokio::signal::ctrl_c() => tracing::info!("Stopping bot"),
+    }
+
+    Ok(())
+}
+
+/// Answer commands from the shared update spool until cancelled.
+async fn poll_commands(bot: &Bot, bot_name: &str, config: &Config) {
+    let mut inbox = UpdateInbox::new(bot.clone());
+    let mut poll_interval = interval(Duration::from_millis(500));
+    let mut bulk_decisions: HashMap<String, BulkDecision> = HashMap::new();
+    let mut yolo_requests: HashMap<String, YoloRequest> = HashMap::new();
+    let mut offered: HashMap<String, String> = HashMap::new();
+    let mut suggestion_timer = config.rule_suggestions.enabled.then(|| {
+        let every = Duration::from_secs(config.rule_suggestions.interval_hours.max(1) * 60 * 60);
+        interval_at(Instant::now() + every, every)
+    });
+
+    loop {
+        tokio::select! {
+            _ = poll_interval.tick() => {}
+            _ = next_suggestions(&mut suggestion_timer) => {
+                if let Err(e) = offer_suggestions(bot, config, &mut offered).await {
+                    tracing::warn!("Failed to offer rule suggestions: {}", e);
                 }
+                continue;
             }
-        });
+        }
 
-    Dispatcher::builder(bot, handler)
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
-
-    Ok(())
+        for shared in inbox.next_updates().await {
+            match shared.update.kind {
+                UpdateKind::Message(msg) => {
+                    let Some(cmd) = msg.text().and_then(|t| Command::parse(t, bot_name).ok())
+                    else {
+                        continue;
+                    };
+                    if is_observer_chat(config, msg.chat.id.0) {
+                        if let Err(e) = refuse_in_observer_chat(bot, &msg).await {
+                            tracing::warn!("Failed to answer command: {}", e);
+                        }
+                        continue;
+                    }
+                    let result = match cmd {
+                        Command::Start => start_handler(bot.clone(), msg).await,
+                        Command::Help => help_handler(bot.clone(), msg).await,
+                        Command::Status => status_handler(bot.clone(), msg, config).await,
+                        Command::Run(args) => run_handler(bot.clone(), msg, config, &args).await,
+                        cmd @ (Command::Sessions | Command::Interrupt(_) | Command::Send(_)) => {
+                            steer_handler(bot.clone(), msg, config, cmd).await
+                        }
+                        Command::Git(target) => {
+                            git_handler(bot.clone(), msg, config, &target).await
+                        }
+                        cmd @ (Command::Cat(_) | Command::Diff(_)) => {
+                            peek_handler(bot.clone(), msg, config, cmd).await
+                        }
+                        cmd @ (Command::Pause(_) | Command::Resume(_)) => {
+                            pause_handler(bot.clone(), msg, config, cmd).await
+                        }
+                        cmd @ (Command::Delegate(_) | Command::Vacation(_)) => {
+                            away_handler(bot.clone(), msg, config, cmd).await
+                        }
+                        Command::Yolo(args) => yolo_handler(bot.clone(), msg, config, &args)
+                            .await
+                            .map(|request| yolo_requests.extend(request)),
+                        Command::Pending(target) => {
+                            pending_handler(bot.clone(), msg, config, &target).await
+                        }
+                        Command::AllowAll(target) => {
+                            bulk_handler(bot.clone(), msg, config, true, &target)
+                                .await
+                                .map(|bulk| bulk_decisions.extend(bulk))
+                        }
+                        Command::DenyAll(target) => {
+                            bulk_handler(bot.clone(), msg, config, false, &target)
+                                .await
+                                .map(|bulk| bulk_decisions.extend(bulk))
+                        }
+                    };
+                    if let Err(e) = result {
+                        tracing::warn!("Failed to answer command: {}", e);
+                    }
+                }
+                UpdateKind::CallbackQuery(query) => {
+                    let from_observer_chat = query
+                        .message
+                        .as_ref()
+                        .is_some_and(|msg| is_observer_chat(config, msg.chat().id.0));
+                    if from_observer_chat {
+                        let _ = send_with_retry(
+                            bot.answer_callback_query(&query.id)
+                                .text("This chat only observes")
+                                .show_alert(true),
+                        )
+                        .await;
+                        continue;
+                    }
+                    let suggested = query
+                        .data
+                        .as_deref()
+                        .and_then(parse_rule_callback)
+                        .and_then(|token| offered.get(token));
+                    if let Some(entry) = suggested {
+                        if let Err(e) = allow_suggested(bot, config, &query, entry).await {
+                            tracing::warn!("Failed to add suggested rule: {}", e);
+                        }
+                        continue;
+                    }
+                    let yolo = query.data.as_deref().and_then(parse_yolo_callback);
+                    if let Some((token, confirmed)) = yolo {
+                        if !yolo_requests.contains_key(token) {
+                            if shared.fetched_here {
+                                answer_unclaimed_press(bot, &query).await;
+                            }
+                            continue;
+                        }
+                        let chat_id = query.message.as_ref().map(|msg| msg.chat().id);
+                        let allowed = chat_id.is_some_and(|chat_id| {
+                            is_allowlisted(config, chat_id, Some(&query.from))
+                        });
+                        if !allowed {
+                            let _ = send_with_retry(
+                                bot.answer_callback_query(&query.id)
+                                    .text("You are not authorized to open yolo windows")
+                                    .show_alert(true),
+                            )
+                            .await;
+                            continue;
+                        }
+                        let Some(request) = yolo_requests.remove(token) else {
+                            continue;
+                        };
+                        if let Err(e) = confirm_yolo(bot, &query, request, confirmed).await {
+                            tracing::warn!("Failed to confirm yolo window: {}", e);
+                        }
+                        continue;
+                    }
+                    match query.data.as_deref().and_then(parse_bulk_callback) {
+                        Some((token, confirmed)) if bulk_decisions.contains_key(token) => {
+                            let chat_id = query.message.as_ref().map(|msg| msg.chat().id);
+                            let allowed = chat_id.is_some_and(|chat_id| {
+                                is_responder(config, chat_id, Some(&query.from))
+                            });
+                            if !allowed {
+                                let _ = send_with_retry(
+                                    bot.answer_callback_query(&query.id)
+                                        .text("You are not authorized to decide requests")
+                                        .show_alert(true),
+                                )
+                                .await;
+                                continue;
+                            }
+                            let Some(bulk) = bulk_decisions.remove(token) else {
+                                continue;
+                            };
+                            if let Err(e) = confirm_bulk(bot, &query, bulk, confirmed).await {
+                                tracing::warn!("Failed to confirm bulk decision: {}", e);
+                            }
+                        }
+                        _ if shared.fetched_here => answer_unclaimed_press(bot, &query).await,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
 }
@@ -1,8 +1,8 @@
 //! CLI argument parsing with subcommands.
 
-use clap::{Parser, Subcommand};
-#[cfg(feature = "signal")]
+use clap::{ArgAction, Parser, Subcommand};
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Claude Code hook & messaging integration.
 ///
@@ -13,6 +13,26 @@ use std::path::PathBuf;
 #[command(about = "Claude Code hook & messaging integration (Telegram, Discord, Signal)")]
 #[command(version)]
 pub struct Cli {
+    /// Config file to use instead of ~/.claude/hook_config.json
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Print would-be messages to stderr instead of sending them (also CCR_DRY_RUN=1)
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Log more detail (-v debug, -vv trace)
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only log errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Append logs to this file instead of stderr
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -21,21 +41,166 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Handle PermissionRequest hooks (reads from stdin)
-    Hook,
+    Hook {
+        /// Hook input format of the calling agent
+        #[arg(long, default_value = "claude", value_parser = crate::adapters::FORMATS)]
+        format: String,
+    },
 
     /// Handle Stop hooks for job completion notifications (reads from stdin)
     Stop,
 
+    /// Keep messenger connections warm and handle `hook` requests for other processes
+    #[cfg(any(unix, windows))]
+    Daemon {
+        #[command(subcommand)]
+        command: Option<DaemonCommands>,
+
+        /// Run as a Windows service, started by the Service Control Manager
+        #[cfg(windows)]
+        #[arg(long)]
+        service: bool,
+    },
+
     /// Handle Notification hooks for relaying Claude Code notifications (reads from stdin)
     Notify,
 
+    /// Handle PreToolUse/PostToolUse hooks to report long-running tools (reads from stdin)
+    ToolTimer,
+
+    /// Alert if a tool call is still running after the threshold (spawned by `tool-timer`)
+    #[command(hide = true)]
+    WatchTool {
+        /// Timer key of the tool call
+        #[arg(long)]
+        key: String,
+
+        /// Unix time the tool call started
+        #[arg(long)]
+        started: u64,
+
+        /// One-line description of the tool call
+        #[arg(long)]
+        label: String,
+
+        /// Working directory of the session
+        #[arg(long, default_value = "")]
+        cwd: String,
+    },
+
+    /// Send escalating idle reminders for a session (spawned by `notify`)
+    #[command(hide = true)]
+    Nudge {
+        /// Transcript to watch for activity
+        #[arg(long)]
+        transcript_path: PathBuf,
+
+        /// Working directory of the idle session
+        #[arg(long, default_value = "")]
+        cwd: String,
+    },
+
+    /// Remind of a snoozed permission request once the snooze is over (spawned by `hook`)
+    #[command(hide = true)]
+    RemindSnoozed {
+        /// One-line description of the snoozed tool call
+        #[arg(long)]
+        label: String,
+
+        /// Working directory of the session
+        #[arg(long, default_value = "")]
+        cwd: String,
+    },
+
     /// Send a custom message to configured messengers
     Relay {
-        /// Message to send
-        message: String,
+        /// Message to send (`-` reads it from stdin)
+        message: Option<String>,
+
+        /// Send to this messenger instead of the first available one
+        #[arg(long, value_parser = ["telegram", "discord", "all"])]
+        to: Option<String>,
+
+        /// Pass the message through as markdown
+        #[arg(long, conflicts_with = "code")]
+        markdown: bool,
+
+        /// Wrap the message in a code block
+        #[arg(long)]
+        code: bool,
+
+        /// Attach a file
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+
+    /// Serve approval requests and notifications to other programs over JSON-RPC
+    Serve {
+        /// Read requests from stdin and write responses to stdout
+        #[arg(long, required = true)]
+        stdio: bool,
+    },
+
+    /// Ask a yes/no question and exit 0 if approved, 1 if denied or timed out
+    Ask {
+        /// Question to send
+        question: String,
+
+        /// Seconds to wait for an answer (default: timeout_seconds from config)
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+
+    /// Stream a session's assistant messages and tool calls as they happen
+    Watch {
+        /// Session ID to find under ~/.claude/projects
+        #[arg(
+            long,
+            required_unless_present = "transcript",
+            conflicts_with = "transcript"
+        )]
+        session: Option<String>,
+
+        /// Transcript JSONL file to watch
+        #[arg(long)]
+        transcript: Option<PathBuf>,
+
+        /// Seconds between transcript checks
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+
+    /// Show recent permission requests, decisions, notifications and errors
+    Logs {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+
+        /// Keep printing new entries as they are recorded
+        #[arg(short, long)]
+        follow: bool,
+    },
+
+    /// Export the permission request history
+    History {
+        #[command(subcommand)]
+        command: HistoryCommands,
+    },
+
+    /// Inspect the tamper-evident audit log
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommands,
+    },
+
+    /// Work with always-allow rules
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommands,
     },
 
     /// Run the Telegram bot for /start, /help, /status commands
+    #[cfg(feature = "telegram")]
     Bot,
 
     /// Link as a Signal secondary device (requires --features signal)
@@ -52,4 +217,171 @@ pub enum Commands {
 
     /// Show current configuration status
     Status,
+
+    /// Print shell completions for bash, zsh, fish, elvish, or powershell
+    Completions {
+        /// Shell to generate completions for
+        shell: clap_complete::Shell,
+    },
+
+    /// Send a test message and report whether delivery succeeded
+    Test {
+        /// Messenger to test (default: every configured messenger)
+        #[arg(long)]
+        messenger: Option<String>,
+    },
+
+    /// Inspect the configuration file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Feed a synthetic hook event through the full pipeline
+    Simulate {
+        #[command(subcommand)]
+        command: SimulateCommands,
+    },
+
+    /// Add hook entries for this binary to Claude Code settings
+    InstallHooks {
+        /// Settings file to edit (default: ~/.claude/settings.json)
+        #[arg(long)]
+        settings: Option<PathBuf>,
+    },
+
+    /// Remove this binary's hook entries from Claude Code settings
+    UninstallHooks {
+        /// Settings file to edit (default: ~/.claude/settings.json)
+        #[arg(long)]
+        settings: Option<PathBuf>,
+    },
+}
+
+/// Daemon subcommands.
+#[cfg(any(unix, windows))]
+#[derive(Subcommand)]
+pub enum DaemonCommands {
+    /// Show a QR code with the command API's URL and token, for a phone or dashboard
+    Pair {
+        /// URL the daemon is reached at from the scanning device (default: http://<listen>)
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+/// Audit log operations.
+#[derive(Subcommand)]
+pub enum AuditCommands {
+    /// Check the hash chain for modified, missing or truncated records
+    Verify {
+        /// Audit log to check (default: the configured log)
+        #[arg(long)]
+        path: Option<PathBuf>,
+    },
+}
+
+/// Always-allow rule subcommands.
+#[derive(Subcommand)]
+pub enum RulesCommands {
+    /// Suggest always-allow entries for calls that were approved every time
+    Suggest {
+        /// Add the suggested entries to the always-allow list
+        #[arg(long)]
+        apply: bool,
+    },
+
+    /// Check past requests against the current rules and report what would change
+    Replay {
+        /// Only replay requests newer than this age, e.g. 12h, 30d or 2w
+        #[arg(long, default_value = "30d", value_parser = crate::history::parse_age)]
+        since: Duration,
+
+        /// Treat an entry as if it were on the always-allow list, e.g. 'Bash(cargo test)'
+        #[arg(long, value_name = "ENTRY")]
+        allow: Vec<String>,
+    },
+}
+
+/// History subcommands.
+#[derive(Subcommand)]
+pub enum HistoryCommands {
+    /// Write one row per permission request (time, tool, command, decision, responder, latency)
+    Export {
+        /// Output format
+        #[arg(long, default_value = "csv", value_parser = ["csv", "jsonl"])]
+        format: String,
+
+        /// Only include requests newer than this age, e.g. 12h, 30d or 2w
+        #[arg(long, value_parser = crate::history::parse_age)]
+        since: Option<Duration>,
+
+        /// Mask secrets in commands with the built-in and configured redaction patterns
+        #[arg(long)]
+        redact: bool,
+    },
+}
+
+/// Simulated hook events.
+#[derive(Subcommand)]
+pub enum SimulateCommands {
+    /// Simulate a PermissionRequest hook and print the hook output
+    Permission {
+        /// Tool requesting permission
+        #[arg(long, default_value = "Bash")]
+        tool: String,
+
+        /// Command for Bash requests
+        #[arg(long)]
+        command: Option<String>,
+
+        /// File path for Edit/Write requests
+        #[arg(long)]
+        file_path: Option<String>,
+    },
+
+    /// Simulate a Stop hook for a finished session
+    Stop {
+        /// Last assistant message of the session
+        #[arg(
+            long,
+            default_value = "All tests pass. Fixed 2 failing tests in src/parser.rs."
+        )]
+        message: String,
+    },
+
+    /// Simulate a Notification hook
+    Notify {
+        /// Notification type (e.g. idle_prompt, permission_prompt)
+        #[arg(long = "type", default_value = "idle_prompt")]
+        notification_type: String,
+
+        /// Notification message
+        #[arg(long, default_value = "Claude is waiting for your input")]
+        message: String,
+    },
+}
+
+/// Configuration subcommands.
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Check the config file for unknown keys, missing fields and invalid IDs
+    Validate {
+        /// Config file to check (default: ~/.claude/hook_config.json)
+        path: Option<PathBuf>,
+    },
+
+    /// Print a JSON Schema for the config file (for editor validation)
+    Schema,
+
+    /// Convert the legacy telegram_hook.json into hook_config.json
+    Migrate {
+        /// Overwrite an existing hook_config.json
+        #[arg(long)]
+        force: bool,
+
+        /// Delete telegram_hook.json after a successful migration
+        #[arg(long)]
+        delete_legacy: bool,
+    },
 }
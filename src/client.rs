@@ -0,0 +1,147 @@
+//! Remote approvals for other Rust tools.
+//!
+//! [`ApprovalClient`] is the library counterpart of the `hook` and `relay`
+//! commands: it sends permission requests and notifications through the
+//! configured messengers, so a tool can ask for approval on a phone without
+//! shelling out to the binary.
+//!
+//! ```no_run
+//! use claude_code_telegram::messenger::new_request_id;
+//! use claude_code_telegram::{ApprovalClient, Config, Decision, PermissionMessage};
+//! use serde_json::json;
+//!
+//! # async fn deploy() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = ApprovalClient::new(Config::load(None)?)?;
+//! let message = PermissionMessage::new(
+//!     new_request_id(),
+//!     "Deploy".to_string(),
+//!     client.hostname().to_string(),
+//!     json!({"target": "production"}),
+//! );
+//! if client.request_approval(message).await? == Decision::Deny {
+//!     client.notify("Deployment cancelled").await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::{Config, ToolDisplay};
+use crate::error::{ConfigError, HookError};
+use crate::messenger::{enabled_messengers, Decision, Messenger, PermissionMessage};
+use crate::redact::Redactor;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Sends approval requests and notifications through the configured messengers.
+#[derive(Clone)]
+pub struct ApprovalClient {
+    config: Config,
+    messengers: Vec<Arc<dyn Messenger>>,
+    timeout: Duration,
+}
+
+impl ApprovalClient {
+    /// Use the enabled messengers, in `messenger_priority` order.
+    ///
+    /// Fails if no messenger is configured.
+    pub fn new(config: Config) -> Result<Self, HookError> {
+        let messengers: Vec<Arc<dyn Messenger>> = enabled_messengers(&config)
+            .into_iter()
+            .map(Arc::from)
+            .collect();
+        if messengers.is_empty() {
+            return Err(ConfigError::MissingField("no messenger configured".to_string()).into());
+        }
+        let timeout = Duration::from_secs(config.timeout_seconds);
+        Ok(Self {
+            config,
+            messengers,
+            timeout,
+        })
+    }
+
+    /// Use a specific messenger instead, e.g. a custom implementation.
+    #[allow(dead_code)]
+    pub fn with_messenger(config: Config, messenger: Arc<dyn Messenger>) -> Self {
+        let timeout = Duration::from_secs(config.timeout_seconds);
+        Self {
+            config,
+            messengers: vec![messenger],
+            timeout,
+        }
+    }
+
+    /// Wait this long for a decision instead of `timeout_seconds`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Hostname to put in requests, from the configuration.
+    pub fn hostname(&self) -> &str {
+        &self.config.hostname
+    }
+
+    /// Ask for a decision and wait for it.
+    ///
+    /// The tool input is redacted before it is sent, the tool gets its
+    /// `tool_display` settings unless the message has its own, this host is
+    /// shown under its configured label, and requests needing several
+    /// approvals go to the first available messenger that can count them. A request that times out is denied. `AlwaysAllow` is
+    /// returned as is; remembering it is up to the caller.
+    pub async fn request_approval(
+        &self,
+        message: PermissionMessage,
+    ) -> Result<Decision, HookError> {
+        let Some(messenger) = self
+            .first_available(|m| message.required_approvals <= 1 || m.supports_quorum())
+            .await
+        else {
+            return Err(ConfigError::MissingField(
+                "no available messenger supports multi-person approval".to_string(),
+            )
+            .into());
+        };
+        let display = if message.display == ToolDisplay::default() {
+            self.config.tool_display(&message.tool_name)
+        } else {
+            message.display.clone()
+        };
+        let host_label = match message.host_label {
+            None if message.hostname == self.config.hostname => {
+                Some(self.config.host_label.clone())
+            }
+            host_label => host_label,
+        };
+        let message = PermissionMessage {
+            tool_input: Redactor::new(&self.config.redaction).redact_value(&message.tool_input),
+            display,
+            host_label,
+            ..message
+        };
+        messenger
+            .send_permission_request(&message, self.timeout)
+            .await
+    }
+
+    /// Send a notification through the first available messenger.
+    pub async fn notify(&self, text: &str) -> Result<(), HookError> {
+        let Some(messenger) = self.first_available(|_| true).await else {
+            return Err(ConfigError::MissingField("no messenger available".to_string()).into());
+        };
+        let text = Redactor::new(&self.config.redaction).redact(text);
+        messenger.send_notification(&text).await
+    }
+
+    async fn first_available(
+        &self,
+        suitable: impl Fn(&dyn Messenger) -> bool,
+    ) -> Option<&dyn Messenger> {
+        for messenger in &self.messengers {
+            if suitable(messenger.as_ref()) && messenger.is_available().await {
+                return Some(messenger.as_ref());
+            }
+        }
+        None
+    }
+}
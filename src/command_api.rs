@@ -0,0 +1,436 @@
+//! Bot commands over HTTP, for automation and setups without a chat.
+//!
+//! With `preferences.command_api` enabled, the daemon takes `POST /command`
+//! with a command in the body, as it would be typed to the bot, and answers
+//! with the reply as plain text:
+//!
+//! - `/pending [host|session]` lists requests waiting for an answer;
+//! - `/approve <id>` and `/deny <id>` decide one of them;
+//! - `/allow_all` and `/deny_all [host|session]` decide them in bulk, without
+//!   the confirmation the bot asks for;
+//! - `/pause <host|session>` and `/resume [host|session]` work as in the bot.
+//!
+//! Each request must carry `Authorization: Bearer <token>`. Decisions are
+//! left in the request ledger for the waiting hook process to pick up, like
+//! `/allow_all` in the bot; requests needing several approvals can only be
+//! denied. `daemon pair` shows the URL and token as a QR code, so a phone
+//! shortcut or dashboard can be set up by scanning it.
+
+use crate::clock::now_secs;
+use crate::config::{default_request_ledger_path, CommandApiConfig, Config};
+use crate::http::{
+    hex, hex_matches, load_config_for_request, read_request, write_response, HttpRequest,
+    READ_TIMEOUT,
+};
+use crate::lockfile::off_reactor;
+use crate::messenger::ledger::{self, Resolution};
+use crate::pause::PauseList;
+use anyhow::{bail, Context, Result};
+use qrcode::QrCode;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Path commands are posted to.
+const COMMAND_PATH: &str = "/command";
+
+/// Who decisions and pauses are recorded as made by.
+const BY: &str = "command API";
+
+type Status = (u16, &'static str);
+
+const OK: Status = (200, "OK");
+const BAD_REQUEST: Status = (400, "Bad Request");
+const CONFLICT: Status = (409, "Conflict");
+
+/// Whether the request carries the configured token.
+///
+/// Both are hashed first, so the comparison takes the same time however
+/// much of the token is right.
+fn authorized(request: &HttpRequest, token: &str) -> bool {
+    let given = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    hex_matches(
+        &hex(&Sha256::digest(token.as_bytes())),
+        &hex(&Sha256::digest(given.trim().as_bytes())),
+    )
+}
+
+/// Decide one waiting request.
+fn decide(ledger_dir: &Path, request_id: &str, allow: bool) -> (Status, String) {
+    if request_id.is_empty() {
+        let usage = if allow { "/approve <id>" } else { "/deny <id>" };
+        return (BAD_REQUEST, format!("Usage: {}", usage));
+    }
+    if let Some(notice) = ledger::closed_notice_in(ledger_dir, request_id) {
+        return (CONFLICT, notice);
+    }
+    let details = ledger::details_in(ledger_dir, request_id).unwrap_or_default();
+    if allow && details.required_approvals > 1 {
+        return (
+            CONFLICT,
+            "This request needs several approvals; answer it in chat".to_string(),
+        );
+    }
+    let resolution = Resolution {
+        allow,
+        by: BY.to_string(),
+    };
+    match ledger::resolve_in(ledger_dir, request_id, resolution) {
+        Ok(true) => {
+            tracing::info!(
+                "{} {} request {}",
+                BY,
+                if allow { "allowed" } else { "denied" },
+                request_id
+            );
+            let verb = if allow { "✅ Allowed" } else { "❌ Denied" };
+            (OK, format!("{} {}", verb, request_id))
+        }
+        // Someone answered in the meantime
+        Ok(false) => (
+            CONFLICT,
+            ledger::closed_notice_in(ledger_dir, request_id)
+                .unwrap_or_else(|| "This request is no longer waiting".to_string()),
+        ),
+        Err(e) => (
+            (500, "Internal Server Error"),
+            format!("Failed to decide: {}", e),
+        ),
+    }
+}
+
+/// Decide every waiting request on a host or session not decided yet,
+/// leaving out requests needing several approvals when allowing.
+fn decide_all(ledger_dir: &Path, allow: bool, target: &str) -> String {
+    let (requests, quorum): (Vec<_>, Vec<_>) =
+        ledger::matching(ledger::pending_in(ledger_dir), target)
+            .into_iter()
+            .filter(|request| ledger::resolution_in(ledger_dir, &request.request_id).is_none())
+            .partition(|request| !allow || request.details.required_approvals <= 1);
+    let decided = requests
+        .iter()
+        .filter(|request| {
+            let resolution = Resolution {
+                allow,
+                by: BY.to_string(),
+            };
+            ledger::resolve_in(ledger_dir, &request.request_id, resolution).unwrap_or(false)
+        })
+        .count();
+    tracing::info!("{} decided {} pending request(s)", BY, decided);
+    let verb = if allow { "✅ Allowed" } else { "❌ Denied" };
+    let mut reply = format!("{} {} request(s)", verb, decided);
+    if !quorum.is_empty() {
+        reply.push_str(&format!(
+            ", left out {} needing several approvals",
+            quorum.len()
+        ));
+    }
+    reply
+}
+
+/// Carry out a command, as typed to the bot, and reply to it.
+fn execute(text: &str, ledger_dir: &Path, pauses: &PauseList, now: u64) -> (Status, String) {
+    let text = text.trim();
+    let (command, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let args = args.trim();
+    match command.trim_start_matches('/') {
+        "pending" => {
+            let lines: Vec<String> = ledger::matching(ledger::pending_in(ledger_dir), args)
+                .iter()
+                .map(|request| {
+                    format!(
+                        "{} {}: {} ({}s left)",
+                        request.request_id,
+                        request.details.host,
+                        request.details.summary,
+                        request.expires_at.saturating_sub(now)
+                    )
+                })
+                .collect();
+            if lines.is_empty() {
+                (OK, "No pending requests".to_string())
+            } else {
+                (OK, lines.join("\n"))
+            }
+        }
+        "approve" => decide(ledger_dir, args, true),
+        "deny" => decide(ledger_dir, args, false),
+        "allow_all" => (OK, decide_all(ledger_dir, true, args)),
+        "deny_all" => (OK, decide_all(ledger_dir, false, args)),
+        "pause" if args.is_empty() => {
+            let lines: Vec<String> = pauses.list().iter().map(ToString::to_string).collect();
+            if lines.is_empty() {
+                (OK, "Nothing is paused".to_string())
+            } else {
+                (OK, lines.join("\n"))
+            }
+        }
+        "pause" => match pauses.pause(args, BY) {
+            Ok(true) => {
+                tracing::info!("{} paused {}", BY, args);
+                (OK, format!("⏸️ Denying requests from {}", args))
+            }
+            Ok(false) => (CONFLICT, format!("{} is already paused", args)),
+            Err(e) => (
+                (500, "Internal Server Error"),
+                format!("Failed to pause: {}", e),
+            ),
+        },
+        "resume" => match pauses.resume(args) {
+            Ok(lifted) if lifted.is_empty() => (OK, "Nothing to resume".to_string()),
+            Ok(lifted) => {
+                let targets: Vec<&str> = lifted.iter().map(|pause| pause.target.as_str()).collect();
+                tracing::info!("{} resumed {}", BY, targets.join(", "));
+                (OK, format!("▶️ Resumed {}", targets.join(", ")))
+            }
+            Err(e) => (
+                (500, "Internal Server Error"),
+                format!("Failed to resume: {}", e),
+            ),
+        },
+        _ => (
+            BAD_REQUEST,
+            "Unknown command; expected /pending, /approve, /deny, /allow_all, /deny_all, \
+             /pause or /resume"
+                .to_string(),
+        ),
+    }
+}
+
+fn respond(request: &HttpRequest, config: &Config) -> (Status, String) {
+    let Some(token) = config
+        .command_api
+        .token
+        .as_deref()
+        .filter(|token| !token.is_empty())
+    else {
+        return (
+            (503, "Service Unavailable"),
+            "The command API has no token".to_string(),
+        );
+    };
+    if request.path() != COMMAND_PATH {
+        return ((404, "Not Found"), "Not found".to_string());
+    }
+    if request.method != "POST" {
+        return (
+            (405, "Method Not Allowed"),
+            "Method not allowed".to_string(),
+        );
+    }
+    if !authorized(request, token) {
+        tracing::warn!("Refused a command with a wrong or missing token");
+        return ((401, "Unauthorized"), "Unauthorized".to_string());
+    }
+    let now = now_secs();
+    execute(
+        &String::from_utf8_lossy(&request.body),
+        &default_request_ledger_path(),
+        &PauseList::new(None),
+        now,
+    )
+}
+
+/// What a pairing QR code carries: where to post commands, and the token.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn pairing_payload(settings: &CommandApiConfig, url: Option<&str>) -> Result<String> {
+    let token = settings
+        .token
+        .as_deref()
+        .filter(|token| !token.is_empty())
+        .context("preferences.command_api has no token")?;
+    let base = match url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => format!("http://{}", settings.listen),
+    };
+    Ok(json!({"url": format!("{}{}", base, COMMAND_PATH), "token": token}).to_string())
+}
+
+/// Show a QR code with the command URL and token, so a phone shortcut or
+/// dashboard can be set up by scanning it.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub fn pair(config_path: Option<&Path>, url: Option<&str>) -> Result<()> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
+    let settings = &config.command_api;
+    if !settings.enabled {
+        bail!("The command API is off; enable preferences.command_api first");
+    }
+    let payload = pairing_payload(settings, url)?;
+    let code = QrCode::new(payload.as_bytes()).context("Failed to make the QR code")?;
+
+    println!("📱 Scan this QR code to send commands to this daemon:\n");
+    println!(
+        "{}",
+        code.render::<char>()
+            .quiet_zone(true)
+            .module_dimensions(2, 1)
+            .build()
+    );
+    let local = settings
+        .listen
+        .parse::<SocketAddr>()
+        .is_ok_and(|address| address.ip().is_loopback() || address.ip().is_unspecified());
+    if url.is_none() && local {
+        println!(
+            "\nOther devices can't reach {}; pass --url with the address they reach the daemon at.",
+            settings.listen
+        );
+    }
+    println!("\n⚠️ The code holds the command API token: anyone who scans it can answer requests.");
+    Ok(())
+}
+
+/// Bind the command listener, so a busy port fails the daemon at start.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn bind(address: &str) -> Result<TcpListener> {
+    TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to listen for commands on {}", address))
+}
+
+/// Take commands until the task is aborted.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn serve(listener: TcpListener, config_path: Option<PathBuf>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle(stream, config_path.clone()));
+            }
+            Err(e) => tracing::warn!("Failed to accept command: {}", e),
+        }
+    }
+}
+
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+async fn handle(mut stream: TcpStream, config_path: Option<PathBuf>) {
+    let request = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) => {
+            tracing::debug!("Invalid command request: {}", e);
+            return;
+        }
+        Err(_) => return,
+    };
+    // Answering takes ledger and pause list locks
+    let (status, body) = off_reactor(move || match load_config_for_request(config_path) {
+        Ok(config) => respond(&request, &config),
+        Err(e) => {
+            tracing::warn!("Failed to load configuration for command: {}", e);
+            (
+                (500, "Internal Server Error"),
+                "Configuration error".to_string(),
+            )
+        }
+    })
+    .await;
+    if let Err(e) = write_response(&mut stream, status, "text/plain; charset=utf-8", &body).await {
+        tracing::debug!("Failed to answer command: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messenger::ledger::RequestDetails;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_authorized() {
+        let request = |value: &str| HttpRequest {
+            headers: vec![("authorization".to_string(), value.to_string())],
+            ..HttpRequest::default()
+        };
+        assert!(authorized(&request("Bearer s3cret"), "s3cret"));
+        assert!(!authorized(&request("Bearer s3cre"), "s3cret"));
+        assert!(!authorized(&request("s3cret"), "s3cret"));
+        assert!(!authorized(&HttpRequest::default(), "s3cret"));
+    }
+
+    #[test]
+    fn test_pairing_payload() {
+        let mut settings = CommandApiConfig {
+            enabled: true,
+            ..CommandApiConfig::default()
+        };
+        assert!(pairing_payload(&settings, None).is_err());
+
+        settings.token = Some("s3cret".to_string());
+        assert_eq!(
+            pairing_payload(&settings, None).unwrap(),
+            r#"{"url":"http://127.0.0.1:8789/command","token":"s3cret"}"#
+        );
+        assert_eq!(
+            pairing_payload(&settings, Some("https://ccr.example.com/")).unwrap(),
+            r#"{"url":"https://ccr.example.com/command","token":"s3cret"}"#
+        );
+    }
+
+    #[test]
+    fn test_execute() {
+        let dir = tempdir().unwrap();
+        let ledger_dir = dir.path().join("requests");
+        let pauses = PauseList::new(Some(dir.path().join("pauses.json")));
+        let open = |request_id: &str, host: &str, required_approvals: usize| {
+            let details = RequestDetails {
+                host: host.to_string(),
+                summary: "Bash \"ls\"".to_string(),
+                required_approvals,
+                ..RequestDetails::default()
+            };
+            ledger::open_in(
+                &ledger_dir,
+                request_id,
+                Duration::from_secs(60),
+                Some(details),
+            )
+            .unwrap();
+        };
+        open("abc12345", "laptop", 1);
+        open("def12345", "laptop", 2);
+        open("fed12345", "gpu-box", 1);
+        let run = |text: &str| execute(text, &ledger_dir, &pauses, 0).1;
+
+        assert_eq!(
+            run("/pending gpu-box"),
+            format!(
+                "fed12345 gpu-box: Bash \"ls\" ({}s left)",
+                ledger::pending_in(&ledger_dir)[2].expires_at
+            )
+        );
+        assert_eq!(run("/approve fed12345"), "✅ Allowed fed12345");
+        assert_eq!(
+            run("/deny fed12345"),
+            "Already decided: ✅ Approved by command API"
+        );
+        assert_eq!(run("/deny 0000"), "This request has expired");
+        assert_eq!(
+            run("/approve def12345"),
+            "This request needs several approvals; answer it in chat"
+        );
+        assert_eq!(
+            run("/allow_all laptop"),
+            "✅ Allowed 1 request(s), left out 1 needing several approvals"
+        );
+        assert_eq!(run("deny_all"), "❌ Denied 1 request(s)");
+        assert_eq!(
+            ledger::resolution_in(&ledger_dir, "abc12345"),
+            Some(Resolution {
+                allow: true,
+                by: BY.to_string()
+            })
+        );
+
+        assert_eq!(run("/pause"), "Nothing is paused");
+        assert_eq!(run("/pause laptop"), "⏸️ Denying requests from laptop");
+        assert_eq!(run("/pause"), "laptop (by command API)");
+        assert_eq!(run("/resume"), "▶️ Resumed laptop");
+        assert_eq!(execute("/run x", &ledger_dir, &pauses, 0).0, BAD_REQUEST);
+    }
+}
@@ -5,17 +5,28 @@
 //! 2. New format: `~/.claude/hook_config.json` with `messengers` section for Telegram and Signal
 //!
 //! Falls back to environment variables if no config file exists.
+//!
+//! When `XDG_CONFIG_HOME` / `XDG_STATE_HOME` are set, config and state files
+//! live under `claude-code-telegram/` in those directories, unless the file
+//! already exists in `~/.claude`.
+//!
+//! In a container, `CCR_CONTAINER=1` takes the config from `CCR_*` variables
+//! and secret files alone, `CCR_STATE_DIR` puts state on a mounted volume,
+//! and `CCR_HOSTNAME` gives the host a name that outlives its pod.
 
 use crate::error::ConfigError;
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
-use teloxide::types::ChatId;
 
 /// Default configuration file path (new format).
 pub fn default_config_path() -> PathBuf {
-    dirs_config_dir().join("hook_config.json")
+    config_file_path("hook_config.json")
 }
 
 /// Legacy configuration file path (old format).
@@ -25,13 +36,119 @@ pub fn legacy_config_path() -> PathBuf {
 
 /// Default always-allow file path.
 pub fn default_always_allow_path() -> PathBuf {
-    dirs_config_dir().join("always_allow.json")
+    state_file_path("always_allow.json")
+}
+
+/// Claude Code user settings file path.
+pub fn default_claude_settings_path() -> PathBuf {
+    dirs_config_dir().join("settings.json")
+}
+
+/// Directory where Claude Code stores session transcripts, one subdirectory per project.
+pub fn default_claude_projects_path() -> PathBuf {
+    dirs_config_dir().join("projects")
+}
+
+/// Default path of the activity history log.
+pub fn default_history_path() -> PathBuf {
+    state_file_path("history.jsonl")
+}
+
+/// Default path of the hash-chained decision audit log.
+pub fn default_audit_log_path() -> PathBuf {
+    state_file_path("audit.jsonl")
+}
+
+/// Default directory for in-flight tool timing markers.
+pub fn default_tool_timers_path() -> PathBuf {
+    state_file_path("tool_timers")
+}
+
+/// Default socket of the pre-warmed hook daemon.
+#[cfg(unix)]
+pub fn default_daemon_socket_path() -> PathBuf {
+    state_file_path("daemon.sock")
+}
+
+/// Default named pipe of the pre-warmed hook daemon.
+///
+/// Pipes share one namespace per machine, so the name carries a hash of the
+/// state directory to keep each user's daemon apart.
+#[cfg(windows)]
+pub fn default_daemon_socket_path() -> PathBuf {
+    use sha2::{Digest, Sha256};
+
+    let state_dir = state_file_path("daemon");
+    let hash: String = Sha256::digest(state_dir.to_string_lossy().as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    PathBuf::from(format!(r"\\.\pipe\claude-code-telegram-{}", hash))
+}
+
+/// Default path of the hosts and sessions paused from chat.
+pub fn default_pauses_path() -> PathBuf {
+    state_file_path("paused.json")
+}
+
+/// Default path of the delegations and vacation mode set from chat.
+pub fn default_away_path() -> PathBuf {
+    state_file_path("away.json")
+}
+
+/// Default path of the token usage and cost alerts tracked per session.
+pub fn default_cost_guard_path() -> PathBuf {
+    state_file_path("cost_guard.json")
+}
+
+/// Default path of the yolo windows opened from chat.
+pub fn default_yolo_path() -> PathBuf {
+    state_file_path("yolo.json")
+}
+
+/// Default path of the permission requests marked to drop duplicate prompts.
+pub fn default_prompt_marks_path() -> PathBuf {
+    state_file_path("prompt_marks.json")
+}
+
+/// Default path of the read-only calls counted for the next digest.
+pub fn default_read_only_digest_path() -> PathBuf {
+    state_file_path("read_only_digest.json")
+}
+
+/// Default directory of per-session files used to group rapid-fire requests.
+pub fn default_request_groups_path() -> PathBuf {
+    state_file_path("request_groups")
+}
+
+/// Default directory tracking where each decision request stands.
+pub fn default_request_ledger_path() -> PathBuf {
+    state_file_path("requests")
+}
+
+/// Default spool of Telegram updates shared by processes polling a bot.
+///
+/// One file per bot, named after the bot ID (the part of the token before `:`).
+#[cfg(feature = "telegram")]
+pub fn default_update_spool_path(bot_token: &str) -> PathBuf {
+    let bot_id = bot_token
+        .split_once(':')
+        .map(|(id, _)| id)
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or("bot");
+    state_file_path("telegram_updates").join(format!("{}.json", bot_id))
 }
 
 /// Default Signal data directory path.
 #[cfg(feature = "signal")]
 pub fn default_signal_data_path() -> PathBuf {
-    dirs_config_dir().join("signal_data")
+    state_file_path("signal_data")
+}
+
+/// Directory messenger plugins are looked up in.
+pub fn default_plugin_dir() -> PathBuf {
+    dirs_config_dir().join("ccr-plugins")
 }
 
 /// Get the .claude config directory path.
@@ -41,6 +158,49 @@ fn dirs_config_dir() -> PathBuf {
         .unwrap_or_else(|| PathBuf::from(".claude"))
 }
 
+/// Directory name used under XDG base directories.
+const XDG_APP_DIR: &str = "claude-code-telegram";
+
+/// Read an XDG base directory variable, ignoring empty or relative values.
+fn xdg_base(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+}
+
+/// Pick the location of a file, keeping an existing `~/.claude` copy.
+///
+/// Otherwise the file lives under the XDG base directory when one is set.
+fn resolve_base_path(legacy_dir: &Path, xdg_base: Option<PathBuf>, name: &str) -> PathBuf {
+    let legacy = legacy_dir.join(name);
+    match xdg_base {
+        Some(base) if !legacy.exists() => base.join(XDG_APP_DIR).join(name),
+        _ => legacy,
+    }
+}
+
+/// Path of a config file, honoring `XDG_CONFIG_HOME`.
+fn config_file_path(name: &str) -> PathBuf {
+    resolve_base_path(&dirs_config_dir(), xdg_base("XDG_CONFIG_HOME"), name)
+}
+
+/// Path of a state file or directory, honoring `CCR_STATE_DIR`, then
+/// `XDG_STATE_HOME`.
+fn state_file_path(name: &str) -> PathBuf {
+    match xdg_base("CCR_STATE_DIR") {
+        Some(state_dir) => state_dir.join(name),
+        None => resolve_base_path(&dirs_config_dir(), xdg_base("XDG_STATE_HOME"), name),
+    }
+}
+
+/// Whether the process runs in a container, as set by `CCR_CONTAINER`.
+///
+/// Config files in the home directory are then ignored, and the daemon
+/// serves health checks.
+pub fn container_mode() -> bool {
+    env_flag("CCR_CONTAINER")
+}
+
 // ============================================================================
 // Legacy Configuration (backward compatibility)
 // ============================================================================
@@ -52,8 +212,21 @@ struct LegacyConfigFile {
     telegram_chat_id: ChatIdValue,
 }
 
+/// Telegram chat ID.
+///
+/// Kept separate from teloxide's type so configuration parses the same in
+/// builds without the Telegram backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChatId(pub i64);
+
+impl fmt::Display for ChatId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// Chat ID that can be either string or integer in JSON.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(untagged)]
 enum ChatIdValue {
     String(String),
@@ -71,20 +244,404 @@ impl ChatIdValue {
     }
 }
 
+// ============================================================================
+// Environment Overrides
+// ============================================================================
+
+/// How an environment override value is converted to JSON.
+#[derive(Debug, Clone, Copy)]
+enum EnvKind {
+    String,
+    Bool,
+    Integer,
+    /// Comma-separated strings
+    StringList,
+    /// Comma-separated integers
+    IntegerList,
+}
+
+/// Environment variables overriding new-format config values, by JSON path.
+const ENV_OVERRIDES: &[(&str, &str, EnvKind)] = &[
+    (
+        "CCR_TELEGRAM_ENABLED",
+        "messengers.telegram.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_TELEGRAM_BOT_TOKEN",
+        "messengers.telegram.bot_token",
+        EnvKind::String,
+    ),
+    (
+        "CCR_TELEGRAM_BOT_TOKEN_FILE",
+        "messengers.telegram.bot_token_file",
+        EnvKind::String,
+    ),
+    (
+        "CCR_TELEGRAM_BOT_TOKEN_COMMAND",
+        "messengers.telegram.bot_token_command",
+        EnvKind::String,
+    ),
+    (
+        "CCR_TELEGRAM_CHAT_ID",
+        "messengers.telegram.chat_id",
+        EnvKind::String,
+    ),
+    (
+        "CCR_TELEGRAM_TOPIC_ID",
+        "messengers.telegram.topic_id",
+        EnvKind::Integer,
+    ),
+    (
+        "CCR_TELEGRAM_COMPACT",
+        "messengers.telegram.compact",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_DISCORD_ENABLED",
+        "messengers.discord.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_DISCORD_BOT_TOKEN",
+        "messengers.discord.bot_token",
+        EnvKind::String,
+    ),
+    (
+        "CCR_DISCORD_BOT_TOKEN_FILE",
+        "messengers.discord.bot_token_file",
+        EnvKind::String,
+    ),
+    (
+        "CCR_DISCORD_BOT_TOKEN_COMMAND",
+        "messengers.discord.bot_token_command",
+        EnvKind::String,
+    ),
+    (
+        "CCR_DISCORD_USER_ID",
+        "messengers.discord.user_id",
+        EnvKind::String,
+    ),
+    (
+        "CCR_DISCORD_COMPACT",
+        "messengers.discord.compact",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_SIGNAL_ENABLED",
+        "messengers.signal.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_SIGNAL_PHONE_NUMBER",
+        "messengers.signal.phone_number",
+        EnvKind::String,
+    ),
+    (
+        "CCR_SIGNAL_DEVICE_NAME",
+        "messengers.signal.device_name",
+        EnvKind::String,
+    ),
+    (
+        "CCR_SIGNAL_DATA_PATH",
+        "messengers.signal.data_path",
+        EnvKind::String,
+    ),
+    (
+        "CCR_HOMEASSISTANT_ENABLED",
+        "messengers.homeassistant.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_HOMEASSISTANT_MQTT_HOST",
+        "messengers.homeassistant.mqtt_host",
+        EnvKind::String,
+    ),
+    (
+        "CCR_HOMEASSISTANT_MQTT_PORT",
+        "messengers.homeassistant.mqtt_port",
+        EnvKind::Integer,
+    ),
+    (
+        "CCR_HOMEASSISTANT_USERNAME",
+        "messengers.homeassistant.username",
+        EnvKind::String,
+    ),
+    (
+        "CCR_HOMEASSISTANT_PASSWORD",
+        "messengers.homeassistant.password",
+        EnvKind::String,
+    ),
+    (
+        "CCR_PRIMARY_MESSENGER",
+        "preferences.primary_messenger",
+        EnvKind::String,
+    ),
+    (
+        "CCR_MESSENGER_PRIORITY",
+        "preferences.messenger_priority",
+        EnvKind::StringList,
+    ),
+    ("CCR_HOST_LABEL", "preferences.host_label", EnvKind::String),
+    ("CCR_HOST_EMOJI", "preferences.host_emoji", EnvKind::String),
+    (
+        "CCR_REQUEST_ID_LENGTH",
+        "preferences.request_id_length",
+        EnvKind::Integer,
+    ),
+    (
+        "CCR_TIMEOUT_SECONDS",
+        "preferences.timeout_seconds",
+        EnvKind::Integer,
+    ),
+    (
+        "CCR_STOP_MIN_DURATION_SECONDS",
+        "preferences.stop_notifications.min_duration_seconds",
+        EnvKind::Integer,
+    ),
+    (
+        "CCR_STOP_PROJECTS",
+        "preferences.stop_notifications.projects",
+        EnvKind::StringList,
+    ),
+    (
+        "CCR_STOP_TASK_PROMPT",
+        "preferences.stop_notifications.task_prompt",
+        EnvKind::String,
+    ),
+    (
+        "CCR_STOP_BROADCAST",
+        "preferences.stop_notifications.broadcast",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_IDLE_NUDGES_ENABLED",
+        "preferences.idle_nudges.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_IDLE_NUDGES_REMINDER_MINUTES",
+        "preferences.idle_nudges.reminder_minutes",
+        EnvKind::IntegerList,
+    ),
+    (
+        "CCR_LLM_SUMMARY_ENABLED",
+        "preferences.llm_summary.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_LLM_SUMMARY_PROVIDER",
+        "preferences.llm_summary.provider",
+        EnvKind::String,
+    ),
+    (
+        "CCR_LLM_SUMMARY_ENDPOINT",
+        "preferences.llm_summary.endpoint",
+        EnvKind::String,
+    ),
+    (
+        "CCR_LLM_SUMMARY_MODEL",
+        "preferences.llm_summary.model",
+        EnvKind::String,
+    ),
+    (
+        "CCR_LLM_SUMMARY_API_KEY",
+        "preferences.llm_summary.api_key",
+        EnvKind::String,
+    ),
+    (
+        "CCR_LLM_SUMMARY_API_KEY_ENV",
+        "preferences.llm_summary.api_key_env",
+        EnvKind::String,
+    ),
+    (
+        "CCR_LLM_SUMMARY_MAX_TRANSCRIPT_CHARS",
+        "preferences.llm_summary.max_transcript_chars",
+        EnvKind::Integer,
+    ),
+    (
+        "CCR_LONG_RUNNING_TOOLS_ENABLED",
+        "preferences.long_running_tools.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_LONG_RUNNING_TOOLS_THRESHOLD_SECONDS",
+        "preferences.long_running_tools.threshold_seconds",
+        EnvKind::Integer,
+    ),
+    ("CCR_STRICT", "preferences.strict", EnvKind::Bool),
+    (
+        "CCR_REDACTION_ENABLED",
+        "preferences.redaction.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_AUDIT_LOG_ENABLED",
+        "preferences.audit_log.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_AUDIT_LOG_PATH",
+        "preferences.audit_log.path",
+        EnvKind::String,
+    ),
+    (
+        "CCR_GITHUB_DEPLOYMENTS_SECRET",
+        "preferences.github_deployments.secret",
+        EnvKind::String,
+    ),
+    (
+        "CCR_GITHUB_DEPLOYMENTS_TOKEN",
+        "preferences.github_deployments.token",
+        EnvKind::String,
+    ),
+    (
+        "CCR_WEB_APPROVALS_SECRET",
+        "preferences.web_approvals.secret",
+        EnvKind::String,
+    ),
+    (
+        "CCR_COMMAND_API_TOKEN",
+        "preferences.command_api.token",
+        EnvKind::String,
+    ),
+    (
+        "CCR_HEALTH_CHECKS_ENABLED",
+        "preferences.health_checks.enabled",
+        EnvKind::Bool,
+    ),
+    (
+        "CCR_HEALTH_CHECKS_LISTEN",
+        "preferences.health_checks.listen",
+        EnvKind::String,
+    ),
+];
+
+/// Convert an environment override value to JSON.
+fn parse_env_value(name: &str, raw: &str, kind: EnvKind) -> Result<Value, ConfigError> {
+    let invalid = || ConfigError::InvalidEnvVar(name.to_string(), raw.to_string());
+    let items = || raw.split(',').map(str::trim).filter(|s| !s.is_empty());
+
+    Ok(match kind {
+        EnvKind::String => Value::String(raw.to_string()),
+        EnvKind::Bool => match raw.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Value::Bool(true),
+            "0" | "false" | "no" | "off" => Value::Bool(false),
+            _ => return Err(invalid()),
+        },
+        EnvKind::Integer => Value::from(raw.trim().parse::<i64>().map_err(|_| invalid())?),
+        EnvKind::StringList => Value::from(items().collect::<Vec<_>>()),
+        EnvKind::IntegerList => Value::from(
+            items()
+                .map(|s| s.parse::<i64>().map_err(|_| invalid()))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    })
+}
+
+/// Apply `CCR_*` overrides to a new-format config document.
+///
+/// Returns whether any override was applied.
+fn apply_env_overrides(
+    root: &mut Value,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<bool, ConfigError> {
+    let mut applied = false;
+
+    for (name, path, kind) in ENV_OVERRIDES {
+        let Some(raw) = lookup(name) else {
+            continue;
+        };
+        let value = parse_env_value(name, &raw, *kind)?;
+
+        let keys: Vec<&str> = path.split('.').collect();
+        let (last, parents) = keys.split_last().expect("override path is non-empty");
+        let mut node = &mut *root;
+        for key in parents {
+            if !node.is_object() {
+                *node = Value::Object(Map::new());
+            }
+            node = node
+                .as_object_mut()
+                .expect("node is an object")
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+        if !node.is_object() {
+            *node = Value::Object(Map::new());
+        }
+        node.as_object_mut()
+            .expect("node is an object")
+            .insert(last.to_string(), value);
+        applied = true;
+    }
+
+    Ok(applied)
+}
+
 // ============================================================================
 // New Configuration Format
 // ============================================================================
 
 /// New JSON configuration file structure with multi-messenger support.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 struct NewConfigFile {
     messengers: MessengersConfig,
     #[serde(default)]
     preferences: PreferencesConfig,
+    #[serde(default)]
+    routes: Vec<ProjectRouteFile>,
+    #[serde(default)]
+    authorized_responders: AuthorizedRespondersFile,
+    #[serde(default)]
+    approval_rules: Vec<ApprovalRule>,
+}
+
+/// People allowed to answer permission requests, per platform, from file.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+struct AuthorizedRespondersFile {
+    #[serde(default)]
+    telegram: Vec<UserIdValue>,
+    #[serde(default)]
+    discord: Vec<UserIdValue>,
+    #[serde(default)]
+    signal: Vec<String>,
+}
+
+/// User ID that can be either string or integer in JSON.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum UserIdValue {
+    String(String),
+    Integer(u64),
+}
+
+impl UserIdValue {
+    fn to_u64(&self, field: &str) -> Result<u64, ConfigError> {
+        match self {
+            UserIdValue::String(s) => s.parse::<u64>().map_err(|_| {
+                ConfigError::MissingField(format!("{} must contain valid integers", field))
+            }),
+            UserIdValue::Integer(i) => Ok(*i),
+        }
+    }
+}
+
+/// Per-project destination override from file.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ProjectRouteFile {
+    projects: Vec<String>,
+    #[serde(default)]
+    telegram_chat_id: Option<ChatIdValue>,
+    #[serde(default)]
+    telegram_topic_id: Option<i32>,
+    #[cfg(feature = "discord")]
+    #[serde(default)]
+    discord_user_id: Option<DiscordUserIdValue>,
 }
 
 /// Configuration for all supported messengers.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[allow(dead_code)]
 struct MessengersConfig {
     #[serde(default)]
@@ -94,20 +651,146 @@ struct MessengersConfig {
     #[cfg(feature = "discord")]
     #[serde(default)]
     discord: Option<DiscordConfigFile>,
+    #[cfg(feature = "homeassistant")]
+    #[serde(default)]
+    homeassistant: Option<HomeAssistantConfig>,
+    /// Messengers run as external programs, by name
+    #[serde(default)]
+    plugins: BTreeMap<String, PluginConfigFile>,
+    /// Telegram chat that only receives events, never decisions
+    #[serde(default)]
+    observer: Option<ObserverConfigFile>,
+}
+
+/// A notification-only Telegram chat from file.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ObserverConfigFile {
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    /// Bot token, if not the one of `messengers.telegram`
+    #[serde(flatten)]
+    token: BotTokenSource,
+    chat_id: ChatIdValue,
+    #[serde(default)]
+    topic_id: Option<i32>,
+    /// Events to post (default: all)
+    #[serde(default)]
+    events: Vec<WebhookEvent>,
 }
 
+/// A messenger plugin from file.
+#[derive(Debug, Deserialize, JsonSchema)]
+struct PluginConfigFile {
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    /// Program to run instead of `~/.claude/ccr-plugins/<name>`
+    #[serde(default)]
+    command: Option<String>,
+    /// Passed to the plugin with every call
+    #[serde(default)]
+    settings: Value,
+}
+
+/// Bot token given inline, in a file, or by a command's output.
+///
+/// The first non-empty source wins, in the order listed.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct BotTokenSource {
+    #[serde(default)]
+    pub bot_token: String,
+    /// File containing the token (should be readable only by you)
+    #[serde(default)]
+    pub bot_token_file: Option<String>,
+    /// Shell command printing the token, e.g. `pass show ccr/telegram`
+    #[serde(default)]
+    pub bot_token_command: Option<String>,
+}
+
+impl BotTokenSource {
+    /// Resolve the token, returning an empty string when no source is set.
+    pub fn resolve(self, messenger: &str) -> Result<String, ConfigError> {
+        if !self.bot_token.is_empty() {
+            return Ok(self.bot_token);
+        }
+
+        if let Some(path) = self.bot_token_file {
+            let field = format!("{}.bot_token_file", messenger);
+            let path = expand_home(&path);
+            warn_if_world_readable(&path);
+            return fs::read_to_string(&path)
+                .map(|token| token.trim().to_string())
+                .map_err(|e| ConfigError::Secret(field, format!("{}: {}", path.display(), e)));
+        }
+
+        if let Some(command) = self.bot_token_command {
+            let field = format!("{}.bot_token_command", messenger);
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .stdin(std::process::Stdio::null())
+                .stderr(std::process::Stdio::inherit())
+                .output()
+                .map_err(|e| ConfigError::Secret(field.clone(), e.to_string()))?;
+            if !output.status.success() {
+                return Err(ConfigError::Secret(
+                    field,
+                    format!("`{}` exited with {}", command, output.status),
+                ));
+            }
+            return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+        }
+
+        Ok(String::new())
+    }
+}
+
+/// Expand a leading `~/` (or `~\` on Windows) to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = path
+        .strip_prefix('~')
+        .and_then(|rest| rest.strip_prefix(['/', std::path::MAIN_SEPARATOR]));
+    match (rest, directories::BaseDirs::new()) {
+        (Some(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Warn when a secret file can be read by other users.
+#[cfg(unix)]
+fn warn_if_world_readable(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Ok(metadata) = fs::metadata(path) {
+        if metadata.permissions().mode() & 0o077 != 0 {
+            tracing::warn!(
+                "{} is accessible by other users; run `chmod 600` on it",
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_world_readable(_path: &Path) {}
+
 /// Telegram-specific configuration from file.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[allow(dead_code)]
 struct TelegramConfigFile {
     #[serde(default = "default_enabled")]
     enabled: bool,
-    bot_token: String,
+    #[serde(flatten)]
+    token: BotTokenSource,
     chat_id: ChatIdValue,
+    #[serde(default)]
+    topic_id: Option<i32>,
+    /// Send one-line requests, for smartwatches and SMS forwarding
+    #[serde(default)]
+    compact: bool,
 }
 
 /// Signal-specific configuration from file.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[allow(dead_code)]
 pub struct SignalConfigFile {
     #[serde(default = "default_enabled")]
@@ -121,18 +804,22 @@ pub struct SignalConfigFile {
 
 /// Discord-specific configuration from file.
 #[cfg(feature = "discord")]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[allow(dead_code)]
 pub struct DiscordConfigFile {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
-    pub bot_token: String,
+    #[serde(flatten)]
+    pub token: BotTokenSource,
     pub user_id: DiscordUserIdValue,
+    /// Send one-line requests, for smartwatches and SMS forwarding
+    #[serde(default)]
+    pub compact: bool,
 }
 
 /// Discord user ID that can be either string or integer in JSON.
 #[cfg(feature = "discord")]
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum DiscordUserIdValue {
     String(String),
@@ -151,6 +838,47 @@ impl DiscordUserIdValue {
     }
 }
 
+/// Home Assistant configuration, reached through its MQTT broker.
+#[cfg(feature = "homeassistant")]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct HomeAssistantConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// MQTT broker Home Assistant uses (e.g. the Mosquitto add-on)
+    pub mqtt_host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub mqtt_port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Topic prefix for requests, decisions and notifications
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+    /// `person` or `device_tracker` entities; requests only go to Home
+    /// Assistant while one of them is home
+    #[serde(default)]
+    pub presence: Vec<String>,
+    /// `base_topic` of Home Assistant's `mqtt_statestream` integration
+    #[serde(default = "default_statestream_topic")]
+    pub statestream_topic: String,
+}
+
+#[cfg(feature = "homeassistant")]
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+#[cfg(feature = "homeassistant")]
+fn default_topic_prefix() -> String {
+    "claude_code_remote".to_string()
+}
+
+#[cfg(feature = "homeassistant")]
+fn default_statestream_topic() -> String {
+    "homeassistant".to_string()
+}
+
 fn default_enabled() -> bool {
     true
 }
@@ -160,29 +888,828 @@ fn default_device_name() -> String {
 }
 
 /// User preferences for messenger behavior.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[allow(dead_code)]
 struct PreferencesConfig {
-    #[serde(default = "default_primary_messenger")]
-    primary_messenger: String,
+    /// Deprecated: first entry of `messenger_priority`
+    #[serde(default)]
+    primary_messenger: Option<String>,
+    #[serde(default)]
+    messenger_priority: Option<Vec<String>>,
+    /// Messengers to use instead of `messenger_priority`, by severity
+    #[serde(default)]
+    severity_routes: BTreeMap<Severity, Vec<String>>,
     #[serde(default = "default_timeout_seconds")]
     timeout_seconds: u64,
+    #[serde(default)]
+    stop_notifications: StopNotificationConfig,
+    #[serde(default)]
+    idle_nudges: IdleNudgeConfig,
+    #[serde(default)]
+    llm_summary: LlmSummaryConfig,
+    #[serde(default)]
+    command_explanations: CommandExplanationConfig,
+    #[serde(default)]
+    long_running_tools: LongRunningToolConfig,
+    #[serde(default)]
+    cost_guard: CostGuardConfig,
+    /// Deny permission requests when config or messenger setup fails
+    #[serde(default)]
+    strict: bool,
+    #[serde(default)]
+    redaction: RedactionConfig,
+    #[serde(default)]
+    audit_log: AuditLogConfig,
+    /// Maximum auto-approvals per hour, by tool name pattern
+    #[serde(default)]
+    auto_approval_limits: BTreeMap<String, u32>,
+    /// Times when always-allowed tools may be approved without asking
+    #[serde(default)]
+    auto_approval_windows: Vec<AutoApprovalWindow>,
+    #[serde(default)]
+    remote_runs: RemoteRunConfig,
+    /// Endpoints that receive request, decision and session events
+    #[serde(default)]
+    webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    github_deployments: GitHubDeploymentConfig,
+    #[serde(default)]
+    web_approvals: WebApprovalConfig,
+    #[serde(default)]
+    command_api: CommandApiConfig,
+    #[serde(default)]
+    notification_queue: NotificationQueueConfig,
+    #[serde(default)]
+    health_checks: HealthCheckConfig,
+    /// WebAssembly modules that may decide requests before anyone is asked
+    #[serde(default)]
+    policy_modules: Vec<String>,
+    /// How requests are decided while `/vacation` is on
+    #[serde(default)]
+    vacation_fallback: VacationFallback,
+    /// Also add "Deny & Block" rules to Claude Code's own deny list
+    #[serde(default)]
+    block_in_claude_settings: bool,
+    /// Emoji, labels and fields for tools, by tool name pattern
+    #[serde(default)]
+    tool_display: BTreeMap<String, ToolDisplay>,
+    #[serde(default)]
+    request_grouping: RequestGroupingConfig,
+    #[serde(default)]
+    escalation: EscalationConfig,
+    /// Characters of Claude's last message shown with each request (0: none)
+    #[serde(default = "default_request_context_chars")]
+    request_context_chars: usize,
+    /// Seconds a permission prompt notification waits for its request (0: always send)
+    #[serde(default = "default_prompt_dedup_seconds")]
+    permission_prompt_dedup_seconds: u64,
+    #[serde(default)]
+    read_only_tools: ReadOnlyToolsConfig,
+    #[serde(default)]
+    rule_suggestions: RuleSuggestionsConfig,
+    /// Short name shown for this host instead of its hostname
+    #[serde(default)]
+    host_label: Option<String>,
+    /// Emoji shown before the host, e.g. "🟦"
+    #[serde(default)]
+    host_emoji: Option<String>,
+    /// Characters in permission request IDs, 8 to 16
+    #[serde(default = "default_request_id_length")]
+    request_id_length: usize,
 }
 
 impl Default for PreferencesConfig {
     fn default() -> Self {
         Self {
-            primary_messenger: default_primary_messenger(),
+            primary_messenger: None,
+            messenger_priority: None,
+            severity_routes: BTreeMap::new(),
             timeout_seconds: default_timeout_seconds(),
+            stop_notifications: StopNotificationConfig::default(),
+            idle_nudges: IdleNudgeConfig::default(),
+            llm_summary: LlmSummaryConfig::default(),
+            command_explanations: CommandExplanationConfig::default(),
+            long_running_tools: LongRunningToolConfig::default(),
+            cost_guard: CostGuardConfig::default(),
+            strict: false,
+            redaction: RedactionConfig::default(),
+            audit_log: AuditLogConfig::default(),
+            auto_approval_limits: BTreeMap::new(),
+            auto_approval_windows: Vec::new(),
+            remote_runs: RemoteRunConfig::default(),
+            webhooks: Vec::new(),
+            github_deployments: GitHubDeploymentConfig::default(),
+            web_approvals: WebApprovalConfig::default(),
+            command_api: CommandApiConfig::default(),
+            notification_queue: NotificationQueueConfig::default(),
+            health_checks: HealthCheckConfig::default(),
+            policy_modules: Vec::new(),
+            vacation_fallback: VacationFallback::default(),
+            block_in_claude_settings: false,
+            tool_display: BTreeMap::new(),
+            request_grouping: RequestGroupingConfig::default(),
+            escalation: EscalationConfig::default(),
+            request_context_chars: default_request_context_chars(),
+            permission_prompt_dedup_seconds: default_prompt_dedup_seconds(),
+            read_only_tools: ReadOnlyToolsConfig::default(),
+            rule_suggestions: RuleSuggestionsConfig::default(),
+            host_label: None,
+            host_emoji: None,
+            request_id_length: default_request_id_length(),
+        }
+    }
+}
+
+fn default_request_context_chars() -> usize {
+    300
+}
+
+fn default_prompt_dedup_seconds() -> u64 {
+    5
+}
+
+fn default_request_id_length() -> usize {
+    12
+}
+
+/// How a tool is shown in permission requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ToolDisplay {
+    /// Emoji shown before the tool name
+    #[serde(default)]
+    pub emoji: Option<String>,
+    /// Name shown instead of the tool name
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Tool input fields to show instead of the whole input as JSON
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Accent color such as `#0969da`, for messengers that support one
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// Filters deciding which Stop events produce a completion notification.
+///
+/// Both filters are optional; when both are set, a session must pass both.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct StopNotificationConfig {
+    /// Skip sessions shorter than this many seconds (0 disables the filter)
+    #[serde(default)]
+    pub min_duration_seconds: u64,
+    /// Only notify for projects matching one of these patterns.
+    ///
+    /// Patterns support `*` and `?` wildcards and are matched against both
+    /// the project directory name and the full working directory path.
+    #[serde(default)]
+    pub projects: Vec<String>,
+    /// Which user prompt to show as the "Task" line in completion messages
+    #[serde(default)]
+    pub task_prompt: TaskPromptSource,
+    /// Send completion messages to every enabled messenger instead of just the primary
+    #[serde(default)]
+    pub broadcast: bool,
+}
+
+/// Which user message from the transcript describes the session's task.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskPromptSource {
+    /// The prompt that started the session
+    #[default]
+    First,
+    /// The most recent prompt
+    Last,
+    /// Do not include the task prompt
+    Off,
+}
+
+/// Escalating reminders while Claude is idle waiting for input.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct IdleNudgeConfig {
+    /// Send reminders after an idle_prompt notification
+    #[serde(default)]
+    pub enabled: bool,
+    /// Minutes after the idle prompt at which each reminder is sent
+    #[serde(default = "default_nudge_minutes")]
+    pub reminder_minutes: Vec<u64>,
+}
+
+impl Default for IdleNudgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reminder_minutes: default_nudge_minutes(),
+        }
+    }
+}
+
+fn default_nudge_minutes() -> Vec<u64> {
+    vec![10, 30, 60]
+}
+
+/// Starting Claude Code sessions from chat with `/run`.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub struct RemoteRunConfig {
+    /// Accept `/run` from authorized Telegram responders
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directories runs may start in, by the project name used in `/run`
+    #[serde(default)]
+    pub projects: BTreeMap<String, PathBuf>,
+    /// Claude Code executable
+    #[serde(default = "default_claude_command")]
+    pub claude_command: String,
+}
+
+impl Default for RemoteRunConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            projects: BTreeMap::new(),
+            claude_command: default_claude_command(),
+        }
+    }
+}
+
+fn default_claude_command() -> String {
+    "claude".to_string()
+}
+
+/// How requests that would be asked are decided in vacation mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VacationFallback {
+    /// Deny without asking
+    #[default]
+    Deny,
+    /// Allow without asking, except requests needing several approvals
+    Allow,
+}
+
+/// How urgent an event is, for routing it with `severity_routes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Permission requests needing several approvals
+    High,
+    /// Other permission requests and questions
+    Normal,
+    /// Notifications, such as finished sessions and idle reminders
+    Info,
+}
+
+/// Event sent to webhooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// A permission request was sent to a messenger
+    RequestCreated,
+    /// A permission request was allowed or denied
+    DecisionMade,
+    /// A permission request got no answer in time
+    Timeout,
+    /// A Claude Code session finished
+    SessionCompleted,
+}
+
+/// An endpoint that receives events as JSON POSTs.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct WebhookConfig {
+    /// URL to POST events to
+    pub url: String,
+    /// Events to send (default: all)
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+impl WebhookConfig {
+    /// Whether this webhook subscribes to `event`.
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// Approving GitHub Actions deployments from chat, served by the daemon.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub struct GitHubDeploymentConfig {
+    /// Listen for GitHub deployment webhooks while `daemon` runs
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to listen on
+    #[serde(default = "default_github_listen")]
+    pub listen: String,
+    /// Webhook secret used to check `X-Hub-Signature-256`
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Token used to approve or reject deployments on GitHub
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl Default for GitHubDeploymentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_github_listen(),
+            secret: None,
+            token: None,
+        }
+    }
+}
+
+fn default_github_listen() -> String {
+    "127.0.0.1:8787".to_string()
+}
+
+/// Signed links to approve requests from a browser, served by the daemon.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub struct WebApprovalConfig {
+    /// Add approval links to one-line requests and serve them while `daemon` runs
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to listen on
+    #[serde(default = "default_web_approvals_listen")]
+    pub listen: String,
+    /// Address the links point at, e.g. `https://approve.example.com`
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// Key the links are signed with
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Bot username for the Telegram Login Widget; when set, only authorized
+    /// Telegram responders who log in on the page can decide
+    #[serde(default)]
+    pub telegram_login: Option<String>,
+}
+
+impl Default for WebApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_web_approvals_listen(),
+            base_url: None,
+            secret: None,
+            telegram_login: None,
+        }
+    }
+}
+
+fn default_web_approvals_listen() -> String {
+    "127.0.0.1:8788".to_string()
+}
+
+/// Bot commands taken over HTTP, for automation, served by the daemon.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub struct CommandApiConfig {
+    /// Take commands while `daemon` runs
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to listen on
+    #[serde(default = "default_command_api_listen")]
+    pub listen: String,
+    /// Bearer token every command must carry
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl Default for CommandApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_command_api_listen(),
+            token: None,
+        }
+    }
+}
+
+fn default_command_api_listen() -> String {
+    "127.0.0.1:8789".to_string()
+}
+
+/// The daemon's queue of auto-approval notices waiting to be sent.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub struct NotificationQueueConfig {
+    /// Notices kept waiting before the oldest is dropped
+    #[serde(default = "default_notification_queue_capacity")]
+    pub capacity: usize,
+}
+
+impl Default for NotificationQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_notification_queue_capacity(),
+        }
+    }
+}
+
+fn default_notification_queue_capacity() -> usize {
+    100
+}
+
+/// Liveness and readiness endpoints for container orchestrators, served by
+/// the daemon.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub struct HealthCheckConfig {
+    /// Serve `/healthz` and `/readyz` while `daemon` runs (always on with `CCR_CONTAINER`)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to listen on
+    #[serde(default = "default_health_checks_listen")]
+    pub listen: String,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: default_health_checks_listen(),
+        }
+    }
+}
+
+fn default_health_checks_listen() -> String {
+    "127.0.0.1:8790".to_string()
+}
+
+/// Collapsing bursts of permission requests from one session into one message.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct RequestGroupingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Requests within the window that are still sent one by one
+    #[serde(default = "default_grouping_threshold")]
+    pub threshold: usize,
+    #[serde(default = "default_grouping_window")]
+    pub window_seconds: u64,
+}
+
+impl Default for RequestGroupingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: default_grouping_threshold(),
+            window_seconds: default_grouping_window(),
+        }
+    }
+}
+
+/// Reminders for permission requests still unanswered part way to the timeout.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct EscalationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Share of the timeout after which to escalate, between 0 and 1
+    #[serde(default = "default_escalation_fraction")]
+    pub after_fraction: f64,
+    /// Send the reminder as a new message on the messenger that was asked
+    #[serde(default = "default_enabled")]
+    pub remind: bool,
+    /// Appended to the reminder to ping someone, e.g. `@oncall`
+    #[serde(default)]
+    pub mention: Option<String>,
+    /// Other messengers to notify, e.g. `["discord"]`
+    #[serde(default)]
+    pub messengers: Vec<String>,
+}
+
+impl Default for EscalationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            after_fraction: default_escalation_fraction(),
+            remind: true,
+            mention: None,
+            messengers: Vec::new(),
+        }
+    }
+}
+
+fn default_escalation_fraction() -> f64 {
+    0.5
+}
+
+/// Low-noise mode: read-only tools allowed without asking, with a digest.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ReadOnlyToolsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Tools allowed without asking
+    #[serde(default = "default_read_only_tools")]
+    pub tools: Vec<String>,
+    /// Minutes between digests of the calls allowed (0: no digest)
+    #[serde(default = "default_digest_minutes")]
+    pub digest_minutes: u64,
+}
+
+impl Default for ReadOnlyToolsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tools: default_read_only_tools(),
+            digest_minutes: default_digest_minutes(),
+        }
+    }
+}
+
+fn default_read_only_tools() -> Vec<String> {
+    ["Read", "Glob", "Grep", "LS"].map(String::from).to_vec()
+}
+
+fn default_digest_minutes() -> u64 {
+    30
+}
+
+/// Always-allow entries proposed from the decision history.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct RuleSuggestionsConfig {
+    /// Have the bot offer new suggestions now and then
+    #[serde(default)]
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub enabled: bool,
+    /// Approvals, with no denials or timeouts, before a call is suggested
+    #[serde(default = "default_suggestion_min_approvals")]
+    pub min_approvals: usize,
+    /// Days of history considered
+    #[serde(default = "default_suggestion_days")]
+    pub days: u64,
+    /// Hours between the bot's suggestion messages
+    #[serde(default = "default_suggestion_interval_hours")]
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub interval_hours: u64,
+}
+
+impl Default for RuleSuggestionsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_approvals: default_suggestion_min_approvals(),
+            days: default_suggestion_days(),
+            interval_hours: default_suggestion_interval_hours(),
         }
     }
 }
 
-fn default_primary_messenger() -> String {
-    "telegram".to_string()
+fn default_suggestion_min_approvals() -> usize {
+    10
 }
 
-fn default_timeout_seconds() -> u64 {
+fn default_suggestion_days() -> u64 {
+    30
+}
+
+fn default_suggestion_interval_hours() -> u64 {
+    24
+}
+
+fn default_grouping_threshold() -> usize {
+    3
+}
+
+fn default_grouping_window() -> u64 {
+    5
+}
+
+/// Alerts for tool executions that run longer than a threshold.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct LongRunningToolConfig {
+    /// Track tool durations from PreToolUse/PostToolUse hooks
+    #[serde(default)]
+    pub enabled: bool,
+    /// Alert once a single tool call has been running this long
+    #[serde(default = "default_long_running_threshold")]
+    pub threshold_seconds: u64,
+}
+
+impl Default for LongRunningToolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_seconds: default_long_running_threshold(),
+        }
+    }
+}
+
+fn default_long_running_threshold() -> u64 {
+    600
+}
+
+/// Alerts when a session's estimated spend passes a threshold.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CostGuardConfig {
+    /// Track token usage from transcripts in PostToolUse hooks
+    #[serde(default)]
+    pub enabled: bool,
+    /// Estimated USD spend that triggers an alert, again at each multiple
+    #[serde(default = "default_cost_threshold")]
+    pub threshold_usd: f64,
+    /// Hold the session until continuing is confirmed in chat
+    #[serde(default)]
+    pub confirm: bool,
+    /// USD prices per million tokens by part of the model name, before the
+    /// built-in ones
+    #[serde(default)]
+    pub prices: BTreeMap<String, ModelPrice>,
+}
+
+impl Default for CostGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_usd: default_cost_threshold(),
+            confirm: false,
+            prices: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_cost_threshold() -> f64 {
+    5.0
+}
+
+/// Prices of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, JsonSchema)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+/// Masking of secrets in content forwarded to messengers.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct RedactionConfig {
+    /// Mask built-in secret patterns (AWS keys, bearer tokens, `password=`, ...)
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Extra regexes to mask; a `secret` capture group limits the mask to that part
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// Tamper-evident log of every permission request and decision.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct AuditLogConfig {
+    /// Append requests and decisions to a hash-chained JSONL file
+    #[serde(default)]
+    pub enabled: bool,
+    /// Log file location (default: `audit.jsonl` in the state directory)
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl AuditLogConfig {
+    /// Resolved location of the audit log.
+    pub fn path(&self) -> PathBuf {
+        self.path
+            .as_deref()
+            .map(expand_home)
+            .unwrap_or_else(default_audit_log_path)
+    }
+}
+
+/// LLM provider used to summarize sessions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProvider {
+    /// Anthropic Messages API
+    #[default]
+    Anthropic,
+    /// Any OpenAI-compatible chat completions endpoint (e.g. a local model server)
+    Openai,
+}
+
+/// Optional LLM-generated session summaries for completion messages.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct LlmSummaryConfig {
+    /// Summarize the transcript instead of quoting the last assistant message
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub provider: LlmProvider,
+    /// Base URL override (defaults to the provider's public API)
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default = "default_summary_model")]
+    pub model: String,
+    /// API key; falls back to the environment variable named by `api_key_env`
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default = "default_summary_api_key_env")]
+    pub api_key_env: String,
+    /// Number of trailing transcript characters sent to the model
+    #[serde(default = "default_summary_max_chars")]
+    pub max_transcript_chars: usize,
+}
+
+impl Default for LlmSummaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: LlmProvider::default(),
+            endpoint: None,
+            model: default_summary_model(),
+            api_key: None,
+            api_key_env: default_summary_api_key_env(),
+            max_transcript_chars: default_summary_max_chars(),
+        }
+    }
+}
+
+fn default_summary_model() -> String {
+    "claude-3-5-haiku-latest".to_string()
+}
+
+fn default_summary_api_key_env() -> String {
+    "ANTHROPIC_API_KEY".to_string()
+}
+
+fn default_summary_max_chars() -> usize {
+    8000
+}
+
+/// Where command explanations come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplanationSource {
+    /// Built-in descriptions of common commands, in English
+    #[default]
+    Rules,
+    /// The model configured in `llm_summary`, falling back to the rules
+    Llm,
+}
+
+/// One-line descriptions shown under Bash commands in requests.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct CommandExplanationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub source: ExplanationSource,
+    /// Language the model writes explanations in
+    #[serde(default = "default_explanation_language")]
+    pub language: String,
+}
+
+impl Default for CommandExplanationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: ExplanationSource::default(),
+            language: default_explanation_language(),
+        }
+    }
+}
+
+fn default_explanation_language() -> String {
+    "English".to_string()
+}
+
+/// Messengers in their default fallback order.
+pub const MESSENGER_NAMES: &[&str] = &["telegram", "discord", "signal", "homeassistant"];
+
+fn default_messenger_priority() -> Vec<String> {
+    MESSENGER_NAMES.iter().map(|s| s.to_string()).collect()
+}
+
+/// Resolve the messenger fallback order from preferences.
+///
+/// An explicit `messenger_priority` wins; otherwise the legacy
+/// `primary_messenger` is moved to the front of the default order, which
+/// ends with the plugins.
+fn resolve_messenger_priority(
+    priority: Option<Vec<String>>,
+    primary: Option<String>,
+    plugins: &[PluginConfig],
+) -> Vec<String> {
+    let default_order = default_messenger_priority()
+        .into_iter()
+        .chain(plugins.iter().map(|plugin| plugin.name.clone()));
+    let mut order: Vec<String> = match (priority, primary) {
+        (Some(priority), _) => priority,
+        (None, Some(primary)) => std::iter::once(primary).chain(default_order).collect(),
+        (None, None) => default_order.collect(),
+    };
+
+    let mut seen = std::collections::HashSet::new();
+    order.retain(|name| seen.insert(name.clone()));
+    order
+}
+
+pub fn default_timeout_seconds() -> u64 {
     300
 }
 
@@ -192,9 +1719,14 @@ fn default_timeout_seconds() -> u64 {
 
 /// Telegram configuration.
 #[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
 pub struct TelegramConfig {
     pub bot_token: String,
     pub chat_id: ChatId,
+    /// Forum topic (message thread) to post into
+    pub topic_id: Option<i32>,
+    /// One-line message profile
+    pub compact: bool,
 }
 
 /// Signal configuration.
@@ -207,14 +1739,157 @@ pub struct SignalConfig {
     pub data_path: PathBuf,
 }
 
-/// Discord configuration.
-#[cfg(feature = "discord")]
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-pub struct DiscordConfig {
-    pub enabled: bool,
-    pub bot_token: String,
-    pub user_id: u64,
+/// Discord configuration.
+#[cfg(feature = "discord")]
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub struct DiscordConfig {
+    pub enabled: bool,
+    pub bot_token: String,
+    pub user_id: u64,
+    /// One-line message profile
+    pub compact: bool,
+}
+
+/// A messenger run as an external program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginConfig {
+    /// Name used in `messenger_priority`
+    pub name: String,
+    pub command: PathBuf,
+    /// Passed to the plugin with every call
+    pub settings: Value,
+}
+
+/// A Telegram chat that is sent events but never asked for decisions.
+#[derive(Debug, Clone)]
+pub struct ObserverConfig {
+    pub bot_token: String,
+    pub chat_id: ChatId,
+    /// Forum topic (message thread) to post into
+    pub topic_id: Option<i32>,
+    /// Events to post (empty: all)
+    pub events: Vec<WebhookEvent>,
+}
+
+impl ObserverConfig {
+    /// Whether the observer chat gets `event`.
+    pub fn wants(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// People allowed to answer permission requests, per platform.
+///
+/// An empty list lets anyone who can reach that platform's buttons or chat
+/// answer, as before.
+#[derive(Debug, Clone, Default)]
+#[allow(dead_code)]
+pub struct AuthorizedResponders {
+    /// Telegram user IDs
+    pub telegram: Vec<u64>,
+    /// Discord user IDs
+    pub discord: Vec<u64>,
+    /// Signal account UUIDs
+    pub signal: Vec<String>,
+}
+
+impl AuthorizedResponders {
+    fn from_file(file: AuthorizedRespondersFile) -> Result<Self, ConfigError> {
+        let ids = |values: Vec<UserIdValue>, field: &str| {
+            values
+                .iter()
+                .map(|v| v.to_u64(field))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            telegram: ids(file.telegram, "authorized_responders.telegram")?,
+            discord: ids(file.discord, "authorized_responders.discord")?,
+            signal: file.signal,
+        })
+    }
+}
+
+/// Destination override for projects matching path globs.
+#[derive(Debug, Clone)]
+pub struct ProjectRoute {
+    /// Patterns matched against the project name and full working directory
+    pub projects: Vec<String>,
+    pub telegram_chat_id: Option<ChatId>,
+    pub telegram_topic_id: Option<i32>,
+    #[cfg(feature = "discord")]
+    pub discord_user_id: Option<u64>,
+}
+
+impl ProjectRoute {
+    /// Check whether this route applies to a working directory.
+    pub fn matches(&self, cwd: &str) -> bool {
+        let project = Path::new(cwd)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.projects
+            .iter()
+            .any(|pattern| glob_match(pattern, &project) || glob_match(pattern, cwd))
+    }
+}
+
+/// Time range in which matching always-allowed tools are auto-approved.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct AutoApprovalWindow {
+    /// Tool names the window applies to, with `*`/`?` wildcards (empty: every tool)
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Days the window starts on: "mon".."sun", "weekdays", "weekends" (empty: every day)
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// Start time, "HH:MM"
+    pub start: String,
+    /// End time, "HH:MM"; earlier than `start` for windows past midnight
+    pub end: String,
+    /// UTC offset the times are given in, e.g. "+09:00" (default: UTC)
+    #[serde(default)]
+    pub utc_offset: Option<String>,
+}
+
+/// Extra approvals required for matching tool calls.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct ApprovalRule {
+    /// Tool names the rule applies to, with `*`/`?` wildcards (empty: every tool)
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Patterns matched against the command, file path or URL (empty: any input)
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Number of distinct responders who must press Allow
+    #[serde(default = "default_quorum")]
+    pub require_quorum: usize,
+}
+
+fn default_quorum() -> usize {
+    2
+}
+
+impl ApprovalRule {
+    /// Check whether this rule applies to a tool call.
+    pub fn matches(&self, tool_name: &str, detail: Option<&str>) -> bool {
+        tool_call_matches(&self.tools, &self.patterns, tool_name, detail)
+    }
+}
+
+/// Match a tool call against tool-name and input wildcard lists.
+///
+/// An empty list matches anything; `detail` is the command, file path or URL.
+pub fn tool_call_matches(
+    tools: &[String],
+    patterns: &[String],
+    tool_name: &str,
+    detail: Option<&str>,
+) -> bool {
+    let tool_matches = tools.is_empty() || tools.iter().any(|p| glob_match(p, tool_name));
+    let input_matches = patterns.is_empty()
+        || detail.is_some_and(|detail| patterns.iter().any(|p| glob_match(p, detail)));
+    tool_matches && input_matches
 }
 
 /// Application configuration.
@@ -222,10 +1897,80 @@ pub struct DiscordConfig {
 pub struct Config {
     /// System hostname
     pub hostname: String,
+    /// Host as shown in messages, e.g. "🟦 gpu-box"
+    pub host_label: String,
     /// Request timeout in seconds
     pub timeout_seconds: u64,
-    /// Primary messenger to use ("telegram", "discord", "signal")
-    pub primary_messenger: String,
+    /// Messengers to try, in order ("telegram", "discord", "signal", "homeassistant")
+    pub messenger_priority: Vec<String>,
+    /// Messengers to try instead of `messenger_priority`, by severity
+    pub severity_routes: BTreeMap<Severity, Vec<String>>,
+    /// Filters for Stop hook completion notifications
+    pub stop_notifications: StopNotificationConfig,
+    /// Idle reminder settings for Notification hooks
+    pub idle_nudges: IdleNudgeConfig,
+    /// LLM session summary settings for Stop hooks
+    pub llm_summary: LlmSummaryConfig,
+    /// One-line explanations of Bash commands in requests
+    pub command_explanations: CommandExplanationConfig,
+    /// Long-running tool alert settings
+    pub long_running_tools: LongRunningToolConfig,
+    /// Session cost alert settings
+    pub cost_guard: CostGuardConfig,
+    /// Deny permission requests instead of failing when setup goes wrong
+    pub strict: bool,
+    /// Secret masking for forwarded content
+    pub redaction: RedactionConfig,
+    /// Hash-chained audit log of decisions
+    pub audit_log: AuditLogConfig,
+    /// Hourly caps on always-allow approvals, by tool name pattern
+    pub auto_approval_limits: BTreeMap<String, u32>,
+    /// Times when always-allowed tools may be approved without asking
+    pub auto_approval_windows: Vec<AutoApprovalWindow>,
+    /// Sessions started from chat
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub remote_runs: RemoteRunConfig,
+    /// Endpoints that receive request, decision and session events
+    pub webhooks: Vec<WebhookConfig>,
+    /// GitHub deployment approvals served by the daemon
+    pub github_deployments: GitHubDeploymentConfig,
+    /// Browser approval links served by the daemon
+    pub web_approvals: WebApprovalConfig,
+    /// Bot commands over HTTP, served by the daemon
+    pub command_api: CommandApiConfig,
+    /// Limits on the daemon's queue of auto-approval notices
+    pub notification_queue: NotificationQueueConfig,
+    /// Liveness and readiness endpoints served by the daemon
+    pub health_checks: HealthCheckConfig,
+    /// WebAssembly policy modules, checked in order
+    pub policy_modules: Vec<PathBuf>,
+    /// How requests are decided while `/vacation` is on
+    pub vacation_fallback: VacationFallback,
+    /// Add "Deny & Block" rules to `permissions.deny` in Claude Code settings
+    pub block_in_claude_settings: bool,
+    /// Emoji, labels and fields for tools, by tool name pattern
+    pub tool_display: BTreeMap<String, ToolDisplay>,
+    /// Grouping of rapid-fire permission requests
+    pub request_grouping: RequestGroupingConfig,
+    /// Reminders for requests nobody answers
+    pub escalation: EscalationConfig,
+    /// Characters of Claude's last message shown with each request (0: none)
+    pub request_context_chars: usize,
+    /// Seconds a permission prompt notification waits for its request (0: always send)
+    pub permission_prompt_dedup_seconds: u64,
+    /// Read-only tools allowed without asking
+    pub read_only_tools: ReadOnlyToolsConfig,
+    /// Always-allow suggestions from the decision history
+    pub rule_suggestions: RuleSuggestionsConfig,
+    /// Characters in permission request IDs, 8 to 16
+    pub request_id_length: usize,
+    /// Per-project destination overrides, first match wins
+    pub routes: Vec<ProjectRoute>,
+    /// Who may answer permission requests on each platform
+    #[cfg_attr(not(any(feature = "telegram", feature = "signal")), allow(dead_code))]
+    pub authorized_responders: AuthorizedResponders,
+    /// Tool calls that need more than one approval
+    pub approval_rules: Vec<ApprovalRule>,
     /// Optional Telegram configuration
     pub telegram: Option<TelegramConfig>,
     /// Optional Signal configuration (only with signal feature)
@@ -234,6 +1979,13 @@ pub struct Config {
     /// Optional Discord configuration (only with discord feature)
     #[cfg(feature = "discord")]
     pub discord: Option<DiscordConfig>,
+    /// Optional Home Assistant configuration (only with homeassistant feature)
+    #[cfg(feature = "homeassistant")]
+    pub homeassistant: Option<HomeAssistantConfig>,
+    /// Messengers run as external programs
+    pub plugins: Vec<PluginConfig>,
+    /// Notification-only Telegram chat
+    pub observer: Option<ObserverConfig>,
 }
 
 impl Config {
@@ -241,15 +1993,20 @@ impl Config {
     ///
     /// Search order:
     /// 1. Provided config_path (if any)
-    /// 2. New format: `~/.claude/hook_config.json`
+    /// 2. New format: `~/.claude/hook_config.json` (or under `XDG_CONFIG_HOME`)
     /// 3. Legacy format: `~/.claude/telegram_hook.json`
     /// 4. Environment variables
+    ///
+    /// `CCR_*` environment variables override values from a new-format file.
     pub fn load(config_path: Option<PathBuf>) -> Result<Self, ConfigError> {
-        // If a specific path is provided, use it
+        // If a specific path is provided, it must exist
         if let Some(path) = config_path {
-            if path.exists() {
-                return Self::from_json(&path);
-            }
+            return Self::from_json(&path);
+        }
+
+        // Containers are configured from the environment and secret files
+        if container_mode() {
+            return Self::from_env();
         }
 
         // Try new config format first
@@ -268,6 +2025,79 @@ impl Config {
         Self::from_env()
     }
 
+    /// Apply the first project route matching the working directory.
+    ///
+    /// Returns a copy of the configuration with the routed chat, topic, or
+    /// user substituted into the messenger settings.
+    pub fn for_project(&self, cwd: &str) -> Config {
+        let mut config = self.clone();
+        if cwd.is_empty() {
+            return config;
+        }
+
+        let Some(route) = self.routes.iter().find(|r| r.matches(cwd)) else {
+            return config;
+        };
+
+        if let Some(ref mut telegram) = config.telegram {
+            if let Some(chat_id) = route.telegram_chat_id {
+                telegram.chat_id = chat_id;
+                telegram.topic_id = None;
+            }
+            if route.telegram_topic_id.is_some() {
+                telegram.topic_id = route.telegram_topic_id;
+            }
+        }
+
+        #[cfg(feature = "discord")]
+        if let (Some(ref mut discord), Some(user_id)) = (&mut config.discord, route.discord_user_id)
+        {
+            discord.user_id = user_id;
+        }
+
+        config
+    }
+
+    /// Messengers to try for events of `severity`, in order.
+    ///
+    /// Its entry in `severity_routes` wins; otherwise `messenger_priority`.
+    pub fn messengers_for(&self, severity: Severity) -> &[String] {
+        self.severity_routes
+            .get(&severity)
+            .unwrap_or(&self.messenger_priority)
+    }
+
+    /// Number of distinct approvals a tool call needs (at least 1).
+    ///
+    /// When several rules match, the strictest wins.
+    pub fn required_approvals(&self, tool_name: &str, detail: Option<&str>) -> usize {
+        self.approval_rules
+            .iter()
+            .filter(|rule| rule.matches(tool_name, detail))
+            .map(|rule| rule.require_quorum)
+            .max()
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    /// How to show a tool in messages.
+    ///
+    /// An entry named after the tool wins; otherwise the longest matching
+    /// pattern does.
+    pub fn tool_display(&self, tool_name: &str) -> ToolDisplay {
+        self.tool_display
+            .get(tool_name)
+            .or_else(|| {
+                self.tool_display
+                    .iter()
+                    .filter(|(pattern, _)| glob_match(pattern, tool_name))
+                    .max_by_key(|(pattern, _)| pattern.len())
+                    .map(|(_, display)| display)
+            })
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// Load configuration from a JSON file.
     ///
     /// Automatically detects whether it's the new or legacy format.
@@ -278,8 +2108,12 @@ impl Config {
 
         let content = fs::read_to_string(path)?;
 
-        // Try new format first (has "messengers" key)
-        if let Ok(new_config) = serde_json::from_str::<NewConfigFile>(&content) {
+        // Try new format first (has "messengers" key), with CCR_* overrides
+        let mut root: Value = serde_json::from_str(&content)?;
+        if root.get("messengers").is_some() {
+            apply_env_overrides(&mut root, |name| env::var(name).ok())?;
+        }
+        if let Ok(new_config) = serde_json::from_value::<NewConfigFile>(root) {
             return Self::from_new_format(new_config);
         }
 
@@ -293,17 +2127,18 @@ impl Config {
         let hostname = get_hostname();
 
         // Parse telegram config (optional)
-        let telegram = config
-            .messengers
-            .telegram
-            .filter(|t| t.enabled && !t.bot_token.is_empty())
-            .map(|t| {
-                t.chat_id.to_chat_id().map(|chat_id| TelegramConfig {
-                    bot_token: t.bot_token,
-                    chat_id,
-                })
-            })
-            .transpose()?;
+        let mut telegram = None;
+        if let Some(t) = config.messengers.telegram.filter(|t| t.enabled) {
+            let bot_token = t.token.resolve("telegram")?;
+            if !bot_token.is_empty() {
+                telegram = Some(TelegramConfig {
+                    bot_token,
+                    chat_id: t.chat_id.to_chat_id()?,
+                    topic_id: t.topic_id,
+                    compact: t.compact,
+                });
+            }
+        }
 
         #[cfg(feature = "signal")]
         let signal = config
@@ -316,7 +2151,7 @@ impl Config {
                 device_name: s.device_name,
                 data_path: s
                     .data_path
-                    .map(PathBuf::from)
+                    .map(|path| expand_home(&path))
                     .unwrap_or_else(default_signal_data_path),
             });
 
@@ -326,20 +2161,93 @@ impl Config {
             .discord
             .filter(|d| d.enabled)
             .map(|d| {
-                d.user_id.to_u64().map(|user_id| DiscordConfig {
+                Ok::<_, ConfigError>(DiscordConfig {
                     enabled: d.enabled,
-                    bot_token: d.bot_token,
-                    user_id,
+                    user_id: d.user_id.to_u64()?,
+                    bot_token: d.token.resolve("discord")?,
+                    compact: d.compact,
                 })
             })
             .transpose()?;
 
+        #[cfg(feature = "homeassistant")]
+        let homeassistant = config.messengers.homeassistant.filter(|h| h.enabled);
+
+        // Plugins can't take the name of a built-in messenger
+        let plugins: Vec<PluginConfig> = config
+            .messengers
+            .plugins
+            .into_iter()
+            .filter(|(name, plugin)| plugin.enabled && !MESSENGER_NAMES.contains(&name.as_str()))
+            .map(|(name, plugin)| PluginConfig {
+                command: plugin
+                    .command
+                    .map(|command| expand_home(&command))
+                    .unwrap_or_else(|| default_plugin_dir().join(&name)),
+                name,
+                settings: plugin.settings,
+            })
+            .collect();
+
+        let routes = config
+            .routes
+            .into_iter()
+            .map(|r| {
+                Ok(ProjectRoute {
+                    projects: r.projects,
+                    telegram_chat_id: r.telegram_chat_id.map(|id| id.to_chat_id()).transpose()?,
+                    telegram_topic_id: r.telegram_topic_id,
+                    #[cfg(feature = "discord")]
+                    discord_user_id: r.discord_user_id.map(|id| id.to_u64()).transpose()?,
+                })
+            })
+            .collect::<Result<Vec<_>, ConfigError>>()?;
+
+        // The observer chat must never be one that requests are asked in
+        let observer = match config.messengers.observer.filter(|o| o.enabled) {
+            Some(o) => {
+                let chat_id = o.chat_id.to_chat_id()?;
+                let mut asked_in = telegram
+                    .iter()
+                    .map(|t| t.chat_id)
+                    .chain(routes.iter().filter_map(|r| r.telegram_chat_id));
+                if asked_in.any(|id| id == chat_id) {
+                    return Err(ConfigError::MissingField(
+                        "messengers.observer.chat_id must differ from the chats requests are \
+                         sent to"
+                            .to_string(),
+                    ));
+                }
+                let mut bot_token = o.token.resolve("observer")?;
+                if bot_token.is_empty() {
+                    bot_token =
+                        telegram
+                            .as_ref()
+                            .map(|t| t.bot_token.clone())
+                            .ok_or_else(|| {
+                                ConfigError::MissingField(
+                                    "messengers.observer.bot_token".to_string(),
+                                )
+                            })?;
+                }
+                Some(ObserverConfig {
+                    bot_token,
+                    chat_id,
+                    topic_id: o.topic_id,
+                    events: o.events,
+                })
+            }
+            None => None,
+        };
+
         // Validate that at least one messenger is configured
-        let has_messenger = telegram.is_some();
+        let has_messenger = telegram.is_some() || !plugins.is_empty();
         #[cfg(feature = "discord")]
         let has_messenger = has_messenger || discord.is_some();
         #[cfg(feature = "signal")]
         let has_messenger = has_messenger || signal.is_some();
+        #[cfg(feature = "homeassistant")]
+        let has_messenger = has_messenger || homeassistant.is_some();
 
         if !has_messenger {
             return Err(ConfigError::MissingField(
@@ -348,14 +2256,65 @@ impl Config {
         }
 
         Ok(Self {
+            host_label: host_label(
+                &hostname,
+                config.preferences.host_label.as_deref(),
+                config.preferences.host_emoji.as_deref(),
+            ),
             hostname,
             timeout_seconds: config.preferences.timeout_seconds,
-            primary_messenger: config.preferences.primary_messenger,
+            messenger_priority: resolve_messenger_priority(
+                config.preferences.messenger_priority,
+                config.preferences.primary_messenger,
+                &plugins,
+            ),
+            severity_routes: config.preferences.severity_routes,
+            stop_notifications: config.preferences.stop_notifications,
+            idle_nudges: config.preferences.idle_nudges,
+            llm_summary: config.preferences.llm_summary,
+            command_explanations: config.preferences.command_explanations,
+            long_running_tools: config.preferences.long_running_tools,
+            cost_guard: config.preferences.cost_guard,
+            strict: config.preferences.strict,
+            redaction: config.preferences.redaction,
+            audit_log: config.preferences.audit_log,
+            auto_approval_limits: config.preferences.auto_approval_limits,
+            auto_approval_windows: config.preferences.auto_approval_windows,
+            remote_runs: config.preferences.remote_runs,
+            webhooks: config.preferences.webhooks,
+            github_deployments: config.preferences.github_deployments,
+            web_approvals: config.preferences.web_approvals,
+            command_api: config.preferences.command_api,
+            notification_queue: config.preferences.notification_queue,
+            health_checks: config.preferences.health_checks,
+            policy_modules: config
+                .preferences
+                .policy_modules
+                .iter()
+                .map(|path| expand_home(path))
+                .collect(),
+            vacation_fallback: config.preferences.vacation_fallback,
+            block_in_claude_settings: config.preferences.block_in_claude_settings,
+            tool_display: config.preferences.tool_display,
+            request_grouping: config.preferences.request_grouping,
+            escalation: config.preferences.escalation,
+            request_context_chars: config.preferences.request_context_chars,
+            permission_prompt_dedup_seconds: config.preferences.permission_prompt_dedup_seconds,
+            read_only_tools: config.preferences.read_only_tools,
+            rule_suggestions: config.preferences.rule_suggestions,
+            request_id_length: config.preferences.request_id_length,
+            routes,
+            authorized_responders: AuthorizedResponders::from_file(config.authorized_responders)?,
+            approval_rules: config.approval_rules,
             telegram,
             #[cfg(feature = "signal")]
             signal,
             #[cfg(feature = "discord")]
             discord,
+            #[cfg(feature = "homeassistant")]
+            homeassistant,
+            plugins,
+            observer,
         })
     }
 
@@ -369,24 +2328,73 @@ impl Config {
         let hostname = get_hostname();
 
         Ok(Self {
+            host_label: hostname.clone(),
             hostname,
             timeout_seconds: default_timeout_seconds(),
-            primary_messenger: default_primary_messenger(),
+            messenger_priority: default_messenger_priority(),
+            severity_routes: BTreeMap::new(),
+            stop_notifications: StopNotificationConfig::default(),
+            idle_nudges: IdleNudgeConfig::default(),
+            llm_summary: LlmSummaryConfig::default(),
+            command_explanations: CommandExplanationConfig::default(),
+            long_running_tools: LongRunningToolConfig::default(),
+            cost_guard: CostGuardConfig::default(),
+            strict: false,
+            redaction: RedactionConfig::default(),
+            audit_log: AuditLogConfig::default(),
+            auto_approval_limits: BTreeMap::new(),
+            auto_approval_windows: Vec::new(),
+            remote_runs: RemoteRunConfig::default(),
+            webhooks: Vec::new(),
+            github_deployments: GitHubDeploymentConfig::default(),
+            web_approvals: WebApprovalConfig::default(),
+            command_api: CommandApiConfig::default(),
+            notification_queue: NotificationQueueConfig::default(),
+            health_checks: HealthCheckConfig::default(),
+            policy_modules: Vec::new(),
+            vacation_fallback: VacationFallback::default(),
+            block_in_claude_settings: false,
+            tool_display: BTreeMap::new(),
+            request_grouping: RequestGroupingConfig::default(),
+            escalation: EscalationConfig::default(),
+            request_context_chars: default_request_context_chars(),
+            permission_prompt_dedup_seconds: default_prompt_dedup_seconds(),
+            read_only_tools: ReadOnlyToolsConfig::default(),
+            rule_suggestions: RuleSuggestionsConfig::default(),
+            request_id_length: default_request_id_length(),
+            routes: Vec::new(),
+            authorized_responders: AuthorizedResponders::default(),
+            approval_rules: Vec::new(),
             telegram: Some(TelegramConfig {
                 bot_token: config.telegram_bot_token,
                 chat_id,
+                topic_id: None,
+                compact: false,
             }),
             #[cfg(feature = "signal")]
             signal: None,
             #[cfg(feature = "discord")]
             discord: None,
+            #[cfg(feature = "homeassistant")]
+            homeassistant: None,
+            plugins: Vec::new(),
+            observer: None,
         })
     }
 
     /// Load configuration from environment variables.
+    ///
+    /// Uses the `CCR_*` overrides when any are set, otherwise the legacy
+    /// `TELEGRAM_BOT_TOKEN` / `TELEGRAM_CHAT_ID` pair.
     pub fn from_env() -> Result<Self, ConfigError> {
         // Try to load .env file (silently ignore if not found)
-        let _ = dotenvy::from_path(dirs_config_dir().join(".env"));
+        let _ = dotenvy::from_path(config_file_path(".env"));
+
+        // CCR_* variables describe a full new-format config
+        let mut root = serde_json::json!({ "messengers": {} });
+        if apply_env_overrides(&mut root, |name| env::var(name).ok())? {
+            return Self::from_new_format(serde_json::from_value(root)?);
+        }
 
         let token = env::var("TELEGRAM_BOT_TOKEN")
             .map_err(|_| ConfigError::MissingEnvVar("TELEGRAM_BOT_TOKEN".to_string()))?;
@@ -401,23 +2409,149 @@ impl Config {
         let hostname = get_hostname();
 
         Ok(Self {
+            host_label: hostname.clone(),
             hostname,
             timeout_seconds: default_timeout_seconds(),
-            primary_messenger: default_primary_messenger(),
+            messenger_priority: default_messenger_priority(),
+            severity_routes: BTreeMap::new(),
+            stop_notifications: StopNotificationConfig::default(),
+            idle_nudges: IdleNudgeConfig::default(),
+            llm_summary: LlmSummaryConfig::default(),
+            command_explanations: CommandExplanationConfig::default(),
+            long_running_tools: LongRunningToolConfig::default(),
+            cost_guard: CostGuardConfig::default(),
+            strict: false,
+            redaction: RedactionConfig::default(),
+            audit_log: AuditLogConfig::default(),
+            auto_approval_limits: BTreeMap::new(),
+            auto_approval_windows: Vec::new(),
+            remote_runs: RemoteRunConfig::default(),
+            webhooks: Vec::new(),
+            github_deployments: GitHubDeploymentConfig::default(),
+            web_approvals: WebApprovalConfig::default(),
+            command_api: CommandApiConfig::default(),
+            notification_queue: NotificationQueueConfig::default(),
+            health_checks: HealthCheckConfig::default(),
+            policy_modules: Vec::new(),
+            vacation_fallback: VacationFallback::default(),
+            block_in_claude_settings: false,
+            tool_display: BTreeMap::new(),
+            request_grouping: RequestGroupingConfig::default(),
+            escalation: EscalationConfig::default(),
+            request_context_chars: default_request_context_chars(),
+            permission_prompt_dedup_seconds: default_prompt_dedup_seconds(),
+            read_only_tools: ReadOnlyToolsConfig::default(),
+            rule_suggestions: RuleSuggestionsConfig::default(),
+            request_id_length: default_request_id_length(),
+            routes: Vec::new(),
+            authorized_responders: AuthorizedResponders::default(),
+            approval_rules: Vec::new(),
             telegram: Some(TelegramConfig {
                 bot_token: token,
                 chat_id,
+                topic_id: None,
+                compact: false,
             }),
             #[cfg(feature = "signal")]
             signal: None,
             #[cfg(feature = "discord")]
             discord: None,
+            #[cfg(feature = "homeassistant")]
+            homeassistant: None,
+            plugins: Vec::new(),
+            observer: None,
         })
     }
 }
 
-/// Get system hostname.
+/// Check whether a boolean environment flag such as `CCR_DRY_RUN` is set.
+pub fn env_flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|raw| {
+        matches!(
+            parse_env_value(name, &raw, EnvKind::Bool),
+            Ok(Value::Bool(true))
+        )
+    })
+}
+
+/// Check whether strict mode is requested, even if the config fails to load.
+///
+/// Reads `CCR_STRICT` and `preferences.strict` from the raw config file,
+/// so a broken file can still fail closed.
+pub fn strict_requested(config_path: Option<&Path>) -> bool {
+    if let Ok(raw) = env::var("CCR_STRICT") {
+        return matches!(
+            parse_env_value("CCR_STRICT", &raw, EnvKind::Bool),
+            Ok(Value::Bool(true))
+        );
+    }
+
+    let path = config_path
+        .map(Path::to_path_buf)
+        .unwrap_or_else(default_config_path);
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .and_then(|root| root.pointer("/preferences/strict")?.as_bool())
+        .unwrap_or(false)
+}
+
+/// JSON Schema for the new-format configuration file.
+///
+/// Generated from the same serde types used to parse the file.
+pub fn config_schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(NewConfigFile)).expect("schema serializes")
+}
+
+/// Match text against a simple wildcard pattern.
+///
+/// Supports `*` (any run of characters) and `?` (any single character).
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The host as shown in messages: its label or hostname, after its emoji.
+fn host_label(hostname: &str, label: Option<&str>, emoji: Option<&str>) -> String {
+    let label = label.map(str::trim).filter(|label| !label.is_empty());
+    let label = label.unwrap_or(hostname);
+    match emoji.map(str::trim).filter(|emoji| !emoji.is_empty()) {
+        Some(emoji) => format!("{} {}", emoji, label),
+        None => label.to_string(),
+    }
+}
+
+/// Get the hostname, or the stable name in `CCR_HOSTNAME` for hosts whose
+/// name changes, like pods.
 fn get_hostname() -> String {
+    if let Some(name) = env::var("CCR_HOSTNAME")
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+    {
+        return name;
+    }
     hostname::get()
         .map(|h| h.to_string_lossy().to_string())
         .unwrap_or_else(|_| "unknown".to_string())
@@ -432,6 +2566,19 @@ mod tests {
     // Legacy Format Tests
     // =========================================================================
 
+    #[test]
+    fn test_expand_home() {
+        let home = directories::BaseDirs::new()
+            .unwrap()
+            .home_dir()
+            .to_path_buf();
+        assert_eq!(expand_home("~/x/y.json"), home.join("x/y.json"));
+        assert_eq!(expand_home("/abs/~/x"), PathBuf::from("/abs/~/x"));
+        assert_eq!(expand_home("~user/x"), PathBuf::from("~user/x"));
+        #[cfg(windows)]
+        assert_eq!(expand_home(r"~\x"), home.join("x"));
+    }
+
     #[test]
     fn test_legacy_config_with_string_chat_id() {
         let dir = tempdir().unwrap();
@@ -532,6 +2679,63 @@ mod tests {
         assert_eq!(config.timeout_seconds, 600);
     }
 
+    #[test]
+    fn test_messenger_priority_resolution() {
+        assert_eq!(
+            resolve_messenger_priority(None, None, &[]),
+            vec!["telegram", "discord", "signal", "homeassistant"]
+        );
+        assert_eq!(
+            resolve_messenger_priority(None, Some("discord".to_string()), &[]),
+            vec!["discord", "telegram", "signal", "homeassistant"]
+        );
+        assert_eq!(
+            resolve_messenger_priority(
+                Some(vec!["signal".to_string(), "signal".to_string()]),
+                Some("discord".to_string()),
+                &[]
+            ),
+            vec!["signal"]
+        );
+        let sms = PluginConfig {
+            name: "sms".to_string(),
+            command: PathBuf::from("/opt/sms"),
+            settings: Value::Null,
+        };
+        assert_eq!(
+            resolve_messenger_priority(None, Some("sms".to_string()), &[sms]),
+            vec!["sms", "telegram", "discord", "signal", "homeassistant"]
+        );
+    }
+
+    #[test]
+    fn test_new_config_with_stop_notification_filters() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{
+                "messengers": {
+                    "telegram": {
+                        "bot_token": "token123",
+                        "chat_id": 111222
+                    }
+                },
+                "preferences": {
+                    "stop_notifications": {
+                        "min_duration_seconds": 120,
+                        "projects": ["work-*"]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::from_json(&config_path).unwrap();
+        assert_eq!(config.stop_notifications.min_duration_seconds, 120);
+        assert_eq!(config.stop_notifications.projects, vec!["work-*"]);
+    }
+
     #[test]
     fn test_new_config_missing_telegram() {
         let dir = tempdir().unwrap();
@@ -548,10 +2752,376 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_project_routes() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{
+                "messengers": {
+                    "telegram": {
+                        "bot_token": "token123",
+                        "chat_id": 111
+                    }
+                },
+                "routes": [
+                    {"projects": ["/home/me/work/*"], "telegram_chat_id": "-100222", "telegram_topic_id": 7},
+                    {"projects": ["side-*"], "telegram_topic_id": 9}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::from_json(&config_path).unwrap();
+        assert_eq!(config.routes.len(), 2);
+
+        let work = config.for_project("/home/me/work/api");
+        let telegram = work.telegram.unwrap();
+        assert_eq!(telegram.chat_id, ChatId(-100222));
+        assert_eq!(telegram.topic_id, Some(7));
+
+        let side = config.for_project("/tmp/side-project");
+        let telegram = side.telegram.unwrap();
+        assert_eq!(telegram.chat_id, ChatId(111));
+        assert_eq!(telegram.topic_id, Some(9));
+
+        let other = config.for_project("/home/me/personal");
+        let telegram = other.telegram.unwrap();
+        assert_eq!(telegram.chat_id, ChatId(111));
+        assert_eq!(telegram.topic_id, None);
+    }
+
+    #[test]
+    fn test_observer_chat() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{
+                "messengers": {
+                    "telegram": {"bot_token": "token123", "chat_id": 111},
+                    "observer": {"chat_id": "-100333", "events": ["decision_made"]}
+                }
+            }"#,
+        )
+        .unwrap();
+        let observer = Config::from_json(&config_path).unwrap().observer.unwrap();
+        assert_eq!(observer.bot_token, "token123");
+        assert_eq!(observer.chat_id, ChatId(-100333));
+        assert!(observer.wants(WebhookEvent::DecisionMade));
+        assert!(!observer.wants(WebhookEvent::RequestCreated));
+
+        // Requests are never sent to the observer chat, directly or by a route
+        for config in [
+            r#"{"messengers": {
+                "telegram": {"bot_token": "token123", "chat_id": 111},
+                "observer": {"chat_id": 111}
+            }}"#,
+            r#"{
+                "messengers": {
+                    "telegram": {"bot_token": "token123", "chat_id": 111},
+                    "observer": {"chat_id": -100333}
+                },
+                "routes": [{"projects": ["team-*"], "telegram_chat_id": "-100333"}]
+            }"#,
+        ] {
+            fs::write(&config_path, config).unwrap();
+            assert!(Config::from_json(&config_path).is_err());
+        }
+    }
+
+    #[test]
+    fn test_authorized_responders() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{
+                "messengers": {"telegram": {"bot_token": "token123", "chat_id": 111}},
+                "authorized_responders": {"telegram": [42, "43"]}
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::from_json(&config_path).unwrap();
+        assert_eq!(config.authorized_responders.telegram, vec![42, 43]);
+        assert!(config.authorized_responders.discord.is_empty());
+    }
+
+    #[test]
+    fn test_required_approvals() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{
+                "messengers": {"telegram": {"bot_token": "token123", "chat_id": 111}},
+                "approval_rules": [
+                    {"tools": ["Bash"], "patterns": ["kubectl *"]},
+                    {"patterns": ["*/prod/*"], "require_quorum": 3}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::from_json(&config_path).unwrap();
+        assert_eq!(
+            config.required_approvals("Bash", Some("kubectl delete pod x")),
+            2
+        );
+        assert_eq!(config.required_approvals("Bash", Some("ls")), 1);
+        assert_eq!(config.required_approvals("Bash", None), 1);
+        assert_eq!(
+            config.required_approvals("Edit", Some("/srv/prod/app.yml")),
+            3
+        );
+    }
+
+    #[test]
+    fn test_messengers_for_severity() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{
+                "messengers": {"telegram": {"bot_token": "token123", "chat_id": 111}},
+                "preferences": {
+                    "messenger_priority": ["telegram", "discord"],
+                    "severity_routes": {"high": ["pager", "telegram"], "info": ["discord"]}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::from_json(&config_path).unwrap();
+        assert_eq!(config.messengers_for(Severity::High), ["pager", "telegram"]);
+        assert_eq!(
+            config.messengers_for(Severity::Normal),
+            ["telegram", "discord"]
+        );
+        assert_eq!(config.messengers_for(Severity::Info), ["discord"]);
+    }
+
+    #[test]
+    fn test_tool_display() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{
+                "messengers": {"telegram": {"bot_token": "token123", "chat_id": 111}},
+                "preferences": {"tool_display": {
+                    "mcp__*": {"emoji": "🔌"},
+                    "mcp__github__*": {"emoji": "🐙", "label": "GitHub", "fields": ["title"]},
+                    "mcp__github__merge": {"label": "Merge PR"}
+                }}
+            }"#,
+        )
+        .unwrap();
+
+        let config = Config::from_json(&config_path).unwrap();
+        let display = config.tool_display("mcp__github__create_issue");
+        assert_eq!(display.label.as_deref(), Some("GitHub"));
+        assert_eq!(display.fields, vec!["title"]);
+        assert_eq!(config.tool_display("mcp__github__merge").emoji, None);
+        assert_eq!(
+            config.tool_display("mcp__db__query").emoji.as_deref(),
+            Some("🔌")
+        );
+        assert_eq!(config.tool_display("Bash"), ToolDisplay::default());
+    }
+
+    #[test]
+    fn test_bot_token_from_file_and_command() {
+        let dir = tempdir().unwrap();
+        let token_path = dir.path().join("token");
+        fs::write(&token_path, "file_token\n").unwrap();
+
+        let source = BotTokenSource {
+            bot_token: String::new(),
+            bot_token_file: Some(token_path.to_string_lossy().to_string()),
+            bot_token_command: Some("echo ignored".to_string()),
+        };
+        assert_eq!(source.resolve("telegram").unwrap(), "file_token");
+
+        let source = BotTokenSource {
+            bot_token: String::new(),
+            bot_token_file: None,
+            bot_token_command: Some("echo command_token".to_string()),
+        };
+        assert_eq!(source.resolve("telegram").unwrap(), "command_token");
+
+        let source = BotTokenSource {
+            bot_token: String::new(),
+            bot_token_file: None,
+            bot_token_command: Some("exit 3".to_string()),
+        };
+        assert!(matches!(
+            source.resolve("telegram"),
+            Err(ConfigError::Secret(field, _)) if field == "telegram.bot_token_command"
+        ));
+    }
+
+    #[test]
+    fn test_new_config_with_bot_token_file() {
+        let dir = tempdir().unwrap();
+        let token_path = dir.path().join("token");
+        fs::write(&token_path, "secret").unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            serde_json::json!({
+                "messengers": {
+                    "telegram": {"bot_token_file": token_path, "chat_id": 1}
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let config = Config::from_json(&config_path).unwrap();
+        assert_eq!(config.telegram.unwrap().bot_token, "secret");
+    }
+
+    // =========================================================================
+    // Environment Override Tests
+    // =========================================================================
+
+    #[test]
+    fn test_env_overrides_replace_file_values() {
+        let mut root = serde_json::json!({
+            "messengers": {"telegram": {"bot_token": "file_token", "chat_id": "1"}},
+            "preferences": {"timeout_seconds": 60}
+        });
+        let env: std::collections::HashMap<&str, &str> = [
+            ("CCR_TELEGRAM_BOT_TOKEN", "env_token"),
+            ("CCR_TIMEOUT_SECONDS", "120"),
+            ("CCR_STOP_PROJECTS", "api-*, web"),
+            ("CCR_IDLE_NUDGES_ENABLED", "yes"),
+            ("CCR_HOST_LABEL", "gpu-box"),
+            ("CCR_HOST_EMOJI", "🟦"),
+            ("CCR_HEALTH_CHECKS_LISTEN", "0.0.0.0:8790"),
+        ]
+        .into_iter()
+        .collect();
+
+        let applied =
+            apply_env_overrides(&mut root, |name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert!(applied);
+
+        let config = Config::from_new_format(serde_json::from_value(root).unwrap()).unwrap();
+        let telegram = config.telegram.unwrap();
+        assert_eq!(telegram.bot_token, "env_token");
+        assert_eq!(telegram.chat_id, ChatId(1));
+        assert_eq!(config.timeout_seconds, 120);
+        assert_eq!(config.stop_notifications.projects, vec!["api-*", "web"]);
+        assert!(config.idle_nudges.enabled);
+        assert_eq!(config.host_label, "🟦 gpu-box");
+        assert_eq!(config.health_checks.listen, "0.0.0.0:8790");
+    }
+
+    #[test]
+    fn test_host_label() {
+        assert_eq!(host_label("ip-10-0-0-7", None, None), "ip-10-0-0-7");
+        assert_eq!(
+            host_label("ip-10-0-0-7", None, Some("🟥")),
+            "🟥 ip-10-0-0-7"
+        );
+        assert_eq!(host_label("ip-10-0-0-7", Some("ci"), Some(" ")), "ci");
+    }
+
+    #[test]
+    fn test_env_overrides_build_config_from_scratch() {
+        let mut root = serde_json::json!({ "messengers": {} });
+        let applied = apply_env_overrides(&mut root, |name| match name {
+            "CCR_TELEGRAM_BOT_TOKEN" => Some("token".to_string()),
+            "CCR_TELEGRAM_CHAT_ID" => Some("-100123".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert!(applied);
+
+        let config = Config::from_new_format(serde_json::from_value(root).unwrap()).unwrap();
+        assert_eq!(config.telegram.unwrap().chat_id, ChatId(-100123));
+    }
+
+    #[test]
+    fn test_env_overrides_invalid_value() {
+        let mut root = serde_json::json!({ "messengers": {} });
+        let result = apply_env_overrides(&mut root, |name| {
+            (name == "CCR_TIMEOUT_SECONDS").then(|| "soon".to_string())
+        });
+        assert!(
+            matches!(result, Err(ConfigError::InvalidEnvVar(name, _)) if name == "CCR_TIMEOUT_SECONDS")
+        );
+
+        let mut root = serde_json::json!({ "messengers": {} });
+        assert!(!apply_env_overrides(&mut root, |_| None).unwrap());
+    }
+
     // =========================================================================
     // General Tests
     // =========================================================================
 
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("work-*", "work-api"));
+        assert!(!glob_match("work-*", "personal"));
+        assert!(glob_match("/home/*/src/*", "/home/user/src/app"));
+        assert!(glob_match("app?", "app1"));
+        assert!(!glob_match("app?", "app"));
+        assert!(glob_match("exact", "exact"));
+    }
+
+    #[test]
+    fn test_resolve_base_path_prefers_existing_legacy_file() {
+        let dir = tempdir().unwrap();
+        let legacy_dir = dir.path().join(".claude");
+        let xdg = dir.path().join("xdg");
+
+        assert_eq!(
+            resolve_base_path(&legacy_dir, None, "hook_config.json"),
+            legacy_dir.join("hook_config.json")
+        );
+        assert_eq!(
+            resolve_base_path(&legacy_dir, Some(xdg.clone()), "hook_config.json"),
+            xdg.join("claude-code-telegram").join("hook_config.json")
+        );
+
+        fs::create_dir_all(&legacy_dir).unwrap();
+        fs::write(legacy_dir.join("hook_config.json"), "{}").unwrap();
+        assert_eq!(
+            resolve_base_path(&legacy_dir, Some(xdg), "hook_config.json"),
+            legacy_dir.join("hook_config.json")
+        );
+    }
+
+    #[test]
+    fn test_strict_requested_from_broken_config() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{"messengers": {}, "preferences": {"strict": true}}"#,
+        )
+        .unwrap();
+
+        assert!(Config::from_json(&config_path).is_err());
+        assert!(strict_requested(Some(&config_path)));
+        assert!(!strict_requested(Some(&dir.path().join("missing.json"))));
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let schema = config_schema();
+        assert_eq!(schema["title"], "NewConfigFile");
+        assert_eq!(schema["required"], serde_json::json!(["messengers"]));
+        assert!(schema["properties"]["preferences"].is_object());
+        assert!(schema["definitions"]["TelegramConfigFile"]["properties"]["topic_id"].is_object());
+    }
+
     #[test]
     fn test_config_file_not_found() {
         let result = Config::from_json(Path::new("/nonexistent/path.json"));
@@ -0,0 +1,483 @@
+//! Alerts when a session's estimated spend passes a threshold.
+//!
+//! PostToolUse reads the token usage Claude Code records in the session
+//! transcript, picking up where the last call left off, and prices it by
+//! model. Each time the estimate passes another `threshold_usd`, an alert goes
+//! to the primary messenger. With `confirm` set the session is also held: its
+//! next tool call (PreToolUse) waits for a yes/no answer in chat and is
+//! denied if the answer is no or doesn't come. Prices are list prices, so the
+//! result is an estimate.
+
+use crate::clock::now_secs;
+use crate::config::{default_cost_guard_path, Config, CostGuardConfig, ModelPrice, Severity};
+use crate::lockfile::FileLock;
+use crate::messenger::{available_messengers, notify_primary, Decision};
+use crate::tool_timer::project_name;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Sessions not seen for this long are forgotten.
+const FORGET_AFTER_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Built-in USD prices per million input and output tokens, by part of the
+/// model name. The first match wins, so more specific names come first.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("opus-4-5", 5.0, 25.0),
+    ("opus", 15.0, 75.0),
+    ("sonnet", 3.0, 15.0),
+    ("haiku-4-5", 1.0, 5.0),
+    ("3-5-haiku", 0.8, 4.0),
+    ("haiku", 0.25, 1.25),
+];
+
+/// Prices of models not in the table, at the Sonnet rate.
+const DEFAULT_PRICE: (f64, f64) = (3.0, 15.0);
+
+/// Input and output prices of a model, configured ones first.
+fn price(model: &str, prices: &BTreeMap<String, ModelPrice>) -> (f64, f64) {
+    prices
+        .iter()
+        .find(|(name, _)| model.contains(name.as_str()))
+        .map(|(_, price)| (price.input, price.output))
+        .or_else(|| {
+            PRICES
+                .iter()
+                .find(|(name, _, _)| model.contains(name))
+                .map(|(_, input, output)| (*input, *output))
+        })
+        .unwrap_or(DEFAULT_PRICE)
+}
+
+/// Estimated cost of one assistant message's usage.
+///
+/// Cache writes cost 1.25 times the input price and cache reads a tenth.
+fn message_cost(usage: &Value, model: &str, prices: &BTreeMap<String, ModelPrice>) -> f64 {
+    let (input, output) = price(model, prices);
+    let tokens = |key: &str| usage.get(key).and_then(Value::as_u64).unwrap_or_default() as f64;
+    (tokens("input_tokens") * input
+        + tokens("cache_creation_input_tokens") * input * 1.25
+        + tokens("cache_read_input_tokens") * input * 0.1
+        + tokens("output_tokens") * output)
+        / 1_000_000.0
+}
+
+/// What is known about one session's spend.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SessionCost {
+    /// Transcript bytes read so far
+    #[serde(default)]
+    offset: u64,
+    /// Cost of the messages read, except the last
+    #[serde(default)]
+    settled: f64,
+    /// ID and cost of the last message read, which later lines may repeat
+    #[serde(default)]
+    last_message: Option<(String, f64)>,
+    /// Thresholds passed and alerted about
+    #[serde(default)]
+    alerted: u32,
+    /// Whether the next tool call waits for confirmation
+    #[serde(default)]
+    held: bool,
+    /// Unix time confirmation was asked for, while it is open
+    #[serde(default)]
+    asking_since: Option<u64>,
+    /// Unix time of the last update
+    #[serde(default)]
+    updated: u64,
+}
+
+impl SessionCost {
+    fn total(&self) -> f64 {
+        self.settled + self.last_message.as_ref().map_or(0.0, |(_, cost)| *cost)
+    }
+
+    /// Read the transcript lines written since the last read.
+    fn read(&mut self, transcript: &Path, prices: &BTreeMap<String, ModelPrice>) -> io::Result<()> {
+        let mut file = File::open(transcript)?;
+        // A transcript shorter than what was read has been rewritten
+        if file.metadata()?.len() < self.offset {
+            self.offset = 0;
+            self.settled = 0.0;
+            self.last_message = None;
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            // Leave a line still being written for the next read
+            if read == 0 || !line.ends_with('\n') {
+                break;
+            }
+            self.offset += read as u64;
+
+            let Ok(entry) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            let Some(message) = entry.get("message") else {
+                continue;
+            };
+            let Some(usage) = message.get("usage") else {
+                continue;
+            };
+            let model = message
+                .get("model")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let id = message
+                .get("id")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let cost = message_cost(usage, model, prices);
+
+            // Each content block of a message is a line repeating its usage
+            let repeated = matches!(
+                &self.last_message,
+                Some((last_id, _)) if !id.is_empty() && last_id == id
+            );
+            if !repeated {
+                if let Some((_, last_cost)) = self.last_message.take() {
+                    self.settled += last_cost;
+                }
+            }
+            self.last_message = Some((id.to_string(), cost));
+        }
+        Ok(())
+    }
+
+    /// Count the thresholds passed, returning the count if it grew.
+    fn passed(&mut self, threshold: f64) -> Option<u32> {
+        if threshold <= 0.0 {
+            return None;
+        }
+        let passed = (self.total() / threshold).floor() as u32;
+        (passed > self.alerted).then(|| {
+            self.alerted = passed;
+            passed
+        })
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CostData {
+    #[serde(default)]
+    sessions: BTreeMap<String, SessionCost>,
+}
+
+/// Whether a tool call may go ahead in a held session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Hold {
+    /// The session is not held
+    Free,
+    /// This call asks, at the estimated cost
+    Ask(f64),
+    /// Another call is already asking
+    Waiting,
+}
+
+/// Per-session spend, kept in the state directory.
+#[derive(Debug, Clone)]
+struct CostGuard {
+    storage_path: PathBuf,
+}
+
+impl CostGuard {
+    fn new(storage_path: Option<PathBuf>) -> Self {
+        Self {
+            storage_path: storage_path.unwrap_or_else(default_cost_guard_path),
+        }
+    }
+
+    fn load(&self) -> CostData {
+        fs::read_to_string(&self.storage_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Read, change and write the state while holding its lock.
+    fn update<T>(&self, change: impl FnOnce(&mut CostData) -> T) -> io::Result<T> {
+        if let Some(parent) = self.storage_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _lock = FileLock::acquire(&self.storage_path)?;
+        let mut data = self.load();
+        let result = change(&mut data);
+        fs::write(&self.storage_path, serde_json::to_string_pretty(&data)?)?;
+        Ok(result)
+    }
+
+    /// Add a session's new usage, returning its estimated cost and the
+    /// number of thresholds passed if another one was.
+    ///
+    /// Passing one holds the session when `confirm` is set.
+    fn track(
+        &self,
+        settings: &CostGuardConfig,
+        session_id: &str,
+        transcript: &Path,
+    ) -> io::Result<(f64, Option<u32>)> {
+        let now = now_secs();
+        self.update(|data| {
+            data.sessions
+                .retain(|_, session| now.saturating_sub(session.updated) < FORGET_AFTER_SECS);
+            let session = data.sessions.entry(session_id.to_string()).or_default();
+            session.updated = now;
+            session.read(transcript, &settings.prices)?;
+            let passed = session.passed(settings.threshold_usd);
+            if passed.is_some() && settings.confirm {
+                session.held = true;
+            }
+            Ok((session.total(), passed))
+        })?
+    }
+
+    /// Start asking whether a held session may go on, unless another call
+    /// asked less than `timeout` ago.
+    fn begin_confirmation(&self, session_id: &str, timeout: Duration) -> io::Result<Hold> {
+        let now = now_secs();
+        self.update(|data| {
+            let Some(session) = data.sessions.get_mut(session_id) else {
+                return Hold::Free;
+            };
+            if !session.held {
+                return Hold::Free;
+            }
+            let asking = session
+                .asking_since
+                .is_some_and(|since| now.saturating_sub(since) < timeout.as_secs());
+            if asking {
+                return Hold::Waiting;
+            }
+            session.asking_since = Some(now);
+            Hold::Ask(session.total())
+        })
+    }
+
+    /// Record the answer, releasing the session if it may go on.
+    fn end_confirmation(&self, session_id: &str, allowed: bool) -> io::Result<()> {
+        self.update(|data| {
+            if let Some(session) = data.sessions.get_mut(session_id) {
+                session.asking_since = None;
+                session.held = session.held && !allowed;
+            }
+        })
+    }
+}
+
+/// Start of a session ID, as shown in messages.
+fn short_session(session_id: &str) -> &str {
+    session_id.get(..8).unwrap_or(session_id)
+}
+
+/// Format the alert sent when a session passes another threshold.
+fn format_alert(config: &Config, session_id: &str, cwd: &str, cost: f64, threshold: f64) -> String {
+    let mut lines = vec![
+        "💸 **Cost Guard**".to_string(),
+        format!(
+            "Session {} on host {} has used about ${:.2} (threshold ${:.2})",
+            short_session(session_id),
+            config.host_label,
+            cost,
+            threshold
+        ),
+    ];
+    if !cwd.is_empty() {
+        lines.push(format!("📁 **Project:** {}", project_name(cwd)));
+    }
+    if config.cost_guard.confirm {
+        lines.push("Its next tool call waits for you to confirm it may continue.".to_string());
+    }
+    lines.join("\n")
+}
+
+/// After a tool call, add the session's new usage and alert if it passed
+/// another threshold.
+pub async fn track(config: &Config, session_id: &str, transcript_path: &str, cwd: &str) {
+    if session_id.is_empty() || transcript_path.is_empty() {
+        return;
+    }
+    let settings = &config.cost_guard;
+    match CostGuard::new(None).track(settings, session_id, Path::new(transcript_path)) {
+        Ok((cost, Some(passed))) => {
+            let threshold = settings.threshold_usd * f64::from(passed);
+            let text = format_alert(config, session_id, cwd, cost, threshold);
+            if let Err(e) = notify_primary(config, &text).await {
+                tracing::warn!("Failed to send cost alert: {}", e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to track session cost: {}", e),
+    }
+}
+
+/// Before a tool call in a held session, ask in chat whether it may go on.
+///
+/// Returns the reason to deny the call, if it may not.
+pub async fn confirm(config: &Config, session_id: &str, cwd: &str) -> Option<String> {
+    if session_id.is_empty() {
+        return None;
+    }
+    let guard = CostGuard::new(None);
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    let cost = match guard.begin_confirmation(session_id, timeout) {
+        Ok(Hold::Free) => return None,
+        Ok(Hold::Waiting) => {
+            return Some(
+                "Waiting for confirmation in chat to continue past the cost threshold".to_string(),
+            )
+        }
+        Ok(Hold::Ask(cost)) => cost,
+        Err(e) => {
+            tracing::warn!("Failed to read session cost: {}", e);
+            return None;
+        }
+    };
+
+    let mut question = format!(
+        "💸 Session {} has used about ${:.2}",
+        short_session(session_id),
+        cost
+    );
+    if !cwd.is_empty() {
+        question.push_str(&format!(" in {}", project_name(cwd)));
+    }
+    question.push_str(". Continue?");
+    let decision = match available_messengers(config, Severity::Normal)
+        .await
+        .into_iter()
+        .next()
+    {
+        Some(messenger) => messenger
+            .send_question(&question, &config.host_label, timeout)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to ask about session cost: {}", e);
+                Decision::Deny
+            }),
+        None => {
+            tracing::warn!("No messenger to confirm session cost; continuing");
+            Decision::Allow
+        }
+    };
+
+    let allowed = decision.to_behavior() == "allow";
+    if let Err(e) = guard.end_confirmation(session_id, allowed) {
+        tracing::warn!("Failed to record cost confirmation: {}", e);
+    }
+    (!allowed).then(|| {
+        "The user did not confirm continuing past the cost threshold; stop and summarize \
+         where things stand"
+            .to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn assistant(id: &str, model: &str, input: u64, output: u64) -> String {
+        format!(
+            "{{\"type\":\"assistant\",\"message\":{{\"id\":\"{}\",\"model\":\"{}\",\
+             \"usage\":{{\"input_tokens\":{},\"output_tokens\":{}}}}}}}\n",
+            id, model, input, output
+        )
+    }
+
+    #[test]
+    fn test_message_cost() {
+        let usage = serde_json::json!({
+            "input_tokens": 1_000_000,
+            "output_tokens": 1_000_000,
+            "cache_creation_input_tokens": 1_000_000,
+            "cache_read_input_tokens": 1_000_000,
+        });
+        let prices = BTreeMap::new();
+        let cost = message_cost(&usage, "claude-sonnet-4-20250514", &prices);
+        assert!((cost - (3.0 + 15.0 + 3.75 + 0.3)).abs() < 1e-9);
+        assert_eq!(price("claude-opus-4-1", &prices), (15.0, 75.0));
+        assert_eq!(price("claude-opus-4-5-20251101", &prices), (5.0, 25.0));
+        assert_eq!(price("some-local-model", &prices), DEFAULT_PRICE);
+
+        let prices = BTreeMap::from([(
+            "opus".to_string(),
+            ModelPrice {
+                input: 1.0,
+                output: 2.0,
+            },
+        )]);
+        assert_eq!(price("claude-opus-4-1", &prices), (1.0, 2.0));
+    }
+
+    #[test]
+    fn test_track_and_confirm() {
+        let dir = tempdir().unwrap();
+        let guard = CostGuard::new(Some(dir.path().join("cost_guard.json")));
+        let transcript = dir.path().join("session.jsonl");
+        let settings = CostGuardConfig {
+            enabled: true,
+            threshold_usd: 1.0,
+            confirm: true,
+            ..CostGuardConfig::default()
+        };
+
+        // $0.60 so far; the repeated line of msg_1 counts once
+        let mut file = File::create(&transcript).unwrap();
+        let line = assistant("msg_1", "claude-sonnet-4", 100_000, 20_000);
+        file.write_all(line.as_bytes()).unwrap();
+        file.write_all(line.as_bytes()).unwrap();
+        file.write_all(b"{\"type\":\"user\"}\n").unwrap();
+        let (cost, passed) = guard.track(&settings, "s1", &transcript).unwrap();
+        assert!((cost - 0.6).abs() < 1e-9);
+        assert_eq!(passed, None);
+        assert_eq!(
+            guard
+                .begin_confirmation("s1", Duration::from_secs(60))
+                .unwrap(),
+            Hold::Free
+        );
+
+        // A half-written line is left for later
+        let line = assistant("msg_2", "claude-sonnet-4", 100_000, 20_000);
+        let (start, rest) = line.split_at(20);
+        file.write_all(start.as_bytes()).unwrap();
+        let (cost, passed) = guard.track(&settings, "s1", &transcript).unwrap();
+        assert!((cost - 0.6).abs() < 1e-9);
+        assert_eq!(passed, None);
+        file.write_all(rest.as_bytes()).unwrap();
+        let (cost, passed) = guard.track(&settings, "s1", &transcript).unwrap();
+        assert!((cost - 1.2).abs() < 1e-9);
+        assert_eq!(passed, Some(1));
+        let (_, passed) = guard.track(&settings, "s1", &transcript).unwrap();
+        assert_eq!(passed, None);
+
+        // Held: one call asks while the others wait, until it is answered
+        let timeout = Duration::from_secs(60);
+        assert!(matches!(
+            guard.begin_confirmation("s1", timeout).unwrap(),
+            Hold::Ask(_)
+        ));
+        assert_eq!(
+            guard.begin_confirmation("s1", timeout).unwrap(),
+            Hold::Waiting
+        );
+        guard.end_confirmation("s1", false).unwrap();
+        assert!(matches!(
+            guard.begin_confirmation("s1", timeout).unwrap(),
+            Hold::Ask(_)
+        ));
+        guard.end_confirmation("s1", true).unwrap();
+        assert_eq!(guard.begin_confirmation("s1", timeout).unwrap(), Hold::Free);
+        assert_eq!(guard.begin_confirmation("s2", timeout).unwrap(), Hold::Free);
+    }
+}
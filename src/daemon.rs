@@ -0,0 +1,376 @@
+//! Pre-warmed daemon that handles permission requests for hook processes.
+//!
+//! Every `hook` invocation is a new process, so each one pays for a TLS
+//! handshake with the messenger before the request is even sent. While
+//! `claude-code-telegram daemon` runs, hook processes pass their input over a
+//! Unix socket in the state directory (a named pipe on Windows, see
+//! [`crate::ipc`]) instead, and the daemon answers with connections it keeps
+//! warm. Without a daemon, hooks work as before.
+//!
+//! A hook process Claude Code terminates sends the daemon a second message
+//! naming its request, so the request is withdrawn from chat as well.
+//! Auto-approval notices are queued and sent in the background, within the
+//! bounds of [`crate::messenger::queue`].
+//!
+//! The daemon also serves GitHub deployment approvals, browser approval
+//! links, bot commands and health checks over HTTP when they are enabled;
+//! see [`crate::github`], [`crate::web_approvals`], [`crate::command_api`]
+//! and [`crate::health`].
+
+use crate::command_api;
+use crate::config::{container_mode, default_daemon_socket_path, Config};
+use crate::error::HookError;
+use crate::github;
+use crate::health;
+use crate::hook_handler;
+use crate::ipc;
+use crate::messenger::{cancellable, queue};
+use crate::web_approvals;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::watch;
+
+/// How long a hook waits to connect before handling the request itself.
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How often the daemon touches the messenger API to keep connections open.
+#[cfg(feature = "telegram")]
+const WARM_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Hook input handed from a hook process to the daemon.
+#[derive(Debug, Serialize, Deserialize)]
+struct DaemonRequest {
+    /// Absolute config path passed to the hook with `--config`
+    #[serde(default)]
+    config: Option<PathBuf>,
+    /// Names the request so the hook can cancel it later
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    input: String,
+    /// A request the hook gave up on, sent instead of input
+    #[serde(default)]
+    cancel: Option<String>,
+}
+
+/// Cancellation senders for the requests being handled, by ID.
+type InFlight = Arc<Mutex<HashMap<String, watch::Sender<bool>>>>;
+
+/// Serialized hook output, or the error that prevented one.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DaemonReply {
+    #[serde(default)]
+    output: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Hand hook input to a running daemon and return its output.
+///
+/// Returns `None` when no daemon is listening, so the caller should handle
+/// the request itself.
+pub async fn forward(config_path: Option<&Path>, input: &str) -> Result<Option<String>, HookError> {
+    forward_to(&default_daemon_socket_path(), config_path, input).await
+}
+
+async fn forward_to(
+    socket: &Path,
+    config_path: Option<&Path>,
+    input: &str,
+) -> Result<Option<String>, HookError> {
+    let mut stream = match tokio::time::timeout(CONNECT_TIMEOUT, ipc::connect(socket)).await {
+        Ok(Ok(stream)) => stream,
+        _ => return Ok(None),
+    };
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let request = DaemonRequest {
+        config: config_path.map(absolute),
+        id: Some(id.clone()),
+        input: input.to_string(),
+        cancel: None,
+    };
+    ipc::send(&mut stream, &request).await?;
+    // Daemons from before line framing read until the end of the stream
+    stream.shutdown().await?;
+
+    let read = async {
+        ipc::receive::<_, DaemonReply>(&mut stream)
+            .await
+            .map_err(|_| HookError::Daemon("connection closed without a reply".to_string()))
+    };
+    let reply = hook_handler::until_terminated(read, || send_cancel(socket, id)).await?;
+    match (reply.output, reply.error) {
+        (Some(output), _) => Ok(Some(output)),
+        (None, error) => Err(HookError::Daemon(
+            error.unwrap_or_else(|| "empty reply".to_string()),
+        )),
+    }
+}
+
+/// Tell the daemon the hook gave up on a request.
+async fn send_cancel(socket: &Path, id: String) {
+    let request = DaemonRequest {
+        config: None,
+        id: None,
+        input: String::new(),
+        cancel: Some(id),
+    };
+    let sent = async {
+        let mut stream = ipc::connect(socket).await?;
+        ipc::send(&mut stream, &request).await?;
+        stream.shutdown().await
+    };
+    if let Err(e) = sent.await {
+        tracing::warn!("Failed to cancel the request in the daemon: {}", e);
+    }
+}
+
+/// Resolve a path against the current directory, since the daemon has its own.
+fn absolute(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .map(|cwd| cwd.join(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Listen for hook requests until interrupted, or on Unix terminated as
+/// container runtimes stop a process.
+pub async fn run(config_path: Option<&Path>) -> Result<()> {
+    let interrupted = async {
+        #[cfg(unix)]
+        if let Ok(mut terminate) =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    run_until(config_path, interrupted).await
+}
+
+/// Listen for hook requests until `shutdown` completes.
+pub async fn run_until(
+    config_path: Option<&Path>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let socket = default_daemon_socket_path();
+    let mut listener = ipc::bind(&socket).await?;
+    let default_config = config_path.map(absolute);
+
+    // Fail now rather than on the first request
+    let config = Config::load(default_config.clone()).context("Failed to load configuration")?;
+    println!("Handling permission requests on {}", socket.display());
+    if container_mode() && std::env::var_os("CCR_HOSTNAME").is_none() {
+        tracing::warn!(
+            "CCR_HOSTNAME is not set; pauses and history for this host won't carry over when its hostname changes"
+        );
+    }
+
+    let github = if config.github_deployments.enabled {
+        let address = &config.github_deployments.listen;
+        let listener = github::bind(address).await?;
+        println!("Handling GitHub deployment webhooks on {}", address);
+        Some(tokio::spawn(github::serve(
+            listener,
+            default_config.clone(),
+        )))
+    } else {
+        None
+    };
+    let links = if config.web_approvals.enabled {
+        let address = &config.web_approvals.listen;
+        let listener = web_approvals::bind(address).await?;
+        println!("Serving approval links on {}", address);
+        Some(tokio::spawn(web_approvals::serve(
+            listener,
+            default_config.clone(),
+        )))
+    } else {
+        None
+    };
+    let commands = if config.command_api.enabled {
+        let address = &config.command_api.listen;
+        let listener = command_api::bind(address).await?;
+        println!("Taking commands on {}", address);
+        Some(tokio::spawn(command_api::serve(
+            listener,
+            default_config.clone(),
+        )))
+    } else {
+        None
+    };
+
+    let health = if config.health_checks.enabled || container_mode() {
+        let address = &config.health_checks.listen;
+        let listener = health::bind(address).await?;
+        println!("Answering health checks on {}", address);
+        Some(tokio::spawn(health::serve(
+            listener,
+            default_config.clone(),
+        )))
+    } else {
+        None
+    };
+
+    #[cfg(feature = "telegram")]
+    let warmer = tokio::spawn(keep_warm(default_config.clone()));
+
+    queue::install(config.notification_queue.capacity);
+    let in_flight = InFlight::default();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                tokio::spawn(serve(accepted?, default_config.clone(), in_flight.clone()));
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    #[cfg(feature = "telegram")]
+    warmer.abort();
+    if let Some(github) = github {
+        github.abort();
+    }
+    if let Some(links) = links {
+        links.abort();
+    }
+    if let Some(commands) = commands {
+        commands.abort();
+    }
+    if let Some(health) = health {
+        health.abort();
+    }
+    ipc::remove(&socket);
+    Ok(())
+}
+
+/// Answer one hook process.
+async fn serve<S>(mut stream: S, default_config: Option<PathBuf>, in_flight: InFlight)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let request: io::Result<DaemonRequest> = ipc::receive(&mut stream).await;
+    let reply = match request {
+        Ok(request) => handle(request, default_config, &in_flight).await,
+        Err(e) => DaemonReply {
+            error: Some(format!("invalid daemon request: {}", e)),
+            ..DaemonReply::default()
+        },
+    };
+    if let Err(e) = ipc::send(&mut stream, &reply).await {
+        tracing::warn!("Failed to answer hook process: {}", e);
+    }
+}
+
+async fn handle(
+    request: DaemonRequest,
+    default_config: Option<PathBuf>,
+    in_flight: &InFlight,
+) -> DaemonReply {
+    if let Some(id) = request.cancel {
+        if let Some(cancel) = in_flight.lock().unwrap().get(&id) {
+            let _ = cancel.send(true);
+        }
+        return DaemonReply::default();
+    }
+
+    let config_path = request.config.or(default_config);
+    let (cancel, cancelled) = watch::channel(false);
+    if let Some(id) = &request.id {
+        in_flight.lock().unwrap().insert(id.clone(), cancel);
+    }
+    let respond = hook_handler::respond(config_path.as_deref(), &request.input);
+    let result = cancellable(cancelled, respond)
+        .await
+        .and_then(|response| Ok(serde_json::to_string(&response)?));
+    if let Some(id) = &request.id {
+        in_flight.lock().unwrap().remove(id);
+    }
+    match result {
+        Ok(output) => DaemonReply {
+            output: Some(output),
+            error: None,
+        },
+        Err(e) => DaemonReply {
+            output: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Call the Telegram API now and then so a pooled connection stays open.
+#[cfg(feature = "telegram")]
+async fn keep_warm(config_path: Option<PathBuf>) {
+    use crate::messenger::clients;
+    use teloxide::prelude::*;
+
+    let mut interval = tokio::time::interval(WARM_INTERVAL);
+    loop {
+        interval.tick().await;
+        let Some(telegram) = Config::load(config_path.clone())
+            .ok()
+            .and_then(|config| config.telegram)
+        else {
+            continue;
+        };
+        let bot = clients::shared().telegram_bot(&telegram.bot_token);
+        if let Err(e) = bot.get_me().await {
+            tracing::debug!("Failed to warm Telegram connection: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_forward_without_daemon() {
+        let dir = tempdir().unwrap();
+        let socket = dir.path().join("daemon.sock");
+        assert!(forward_to(&socket, None, "{}").await.unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_forward_round_trip() {
+        let dir = tempdir().unwrap();
+        let socket = dir.path().join("daemon.sock");
+        let mut listener = ipc::bind(&socket).await.unwrap();
+
+        let daemon = tokio::spawn(async move {
+            let mut stream = listener.accept().await.unwrap();
+            let request: DaemonRequest = ipc::receive(&mut stream).await.unwrap();
+            let reply = DaemonReply {
+                output: Some(format!("echo {}", request.input)),
+                error: None,
+            };
+            ipc::send(&mut stream, &reply).await.unwrap();
+            request.config
+        });
+
+        let output = forward_to(&socket, Some(Path::new("hook.json")), "{}")
+            .await
+            .unwrap();
+        assert_eq!(output.as_deref(), Some("echo {}"));
+        let config = daemon.await.unwrap().unwrap();
+        assert!(config.is_absolute() && config.ends_with("hook.json"));
+
+        // The listener is gone, so its socket is stale and can be replaced
+        let _listener = ipc::bind(&socket).await.unwrap();
+        assert!(ipc::bind(&socket).await.is_err());
+    }
+}
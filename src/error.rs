@@ -20,6 +20,12 @@ pub enum ConfigError {
 
     #[error("Missing environment variable: {0}")]
     MissingEnvVar(String),
+
+    #[error("Invalid value for environment variable {0}: {1}")]
+    InvalidEnvVar(String, String),
+
+    #[error("Failed to resolve {0}: {1}")]
+    Secret(String, String),
 }
 
 /// Errors related to the always-allow manager.
@@ -41,6 +47,7 @@ pub enum HookError {
     #[error("Invalid hook input: {0}")]
     InvalidInput(#[from] serde_json::Error),
 
+    #[cfg(feature = "telegram")]
     #[error("Telegram error: {0}")]
     TelegramError(#[from] teloxide::RequestError),
 
@@ -52,12 +59,38 @@ pub enum HookError {
     #[allow(dead_code)]
     Discord(String),
 
+    #[error("Home Assistant error: {0}")]
+    #[allow(dead_code)]
+    HomeAssistant(String),
+
+    #[error("Plugin {0} error: {1}")]
+    Plugin(String, String),
+
     #[error("Timeout waiting for decision")]
     #[allow(dead_code)]
     Timeout,
 
     #[error("Configuration error: {0}")]
     ConfigError(#[from] ConfigError),
+
+    #[error("Denied by organization policy: {0}")]
+    PolicyDenied(String),
+
+    #[error("Denied by policy module {0}")]
+    ModuleDenied(String),
+
+    #[error("Blocked from chat: {0}")]
+    Blocked(String),
+
+    #[error("Paused from chat: {0}")]
+    Paused(String),
+
+    #[error("Cancelled by Claude Code")]
+    Cancelled,
+
+    #[error("Hook daemon error: {0}")]
+    #[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+    Daemon(String),
 }
 
 /// Errors related to the stop handler.
@@ -69,9 +102,22 @@ pub enum StopError {
     #[error("Invalid hook input: {0}")]
     InvalidInput(#[from] serde_json::Error),
 
-    #[error("Telegram error: {0}")]
-    TelegramError(#[from] teloxide::RequestError),
-
     #[error("Configuration error: {0}")]
     ConfigError(#[from] ConfigError),
+
+    #[error("Delivery failed: {}", .0.join("; "))]
+    DeliveryFailed(Vec<String>),
+}
+
+/// Errors related to LLM session summaries.
+#[derive(Error, Debug)]
+pub enum SummaryError {
+    #[error("Missing API key (set {0})")]
+    MissingApiKey(String),
+
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Unexpected response: {0}")]
+    InvalidResponse(String),
 }
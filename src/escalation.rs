@@ -0,0 +1,134 @@
+//! Escalation of permission requests nobody answers.
+//!
+//! With `preferences.escalation` enabled, a request still unanswered after
+//! `after_fraction` of the timeout is sent again as a new message, which
+//! notifies where an edit would not, optionally mentioning a second person.
+//! Other messengers can be pinged at the same time. The request itself keeps
+//! waiting and times out as usual.
+
+use crate::config::{Config, EscalationConfig};
+use crate::messenger::{build_messenger, Messenger, TextFormat};
+use crate::redact::Redactor;
+use std::future::Future;
+use std::pin::pin;
+use std::time::Duration;
+
+/// How long to wait before escalating, if escalation applies.
+fn escalation_delay(settings: &EscalationConfig, timeout: Duration) -> Option<Duration> {
+    let valid = settings.enabled && settings.after_fraction > 0.0 && settings.after_fraction < 1.0;
+    valid.then(|| timeout.mul_f64(settings.after_fraction))
+}
+
+/// The reminder text, e.g. ``⏰ Still waiting: Bash `ls` on laptop (2m left) @bob``.
+fn reminder_text(
+    settings: &EscalationConfig,
+    label: &str,
+    hostname: &str,
+    left: Duration,
+) -> String {
+    let left = left.as_secs();
+    let left = if left >= 60 {
+        format!("{}m", left / 60)
+    } else {
+        format!("{}s", left)
+    };
+    let mention = settings
+        .mention
+        .as_deref()
+        .map(|mention| format!(" {}", mention))
+        .unwrap_or_default();
+    format!(
+        "⏰ Still waiting: {} on {} ({} left){}",
+        label, hostname, left, mention
+    )
+}
+
+/// Wait for `decide`, escalating once if it takes too long.
+///
+/// `asking` is the messenger the request went to and `label` describes the
+/// tool call.
+pub async fn with_escalation<T>(
+    config: &Config,
+    asking: &dyn Messenger,
+    label: &str,
+    timeout: Duration,
+    decide: impl Future<Output = T>,
+) -> T {
+    let Some(delay) = escalation_delay(&config.escalation, timeout) else {
+        return decide.await;
+    };
+    let mut decide = pin!(decide);
+    tokio::select! {
+        result = &mut decide => return result,
+        _ = tokio::time::sleep(delay) => {}
+    }
+
+    tracing::info!("Request unanswered after {:?}, escalating", delay);
+    let (result, ()) = tokio::join!(decide, escalate(config, asking, label, timeout - delay));
+    result
+}
+
+async fn escalate(config: &Config, asking: &dyn Messenger, label: &str, left: Duration) {
+    let settings = &config.escalation;
+    let text = Redactor::new(&config.redaction).redact(&reminder_text(
+        settings,
+        label,
+        &config.host_label,
+        left,
+    ));
+
+    if settings.remind {
+        if let Err(e) = asking.send_text(&text, TextFormat::Plain).await {
+            tracing::warn!(
+                "Failed to send reminder via {}: {}",
+                asking.platform_name(),
+                e
+            );
+        }
+    }
+    let elsewhere = format!("{} Answer it in {}.", text, asking.platform_name());
+    for messenger in settings
+        .messengers
+        .iter()
+        .filter_map(|name| build_messenger(config, name))
+        .filter(|messenger| messenger.platform_name() != asking.platform_name())
+    {
+        if let Err(e) = messenger.send_notification(&elsewhere).await {
+            tracing::warn!(
+                "Failed to escalate via {}: {}",
+                messenger.platform_name(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escalation_delay_and_text() {
+        let mut settings = EscalationConfig::default();
+        let timeout = Duration::from_secs(300);
+        assert_eq!(escalation_delay(&settings, timeout), None);
+
+        settings.enabled = true;
+        assert_eq!(
+            escalation_delay(&settings, timeout),
+            Some(Duration::from_secs(150))
+        );
+        settings.after_fraction = 1.5;
+        assert_eq!(escalation_delay(&settings, timeout), None);
+
+        assert_eq!(
+            reminder_text(&settings, "Bash `ls`", "laptop", Duration::from_secs(150)),
+            "⏰ Still waiting: Bash `ls` on laptop (2m left)"
+        );
+        settings.mention = Some("@bob".to_string());
+        assert_eq!(
+            reminder_text(&settings, "Bash `ls`", "laptop", Duration::from_secs(45)),
+            "⏰ Still waiting: Bash `ls` on laptop (45s left) @bob"
+        );
+    }
+}
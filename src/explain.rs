@@ -0,0 +1,554 @@
+//! One-line explanations of Bash commands for approvers.
+//!
+//! With `preferences.command_explanations` enabled, Bash requests get a short
+//! plain description under the command, such as "recursively deletes ./build
+//! and installs node modules", for approvers who don't read shell fluently.
+//! Built-in rules cover common commands in English; with `source: "llm"` the
+//! model configured in `llm_summary` writes the description in the configured
+//! language, and the rules are used when it can't be reached.
+
+use crate::config::{CommandExplanationConfig, ExplanationSource, LlmSummaryConfig};
+use crate::summary;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long the model may take, since the request waits for it.
+const LLM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most commands of a compound command described one by one.
+const MAX_PARTS: usize = 4;
+
+/// Most arguments named in a description.
+const MAX_OPERANDS: usize = 3;
+
+/// Longest explanation shown, in characters.
+const MAX_CHARS: usize = 200;
+
+/// Explain a Bash command, if explanations are on.
+///
+/// The command should already be redacted, since it may be sent to a model.
+pub async fn explain(
+    settings: &CommandExplanationConfig,
+    llm: &LlmSummaryConfig,
+    command: &str,
+) -> Option<String> {
+    if !settings.enabled || command.trim().is_empty() {
+        return None;
+    }
+    if settings.source == ExplanationSource::Llm {
+        let prompt = build_prompt(command, &settings.language);
+        match summary::complete(llm, &prompt, LLM_TIMEOUT).await {
+            Ok(text) => return one_line(&text),
+            Err(e) => tracing::warn!("Failed to explain command, using built-in rules: {}", e),
+        }
+    }
+    describe(command)
+}
+
+/// Build the prompt asking the model to explain a command.
+fn build_prompt(command: &str, language: &str) -> String {
+    format!(
+        "Explain what this shell command does in one short plain sentence in {}, for \
+        someone who does not read shell scripts, like \"recursively deletes ./build and \
+        reinstalls node modules\". Mention anything destructive. Answer with the sentence \
+        only, without markdown.\n\n<command>\n{}\n</command>",
+        language, command
+    )
+}
+
+/// The first line of a model's answer, cut to `MAX_CHARS`.
+fn one_line(text: &str) -> Option<String> {
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line.trim_end_matches('.');
+    let mut shown: String = line.chars().take(MAX_CHARS).collect();
+    if shown.len() < line.len() {
+        shown.push('…');
+    }
+    Some(shown)
+}
+
+/// One command of a compound command.
+#[derive(Debug, Default)]
+struct Part {
+    words: Vec<String>,
+    /// Whether its input is piped from the command before
+    piped: bool,
+    /// File its output is written to
+    output: Option<String>,
+}
+
+/// Split a command line into its commands and their words, dropping quotes.
+///
+/// This is a rough reading of shell syntax, good enough to describe
+/// common commands.
+fn split(command: &str) -> Vec<Part> {
+    let mut parts = vec![Part::default()];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = command.chars().peekable();
+
+    fn end_word(parts: &mut [Part], word: &mut String, in_word: &mut bool) {
+        if *in_word {
+            if let Some(part) = parts.last_mut() {
+                part.words.push(std::mem::take(word));
+            }
+            *in_word = false;
+        }
+    }
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '\\') => {
+                if let Some(next) = chars.next().filter(|next| *next != '\n') {
+                    word.push(next);
+                    in_word = true;
+                }
+            }
+            // Part of a redirection such as `2>&1` or `&>`, not a separator
+            (None, '&') if word.ends_with(['>', '<']) || chars.peek() == Some(&'>') => {
+                word.push(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() && c != '\n' => {
+                end_word(&mut parts, &mut word, &mut in_word)
+            }
+            (None, ';' | '\n' | '&' | '|' | '(' | ')') => {
+                end_word(&mut parts, &mut word, &mut in_word);
+                let piped = c == '|' && chars.peek() != Some(&'|');
+                if matches!(c, '&' | '|') && chars.peek() == Some(&c) {
+                    chars.next();
+                }
+                if !parts.last().is_some_and(|part| part.words.is_empty()) {
+                    parts.push(Part::default());
+                }
+                if let Some(part) = parts.last_mut() {
+                    part.piped = piped;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    end_word(&mut parts, &mut word, &mut in_word);
+
+    for part in &mut parts {
+        let mut words = Vec::new();
+        let mut iter = std::mem::take(&mut part.words).into_iter();
+        while let Some(word) = iter.next() {
+            let operator = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '&');
+            if !operator.starts_with(['>', '<']) {
+                words.push(word);
+                continue;
+            }
+            let target = operator.trim_start_matches(['>', '<']);
+            let target = if target.is_empty() {
+                iter.next().unwrap_or_default()
+            } else {
+                target.to_string()
+            };
+            let writes = operator.starts_with('>') && !word.starts_with('2');
+            if writes && !target.starts_with('&') && target != "/dev/null" {
+                part.output = Some(target);
+            }
+        }
+        part.words = words;
+    }
+    parts.retain(|part| !part.words.is_empty());
+    parts
+}
+
+/// Describe a command line, if any of its commands is familiar.
+fn describe(command: &str) -> Option<String> {
+    let parts = split(command);
+    let described: Vec<Option<String>> = parts.iter().map(describe_part).collect();
+    if described.iter().all(Option::is_none) {
+        return None;
+    }
+
+    let mut phrases: Vec<String> = parts
+        .iter()
+        .zip(described)
+        .take(MAX_PARTS)
+        .map(|(part, phrase)| {
+            let first = part.words.first().map(String::as_str).unwrap_or_default();
+            let mut phrase = phrase.unwrap_or_else(|| format!("runs {}", program(first)));
+            if let Some(output) = &part.output {
+                phrase.push_str(&format!(", writing the output to {}", output));
+            }
+            phrase
+        })
+        .collect();
+    if parts.len() > MAX_PARTS {
+        phrases.push(format!("{} more command(s)", parts.len() - MAX_PARTS));
+    }
+    let mut text = match phrases.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => phrases.concat(),
+    };
+    if text.chars().count() > MAX_CHARS {
+        text = text.chars().take(MAX_CHARS).collect();
+        text.push('…');
+    }
+    Some(text)
+}
+
+/// Name of the program a command's first word runs.
+fn program(first: &str) -> String {
+    Path::new(first)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| first.to_string())
+}
+
+/// Whether the arguments include a short flag letter (alone or combined,
+/// as in `-rf`) or a long flag, if it has one.
+fn has_flag(args: &[&str], short: char, long: &str) -> bool {
+    args.iter().any(|arg| {
+        (!long.is_empty() && *arg == long)
+            || (arg.starts_with('-') && !arg.starts_with("--") && arg[1..].contains(short))
+    })
+}
+
+/// Name some arguments, e.g. "a, b and 2 more", or `default` if there are none.
+fn name_list(operands: &[&str], default: &str) -> String {
+    match operands {
+        [] => default.to_string(),
+        [one] => one.to_string(),
+        _ if operands.len() > MAX_OPERANDS => format!(
+            "{} and {} more",
+            operands[..MAX_OPERANDS].join(", "),
+            operands.len() - MAX_OPERANDS
+        ),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// Describe one command, or None if it is unfamiliar.
+fn describe_part(part: &Part) -> Option<String> {
+    let mut words: Vec<&str> = part.words.iter().map(String::as_str).collect();
+    // Environment assignments and wrappers don't change what runs
+    while let Some(first) = words.first() {
+        let assignment = first
+            .split_once('=')
+            .is_some_and(|(name, _)| !name.is_empty() && !name.starts_with('-'));
+        if assignment || matches!(*first, "env" | "time" | "nohup" | "exec" | "command") {
+            words.remove(0);
+        } else {
+            break;
+        }
+    }
+    let as_root = words.first() == Some(&"sudo");
+    if as_root {
+        words.remove(0);
+        while words.first().is_some_and(|word| word.starts_with('-')) {
+            words.remove(0);
+        }
+    }
+    let program = program(words.first().copied().unwrap_or_default());
+    let args = words.get(1..).unwrap_or_default();
+    let operands: Vec<&str> = args
+        .iter()
+        .copied()
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    let sub = operands.first().copied().unwrap_or_default();
+    let sub_operands = operands.get(1..).unwrap_or_default();
+
+    let phrase = match program.as_str() {
+        "rm" if has_flag(args, 'r', "--recursive") || has_flag(args, 'R', "--recursive") => {
+            format!("recursively deletes {}", name_list(&operands, "files"))
+        }
+        "rm" => format!("deletes {}", name_list(&operands, "files")),
+        "rmdir" => format!("deletes the empty directory {}", name_list(&operands, "")),
+        "mkdir" => format!("creates the directory {}", name_list(&operands, "")),
+        "touch" => format!("creates or updates {}", name_list(&operands, "files")),
+        "cp" | "mv" | "ln" if operands.len() >= 2 => {
+            let (last, rest) = operands.split_last()?;
+            let verb = match program.as_str() {
+                "cp" if has_flag(args, 'r', "--recursive") || has_flag(args, 'R', "") => {
+                    "recursively copies"
+                }
+                "cp" => "copies",
+                "mv" => "moves",
+                _ => "links",
+            };
+            format!("{} {} to {}", verb, name_list(rest, ""), last)
+        }
+        "cd" => format!(
+            "changes into {}",
+            name_list(&operands, "the home directory")
+        ),
+        "ls" => format!("lists {}", name_list(&operands, "the current directory")),
+        "pwd" => "shows the current directory".to_string(),
+        "cat" | "less" | "more" | "head" | "tail" | "bat" if !operands.is_empty() => {
+            format!("shows {}", name_list(&operands, ""))
+        }
+        "cat" | "less" | "more" | "head" | "tail" | "bat" => "shows the output".to_string(),
+        "echo" | "printf" => "prints text".to_string(),
+        "grep" | "egrep" | "rg" | "ag" => match operands.split_first() {
+            Some((pattern, files)) if !files.is_empty() => {
+                format!("searches {} for \"{}\"", name_list(files, ""), pattern)
+            }
+            Some((pattern, _)) => format!("searches for \"{}\"", pattern),
+            None => "searches text".to_string(),
+        },
+        "find" => {
+            let dir = args.first().filter(|arg| !arg.starts_with('-'));
+            let dir = dir.copied().unwrap_or(".");
+            let deletes = args.contains(&"-delete")
+                || args
+                    .windows(2)
+                    .any(|pair| pair[0] == "-exec" && pair[1] == "rm");
+            if deletes {
+                format!("deletes matching files under {}", dir)
+            } else {
+                format!("searches for files under {}", dir)
+            }
+        }
+        "sed" | "perl" if has_flag(args, 'i', "--in-place") => {
+            format!("edits {} in place", name_list(sub_operands, "files"))
+        }
+        "sed" | "awk" | "cut" | "tr" | "jq" => "transforms text".to_string(),
+        "sort" => "sorts lines".to_string(),
+        "uniq" => "removes repeated lines".to_string(),
+        "wc" => "counts lines, words and bytes".to_string(),
+        "tee" => format!(
+            "writes the output to {}",
+            name_list(&operands, "the screen")
+        ),
+        "chmod" => format!(
+            "changes the permissions of {}",
+            name_list(sub_operands, "files")
+        ),
+        "chown" => format!("changes the owner of {}", name_list(sub_operands, "files")),
+        "curl" | "wget" => {
+            let url = operands
+                .iter()
+                .find(|operand| operand.contains("://"))
+                .or(operands.first())
+                .copied()
+                .unwrap_or("a URL");
+            let sends = has_flag(args, 'd', "--data") || has_flag(args, 'X', "--request");
+            if sends {
+                format!("sends a request to {}", url)
+            } else {
+                format!("downloads {}", url)
+            }
+        }
+        "sh" | "bash" | "zsh" if has_flag(args, 'c', "") => "runs a shell command".to_string(),
+        "sh" | "bash" | "zsh" if operands.is_empty() && part.piped => {
+            "runs the output as a shell script".to_string()
+        }
+        "sh" | "bash" | "zsh" | "source" | "." if !operands.is_empty() => {
+            format!("runs the script {}", sub)
+        }
+        "python" | "python3" | "node" | "ruby" | "deno" | "bun"
+            if has_flag(args, 'c', "") || has_flag(args, 'e', "--eval") =>
+        {
+            format!("runs inline {} code", program)
+        }
+        "python" | "python3" | "node" | "ruby" if !operands.is_empty() => {
+            format!("runs {} with {}", sub, program)
+        }
+        "git" => describe_git(args, sub, sub_operands),
+        "npm" | "pnpm" | "yarn" => match sub {
+            "install" | "i" | "ci" | "add" if sub_operands.is_empty() => {
+                "installs node modules".to_string()
+            }
+            "" if program == "yarn" => "installs node modules".to_string(),
+            "install" | "i" | "add" => format!("installs {}", name_list(sub_operands, "")),
+            "uninstall" | "remove" | "rm" => format!("removes {}", name_list(sub_operands, "")),
+            "run" => format!("runs the {} script", name_list(sub_operands, "")),
+            "test" | "t" => "runs the tests".to_string(),
+            "start" => "starts the app".to_string(),
+            "publish" => "publishes the package".to_string(),
+            script if program == "yarn" => format!("runs the {} script", script),
+            other => format!("runs {} {}", program, other).trim_end().to_string(),
+        },
+        "npx" => format!("runs {} from npm", operands.first().unwrap_or(&"a package")),
+        "pip" | "pip3" if sub == "install" && has_flag(args, 'r', "--requirement") => {
+            "installs the Python packages listed in a requirements file".to_string()
+        }
+        "pip" | "pip3" if sub == "install" => {
+            format!(
+                "installs the Python packages {}",
+                name_list(sub_operands, "")
+            )
+        }
+        "pip" | "pip3" if sub == "uninstall" => {
+            format!(
+                "removes the Python packages {}",
+                name_list(sub_operands, "")
+            )
+        }
+        "cargo" => match sub {
+            "build" | "b" => "builds the Rust project".to_string(),
+            "test" | "t" => "runs the Rust tests".to_string(),
+            "run" | "r" => "runs the Rust program".to_string(),
+            "check" | "c" => "checks the Rust code for errors".to_string(),
+            "clippy" => "lints the Rust code".to_string(),
+            "fmt" => "formats the Rust code".to_string(),
+            "install" => format!("installs {}", name_list(sub_operands, "a Rust tool")),
+            "add" => format!("adds the dependency {}", name_list(sub_operands, "")),
+            "publish" => "publishes the crate".to_string(),
+            other => format!("runs cargo {}", other).trim_end().to_string(),
+        },
+        "make" => match operands.as_slice() {
+            [] => "builds with make".to_string(),
+            targets => format!("runs the make target {}", name_list(targets, "")),
+        },
+        "docker" | "podman" => match sub {
+            "run" => "runs a container".to_string(),
+            "build" => "builds a container image".to_string(),
+            "rm" => format!("deletes the container {}", name_list(sub_operands, "")),
+            "rmi" => format!("deletes the image {}", name_list(sub_operands, "")),
+            "ps" => "lists containers".to_string(),
+            "compose" => match sub_operands.first().copied() {
+                Some("up") => "starts the compose services".to_string(),
+                Some("down") => "stops and removes the compose services".to_string(),
+                _ => "runs docker compose".to_string(),
+            },
+            other => format!("runs {} {}", program, other).trim_end().to_string(),
+        },
+        "kubectl" => match sub {
+            "apply" => "applies Kubernetes resources".to_string(),
+            "delete" => format!(
+                "deletes the Kubernetes resources {}",
+                name_list(sub_operands, "")
+            ),
+            "get" | "describe" => format!("shows Kubernetes {}", name_list(sub_operands, "")),
+            other => format!("runs kubectl {}", other).trim_end().to_string(),
+        },
+        "kill" | "pkill" | "killall" => format!("stops {}", name_list(&operands, "processes")),
+        "tar" => {
+            let flags = args.first().copied().unwrap_or_default();
+            if flags.contains('x') {
+                "extracts an archive".to_string()
+            } else if flags.contains('c') {
+                "creates an archive".to_string()
+            } else {
+                "works with an archive".to_string()
+            }
+        }
+        "export" => "sets environment variables".to_string(),
+        "sleep" => format!("waits {}", name_list(&operands, "a while")),
+        "xargs" => {
+            let rest = Part {
+                words: args
+                    .iter()
+                    .skip_while(|arg| arg.starts_with('-'))
+                    .map(|word| word.to_string())
+                    .collect(),
+                ..Part::default()
+            };
+            let phrase = describe_part(&rest).unwrap_or_else(|| format!("runs {}", sub));
+            format!("{} for each input line", phrase)
+        }
+        _ => return None,
+    };
+    Some(if as_root {
+        format!("{} as root", phrase)
+    } else {
+        phrase
+    })
+}
+
+/// Describe a git command.
+fn describe_git(args: &[&str], sub: &str, operands: &[&str]) -> String {
+    match sub {
+        "push" if has_flag(args, 'f', "") || args.iter().any(|arg| arg.starts_with("--force")) => {
+            "force-pushes commits, overwriting the remote branch".to_string()
+        }
+        "push" => "pushes commits to the remote".to_string(),
+        "pull" => "pulls changes from the remote".to_string(),
+        "fetch" => "fetches changes from the remote".to_string(),
+        "commit" if has_flag(args, 'a', "--all") => "commits all changes".to_string(),
+        "commit" => "commits the staged changes".to_string(),
+        "add" => format!("stages {}", name_list(operands, "changes")),
+        "status" => "shows the working tree status".to_string(),
+        "diff" => "shows uncommitted changes".to_string(),
+        "log" | "show" => "shows the commit history".to_string(),
+        "checkout" | "switch" if operands.first() == Some(&".") => {
+            "discards uncommitted changes".to_string()
+        }
+        "checkout" | "switch" => format!("switches to {}", name_list(operands, "a branch")),
+        "reset" if args.contains(&"--hard") => "discards all uncommitted changes".to_string(),
+        "reset" => "unstages changes".to_string(),
+        "restore" => format!("discards changes to {}", name_list(operands, "files")),
+        "clean" => "deletes untracked files".to_string(),
+        "clone" => format!("clones {}", operands.first().unwrap_or(&"a repository")),
+        "merge" => format!("merges {}", name_list(operands, "")),
+        "rebase" => format!("rebases onto {}", name_list(operands, "")),
+        "stash" => "stashes uncommitted changes".to_string(),
+        "branch" if has_flag(args, 'd', "--delete") || has_flag(args, 'D', "") => {
+            format!("deletes the branch {}", name_list(operands, ""))
+        }
+        "branch" => "lists or creates branches".to_string(),
+        "" => "runs git".to_string(),
+        other => format!("runs git {}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe("rm -rf ./build && npm install").as_deref(),
+            Some("recursively deletes ./build and installs node modules")
+        );
+        assert_eq!(
+            describe("cd /srv/app; git push --force origin main").as_deref(),
+            Some("changes into /srv/app and force-pushes commits, overwriting the remote branch")
+        );
+        assert_eq!(
+            describe("curl -fsSL https://example.com/install.sh | sudo bash").as_deref(),
+            Some("downloads https://example.com/install.sh and runs the output as a shell script as root")
+        );
+        assert_eq!(
+            describe("FOO=1 cargo test 2>/dev/null > out.txt").as_deref(),
+            Some("runs the Rust tests, writing the output to out.txt")
+        );
+        assert_eq!(
+            describe("grep -rn 'a b' src | mytool").as_deref(),
+            Some("searches src for \"a b\" and runs mytool")
+        );
+        assert_eq!(
+            describe("find . -name '*.tmp' -delete").as_deref(),
+            Some("deletes matching files under .")
+        );
+        assert_eq!(describe("echo \"a && b\"").as_deref(), Some("prints text"));
+        assert_eq!(
+            describe("ls a && ls b && ls c && ls d && ls e").as_deref(),
+            Some("lists a, lists b, lists c, lists d and 1 more command(s)")
+        );
+        assert_eq!(describe("frobnicate --all"), None);
+    }
+
+    #[test]
+    fn test_one_line() {
+        assert_eq!(
+            one_line("\n  Deletes the build folder.\nMore text").as_deref(),
+            Some("Deletes the build folder")
+        );
+        assert_eq!(one_line("  \n"), None);
+        let long = "x".repeat(MAX_CHARS + 10);
+        assert_eq!(one_line(&long).unwrap().chars().count(), MAX_CHARS + 1);
+    }
+}
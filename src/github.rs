@@ -0,0 +1,380 @@
+//! GitHub Actions deployment approvals from chat.
+//!
+//! With `preferences.github_deployments` enabled, `daemon` also listens for
+//! GitHub webhooks and turns deployments waiting on an environment into
+//! permission requests, so the buttons that approve Claude's tool calls also
+//! open CI deploy gates. Two kinds of events are understood:
+//!
+//! - `deployment_review`, sent for environments with required reviewers. The
+//!   decision is submitted through the run's pending deployments API, so the
+//!   token's owner must be one of the reviewers.
+//! - `deployment_protection_rule`, sent to a GitHub App installed as a custom
+//!   deployment protection rule. The decision goes to the event's callback URL
+//!   and needs a token of that App.
+//!
+//! Every delivery must carry a valid `X-Hub-Signature-256` for `secret`.
+
+use crate::client::ApprovalClient;
+use crate::config::Config;
+use crate::http::{
+    hex, hex_matches, hmac_sha256, load_config_for_request, read_request, write_status,
+    HttpRequest, READ_TIMEOUT,
+};
+use crate::messenger::{clients, dry_run, scoped_request_id, Decision, PermissionMessage};
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tokio::net::{TcpListener, TcpStream};
+
+/// GitHub's API rejects requests without a user agent.
+const USER_AGENT: &str = concat!("claude-code-telegram/", env!("CARGO_PKG_VERSION"));
+
+/// A deployment waiting for approval.
+#[derive(Debug, PartialEq)]
+struct Deployment {
+    repository: String,
+    environment: String,
+    git_ref: Option<String>,
+    sha: Option<String>,
+    requested_by: Option<String>,
+    url: Option<String>,
+    review: Review,
+}
+
+/// Where the decision for a deployment is sent.
+#[derive(Debug, PartialEq)]
+enum Review {
+    /// `deployment_protection_rule`: the event's callback URL
+    Callback(String),
+    /// `deployment_review`: the workflow run's pending deployments endpoint
+    PendingDeployments(String),
+}
+
+impl Deployment {
+    /// Read a deployment from a webhook event, if it asks for a review.
+    fn from_event(event: &str, payload: &Value) -> Option<Self> {
+        let text = |pointer: &str| {
+            payload
+                .pointer(pointer)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        if payload.get("action").and_then(Value::as_str) != Some("requested") {
+            return None;
+        }
+        let repository = text("/repository/full_name")?;
+
+        match event {
+            "deployment_protection_rule" => Some(Self {
+                repository,
+                environment: text("/environment")?,
+                git_ref: text("/deployment/ref"),
+                sha: text("/deployment/sha"),
+                requested_by: text("/deployment/creator/login").or_else(|| text("/sender/login")),
+                url: None,
+                review: Review::Callback(text("/deployment_callback_url")?),
+            }),
+            "deployment_review" => {
+                let run_id = payload.pointer("/workflow_run/id")?.as_u64()?;
+                let endpoint = format!(
+                    "{}/actions/runs/{}/pending_deployments",
+                    text("/repository/url")?,
+                    run_id
+                );
+                Some(Self {
+                    repository,
+                    environment: text("/environment")
+                        .or_else(|| text("/workflow_job_run/environment"))?,
+                    git_ref: text("/workflow_run/head_branch"),
+                    sha: text("/workflow_run/head_sha"),
+                    requested_by: text("/requestor/login").or_else(|| text("/sender/login")),
+                    url: text("/workflow_run/html_url"),
+                    review: Review::PendingDeployments(endpoint),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Tool input shown in the permission request.
+    fn details(&self) -> Value {
+        json!({
+            "repository": self.repository,
+            "environment": self.environment,
+            "ref": self.git_ref,
+            "sha": self.sha,
+            "requested_by": self.requested_by,
+            "url": self.url,
+        })
+    }
+}
+
+/// Check an `X-Hub-Signature-256` header against the body.
+fn signature_matches(secret: &str, body: &[u8], header: Option<&str>) -> bool {
+    let Some(signature) = header.and_then(|h| h.trim().strip_prefix("sha256=")) else {
+        return false;
+    };
+    hex_matches(&hex(&hmac_sha256(secret.as_bytes(), body)), signature)
+}
+
+/// Decide the response to a webhook delivery and the deployment to review.
+fn route(request: &HttpRequest, secret: &str) -> ((u16, &'static str), Option<Deployment>) {
+    if request.method != "POST" {
+        return ((405, "Method Not Allowed"), None);
+    }
+    if !signature_matches(secret, &request.body, request.header("x-hub-signature-256")) {
+        return ((401, "Unauthorized"), None);
+    }
+    let Ok(payload) = serde_json::from_slice::<Value>(&request.body) else {
+        return ((400, "Bad Request"), None);
+    };
+    let event = request.header("x-github-event").unwrap_or_default();
+    match Deployment::from_event(event, &payload) {
+        Some(deployment) => ((202, "Accepted"), Some(deployment)),
+        None => ((200, "OK"), None),
+    }
+}
+
+/// Bind the webhook listener, so a busy port fails the daemon at start.
+pub async fn bind(address: &str) -> Result<TcpListener> {
+    TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to listen for GitHub webhooks on {}", address))
+}
+
+/// Accept webhook deliveries until the task is aborted.
+pub async fn serve(listener: TcpListener, config_path: Option<PathBuf>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle(stream, config_path.clone()));
+            }
+            Err(e) => tracing::warn!("Failed to accept GitHub webhook: {}", e),
+        }
+    }
+}
+
+/// Answer one delivery, then ask for a decision if it is a deployment.
+async fn handle(mut stream: TcpStream, config_path: Option<PathBuf>) {
+    let request = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) => {
+            tracing::debug!("Invalid GitHub webhook request: {}", e);
+            return;
+        }
+        Err(_) => return,
+    };
+    let config = match load_config_for_request(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::warn!("Failed to load configuration for GitHub webhook: {}", e);
+            let _ = write_status(&mut stream, (500, "Internal Server Error")).await;
+            return;
+        }
+    };
+    let secret = config.github_deployments.secret.clone().unwrap_or_default();
+    let (status, deployment) = if secret.is_empty() {
+        ((503, "Service Unavailable"), None)
+    } else {
+        route(&request, &secret)
+    };
+    if let Err(e) = write_status(&mut stream, status).await {
+        tracing::debug!("Failed to answer GitHub webhook: {}", e);
+    }
+    if let Some(deployment) = deployment {
+        review(config, deployment).await;
+    }
+}
+
+/// Ask for approval in chat and pass the decision on to GitHub.
+///
+/// If no messenger could be reached the deployment is left waiting, so it
+/// can still be reviewed on GitHub.
+async fn review(config: Config, deployment: Deployment) {
+    let token = config.github_deployments.token.clone().unwrap_or_default();
+    let message = PermissionMessage::new(
+        scoped_request_id(&config.hostname, "github", config.request_id_length),
+        "Deploy".to_string(),
+        config.hostname.clone(),
+        deployment.details(),
+    )
+    .with_host_label(Some(config.host_label.clone()));
+    let decision = match ApprovalClient::new(config) {
+        Ok(client) => client.request_approval(message).await,
+        Err(e) => Err(e),
+    };
+    let approved = match decision {
+        Ok(Decision::Deny | Decision::DenyAndBlock | Decision::Snooze) => false,
+        Ok(_) => true,
+        Err(e) => {
+            tracing::warn!(
+                "Failed to request approval for {} deployment to {}: {}",
+                deployment.repository,
+                deployment.environment,
+                e
+            );
+            return;
+        }
+    };
+    if let Err(e) = submit(&deployment, &token, approved).await {
+        tracing::warn!(
+            "Failed to submit review for {} deployment to {}: {}",
+            deployment.repository,
+            deployment.environment,
+            e
+        );
+    }
+}
+
+/// Approve or reject the deployment on GitHub.
+async fn submit(deployment: &Deployment, token: &str, approved: bool) -> Result<()> {
+    let state = if approved { "approved" } else { "rejected" };
+    if dry_run() {
+        eprintln!(
+            "[dry-run] GitHub review of {} deployment to {}: {}\n",
+            deployment.repository, deployment.environment, state
+        );
+        return Ok(());
+    }
+    let comment = if approved {
+        "Approved from chat"
+    } else {
+        "Rejected from chat or not answered in time"
+    };
+    let client = clients::shared().http();
+    let github = |request: reqwest::RequestBuilder| {
+        request
+            .bearer_auth(token)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", USER_AGENT)
+    };
+
+    let response = match &deployment.review {
+        Review::Callback(url) => {
+            let body = json!({
+                "environment_name": deployment.environment,
+                "state": state,
+                "comment": comment,
+            });
+            github(client.post(url)).json(&body).send().await?
+        }
+        Review::PendingDeployments(url) => {
+            let pending: Value = github(client.get(url))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            let environment_ids: Vec<u64> = pending
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|item| {
+                    item.pointer("/environment/name").and_then(Value::as_str)
+                        == Some(deployment.environment.as_str())
+                })
+                .filter_map(|item| item.pointer("/environment/id").and_then(Value::as_u64))
+                .collect();
+            if environment_ids.is_empty() {
+                bail!("the deployment is no longer waiting for review");
+            }
+            let body = json!({
+                "environment_ids": environment_ids,
+                "state": state,
+                "comment": comment,
+            });
+            github(client.post(url)).json(&body).send().await?
+        }
+    };
+    response.error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed(event: &str, payload: &Value, secret: &str) -> HttpRequest {
+        let body = payload.to_string().into_bytes();
+        let signature = hex(&hmac_sha256(secret.as_bytes(), &body));
+        HttpRequest {
+            method: "POST".to_string(),
+            headers: vec![
+                ("x-github-event".to_string(), event.to_string()),
+                (
+                    "x-hub-signature-256".to_string(),
+                    format!("sha256={}", signature),
+                ),
+            ],
+            body,
+            ..HttpRequest::default()
+        }
+    }
+
+    #[test]
+    fn test_signature() {
+        // Example from GitHub's webhook validation docs
+        let header = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(signature_matches(
+            "It's a Secret to Everybody",
+            b"Hello, World!",
+            Some(header)
+        ));
+        assert!(!signature_matches("other", b"Hello, World!", Some(header)));
+        assert!(!signature_matches(
+            "It's a Secret to Everybody",
+            b"Hello, World!",
+            None
+        ));
+    }
+
+    #[test]
+    fn test_route_deployment_events() {
+        let review = json!({
+            "action": "requested",
+            "environment": "production",
+            "workflow_run": {
+                "id": 42,
+                "head_branch": "main",
+                "head_sha": "abc123",
+                "html_url": "https://github.com/octo/app/actions/runs/42",
+            },
+            "requestor": {"login": "ana"},
+            "repository": {"full_name": "octo/app", "url": "https://api.github.com/repos/octo/app"},
+        });
+        let (status, deployment) = route(&signed("deployment_review", &review, "s3cret"), "s3cret");
+        assert_eq!(status.0, 202);
+        let deployment = deployment.unwrap();
+        assert_eq!(deployment.environment, "production");
+        assert_eq!(deployment.requested_by.as_deref(), Some("ana"));
+        assert_eq!(
+            deployment.review,
+            Review::PendingDeployments(
+                "https://api.github.com/repos/octo/app/actions/runs/42/pending_deployments"
+                    .to_string()
+            )
+        );
+
+        let rule = json!({
+            "action": "requested",
+            "environment": "staging",
+            "deployment_callback_url": "https://api.github.com/repos/octo/app/actions/runs/7/deployment_protection_rule",
+            "deployment": {"ref": "v1.2.0", "sha": "def456", "creator": {"login": "bo"}},
+            "repository": {"full_name": "octo/app"},
+        });
+        let (_, deployment) = route(
+            &signed("deployment_protection_rule", &rule, "s3cret"),
+            "s3cret",
+        );
+        let deployment = deployment.unwrap();
+        assert_eq!(deployment.git_ref.as_deref(), Some("v1.2.0"));
+        assert!(matches!(deployment.review, Review::Callback(_)));
+
+        // Bad signatures and other events don't start a review
+        let (status, deployment) = route(&signed("deployment_review", &review, "wrong"), "s3cret");
+        assert_eq!((status.0, deployment), (401, None));
+        let (status, deployment) =
+            route(&signed("ping", &json!({"zen": "hi"}), "s3cret"), "s3cret");
+        assert_eq!((status.0, deployment), (200, None));
+    }
+}
@@ -0,0 +1,169 @@
+//! Liveness and readiness endpoints for container orchestrators.
+//!
+//! With `preferences.health_checks` enabled, or in a container
+//! (`CCR_CONTAINER=1`), the daemon answers:
+//!
+//! - `GET /healthz` with `200 ok` while it runs;
+//! - `GET /readyz` with `200 ready` once it can take requests, or `503` and
+//!   one line per problem: the configuration fails to load, no messenger is
+//!   enabled, the state directory can't be written, or the shared database
+//!   doesn't answer.
+//!
+//! Neither needs a token, and neither says more than that, so the port can
+//! be left open to the cluster.
+
+use crate::config::{default_request_ledger_path, Config};
+use crate::error::ConfigError;
+use crate::http::{
+    load_config_for_request, read_request, write_response, HttpRequest, READ_TIMEOUT,
+};
+use crate::messenger::enabled_messengers;
+use crate::shared_state;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::net::{TcpListener, TcpStream};
+
+type Status = (u16, &'static str);
+
+const OK: Status = (200, "OK");
+const UNAVAILABLE: Status = (503, "Service Unavailable");
+
+/// Why the daemon can't take requests, if it can't.
+fn problems(config: &Result<Config, ConfigError>, state_dir: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+    match config {
+        Ok(config) if enabled_messengers(config).is_empty() => {
+            problems.push("no messenger is enabled".to_string());
+        }
+        Ok(_) => {}
+        Err(e) => problems.push(format!("configuration: {}", e)),
+    }
+    if let Err(e) = probe_writable(state_dir) {
+        problems.push(format!("state directory {}: {}", state_dir.display(), e));
+    }
+    if let Some(Err(e)) = shared_state::database().map(|database| database.ping()) {
+        problems.push(format!("shared state database: {}", e));
+    }
+    problems
+}
+
+/// Write and remove a file, since a read-only mount still lists as a directory.
+fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(format!(".ready-{}", std::process::id()));
+    fs::write(&probe, b"")?;
+    fs::remove_file(probe)
+}
+
+fn respond(request: &HttpRequest, config_path: Option<PathBuf>) -> (Status, String) {
+    if request.method != "GET" && request.method != "HEAD" {
+        return (
+            (405, "Method Not Allowed"),
+            "Method not allowed".to_string(),
+        );
+    }
+    match request.path() {
+        "/healthz" => (OK, "ok".to_string()),
+        "/readyz" => {
+            let problems = problems(
+                &load_config_for_request(config_path),
+                &default_request_ledger_path(),
+            );
+            if problems.is_empty() {
+                (OK, "ready".to_string())
+            } else {
+                (UNAVAILABLE, problems.join("\n"))
+            }
+        }
+        _ => ((404, "Not Found"), "Not found".to_string()),
+    }
+}
+
+/// Bind the health check listener, so a busy port fails the daemon at start.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn bind(address: &str) -> Result<TcpListener> {
+    TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to listen for health checks on {}", address))
+}
+
+/// Answer health checks until the task is aborted.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn serve(listener: TcpListener, config_path: Option<PathBuf>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle(stream, config_path.clone()));
+            }
+            Err(e) => tracing::warn!("Failed to accept health check: {}", e),
+        }
+    }
+}
+
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+async fn handle(mut stream: TcpStream, config_path: Option<PathBuf>) {
+    let request = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) => {
+            tracing::debug!("Invalid health check request: {}", e);
+            return;
+        }
+        Err(_) => return,
+    };
+    let (status, body) = respond(&request, config_path);
+    if let Err(e) = write_response(&mut stream, status, "text/plain; charset=utf-8", &body).await {
+        tracing::debug!("Failed to answer health check: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config(dir: &Path, json: &str) -> Result<Config, ConfigError> {
+        let path = dir.join("config.json");
+        fs::write(&path, json).unwrap();
+        Config::from_json(&path)
+    }
+
+    #[test]
+    fn test_problems() {
+        let dir = tempdir().unwrap();
+        let state_dir = dir.path().join("state");
+        let ready = config(
+            dir.path(),
+            r#"{"telegram_bot_token":"test_token","telegram_chat_id":"123456"}"#,
+        );
+        assert!(problems(&ready, &state_dir).is_empty());
+        assert!(state_dir.is_dir());
+
+        let missing = Config::from_json(&dir.path().join("missing.json"));
+        let found = problems(&missing, &state_dir);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("configuration: "));
+
+        // A file where the state directory should be can't be written to
+        let blocked = dir.path().join("blocked");
+        fs::write(&blocked, "").unwrap();
+        let found = problems(&ready, &blocked);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].starts_with("state directory "));
+    }
+
+    #[test]
+    fn test_respond() {
+        let request = |method: &str, path: &str| HttpRequest {
+            method: method.to_string(),
+            target: path.to_string(),
+            ..HttpRequest::default()
+        };
+        assert_eq!(
+            respond(&request("GET", "/healthz"), None),
+            (OK, "ok".to_string())
+        );
+        assert_eq!(respond(&request("GET", "/metrics"), None).0 .0, 404);
+        assert_eq!(respond(&request("POST", "/healthz"), None).0 .0, 405);
+    }
+}
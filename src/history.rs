@@ -0,0 +1,737 @@
+//! Activity history of permission requests, decisions and notifications.
+//!
+//! Each event is appended as one JSON line to `history.jsonl` in the state
+//! directory. `logs` prints the most recent entries and can follow new ones;
+//! `history export` flattens permission requests into CSV or JSONL rows.
+//! With a shared database (see [`crate::shared_state`]), entries go there
+//! instead, and every host reads the same history.
+
+use crate::clock::now_secs;
+use crate::config::{default_history_path, Config, RedactionConfig};
+use crate::messenger::dry_run;
+use crate::redact::Redactor;
+use crate::shared_state::{self, SharedState};
+use crate::watch::FileTail;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Size at which the history file is rotated to `history.jsonl.1`.
+const MAX_HISTORY_BYTES: u64 = 1024 * 1024;
+
+/// Days of history summed up by `status` and the bot's /status.
+pub const STATS_DAYS: u64 = 7;
+
+/// Log of history entries in a shared database.
+const LOG: &str = "history";
+
+/// Kind of recorded event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    PermissionRequest,
+    Decision,
+    Notification,
+    Error,
+}
+
+impl EventKind {
+    fn label(self) -> &'static str {
+        match self {
+            EventKind::PermissionRequest => "request",
+            EventKind::Decision => "decision",
+            EventKind::Notification => "notification",
+            EventKind::Error => "error",
+        }
+    }
+}
+
+/// One line of the history log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix time of the event
+    pub time: u64,
+    pub event: EventKind,
+    /// Platform the event went through, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messenger: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub detail: String,
+    /// Tool name of a permission request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Full command, file path or URL of the tool call (already redacted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// "allow", "deny", "deny_block", "snooze", "auto_allow", "timeout",
+    /// "policy_deny", "module_allow", "module_deny", "yolo_allow" or "blocked"
+    /// for decisions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<String>,
+    /// Who answered, when the platform reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub responder: Option<String>,
+    /// Milliseconds from sending the request to the decision
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+impl HistoryEntry {
+    /// Create an entry stamped with the current time.
+    pub fn new(event: EventKind, messenger: Option<&str>, detail: impl Into<String>) -> Self {
+        Self {
+            time: now_secs(),
+            event,
+            messenger: messenger.map(str::to_string),
+            request_id: None,
+            detail: detail.into(),
+            tool: None,
+            command: None,
+            decision: None,
+            responder: None,
+            latency_ms: None,
+        }
+    }
+
+    /// Attach the permission request ID.
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+
+    /// Attach the tool call a permission request or decision is about.
+    pub fn with_tool(mut self, tool: &str, command: Option<&str>) -> Self {
+        self.tool = Some(tool.to_string());
+        self.command = command.map(str::to_string);
+        self
+    }
+
+    /// Attach the outcome of a decision.
+    pub fn with_decision(mut self, decision: &str, responder: Option<String>) -> Self {
+        self.decision = Some(decision.to_string());
+        self.responder = responder;
+        self
+    }
+
+    /// Attach how long the decision took.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency_ms = Some(latency.as_millis() as u64);
+        self
+    }
+}
+
+impl std::fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}  {:<12}  {:<8}  ",
+            format_time(self.time),
+            self.event.label(),
+            self.messenger.as_deref().unwrap_or("-")
+        )?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, "[{}] ", request_id)?;
+        }
+        write!(f, "{}", self.detail)
+    }
+}
+
+/// Format Unix seconds as `YYYY-MM-DD HH:MM:SS` (UTC).
+fn format_time(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // Civil date from days since the Unix epoch (proleptic Gregorian)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Append an entry to a history file, rotating it when it grows too large.
+pub fn append(path: &Path, entry: &HistoryEntry) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::metadata(path).is_ok_and(|m| m.len() > MAX_HISTORY_BYTES) {
+        fs::rename(path, path.with_extension("jsonl.1"))?;
+    }
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())
+}
+
+/// Record an event in the default history file.
+///
+/// Failures are logged and otherwise ignored so history never breaks a hook.
+/// Nothing is recorded in dry-run mode.
+pub fn record(entry: HistoryEntry) {
+    if dry_run() {
+        return;
+    }
+    let recorded = match shared_state::database() {
+        Some(database) => serde_json::to_string(&entry)
+            .map_err(io::Error::from)
+            .and_then(|line| database.append(LOG, &line)),
+        None => append(&default_history_path(), &entry),
+    };
+    if let Err(e) = recorded {
+        tracing::warn!("Failed to record history: {}", e);
+    }
+}
+
+/// Entries in a shared database after the one numbered `after`, with the
+/// number of the last one read.
+fn read_shared(database: &SharedState, after: i64) -> Result<(i64, Vec<HistoryEntry>)> {
+    let lines = database
+        .lines_after(LOG, after)
+        .context("Failed to read history")?;
+    let last = lines.last().map_or(after, |(id, _)| *id);
+    let entries = lines
+        .iter()
+        .filter_map(|(_, line)| serde_json::from_str(line).ok())
+        .collect();
+    Ok((last, entries))
+}
+
+/// Every entry, rotated history included, oldest first.
+fn read_all() -> Vec<HistoryEntry> {
+    if let Some(database) = shared_state::database() {
+        return read_shared(database, 0)
+            .map(|(_, entries)| entries)
+            .unwrap_or_else(|e| {
+                tracing::warn!("{:#}", e);
+                Vec::new()
+            });
+    }
+    let path = default_history_path();
+    let mut entries = read_entries(&path.with_extension("jsonl.1"));
+    entries.extend(read_entries(&path));
+    entries
+}
+
+/// Read every entry of a history file, skipping malformed lines.
+fn read_entries(path: &Path) -> Vec<HistoryEntry> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Read the last `count` entries of a history file, skipping malformed lines.
+pub fn read_last(path: &Path, count: usize) -> Vec<HistoryEntry> {
+    let entries = read_entries(path);
+    entries[entries.len().saturating_sub(count)..].to_vec()
+}
+
+/// Print recent history, then keep printing new entries if `follow` is set.
+pub async fn run_logs(count: usize, follow: bool) -> Result<()> {
+    if let Some(database) = shared_state::database() {
+        return follow_shared(database, count, follow).await;
+    }
+    let path = default_history_path();
+    let entries = read_last(&path, count);
+    if entries.is_empty() && !follow {
+        println!("No activity recorded in {}", path.display());
+        return Ok(());
+    }
+    for entry in &entries {
+        println!("{}", entry);
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut tail = FileTail::at_end(path);
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+        for line in tail.read_new_lines().context("Failed to read history")? {
+            if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
+                println!("{}", entry);
+            }
+        }
+    }
+}
+
+/// `run_logs` for history in a shared database.
+async fn follow_shared(database: &SharedState, count: usize, follow: bool) -> Result<()> {
+    let (mut last, entries) = read_shared(database, 0)?;
+    if entries.is_empty() && !follow {
+        println!("No activity recorded in the shared database");
+        return Ok(());
+    }
+    for entry in &entries[entries.len().saturating_sub(count)..] {
+        println!("{}", entry);
+    }
+    if !follow {
+        return Ok(());
+    }
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+        let (read_up_to, entries) = read_shared(database, last)?;
+        last = read_up_to;
+        for entry in entries {
+            println!("{}", entry);
+        }
+    }
+}
+
+/// Parse a `--since` age such as `90m`, `12h`, `30d` or `2w`.
+pub fn parse_age(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let unit_at = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(unit_at);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("expected an age like 30d, got `{}`", value))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return Err(format!("unknown unit `{}` (use s, m, h, d or w)", unit)),
+    };
+    Ok(Duration::from_secs(amount * unit_secs))
+}
+
+/// Columns of `history export`, in order.
+const EXPORT_COLUMNS: [&str; 8] = [
+    "time",
+    "request_id",
+    "messenger",
+    "tool",
+    "command",
+    "decision",
+    "responder",
+    "latency_ms",
+];
+
+/// One permission request as exported by `history export`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExportRow {
+    /// When the request was sent, as `YYYY-MM-DDTHH:MM:SSZ`
+    pub time: String,
+    pub request_id: String,
+    pub messenger: Option<String>,
+    pub tool: Option<String>,
+    pub command: Option<String>,
+    pub decision: Option<String>,
+    pub responder: Option<String>,
+    pub latency_ms: Option<u64>,
+    #[serde(skip)]
+    pub(crate) started: u64,
+    /// Whether the row starts with a request entry, rather than a policy denial
+    #[serde(skip)]
+    pub(crate) requested: bool,
+}
+
+impl ExportRow {
+    fn csv_line(&self) -> String {
+        let latency = self.latency_ms.map(|ms| ms.to_string());
+        [
+            Some(&self.time),
+            Some(&self.request_id),
+            self.messenger.as_ref(),
+            self.tool.as_ref(),
+            self.command.as_ref(),
+            self.decision.as_ref(),
+            self.responder.as_ref(),
+            latency.as_ref(),
+        ]
+        .iter()
+        .map(|field| csv_field(field.map(String::as_str).unwrap_or_default()))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Split a `describe_tool` label, as recorded by older versions, into tool and command.
+fn split_label(label: &str) -> (String, Option<String>) {
+    match label.split_once(" `") {
+        Some((tool, command)) => (
+            tool.to_string(),
+            Some(command.trim_end_matches('`').to_string()),
+        ),
+        None => (label.to_string(), None),
+    }
+}
+
+/// Flatten history into one row per permission request, oldest first.
+fn export_rows(entries: &[HistoryEntry]) -> Vec<ExportRow> {
+    let mut rows: Vec<ExportRow> = Vec::new();
+    let mut by_id: HashMap<&str, usize> = HashMap::new();
+
+    for entry in entries {
+        let Some(request_id) = entry.request_id.as_deref() else {
+            continue;
+        };
+        let index = match by_id.get(request_id) {
+            Some(&index) if entry.event != EventKind::PermissionRequest => index,
+            // Policy denials have no request entry
+            _ => {
+                let (tool, command) = match &entry.tool {
+                    Some(tool) => (tool.clone(), entry.command.clone()),
+                    None => split_label(entry.detail.split(" → ").next().unwrap_or_default()),
+                };
+                rows.push(ExportRow {
+                    time: format!("{}Z", format_time(entry.time).replacen(' ', "T", 1)),
+                    request_id: request_id.to_string(),
+                    messenger: entry.messenger.clone(),
+                    tool: Some(tool),
+                    command,
+                    started: entry.time,
+                    requested: entry.event == EventKind::PermissionRequest,
+                    ..ExportRow::default()
+                });
+                by_id.insert(request_id, rows.len() - 1);
+                rows.len() - 1
+            }
+        };
+
+        let row = &mut rows[index];
+        match entry.event {
+            EventKind::Decision => {
+                row.decision = entry.decision.clone().or_else(|| {
+                    let (_, outcome) = entry.detail.rsplit_once(" → ")?;
+                    outcome.split_whitespace().next().map(str::to_string)
+                });
+                row.responder = entry.responder.clone();
+                row.latency_ms = entry.latency_ms.or_else(|| {
+                    row.requested
+                        .then(|| entry.time.saturating_sub(row.started) * 1000)
+                });
+            }
+            EventKind::Error => row.decision = Some("error".to_string()),
+            EventKind::PermissionRequest | EventKind::Notification => {}
+        }
+    }
+    rows
+}
+
+/// How quickly and how permission requests were decided.
+#[derive(Debug, Default, PartialEq)]
+pub struct DecisionStats {
+    /// Every decided request, however it was decided
+    pub total: usize,
+    /// Milliseconds to each allow or deny from a responder, sorted
+    answered_ms: Vec<u64>,
+    /// Requests nobody answered in time
+    pub timeouts: usize,
+    /// Requests allowed from the always-allow list or as read-only tools
+    pub auto_approved: usize,
+}
+
+impl DecisionStats {
+    fn from_rows(rows: &[ExportRow]) -> Self {
+        let mut stats = Self::default();
+        for row in rows {
+            let Some(decision) = row.decision.as_deref() else {
+                continue;
+            };
+            stats.total += 1;
+            match decision {
+                "allow" | "deny" | "deny_block" | "snooze" => {
+                    stats.answered_ms.extend(row.latency_ms)
+                }
+                "timeout" => stats.timeouts += 1,
+                "auto_allow" | "read_only_allow" | "module_allow" | "yolo_allow" => {
+                    stats.auto_approved += 1
+                }
+                _ => {}
+            }
+        }
+        stats.answered_ms.sort_unstable();
+        stats
+    }
+
+    /// Time to decide at a percentile between 0 and 100, by nearest rank.
+    fn percentile(&self, percent: usize) -> Option<u64> {
+        let rank = (self.answered_ms.len() * percent).div_ceil(100).max(1);
+        self.answered_ms.get(rank - 1).copied()
+    }
+
+    /// Summary lines for `status` and the bot's /status.
+    pub fn lines(&self) -> Vec<String> {
+        if self.total == 0 {
+            return vec!["No decisions recorded".to_string()];
+        }
+        let share = |part: usize, whole: usize| part * 100 / whole.max(1);
+        let asked = self.answered_ms.len() + self.timeouts;
+        let mut lines = vec![format!("Decisions: {}", self.total)];
+        if let (Some(median), Some(p90)) = (self.percentile(50), self.percentile(90)) {
+            let average = self.answered_ms.iter().sum::<u64>() / self.answered_ms.len() as u64;
+            lines.push(format!(
+                "Time to decide: avg {}, median {}, p90 {}",
+                format_latency(average),
+                format_latency(median),
+                format_latency(p90)
+            ));
+        }
+        lines.push(format!(
+            "Timeouts: {}% ({} of {} asked)",
+            share(self.timeouts, asked),
+            self.timeouts,
+            asked
+        ));
+        lines.push(format!(
+            "Auto-approved: {}% ({} of {})",
+            share(self.auto_approved, self.total),
+            self.auto_approved,
+            self.total
+        ));
+        lines
+    }
+}
+
+/// Format milliseconds as e.g. `8s` or `2m05s`.
+fn format_latency(ms: u64) -> String {
+    let secs = (ms + 500) / 1000;
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Permission requests of the last `window`, oldest first, rotated history included.
+pub fn recent_rows(window: Duration) -> Vec<ExportRow> {
+    let entries = read_all();
+
+    let now = now_secs();
+    let cutoff = now.saturating_sub(window.as_secs());
+    export_rows(&entries)
+        .into_iter()
+        .filter(|row| row.started >= cutoff)
+        .collect()
+}
+
+/// Decision statistics over the requests of the last `window`.
+pub fn decision_stats(window: Duration) -> DecisionStats {
+    DecisionStats::from_rows(&recent_rows(window))
+}
+
+/// Write permission requests from the history as CSV or JSONL to stdout.
+///
+/// Rotated history is included. With `redact`, commands are masked with the
+/// built-in and configured patterns, even if redaction was off when they
+/// were recorded.
+pub fn run_export(
+    config_path: Option<&Path>,
+    format: &str,
+    since: Option<Duration>,
+    redact: bool,
+) -> Result<()> {
+    let entries = read_all();
+
+    let now = now_secs();
+    let cutoff = since.map_or(0, |age| now.saturating_sub(age.as_secs()));
+    let mut rows: Vec<ExportRow> = export_rows(&entries)
+        .into_iter()
+        .filter(|row| row.started >= cutoff)
+        .collect();
+
+    if redact {
+        let config = Config::load(config_path.map(Path::to_path_buf))?;
+        let redactor = Redactor::new(&RedactionConfig {
+            enabled: true,
+            ..config.redaction
+        });
+        for row in &mut rows {
+            row.command = row
+                .command
+                .as_deref()
+                .map(|command| redactor.redact(command));
+        }
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    if format == "csv" {
+        writeln!(out, "{}", EXPORT_COLUMNS.join(","))?;
+    }
+    for row in &rows {
+        match format {
+            "csv" => writeln!(out, "{}", row.csv_line())?,
+            _ => writeln!(out, "{}", serde_json::to_string(row)?)?,
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_append_and_read_last() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+
+        for i in 0..5 {
+            let entry = HistoryEntry::new(EventKind::Notification, Some("Telegram"), i.to_string());
+            append(&path, &entry).unwrap();
+        }
+        fs::write(&path, fs::read_to_string(&path).unwrap() + "not json\n").unwrap();
+
+        let last = read_last(&path, 2);
+        assert_eq!(last.len(), 2);
+        assert_eq!(last[0].detail, "3");
+        assert_eq!(last[1].detail, "4");
+        assert!(read_last(&dir.path().join("missing"), 10).is_empty());
+    }
+
+    #[test]
+    fn test_entry_display() {
+        let mut entry =
+            HistoryEntry::new(EventKind::Decision, Some("Telegram"), "Bash `ls` → allow")
+                .with_request_id("abc12345");
+        entry.time = 1738326896;
+        assert_eq!(
+            entry.to_string(),
+            "2025-01-31 12:34:56  decision      Telegram  [abc12345] Bash `ls` → allow"
+        );
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_age("90m"), Ok(Duration::from_secs(5400)));
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+    }
+
+    #[test]
+    fn test_export_rows() {
+        let at = |entry: HistoryEntry, time: u64| HistoryEntry { time, ..entry };
+        let entries = [
+            // Recorded by an older version: only the label
+            at(
+                HistoryEntry::new(
+                    EventKind::PermissionRequest,
+                    Some("Discord"),
+                    "Bash `ls -la`",
+                )
+                .with_request_id("old00001"),
+                1738326896,
+            ),
+            at(
+                HistoryEntry::new(EventKind::Decision, Some("Discord"), "Bash `ls -la` → deny")
+                    .with_request_id("old00001"),
+                1738326899,
+            ),
+            HistoryEntry::new(
+                EventKind::PermissionRequest,
+                Some("Telegram"),
+                "Edit `a, b`",
+            )
+            .with_request_id("new00002")
+            .with_tool("Edit", Some("a, b")),
+            HistoryEntry::new(EventKind::Notification, Some("Telegram"), "Job done"),
+            HistoryEntry::new(EventKind::Decision, Some("Telegram"), "Edit `a, b` → allow")
+                .with_request_id("new00002")
+                .with_decision("allow", Some("@ana (42)".to_string()))
+                .with_latency(Duration::from_millis(1500)),
+            HistoryEntry::new(EventKind::Decision, None, "Bash `rm -rf /` → deny (policy)")
+                .with_request_id("pol00003")
+                .with_tool("Bash", Some("rm -rf /"))
+                .with_decision("policy_deny", None),
+        ];
+
+        let rows = export_rows(&entries);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].time, "2025-01-31T12:34:56Z");
+        assert_eq!(rows[0].tool.as_deref(), Some("Bash"));
+        assert_eq!(rows[0].command.as_deref(), Some("ls -la"));
+        assert_eq!(rows[0].decision.as_deref(), Some("deny"));
+        assert_eq!(rows[0].latency_ms, Some(3000));
+        assert_eq!(
+            rows[1].csv_line(),
+            format!(
+                "{},new00002,Telegram,Edit,\"a, b\",allow,@ana (42),1500",
+                rows[1].time
+            )
+        );
+        assert_eq!(rows[2].decision.as_deref(), Some("policy_deny"));
+        assert_eq!(rows[2].messenger, None);
+        assert_eq!(rows[2].latency_ms, None);
+    }
+
+    #[test]
+    fn test_decision_stats() {
+        let row = |decision: &str, latency_ms: Option<u64>| ExportRow {
+            decision: Some(decision.to_string()),
+            latency_ms,
+            ..ExportRow::default()
+        };
+        let mut rows: Vec<ExportRow> = [4_000, 10_000, 6_000, 125_000]
+            .into_iter()
+            .map(|ms| row("allow", Some(ms)))
+            .collect();
+        rows.push(row("deny", Some(5_000)));
+        rows.push(row("timeout", Some(300_000)));
+        rows.push(row("auto_allow", None));
+        rows.push(row("read_only_allow", None));
+        rows.push(row("policy_deny", None));
+        rows.push(ExportRow::default());
+
+        let stats = DecisionStats::from_rows(&rows);
+        assert_eq!(stats.total, 9);
+        assert_eq!(
+            stats.lines(),
+            [
+                "Decisions: 9",
+                "Time to decide: avg 30s, median 6s, p90 2m05s",
+                "Timeouts: 16% (1 of 6 asked)",
+                "Auto-approved: 22% (2 of 9)",
+            ]
+        );
+        assert_eq!(DecisionStats::default().lines(), ["No decisions recorded"]);
+    }
+}
@@ -3,17 +3,51 @@
 //! Handles PermissionRequest hook events by sending messages via configured
 //! messenger (Telegram, Signal, Discord) with interactive decision options.
 
-use crate::always_allow::AlwaysAllowManager;
-use crate::config::Config;
+use crate::adapters;
+use crate::always_allow::{allow_entry, AlwaysAllowManager, AutoApproval, BlockRule};
+use crate::audit::{self, AuditEntry, AuditEvent};
+use crate::away::Away;
+use crate::clock::now_secs;
+use crate::config::{
+    default_claude_settings_path, strict_requested, Config, Severity, ToolDisplay, WebhookEvent,
+};
 use crate::error::HookError;
-#[cfg(feature = "discord")]
-use crate::messenger::discord::DiscordMessenger;
-use crate::messenger::telegram::TelegramMessenger;
-use crate::messenger::{Decision, Messenger, PermissionMessage};
+use crate::escalation::with_escalation;
+use crate::explain;
+use crate::history::{self, EventKind, HistoryEntry};
+use crate::install;
+use crate::lockfile::off_reactor;
+use crate::messenger::queue;
+use crate::messenger::vacation::VacationMessenger;
+use crate::messenger::{
+    available_messengers, cancellable, dry_run, new_request_id, notify_primary, scoped_request_id,
+    Decision, Messenger, PermissionMessage, TextFormat,
+};
+use crate::pause::PauseList;
+use crate::policy::Policy;
+use crate::policy_module::{self, Verdict};
+use crate::prompt_dedup::PromptMarks;
+use crate::read_only;
+use crate::redact::Redactor;
+use crate::request_groups::RequestGroups;
+use crate::snooze;
+use crate::stop_handler::last_assistant_message;
+use crate::tool_timer::{describe_tool, tool_detail};
+use crate::web_approvals;
+use crate::webhooks;
+use crate::yolo::YoloWindows;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::io::{self, Read};
-use std::time::Duration;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::sync::watch;
+
+/// How long a request Claude Code gave up on gets to be withdrawn from chat.
+pub(crate) const CANCEL_GRACE: Duration = Duration::from_secs(10);
 
 /// Claude Code hook input for permission requests.
 #[derive(Debug, Deserialize)]
@@ -22,39 +56,180 @@ pub struct HookInput {
     pub tool_name: String,
     #[serde(default)]
     pub tool_input: Value,
+    #[serde(default)]
+    pub cwd: String,
+    #[serde(default)]
+    pub session_id: String,
+    #[serde(default)]
+    pub transcript_path: String,
+    /// Permission updates Claude Code offers to apply along with an approval
+    #[serde(default)]
+    pub permission_suggestions: Vec<Value>,
 }
 
 fn default_tool_name() -> String {
     "unknown".to_string()
 }
 
+/// Parse permission hook input from Claude Code.
+///
+/// Missing fields take their defaults. Input that is not a JSON object, or
+/// has a field of the wrong type, is an error rather than a panic.
+pub fn parse_hook_input(input: &str) -> Result<HookInput, HookError> {
+    Ok(serde_json::from_str(input)?)
+}
+
 /// Permission request with a unique ID.
 #[derive(Debug, Clone)]
 pub struct PermissionRequest {
     pub tool_name: String,
     pub tool_input: Value,
     pub request_id: String,
+    /// Distinct responders who must press Allow
+    pub required_approvals: usize,
+    /// Permission suggestions that can be offered as buttons
+    pub permission_suggestions: Vec<Value>,
+    /// How the tool is shown in messages
+    pub display: ToolDisplay,
+    /// Claude Code session that asked (empty if unknown)
+    pub session_id: String,
+    /// Claude's last message before asking, from the transcript
+    pub context: Option<String>,
+    /// Host as shown in messages, if labeled
+    pub host_label: Option<String>,
+    /// Signed link to decide in a browser
+    pub approval_link: Option<String>,
+    /// One-line description of a Bash command
+    pub explanation: Option<String>,
+    /// Masks secrets in what leaves the machine; rules are matched and
+    /// saved from the unmasked input
+    pub redactor: Option<Redactor>,
 }
 
 impl PermissionRequest {
     /// Create a new permission request from hook input.
+    ///
+    /// Suggestions that cannot be described to the user are dropped.
     pub fn from_hook_input(input: HookInput) -> Self {
-        let request_id = uuid::Uuid::new_v4().to_string()[..8].to_string();
         Self {
             tool_name: input.tool_name,
             tool_input: input.tool_input,
-            request_id,
+            request_id: new_request_id(),
+            required_approvals: 1,
+            permission_suggestions: input
+                .permission_suggestions
+                .into_iter()
+                .filter(|suggestion| describe_suggestion(suggestion).is_some())
+                .collect(),
+            display: ToolDisplay::default(),
+            session_id: input.session_id,
+            context: None,
+            host_label: None,
+            approval_link: None,
+            explanation: None,
+            redactor: None,
+        }
+    }
+
+    /// A value as shown outside the machine, with secrets masked.
+    fn shown(&self, value: &Value) -> Value {
+        match &self.redactor {
+            Some(redactor) => redactor.redact_value(value),
+            None => value.clone(),
         }
     }
 
+    /// The tool input as shown in chat, history and webhooks.
+    pub fn shown_input(&self) -> Value {
+        self.shown(&self.tool_input)
+    }
+
     /// Convert to a PermissionMessage for sending via messenger.
     pub fn to_message(&self, hostname: &str) -> PermissionMessage {
+        let context = self.context.as_deref().map(|context| match &self.redactor {
+            Some(redactor) => redactor.redact(context),
+            None => context.to_string(),
+        });
         PermissionMessage::new(
             self.request_id.clone(),
             self.tool_name.clone(),
             hostname.to_string(),
-            self.tool_input.clone(),
+            self.shown_input(),
+        )
+        .with_required_approvals(self.required_approvals)
+        .with_suggestions(
+            self.permission_suggestions
+                .iter()
+                .filter_map(|suggestion| describe_suggestion(&self.shown(suggestion)))
+                .collect(),
         )
+        .with_display(self.display.clone())
+        .with_session(&self.session_id)
+        .with_context(context)
+        .with_host_label(self.host_label.clone())
+        .with_approval_link(self.approval_link.clone())
+        .with_explanation(self.explanation.clone())
+    }
+}
+
+/// Describe where a permission update is saved, e.g. "for this project".
+fn describe_destination(destination: Option<&str>) -> &'static str {
+    match destination {
+        Some("userSettings") => "everywhere",
+        Some("projectSettings") | Some("localSettings") => "for this project",
+        _ => "for this session",
+    }
+}
+
+/// Button label for a permission suggestion, e.g. "Allow Bash(git *) for this project".
+///
+/// Returns `None` for suggestions that do not widen what Claude may do
+/// without asking, which are not offered.
+pub fn describe_suggestion(suggestion: &Value) -> Option<String> {
+    let destination = describe_destination(suggestion.get("destination").and_then(Value::as_str));
+    match suggestion.get("type").and_then(Value::as_str)? {
+        "addRules" => {
+            if suggestion.get("behavior").and_then(Value::as_str) != Some("allow") {
+                return None;
+            }
+            let rules: Vec<String> = suggestion
+                .get("rules")?
+                .as_array()?
+                .iter()
+                .map(|rule| {
+                    let tool = rule.get("toolName")?.as_str()?;
+                    Some(match rule.get("ruleContent").and_then(Value::as_str) {
+                        Some(content) => format!("{}({})", tool, content),
+                        None => tool.to_string(),
+                    })
+                })
+                .collect::<Option<_>>()?;
+            if rules.is_empty() {
+                return None;
+            }
+            Some(format!("Allow {} {}", rules.join(", "), destination))
+        }
+        "setMode" => {
+            let mode = suggestion.get("mode").and_then(Value::as_str)?;
+            Some(format!("Switch to {} mode {}", mode, destination))
+        }
+        "addDirectories" => {
+            let directories: Vec<&str> = suggestion
+                .get("directories")?
+                .as_array()?
+                .iter()
+                .map(Value::as_str)
+                .collect::<Option<_>>()?;
+            if directories.is_empty() {
+                return None;
+            }
+            Some(format!(
+                "Allow access to {} {}",
+                directories.join(", "),
+                destination
+            ))
+        }
+        _ => None,
     }
 }
 
@@ -75,6 +250,15 @@ pub struct HookSpecificOutput {
 #[derive(Debug, Serialize)]
 pub struct DecisionOutput {
     pub behavior: String,
+    /// Reason shown to Claude when denying
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Permission suggestions the user chose to apply
+    #[serde(rename = "updatedPermissions", skip_serializing_if = "Option::is_none")]
+    pub updated_permissions: Option<Vec<Value>>,
+    /// Stop Claude instead of letting it try something else
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub interrupt: bool,
 }
 
 /// Create the hook response JSON.
@@ -84,17 +268,75 @@ pub fn create_hook_response(decision: Decision) -> HookOutput {
             hook_event_name: "PermissionRequest".to_string(),
             decision: DecisionOutput {
                 behavior: decision.to_behavior().to_string(),
+                message: None,
+                updated_permissions: None,
+                interrupt: false,
             },
         },
     }
 }
 
+/// Create the hook response for a decision on a specific request.
+///
+/// A chosen permission suggestion is written back so Claude Code applies it,
+/// "Deny & Block" interrupts Claude so a runaway loop stops, and a snoozed
+/// request tells Claude to leave the call for later.
+pub fn create_request_response(request: &PermissionRequest, decision: Decision) -> HookOutput {
+    let mut response = create_hook_response(decision);
+    match decision {
+        Decision::ApplySuggestion(index) => {
+            response.hook_specific_output.decision.updated_permissions = request
+                .permission_suggestions
+                .get(index)
+                .map(|suggestion| vec![suggestion.clone()]);
+        }
+        Decision::DenyAndBlock => {
+            let rule = BlockRule::for_call(
+                &request.tool_name,
+                tool_detail(&request.tool_name, &request.tool_input),
+            );
+            let decision = &mut response.hook_specific_output.decision;
+            decision.message = Some(HookError::Blocked(rule.to_string()).to_string());
+            decision.interrupt = true;
+        }
+        Decision::Snooze => {
+            response.hook_specific_output.decision.message = Some(snooze::snooze_message());
+        }
+        _ => {}
+    }
+    response
+}
+
+/// The end of Claude's last message, where it says what it is about to do.
+///
+/// Whitespace is collapsed and at most `max_chars` are kept; `None` when
+/// empty or when `max_chars` is 0.
+fn context_excerpt(text: &str, max_chars: usize) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() || max_chars == 0 {
+        return None;
+    }
+    let count = text.chars().count();
+    if count <= max_chars {
+        return Some(text);
+    }
+    let tail: String = text.chars().skip(count - max_chars + 1).collect();
+    Some(format!("…{}", tail))
+}
+
+/// Create a deny response explaining why the request was refused.
+pub fn create_deny_response(reason: &str) -> HookOutput {
+    let mut response = create_hook_response(Decision::Deny);
+    response.hook_specific_output.decision.message = Some(reason.to_string());
+    response
+}
+
 /// Handle a permission request using the provided messenger.
 ///
 /// This is the main entry point for processing permission requests.
-/// It checks the always-allow list first, then sends a message via
-/// the messenger and waits for user decision.
-pub async fn handle_permission_request_with_messenger<M: Messenger>(
+/// It checks the block and always-allow lists first, then sends a message
+/// via the messenger and waits for user decision.
+pub async fn handle_permission_request_with_messenger<M: Messenger + ?Sized>(
     messenger: &M,
     always_allow: &AlwaysAllowManager,
     request: &PermissionRequest,
@@ -103,10 +345,57 @@ pub async fn handle_permission_request_with_messenger<M: Messenger>(
 ) -> Result<Decision, HookError> {
     let message = request.to_message(hostname);
 
-    // Check if tool is in always-allow list
-    if always_allow.is_allowed(&request.tool_name) {
-        messenger.send_auto_approved(&message).await?;
-        return Ok(Decision::Allow);
+    // Calls blocked from chat are denied without asking
+    let manager = always_allow.clone();
+    let rule = BlockRule::for_call(
+        &request.tool_name,
+        tool_detail(&request.tool_name, &request.tool_input),
+    );
+    let (tool_name, tool_input) = (request.tool_name.clone(), request.tool_input.clone());
+    if let Some(rule) =
+        off_reactor(move || manager.blocked_by(&tool_name, tool_detail(&tool_name, &tool_input)))
+            .await
+    {
+        return Err(HookError::Blocked(rule.to_string()));
+    }
+
+    // Check if tool is in always-allow list; two-person rules are never bypassed
+    if request.required_approvals == 1 {
+        let manager = always_allow.clone();
+        let (tool_name, tool_input) = (request.tool_name.clone(), request.tool_input.clone());
+        let approval = off_reactor(move || manager.claim_auto_approval(&tool_name, &tool_input))
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to check auto-approval, asking instead: {}", e);
+                AutoApproval::NotAllowed
+            });
+        match approval {
+            AutoApproval::Allowed => {
+                messenger.send_auto_approved(&message).await?;
+                return Ok(Decision::Allow);
+            }
+            AutoApproval::LimitReached { limit } => {
+                tracing::warn!(
+                    "Auto-approval limit for {} reached, asking instead",
+                    request.tool_name
+                );
+                let warning = format!(
+                    "⚠️ Auto-approval limit for {} reached ({} per hour) on {}; \
+                     asking for this request instead.",
+                    request.tool_name, limit, hostname
+                );
+                if let Err(e) = messenger.send_text(&warning, TextFormat::Plain).await {
+                    tracing::warn!("Failed to send auto-approval limit notice: {}", e);
+                }
+            }
+            AutoApproval::OutsideWindow => {
+                tracing::info!(
+                    "Outside auto-approval hours for {}, asking instead",
+                    request.tool_name
+                );
+            }
+            AutoApproval::NotAllowed => {}
+        }
     }
 
     // Send permission request and wait for decision
@@ -116,16 +405,28 @@ pub async fn handle_permission_request_with_messenger<M: Messenger>(
 
     // Handle always allow
     if decision == Decision::AlwaysAllow {
-        let _ = always_allow.add_tool(&request.tool_name);
+        let manager = always_allow.clone();
+        let entry = allow_entry(&request.tool_name, &request.tool_input);
+        if let Err(e) = off_reactor(move || manager.add_tool(&entry)).await {
+            tracing::warn!("Failed to save always-allow rule: {}", e);
+        }
         return Ok(Decision::Allow);
     }
 
+    if decision == Decision::DenyAndBlock {
+        let manager = always_allow.clone();
+        if let Err(e) = off_reactor(move || manager.block(rule)).await {
+            tracing::warn!("Failed to save block rule: {}", e);
+        }
+    }
+
     Ok(decision)
 }
 
-/// Handle a permission request using the configured primary messenger.
+/// Handle a permission request using the first available messenger.
 ///
-/// Selects between Telegram, Discord, or Signal based on config.primary_messenger.
+/// Messengers are tried in the order `config.messengers_for` gives for the
+/// request's severity.
 pub async fn handle_permission_request(
     config: &Config,
     always_allow: &AlwaysAllowManager,
@@ -133,89 +434,639 @@ pub async fn handle_permission_request(
 ) -> Result<Decision, HookError> {
     let timeout = Duration::from_secs(config.timeout_seconds);
 
-    // Try Discord if configured as primary
-    #[cfg(feature = "discord")]
-    if config.primary_messenger == "discord" {
-        if let Some(ref discord_config) = config.discord {
-            if discord_config.enabled {
-                let messenger =
-                    DiscordMessenger::new(&discord_config.bot_token, discord_config.user_id);
-                return handle_permission_request_with_messenger(
-                    &messenger,
+    let detail = tool_detail(&request.tool_name, &request.tool_input);
+    let policy_denial = off_reactor(Policy::load_system)
+        .await
+        .denial(&request.tool_name, detail);
+    let required_approvals = config.required_approvals(&request.tool_name, detail);
+
+    // Organization policy outranks every user setting, including always-allow
+    if let Some(reason) = policy_denial {
+        let tool_input = Redactor::new(&config.redaction).redact_value(&request.tool_input);
+        let label = describe_tool(&request.tool_name, &tool_input);
+        record_history(
+            HistoryEntry::new(
+                EventKind::Decision,
+                None,
+                format!("{} → deny ({})", label, reason),
+            )
+            .with_request_id(&request.request_id)
+            .with_tool(
+                &request.tool_name,
+                tool_detail(&request.tool_name, &tool_input),
+            )
+            .with_decision("policy_deny", None),
+        )
+        .await;
+        record_audit(
+            config,
+            AuditEntry {
+                event: AuditEvent::Decision,
+                request_id: request.request_id.clone(),
+                platform: "policy".to_string(),
+                host: config.hostname.clone(),
+                tool: label,
+                responder: None,
+                outcome: Some(format!("policy_deny: {}", reason)),
+            },
+        )
+        .await;
+        webhooks::emit(
+            config,
+            WebhookEvent::DecisionMade,
+            json!({
+                "request_id": request.request_id,
+                "platform": "policy",
+                "tool_name": request.tool_name,
+                "tool_input": tool_input,
+                "decision": "policy_deny",
+                "reason": reason,
+            }),
+        )
+        .await;
+        return Err(HookError::PolicyDenied(reason));
+    }
+
+    // Hosts and sessions paused from chat are denied until resumed
+    let (hostname, session_id) = (config.hostname.clone(), request.session_id.clone());
+    let pause = off_reactor(move || PauseList::new(None).matching(&hostname, &session_id)).await;
+    if let Some(pause) = pause {
+        let tool_input = Redactor::new(&config.redaction).redact_value(&request.tool_input);
+        let label = describe_tool(&request.tool_name, &tool_input);
+        record_history(
+            HistoryEntry::new(
+                EventKind::Decision,
+                None,
+                format!("{} → deny (paused {})", label, pause),
+            )
+            .with_request_id(&request.request_id)
+            .with_decision("paused", None),
+        )
+        .await;
+        return Err(HookError::Paused(pause.to_string()));
+    }
+
+    // Policy modules may decide before anyone is asked
+    if !config.policy_modules.is_empty() {
+        let modules = config.policy_modules.clone();
+        let input = json!({
+            "tool_name": request.tool_name,
+            "tool_input": request.tool_input,
+            "host": config.hostname,
+            "session_id": request.session_id,
+        });
+        match off_reactor(move || policy_module::evaluate(&modules, &input)).await {
+            Some((Verdict::Deny, module)) => {
+                record_unasked_decision(config, request, "policy_module", &module, "module_deny")
+                    .await;
+                return Err(HookError::ModuleDenied(module));
+            }
+            // Modules can't stand in for a second approver or lift a block
+            Some((Verdict::Allow, module)) if required_approvals == 1 => {
+                let manager = always_allow.clone();
+                let (tool_name, tool_input) =
+                    (request.tool_name.clone(), request.tool_input.clone());
+                let blocked = off_reactor(move || {
+                    manager.blocked_by(&tool_name, tool_detail(&tool_name, &tool_input))
+                })
+                .await;
+                if blocked.is_none() {
+                    record_unasked_decision(
+                        config,
+                        request,
+                        "policy_module",
+                        &module,
+                        "module_allow",
+                    )
+                    .await;
+                    return Ok(Decision::Allow);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Sessions in a /yolo window are allowed without asking until it ends,
+    // though not past a second approver or a block rule
+    if required_approvals == 1 {
+        let session_id = request.session_id.clone();
+        let window = off_reactor(move || YoloWindows::new(None).matching(&session_id)).await;
+        if let Some(window) = window {
+            let manager = always_allow.clone();
+            let (tool_name, tool_input) = (request.tool_name.clone(), request.tool_input.clone());
+            let blocked = off_reactor(move || {
+                manager.blocked_by(&tool_name, tool_detail(&tool_name, &tool_input))
+            })
+            .await;
+            if blocked.is_none() {
+                let reason = format!("yolo by {}", window.by);
+                record_unasked_decision(config, request, "yolo", &reason, "yolo_allow").await;
+                return Ok(Decision::Allow);
+            }
+        }
+    }
+
+    // Read-only tools in low-noise mode are allowed without a message
+    if required_approvals == 1 && read_only::is_quiet(&config.read_only_tools, &request.tool_name) {
+        let manager = always_allow.clone();
+        let (tool_name, tool_input) = (request.tool_name.clone(), request.tool_input.clone());
+        let blocked = off_reactor(move || {
+            manager.blocked_by(&tool_name, tool_detail(&tool_name, &tool_input))
+        })
+        .await;
+        if blocked.is_none() {
+            allow_read_only(config, request).await;
+            return Ok(Decision::Allow);
+        }
+    }
+
+    // Only some platforms can tell responders apart to count approvals
+    let severity = if required_approvals > 1 {
+        Severity::High
+    } else {
+        Severity::Normal
+    };
+    let Some(messenger) = available_messengers(config, severity)
+        .await
+        .into_iter()
+        .find(|m| required_approvals == 1 || m.supports_quorum())
+    else {
+        let missing = if required_approvals == 1 {
+            "no messenger configured"
+        } else {
+            "no configured messenger supports multi-person approval"
+        };
+        return Err(HookError::ConfigError(
+            crate::error::ConfigError::MissingField(missing.to_string()),
+        ));
+    };
+
+    // In vacation mode requests are decided without asking anyone
+    let on_vacation = off_reactor(|| Away::new(None).vacation()).await.is_some();
+    let messenger: Box<dyn Messenger> = if on_vacation {
+        Box::new(VacationMessenger::new(messenger, config.vacation_fallback))
+    } else {
+        messenger
+    };
+    // The daemon queues auto-approval notices rather than waiting on them
+    let messenger = queue::wrap(messenger);
+
+    // Never forward raw credentials to a chat platform, or to an explainer;
+    // rules are still matched and saved from the real input
+    let redactor = Redactor::new(&config.redaction);
+    let shown_input = redactor.redact_value(&request.tool_input);
+    let explanation = match shown_input.get("command").and_then(Value::as_str) {
+        Some(command) if request.tool_name == "Bash" => {
+            explain::explain(&config.command_explanations, &config.llm_summary, command).await
+        }
+        _ => None,
+    };
+    let request = &PermissionRequest {
+        explanation,
+        required_approvals,
+        display: config.tool_display(&request.tool_name),
+        context: request
+            .context
+            .as_deref()
+            .and_then(|context| context_excerpt(context, config.request_context_chars)),
+        redactor: Some(redactor),
+        host_label: Some(config.host_label.clone()),
+        // Links can't count approvals, so they only decide single-approval requests
+        approval_link: (required_approvals == 1)
+            .then(|| web_approvals::link(&config.web_approvals, &request.request_id, timeout))
+            .flatten(),
+        ..request.clone()
+    };
+
+    let platform = Some(messenger.platform_name());
+    let label = describe_tool(&request.tool_name, &shown_input);
+    record_history(
+        HistoryEntry::new(EventKind::PermissionRequest, platform, &label)
+            .with_request_id(&request.request_id)
+            .with_tool(
+                &request.tool_name,
+                tool_detail(&request.tool_name, &shown_input),
+            ),
+    )
+    .await;
+    let audit_entry = AuditEntry {
+        event: AuditEvent::Request,
+        request_id: request.request_id.clone(),
+        platform: messenger.platform_name().to_string(),
+        host: config.hostname.clone(),
+        tool: label.clone(),
+        responder: None,
+        outcome: None,
+    };
+    record_audit(config, audit_entry.clone()).await;
+    mark_request(config, request, true).await;
+    let manager = always_allow.clone();
+    let (tool_name, tool_input) = (request.tool_name.clone(), request.tool_input.clone());
+    let auto_approved = required_approvals == 1
+        && off_reactor(move || manager.auto_approval(&tool_name, &tool_input)).await
+            == AutoApproval::Allowed;
+    let manager = always_allow.clone();
+    let (tool_name, tool_input) = (request.tool_name.clone(), request.tool_input.clone());
+    let blocked =
+        off_reactor(move || manager.blocked_by(&tool_name, tool_detail(&tool_name, &tool_input)))
+            .await
+            .is_some();
+    // Bursts of ordinary requests may be asked together in one message
+    let groupable = required_approvals == 1 && !auto_approved && !blocked && !on_vacation;
+    let groups = RequestGroups::new(config.request_grouping.clone());
+
+    let event_fields = json!({
+        "request_id": request.request_id,
+        "platform": messenger.platform_name(),
+        "tool_name": request.tool_name,
+        "tool_input": shown_input,
+    });
+    let started = Instant::now();
+    let decide = async {
+        let grouped = if groupable {
+            let message = request.to_message(&config.hostname);
+            groups
+                .decide(messenger.as_ref(), &request.session_id, &message, timeout)
+                .await
+        } else {
+            None
+        };
+        match grouped {
+            Some(result) => result,
+            None => {
+                let ask = handle_permission_request_with_messenger(
+                    messenger.as_ref(),
                     always_allow,
                     request,
                     &config.hostname,
                     timeout,
-                )
-                .await;
+                );
+                with_escalation(config, messenger.as_ref(), &label, timeout, ask).await
             }
         }
+    };
+    let (_, result) = tokio::join!(
+        webhooks::emit(config, WebhookEvent::RequestCreated, event_fields.clone()),
+        decide
+    );
+    mark_request(config, request, false).await;
+    if matches!(result, Ok(Decision::DenyAndBlock)) && config.block_in_claude_settings {
+        add_claude_deny_rule(request).await;
     }
 
-    // Try Telegram if configured as primary or as fallback
-    if let Some(ref telegram_config) = config.telegram {
-        let messenger = TelegramMessenger::new(&telegram_config.bot_token, telegram_config.chat_id);
-        return handle_permission_request_with_messenger(
-            &messenger,
-            always_allow,
-            request,
-            &config.hostname,
-            timeout,
+    let latency = started.elapsed();
+    let outcome = match &result {
+        Ok(_) if auto_approved => "auto_allow".to_string(),
+        Ok(Decision::DenyAndBlock) => "deny_block".to_string(),
+        Ok(Decision::Snooze) => "snooze".to_string(),
+        Ok(decision) => decision.to_behavior().to_string(),
+        Err(HookError::Blocked(rule)) => format!("blocked: {}", rule),
+        Err(HookError::Cancelled) => "cancelled".to_string(),
+        Err(e) => format!("error: {}", e),
+    };
+    let responder = messenger.last_responder();
+    // Messengers deny on timeout, so a denial at the deadline counts as one
+    let timed_out = matches!(result, Ok(Decision::Deny)) && latency >= timeout;
+
+    let entry = match &result {
+        Ok(decision) => HistoryEntry::new(
+            EventKind::Decision,
+            platform,
+            format!("{} → {}", label, decision.to_behavior()),
         )
-        .await;
+        .with_decision(
+            if timed_out { "timeout" } else { &outcome },
+            responder.clone(),
+        )
+        .with_latency(latency),
+        Err(HookError::Blocked(rule)) => HistoryEntry::new(
+            EventKind::Decision,
+            platform,
+            format!("{} → deny (blocked {})", label, rule),
+        )
+        .with_decision("blocked", None),
+        Err(HookError::Cancelled) => HistoryEntry::new(
+            EventKind::Decision,
+            platform,
+            format!("{} → cancelled", label),
+        )
+        .with_decision("cancelled", None)
+        .with_latency(latency),
+        Err(e) => HistoryEntry::new(EventKind::Error, platform, e.to_string()),
+    };
+    record_history(entry.with_request_id(&request.request_id)).await;
+
+    record_audit(
+        config,
+        AuditEntry {
+            event: AuditEvent::Decision,
+            responder: responder.clone(),
+            outcome: Some(outcome.clone()),
+            ..audit_entry
+        },
+    )
+    .await;
+
+    if result.is_ok() {
+        let mut fields = event_fields;
+        let event = if timed_out {
+            WebhookEvent::Timeout
+        } else {
+            fields["decision"] = json!(outcome);
+            fields["responder"] = json!(responder);
+            WebhookEvent::DecisionMade
+        };
+        webhooks::emit(config, event, fields).await;
     }
 
-    // Try Discord as fallback if telegram not available
-    #[cfg(feature = "discord")]
-    if let Some(ref discord_config) = config.discord {
-        if discord_config.enabled {
-            let messenger =
-                DiscordMessenger::new(&discord_config.bot_token, discord_config.user_id);
-            return handle_permission_request_with_messenger(
-                &messenger,
-                always_allow,
-                request,
-                &config.hostname,
-                timeout,
+    result
+}
+
+/// Add a "Deny & Block" rule to Claude Code's own deny list, so later
+/// sessions stop asking for such calls at all.
+async fn add_claude_deny_rule(request: &PermissionRequest) {
+    let rule = BlockRule::for_call(
+        &request.tool_name,
+        tool_detail(&request.tool_name, &request.tool_input),
+    )
+    .claude_rule();
+    let settings_path = default_claude_settings_path();
+    let added = {
+        let rule = rule.clone();
+        off_reactor(move || install::add_deny_rule_to(&settings_path, &rule)).await
+    };
+    match added {
+        Ok(true) => tracing::info!("Added {} to Claude Code's deny list", rule),
+        Ok(false) => {}
+        Err(e) => tracing::warn!("Failed to add {} to Claude Code settings: {:#}", rule, e),
+    }
+}
+
+/// Record a request decided without asking, by a policy module or in a
+/// yolo window, with the reason shown in the history.
+async fn record_unasked_decision(
+    config: &Config,
+    request: &PermissionRequest,
+    platform: &str,
+    reason: &str,
+    decision: &str,
+) {
+    let tool_input = Redactor::new(&config.redaction).redact_value(&request.tool_input);
+    let label = describe_tool(&request.tool_name, &tool_input);
+    let behavior = if decision.ends_with("_allow") {
+        "allow"
+    } else {
+        "deny"
+    };
+    record_history(
+        HistoryEntry::new(
+            EventKind::Decision,
+            None,
+            format!("{} → {} ({})", label, behavior, reason),
+        )
+        .with_request_id(&request.request_id)
+        .with_tool(
+            &request.tool_name,
+            tool_detail(&request.tool_name, &tool_input),
+        )
+        .with_decision(decision, None),
+    )
+    .await;
+    record_audit(
+        config,
+        AuditEntry {
+            event: AuditEvent::Decision,
+            request_id: request.request_id.clone(),
+            platform: platform.to_string(),
+            host: config.hostname.clone(),
+            tool: label,
+            responder: None,
+            outcome: Some(format!("{}: {}", decision, reason)),
+        },
+    )
+    .await;
+}
+
+/// Record a read-only call allowed in low-noise mode, sending the digest if due.
+async fn allow_read_only(config: &Config, request: &PermissionRequest) {
+    let tool_input = Redactor::new(&config.redaction).redact_value(&request.tool_input);
+    let label = describe_tool(&request.tool_name, &tool_input);
+    record_history(
+        HistoryEntry::new(EventKind::Decision, None, format!("{} → allow", label))
+            .with_request_id(&request.request_id)
+            .with_tool(
+                &request.tool_name,
+                tool_detail(&request.tool_name, &tool_input),
             )
-            .await;
+            .with_decision("read_only_allow", None),
+    )
+    .await;
+    record_audit(
+        config,
+        AuditEntry {
+            event: AuditEvent::Decision,
+            request_id: request.request_id.clone(),
+            platform: "read_only".to_string(),
+            host: config.hostname.clone(),
+            tool: label,
+            responder: None,
+            outcome: Some("read_only_allow".to_string()),
+        },
+    )
+    .await;
+
+    let now = now_secs();
+    let (settings, tool_name) = (config.read_only_tools.clone(), request.tool_name.clone());
+    match off_reactor(move || read_only::record(&settings, &tool_name, now)).await {
+        Ok(Some(digest)) => {
+            if let Err(e) = notify_primary(config, &digest.text(&config.host_label, now)).await {
+                tracing::warn!("Failed to send read-only digest: {}", e);
+            }
         }
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to count read-only call: {}", e),
     }
+}
 
-    // No messenger available
-    Err(HookError::ConfigError(
-        crate::error::ConfigError::MissingField("no messenger configured".to_string()),
-    ))
+/// Open or close the mark that lets a permission prompt notification for
+/// the same call be dropped.
+async fn mark_request(config: &Config, request: &PermissionRequest, open: bool) {
+    if config.permission_prompt_dedup_seconds == 0 || request.session_id.is_empty() {
+        return;
+    }
+    let (session_id, tool_name) = (request.session_id.clone(), request.tool_name.clone());
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    let marked = off_reactor(move || {
+        let marks = PromptMarks::new(None);
+        if open {
+            marks.open(&session_id, &tool_name, timeout)
+        } else {
+            marks.close(&session_id, &tool_name)
+        }
+    })
+    .await;
+    if let Err(e) = marked {
+        tracing::warn!("Failed to mark request for prompt deduplication: {}", e);
+    }
+}
+
+async fn record_history(entry: HistoryEntry) {
+    off_reactor(move || history::record(entry)).await
+}
+
+async fn record_audit(config: &Config, entry: AuditEntry) {
+    let audit_log = config.audit_log.clone();
+    off_reactor(move || audit::record(&audit_log, entry)).await
 }
 
 /// Read JSON input from stdin.
-fn read_stdin() -> Result<String, io::Error> {
+async fn read_stdin() -> Result<String, io::Error> {
     let mut buffer = String::new();
-    io::stdin().read_to_string(&mut buffer)?;
+    tokio::io::stdin().read_to_string(&mut buffer).await?;
     Ok(buffer)
 }
 
 /// Main entry point for the hook handler.
-pub async fn run() -> Result<(), HookError> {
-    // Read and parse input
-    let input_str = read_stdin()?;
-    let input: HookInput = serde_json::from_str(&input_str)?;
+///
+/// Input in another agent's `format` is translated to Claude Code's and the
+/// answer back. Hands the request to a running `daemon` when there is one, so
+/// its warm connections are reused, and handles it in this process otherwise.
+/// Either way the request is withdrawn if Claude Code terminates the hook.
+pub async fn run(config_path: Option<&Path>, format: &str) -> Result<(), HookError> {
+    let input_str = adapters::to_claude_input(format, &read_stdin().await?)?;
+    #[cfg(any(unix, windows))]
+    if !dry_run() {
+        if let Some(output) = crate::daemon::forward(config_path, &input_str).await? {
+            println!("{}", adapters::from_claude_output(format, &output)?);
+            return Ok(());
+        }
+    }
+    let (cancel, cancelled) = watch::channel(false);
+    let decide = cancellable(cancelled, respond(config_path, &input_str));
+    let withdraw = move || async move {
+        let _ = cancel.send(true);
+    };
+    let response = until_terminated(decide, withdraw).await?;
+    let output = serde_json::to_string(&response)?;
+    println!("{}", adapters::from_claude_output(format, &output)?);
+    Ok(())
+}
+
+/// Wait until Claude Code gives up on the hook, which it does by
+/// terminating the process when the tool call is aborted.
+async fn termination() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = terminate.recv() => return,
+                Ok(()) = tokio::signal::ctrl_c() => return,
+            }
+        }
+    }
+    if tokio::signal::ctrl_c().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
 
-    // Load config
-    let config = Config::load(None)?;
+/// Run `decide` until Claude Code gives up on it.
+///
+/// Then `cancel` is called and `decide` gets [`CANCEL_GRACE`] to withdraw
+/// the request before the hook exits with [`HookError::Cancelled`].
+pub(crate) async fn until_terminated<T, C>(
+    decide: impl Future<Output = Result<T, HookError>>,
+    cancel: impl FnOnce() -> C,
+) -> Result<T, HookError>
+where
+    C: Future<Output = ()>,
+{
+    let mut decide = pin!(decide);
+    tokio::select! {
+        result = &mut decide => return result,
+        () = termination() => {}
+    }
+    tracing::info!("Claude Code gave up on the request, withdrawing it");
+    cancel().await;
+    let _ = tokio::time::timeout(CANCEL_GRACE, decide).await;
+    Err(HookError::Cancelled)
+}
+
+/// Handle raw hook input JSON and print the hook response.
+pub async fn run_with_input(config_path: Option<&Path>, input_str: &str) -> Result<(), HookError> {
+    let response = respond(config_path, input_str).await?;
+    println!("{}", serde_json::to_string(&response)?);
+    Ok(())
+}
+
+/// Decide on raw hook input JSON and return the hook response.
+pub async fn respond(config_path: Option<&Path>, input_str: &str) -> Result<HookOutput, HookError> {
+    let input = parse_hook_input(input_str)?;
+
+    // Load config, applying any project route
+    let config = match Config::load(config_path.map(Path::to_path_buf)) {
+        Ok(config) => config.for_project(&input.cwd),
+        Err(e) => {
+            record_history(HistoryEntry::new(EventKind::Error, None, e.to_string())).await;
+            if strict_requested(config_path) {
+                return Ok(strict_deny(&format!("configuration error: {}", e)));
+            }
+            return Err(e.into());
+        }
+    };
 
     // Create request and handler
-    let request = PermissionRequest::from_hook_input(input);
-    let always_allow = AlwaysAllowManager::new(None);
+    let cwd = input.cwd.clone();
+    let transcript_path = PathBuf::from(&input.transcript_path);
+    let request = PermissionRequest {
+        request_id: scoped_request_id(
+            &config.hostname,
+            &input.session_id,
+            config.request_id_length,
+        ),
+        context: off_reactor(move || last_assistant_message(&transcript_path)).await,
+        ..PermissionRequest::from_hook_input(input)
+    };
+    let always_allow = AlwaysAllowManager::new(None)
+        .with_limits(config.auto_approval_limits.clone())
+        .with_windows(config.auto_approval_windows.clone());
 
     // Get decision
-    let decision = handle_permission_request(&config, &always_allow, &request).await?;
+    let decision = match handle_permission_request(&config, &always_allow, &request).await {
+        Ok(decision) => decision,
+        Err(
+            e @ (HookError::PolicyDenied(_)
+            | HookError::ModuleDenied(_)
+            | HookError::Blocked(_)
+            | HookError::Paused(_)
+            | HookError::Cancelled),
+        ) => {
+            return Ok(create_deny_response(&e.to_string()));
+        }
+        Err(e) if config.strict => {
+            return Ok(strict_deny(&format!("messenger error: {}", e)));
+        }
+        Err(e) => return Err(e),
+    };
 
-    // Output response
-    let response = create_hook_response(decision);
-    println!("{}", serde_json::to_string(&response)?);
+    if decision == Decision::Snooze {
+        let tool_input = Redactor::new(&config.redaction).redact_value(&request.tool_input);
+        let label = describe_tool(&request.tool_name, &tool_input);
+        if let Err(e) = snooze::spawn_reminder(&label, &cwd, config_path) {
+            tracing::warn!("Failed to start snooze reminder: {}", e);
+        }
+    }
 
-    Ok(())
+    Ok(create_request_response(&request, decision))
+}
+
+/// Fail closed in strict mode by denying with the reason.
+fn strict_deny(reason: &str) -> HookOutput {
+    tracing::error!("Strict mode: denying request after {}", reason);
+    create_deny_response(&format!(
+        "Denied by claude-code-telegram strict mode ({})",
+        reason
+    ))
 }
 
 #[cfg(test)]
@@ -227,11 +1078,34 @@ mod tests {
         let input = HookInput {
             tool_name: "Bash".to_string(),
             tool_input: serde_json::json!({"command": "ls -la"}),
+            cwd: String::new(),
+            session_id: "session-1".to_string(),
+            transcript_path: String::new(),
+            permission_suggestions: vec![
+                serde_json::json!({"type": "addRules", "behavior": "deny", "rules": []}),
+                serde_json::json!({"type": "setMode", "mode": "acceptEdits"}),
+            ],
         };
 
         let request = PermissionRequest::from_hook_input(input);
         assert_eq!(request.tool_name, "Bash");
         assert_eq!(request.request_id.len(), 8);
+        assert_eq!(request.permission_suggestions.len(), 1);
+        assert_eq!(request.session_id, "session-1");
+    }
+
+    #[test]
+    fn test_context_excerpt() {
+        assert_eq!(
+            context_excerpt("Let me run\n  the tests.", 300).as_deref(),
+            Some("Let me run the tests.")
+        );
+        assert_eq!(
+            context_excerpt("First I read the code. Now the tests.", 15).as_deref(),
+            Some("…Now the tests.")
+        );
+        assert_eq!(context_excerpt("  ", 300), None);
+        assert_eq!(context_excerpt("Running tests.", 0), None);
     }
 
     #[test]
@@ -240,12 +1114,22 @@ mod tests {
             tool_name: "Bash".to_string(),
             tool_input: serde_json::json!({"command": "ls -la"}),
             request_id: "abc12345".to_string(),
+            required_approvals: 2,
+            permission_suggestions: Vec::new(),
+            display: ToolDisplay::default(),
+            session_id: String::new(),
+            context: None,
+            host_label: None,
+            approval_link: None,
+            explanation: None,
+            redactor: None,
         };
 
         let message = request.to_message("test-host");
         assert_eq!(message.tool_name, "Bash");
         assert_eq!(message.hostname, "test-host");
         assert_eq!(message.request_id, "abc12345");
+        assert_eq!(message.required_approvals, 2);
     }
 
     #[test]
@@ -259,4 +1143,126 @@ mod tests {
         let response = create_hook_response(Decision::Deny);
         assert_eq!(response.hook_specific_output.decision.behavior, "deny");
     }
+
+    #[test]
+    fn test_create_request_response_deny_and_block() {
+        let request = PermissionRequest {
+            tool_name: "Bash".to_string(),
+            tool_input: serde_json::json!({"command": "rm -rf build"}),
+            request_id: "abc12345".to_string(),
+            required_approvals: 1,
+            permission_suggestions: Vec::new(),
+            display: ToolDisplay::default(),
+            session_id: String::new(),
+            context: None,
+            host_label: None,
+            approval_link: None,
+            explanation: None,
+            redactor: None,
+        };
+
+        let json = serde_json::to_value(create_request_response(&request, Decision::DenyAndBlock))
+            .unwrap();
+        let decision = &json["hookSpecificOutput"]["decision"];
+        assert_eq!(decision["behavior"], "deny");
+        assert_eq!(decision["message"], "Blocked from chat: Bash(rm *)");
+        assert_eq!(decision["interrupt"], true);
+
+        let json = serde_json::to_value(create_request_response(&request, Decision::Deny)).unwrap();
+        assert!(json["hookSpecificOutput"]["decision"]
+            .get("interrupt")
+            .is_none());
+
+        // Snoozing denies without stopping Claude
+        let json =
+            serde_json::to_value(create_request_response(&request, Decision::Snooze)).unwrap();
+        let decision = &json["hookSpecificOutput"]["decision"];
+        assert_eq!(decision["behavior"], "deny");
+        assert!(decision["message"].as_str().unwrap().contains("snoozed"));
+        assert!(decision.get("interrupt").is_none());
+    }
+
+    #[test]
+    fn test_create_deny_response_includes_message() {
+        let json = serde_json::to_value(create_deny_response("no messenger")).unwrap();
+        assert_eq!(json["hookSpecificOutput"]["decision"]["behavior"], "deny");
+        assert_eq!(
+            json["hookSpecificOutput"]["decision"]["message"],
+            "no messenger"
+        );
+
+        let json = serde_json::to_value(create_hook_response(Decision::Allow)).unwrap();
+        assert!(json["hookSpecificOutput"]["decision"]
+            .get("message")
+            .is_none());
+    }
+
+    #[test]
+    fn test_describe_suggestion() {
+        let suggestion = serde_json::json!({
+            "type": "addRules",
+            "rules": [{"toolName": "Bash", "ruleContent": "git *"}],
+            "behavior": "allow",
+            "destination": "localSettings"
+        });
+        assert_eq!(
+            describe_suggestion(&suggestion).as_deref(),
+            Some("Allow Bash(git *) for this project")
+        );
+
+        let suggestion = serde_json::json!({
+            "type": "setMode",
+            "mode": "acceptEdits",
+            "destination": "session"
+        });
+        assert_eq!(
+            describe_suggestion(&suggestion).as_deref(),
+            Some("Switch to acceptEdits mode for this session")
+        );
+
+        let suggestion = serde_json::json!({"type": "removeRules", "rules": []});
+        assert!(describe_suggestion(&suggestion).is_none());
+    }
+
+    #[test]
+    fn test_create_request_response_applies_suggestion() {
+        let suggestion = serde_json::json!({
+            "type": "addRules",
+            "rules": [{"toolName": "Bash", "ruleContent": "git *"}],
+            "behavior": "allow",
+            "destination": "userSettings"
+        });
+        let request = PermissionRequest {
+            tool_name: "Bash".to_string(),
+            tool_input: serde_json::json!({"command": "git status"}),
+            request_id: "abc12345".to_string(),
+            required_approvals: 1,
+            permission_suggestions: vec![suggestion.clone()],
+            display: ToolDisplay::default(),
+            session_id: String::new(),
+            context: None,
+            host_label: None,
+            approval_link: None,
+            explanation: None,
+            redactor: None,
+        };
+
+        let json = serde_json::to_value(create_request_response(
+            &request,
+            Decision::ApplySuggestion(0),
+        ))
+        .unwrap();
+        let decision = &json["hookSpecificOutput"]["decision"];
+        assert_eq!(decision["behavior"], "allow");
+        assert_eq!(
+            decision["updatedPermissions"],
+            serde_json::json!([suggestion])
+        );
+
+        let json =
+            serde_json::to_value(create_request_response(&request, Decision::Allow)).unwrap();
+        assert!(json["hookSpecificOutput"]["decision"]
+            .get("updatedPermissions")
+            .is_none());
+    }
 }
@@ -0,0 +1,235 @@
+//! Minimal HTTP/1.1 handling for the listeners the daemon runs.
+//!
+//! GitHub webhooks and browser approval links only need one request per
+//! connection, so requests are read whole and answered with
+//! `Connection: close`.
+
+use crate::config::Config;
+use crate::error::ConfigError;
+use anyhow::{bail, Result};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+/// Largest body accepted; GitHub caps payloads at 25 MB but deployment
+/// events are a few kilobytes.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Most bytes read from one connection, headers included.
+const MAX_REQUEST: u64 = MAX_BODY as u64 + 64 * 1024;
+
+/// How long a client may take to send its request.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Load the configuration for one request.
+///
+/// Listeners reload it on every request, like hook requests, so config edits
+/// apply without restarting the daemon.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub fn load_config_for_request(config_path: Option<PathBuf>) -> Result<Config, ConfigError> {
+    Config::load(config_path)
+}
+
+/// HMAC-SHA256, as used by GitHub webhook signatures and approval links.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// Lowercase hex of a digest.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compare a hex digest with one from a client, ignoring case.
+///
+/// The comparison takes the same time wherever they differ, so a signature
+/// can't be guessed byte by byte.
+pub fn hex_matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.to_ascii_lowercase().bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Decode a percent-encoded query string or form value.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match text
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// A value from a query string or form body, e.g. `exp` in `exp=1&sig=ab`.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub fn form_value(encoded: &str, key: &str) -> Option<String> {
+    encoded
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| decode(name) == key)
+        .map(|(_, value)| decode(value))
+}
+
+/// An HTTP request as far as the listeners need it.
+#[derive(Debug, Default)]
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub struct HttpRequest {
+    pub method: String,
+    /// Path and query, e.g. `/approve/abc12345?exp=1&sig=ab`
+    pub target: String,
+    /// Header names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The target without its query.
+    pub fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+
+    /// A value from the query string.
+    pub fn query(&self, key: &str) -> Option<String> {
+        form_value(self.target.split_once('?')?.1, key)
+    }
+}
+
+/// Read one request with its body.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn read_request(stream: &mut TcpStream) -> Result<HttpRequest> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST));
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let mut request = HttpRequest {
+        method: parts.next().unwrap_or_default().to_string(),
+        target: parts.next().unwrap_or_default().to_string(),
+        ..HttpRequest::default()
+    };
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            bail!("connection closed in headers");
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if request.headers.len() >= 100 {
+            bail!("too many headers");
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            request
+                .headers
+                .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    let length: usize = request
+        .header("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_BODY {
+        bail!("body too large");
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).await?;
+    Ok(request)
+}
+
+/// Answer with a body of the given content type and close the connection.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn write_response(
+    stream: &mut TcpStream,
+    (code, reason): (u16, &str),
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Answer with just the status, e.g. `404 Not Found`.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn write_status(stream: &mut TcpStream, status: (u16, &str)) -> Result<()> {
+    write_response(stream, status, "text/plain", status.1).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_form_values() {
+        let request = HttpRequest {
+            target: "/approve/abc12345?exp=1700000000&sig=AB%2bcd".to_string(),
+            ..HttpRequest::default()
+        };
+        assert_eq!(request.path(), "/approve/abc12345");
+        assert_eq!(request.query("exp").as_deref(), Some("1700000000"));
+        assert_eq!(request.query("sig").as_deref(), Some("AB+cd"));
+        assert_eq!(request.query("other"), None);
+
+        let body = "decision=allow&telegram=%7B%22id%22%3A42%7D&note=a+b%ZZ";
+        assert_eq!(
+            form_value(body, "telegram").as_deref(),
+            Some(r#"{"id":42}"#)
+        );
+        assert_eq!(form_value(body, "note").as_deref(), Some("a b%ZZ"));
+        assert!(hex_matches(&hex(&[0xab, 0x01]), "AB01"));
+        assert!(!hex_matches(&hex(&[0xab, 0x01]), "ab0"));
+    }
+}
@@ -0,0 +1,383 @@
+//! Installation of hook entries into Claude Code settings.
+//!
+//! Edits `~/.claude/settings.json` in place, replacing any previous entries
+//! that point at this binary so repeated installs are idempotent. "Deny &
+//! Block" rules can also be added to its `permissions.deny` list.
+
+use crate::lockfile::FileLock;
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+/// Name of the installed binary, used to recognize our own hook entries.
+const BINARY_NAME: &str = "claude-code-telegram";
+
+/// Hook events and the subcommand (and optional tool matcher) for each.
+const HOOKS: &[(&str, &str, Option<&str>)] = &[
+    ("PermissionRequest", "hook", Some("Bash|Edit|Write")),
+    ("PreToolUse", "tool-timer", Some("")),
+    ("PostToolUse", "tool-timer", Some("")),
+    ("Stop", "stop", None),
+    ("Notification", "notify", None),
+];
+
+/// Quote a path for use in a hook command if it contains spaces.
+fn quote_path(path: &str) -> String {
+    if path.contains(char::is_whitespace) {
+        format!("\"{}\"", path)
+    } else {
+        path.to_string()
+    }
+}
+
+/// Split a hook command into words, honoring double quotes.
+fn split_command(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in command.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Check whether a hook command invokes this binary.
+fn is_own_command(command: &str) -> bool {
+    split_command(command).first().is_some_and(|program| {
+        Path::new(program)
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy() == BINARY_NAME)
+    })
+}
+
+/// The `--config` path a hook command passes, if any.
+fn command_config(command: &str) -> Option<String> {
+    let words = split_command(command);
+    words
+        .iter()
+        .position(|w| w == "--config")
+        .and_then(|i| words.get(i + 1).cloned())
+}
+
+/// Check whether a hook command belongs to this binary with the given config.
+fn is_own_hook(command: &str, config: Option<&str>) -> bool {
+    is_own_command(command) && command_config(command).as_deref() == config
+}
+
+/// Remove hook entries pointing at this binary with the given `--config`.
+///
+/// Entries for other config files are left alone so independent setups can
+/// coexist. Returns how many entries were removed.
+pub fn remove_hooks(settings: &mut Value, config: Option<&str>) -> usize {
+    let Some(hooks) = settings.get_mut("hooks").and_then(Value::as_object_mut) else {
+        return 0;
+    };
+
+    let mut removed = 0;
+    for groups in hooks.values_mut() {
+        let Some(groups) = groups.as_array_mut() else {
+            continue;
+        };
+
+        for group in groups.iter_mut() {
+            if let Some(entries) = group.get_mut("hooks").and_then(Value::as_array_mut) {
+                let before = entries.len();
+                entries.retain(|entry| {
+                    !entry
+                        .get("command")
+                        .and_then(Value::as_str)
+                        .is_some_and(|command| is_own_hook(command, config))
+                });
+                removed += before - entries.len();
+            }
+        }
+
+        // Drop groups left without any hooks
+        groups.retain(|group| {
+            group
+                .get("hooks")
+                .and_then(Value::as_array)
+                .map_or(true, |entries| !entries.is_empty())
+        });
+    }
+
+    // Drop events left without any groups
+    hooks.retain(|_, groups| groups.as_array().map_or(true, |g| !g.is_empty()));
+
+    removed
+}
+
+/// Install hook entries for this binary, replacing any previous ones.
+pub fn install_hooks(settings: &mut Value, exe: &str, config: Option<&str>) {
+    remove_hooks(settings, config);
+
+    let program = match config {
+        Some(config) => format!("{} --config {}", quote_path(exe), quote_path(config)),
+        None => quote_path(exe),
+    };
+
+    if !settings.is_object() {
+        *settings = Value::Object(Map::new());
+    }
+    let hooks = settings
+        .as_object_mut()
+        .expect("settings is an object")
+        .entry("hooks")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !hooks.is_object() {
+        *hooks = Value::Object(Map::new());
+    }
+    let hooks = hooks.as_object_mut().expect("hooks is an object");
+
+    for (event, subcommand, matcher) in HOOKS {
+        let mut group = json!({
+            "hooks": [{
+                "type": "command",
+                "command": format!("{} {}", program, subcommand),
+            }]
+        });
+        if let Some(matcher) = matcher {
+            group["matcher"] = json!(matcher);
+        }
+
+        let groups = hooks
+            .entry(event.to_string())
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Some(groups) = groups.as_array_mut() {
+            groups.push(group);
+        } else {
+            *groups = Value::Array(vec![group]);
+        }
+    }
+}
+
+/// Add a rule to `permissions.deny`. Returns false if it was there already.
+pub fn add_deny_rule(settings: &mut Value, rule: &str) -> bool {
+    if !settings.is_object() {
+        *settings = Value::Object(Map::new());
+    }
+    let permissions = settings
+        .as_object_mut()
+        .expect("settings is an object")
+        .entry("permissions")
+        .or_insert_with(|| json!({}));
+    if !permissions.is_object() {
+        *permissions = json!({});
+    }
+    let deny = permissions
+        .as_object_mut()
+        .expect("permissions is an object")
+        .entry("deny")
+        .or_insert_with(|| json!([]));
+    if !deny.is_array() {
+        *deny = json!([]);
+    }
+    let deny = deny.as_array_mut().expect("deny is an array");
+    if deny.iter().any(|existing| existing == rule) {
+        return false;
+    }
+    deny.push(json!(rule));
+    true
+}
+
+/// Add a deny rule to a settings file, keeping a backup of the previous file.
+///
+/// Returns false, leaving the file alone, if the rule was there already.
+pub fn add_deny_rule_to(settings_path: &Path, rule: &str) -> Result<bool> {
+    let _lock = FileLock::acquire(settings_path)?;
+    let mut settings = read_settings(settings_path)?;
+    if !add_deny_rule(&mut settings, rule) {
+        return Ok(false);
+    }
+    write_settings(settings_path, &settings)?;
+    Ok(true)
+}
+
+/// Read settings, returning an empty object if the file does not exist.
+fn read_settings(path: &Path) -> Result<Value> {
+    if !path.exists() {
+        return Ok(json!({}));
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if content.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    serde_json::from_str(&content).with_context(|| format!("Invalid JSON in {}", path.display()))
+}
+
+/// Write settings, keeping a backup of the previous file.
+fn write_settings(path: &Path, settings: &Value) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if path.exists() {
+        let backup = path.with_extension("json.bak");
+        fs::copy(path, &backup).with_context(|| format!("Failed to back up {}", path.display()))?;
+    }
+    let content = serde_json::to_string_pretty(settings)?;
+    fs::write(path, content + "\n").with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Make a config path absolute so hooks work from any working directory.
+fn absolute_config(config_path: Option<&Path>) -> Result<Option<String>> {
+    config_path
+        .map(|path| {
+            let path = if path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                std::env::current_dir()?.join(path)
+            };
+            Ok(path.to_string_lossy().to_string())
+        })
+        .transpose()
+}
+
+/// Install hooks pointing at the currently running binary.
+pub fn run_install(settings_path: &Path, config_path: Option<&Path>) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to locate current executable")?;
+    let exe = exe.to_string_lossy();
+    let config = absolute_config(config_path)?;
+
+    let mut settings = read_settings(settings_path)?;
+    install_hooks(&mut settings, &exe, config.as_deref());
+    write_settings(settings_path, &settings)?;
+
+    println!("✅ Installed hooks in {}", settings_path.display());
+    for (event, subcommand, _) in HOOKS {
+        println!("   {:<18} → {} {}", event, exe, subcommand);
+    }
+    Ok(())
+}
+
+/// Remove hooks pointing at this binary.
+pub fn run_uninstall(settings_path: &Path, config_path: Option<&Path>) -> Result<()> {
+    let config = absolute_config(config_path)?;
+    let mut settings = read_settings(settings_path)?;
+    let removed = remove_hooks(&mut settings, config.as_deref());
+
+    if removed == 0 {
+        println!("No hooks found in {}", settings_path.display());
+        return Ok(());
+    }
+
+    write_settings(settings_path, &settings)?;
+    println!(
+        "✅ Removed {} hook(s) from {}",
+        removed,
+        settings_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_own_command() {
+        assert!(is_own_command("claude-code-telegram hook"));
+        assert!(is_own_command("/usr/local/bin/claude-code-telegram stop"));
+        assert!(is_own_command(
+            "\"/Users/me/My Tools/claude-code-telegram\" notify"
+        ));
+        assert!(!is_own_command("other-tool hook"));
+        assert!(!is_own_command("echo claude-code-telegram"));
+    }
+
+    #[test]
+    fn test_install_is_idempotent_and_preserves_other_hooks() {
+        let mut settings = json!({
+            "model": "opus",
+            "hooks": {
+                "Stop": [
+                    {"hooks": [{"type": "command", "command": "say done"}]},
+                    {"hooks": [{"type": "command", "command": "claude-code-telegram stop"}]}
+                ]
+            }
+        });
+
+        install_hooks(&mut settings, "/opt/bin/claude-code-telegram", None);
+        install_hooks(&mut settings, "/opt/bin/claude-code-telegram", None);
+
+        assert_eq!(settings["model"], "opus");
+        let stop = settings["hooks"]["Stop"].as_array().unwrap();
+        assert_eq!(stop.len(), 2);
+        assert_eq!(stop[0]["hooks"][0]["command"], "say done");
+        assert_eq!(
+            stop[1]["hooks"][0]["command"],
+            "/opt/bin/claude-code-telegram stop"
+        );
+        assert_eq!(
+            settings["hooks"]["PermissionRequest"][0]["matcher"],
+            "Bash|Edit|Write"
+        );
+        assert_eq!(settings["hooks"]["PreToolUse"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_remove_hooks() {
+        let mut settings = json!({});
+        install_hooks(&mut settings, "/opt/bin/claude-code-telegram", None);
+
+        assert_eq!(remove_hooks(&mut settings, None), HOOKS.len());
+        assert_eq!(settings["hooks"], json!({}));
+        assert_eq!(remove_hooks(&mut settings, None), 0);
+    }
+
+    #[test]
+    fn test_hooks_per_config_coexist() {
+        let mut settings = json!({});
+        let exe = "/opt/bin/claude-code-telegram";
+        install_hooks(&mut settings, exe, None);
+        install_hooks(&mut settings, exe, Some("/srv/work config.json"));
+        install_hooks(&mut settings, exe, Some("/srv/work config.json"));
+
+        let stop = settings["hooks"]["Stop"].as_array().unwrap();
+        assert_eq!(stop.len(), 2);
+        assert_eq!(
+            stop[1]["hooks"][0]["command"],
+            "/opt/bin/claude-code-telegram --config \"/srv/work config.json\" stop"
+        );
+
+        assert_eq!(
+            remove_hooks(&mut settings, Some("/srv/work config.json")),
+            HOOKS.len()
+        );
+        assert_eq!(settings["hooks"]["Stop"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_add_deny_rule() {
+        let mut settings = json!({"permissions": {"allow": ["Read"]}});
+        assert!(add_deny_rule(&mut settings, "Bash(rm:*)"));
+        assert!(!add_deny_rule(&mut settings, "Bash(rm:*)"));
+        assert!(add_deny_rule(&mut settings, "WebFetch"));
+        assert_eq!(
+            settings["permissions"],
+            json!({"allow": ["Read"], "deny": ["Bash(rm:*)", "WebFetch"]})
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        assert!(add_deny_rule_to(&path, "Bash(curl:*)").unwrap());
+        assert!(!add_deny_rule_to(&path, "Bash(curl:*)").unwrap());
+        assert_eq!(
+            read_settings(&path).unwrap(),
+            json!({"permissions": {"deny": ["Bash(curl:*)"]}})
+        );
+    }
+}
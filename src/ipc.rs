@@ -0,0 +1,298 @@
+//! Local connections between hook processes and the daemon.
+//!
+//! On macOS and Linux the daemon listens on a Unix socket in the state
+//! directory that only its user may open. On Windows it listens on a named
+//! pipe named after the state directory that only its user may open, and
+//! remote clients are refused. Since other users can guess the name and
+//! create the pipe first, hook processes check that the process serving it
+//! runs as their user before sending a request.
+//!
+//! Each message is one line of JSON, since a named pipe cannot be half
+//! closed to mark the end of a message.
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+#[cfg(unix)]
+pub use unix::{bind, connect, remove};
+#[cfg(windows)]
+pub use windows::{bind, connect, remove};
+
+/// Write a message as one line.
+pub async fn send<S, T>(stream: &mut S, message: &T) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).await?;
+    stream.flush().await
+}
+
+/// Read one message, ending at a newline or the end of the stream.
+pub async fn receive<S, T>(stream: &mut S) -> io::Result<T>
+where
+    S: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use anyhow::{bail, Context};
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    /// Hook processes waiting to be served.
+    pub struct Listener(UnixListener);
+
+    impl Listener {
+        /// The next hook process to connect.
+        pub async fn accept(&mut self) -> io::Result<UnixStream> {
+            self.0.accept().await.map(|(stream, _)| stream)
+        }
+    }
+
+    /// Listen on the socket, replacing one left behind by a daemon that is gone.
+    pub async fn bind(socket: &Path) -> Result<Listener> {
+        if socket.exists() {
+            if UnixStream::connect(socket).await.is_ok() {
+                bail!("A daemon is already listening on {}", socket.display());
+            }
+            fs::remove_file(socket)?;
+        }
+        if let Some(parent) = socket.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let listener = UnixListener::bind(socket)
+            .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+        // Only this user may submit requests or read decisions
+        fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+        Ok(Listener(listener))
+    }
+
+    /// Connect to a listening daemon.
+    pub async fn connect(socket: &Path) -> io::Result<UnixStream> {
+        UnixStream::connect(socket).await
+    }
+
+    /// Remove the socket once the daemon stops.
+    pub fn remove(socket: &Path) {
+        let _ = fs::remove_file(socket);
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::*;
+    use anyhow::{anyhow, Context};
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::AsRawHandle;
+    use std::ptr::null_mut;
+    use std::time::{Duration, Instant};
+    use tokio::net::windows::named_pipe::{
+        ClientOptions, NamedPipeClient, NamedPipeServer, ServerOptions,
+    };
+    use windows_sys::Win32::Foundation::{CloseHandle, LocalFree, HANDLE};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+        SDDL_REVISION_1,
+    };
+    use windows_sys::Win32::Security::{
+        GetTokenInformation, TokenUser, SECURITY_ATTRIBUTES, TOKEN_QUERY, TOKEN_USER,
+    };
+    use windows_sys::Win32::System::Pipes::GetNamedPipeServerProcessId;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    /// Every instance of the pipe is taken by another hook process.
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    /// Longest wait for a free instance of the pipe.
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Pause between tries while every instance is busy.
+    const BUSY_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+    /// Closes a handle when dropped.
+    struct Handle(HANDLE);
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    /// The SID (`S-1-5-21-...`) of the user a process runs as.
+    fn process_user(process: HANDLE) -> io::Result<String> {
+        unsafe {
+            let mut token = null_mut();
+            if OpenProcessToken(process, TOKEN_QUERY, &mut token) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let token = Handle(token);
+
+            // The first call only asks for the size; u64s keep it aligned
+            let mut size = 0;
+            GetTokenInformation(token.0, TokenUser, null_mut(), 0, &mut size);
+            let mut buffer = vec![0u64; (size as usize).div_ceil(8)];
+            if GetTokenInformation(
+                token.0,
+                TokenUser,
+                buffer.as_mut_ptr().cast(),
+                size,
+                &mut size,
+            ) == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            let user = &*buffer.as_ptr().cast::<TOKEN_USER>();
+
+            let mut sid = null_mut();
+            if ConvertSidToStringSidW(user.User.Sid, &mut sid) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let len = (0..).take_while(|&i| *sid.add(i) != 0).count();
+            let string = String::from_utf16_lossy(std::slice::from_raw_parts(sid, len));
+            LocalFree(sid.cast());
+            Ok(string)
+        }
+    }
+
+    fn current_user() -> io::Result<String> {
+        process_user(unsafe { GetCurrentProcess() })
+    }
+
+    /// Create an instance of the pipe that only this user may open.
+    fn create(options: &ServerOptions, name: &OsStr) -> io::Result<NamedPipeServer> {
+        // A protected DACL, so nothing is inherited past the one entry
+        let sddl: Vec<u16> = OsStr::new(&format!("D:P(A;;GA;;;{})", current_user()?))
+            .encode_wide()
+            .chain(Some(0))
+            .collect();
+        let mut descriptor = null_mut();
+        if unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                null_mut(),
+            )
+        } == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        let mut attributes = SECURITY_ATTRIBUTES {
+            nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: descriptor,
+            bInheritHandle: 0,
+        };
+        let server = unsafe {
+            options.create_with_security_attributes_raw(
+                name,
+                (&mut attributes as *mut SECURITY_ATTRIBUTES).cast(),
+            )
+        };
+        unsafe { LocalFree(descriptor) };
+        server
+    }
+
+    /// Refuse a pipe served by a process of another user, which could have
+    /// taken the name before the daemon started.
+    fn check_server(pipe: &NamedPipeClient) -> io::Result<()> {
+        let mut process_id = 0;
+        if unsafe { GetNamedPipeServerProcessId(pipe.as_raw_handle(), &mut process_id) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, process_id) };
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let process = Handle(process);
+        if process_user(process.0)? != current_user()? {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "the pipe is served by another user's process ({})",
+                    process_id
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Hook processes waiting to be served.
+    ///
+    /// A named pipe instance serves one client, so a fresh one is created
+    /// for the next hook process as soon as one connects.
+    pub struct Listener {
+        name: std::ffi::OsString,
+        next: NamedPipeServer,
+    }
+
+    impl Listener {
+        /// The next hook process to connect.
+        pub async fn accept(&mut self) -> io::Result<NamedPipeServer> {
+            self.next.connect().await?;
+            let next = create(ServerOptions::new().reject_remote_clients(true), &self.name)?;
+            Ok(std::mem::replace(&mut self.next, next))
+        }
+    }
+
+    /// Create the first instance of the pipe, failing if another daemon
+    /// already owns it.
+    pub async fn bind(pipe: &Path) -> Result<Listener> {
+        let name = pipe.as_os_str().to_os_string();
+        let next = create(
+            ServerOptions::new()
+                .first_pipe_instance(true)
+                .reject_remote_clients(true),
+            &name,
+        )
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => {
+                anyhow!("A daemon is already listening on {}", pipe.display())
+            }
+            _ => anyhow!(e),
+        })
+        .with_context(|| format!("Failed to listen on {}", pipe.display()))?;
+        Ok(Listener { name, next })
+    }
+
+    /// Connect to a listening daemon run by this user, waiting a while if
+    /// all instances are busy.
+    pub async fn connect(pipe: &Path) -> io::Result<NamedPipeClient> {
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let client = loop {
+            match ClientOptions::new().open(pipe.as_os_str()) {
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {}
+                result => break result?,
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "every instance of the daemon's pipe stayed busy",
+                ));
+            }
+            tokio::time::sleep(BUSY_RETRY_DELAY).await;
+        };
+        check_server(&client)?;
+        Ok(client)
+    }
+
+    /// Named pipes go away with the daemon.
+    pub fn remove(_pipe: &Path) {}
+}
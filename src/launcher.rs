@@ -0,0 +1,196 @@
+//! Claude Code sessions started from chat with `/run`.
+//!
+//! A run starts `claude -p <prompt>` in a detached tmux session inside one of
+//! the configured project directories, with a session ID chosen up front so
+//! it can be reported right away. Once the tmux session ends, the usual Stop
+//! summary is built from the session's transcript and sent back. The run's
+//! own Stop hook stays quiet, so the summary is not delivered twice.
+
+use crate::config::{default_claude_projects_path, RemoteRunConfig};
+use crate::stop_handler::{StopEvent, REMOTE_RUN_ENV};
+use crate::watch::find_session_transcript;
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// How often a running session is checked for exit.
+const EXIT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A Claude Code session started in tmux.
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub project: String,
+    pub cwd: PathBuf,
+    pub session_id: String,
+    pub tmux_session: String,
+}
+
+/// Split `/run` arguments into the project name and the prompt.
+pub fn parse_run_args(args: &str) -> Option<(&str, &str)> {
+    let (project, prompt) = args.trim().split_once(char::is_whitespace)?;
+    let prompt = prompt.trim();
+    (!prompt.is_empty()).then_some((project, prompt))
+}
+
+/// Check a run request against the configuration.
+pub fn plan(config: &RemoteRunConfig, project: &str) -> Result<Run> {
+    if !config.enabled {
+        bail!("Remote runs are disabled; set preferences.remote_runs.enabled");
+    }
+    let Some(cwd) = config.projects.get(project) else {
+        let known: Vec<&str> = config.projects.keys().map(String::as_str).collect();
+        bail!(
+            "Unknown project `{}` (configured: {})",
+            project,
+            if known.is_empty() {
+                "none".to_string()
+            } else {
+                known.join(", ")
+            }
+        );
+    };
+    if !cwd.is_dir() {
+        bail!("Project directory {} does not exist", cwd.display());
+    }
+
+    let session_id = uuid::Uuid::new_v4().to_string();
+    Ok(Run {
+        project: project.to_string(),
+        cwd: cwd.clone(),
+        tmux_session: format!("ccr-{}", &session_id[..8]),
+        session_id,
+    })
+}
+
+/// Arguments for `tmux` that start the run detached.
+///
+/// The prompt is passed as its own argument, never through a shell.
+fn tmux_args(run: &Run, claude_command: &str, prompt: &str) -> Vec<String> {
+    [
+        "new-session",
+        "-d",
+        "-s",
+        &run.tmux_session,
+        "-c",
+        &run.cwd.to_string_lossy(),
+        "-e",
+        &format!("{}={}", REMOTE_RUN_ENV, run.session_id),
+        claude_command,
+        "-p",
+        prompt,
+        "--session-id",
+        &run.session_id,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+/// Start a planned run in tmux.
+pub fn start(run: &Run, config: &RemoteRunConfig, prompt: &str) -> Result<()> {
+    let status = Command::new("tmux")
+        .args(tmux_args(run, &config.claude_command, prompt))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("Failed to run tmux")?;
+    if !status.success() {
+        bail!("tmux exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Check whether the run's tmux session still exists.
+fn is_running(run: &Run) -> bool {
+    Command::new("tmux")
+        .args(["has-session", "-t", &format!("={}", run.tmux_session)])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Wait for the run to exit and return its Stop event, if it left a transcript.
+pub async fn wait_for_exit(run: &Run) -> Option<StopEvent> {
+    while is_running(run) {
+        sleep(EXIT_POLL_INTERVAL).await;
+    }
+
+    let transcript_path =
+        find_session_transcript(&default_claude_projects_path(), &run.session_id)?;
+    Some(StopEvent {
+        session_id: run.session_id.clone(),
+        transcript_path,
+        cwd: run.cwd.clone(),
+        stop_hook_active: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_run_args() {
+        assert_eq!(
+            parse_run_args(" api  fix the flaky test "),
+            Some(("api", "fix the flaky test"))
+        );
+        assert_eq!(parse_run_args("api"), None);
+        assert_eq!(parse_run_args(""), None);
+    }
+
+    #[test]
+    fn test_plan_checks_projects() {
+        let dir = tempdir().unwrap();
+        let mut config = RemoteRunConfig {
+            enabled: false,
+            projects: BTreeMap::from([("api".to_string(), dir.path().to_path_buf())]),
+            claude_command: "claude".to_string(),
+        };
+        assert!(plan(&config, "api").is_err());
+
+        config.enabled = true;
+        let error = plan(&config, "web").unwrap_err().to_string();
+        assert!(error.contains("configured: api"));
+
+        let run = plan(&config, "api").unwrap();
+        assert_eq!(run.cwd, dir.path());
+        assert!(run.tmux_session.starts_with("ccr-"));
+        assert!(run.session_id.starts_with(&run.tmux_session[4..]));
+    }
+
+    #[test]
+    fn test_tmux_args_pass_prompt_verbatim() {
+        let run = Run {
+            project: "api".to_string(),
+            cwd: PathBuf::from("/src/api"),
+            session_id: "0123abcd-0000".to_string(),
+            tmux_session: "ccr-0123abcd".to_string(),
+        };
+        let args = tmux_args(&run, "claude", "fix it; rm -rf ~");
+        assert_eq!(
+            args,
+            [
+                "new-session",
+                "-d",
+                "-s",
+                "ccr-0123abcd",
+                "-c",
+                "/src/api",
+                "-e",
+                "CCR_REMOTE_RUN=0123abcd-0000",
+                "claude",
+                "-p",
+                "fix it; rm -rf ~",
+                "--session-id",
+                "0123abcd-0000",
+            ]
+        );
+    }
+}
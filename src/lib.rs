@@ -3,21 +3,78 @@
 //! This library provides the core functionality for the Claude Code messaging integration.
 //! Supports Telegram, Discord (with the `discord` feature), and Signal (with the `signal` feature).
 
+pub mod adapters;
 pub mod always_allow;
+pub mod ask;
+pub mod audit;
+pub mod away;
+#[cfg(feature = "telegram")]
 pub mod bot;
 pub mod cli;
+pub mod client;
+pub mod clock;
+pub mod command_api;
 pub mod config;
+pub mod cost_guard;
+#[cfg(any(unix, windows))]
+pub mod daemon;
 pub mod error;
+pub mod escalation;
+pub mod explain;
+#[cfg(any(unix, windows))]
+pub mod github;
+pub mod health;
+pub mod history;
 pub mod hook_handler;
+pub mod http;
+pub mod install;
+#[cfg(any(unix, windows))]
+pub mod ipc;
+#[cfg(feature = "telegram")]
+pub mod launcher;
+pub mod lockfile;
+pub mod logging;
 pub mod messenger;
+pub mod migrate;
 pub mod notification_handler;
+pub mod observer;
+pub mod pause;
+pub mod peek;
+pub mod policy;
+pub mod policy_module;
+pub mod probe;
+pub mod prompt_dedup;
+pub mod read_only;
+pub mod redact;
+pub mod relay;
+pub mod replay;
+pub mod request_groups;
+pub mod schedule;
+pub mod serve;
+#[cfg(windows)]
+pub mod service;
+pub mod shared_state;
+pub mod simulate;
+pub mod snooze;
 pub mod stop_handler;
+pub mod suggestions;
+pub mod summary;
+#[cfg(feature = "telegram")]
 pub mod telegram;
+#[cfg(feature = "telegram")]
+pub mod tmux;
+pub mod tool_timer;
+pub mod validate;
+pub mod watch;
+pub mod web_approvals;
+pub mod webhooks;
+pub mod yolo;
 
 // Re-export commonly used types
 pub use always_allow::AlwaysAllowManager;
+pub use client::ApprovalClient;
 pub use config::Config;
-pub use hook_handler::{HookInput, HookOutput, PermissionRequest};
+pub use hook_handler::{parse_hook_input, HookInput, HookOutput, PermissionRequest};
 pub use messenger::{Decision, Messenger, PermissionMessage};
 pub use notification_handler::NotificationInput;
 pub use stop_handler::{StopEvent, StopInput};
@@ -0,0 +1,132 @@
+//! Advisory lock files shared by concurrent hook processes.
+//!
+//! A lock is a `<file>.lock` next to the file it protects, created with
+//! `create_new` and removed on drop. Locks older than `STALE_LOCK` are
+//! assumed to be left over from a crashed process and are broken. Waiting
+//! for a lock sleeps the thread, so async code takes it through
+//! [`off_reactor`].
+
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A lock file older than this is assumed to be left over from a crash.
+const STALE_LOCK: Duration = Duration::from_secs(10);
+
+/// Exclusive lock on a file, released on drop.
+#[derive(Debug)]
+pub struct FileLock(PathBuf);
+
+impl FileLock {
+    /// Wait for other processes to release the lock, breaking stale locks.
+    pub fn acquire(target: &Path) -> io::Result<Self> {
+        let path = lock_path(target);
+        for _ in 0..250 {
+            if let Some(lock) = Self::try_lock(&path)? {
+                return Ok(lock);
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("timed out waiting for {}", path.display()),
+        ))
+    }
+
+    /// Take the lock if nobody else holds it.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn try_acquire(target: &Path) -> io::Result<Option<Self>> {
+        Self::try_lock(&lock_path(target))
+    }
+
+    fn try_lock(path: &Path) -> io::Result<Option<Self>> {
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(_) => Ok(Some(Self(path.to_path_buf()))),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let stale = fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok())
+                    .is_some_and(|age| age > STALE_LOCK);
+                if stale {
+                    let _ = fs::remove_file(path);
+                }
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Replace a file's contents atomically, so readers that don't take the
+/// lock never see it empty or half written.
+///
+/// The contents are written to a temporary file next to the target, which
+/// is then renamed over it.
+pub fn write_atomic(target: &Path, contents: &str) -> io::Result<()> {
+    let mut name = OsString::from(target.as_os_str());
+    name.push(".tmp");
+    let tmp = PathBuf::from(name);
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, target)
+}
+
+/// Run blocking file IO on the blocking thread pool.
+///
+/// State files may live on a slow network home directory, and lock waits
+/// sleep the thread; neither should stall the async runtime.
+pub async fn off_reactor<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+fn lock_path(target: &Path) -> PathBuf {
+    let mut name = OsString::from(target.as_os_str());
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lock_is_exclusive_until_dropped() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("spool.json");
+
+        let lock = FileLock::try_acquire(&target).unwrap();
+        assert!(lock.is_some());
+        assert!(FileLock::try_acquire(&target).unwrap().is_none());
+
+        drop(lock);
+        assert!(FileLock::acquire(&target).is_ok());
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_contents() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("paused.json");
+
+        write_atomic(&target, "{\"paused\": []}").unwrap();
+        write_atomic(&target, "{}").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{}");
+        // Nothing is left next to the file
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
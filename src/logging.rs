@@ -0,0 +1,67 @@
+//! Tracing setup for all subcommands.
+//!
+//! Hooks run non-interactively, so verbosity and the log destination can be
+//! chosen with flags in the hook command instead of `RUST_LOG`.
+
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
+
+/// Level selected by `-v`/`-q`, or `None` to defer to `RUST_LOG`.
+fn flag_level(verbose: u8, quiet: bool) -> Option<Level> {
+    match (quiet, verbose) {
+        (true, _) => Some(Level::ERROR),
+        (false, 0) => None,
+        (false, 1) => Some(Level::DEBUG),
+        (false, _) => Some(Level::TRACE),
+    }
+}
+
+/// Build the log filter: explicit flags win over `RUST_LOG`, which wins over `info`.
+fn build_filter(verbose: u8, quiet: bool) -> EnvFilter {
+    match flag_level(verbose, quiet) {
+        Some(level) => EnvFilter::new(level.as_str()),
+        None => EnvFilter::builder()
+            .with_default_directive(Level::INFO.into())
+            .from_env_lossy(),
+    }
+}
+
+/// Initialize tracing, writing to `log_file` (appending) or stderr.
+///
+/// Stderr keeps stdout free for hook output.
+pub fn init(verbose: u8, quiet: bool, log_file: Option<&Path>) -> Result<()> {
+    let builder = tracing_subscriber::fmt().with_env_filter(build_filter(verbose, quiet));
+
+    match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            builder
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .init();
+        }
+        None => builder.with_writer(std::io::stderr).init(),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_level() {
+        assert_eq!(flag_level(0, false), None);
+        assert_eq!(flag_level(1, false), Some(Level::DEBUG));
+        assert_eq!(flag_level(3, false), Some(Level::TRACE));
+        assert_eq!(flag_level(2, true), Some(Level::ERROR));
+    }
+}
@@ -2,62 +2,246 @@
 //!
 //! Provides subcommands for hook handlers, Telegram bot, and Signal linking.
 
+mod adapters;
 mod always_allow;
+mod ask;
+mod audit;
+mod away;
+#[cfg(feature = "telegram")]
 mod bot;
 mod cli;
+mod client;
+mod clock;
+mod command_api;
 mod config;
+mod cost_guard;
+#[cfg(any(unix, windows))]
+mod daemon;
 mod error;
+mod escalation;
+mod explain;
+#[cfg(any(unix, windows))]
+mod github;
+mod health;
+mod history;
 mod hook_handler;
+mod http;
+mod install;
+#[cfg(any(unix, windows))]
+mod ipc;
+#[cfg(feature = "telegram")]
+mod launcher;
+mod lockfile;
+mod logging;
 mod messenger;
+mod migrate;
 mod notification_handler;
+mod observer;
+mod pause;
+mod peek;
+mod policy;
+mod policy_module;
+mod probe;
+mod prompt_dedup;
+mod read_only;
+mod redact;
+mod relay;
+mod replay;
+mod request_groups;
+mod schedule;
+mod serve;
+#[cfg(windows)]
+mod service;
+mod shared_state;
+mod simulate;
+mod snooze;
 mod stop_handler;
+mod suggestions;
+mod summary;
+#[cfg(feature = "telegram")]
 mod telegram;
+#[cfg(feature = "telegram")]
+mod tmux;
+mod tool_timer;
+mod validate;
+mod watch;
+mod web_approvals;
+mod webhooks;
+mod yolo;
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use cli::{Cli, Commands};
-use config::Config;
-
-#[cfg(feature = "discord")]
-use messenger::discord::DiscordMessenger;
-use messenger::telegram::TelegramMessenger;
-use messenger::Messenger;
+use clap::{CommandFactory, Parser};
+#[cfg(any(unix, windows))]
+use cli::DaemonCommands;
+use cli::{
+    AuditCommands, Cli, Commands, ConfigCommands, HistoryCommands, RulesCommands, SimulateCommands,
+};
+use config::{Config, VacationFallback};
+use messenger::TextFormat;
+use std::path::Path;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
-        )
-        .init();
-
     let cli = Cli::parse();
+    logging::init(cli.verbose, cli.quiet, cli.log_file.as_deref())?;
+
+    let config_path = cli.config.as_deref();
+    if cli.dry_run {
+        messenger::enable_dry_run();
+    }
 
     match cli.command {
-        Commands::Hook => {
-            hook_handler::run()
+        Commands::Hook { format } => {
+            hook_handler::run(config_path, &format)
                 .await
                 .context("Failed to handle permission request")?;
         }
+        #[cfg(any(unix, windows))]
+        Commands::Daemon {
+            command: Some(DaemonCommands::Pair { url }),
+            ..
+        } => {
+            command_api::pair(config_path, url.as_deref()).context("Failed to pair")?;
+        }
+        #[cfg(windows)]
+        Commands::Daemon { service: true, .. } => {
+            tokio::task::block_in_place(|| service::run(config_path))
+                .context("Failed to run hook daemon service")?;
+        }
+        #[cfg(any(unix, windows))]
+        Commands::Daemon { .. } => {
+            daemon::run(config_path)
+                .await
+                .context("Failed to run hook daemon")?;
+        }
         Commands::Stop => {
-            stop_handler::run()
+            stop_handler::run(config_path)
                 .await
                 .context("Failed to handle stop event")?;
         }
         Commands::Notify => {
-            notification_handler::run()
+            notification_handler::run(config_path)
                 .await
                 .context("Failed to handle notification")?;
         }
-        Commands::Relay { message } => {
-            relay_message(&message)
+        Commands::ToolTimer => {
+            tool_timer::run(config_path)
+                .await
+                .context("Failed to handle tool timing event")?;
+        }
+        Commands::WatchTool {
+            key,
+            started,
+            label,
+            cwd,
+        } => {
+            tool_timer::run_watcher(&key, started, &label, &cwd, config_path)
+                .await
+                .context("Failed to watch tool call")?;
+        }
+        Commands::Nudge {
+            transcript_path,
+            cwd,
+        } => {
+            notification_handler::run_idle_nudges(&transcript_path, &cwd, config_path)
+                .await
+                .context("Failed to send idle reminders")?;
+        }
+        Commands::RemindSnoozed { label, cwd } => {
+            snooze::run_reminder(&label, &cwd, config_path)
+                .await
+                .context("Failed to remind of snoozed request")?;
+        }
+        Commands::Relay {
+            message,
+            to,
+            markdown,
+            code,
+            file,
+        } => {
+            let format = if markdown {
+                TextFormat::Markdown
+            } else if code {
+                TextFormat::Code
+            } else {
+                TextFormat::Plain
+            };
+            relay::run(
+                config_path,
+                message.as_deref(),
+                to.as_deref(),
+                format,
+                file.as_deref(),
+            )
+            .await
+            .context("Failed to relay message")?;
+        }
+        Commands::Serve { .. } => {
+            serve::run_stdio(config_path)
                 .await
-                .context("Failed to relay message")?;
+                .context("Failed to serve JSON-RPC")?;
+        }
+        Commands::Ask { question, timeout } => {
+            match ask::run(config_path, &question, timeout).await {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("Error: {:#}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Commands::Watch {
+            session,
+            transcript,
+            interval,
+        } => {
+            watch::run(
+                config_path,
+                session.as_deref(),
+                transcript.as_deref(),
+                interval,
+            )
+            .await
+            .context("Failed to watch session")?;
         }
+        Commands::Logs { lines, follow } => {
+            history::run_logs(lines, follow).await?;
+        }
+        Commands::History {
+            command:
+                HistoryCommands::Export {
+                    format,
+                    since,
+                    redact,
+                },
+        } => {
+            history::run_export(config_path, &format, since, redact)
+                .context("Failed to export history")?;
+        }
+        Commands::Audit {
+            command: AuditCommands::Verify { path },
+        } => {
+            if !audit::run_verify(config_path, path.as_deref())? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Rules {
+            command: RulesCommands::Suggest { apply },
+        } => {
+            suggestions::run(config_path, apply).context("Failed to suggest rules")?;
+        }
+        Commands::Rules {
+            command: RulesCommands::Replay { since, allow },
+        } => {
+            replay::run(config_path, since, allow).context("Failed to replay history")?;
+        }
+        #[cfg(feature = "telegram")]
         Commands::Bot => {
-            bot::run().await.context("Failed to run Telegram bot")?;
+            bot::run(config_path)
+                .await
+                .context("Failed to run Telegram bot")?;
         }
         #[cfg(feature = "signal")]
         Commands::SignalLink {
@@ -81,62 +265,124 @@ async fn main() -> Result<()> {
             println!("You can now use Signal for permission requests.");
         }
         Commands::Status => {
-            print_status().await?;
+            print_status(config_path).await?;
         }
-    }
-
-    Ok(())
-}
-
-/// Relay a custom message to configured messengers.
-async fn relay_message(message: &str) -> Result<()> {
-    let config = Config::load(None)?;
-
-    // Try Discord if configured as primary
-    #[cfg(feature = "discord")]
-    if config.primary_messenger == "discord" {
-        if let Some(ref discord_config) = config.discord {
-            if discord_config.enabled {
-                let messenger =
-                    DiscordMessenger::new(&discord_config.bot_token, discord_config.user_id);
-                messenger.send_notification(message).await?;
-                return Ok(());
+        Commands::Completions { shell } => {
+            let mut command = completion_command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        }
+        Commands::Test { messenger } => {
+            if !probe::run(config_path, messenger.as_deref()).await? {
+                std::process::exit(1);
             }
         }
+        Commands::Simulate { command } => {
+            match command {
+                SimulateCommands::Permission {
+                    tool,
+                    command,
+                    file_path,
+                } => {
+                    simulate::permission(
+                        config_path,
+                        &tool,
+                        command.as_deref(),
+                        file_path.as_deref(),
+                    )
+                    .await
+                }
+                SimulateCommands::Stop { message } => simulate::stop(config_path, &message).await,
+                SimulateCommands::Notify {
+                    notification_type,
+                    message,
+                } => simulate::notification(config_path, &notification_type, &message).await,
+            }
+            .context("Simulation failed")?;
+        }
+        Commands::Config {
+            command: ConfigCommands::Validate { path },
+        } => {
+            let path = path.or_else(|| cli.config.clone()).unwrap_or_else(|| {
+                let new_path = config::default_config_path();
+                if new_path.exists() {
+                    new_path
+                } else {
+                    config::legacy_config_path()
+                }
+            });
+            let errors = validate::run(&path)?;
+            if errors > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Config {
+            command: ConfigCommands::Schema,
+        } => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&config::config_schema())?
+            );
+        }
+        Commands::Config {
+            command:
+                ConfigCommands::Migrate {
+                    force,
+                    delete_legacy,
+                },
+        } => {
+            let new_path = cli
+                .config
+                .clone()
+                .unwrap_or_else(config::default_config_path);
+            migrate::run(
+                &config::legacy_config_path(),
+                &new_path,
+                force,
+                delete_legacy,
+            )?;
+        }
+        Commands::InstallHooks { settings } => {
+            let settings = settings.unwrap_or_else(config::default_claude_settings_path);
+            install::run_install(&settings, config_path).context("Failed to install hooks")?;
+        }
+        Commands::UninstallHooks { settings } => {
+            let settings = settings.unwrap_or_else(config::default_claude_settings_path);
+            install::run_uninstall(&settings, config_path).context("Failed to uninstall hooks")?;
+        }
     }
 
-    // Try Telegram if configured
-    if let Some(ref telegram_config) = config.telegram {
-        let messenger = TelegramMessenger::new(&telegram_config.bot_token, telegram_config.chat_id);
-        messenger.send_notification(message).await?;
-        return Ok(());
-    }
+    Ok(())
+}
 
-    // Try Discord as fallback
-    #[cfg(feature = "discord")]
-    if let Some(ref discord_config) = config.discord {
-        if discord_config.enabled {
-            let messenger =
-                DiscordMessenger::new(&discord_config.bot_token, discord_config.user_id);
-            messenger.send_notification(message).await?;
-            return Ok(());
-        }
-    }
+/// CLI definition for completions, without hidden internal subcommands.
+fn completion_command() -> clap::Command {
+    let command = Cli::command();
+    let visible: Vec<clap::Command> = command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .cloned()
+        .collect();
 
-    anyhow::bail!("No messenger configured")
+    clap::Command::new(env!("CARGO_BIN_NAME"))
+        .args(command.get_arguments().cloned())
+        .subcommands(visible)
 }
 
 /// Print configuration status.
-async fn print_status() -> Result<()> {
+async fn print_status(config_path: Option<&Path>) -> Result<()> {
     println!("📊 Claude Code Messaging Status\n");
 
     // Try to load config
-    match Config::load(None) {
+    match Config::load(config_path.map(Path::to_path_buf)) {
         Ok(config) => {
             println!("✅ Configuration: Found");
             println!("   Hostname: {}", config.hostname);
+            if config.host_label != config.hostname {
+                println!("   Shown as: {}", config.host_label);
+            }
             println!("   Timeout: {}s", config.timeout_seconds);
-            println!("   Primary: {}", config.primary_messenger);
+            println!("   Priority: {}", config.messenger_priority.join(" → "));
             println!();
             println!("📱 Telegram:");
             if let Some(telegram) = &config.telegram {
@@ -173,6 +419,24 @@ async fn print_status() -> Result<()> {
                 println!();
                 println!("📱 Signal: Not available (compile with --features signal)");
             }
+
+            if let Some(vacation) = away::Away::new(None).vacation() {
+                println!();
+                let decided = match config.vacation_fallback {
+                    VacationFallback::Deny => "denied",
+                    VacationFallback::Allow => "allowed",
+                };
+                println!(
+                    "🏖️ Vacation mode: on (by {}); requests are {} without asking",
+                    vacation.by, decided
+                );
+            }
+
+            for plugin in &config.plugins {
+                println!();
+                println!("🧩 Plugin {}:", plugin.name);
+                println!("   Command: {}", plugin.command.display());
+            }
         }
         Err(e) => {
             println!("❌ Configuration: Not found or invalid");
@@ -183,5 +447,31 @@ async fn print_status() -> Result<()> {
         }
     }
 
+    let policy = policy::Policy::load_system();
+    println!();
+    match (&policy.path, &policy.error) {
+        (None, _) => println!(
+            "🏢 Organization policy: None ({})",
+            policy::system_policy_path().display()
+        ),
+        (Some(path), Some(error)) => {
+            println!("🏢 Organization policy: ❌ {} is invalid", path.display());
+            println!("   Error: {}", error);
+            println!("   All permission requests are denied until it is fixed");
+        }
+        (Some(path), None) => println!(
+            "🏢 Organization policy: {} ({} deny rule(s))",
+            path.display(),
+            policy.deny.len()
+        ),
+    }
+
+    let stats = history::decision_stats(Duration::from_secs(history::STATS_DAYS * 86400));
+    println!();
+    println!("⏱️ Decisions (last {} days):", history::STATS_DAYS);
+    for line in stats.lines() {
+        println!("   {}", line);
+    }
+
     Ok(())
 }
@@ -0,0 +1,101 @@
+//! HTTP clients shared by everything one process sends.
+//!
+//! Every `reqwest::Client` (and every `Bot` built with `Bot::new`) owns its
+//! own connection pool, so each new one pays for a fresh TLS handshake. A
+//! hook invocation can build the same messenger several times over (routing,
+//! notifications, summaries), so clients are created once per process and
+//! handed out as cheap clones.
+
+#[cfg(feature = "telegram")]
+use std::collections::HashMap;
+#[cfg(feature = "telegram")]
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::Duration;
+#[cfg(feature = "telegram")]
+use teloxide::Bot;
+
+/// Names a Telegram Bot API server to use instead of `api.telegram.org`,
+/// such as a self-hosted one or a test double.
+pub const TELEGRAM_API_URL_ENV: &str = "CCR_TELEGRAM_API_URL";
+
+/// Base URL of the Telegram Bot API, without a trailing slash.
+pub fn telegram_api_url() -> String {
+    std::env::var(TELEGRAM_API_URL_ENV)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| "https://api.telegram.org".to_string())
+}
+
+/// Process-wide HTTP client and per-token Telegram bots.
+pub struct Clients {
+    http: reqwest::Client,
+    #[cfg(feature = "telegram")]
+    telegram_bots: Mutex<HashMap<String, Bot>>,
+}
+
+impl Clients {
+    fn new() -> Self {
+        Self {
+            // Same settings `Bot::new` would use
+            http: reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(5))
+                .timeout(Duration::from_secs(17))
+                .tcp_nodelay(true)
+                .build()
+                .expect("creating reqwest::Client"),
+            #[cfg(feature = "telegram")]
+            telegram_bots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The shared HTTP client; set per-request timeouts as needed.
+    pub fn http(&self) -> reqwest::Client {
+        self.http.clone()
+    }
+
+    /// The Telegram bot for a token, created on first use.
+    #[cfg(feature = "telegram")]
+    pub fn telegram_bot(&self, bot_token: &str) -> Bot {
+        let mut bots = self
+            .telegram_bots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        bots.entry(bot_token.to_string())
+            .or_insert_with(|| {
+                let bot = Bot::with_client(bot_token, self.http.clone());
+                match reqwest::Url::parse(&telegram_api_url()) {
+                    Ok(url) => bot.set_api_url(url),
+                    Err(e) => {
+                        tracing::warn!("Ignoring {}: {}", TELEGRAM_API_URL_ENV, e);
+                        bot
+                    }
+                }
+            })
+            .clone()
+    }
+}
+
+/// Clients for this process.
+pub fn shared() -> &'static Clients {
+    static CLIENTS: OnceLock<Clients> = OnceLock::new();
+    CLIENTS.get_or_init(Clients::new)
+}
+
+#[cfg(all(test, feature = "telegram"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bots_are_reused_per_token() {
+        let clients = Clients::new();
+        let first = clients.telegram_bot("123:abc");
+        let again = clients.telegram_bot("123:abc");
+        let other = clients.telegram_bot("456:def");
+
+        assert_eq!(first.token(), again.token());
+        assert_eq!(other.token(), "456:def");
+        assert_eq!(clients.telegram_bots.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,118 @@
+//! One-line message profile for smartwatches and SMS gateways.
+//!
+//! Messengers with `compact: true` send requests as a single plain line,
+//! e.g. `🔐 laptop:Bash "cargo test" → ✅?`, instead of the full layout.
+//! Requests end with their approval link, if any, for gateways that drop
+//! buttons.
+
+use super::PermissionMessage;
+
+/// Longest tool detail kept in a line.
+const MAX_DETAIL_CHARS: usize = 60;
+
+/// `host:Tool "detail"`, with the detail squeezed onto one line.
+fn subject(message: &PermissionMessage) -> String {
+    format!("{}:{}", message.host(), item_line(message))
+}
+
+/// Collapse whitespace and shorten to `MAX_DETAIL_CHARS`.
+fn one_line(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= MAX_DETAIL_CHARS {
+        return text;
+    }
+    let truncated: String = text.chars().take(MAX_DETAIL_CHARS - 1).collect();
+    format!("{}…", truncated)
+}
+
+/// A request within a group: `Tool "detail"`.
+pub fn item_line(message: &PermissionMessage) -> String {
+    match message.detail() {
+        Some(detail) => format!("{} \"{}\"", message.tool_label(), one_line(&detail)),
+        None => message.tool_label(),
+    }
+}
+
+/// A permission request waiting for a decision.
+pub fn permission_line(message: &PermissionMessage) -> String {
+    let quorum = if message.required_approvals > 1 {
+        format!(" ({} approvals)", message.required_approvals)
+    } else {
+        String::new()
+    };
+    let link = message
+        .approval_link
+        .as_deref()
+        .map(|link| format!(" {}", link))
+        .unwrap_or_default();
+    format!("🔐 {}{} → ✅?{}", subject(message), quorum, link)
+}
+
+/// A request approved from the always-allow list.
+pub fn auto_approved_line(message: &PermissionMessage) -> String {
+    format!("⚙️ {} → ✅ auto", subject(message))
+}
+
+/// A yes/no question.
+pub fn question_line(hostname: &str, question: &str) -> String {
+    format!(
+        "❓ {}: {} → ✅?",
+        hostname,
+        question.split_whitespace().collect::<Vec<_>>().join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compact_lines() {
+        let message = PermissionMessage::new(
+            "abc12345".to_string(),
+            "Bash".to_string(),
+            "laptop".to_string(),
+            json!({"command": "cargo test\n  --all"}),
+        );
+        assert_eq!(
+            permission_line(&message),
+            "🔐 laptop:Bash \"cargo test --all\" → ✅?"
+        );
+        assert_eq!(
+            auto_approved_line(&message),
+            "⚙️ laptop:Bash \"cargo test --all\" → ✅ auto"
+        );
+        let linked = message
+            .clone()
+            .with_approval_link(Some("https://a.example/approve/abc12345".to_string()));
+        assert_eq!(
+            permission_line(&linked),
+            "🔐 laptop:Bash \"cargo test --all\" → ✅? https://a.example/approve/abc12345"
+        );
+        let labeled = message.with_host_label(Some("🟦 gpu-box".to_string()));
+        assert_eq!(
+            permission_line(&labeled),
+            "🔐 🟦 gpu-box:Bash \"cargo test --all\" → ✅?"
+        );
+
+        let message = PermissionMessage::new(
+            "abc12345".to_string(),
+            "mcp__db__query".to_string(),
+            "laptop".to_string(),
+            json!({"sql": "select 1"}),
+        )
+        .with_required_approvals(2);
+        assert_eq!(
+            permission_line(&message),
+            "🔐 laptop:mcp__db__query (2 approvals) → ✅?"
+        );
+
+        let long = "x".repeat(100);
+        assert_eq!(one_line(&long).chars().count(), MAX_DETAIL_CHARS);
+        assert_eq!(
+            question_line("ci", "Deploy\nnow?"),
+            "❓ ci: Deploy now? → ✅?"
+        );
+    }
+}
@@ -3,13 +3,20 @@
 //! Implements the Messenger trait for Discord using interactive buttons
 //! for permission decisions.
 
-use super::{Decision, Messenger, PermissionMessage};
+use super::ledger::{self, PostedMessage, RequestDetails, RequestState};
+use super::types::SHOWN_EDITS;
+use super::{
+    cancelled, compact, new_request_id, Decision, Messenger, PermissionMessage, TextFormat,
+};
 use crate::error::HookError;
+use crate::snooze::SNOOZE_MINUTES;
 use async_trait::async_trait;
 use serenity::all::{
-    ButtonStyle, ChannelId, CreateActionRow, CreateButton, CreateMessage, EditMessage, Http,
-    MessageId, UserId,
+    ButtonStyle, ChannelId, CreateActionRow, CreateAttachment, CreateButton, CreateMessage,
+    EditMessage, Http, MessageId, UserId,
 };
+use std::num::NonZeroU64;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::{interval, timeout};
@@ -19,6 +26,8 @@ use tokio::time::{interval, timeout};
 pub struct DiscordMessenger {
     http: Arc<Http>,
     user_id: UserId,
+    /// Send one-line requests
+    compact: bool,
 }
 
 #[allow(dead_code)]
@@ -28,9 +37,16 @@ impl DiscordMessenger {
         Self {
             http: Arc::new(Http::new(bot_token)),
             user_id: UserId::new(user_id),
+            compact: false,
         }
     }
 
+    /// Send requests as one plain line, for smartwatches and SMS.
+    pub fn with_compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
     /// Get or create a DM channel with the user.
     async fn get_dm_channel(&self) -> Result<ChannelId, HookError> {
         let user = self
@@ -48,23 +64,25 @@ impl DiscordMessenger {
     }
 }
 
-#[async_trait]
-impl Messenger for DiscordMessenger {
-    async fn send_permission_request(
+impl DiscordMessenger {
+    /// Send a message with decision buttons and wait for a button press.
+    ///
+    /// The message is edited with `status(decision)` once answered, or
+    /// marked as denied when the timeout expires. Requests with `details`
+    /// can also be decided through their approval link.
+    async fn request_decision(
         &self,
-        message: &PermissionMessage,
+        text: &str,
+        buttons: CreateActionRow,
+        request_id: &str,
         request_timeout: Duration,
+        details: Option<RequestDetails>,
+        status: impl Fn(Decision) -> String + Send,
     ) -> Result<Decision, HookError> {
         let channel_id = self.get_dm_channel().await?;
 
-        // Create buttons
-        let buttons = create_permission_buttons(&message.request_id);
-        let original_message = format_permission_message(message);
-
         // Send message with buttons
-        let builder = CreateMessage::new()
-            .content(&original_message)
-            .components(vec![buttons]);
+        let builder = CreateMessage::new().content(text).components(vec![buttons]);
 
         let sent = channel_id
             .send_message(&self.http, builder)
@@ -72,27 +90,44 @@ impl Messenger for DiscordMessenger {
             .map_err(|e| HookError::Discord(format!("Failed to send message: {}", e)))?;
 
         let message_id = sent.id;
-
-        // Poll for button interaction with timeout
-        let poll_result = timeout(
-            request_timeout,
-            poll_for_interaction(&self.http, channel_id, message_id, &message.request_id),
-        )
-        .await;
+        ledger::open_with_details(request_id, request_timeout, details).await;
+
+        // Poll for button interaction with timeout, unless Claude gives up first
+        let poll_result = tokio::select! {
+            result = timeout(
+                request_timeout,
+                poll_for_interaction(&self.http, channel_id, message_id, request_id),
+            ) => result,
+            _ = cancelled() => {
+                ledger::transition(request_id, RequestState::Cancelled).await;
+                ledger::keep_message(
+                    request_id,
+                    PostedMessage {
+                        platform: self.platform_name().to_string(),
+                        chat_id: channel_id.get().to_string(),
+                        message_id: message_id.get().to_string(),
+                        text: text.to_string(),
+                    },
+                )
+                .await;
+                let _ = channel_id
+                    .edit_message(
+                        &self.http,
+                        message_id,
+                        EditMessage::new()
+                            .content(format!("{}\n\n**Status:** 🚫 Cancelled by Claude", text))
+                            .components(vec![]),
+                    )
+                    .await;
+                return Err(HookError::Cancelled);
+            }
+        };
 
         match poll_result {
             Ok(Ok(callback_decision)) => {
-                // Determine status text
-                let status = match callback_decision {
-                    Decision::Allow => "✅ Approved",
-                    Decision::Deny => "❌ Denied",
-                    Decision::AlwaysAllow => {
-                        &format!("🔓 Always Allowed (`{}` added to list)", message.tool_name)
-                    }
-                };
-
+                ledger::transition(request_id, RequestState::Answered).await;
                 // Update message with status (remove buttons)
-                let new_text = format!("{}\n\n**Status:** {}", original_message, status);
+                let new_text = format!("{}\n\n**Status:** {}", text, status(callback_decision));
                 let edit_builder = EditMessage::new().content(new_text).components(vec![]);
 
                 let _ = channel_id
@@ -103,12 +138,13 @@ impl Messenger for DiscordMessenger {
             }
             Ok(Err(e)) => {
                 // Error during polling
+                ledger::transition(request_id, RequestState::Cancelled).await;
                 let _ = channel_id
                     .edit_message(
                         &self.http,
                         message_id,
                         EditMessage::new()
-                            .content(format!("{}\n\n**Status:** ❌ Error", original_message))
+                            .content(format!("{}\n\n**Status:** ❌ Error", text))
                             .components(vec![]),
                     )
                     .await;
@@ -116,15 +152,13 @@ impl Messenger for DiscordMessenger {
             }
             Err(_) => {
                 // Timeout - deny by default
+                ledger::transition(request_id, RequestState::TimedOut).await;
                 let _ = channel_id
                     .edit_message(
                         &self.http,
                         message_id,
                         EditMessage::new()
-                            .content(format!(
-                                "{}\n\n**Status:** ⏱️ Timeout - Denied",
-                                original_message
-                            ))
+                            .content(format!("{}\n\n**Status:** ⏱️ Timeout - Denied", text))
                             .components(vec![]),
                     )
                     .await;
@@ -132,6 +166,75 @@ impl Messenger for DiscordMessenger {
             }
         }
     }
+}
+
+#[async_trait]
+impl Messenger for DiscordMessenger {
+    async fn send_permission_request(
+        &self,
+        message: &PermissionMessage,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let buttons = create_permission_buttons(&message.request_id);
+        let text = if self.compact {
+            compact::permission_line(message)
+        } else {
+            format_permission_message(message)
+        };
+
+        self.request_decision(
+            &text,
+            buttons,
+            &message.request_id,
+            request_timeout,
+            Some(RequestDetails::of(message)),
+            |decision| match decision {
+                Decision::Allow | Decision::ApplySuggestion(_) => "✅ Approved".to_string(),
+                Decision::Deny => "❌ Denied".to_string(),
+                Decision::DenyAndBlock => {
+                    format!("🚫 Denied and blocked `{}`", message.block_rule())
+                }
+                Decision::Snooze => format!("⏰ Snoozed for {} min", SNOOZE_MINUTES),
+                Decision::AlwaysAllow => {
+                    format!(
+                        "🔓 Always Allowed (`{}` added to list)",
+                        message.always_allow_entry()
+                    )
+                }
+            },
+        )
+        .await
+    }
+
+    async fn send_question(
+        &self,
+        question: &str,
+        hostname: &str,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let request_id = new_request_id();
+        let text = if self.compact {
+            compact::question_line(hostname, question)
+        } else {
+            format!(
+                "❓ **Question** [{}]\n🖥️ **Host:** {}\n\n{}",
+                request_id, hostname, question
+            )
+        };
+
+        self.request_decision(
+            &text,
+            create_question_buttons(&request_id),
+            &request_id,
+            request_timeout,
+            None,
+            |decision| match decision {
+                Decision::Deny => "❌ Denied".to_string(),
+                _ => "✅ Approved".to_string(),
+            },
+        )
+        .await
+    }
 
     async fn send_notification(&self, text: &str) -> Result<(), HookError> {
         let channel_id = self.get_dm_channel().await?;
@@ -146,16 +249,71 @@ impl Messenger for DiscordMessenger {
         Ok(())
     }
 
+    async fn send_text(&self, text: &str, format: TextFormat) -> Result<(), HookError> {
+        self.send_notification(&format_text(text, format)).await
+    }
+
+    async fn send_file(&self, path: &Path) -> Result<(), HookError> {
+        let channel_id = self.get_dm_channel().await?;
+
+        let attachment = CreateAttachment::path(path)
+            .await
+            .map_err(|e| HookError::Discord(format!("Failed to read attachment: {}", e)))?;
+
+        channel_id
+            .send_message(&self.http, CreateMessage::new().add_file(attachment))
+            .await
+            .map_err(|e| HookError::Discord(format!("Failed to send file: {}", e)))?;
+
+        Ok(())
+    }
+
     async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError> {
-        let text = format_auto_approved_message(message);
+        let text = if self.compact {
+            compact::auto_approved_line(message)
+        } else {
+            format_auto_approved_message(message)
+        };
         self.send_notification(&text).await
     }
 
+    async fn mark_handled_locally(&self, message: &PostedMessage) -> Result<(), HookError> {
+        let (Ok(channel_id), Ok(message_id)) = (
+            message.chat_id.parse::<NonZeroU64>(),
+            message.message_id.parse::<NonZeroU64>(),
+        ) else {
+            return Ok(());
+        };
+        ChannelId::from(channel_id)
+            .edit_message(
+                &self.http,
+                MessageId::from(message_id),
+                EditMessage::new()
+                    .content(format!(
+                        "{}\n\n**Status:** ✅ Handled locally",
+                        message.text
+                    ))
+                    .components(vec![]),
+            )
+            .await
+            .map_err(|e| HookError::Discord(format!("Failed to edit message: {}", e)))?;
+        Ok(())
+    }
+
     fn platform_name(&self) -> &'static str {
         "Discord"
     }
 }
 
+/// Render free-form text as Discord markdown.
+fn format_text(text: &str, format: TextFormat) -> String {
+    match format {
+        TextFormat::Plain | TextFormat::Markdown => text.to_string(),
+        // A zero-width space keeps embedded fences from closing the block
+        TextFormat::Code => format!("```\n{}\n```", text.replace("```", "`\u{200b}``")),
+    }
+}
+
 /// Create permission buttons for Discord.
 #[allow(dead_code)]
 fn create_permission_buttons(request_id: &str) -> CreateActionRow {
@@ -169,6 +327,24 @@ fn create_permission_buttons(request_id: &str) -> CreateActionRow {
         CreateButton::new(format!("always:{}", request_id))
             .label("Always Allow")
             .style(ButtonStyle::Primary),
+        CreateButton::new(format!("block:{}", request_id))
+            .label("Deny & Block")
+            .style(ButtonStyle::Danger),
+        CreateButton::new(format!("snooze:{}", request_id))
+            .label(format!("Snooze {}m", SNOOZE_MINUTES))
+            .style(ButtonStyle::Secondary),
+    ])
+}
+
+/// Create yes/no question buttons for Discord.
+fn create_question_buttons(request_id: &str) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("allow:{}", request_id))
+            .label("Allow")
+            .style(ButtonStyle::Success),
+        CreateButton::new(format!("deny:{}", request_id))
+            .label("Deny")
+            .style(ButtonStyle::Danger),
     ])
 }
 
@@ -178,13 +354,21 @@ async fn poll_for_interaction(
     http: &Http,
     channel_id: ChannelId,
     message_id: MessageId,
-    _request_id: &str,
+    request_id: &str,
 ) -> Result<Decision, HookError> {
     let mut poll_interval = interval(Duration::from_millis(500));
 
     loop {
         poll_interval.tick().await;
 
+        if let Some(resolution) = ledger::resolution(request_id) {
+            return Ok(if resolution.allow {
+                Decision::Allow
+            } else {
+                Decision::Deny
+            });
+        }
+
         // Fetch the message to check for interactions
         let message = channel_id
             .message(http, message_id)
@@ -219,17 +403,26 @@ async fn poll_for_interaction(
 fn format_permission_message(message: &PermissionMessage) -> String {
     let mut lines = vec![
         format!("🔐 **Permission Request** [{}]", message.request_id),
-        format!("🖥️ **Host:** {}", message.hostname),
+        format!("🖥️ **Host:** {}", message.host()),
         String::new(),
-        format!("**Tool:** {}", message.tool_name),
     ];
+    if let Some(context) = &message.context {
+        lines.push(format!("💬 *{}*", context));
+        lines.push(String::new());
+    }
+    lines.push(format!("**Tool:** {}", format_tool_name(message)));
 
+    let fields = message.display_fields();
     match message.tool_name.as_str() {
+        _ if !fields.is_empty() => lines.extend(format_display_fields(&fields)),
         "Bash" => {
             if let Some(command) = message.tool_input.get("command").and_then(|v| v.as_str()) {
                 let truncated: String = command.chars().take(500).collect();
                 lines.push(format!("**Command:**\n```\n{}\n```", truncated));
             }
+            if let Some(explanation) = &message.explanation {
+                lines.push(format!("ℹ️ *{}*", explanation));
+            }
         }
         "Edit" | "Write" => {
             if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
@@ -255,6 +448,78 @@ fn format_permission_message(message: &PermissionMessage) -> String {
                 }
             }
         }
+        "MultiEdit" => {
+            if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
+                lines.push(format!("**File:** `{}`", file_path));
+            }
+            let edits = message.multi_edits();
+            lines.push(format!("**Edits:** {}", edits.len()));
+            for (index, (old_string, new_string)) in edits.iter().take(SHOWN_EDITS).enumerate() {
+                let old: String = old_string.chars().take(200).collect();
+                let new: String = new_string.chars().take(200).collect();
+                lines.push(format!(
+                    "**{}. Old:**\n```\n{}\n```\n**{}. New:**\n```\n{}\n```",
+                    index + 1,
+                    old,
+                    index + 1,
+                    new
+                ));
+            }
+            if edits.len() > SHOWN_EDITS {
+                lines.push(format!("*... and {} more*", edits.len() - SHOWN_EDITS));
+            }
+        }
+        "NotebookEdit" => {
+            if let Some(path) = message
+                .tool_input
+                .get("notebook_path")
+                .and_then(|v| v.as_str())
+            {
+                lines.push(format!("**Notebook:** `{}`", path));
+            }
+            lines.push(format!("**Change:** {}", message.notebook_change()));
+            if let Some(source) = message
+                .tool_input
+                .get("new_source")
+                .and_then(|v| v.as_str())
+            {
+                let truncated: String = source.chars().take(200).collect();
+                lines.push(format!("**Source:**\n```\n{}\n```", truncated));
+            }
+        }
+        "Task" => {
+            lines.push(format!("🤖 **{}**", message.subagent_line()));
+            if let Some(prompt) = message.tool_input.get("prompt").and_then(|v| v.as_str()) {
+                let truncated: String = prompt.chars().take(300).collect();
+                lines.push(format!("**Prompt:** {}", truncated));
+            }
+        }
+        "WebFetch" => {
+            if let Some(domain) = message.fetch_domain() {
+                lines.push(format!("🌐 **{}**", domain));
+            }
+            if let Some(url) = message.tool_input.get("url").and_then(|v| v.as_str()) {
+                lines.push(format!("**URL:** <{}>", url));
+            }
+            if let Some(prompt) = message.tool_input.get("prompt").and_then(|v| v.as_str()) {
+                let truncated: String = prompt.chars().take(200).collect();
+                lines.push(format!("**Prompt:** {}", truncated));
+            }
+        }
+        "WebSearch" => {
+            if let Some(query) = message.tool_input.get("query").and_then(|v| v.as_str()) {
+                lines.push(format!("🔎 **{}**", query));
+            }
+            for (key, label) in [
+                ("allowed_domains", "Only"),
+                ("blocked_domains", "Excluding"),
+            ] {
+                let domains = message.search_domains(key);
+                if !domains.is_empty() {
+                    lines.push(format!("**{}:** {}", label, domains.join(", ")));
+                }
+            }
+        }
         _ => {
             let input_str = serde_json::to_string_pretty(&message.tool_input).unwrap_or_default();
             let truncated: String = input_str.chars().take(500).collect();
@@ -270,23 +535,40 @@ fn format_permission_message(message: &PermissionMessage) -> String {
 fn format_auto_approved_message(message: &PermissionMessage) -> String {
     let mut lines = vec![
         format!("⚙️ **Auto-Approved** [{}]", message.request_id),
-        format!("🖥️ **Host:** {}", message.hostname),
+        format!("🖥️ **Host:** {}", message.host()),
         String::new(),
-        format!("**Tool:** {} *(in always-allow list)*", message.tool_name),
+        format!(
+            "**Tool:** {} *(in always-allow list)*",
+            format_tool_name(message)
+        ),
     ];
 
+    let fields = message.display_fields();
     match message.tool_name.as_str() {
+        _ if !fields.is_empty() => lines.extend(format_display_fields(&fields)),
         "Bash" => {
             if let Some(command) = message.tool_input.get("command").and_then(|v| v.as_str()) {
                 let truncated: String = command.chars().take(500).collect();
                 lines.push(format!("**Command:**\n```\n{}\n```", truncated));
             }
         }
-        "Edit" | "Write" => {
+        "Edit" | "Write" | "MultiEdit" => {
             if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
                 lines.push(format!("**File:** `{}`", file_path));
             }
         }
+        "NotebookEdit" => {
+            if let Some(path) = message
+                .tool_input
+                .get("notebook_path")
+                .and_then(|v| v.as_str())
+            {
+                lines.push(format!("**Notebook:** `{}`", path));
+            }
+        }
+        "Task" => {
+            lines.push(format!("🤖 **{}**", message.subagent_line()));
+        }
         _ => {
             let input_str = serde_json::to_string_pretty(&message.tool_input).unwrap_or_default();
             let truncated: String = input_str.chars().take(500).collect();
@@ -297,6 +579,26 @@ fn format_auto_approved_message(message: &PermissionMessage) -> String {
     lines.join("\n")
 }
 
+/// The tool name, after its configured emoji and label if there are any.
+fn format_tool_name(message: &PermissionMessage) -> String {
+    if message.has_custom_label() {
+        format!("{} (`{}`)", message.tool_label(), message.tool_name)
+    } else {
+        message.tool_name.clone()
+    }
+}
+
+/// One line per configured input field, instead of the JSON input.
+fn format_display_fields(fields: &[(&str, String)]) -> Vec<String> {
+    fields
+        .iter()
+        .map(|(field, value)| {
+            let truncated: String = value.chars().take(200).collect();
+            format!("**{}:** `{}`", field, truncated)
+        })
+        .collect()
+}
+
 /// Parse a button custom_id to extract decision and request_id.
 #[allow(dead_code)]
 pub fn parse_button_custom_id(custom_id: &str) -> Option<(Decision, String)> {
@@ -309,6 +611,8 @@ pub fn parse_button_custom_id(custom_id: &str) -> Option<(Decision, String)> {
         "allow" => Decision::Allow,
         "deny" => Decision::Deny,
         "always" => Decision::AlwaysAllow,
+        "block" => Decision::DenyAndBlock,
+        "snooze" => Decision::Snooze,
         _ => return None,
     };
 
@@ -318,6 +622,15 @@ pub fn parse_button_custom_id(custom_id: &str) -> Option<(Decision, String)> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_format_text_code_block() {
+        assert_eq!(format_text("ok", TextFormat::Code), "```\nok\n```");
+        assert!(!format_text("a\n```\nb", TextFormat::Code)[3..]
+            .trim_end_matches("```")
+            .contains("```"));
+    }
 
     #[test]
     fn test_parse_button_custom_id_allow() {
@@ -340,10 +653,33 @@ mod tests {
         assert_eq!(result.1, "test123");
     }
 
+    #[test]
+    fn test_parse_button_custom_id_snooze() {
+        let result = parse_button_custom_id("snooze:test123").unwrap();
+        assert_eq!(result.0, Decision::Snooze);
+        assert_eq!(result.1, "test123");
+    }
+
     #[test]
     fn test_parse_button_custom_id_invalid() {
         assert!(parse_button_custom_id("invalid").is_none());
         assert!(parse_button_custom_id("approve:abc123").is_none());
         assert!(parse_button_custom_id("").is_none());
     }
+
+    proptest! {
+        #[test]
+        fn prop_parse_button_custom_id_never_panics(custom_id in any::<String>()) {
+            let _ = parse_button_custom_id(&custom_id);
+        }
+
+        #[test]
+        fn prop_parse_button_custom_id_keeps_request_id(
+            action in prop::sample::select(&["allow", "deny", "always", "block", "snooze"][..]),
+            request_id in any::<String>(),
+        ) {
+            let (_, parsed) = parse_button_custom_id(&format!("{}:{}", action, request_id)).unwrap();
+            prop_assert_eq!(parsed, request_id);
+        }
+    }
 }
@@ -0,0 +1,151 @@
+//! Dry-run messenger that prints messages instead of sending them.
+//!
+//! Used by `--dry-run` / `CCR_DRY_RUN=1` to debug hook wiring without
+//! contacting any messaging platform.
+
+use super::{Decision, Messenger, PermissionMessage, TextFormat};
+use crate::error::HookError;
+use async_trait::async_trait;
+use std::path::Path;
+use std::time::Duration;
+
+/// Messenger that prints each would-be message to stderr.
+///
+/// Stderr keeps stdout free for the hook output Claude Code would receive.
+/// Requests for a decision are answered as if they had timed out.
+pub struct DryRunMessenger {
+    platform: &'static str,
+    /// Mirrors whether the real messenger enforces multi-person approval
+    quorum: bool,
+}
+
+impl DryRunMessenger {
+    /// Create a dry-run stand-in for the named platform.
+    pub fn new(platform: &'static str) -> Self {
+        Self {
+            platform,
+            quorum: false,
+        }
+    }
+
+    /// Accept requests needing several approvals, like the real messenger.
+    pub fn with_quorum(mut self, quorum: bool) -> Self {
+        self.quorum = quorum;
+        self
+    }
+
+    fn print(&self, kind: &str, body: &str) {
+        eprintln!("[dry-run] {} {}:\n{}\n", self.platform, kind, body);
+    }
+}
+
+/// Render a permission request as plain text.
+fn format_permission_message(message: &PermissionMessage) -> String {
+    let input = serde_json::to_string_pretty(&message.tool_input).unwrap_or_default();
+    let tool = if message.has_custom_label() {
+        format!("{} ({})", message.tool_label(), message.tool_name)
+    } else {
+        message.tool_name.clone()
+    };
+    let mut text = format!("Request: {}\nHost: {}", message.request_id, message.host());
+    if let Some(context) = &message.context {
+        text.push_str(&format!("\nContext: {}", context));
+    }
+    text.push_str(&format!("\nTool: {}\nInput: {}", tool, input));
+    if let Some(explanation) = &message.explanation {
+        text.push_str(&format!("\nExplanation: {}", explanation));
+    }
+    if message.required_approvals > 1 {
+        text.push_str(&format!(
+            "\nRequires: {} approvals",
+            message.required_approvals
+        ));
+    }
+    for label in &message.suggestions {
+        text.push_str(&format!("\nSuggestion: {}", label));
+    }
+    text
+}
+
+#[async_trait]
+impl Messenger for DryRunMessenger {
+    async fn send_permission_request(
+        &self,
+        message: &PermissionMessage,
+        timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        self.print("permission request", &format_permission_message(message));
+        eprintln!(
+            "[dry-run] No answer; treating as a timeout after {}s\n",
+            timeout.as_secs()
+        );
+        Ok(Decision::Deny)
+    }
+
+    async fn send_question(
+        &self,
+        question: &str,
+        hostname: &str,
+        timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        self.print("question", &format!("Host: {}\n{}", hostname, question));
+        eprintln!(
+            "[dry-run] No answer; treating as a timeout after {}s\n",
+            timeout.as_secs()
+        );
+        Ok(Decision::Deny)
+    }
+
+    async fn send_notification(&self, text: &str) -> Result<(), HookError> {
+        self.print("notification", text);
+        Ok(())
+    }
+
+    async fn send_text(&self, text: &str, format: TextFormat) -> Result<(), HookError> {
+        self.print(&format!("{:?} text", format).to_lowercase(), text);
+        Ok(())
+    }
+
+    async fn send_file(&self, path: &Path) -> Result<(), HookError> {
+        self.print("file", &path.display().to_string());
+        Ok(())
+    }
+
+    async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError> {
+        self.print("auto-approved", &format_permission_message(message));
+        Ok(())
+    }
+
+    fn supports_quorum(&self) -> bool {
+        self.quorum
+    }
+
+    fn platform_name(&self) -> &'static str {
+        self.platform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_permission_message() {
+        let message = PermissionMessage::new(
+            "abc12345".to_string(),
+            "Bash".to_string(),
+            "my-host".to_string(),
+            serde_json::json!({"command": "ls"}),
+        );
+
+        let text = format_permission_message(&message);
+        assert!(text.starts_with("Request: abc12345\nHost: my-host\nTool: Bash"));
+        assert!(text.contains("\"command\": \"ls\""));
+
+        let message = message.with_context(Some("Listing files first.".to_string()));
+        let text = format_permission_message(&message);
+        assert!(text.starts_with(
+            "Request: abc12345\nHost: my-host\nContext: Listing files first.\nTool: Bash"
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+//! Debounced Telegram message edits.
+//!
+//! Status and progress updates edit the message they belong to. When several
+//! land on one message in quick succession (approvals arriving together, a
+//! burst of presses on a closed request) Telegram answers with 429s, so edits
+//! go through a process-wide debouncer: edits to the same message are spaced
+//! at least `MIN_EDIT_INTERVAL` apart, and an edit still waiting for its turn
+//! is dropped once a newer one for the same message comes in.
+
+use super::telegram::send_with_retry;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use teloxide::requests::Request;
+use teloxide::types::{ChatId, MessageId};
+use teloxide::RequestError;
+use tokio::time::sleep;
+
+/// Minimum time between two edits of the same message.
+const MIN_EDIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Messages not edited for this long are forgotten.
+const RETENTION: Duration = Duration::from_secs(10 * 60);
+
+type MessageKey = (ChatId, MessageId);
+
+#[derive(Debug, Default)]
+struct Slot {
+    /// When the last edit of the message went out
+    last_sent: Option<Instant>,
+    /// Ticket of the newest edit requested for the message
+    latest: u64,
+    /// When the newest edit was requested
+    touched: Option<Instant>,
+}
+
+/// Per-message edit spacing and coalescing.
+#[derive(Debug, Default)]
+pub struct EditDebouncer {
+    slots: Mutex<HashMap<MessageKey, Slot>>,
+}
+
+impl EditDebouncer {
+    /// Edit a message once its previous edit is far enough in the past.
+    ///
+    /// Returns `Ok(false)` without sending if a newer edit of the same
+    /// message was requested in the meantime.
+    pub async fn edit<R>(
+        &self,
+        chat_id: ChatId,
+        message_id: MessageId,
+        request: R,
+    ) -> Result<bool, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        let key = (chat_id, message_id);
+        let (ticket, wait) = self.reserve(key, Instant::now());
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+        if !self.claim(key, ticket, Instant::now()) {
+            tracing::debug!("Skipping superseded edit of message {}", message_id.0);
+            return Ok(false);
+        }
+        send_with_retry(request).await?;
+        Ok(true)
+    }
+
+    /// Queue an edit, returning its ticket and how long it has to wait.
+    fn reserve(&self, key: MessageKey, now: Instant) -> (u64, Duration) {
+        let mut slots = self.lock();
+        slots.retain(|_, slot| {
+            slot.touched
+                .is_some_and(|touched| now.saturating_duration_since(touched) < RETENTION)
+        });
+        let slot = slots.entry(key).or_default();
+        slot.latest += 1;
+        slot.touched = Some(now);
+        let wait = slot
+            .last_sent
+            .map(|sent| (sent + MIN_EDIT_INTERVAL).saturating_duration_since(now))
+            .unwrap_or_default();
+        (slot.latest, wait)
+    }
+
+    /// Mark a waiting edit as sent, unless a newer one took its place.
+    fn claim(&self, key: MessageKey, ticket: u64, now: Instant) -> bool {
+        let mut slots = self.lock();
+        let Some(slot) = slots.get_mut(&key).filter(|slot| slot.latest == ticket) else {
+            return false;
+        };
+        slot.last_sent = Some(now);
+        true
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<MessageKey, Slot>> {
+        self.slots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Debouncer shared by every messenger in this process.
+pub fn shared() -> &'static EditDebouncer {
+    static EDITS: OnceLock<EditDebouncer> = OnceLock::new();
+    EDITS.get_or_init(EditDebouncer::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: MessageKey = (ChatId(1), MessageId(7));
+
+    #[test]
+    fn test_edits_are_spaced_per_message() {
+        let edits = EditDebouncer::default();
+        let start = Instant::now();
+
+        let (ticket, wait) = edits.reserve(MESSAGE, start);
+        assert!(wait.is_zero());
+        assert!(edits.claim(MESSAGE, ticket, start));
+
+        let later = start + Duration::from_millis(300);
+        let (_, wait) = edits.reserve(MESSAGE, later);
+        assert_eq!(wait, Duration::from_millis(700));
+
+        // Other messages are not held back
+        let (_, wait) = edits.reserve((ChatId(1), MessageId(8)), later);
+        assert!(wait.is_zero());
+    }
+
+    #[test]
+    fn test_newer_edit_supersedes_waiting_one() {
+        let edits = EditDebouncer::default();
+        let start = Instant::now();
+        let (first, _) = edits.reserve(MESSAGE, start);
+        assert!(edits.claim(MESSAGE, first, start));
+
+        let (progress, _) = edits.reserve(MESSAGE, start);
+        let (status, _) = edits.reserve(MESSAGE, start);
+        let due = start + MIN_EDIT_INTERVAL;
+        assert!(!edits.claim(MESSAGE, progress, due));
+        assert!(edits.claim(MESSAGE, status, due));
+    }
+}
@@ -0,0 +1,450 @@
+//! Home Assistant messenger over MQTT.
+//!
+//! Requests and notifications are published as JSON under `topic_prefix`,
+//! where Home Assistant automations turn them into actionable phone
+//! notifications, wall tablet prompts or smart speaker announcements. The
+//! answer comes back through `mqtt.publish` to `<prefix>/decision/<request_id>`,
+//! and `<prefix>/resolved` tells automations when to clear the prompt and,
+//! in `decided`, what the first answer was, e.g. "❌ Denied by ana".
+//!
+//! With `presence` set, the messenger only takes messages while one of the
+//! listed `person` or `device_tracker` entities is home, as published by the
+//! `mqtt_statestream` integration; otherwise the next messenger is used.
+
+use super::ledger::{self, RequestState};
+use super::{cancelled, new_request_id, Decision, Messenger, PermissionMessage, TextFormat};
+use crate::config::HomeAssistantConfig;
+use crate::error::HookError;
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS, SubscribeFilter};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::timeout;
+
+/// How long to wait for the broker to accept the connection and subscriptions.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for retained presence states.
+const PRESENCE_WAIT: Duration = Duration::from_secs(2);
+
+/// Home Assistant messenger for permission requests.
+pub struct HomeAssistantMessenger {
+    config: HomeAssistantConfig,
+    last_responder: Mutex<Option<String>>,
+}
+
+impl HomeAssistantMessenger {
+    /// Create a new Home Assistant messenger.
+    pub fn new(config: HomeAssistantConfig) -> Self {
+        Self {
+            config,
+            last_responder: Mutex::new(None),
+        }
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!(
+            "{}/{}",
+            self.config.topic_prefix.trim_end_matches('/'),
+            name
+        )
+    }
+
+    /// Open a connection; it is established by the first `poll`.
+    fn connect(&self) -> (AsyncClient, EventLoop) {
+        let mut options = MqttOptions::new(
+            format!("claude-code-remote-{}", new_request_id()),
+            &self.config.mqtt_host,
+            self.config.mqtt_port,
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &self.config.username {
+            options.set_credentials(
+                username,
+                self.config.password.as_deref().unwrap_or_default(),
+            );
+        }
+        AsyncClient::new(options, 16)
+    }
+
+    /// Publish one message and wait for the broker to acknowledge it.
+    async fn publish(&self, topic: &str, payload: Value) -> Result<(), HookError> {
+        let (client, mut events) = self.connect();
+        client
+            .publish(topic, QoS::AtLeastOnce, false, payload.to_string())
+            .await
+            .map_err(mqtt_error)?;
+        let acked = timeout(
+            CONNECT_TIMEOUT,
+            drive(&mut events, |event| {
+                matches!(event, Event::Incoming(Packet::PubAck(_))).then_some(())
+            }),
+        )
+        .await;
+        disconnect(&client, &mut events).await;
+        acked.map_err(|_| HookError::HomeAssistant("MQTT broker did not respond".to_string()))?
+    }
+
+    /// Publish a request and wait for its decision.
+    ///
+    /// Unanswered requests are denied once the timeout expires.
+    async fn request_decision(
+        &self,
+        request_id: &str,
+        request: Value,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let decision_topic = self.topic(&format!("decision/{}", request_id));
+        let (client, mut events) = self.connect();
+
+        // Subscribe first so an instant answer is not missed
+        client
+            .subscribe(&decision_topic, QoS::AtLeastOnce)
+            .await
+            .map_err(mqtt_error)?;
+        timeout(
+            CONNECT_TIMEOUT,
+            drive(&mut events, |event| {
+                matches!(event, Event::Incoming(Packet::SubAck(_))).then_some(())
+            }),
+        )
+        .await
+        .map_err(|_| HookError::HomeAssistant("MQTT broker did not respond".to_string()))??;
+        client
+            .publish(
+                self.topic("request"),
+                QoS::AtLeastOnce,
+                false,
+                request.to_string(),
+            )
+            .await
+            .map_err(mqtt_error)?;
+        ledger::open(request_id, request_timeout).await;
+
+        let answer = tokio::select! {
+            answer = timeout(
+                request_timeout,
+                drive(&mut events, |event| match event {
+                    Event::Incoming(Packet::Publish(publish)) if publish.topic == decision_topic => {
+                        parse_decision(&publish.payload)
+                    }
+                    _ => None,
+                }),
+            ) => Some(answer),
+            _ = cancelled() => None,
+        };
+        let (decision, status) = match answer {
+            // Claude gave up on the request
+            None => {
+                ledger::transition(request_id, RequestState::Cancelled).await;
+                (Decision::Deny, "cancelled")
+            }
+            Some(Ok(Ok((decision, responder)))) => {
+                let (decision, responder) = ledger::settle(request_id, decision, responder).await;
+                ledger::transition(request_id, RequestState::Answered).await;
+                *self
+                    .last_responder
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) = responder;
+                (decision, decision_name(decision))
+            }
+            Some(Ok(Err(e))) => {
+                ledger::transition(request_id, RequestState::Cancelled).await;
+                disconnect(&client, &mut events).await;
+                return Err(e);
+            }
+            // Timeout - deny by default
+            Some(Err(_)) => {
+                ledger::transition(request_id, RequestState::TimedOut).await;
+                (Decision::Deny, "timeout")
+            }
+        };
+
+        // Let automations clear the prompt; the decision stands either way
+        let resolved = json!({
+            "request_id": request_id,
+            "status": status,
+            "decided": ledger::answer(request_id).map(|answer| answer.to_string()),
+        });
+        if client
+            .publish(
+                self.topic("resolved"),
+                QoS::AtLeastOnce,
+                false,
+                resolved.to_string(),
+            )
+            .await
+            .is_ok()
+        {
+            let _ = timeout(
+                CONNECT_TIMEOUT,
+                drive(&mut events, |event| {
+                    matches!(event, Event::Incoming(Packet::PubAck(_))).then_some(())
+                }),
+            )
+            .await;
+        }
+        disconnect(&client, &mut events).await;
+        if status == "cancelled" {
+            return Err(HookError::Cancelled);
+        }
+        Ok(decision)
+    }
+}
+
+#[async_trait]
+impl Messenger for HomeAssistantMessenger {
+    async fn send_permission_request(
+        &self,
+        message: &PermissionMessage,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let request = json!({
+            "request_id": message.request_id,
+            "kind": "permission",
+            "host": message.hostname,
+            "tool_name": message.tool_name,
+            "tool_input": message.tool_input,
+            "title": format!("Claude Code on {}", message.host()),
+            "message": format!("Allow {}?", describe(message)),
+            "context": message.context,
+            "explanation": message.explanation,
+            "emoji": message.display.emoji,
+            "color": message.display.color,
+            "actions": ["allow", "deny", "always_allow", "block", "snooze"],
+        });
+        self.request_decision(&message.request_id, request, request_timeout)
+            .await
+    }
+
+    async fn send_question(
+        &self,
+        question: &str,
+        hostname: &str,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let request_id = new_request_id();
+        let request = json!({
+            "request_id": request_id,
+            "kind": "question",
+            "host": hostname,
+            "title": format!("Question from {}", hostname),
+            "message": question,
+            "actions": ["allow", "deny"],
+        });
+        self.request_decision(&request_id, request, request_timeout)
+            .await
+    }
+
+    async fn send_notification(&self, text: &str) -> Result<(), HookError> {
+        self.send_text(text, TextFormat::Markdown).await
+    }
+
+    async fn send_text(&self, text: &str, _format: TextFormat) -> Result<(), HookError> {
+        // Phones and speakers show plain text
+        let text = text.replace("**", "").replace("```", "");
+        self.publish(&self.topic("notification"), json!({"message": text.trim()}))
+            .await
+    }
+
+    async fn send_file(&self, path: &Path) -> Result<(), HookError> {
+        Err(HookError::HomeAssistant(format!(
+            "cannot send files such as {}",
+            path.display()
+        )))
+    }
+
+    async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError> {
+        self.publish(
+            &self.topic("notification"),
+            json!({
+                "request_id": message.request_id,
+                "host": message.hostname,
+                "message": format!("Auto-approved {} on {}", describe(message), message.host()),
+            }),
+        )
+        .await
+    }
+
+    fn last_responder(&self) -> Option<String> {
+        self.last_responder
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    async fn is_available(&self) -> bool {
+        let topics: Vec<String> = self
+            .config
+            .presence
+            .iter()
+            .filter_map(|entity| presence_topic(&self.config.statestream_topic, entity))
+            .collect();
+        if topics.is_empty() {
+            return true;
+        }
+
+        let (client, mut events) = self.connect();
+        let filters = topics
+            .iter()
+            .map(|topic| SubscribeFilter::new(topic.clone(), QoS::AtMostOnce));
+        if client.subscribe_many(filters).await.is_err() {
+            return false;
+        }
+        // Retained states arrive right after subscribing
+        let mut seen = HashSet::new();
+        let home = timeout(
+            PRESENCE_WAIT,
+            drive(&mut events, |event| match event {
+                Event::Incoming(Packet::Publish(publish)) if topics.contains(&publish.topic) => {
+                    seen.insert(publish.topic.clone());
+                    if is_home(&publish.payload) {
+                        Some(true)
+                    } else {
+                        (seen.len() == topics.len()).then_some(false)
+                    }
+                }
+                _ => None,
+            }),
+        )
+        .await;
+        disconnect(&client, &mut events).await;
+
+        match home {
+            Ok(Ok(home)) => {
+                if !home {
+                    tracing::info!("Nobody is home, skipping Home Assistant");
+                }
+                home
+            }
+            Ok(Err(e)) => {
+                tracing::warn!("Failed to check presence: {}", e);
+                false
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn platform_name(&self) -> &'static str {
+        "Home Assistant"
+    }
+}
+
+/// Poll the connection until `done` picks a value out of an event.
+async fn drive<T>(
+    events: &mut EventLoop,
+    mut done: impl FnMut(&Event) -> Option<T>,
+) -> Result<T, HookError> {
+    loop {
+        let event = events
+            .poll()
+            .await
+            .map_err(|e| HookError::HomeAssistant(format!("MQTT connection failed: {}", e)))?;
+        if let Some(value) = done(&event) {
+            return Ok(value);
+        }
+    }
+}
+
+/// Send a disconnect, giving the connection a moment to flush it.
+async fn disconnect(client: &AsyncClient, events: &mut EventLoop) {
+    if client.disconnect().await.is_ok() {
+        let _ = timeout(Duration::from_millis(200), events.poll()).await;
+    }
+}
+
+fn mqtt_error(error: rumqttc::ClientError) -> HookError {
+    HookError::HomeAssistant(format!("MQTT request failed: {}", error))
+}
+
+/// One line describing a tool call, e.g. "Bash: cargo test".
+fn describe(message: &PermissionMessage) -> String {
+    match message.detail() {
+        Some(detail) => format!("{}: {}", message.tool_label(), detail),
+        None => message.tool_label(),
+    }
+}
+
+fn decision_name(decision: Decision) -> &'static str {
+    match decision {
+        Decision::Deny => "deny",
+        Decision::AlwaysAllow => "always_allow",
+        Decision::DenyAndBlock => "block",
+        Decision::Snooze => "snooze",
+        Decision::Allow | Decision::ApplySuggestion(_) => "allow",
+    }
+}
+
+/// Parse a decision payload: `allow`, `deny`, `always_allow`, `block`,
+/// `snooze`, or JSON like `{"decision": "allow", "responder": "Ana"}`.
+fn parse_decision(payload: &[u8]) -> Option<(Decision, Option<String>)> {
+    let text = std::str::from_utf8(payload).ok()?.trim();
+    let (decision, responder) = match serde_json::from_str::<Value>(text) {
+        Ok(Value::Object(answer)) => (
+            answer.get("decision")?.as_str()?.to_string(),
+            answer
+                .get("responder")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        ),
+        Ok(Value::String(decision)) => (decision, None),
+        _ => (text.to_string(), None),
+    };
+    let decision = match decision.trim().to_ascii_lowercase().as_str() {
+        "allow" | "yes" => Decision::Allow,
+        "deny" | "no" => Decision::Deny,
+        "always_allow" | "always" => Decision::AlwaysAllow,
+        "block" => Decision::DenyAndBlock,
+        "snooze" => Decision::Snooze,
+        _ => return None,
+    };
+    Some((decision, responder))
+}
+
+/// `mqtt_statestream` topic holding an entity's state, e.g. `person.ana`.
+fn presence_topic(base_topic: &str, entity: &str) -> Option<String> {
+    let (domain, object_id) = entity.split_once('.')?;
+    Some(format!(
+        "{}/{}/{}/state",
+        base_topic.trim_end_matches('/'),
+        domain,
+        object_id
+    ))
+}
+
+/// Whether a published state is `home`; statestream may JSON-encode it.
+fn is_home(payload: &[u8]) -> bool {
+    std::str::from_utf8(payload).is_ok_and(|state| state.trim().trim_matches('"') == "home")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decision() {
+        assert_eq!(parse_decision(b"allow"), Some((Decision::Allow, None)));
+        assert_eq!(parse_decision(b"\"DENY\"\n"), Some((Decision::Deny, None)));
+        assert_eq!(
+            parse_decision(br#"{"decision": "always_allow", "responder": "Ana"}"#),
+            Some((Decision::AlwaysAllow, Some("Ana".to_string())))
+        );
+        assert_eq!(parse_decision(b"maybe"), None);
+        assert_eq!(parse_decision(br#"{"responder": "Ana"}"#), None);
+    }
+
+    #[test]
+    fn test_presence_topics() {
+        assert_eq!(
+            presence_topic("homeassistant/", "person.ana").as_deref(),
+            Some("homeassistant/person/ana/state")
+        );
+        assert_eq!(presence_topic("homeassistant", "ana"), None);
+        assert!(is_home(b"home"));
+        assert!(is_home(b"\"home\""));
+        assert!(!is_home(b"not_home"));
+    }
+}
@@ -0,0 +1,907 @@
+//! Shared record of where each decision request stands.
+//!
+//! Every hook process polls the same chat, so a button press or reply for an
+//! old request can reach a process waiting on a different one. Each request
+//! gets a small file in the state directory when it is sent, and moves once
+//! from `Sent` to a final state when it is answered, times out or is given
+//! up on, so any process can tell a stale press from one meant for a live
+//! request. Each move is logged and kept with the request.
+//!
+//! Presses, replies, bulk decisions and approval links can race for the
+//! same request, e.g. when two responders answer from phone and desktop at
+//! once. The first to record its answer here wins, and everyone after it is
+//! told what was decided and by whom.
+//!
+//! Permission requests also record what they are about, so `/allow_all` and
+//! `/deny_all` in the bot can list them and leave a decision for the waiting
+//! process to pick up. When Claude gives up on one because it was answered in
+//! the terminal, the chat message showing it is kept, so the PostToolUse hook
+//! can mark it as handled locally once the tool runs.
+//!
+//! With a shared database (see [`crate::shared_state`]), entries are kept
+//! there instead of in files, so every host sees the same requests.
+//!
+//! Changing an entry waits for its lock, so the functions that change the
+//! default ledger are async and wait on the blocking thread pool; the `_in`
+//! functions are for code that already runs there.
+
+use super::{compact, Decision, PermissionMessage};
+use crate::clock::now_secs;
+use crate::config::default_request_ledger_path;
+use crate::lockfile::{off_reactor, FileLock};
+use crate::shared_state;
+use crate::tool_timer::describe_tool;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Entries older than this are removed when a new request is opened.
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Kind of the ledger's documents in a shared database.
+const KIND: &str = "requests";
+
+/// Where a request stands.
+///
+/// Requests start out `Sent` and move once to one of the other states,
+/// which are final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestState {
+    /// Sent and waiting for an answer
+    #[serde(alias = "pending")]
+    Sent,
+    /// Answered by someone
+    #[serde(alias = "resolved")]
+    Answered,
+    /// Nobody answered before the timeout
+    TimedOut,
+    /// Given up on before an answer, e.g. after a delivery error
+    Cancelled,
+    /// Past its timeout without being closed, or unknown to this machine
+    Expired,
+}
+
+impl fmt::Display for RequestState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RequestState::Sent => "sent",
+            RequestState::Answered => "answered",
+            RequestState::TimedOut => "timed out",
+            RequestState::Cancelled => "cancelled",
+            RequestState::Expired => "expired",
+        })
+    }
+}
+
+/// A move of a request into a state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transition {
+    pub state: RequestState,
+    /// Unix time of the move
+    pub at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LedgerEntry {
+    #[serde(alias = "status")]
+    state: RequestState,
+    /// Unix time after which a sent request counts as expired
+    expires_at: u64,
+    /// Every state the request has been in, oldest first
+    #[serde(default)]
+    transitions: Vec<Transition>,
+    /// What the request is about, for permission requests that can be
+    /// decided in bulk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<RequestDetails>,
+    /// Decision made in bulk, waiting for the request's process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resolution: Option<Resolution>,
+    /// The first answer, which later presses are told about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    answer: Option<Answer>,
+    /// Chat message of a request Claude gave up on, until the tool runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<PostedMessage>,
+}
+
+impl LedgerEntry {
+    /// Its state, with a sent request past its timeout counting as expired.
+    fn current_state(&self) -> RequestState {
+        if self.state == RequestState::Sent && self.expires_at < now_secs() {
+            RequestState::Expired
+        } else {
+            self.state
+        }
+    }
+}
+
+/// What a pending permission request is about.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestDetails {
+    pub host: String,
+    /// Claude Code session (empty if unknown)
+    #[serde(default)]
+    pub session_id: String,
+    /// One-line description, e.g. `Bash "cargo test"`
+    pub summary: String,
+    /// Requests needing several approvals cannot be allowed in bulk
+    #[serde(default)]
+    pub required_approvals: usize,
+    /// The tool call, as `describe_tool` shows it
+    #[serde(default)]
+    pub tool: String,
+}
+
+impl RequestDetails {
+    /// What a permission request is about, for `/allow_all`, `/deny_all`
+    /// and approval links.
+    pub fn of(message: &PermissionMessage) -> Self {
+        Self {
+            host: message.hostname.clone(),
+            session_id: message.session_id.clone(),
+            summary: compact::item_line(message),
+            required_approvals: message.required_approvals,
+            tool: describe_tool(&message.tool_name, &message.tool_input),
+        }
+    }
+}
+
+/// Where a request was shown in chat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostedMessage {
+    /// Messenger that sent it, e.g. `telegram`
+    pub platform: String,
+    pub chat_id: String,
+    pub message_id: String,
+    /// Text of the request as sent, in the messenger's markup
+    pub text: String,
+}
+
+/// A decision left for a pending request by `/allow_all`, `/deny_all` or
+/// an approval link.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resolution {
+    pub allow: bool,
+    /// Who decided
+    pub by: String,
+}
+
+/// The answer that decided a request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Answer {
+    /// The decision as shown in chat, e.g. `❌ Denied`
+    pub decision: String,
+    /// Who answered (empty if unknown)
+    #[serde(default)]
+    pub by: String,
+}
+
+impl Answer {
+    pub fn new(decision: Decision, by: &str) -> Self {
+        Self {
+            decision: decision.label().to_string(),
+            by: by.to_string(),
+        }
+    }
+
+    /// What anyone answering after this is told.
+    pub fn notice(&self) -> String {
+        format!("Already decided: {}", self)
+    }
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.by.is_empty() {
+            f.write_str(&self.decision)
+        } else {
+            write!(f, "{} by {}", self.decision, self.by)
+        }
+    }
+}
+
+/// A request still waiting for an answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRequest {
+    pub request_id: String,
+    pub details: RequestDetails,
+    /// Unix time it was sent
+    pub sent_at: u64,
+    /// Unix time it times out
+    pub expires_at: u64,
+}
+
+/// Request IDs are short hex prefixes; anything else cannot name an entry.
+fn entry_key(request_id: &str) -> Option<String> {
+    let valid = !request_id.is_empty()
+        && request_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    valid.then(|| request_id.to_ascii_lowercase())
+}
+
+fn write_entry(dir: &Path, request_id: &str, entry: &LedgerEntry) -> io::Result<()> {
+    let Some(key) = entry_key(request_id) else {
+        return Ok(());
+    };
+    let content = serde_json::to_string(entry)?;
+    if let Some(database) = shared_state::database() {
+        return database.put(KIND, &key, &content);
+    }
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(key), content)
+}
+
+/// Held while an entry is read and rewritten, and released on drop.
+#[allow(dead_code)]
+enum EntryLock {
+    File(FileLock),
+    Shared(shared_state::Lock),
+}
+
+/// Lock an entry, so reading and rewriting it cannot interleave with
+/// another process doing the same.
+fn lock_entry(dir: &Path, request_id: &str) -> io::Result<Option<EntryLock>> {
+    let Some(key) = entry_key(request_id) else {
+        return Ok(None);
+    };
+    if let Some(database) = shared_state::database() {
+        return database
+            .lock(KIND, &key)
+            .map(|lock| Some(EntryLock::Shared(lock)));
+    }
+    fs::create_dir_all(dir)?;
+    FileLock::acquire(&dir.join(key)).map(|lock| Some(EntryLock::File(lock)))
+}
+
+/// Remove entries not touched for `MAX_AGE`.
+fn prune(dir: &Path) {
+    if let Some(database) = shared_state::database() {
+        if let Err(e) = database.prune(KIND, MAX_AGE) {
+            tracing::warn!("Failed to prune old requests: {}", e);
+        }
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let old = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > MAX_AGE);
+        if old {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn read_entry(dir: &Path, request_id: &str) -> Option<LedgerEntry> {
+    let key = entry_key(request_id)?;
+    let content = match shared_state::database() {
+        Some(database) => database.get(KIND, &key).unwrap_or_else(|e| {
+            tracing::warn!("Failed to read request {}: {}", request_id, e);
+            None
+        })?,
+        None => fs::read_to_string(dir.join(key)).ok()?,
+    };
+    serde_json::from_str(&content).ok()
+}
+
+/// Every readable entry, with its request ID.
+fn entries(dir: &Path) -> Vec<(String, LedgerEntry)> {
+    let documents: Vec<(String, String)> = match shared_state::database() {
+        Some(database) => database.list(KIND).unwrap_or_else(|e| {
+            tracing::warn!("Failed to list requests: {}", e);
+            Vec::new()
+        }),
+        None => fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let request_id = entry.file_name().into_string().ok()?;
+                let content = fs::read_to_string(entry.path()).ok()?;
+                Some((request_id, content))
+            })
+            .collect(),
+    };
+    documents
+        .into_iter()
+        .filter_map(|(request_id, content)| {
+            Some((request_id, serde_json::from_str(&content).ok()?))
+        })
+        .collect()
+}
+
+/// Record a request as sent, waiting until `timeout` from now.
+///
+/// Only requests with `details` can be decided in bulk.
+pub fn open_in(
+    dir: &Path,
+    request_id: &str,
+    timeout: Duration,
+    details: Option<RequestDetails>,
+) -> io::Result<()> {
+    prune(dir);
+    let now = now_secs();
+    write_entry(
+        dir,
+        request_id,
+        &LedgerEntry {
+            state: RequestState::Sent,
+            expires_at: now + timeout.as_secs(),
+            transitions: vec![Transition {
+                state: RequestState::Sent,
+                at: now,
+            }],
+            details,
+            resolution: None,
+            answer: None,
+            message: None,
+        },
+    )
+}
+
+/// Move a sent request into a final state.
+///
+/// Returns false, leaving the request as it is, if it was already final.
+pub fn transition_in(dir: &Path, request_id: &str, to: RequestState) -> io::Result<bool> {
+    let _lock = lock_entry(dir, request_id)?;
+    let now = now_secs();
+    let mut entry = read_entry(dir, request_id).unwrap_or(LedgerEntry {
+        state: RequestState::Sent,
+        expires_at: now,
+        transitions: Vec::new(),
+        details: None,
+        resolution: None,
+        answer: None,
+        message: None,
+    });
+    if entry.state != RequestState::Sent {
+        tracing::debug!(
+            "Request {} is already {}, not moving it to {}",
+            request_id,
+            entry.state,
+            to
+        );
+        return Ok(false);
+    }
+
+    tracing::info!("Request {}: {} → {}", request_id, entry.state, to);
+    entry.state = to;
+    entry.expires_at = now;
+    entry.transitions.push(Transition { state: to, at: now });
+    write_entry(dir, request_id, &entry)?;
+    Ok(true)
+}
+
+/// Every state a request has been in, oldest first.
+pub fn transitions_in(dir: &Path, request_id: &str) -> Vec<Transition> {
+    read_entry(dir, request_id)
+        .map(|entry| entry.transitions)
+        .unwrap_or_default()
+}
+
+/// What a request is about, if it recorded that.
+pub fn details_in(dir: &Path, request_id: &str) -> Option<RequestDetails> {
+    read_entry(dir, request_id).and_then(|entry| entry.details)
+}
+
+/// Permission requests that are waiting and can be decided in bulk.
+pub fn pending_in(dir: &Path) -> Vec<PendingRequest> {
+    let mut pending: Vec<PendingRequest> = entries(dir)
+        .into_iter()
+        .filter_map(|(request_id, entry)| {
+            let waiting = entry.current_state() == RequestState::Sent;
+            waiting.then_some(PendingRequest {
+                details: entry.details?,
+                sent_at: entry.transitions.first().map_or(0, |sent| sent.at),
+                expires_at: entry.expires_at,
+                request_id,
+            })
+        })
+        .collect();
+    pending.sort_by(|a, b| a.request_id.cmp(&b.request_id));
+    pending
+}
+
+/// Pending requests on a host or session (session IDs may be shortened).
+#[cfg_attr(not(any(feature = "telegram", unix, windows)), allow(dead_code))]
+pub fn matching(pending: Vec<PendingRequest>, target: &str) -> Vec<PendingRequest> {
+    pending
+        .into_iter()
+        .filter(|request| {
+            target.is_empty()
+                || request.details.host == target
+                || (!request.details.session_id.is_empty()
+                    && request.details.session_id.starts_with(target))
+        })
+        .collect()
+}
+
+/// Leave a decision for a waiting request.
+///
+/// Returns whether the request was still waiting with nobody having
+/// answered it yet; the first answer wins.
+pub fn resolve_in(dir: &Path, request_id: &str, resolution: Resolution) -> io::Result<bool> {
+    let _lock = lock_entry(dir, request_id)?;
+    match read_entry(dir, request_id) {
+        Some(mut entry)
+            if entry.answer.is_none() && state_in(dir, request_id) == RequestState::Sent =>
+        {
+            let decision = if resolution.allow {
+                Decision::Allow
+            } else {
+                Decision::Deny
+            };
+            entry.answer = Some(Answer::new(decision, &resolution.by));
+            entry.resolution = Some(resolution);
+            write_entry(dir, request_id, &entry)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Record the answer to a request, unless someone answered it first.
+///
+/// Returns the earlier answer when this one lost the race, so the caller
+/// can tell the responder. Requests not in the ledger take any answer.
+pub fn claim_in(dir: &Path, request_id: &str, answer: Answer) -> io::Result<Result<(), Answer>> {
+    let _lock = lock_entry(dir, request_id)?;
+    let Some(mut entry) = read_entry(dir, request_id) else {
+        return Ok(Ok(()));
+    };
+    if let Some(first) = entry.answer {
+        return Ok(Err(first));
+    }
+    entry.answer = Some(answer);
+    write_entry(dir, request_id, &entry)?;
+    Ok(Ok(()))
+}
+
+/// The decision left for a waiting request, if any.
+pub fn resolution_in(dir: &Path, request_id: &str) -> Option<Resolution> {
+    read_entry(dir, request_id)
+        .filter(|entry| entry.state == RequestState::Sent)
+        .and_then(|entry| entry.resolution)
+}
+
+/// The answer that decided a request, if it was answered.
+pub fn answer_in(dir: &Path, request_id: &str) -> Option<Answer> {
+    read_entry(dir, request_id).and_then(|entry| entry.answer)
+}
+
+/// Keep the chat message of a request Claude gave up on.
+pub fn keep_message_in(dir: &Path, request_id: &str, message: PostedMessage) -> io::Result<()> {
+    match read_entry(dir, request_id) {
+        Some(mut entry) if entry.state == RequestState::Cancelled => {
+            entry.message = Some(message);
+            write_entry(dir, request_id, &entry)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Take the chat message of the latest request for this tool call that
+/// Claude gave up on, now that the tool ran after all.
+///
+/// Each message is only returned once.
+pub fn take_handled_locally_in(
+    dir: &Path,
+    session_id: &str,
+    tool: &str,
+) -> io::Result<Option<PostedMessage>> {
+    if session_id.is_empty() {
+        return Ok(None);
+    }
+    let latest = entries(dir)
+        .into_iter()
+        .filter_map(|(request_id, entry)| {
+            let details = entry.details.as_ref()?;
+            let matches =
+                entry.message.is_some() && details.session_id == session_id && details.tool == tool;
+            matches.then(|| {
+                (
+                    entry.transitions.last().map_or(0, |t| t.at),
+                    request_id,
+                    entry,
+                )
+            })
+        })
+        .max_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    let Some((_, request_id, mut entry)) = latest else {
+        return Ok(None);
+    };
+    let message = entry.message.take();
+    write_entry(dir, &request_id, &entry)?;
+    Ok(message)
+}
+
+/// Look up a request; unknown requests count as expired.
+pub fn state_in(dir: &Path, request_id: &str) -> RequestState {
+    read_entry(dir, request_id).map_or(RequestState::Expired, |entry| entry.current_state())
+}
+
+/// Record a request as sent in the default ledger, logging failures.
+#[cfg_attr(
+    not(any(feature = "telegram", feature = "signal", feature = "homeassistant")),
+    allow(dead_code)
+)]
+pub async fn open(request_id: &str, timeout: Duration) {
+    open_with_details(request_id, timeout, None).await;
+}
+
+/// Record a permission request as sent, along with what it is about.
+pub async fn open_with_details(
+    request_id: &str,
+    timeout: Duration,
+    details: Option<RequestDetails>,
+) {
+    let request_id = request_id.to_string();
+    off_reactor(move || {
+        if let Err(e) = open_in(
+            &default_request_ledger_path(),
+            &request_id,
+            timeout,
+            details,
+        ) {
+            tracing::warn!("Failed to record sent request {}: {}", request_id, e);
+        }
+    })
+    .await
+}
+
+/// Move a request into a final state in the default ledger, logging failures.
+pub async fn transition(request_id: &str, to: RequestState) {
+    let request_id = request_id.to_string();
+    off_reactor(move || {
+        if let Err(e) = transition_in(&default_request_ledger_path(), &request_id, to) {
+            tracing::warn!("Failed to record request {} as {}: {}", request_id, to, e);
+        }
+    })
+    .await
+}
+
+/// Look up a request in the default ledger.
+pub fn state(request_id: &str) -> RequestState {
+    state_in(&default_request_ledger_path(), request_id)
+}
+
+/// Every state a request in the default ledger has been in.
+pub fn transitions(request_id: &str) -> Vec<Transition> {
+    transitions_in(&default_request_ledger_path(), request_id)
+}
+
+/// Pending permission requests in the default ledger.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn pending() -> Vec<PendingRequest> {
+    pending_in(&default_request_ledger_path())
+}
+
+/// What a request in the default ledger is about, if it recorded that.
+pub fn details(request_id: &str) -> Option<RequestDetails> {
+    details_in(&default_request_ledger_path(), request_id)
+}
+
+/// Leave a decision for a request in the default ledger, logging failures.
+#[cfg_attr(not(any(feature = "telegram", unix, windows)), allow(dead_code))]
+pub async fn resolve(request_id: &str, resolution: Resolution) -> bool {
+    let request_id = request_id.to_string();
+    off_reactor(move || {
+        resolve_in(&default_request_ledger_path(), &request_id, resolution).unwrap_or_else(|e| {
+            tracing::warn!("Failed to resolve request {}: {}", request_id, e);
+            false
+        })
+    })
+    .await
+}
+
+/// Record the answer to a request in the default ledger, unless someone
+/// answered it first.
+///
+/// A ledger that cannot be written does not hold up the decision.
+pub async fn claim(request_id: &str, answer: Answer) -> Result<(), Answer> {
+    let request_id = request_id.to_string();
+    off_reactor(move || {
+        claim_in(&default_request_ledger_path(), &request_id, answer).unwrap_or_else(|e| {
+            tracing::warn!("Failed to record answer to request {}: {}", request_id, e);
+            Ok(())
+        })
+    })
+    .await
+}
+
+/// Record an answer that came without a button to refuse, e.g. from an
+/// automation or plugin, and return the decision that stands.
+///
+/// If a bulk decision, approval link or the command API got there first,
+/// its decision and author win over the late answer.
+pub async fn settle(
+    request_id: &str,
+    decision: Decision,
+    by: Option<String>,
+) -> (Decision, Option<String>) {
+    let answer = Answer::new(decision, by.as_deref().unwrap_or_default());
+    if claim(request_id, answer).await.is_ok() {
+        return (decision, by);
+    }
+    match resolution(request_id) {
+        Some(resolution) if resolution.allow => (Decision::Allow, Some(resolution.by)),
+        Some(resolution) => (Decision::Deny, Some(resolution.by)),
+        None => (decision, by),
+    }
+}
+
+/// Reply for a press or answer on a request in the default ledger that can
+/// no longer take one.
+#[cfg_attr(
+    not(any(feature = "telegram", feature = "signal", unix, windows)),
+    allow(dead_code)
+)]
+pub fn closed_notice(request_id: &str) -> Option<String> {
+    closed_notice_in(&default_request_ledger_path(), request_id)
+}
+
+/// The decision left for a request in the default ledger, if any.
+pub fn resolution(request_id: &str) -> Option<Resolution> {
+    resolution_in(&default_request_ledger_path(), request_id)
+}
+
+/// The answer that decided a request in the default ledger, if any.
+pub fn answer(request_id: &str) -> Option<Answer> {
+    answer_in(&default_request_ledger_path(), request_id)
+}
+
+/// Keep the chat message of a request in the default ledger, logging failures.
+#[cfg_attr(not(any(feature = "telegram", feature = "discord")), allow(dead_code))]
+pub async fn keep_message(request_id: &str, message: PostedMessage) {
+    let request_id = request_id.to_string();
+    off_reactor(move || {
+        if let Err(e) = keep_message_in(&default_request_ledger_path(), &request_id, message) {
+            tracing::warn!("Failed to keep message of request {}: {}", request_id, e);
+        }
+    })
+    .await
+}
+
+/// Take the chat message of a request in the default ledger that was
+/// handled in the terminal, logging failures.
+pub async fn take_handled_locally(session_id: &str, tool: &str) -> Option<PostedMessage> {
+    let (session_id, tool) = (session_id.to_string(), tool.to_string());
+    off_reactor(move || {
+        take_handled_locally_in(&default_request_ledger_path(), &session_id, &tool).unwrap_or_else(
+            |e| {
+                tracing::warn!("Failed to look up requests handled locally: {}", e);
+                None
+            },
+        )
+    })
+    .await
+}
+
+/// Reply for a press or answer on a request that can no longer take one.
+///
+/// Once a request is answered, it names the answer, e.g. "Already decided:
+/// ❌ Denied by @ana". Waiting requests get none.
+pub fn closed_notice_in(dir: &Path, request_id: &str) -> Option<String> {
+    if let Some(answer) = answer_in(dir, request_id) {
+        return Some(answer.notice());
+    }
+    stale_notice(state_in(dir, request_id)).map(str::to_string)
+}
+
+fn stale_notice(state: RequestState) -> Option<&'static str> {
+    match state {
+        RequestState::Sent => None,
+        RequestState::Answered => Some("This request has already been answered"),
+        RequestState::TimedOut => Some("This request has timed out"),
+        RequestState::Cancelled => Some("This request was cancelled"),
+        RequestState::Expired => Some("This request has expired"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_request_lifecycle() {
+        let dir = tempdir().unwrap();
+        assert_eq!(state_in(dir.path(), "abc12345"), RequestState::Expired);
+
+        open_in(dir.path(), "abc12345", Duration::from_secs(60), None).unwrap();
+        assert_eq!(state_in(dir.path(), "ABC12345"), RequestState::Sent);
+
+        assert!(transition_in(dir.path(), "abc12345", RequestState::Answered).unwrap());
+        assert_eq!(state_in(dir.path(), "abc12345"), RequestState::Answered);
+        assert_eq!(
+            stale_notice(state_in(dir.path(), "abc12345")),
+            Some("This request has already been answered")
+        );
+
+        // Final states stay final
+        assert!(!transition_in(dir.path(), "abc12345", RequestState::TimedOut).unwrap());
+        let states: Vec<_> = transitions_in(dir.path(), "abc12345")
+            .iter()
+            .map(|transition| transition.state)
+            .collect();
+        assert_eq!(states, [RequestState::Sent, RequestState::Answered]);
+    }
+
+    #[test]
+    fn test_legacy_entry_is_read() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("abc12345"),
+            r#"{"status": "resolved", "expires_at": 0}"#,
+        )
+        .unwrap();
+        assert_eq!(state_in(dir.path(), "abc12345"), RequestState::Answered);
+    }
+
+    #[test]
+    fn test_bulk_resolution() {
+        let dir = tempdir().unwrap();
+        let details = RequestDetails {
+            host: "laptop".to_string(),
+            session_id: "session-1".to_string(),
+            summary: "Bash \"ls\"".to_string(),
+            required_approvals: 1,
+            tool: "Bash `ls`".to_string(),
+        };
+        open_in(
+            dir.path(),
+            "abc12345",
+            Duration::from_secs(60),
+            Some(details.clone()),
+        )
+        .unwrap();
+        open_in(dir.path(), "question1", Duration::from_secs(60), None).unwrap();
+
+        let pending = pending_in(dir.path());
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].request_id, "abc12345");
+        assert_eq!(pending[0].details, details);
+        assert_eq!(pending[0].expires_at, pending[0].sent_at + 60);
+        assert_eq!(resolution_in(dir.path(), "abc12345"), None);
+
+        let resolution = Resolution {
+            allow: true,
+            by: "@ana".to_string(),
+        };
+        assert!(resolve_in(dir.path(), "abc12345", resolution.clone()).unwrap());
+        assert_eq!(
+            resolution_in(dir.path(), "abc12345"),
+            Some(resolution.clone())
+        );
+
+        transition_in(dir.path(), "abc12345", RequestState::Answered).unwrap();
+        assert!(pending_in(dir.path()).is_empty());
+        assert!(!resolve_in(dir.path(), "abc12345", resolution).unwrap());
+    }
+
+    #[test]
+    fn test_first_answer_wins() {
+        let dir = tempdir().unwrap();
+        open_in(dir.path(), "abc12345", Duration::from_secs(60), None).unwrap();
+        assert_eq!(closed_notice_in(dir.path(), "abc12345"), None);
+
+        let alice = Answer::new(Decision::Deny, "@alice");
+        let bob = Answer::new(Decision::Allow, "@bob");
+        assert_eq!(
+            claim_in(dir.path(), "abc12345", alice.clone()).unwrap(),
+            Ok(())
+        );
+        assert_eq!(
+            claim_in(dir.path(), "abc12345", bob).unwrap(),
+            Err(alice.clone())
+        );
+        let resolution = Resolution {
+            allow: true,
+            by: "approval link".to_string(),
+        };
+        assert!(!resolve_in(dir.path(), "abc12345", resolution.clone()).unwrap());
+        assert_eq!(
+            closed_notice_in(dir.path(), "abc12345").as_deref(),
+            Some("Already decided: ❌ Denied by @alice")
+        );
+
+        // A bulk decision or link answers first just the same
+        open_in(dir.path(), "def12345", Duration::from_secs(60), None).unwrap();
+        assert!(resolve_in(dir.path(), "def12345", resolution).unwrap());
+        assert_eq!(
+            claim_in(dir.path(), "def12345", alice).unwrap(),
+            Err(Answer::new(Decision::Allow, "approval link"))
+        );
+
+        // Requests never recorded cannot be raced for
+        let answer = Answer::new(Decision::Allow, "");
+        assert_eq!(claim_in(dir.path(), "9876abcd", answer).unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn test_handled_locally() {
+        let dir = tempdir().unwrap();
+        let details = RequestDetails {
+            session_id: "session-1".to_string(),
+            tool: "Bash `ls`".to_string(),
+            ..RequestDetails::default()
+        };
+        let message = PostedMessage {
+            platform: "telegram".to_string(),
+            chat_id: "42".to_string(),
+            message_id: "7".to_string(),
+            text: "Bash ls".to_string(),
+        };
+        for request_id in ["abc12345", "def12345"] {
+            let details = Some(details.clone());
+            open_in(dir.path(), request_id, Duration::from_secs(60), details).unwrap();
+        }
+
+        // Only requests Claude gave up on keep their message
+        keep_message_in(dir.path(), "abc12345", message.clone()).unwrap();
+        assert!(transition_in(dir.path(), "def12345", RequestState::Answered).unwrap());
+        keep_message_in(dir.path(), "def12345", message.clone()).unwrap();
+        assert_eq!(
+            take_handled_locally_in(dir.path(), "session-1", "Bash `ls`").unwrap(),
+            None
+        );
+
+        assert!(transition_in(dir.path(), "abc12345", RequestState::Cancelled).unwrap());
+        keep_message_in(dir.path(), "abc12345", message.clone()).unwrap();
+        assert_eq!(
+            take_handled_locally_in(dir.path(), "session-2", "Bash `ls`").unwrap(),
+            None
+        );
+        assert_eq!(
+            take_handled_locally_in(dir.path(), "session-1", "Bash `pwd`").unwrap(),
+            None
+        );
+        assert_eq!(
+            take_handled_locally_in(dir.path(), "session-1", "Bash `ls`").unwrap(),
+            Some(message)
+        );
+        assert_eq!(
+            take_handled_locally_in(dir.path(), "session-1", "Bash `ls`").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pending_request_expires() {
+        let dir = tempdir().unwrap();
+        write_entry(
+            dir.path(),
+            "abc12345",
+            &LedgerEntry {
+                state: RequestState::Sent,
+                expires_at: now_secs() - 1,
+                transitions: Vec::new(),
+                details: None,
+                resolution: None,
+                answer: None,
+                message: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(state_in(dir.path(), "abc12345"), RequestState::Expired);
+    }
+
+    #[test]
+    fn test_invalid_request_id_is_ignored() {
+        let dir = tempdir().unwrap();
+        open_in(dir.path(), "../escape", Duration::from_secs(60), None).unwrap();
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+        assert_eq!(state_in(dir.path(), "../escape"), RequestState::Expired);
+    }
+}
@@ -0,0 +1,258 @@
+//! Mock messenger for integration testing (requires the `mock` feature).
+//!
+//! Records every message instead of sending it and answers decision requests
+//! from a script. When `CCR_MOCK_DECISIONS` or `CCR_MOCK_FIXTURE` is set,
+//! `build_messenger` returns a mock in place of each configured messenger, so
+//! the binary's full hook pipeline can run without live APIs.
+
+use super::{Decision, Messenger, PermissionMessage, TextFormat};
+use crate::error::HookError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Comma-separated scripted decisions, e.g. `allow,deny,always_allow`.
+pub const DECISIONS_ENV: &str = "CCR_MOCK_DECISIONS";
+
+/// Path of a JSON array of scripted decisions.
+pub const FIXTURE_ENV: &str = "CCR_MOCK_FIXTURE";
+
+/// Path of a JSONL file that every recorded message is appended to.
+pub const LOG_ENV: &str = "CCR_MOCK_LOG";
+
+/// A message the mock was asked to send.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SentMessage {
+    PermissionRequest {
+        request_id: String,
+        tool_name: String,
+        tool_input: Value,
+    },
+    PermissionGroup {
+        request_ids: Vec<String>,
+    },
+    AutoApproved {
+        request_id: String,
+        tool_name: String,
+        tool_input: Value,
+    },
+    Question {
+        question: String,
+    },
+    Notification {
+        text: String,
+    },
+    Text {
+        text: String,
+    },
+    File {
+        path: PathBuf,
+    },
+}
+
+/// Parse a scripted decision ("allow", "deny", "always_allow", "block",
+/// "snooze", "suggest:N").
+pub fn parse_decision(value: &str) -> Option<Decision> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "allow" => Some(Decision::Allow),
+        "deny" => Some(Decision::Deny),
+        "always_allow" | "always" => Some(Decision::AlwaysAllow),
+        "block" => Some(Decision::DenyAndBlock),
+        "snooze" => Some(Decision::Snooze),
+        other => other
+            .strip_prefix("suggest:")
+            .and_then(|index| index.parse().ok())
+            .map(Decision::ApplySuggestion),
+    }
+}
+
+/// Whether the environment asks for mock messengers.
+pub fn enabled() -> bool {
+    std::env::var_os(DECISIONS_ENV).is_some() || std::env::var_os(FIXTURE_ENV).is_some()
+}
+
+/// Read the messages appended to a mock log file.
+#[allow(dead_code)]
+pub fn read_log(path: &Path) -> Vec<SentMessage> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Messenger that records messages and returns scripted decisions.
+///
+/// Once the script runs out, requests are denied as if they timed out.
+pub struct MockMessenger {
+    platform: &'static str,
+    decisions: Mutex<VecDeque<Decision>>,
+    sent: Mutex<Vec<SentMessage>>,
+    log_path: Option<PathBuf>,
+}
+
+impl MockMessenger {
+    /// Create a mock that answers with the given decisions in order.
+    pub fn with_decisions(decisions: impl IntoIterator<Item = Decision>) -> Self {
+        Self {
+            platform: "Mock",
+            decisions: Mutex::new(decisions.into_iter().collect()),
+            sent: Mutex::new(Vec::new()),
+            log_path: None,
+        }
+    }
+
+    /// Create a mock standing in for `platform`, scripted from the environment.
+    ///
+    /// Unknown decision names are skipped with a warning.
+    pub fn from_env(platform: &'static str) -> Self {
+        let script = match std::env::var(DECISIONS_ENV) {
+            Ok(list) => list.split(',').map(str::to_string).collect(),
+            Err(_) => std::env::var(FIXTURE_ENV)
+                .ok()
+                .and_then(|path| fs::read_to_string(path).ok())
+                .and_then(|content| serde_json::from_str::<Vec<String>>(&content).ok())
+                .unwrap_or_default(),
+        };
+        let decisions = script.iter().filter_map(|name| {
+            let decision = parse_decision(name);
+            if decision.is_none() {
+                tracing::warn!("Ignoring unknown mock decision: {}", name);
+            }
+            decision
+        });
+
+        let mut mock = Self::with_decisions(decisions);
+        mock.platform = platform;
+        mock.log_path = std::env::var_os(LOG_ENV).map(PathBuf::from);
+        mock
+    }
+
+    /// Messages recorded so far.
+    #[allow(dead_code)]
+    pub fn sent(&self) -> Vec<SentMessage> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    fn record(&self, message: SentMessage) -> Result<(), HookError> {
+        if let Some(path) = &self.log_path {
+            let line = serde_json::to_string(&message)? + "\n";
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .write_all(line.as_bytes())?;
+        }
+        self.sent.lock().unwrap().push(message);
+        Ok(())
+    }
+
+    fn next_decision(&self) -> Decision {
+        self.decisions
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or(Decision::Deny)
+    }
+}
+
+#[async_trait]
+impl Messenger for MockMessenger {
+    async fn send_permission_request(
+        &self,
+        message: &PermissionMessage,
+        _timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        self.record(SentMessage::PermissionRequest {
+            request_id: message.request_id.clone(),
+            tool_name: message.tool_name.clone(),
+            tool_input: message.tool_input.clone(),
+        })?;
+        Ok(self.next_decision())
+    }
+
+    /// Answers each request in the group with the next scripted decision.
+    async fn send_permission_group(
+        &self,
+        messages: &[PermissionMessage],
+        _timeout: Duration,
+    ) -> Result<Vec<Decision>, HookError> {
+        self.record(SentMessage::PermissionGroup {
+            request_ids: messages
+                .iter()
+                .map(|message| message.request_id.clone())
+                .collect(),
+        })?;
+        Ok(messages.iter().map(|_| self.next_decision()).collect())
+    }
+
+    fn supports_grouping(&self) -> bool {
+        true
+    }
+
+    async fn send_question(
+        &self,
+        question: &str,
+        _hostname: &str,
+        _timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        self.record(SentMessage::Question {
+            question: question.to_string(),
+        })?;
+        Ok(self.next_decision())
+    }
+
+    async fn send_notification(&self, text: &str) -> Result<(), HookError> {
+        self.record(SentMessage::Notification {
+            text: text.to_string(),
+        })
+    }
+
+    async fn send_text(&self, text: &str, _format: TextFormat) -> Result<(), HookError> {
+        self.record(SentMessage::Text {
+            text: text.to_string(),
+        })
+    }
+
+    async fn send_file(&self, path: &Path) -> Result<(), HookError> {
+        self.record(SentMessage::File {
+            path: path.to_path_buf(),
+        })
+    }
+
+    async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError> {
+        self.record(SentMessage::AutoApproved {
+            request_id: message.request_id.clone(),
+            tool_name: message.tool_name.clone(),
+            tool_input: message.tool_input.clone(),
+        })
+    }
+
+    fn platform_name(&self) -> &'static str {
+        self.platform
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decision() {
+        assert_eq!(parse_decision("allow"), Some(Decision::Allow));
+        assert_eq!(parse_decision(" Deny "), Some(Decision::Deny));
+        assert_eq!(parse_decision("always_allow"), Some(Decision::AlwaysAllow));
+        assert_eq!(
+            parse_decision("suggest:0"),
+            Some(Decision::ApplySuggestion(0))
+        );
+        assert_eq!(parse_decision("maybe"), None);
+    }
+}
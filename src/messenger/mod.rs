@@ -3,21 +3,49 @@
 //! Provides a trait-based abstraction over messaging platforms (Telegram, Signal, etc.)
 //! to enable sending permission requests and receiving user decisions.
 
+mod compact;
+mod dry_run;
+pub mod ledger;
+pub mod plugin;
+pub mod queue;
 mod types;
+pub mod vacation;
 
+pub mod clients;
+
+#[cfg(feature = "telegram")]
+mod edits;
+#[cfg(feature = "telegram")]
 pub mod telegram;
 
+#[cfg(feature = "telegram")]
+pub mod updates;
+
 #[cfg(feature = "signal")]
 pub mod signal;
 
 #[cfg(feature = "discord")]
 pub mod discord;
 
-pub use types::{Decision, PermissionMessage};
+#[cfg(feature = "homeassistant")]
+pub mod homeassistant;
+
+#[cfg(feature = "mock")]
+pub mod mock;
+
+pub use types::{Decision, PermissionMessage, TextFormat};
 
+use crate::config::{env_flag, Config, Severity};
 use crate::error::HookError;
+use crate::history::{self, EventKind, HistoryEntry};
+use crate::redact::Redactor;
 use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
+use tokio::sync::watch;
 
 /// Abstraction over messaging platforms for permission request handling.
 #[async_trait]
@@ -36,15 +64,313 @@ pub trait Messenger: Send + Sync {
         timeout: Duration,
     ) -> Result<Decision, HookError>;
 
+    /// Ask a yes/no question and wait for the answer.
+    ///
+    /// Returns `Decision::Deny` if no answer arrives before the timeout.
+    async fn send_question(
+        &self,
+        question: &str,
+        hostname: &str,
+        timeout: Duration,
+    ) -> Result<Decision, HookError>;
+
     /// Send a notification message (no response expected).
     ///
     /// Used for auto-approved notifications and job completion alerts.
     async fn send_notification(&self, text: &str) -> Result<(), HookError>;
 
+    /// Send free-form text rendered according to `format`.
+    async fn send_text(&self, text: &str, format: TextFormat) -> Result<(), HookError>;
+
+    /// Upload a file as an attachment.
+    async fn send_file(&self, path: &Path) -> Result<(), HookError>;
+
     /// Send an auto-approved notification with request details.
     async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError>;
 
+    /// Who answered the most recent permission request or question.
+    ///
+    /// Platforms that cannot identify the responder return `None`.
+    fn last_responder(&self) -> Option<String> {
+        None
+    }
+
+    /// Mark the message of a request Claude gave up on as handled in the
+    /// terminal.
+    ///
+    /// Platforms that cannot edit sent messages leave it as it is.
+    async fn mark_handled_locally(
+        &self,
+        _message: &ledger::PostedMessage,
+    ) -> Result<(), HookError> {
+        Ok(())
+    }
+
+    /// Send several permission requests as one message and wait until each
+    /// is decided.
+    ///
+    /// Decisions come back in the order of `messages`, and requests still
+    /// undecided at the timeout are denied. Only used when
+    /// [`supports_grouping`](Self::supports_grouping) is true; by default the
+    /// requests are sent one at a time.
+    async fn send_permission_group(
+        &self,
+        messages: &[PermissionMessage],
+        timeout: Duration,
+    ) -> Result<Vec<Decision>, HookError> {
+        let mut decisions = Vec::with_capacity(messages.len());
+        for message in messages {
+            decisions.push(self.send_permission_request(message, timeout).await?);
+        }
+        Ok(decisions)
+    }
+
+    /// Whether bursts of requests can be shown as one message.
+    fn supports_grouping(&self) -> bool {
+        false
+    }
+
+    /// Whether `PermissionMessage::required_approvals` above 1 is enforced.
+    ///
+    /// Requests needing several approvals are only sent through messengers
+    /// that can tell distinct responders apart.
+    fn supports_quorum(&self) -> bool {
+        false
+    }
+
+    /// Whether this messenger should take messages right now.
+    ///
+    /// Messengers tied to a place, like Home Assistant while nobody is home,
+    /// step aside so the next one in `messenger_priority` is used.
+    async fn is_available(&self) -> bool {
+        true
+    }
+
     /// Get the platform name for logging purposes.
-    #[allow(dead_code)]
     fn platform_name(&self) -> &'static str;
 }
+
+/// Check a responder against an allowlist; an empty list allows anyone.
+#[cfg_attr(not(any(feature = "telegram", feature = "signal")), allow(dead_code))]
+pub fn is_authorized<T: PartialEq>(allowed: &[T], responder: &T) -> bool {
+    allowed.is_empty() || allowed.contains(responder)
+}
+
+/// Generate a short request ID (8-char UUID prefix).
+pub fn new_request_id() -> String {
+    uuid::Uuid::new_v4().to_string()[..8].to_string()
+}
+
+/// Generate a permission request ID for a host and session.
+///
+/// The last 8 characters are random. Lengths past 8 (up to 16) lead the ID
+/// with a hash of the host and session, so requests from machines sharing a
+/// chat cannot match each other's button presses even if their random parts
+/// collide.
+pub fn scoped_request_id(hostname: &str, session_id: &str, length: usize) -> String {
+    let scope_chars = length.clamp(8, 16) - 8;
+    let scope: String = Sha256::digest(format!("{}\n{}", hostname, session_id))
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("{}{}", &scope[..scope_chars], new_request_id())
+}
+
+tokio::task_local! {
+    /// Turns true once whoever asked for the current decision gives up on it.
+    static CANCEL: watch::Receiver<bool>;
+}
+
+/// Decide with `decide`, letting messengers withdraw the request they sent
+/// once `cancel` turns true.
+pub async fn cancellable<F: Future>(cancel: watch::Receiver<bool>, decide: F) -> F::Output {
+    CANCEL.scope(cancel, decide).await
+}
+
+/// Wait until the decision being made is cancelled.
+///
+/// Never completes outside [`cancellable`].
+pub async fn cancelled() {
+    let Ok(mut cancel) = CANCEL.try_with(watch::Receiver::clone) else {
+        return std::future::pending().await;
+    };
+    if cancel.wait_for(|cancelled| *cancelled).await.is_err() {
+        std::future::pending().await
+    }
+}
+
+/// Set by `--dry-run`.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Print messages instead of sending them for the rest of the process.
+pub fn enable_dry_run() {
+    DRY_RUN.store(true, Ordering::Relaxed);
+}
+
+/// Whether messages are printed instead of sent (`--dry-run` or `CCR_DRY_RUN=1`).
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed) || env_flag("CCR_DRY_RUN")
+}
+
+/// Build the messenger with the given name, if it is configured and enabled.
+///
+/// In dry-run mode a printing stand-in is returned instead; with the `mock`
+/// feature, a scripted mock when `CCR_MOCK_DECISIONS` or `CCR_MOCK_FIXTURE` is set.
+/// Signal is never built because it does not implement `Messenger`.
+pub fn build_messenger(config: &Config, name: &str) -> Option<Box<dyn Messenger>> {
+    let messenger = build_live_messenger(config, name)?;
+    if dry_run() {
+        return Some(Box::new(
+            dry_run::DryRunMessenger::new(messenger.platform_name())
+                .with_quorum(messenger.supports_quorum()),
+        ));
+    }
+    #[cfg(feature = "mock")]
+    if mock::enabled() {
+        return Some(Box::new(mock::MockMessenger::from_env(
+            messenger.platform_name(),
+        )));
+    }
+    Some(messenger)
+}
+
+fn build_live_messenger(config: &Config, name: &str) -> Option<Box<dyn Messenger>> {
+    match name {
+        #[cfg(feature = "telegram")]
+        "telegram" => config.telegram.as_ref().map(|telegram_config| {
+            Box::new(
+                telegram::TelegramMessenger::new(
+                    &telegram_config.bot_token,
+                    teloxide::types::ChatId(telegram_config.chat_id.0),
+                )
+                .with_topic(telegram_config.topic_id)
+                .with_compact(telegram_config.compact)
+                .with_responders(config.authorized_responders.telegram.clone()),
+            ) as Box<dyn Messenger>
+        }),
+        #[cfg(feature = "discord")]
+        "discord" => config
+            .discord
+            .as_ref()
+            .filter(|discord_config| discord_config.enabled)
+            .map(|discord_config| {
+                Box::new(
+                    discord::DiscordMessenger::new(
+                        &discord_config.bot_token,
+                        discord_config.user_id,
+                    )
+                    .with_compact(discord_config.compact),
+                ) as Box<dyn Messenger>
+            }),
+        #[cfg(feature = "homeassistant")]
+        "homeassistant" => config.homeassistant.as_ref().map(|homeassistant_config| {
+            Box::new(homeassistant::HomeAssistantMessenger::new(
+                homeassistant_config.clone(),
+            )) as Box<dyn Messenger>
+        }),
+        name => config
+            .plugins
+            .iter()
+            .find(|plugin| plugin.name == name)
+            .map(|plugin| {
+                Box::new(plugin::PluginMessenger::new(plugin.clone())) as Box<dyn Messenger>
+            }),
+    }
+}
+
+/// Build every enabled messenger, in `messenger_priority` order.
+pub fn enabled_messengers(config: &Config) -> Vec<Box<dyn Messenger>> {
+    config
+        .messenger_priority
+        .iter()
+        .filter_map(|name| build_messenger(config, name))
+        .collect()
+}
+
+/// Build every enabled messenger routed events of `severity` that is
+/// available right now, in order.
+pub async fn available_messengers(config: &Config, severity: Severity) -> Vec<Box<dyn Messenger>> {
+    let mut available = Vec::new();
+    let routed = config
+        .messengers_for(severity)
+        .iter()
+        .filter_map(|name| build_messenger(config, name));
+    for messenger in routed {
+        if messenger.is_available().await {
+            available.push(messenger);
+        }
+    }
+    available
+}
+
+/// First line of a notification without markup, for the history log.
+fn notification_summary(text: &str) -> String {
+    text.lines().next().unwrap_or_default().replace("**", "")
+}
+
+/// Send a notification via the first messenger routed info events that delivers it.
+///
+/// Falls back to the next messenger on failure and returns the last error if
+/// all fail. Silently succeeds when no messenger is configured.
+pub async fn notify_primary(config: &Config, text: &str) -> Result<(), HookError> {
+    let text = &Redactor::new(&config.redaction).redact(text);
+    let mut last_error = None;
+    let summary = notification_summary(text);
+    for messenger in available_messengers(config, Severity::Info).await {
+        let platform = Some(messenger.platform_name());
+        match messenger.send_notification(text).await {
+            Ok(()) => {
+                history::record(HistoryEntry::new(
+                    EventKind::Notification,
+                    platform,
+                    summary.as_str(),
+                ));
+                return Ok(());
+            }
+            Err(e) => {
+                tracing::warn!("Failed to notify via {}: {}", messenger.platform_name(), e);
+                history::record(HistoryEntry::new(EventKind::Error, platform, e.to_string()));
+                last_error = Some(e);
+            }
+        }
+    }
+    last_error.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoped_request_id() {
+        assert_eq!(scoped_request_id("laptop", "s1", 8).len(), 8);
+        assert_eq!(scoped_request_id("laptop", "s1", 40).len(), 16);
+
+        let first = scoped_request_id("laptop", "s1", 12);
+        let second = scoped_request_id("laptop", "s1", 12);
+        assert_eq!(first.len(), 12);
+        assert_eq!(first[..4], second[..4]);
+        assert_ne!(first[4..], second[4..]);
+        assert_ne!(scoped_request_id("gpu-box", "s1", 12)[..4], first[..4]);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled() {
+        let wait = Duration::from_millis(50);
+        assert!(tokio::time::timeout(wait, cancelled()).await.is_err());
+
+        let (cancel, receiver) = watch::channel(false);
+        let decide = cancellable(receiver, tokio::time::timeout(wait * 20, cancelled()));
+        let (result, ()) = tokio::join!(decide, async {
+            tokio::time::sleep(wait).await;
+            cancel.send(true).unwrap();
+        });
+        assert!(result.is_ok());
+
+        // A dropped sender never cancels
+        let (cancel, receiver) = watch::channel(false);
+        drop(cancel);
+        let decide = cancellable(receiver, tokio::time::timeout(wait, cancelled()));
+        assert!(decide.await.is_err());
+    }
+}
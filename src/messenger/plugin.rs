@@ -0,0 +1,414 @@
+//! Messengers run as external programs.
+//!
+//! A plugin is any executable, by default `~/.claude/ccr-plugins/<name>`,
+//! enabled under `messengers.plugins.<name>` and used like a built-in
+//! messenger by that name. It is run once per call with the call as its only
+//! argument, reads one JSON object on stdin and writes one on stdout:
+//!
+//! - `send` delivers a message. The object has a `kind` (`permission`,
+//!   `question`, `notification`, `auto_approved`, `resolved` or `file`), a
+//!   one-line `text` and the `settings` from the config, plus the request's
+//!   `request_id`, `tool_name`, `tool_input`, `host`, `approval_link`,
+//!   `explanation` and `actions` where they apply. The plugin answers `{}`, or
+//!   `{"error": "..."}` if delivery failed.
+//! - `receive` asks about the answer to `request_id` and is repeated until
+//!   there is one. The plugin answers `{"decision": null}` while nobody has
+//!   answered, then e.g. `{"decision": "allow", "responder": "Ana"}`, with
+//!   a decision of `allow`, `deny`, `always_allow` or `block`.
+//!
+//! Once a request is decided, times out or is withdrawn, a `resolved`
+//! message with its `status` lets the plugin clear it. Its `decided` names
+//! the answer that won, e.g. "❌ Denied by Ana", for anyone answering later:
+//! the first answer stands, even if it came from `/allow_all` or a link. A non-zero exit or
+//! more than 30 seconds per call counts as a failure.
+
+use super::compact;
+use super::ledger::{self, RequestDetails, RequestState};
+use super::{cancelled, new_request_id, Decision, Messenger, PermissionMessage, TextFormat};
+use crate::config::PluginConfig;
+use crate::error::HookError;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// Longest a plugin may take for one call.
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a plugin is asked for an answer.
+const RECEIVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Messenger backed by a plugin program.
+pub struct PluginMessenger {
+    config: PluginConfig,
+    last_responder: Mutex<Option<String>>,
+}
+
+impl PluginMessenger {
+    /// Create a messenger running the configured plugin.
+    pub fn new(config: PluginConfig) -> Self {
+        Self {
+            config,
+            last_responder: Mutex::new(None),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> HookError {
+        HookError::Plugin(self.config.name.clone(), message.into())
+    }
+
+    /// Run the plugin for one call and return its answer.
+    async fn call(&self, action: &str, mut input: Value) -> Result<Value, HookError> {
+        input["settings"] = self.config.settings.clone();
+        let mut child = Command::new(&self.config.command)
+            .arg(action)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                self.error(format!(
+                    "failed to run {}: {}",
+                    self.config.command.display(),
+                    e
+                ))
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A plugin that exits without reading is judged by its exit status
+            let _ = stdin.write_all(input.to_string().as_bytes()).await;
+        }
+
+        let output = timeout(CALL_TIMEOUT, child.wait_with_output())
+            .await
+            .map_err(|_| self.error(format!("`{}` timed out", action)))?
+            .map_err(|e| self.error(e.to_string()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr.lines().last().unwrap_or_default();
+            return Err(self.error(format!(
+                "`{}` exited with {} {}",
+                action, output.status, reason
+            )));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.trim().is_empty() {
+            return Ok(json!({}));
+        }
+        let answer: Value = serde_json::from_str(stdout.trim())
+            .map_err(|e| self.error(format!("invalid answer to `{}`: {}", action, e)))?;
+        match answer.get("error").and_then(Value::as_str) {
+            Some(error) => Err(self.error(error)),
+            None => Ok(answer),
+        }
+    }
+
+    async fn send(&self, message: Value) -> Result<(), HookError> {
+        self.call("send", message).await.map(|_| ())
+    }
+
+    /// Ask the plugin until it has an answer, or one is left in the ledger.
+    async fn receive(&self, request_id: &str) -> Result<(Decision, Option<String>), HookError> {
+        loop {
+            if let Some(resolution) = ledger::resolution(request_id) {
+                let decision = if resolution.allow {
+                    Decision::Allow
+                } else {
+                    Decision::Deny
+                };
+                return Ok((decision, Some(resolution.by)));
+            }
+            let answer = self
+                .call("receive", json!({"request_id": request_id}))
+                .await?;
+            if let Some(decision) = parse_answer(&answer) {
+                return Ok(decision);
+            }
+            tokio::time::sleep(RECEIVE_INTERVAL).await;
+        }
+    }
+
+    /// Send a request and wait for its decision.
+    ///
+    /// Unanswered requests are denied once the timeout expires.
+    async fn request_decision(
+        &self,
+        request_id: &str,
+        request: Value,
+        request_timeout: Duration,
+        details: Option<RequestDetails>,
+    ) -> Result<Decision, HookError> {
+        self.send(request).await?;
+        ledger::open_with_details(request_id, request_timeout, details).await;
+
+        let answer = tokio::select! {
+            answer = timeout(request_timeout, self.receive(request_id)) => Some(answer),
+            _ = cancelled() => None,
+        };
+        let (decision, status) = match answer {
+            // Claude gave up on the request
+            None => {
+                ledger::transition(request_id, RequestState::Cancelled).await;
+                (Decision::Deny, "cancelled")
+            }
+            Some(Ok(Ok((decision, responder)))) => {
+                let (decision, responder) = ledger::settle(request_id, decision, responder).await;
+                ledger::transition(request_id, RequestState::Answered).await;
+                *self
+                    .last_responder
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()) = responder;
+                (decision, decision_name(decision))
+            }
+            Some(Ok(Err(e))) => {
+                ledger::transition(request_id, RequestState::Cancelled).await;
+                return Err(e);
+            }
+            // Timeout - deny by default
+            Some(Err(_)) => {
+                ledger::transition(request_id, RequestState::TimedOut).await;
+                (Decision::Deny, "timeout")
+            }
+        };
+
+        // Let the plugin clear the request; the decision stands either way
+        let resolved = json!({
+            "kind": "resolved",
+            "request_id": request_id,
+            "status": status,
+            "decided": ledger::answer(request_id).map(|answer| answer.to_string()),
+            "text": format!("Request [{}]: {}", request_id, status_text(status)),
+        });
+        if let Err(e) = self.send(resolved).await {
+            tracing::warn!("{}", e);
+        }
+        if status == "cancelled" {
+            return Err(HookError::Cancelled);
+        }
+        Ok(decision)
+    }
+}
+
+#[async_trait]
+impl Messenger for PluginMessenger {
+    async fn send_permission_request(
+        &self,
+        message: &PermissionMessage,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let request = json!({
+            "kind": "permission",
+            "request_id": message.request_id,
+            "text": compact::permission_line(message),
+            "host": message.host(),
+            "tool_name": message.tool_name,
+            "tool_input": message.tool_input,
+            "context": message.context,
+            "explanation": message.explanation,
+            "approval_link": message.approval_link,
+            "timeout_seconds": request_timeout.as_secs(),
+            "actions": ["allow", "deny", "always_allow", "block", "snooze"],
+        });
+        self.request_decision(
+            &message.request_id,
+            request,
+            request_timeout,
+            Some(RequestDetails::of(message)),
+        )
+        .await
+    }
+
+    async fn send_question(
+        &self,
+        question: &str,
+        hostname: &str,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let request_id = new_request_id();
+        let request = json!({
+            "kind": "question",
+            "request_id": request_id,
+            "text": compact::question_line(hostname, question),
+            "host": hostname,
+            "timeout_seconds": request_timeout.as_secs(),
+            "actions": ["allow", "deny"],
+        });
+        self.request_decision(&request_id, request, request_timeout, None)
+            .await
+    }
+
+    async fn send_notification(&self, text: &str) -> Result<(), HookError> {
+        self.send_text(text, TextFormat::Markdown).await
+    }
+
+    async fn send_text(&self, text: &str, format: TextFormat) -> Result<(), HookError> {
+        let format = match format {
+            TextFormat::Plain => "plain",
+            TextFormat::Markdown => "markdown",
+            TextFormat::Code => "code",
+        };
+        self.send(json!({"kind": "notification", "text": text, "format": format}))
+            .await
+    }
+
+    async fn send_file(&self, path: &Path) -> Result<(), HookError> {
+        self.send(json!({
+            "kind": "file",
+            "path": path,
+            "text": path.file_name().map(|name| name.to_string_lossy()),
+        }))
+        .await
+    }
+
+    async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError> {
+        self.send(json!({
+            "kind": "auto_approved",
+            "request_id": message.request_id,
+            "text": compact::auto_approved_line(message),
+            "host": message.host(),
+            "tool_name": message.tool_name,
+            "tool_input": message.tool_input,
+        }))
+        .await
+    }
+
+    fn last_responder(&self) -> Option<String> {
+        self.last_responder
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    fn platform_name(&self) -> &'static str {
+        platform_name(&self.config.name)
+    }
+}
+
+/// The plugin's name as a static string, leaked once per name.
+fn platform_name(name: &str) -> &'static str {
+    static NAMES: OnceLock<Mutex<Vec<&'static str>>> = OnceLock::new();
+    let mut names = NAMES
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(known) = names.iter().find(|known| **known == name) {
+        return known;
+    }
+    let leaked: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.push(leaked);
+    leaked
+}
+
+fn decision_name(decision: Decision) -> &'static str {
+    match decision {
+        Decision::Deny => "deny",
+        Decision::AlwaysAllow => "always_allow",
+        Decision::DenyAndBlock => "block",
+        Decision::Snooze => "snooze",
+        Decision::Allow | Decision::ApplySuggestion(_) => "allow",
+    }
+}
+
+fn status_text(status: &str) -> &'static str {
+    match status {
+        "allow" => "✅ Approved",
+        "always_allow" => "🔓 Always Allowed",
+        "deny" => "❌ Denied",
+        "block" => "🚫 Denied and blocked",
+        "snooze" => "⏰ Snoozed",
+        "timeout" => "⏱️ Timeout - Denied",
+        _ => "🚫 Cancelled by Claude",
+    }
+}
+
+/// The decision and responder in an answer to `receive`, if there is one.
+fn parse_answer(answer: &Value) -> Option<(Decision, Option<String>)> {
+    let decision = match answer.get("decision")?.as_str()? {
+        "allow" => Decision::Allow,
+        "deny" => Decision::Deny,
+        "always_allow" => Decision::AlwaysAllow,
+        "block" => Decision::DenyAndBlock,
+        "snooze" => Decision::Snooze,
+        _ => return None,
+    };
+    let responder = answer
+        .get("responder")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    Some((decision, responder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_answer() {
+        assert_eq!(parse_answer(&json!({"decision": null})), None);
+        assert_eq!(
+            parse_answer(&json!({"decision": "block", "responder": "Ana"})),
+            Some((Decision::DenyAndBlock, Some("Ana".to_string())))
+        );
+        assert_eq!(parse_answer(&json!({"decision": "maybe"})), None);
+        // Names are leaked once, not per messenger
+        assert!(std::ptr::eq(platform_name("sms"), platform_name("sms")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_plugin_calls() {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("calls.log");
+        let command = dir.path().join("sms");
+        fs::write(
+            &command,
+            format!(
+                "#!/bin/sh\necho \"$1 $(cat)\" >> {}\n\
+                 case $1 in\n  receive) echo '{{\"decision\": \"allow\", \"responder\": \"Ana\"}}' ;;\n\
+                   *) echo '{{}}' ;;\nesac\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&command, fs::Permissions::from_mode(0o755)).unwrap();
+        let messenger = PluginMessenger::new(PluginConfig {
+            name: "sms".to_string(),
+            command,
+            settings: json!({"to": "+15550100"}),
+        });
+
+        let decision = messenger
+            .send_question("Deploy?", "laptop", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(decision, Decision::Allow);
+        assert_eq!(messenger.last_responder().as_deref(), Some("Ana"));
+        let calls = fs::read_to_string(&log).unwrap();
+        let actions: Vec<&str> = calls
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+        assert_eq!(actions, ["send", "receive", "send"]);
+        assert!(calls.contains(r#""to":"+15550100""#));
+        assert!(calls.contains(r#""status":"allow""#));
+
+        let failing = PluginMessenger::new(PluginConfig {
+            name: "missing".to_string(),
+            command: dir.path().join("missing"),
+            settings: Value::Null,
+        });
+        assert!(matches!(
+            failing.send_notification("hi").await,
+            Err(HookError::Plugin(name, _)) if name == "missing"
+        ));
+    }
+}
@@ -0,0 +1,294 @@
+//! Bounded queue for the daemon's auto-approval notices.
+//!
+//! A hook process sends its auto-approval notice before it answers, one per
+//! process. The daemon answers at once and queues the notice instead, so a
+//! runaway session approving thousands of calls can neither pile up memory
+//! nor keep the chat busy for hours. The queue holds at most
+//! `preferences.notification_queue.capacity` notices:
+//!
+//! - A notice for the same call as one still waiting is counted into it and
+//!   sent as a single line, e.g. `⚙️ laptop:Bash "ls" → ✅ auto ×40`.
+//! - When the queue is full, the oldest notice is dropped, and the next one
+//!   sent is preceded by a line saying how many were lost.
+
+use super::{compact, Decision, Messenger, PermissionMessage, TextFormat};
+use crate::error::HookError;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// An auto-approval waiting to be sent.
+struct Notice {
+    messenger: Arc<dyn Messenger>,
+    message: PermissionMessage,
+    /// Calls it stands for
+    count: usize,
+}
+
+impl Notice {
+    /// Notices for the same call through the same platform are merged.
+    fn key(&self) -> (&'static str, String) {
+        (
+            self.messenger.platform_name(),
+            compact::auto_approved_line(&self.message),
+        )
+    }
+}
+
+#[derive(Default)]
+struct Queued {
+    notices: VecDeque<Notice>,
+    /// Notices dropped since the last one was sent
+    dropped: usize,
+}
+
+/// Auto-approval notices waiting to be sent, oldest first.
+pub struct NotificationQueue {
+    capacity: usize,
+    queued: Mutex<Queued>,
+    ready: Notify,
+}
+
+impl NotificationQueue {
+    /// A queue keeping at most `capacity` notices (at least one).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            queued: Mutex::new(Queued::default()),
+            ready: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queued> {
+        self.queued
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Queue a notice, merging it into a waiting one for the same call or
+    /// making room by dropping the oldest.
+    fn push(&self, notice: Notice) {
+        let mut queued = self.lock();
+        let key = notice.key();
+        if let Some(waiting) = queued.notices.iter_mut().find(|n| n.key() == key) {
+            waiting.count += notice.count;
+        } else {
+            if queued.notices.len() >= self.capacity {
+                let oldest = queued.notices.pop_front();
+                queued.dropped += oldest.map_or(0, |n| n.count);
+            }
+            queued.notices.push_back(notice);
+        }
+        drop(queued);
+        self.ready.notify_one();
+    }
+
+    /// The oldest notice, with how many were dropped before it.
+    fn pop(&self) -> Option<(Notice, usize)> {
+        let mut queued = self.lock();
+        let notice = queued.notices.pop_front()?;
+        Some((notice, std::mem::take(&mut queued.dropped)))
+    }
+
+    /// Send notices as they arrive, one at a time.
+    pub async fn run(&self) {
+        loop {
+            let Some((notice, dropped)) = self.pop() else {
+                self.ready.notified().await;
+                continue;
+            };
+            if let Err(e) = send(&notice, dropped).await {
+                tracing::warn!("Failed to send auto-approval notice: {}", e);
+            }
+        }
+    }
+}
+
+async fn send(notice: &Notice, dropped: usize) -> Result<(), HookError> {
+    if dropped > 0 {
+        let line = format!(
+            "⚠️ {} auto-approval notices were dropped because they arrived faster than they could be sent",
+            dropped
+        );
+        notice.messenger.send_text(&line, TextFormat::Plain).await?;
+    }
+    if notice.count == 1 {
+        return notice.messenger.send_auto_approved(&notice.message).await;
+    }
+    let line = format!(
+        "{} ×{}",
+        compact::auto_approved_line(&notice.message),
+        notice.count
+    );
+    notice.messenger.send_text(&line, TextFormat::Plain).await
+}
+
+static QUEUE: OnceLock<Arc<NotificationQueue>> = OnceLock::new();
+
+/// Queue auto-approval notices in this process from now on, and start
+/// sending them.
+///
+/// Only the first call has an effect.
+pub fn install(capacity: usize) {
+    let queue = Arc::new(NotificationQueue::new(capacity));
+    if QUEUE.set(queue.clone()).is_ok() {
+        tokio::spawn(async move { queue.run().await });
+    }
+}
+
+/// Queue `messenger`'s auto-approval notices if this process has a queue.
+pub fn wrap(messenger: Box<dyn Messenger>) -> Box<dyn Messenger> {
+    match QUEUE.get() {
+        Some(queue) => Box::new(QueuedMessenger {
+            inner: messenger.into(),
+            queue: queue.clone(),
+        }),
+        None => messenger,
+    }
+}
+
+/// Messenger whose auto-approval notices go through a [`NotificationQueue`].
+struct QueuedMessenger {
+    inner: Arc<dyn Messenger>,
+    queue: Arc<NotificationQueue>,
+}
+
+#[async_trait]
+impl Messenger for QueuedMessenger {
+    async fn send_permission_request(
+        &self,
+        message: &PermissionMessage,
+        timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        self.inner.send_permission_request(message, timeout).await
+    }
+
+    async fn send_question(
+        &self,
+        question: &str,
+        hostname: &str,
+        timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        self.inner.send_question(question, hostname, timeout).await
+    }
+
+    async fn send_notification(&self, text: &str) -> Result<(), HookError> {
+        self.inner.send_notification(text).await
+    }
+
+    async fn send_text(&self, text: &str, format: TextFormat) -> Result<(), HookError> {
+        self.inner.send_text(text, format).await
+    }
+
+    async fn send_file(&self, path: &Path) -> Result<(), HookError> {
+        self.inner.send_file(path).await
+    }
+
+    async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError> {
+        self.queue.push(Notice {
+            messenger: self.inner.clone(),
+            message: message.clone(),
+            count: 1,
+        });
+        Ok(())
+    }
+
+    fn last_responder(&self) -> Option<String> {
+        self.inner.last_responder()
+    }
+
+    async fn mark_handled_locally(
+        &self,
+        message: &super::ledger::PostedMessage,
+    ) -> Result<(), HookError> {
+        self.inner.mark_handled_locally(message).await
+    }
+
+    async fn send_permission_group(
+        &self,
+        messages: &[PermissionMessage],
+        timeout: Duration,
+    ) -> Result<Vec<Decision>, HookError> {
+        self.inner.send_permission_group(messages, timeout).await
+    }
+
+    fn supports_grouping(&self) -> bool {
+        self.inner.supports_grouping()
+    }
+
+    fn supports_quorum(&self) -> bool {
+        self.inner.supports_quorum()
+    }
+
+    async fn is_available(&self) -> bool {
+        self.inner.is_available().await
+    }
+
+    fn platform_name(&self) -> &'static str {
+        self.inner.platform_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messenger::dry_run::DryRunMessenger;
+    use serde_json::json;
+
+    fn notice(command: &str) -> Notice {
+        Notice {
+            messenger: Arc::new(DryRunMessenger::new("telegram")),
+            message: PermissionMessage::new(
+                "abc12345".to_string(),
+                "Bash".to_string(),
+                "laptop".to_string(),
+                json!({"command": command}),
+            ),
+            count: 1,
+        }
+    }
+
+    #[test]
+    fn test_repeated_notices_are_coalesced() {
+        let queue = NotificationQueue::new(10);
+        for _ in 0..40 {
+            queue.push(notice("ls"));
+        }
+        queue.push(notice("pwd"));
+
+        let (first, dropped) = queue.pop().unwrap();
+        assert_eq!(first.count, 40);
+        assert_eq!(dropped, 0);
+        assert_eq!(queue.pop().unwrap().0.count, 1);
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn test_full_queue_drops_oldest() {
+        let queue = NotificationQueue::new(2);
+        queue.push(notice("one"));
+        queue.push(notice("one"));
+        queue.push(notice("two"));
+        queue.push(notice("three"));
+
+        let (oldest, dropped) = queue.pop().unwrap();
+        assert_eq!(oldest.message.tool_input["command"], "two");
+        assert_eq!(dropped, 2);
+        let (newest, dropped) = queue.pop().unwrap();
+        assert_eq!(newest.message.tool_input["command"], "three");
+        assert_eq!(dropped, 0);
+    }
+
+    #[test]
+    fn test_capacity_is_at_least_one() {
+        let queue = NotificationQueue::new(0);
+        queue.push(notice("one"));
+        queue.push(notice("two"));
+        let (notice, dropped) = queue.pop().unwrap();
+        assert_eq!(notice.message.tool_input["command"], "two");
+        assert_eq!(dropped, 1);
+    }
+}
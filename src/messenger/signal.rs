@@ -11,7 +11,9 @@
 //! - `DENY {request_id}` - Deny the permission request
 //! - `ALWAYS {request_id}` - Always allow this tool
 
-use super::{Decision, PermissionMessage};
+use super::ledger::{self, Answer, RequestState};
+use super::types::SHOWN_EDITS;
+use super::{cancelled, is_authorized, Decision, PermissionMessage};
 use crate::error::HookError;
 use futures_util::StreamExt;
 use presage::libsignal_service::content::ContentBody;
@@ -38,6 +40,8 @@ pub struct SignalMessenger {
     manager: Manager<SqliteStore, Registered>,
     /// Recipient's Signal UUID
     recipient_uuid: uuid::Uuid,
+    /// Account UUIDs allowed to reply with decisions (empty: anyone)
+    responders: Vec<String>,
 }
 
 #[allow(dead_code)]
@@ -54,9 +58,19 @@ impl SignalMessenger {
         Ok(Self {
             manager,
             recipient_uuid,
+            responders: Vec::new(),
         })
     }
 
+    /// Only honor decision replies from these account UUIDs.
+    pub fn with_responders(mut self, responders: Vec<String>) -> Self {
+        self.responders = responders
+            .iter()
+            .map(|uuid| uuid.to_ascii_lowercase())
+            .collect();
+        self
+    }
+
     /// Load an existing registered manager from storage.
     ///
     /// # Arguments
@@ -67,7 +81,7 @@ impl SignalMessenger {
         recipient_uuid: uuid::Uuid,
     ) -> Result<Self, HookError> {
         let db_path = data_path.join("signal.db");
-        let db_url = format!("sqlite://{}", db_path.display());
+        let db_url = sqlite_url(&db_path);
 
         let store = SqliteStore::open(&db_url, presage_store_sqlite::OnNewIdentity::Trust)
             .await
@@ -105,16 +119,19 @@ impl SignalMessenger {
     }
 
     /// Poll for incoming messages and look for a matching reply.
+    ///
+    /// Replies naming a request that is no longer open get a notice back.
+    /// Returns `None` when the timeout expires.
     async fn poll_for_reply(
         &mut self,
         request_id: &str,
         poll_timeout: Duration,
-    ) -> Result<Decision, HookError> {
+    ) -> Result<Option<Decision>, HookError> {
         let start = std::time::Instant::now();
 
         loop {
             if start.elapsed() >= poll_timeout {
-                return Ok(Decision::Deny); // Timeout - deny by default
+                return Ok(None);
             }
 
             // Check for new messages
@@ -140,9 +157,48 @@ impl SignalMessenger {
 
                     for item in items {
                         if let Received::Content(content) = item {
-                            if let Some(decision) = process_content(&content, request_id) {
-                                return Ok(decision);
+                            let Some((decision, reply_id)) = decision_reply(&content) else {
+                                continue;
+                            };
+                            if !reply_id.eq_ignore_ascii_case(request_id) {
+                                if let Some(notice) = ledger::closed_notice(&reply_id) {
+                                    let _ = self
+                                        .send_message(&format!(
+                                            "Request [{}]: {}",
+                                            reply_id, notice
+                                        ))
+                                        .await;
+                                }
+                                continue;
                             }
+                            let sender = content.metadata.sender.raw_uuid().to_string();
+                            if !is_authorized(&self.responders, &sender) {
+                                tracing::warn!(
+                                    "Ignoring decision on request {} from unauthorized Signal account {}",
+                                    request_id,
+                                    sender
+                                );
+                                let _ = self
+                                    .send_message(&format!(
+                                        "⚠️ Unauthorized decision attempt on request [{}] by {}",
+                                        request_id, sender
+                                    ))
+                                    .await;
+                                continue;
+                            }
+                            if let Err(first) =
+                                ledger::claim(request_id, Answer::new(decision, &sender)).await
+                            {
+                                let _ = self
+                                    .send_message(&format!(
+                                        "Request [{}]: {}",
+                                        request_id,
+                                        first.notice()
+                                    ))
+                                    .await;
+                                continue;
+                            }
+                            return Ok(Some(decision));
                         }
                     }
                 }
@@ -168,24 +224,50 @@ impl SignalMessenger {
 
         // Send the message
         self.send_message(&text).await?;
+        ledger::open(&message.request_id, request_timeout).await;
+
+        // Poll for reply with timeout, unless Claude gives up first
+        let reply = tokio::select! {
+            reply = tokio::time::timeout(
+                request_timeout,
+                self.poll_for_reply(&message.request_id, request_timeout),
+            ) => Some(reply.unwrap_or(Ok(None))),
+            _ = cancelled() => None,
+        };
+        let Some(reply) = reply else {
+            ledger::transition(&message.request_id, RequestState::Cancelled).await;
+            let _ = self
+                .send_message(&format!(
+                    "Request [{}]: 🚫 Cancelled by Claude",
+                    message.request_id
+                ))
+                .await;
+            return Err(HookError::Cancelled);
+        };
 
-        // Poll for reply with timeout
-        let decision = tokio::time::timeout(
-            request_timeout,
-            self.poll_for_reply(&message.request_id, request_timeout),
-        )
-        .await
-        .unwrap_or(Ok(Decision::Deny))?;
-
-        // Send status update
-        let status = match decision {
-            Decision::Allow => "✅ Approved",
-            Decision::Deny => "❌ Denied",
-            Decision::AlwaysAllow => "🔓 Always Allowed",
+        // Timeout - deny by default
+        let decision = match reply {
+            Ok(Some(decision)) => {
+                ledger::transition(&message.request_id, RequestState::Answered).await;
+                decision
+            }
+            Ok(None) => {
+                ledger::transition(&message.request_id, RequestState::TimedOut).await;
+                Decision::Deny
+            }
+            Err(e) => {
+                ledger::transition(&message.request_id, RequestState::Cancelled).await;
+                return Err(e);
+            }
         };
 
+        // Send status update
         let _ = self
-            .send_message(&format!("Request [{}]: {}", message.request_id, status))
+            .send_message(&format!(
+                "Request [{}]: {}",
+                message.request_id,
+                decision.label()
+            ))
             .await;
 
         Ok(decision)
@@ -212,21 +294,15 @@ impl SignalMessenger {
     }
 }
 
-/// Process incoming content and check for a matching decision reply.
+/// Extract a decision reply and the request ID it names from incoming content.
 #[allow(dead_code)]
-fn process_content(content: &Content, request_id: &str) -> Option<Decision> {
-    // Extract the body from the content
-    if let ContentBody::DataMessage(data_message) = &content.body {
-        if let Some(body) = &data_message.body {
-            if let Some((decision, reply_id)) = parse_decision_reply(body) {
-                // Check if this reply matches our request
-                if reply_id.eq_ignore_ascii_case(request_id) {
-                    return Some(decision);
-                }
-            }
+fn decision_reply(content: &Content) -> Option<(Decision, String)> {
+    match &content.body {
+        ContentBody::DataMessage(data_message) => {
+            data_message.body.as_deref().and_then(parse_decision_reply)
         }
+        _ => None,
     }
-    None
 }
 
 /// Format a permission request as a Signal message.
@@ -234,10 +310,14 @@ fn process_content(content: &Content, request_id: &str) -> Option<Decision> {
 fn format_permission_message(message: &PermissionMessage) -> String {
     let mut lines = vec![
         format!("🔐 Permission Request [{}]", message.request_id),
-        format!("🖥️ Host: {}", message.hostname),
+        format!("🖥️ Host: {}", message.host()),
         String::new(),
-        format!("Tool: {}", message.tool_name),
     ];
+    if let Some(context) = &message.context {
+        lines.push(format!("💬 {}", context));
+        lines.push(String::new());
+    }
+    lines.push(format!("Tool: {}", message.tool_name));
 
     match message.tool_name.as_str() {
         "Bash" => {
@@ -245,6 +325,9 @@ fn format_permission_message(message: &PermissionMessage) -> String {
                 let truncated: String = command.chars().take(500).collect();
                 lines.push(format!("Command:\n{}", truncated));
             }
+            if let Some(explanation) = &message.explanation {
+                lines.push(format!("ℹ️ {}", explanation));
+            }
         }
         "Edit" | "Write" => {
             if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
@@ -270,6 +353,78 @@ fn format_permission_message(message: &PermissionMessage) -> String {
                 }
             }
         }
+        "MultiEdit" => {
+            if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
+                lines.push(format!("File: {}", file_path));
+            }
+            let edits = message.multi_edits();
+            lines.push(format!("Edits: {}", edits.len()));
+            for (index, (old_string, new_string)) in edits.iter().take(SHOWN_EDITS).enumerate() {
+                let old: String = old_string.chars().take(200).collect();
+                let new: String = new_string.chars().take(200).collect();
+                lines.push(format!(
+                    "{}. Old:\n{}\n{}. New:\n{}",
+                    index + 1,
+                    old,
+                    index + 1,
+                    new
+                ));
+            }
+            if edits.len() > SHOWN_EDITS {
+                lines.push(format!("... and {} more", edits.len() - SHOWN_EDITS));
+            }
+        }
+        "NotebookEdit" => {
+            if let Some(path) = message
+                .tool_input
+                .get("notebook_path")
+                .and_then(|v| v.as_str())
+            {
+                lines.push(format!("Notebook: {}", path));
+            }
+            lines.push(format!("Change: {}", message.notebook_change()));
+            if let Some(source) = message
+                .tool_input
+                .get("new_source")
+                .and_then(|v| v.as_str())
+            {
+                let truncated: String = source.chars().take(200).collect();
+                lines.push(format!("Source:\n{}", truncated));
+            }
+        }
+        "Task" => {
+            lines.push(format!("🤖 {}", message.subagent_line()));
+            if let Some(prompt) = message.tool_input.get("prompt").and_then(|v| v.as_str()) {
+                let truncated: String = prompt.chars().take(300).collect();
+                lines.push(format!("Prompt: {}", truncated));
+            }
+        }
+        "WebFetch" => {
+            if let Some(domain) = message.fetch_domain() {
+                lines.push(format!("🌐 {}", domain));
+            }
+            if let Some(url) = message.tool_input.get("url").and_then(|v| v.as_str()) {
+                lines.push(format!("URL: {}", url));
+            }
+            if let Some(prompt) = message.tool_input.get("prompt").and_then(|v| v.as_str()) {
+                let truncated: String = prompt.chars().take(200).collect();
+                lines.push(format!("Prompt: {}", truncated));
+            }
+        }
+        "WebSearch" => {
+            if let Some(query) = message.tool_input.get("query").and_then(|v| v.as_str()) {
+                lines.push(format!("🔎 {}", query));
+            }
+            for (key, label) in [
+                ("allowed_domains", "Only"),
+                ("blocked_domains", "Excluding"),
+            ] {
+                let domains = message.search_domains(key);
+                if !domains.is_empty() {
+                    lines.push(format!("{}: {}", label, domains.join(", ")));
+                }
+            }
+        }
         _ => {
             let input_str = serde_json::to_string_pretty(&message.tool_input).unwrap_or_default();
             let truncated: String = input_str.chars().take(500).collect();
@@ -279,8 +434,12 @@ fn format_permission_message(message: &PermissionMessage) -> String {
 
     lines.push(String::new());
     lines.push(format!(
-        "Reply with:\n• ALLOW {}\n• DENY {}\n• ALWAYS {}",
-        message.request_id, message.request_id, message.request_id
+        "Reply with:\n• ALLOW {}\n• DENY {}\n• ALWAYS {}\n• BLOCK {}\n• SNOOZE {}",
+        message.request_id,
+        message.request_id,
+        message.request_id,
+        message.request_id,
+        message.request_id
     ));
 
     lines.join("\n")
@@ -291,7 +450,7 @@ fn format_permission_message(message: &PermissionMessage) -> String {
 fn format_auto_approved_message(message: &PermissionMessage) -> String {
     let mut lines = vec![
         format!("⚙️ Auto-Approved [{}]", message.request_id),
-        format!("🖥️ Host: {}", message.hostname),
+        format!("🖥️ Host: {}", message.host()),
         String::new(),
         format!("Tool: {} (in always-allow list)", message.tool_name),
     ];
@@ -303,11 +462,23 @@ fn format_auto_approved_message(message: &PermissionMessage) -> String {
                 lines.push(format!("Command:\n{}", truncated));
             }
         }
-        "Edit" | "Write" => {
+        "Edit" | "Write" | "MultiEdit" => {
             if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
                 lines.push(format!("File: {}", file_path));
             }
         }
+        "NotebookEdit" => {
+            if let Some(path) = message
+                .tool_input
+                .get("notebook_path")
+                .and_then(|v| v.as_str())
+            {
+                lines.push(format!("Notebook: {}", path));
+            }
+        }
+        "Task" => {
+            lines.push(format!("🤖 {}", message.subagent_line()));
+        }
         _ => {
             let input_str = serde_json::to_string_pretty(&message.tool_input).unwrap_or_default();
             let truncated: String = input_str.chars().take(500).collect();
@@ -324,6 +495,8 @@ fn format_auto_approved_message(message: &PermissionMessage) -> String {
 /// - `ALLOW abc123`
 /// - `DENY abc123`
 /// - `ALWAYS abc123`
+/// - `BLOCK abc123`
+/// - `SNOOZE abc123`
 #[allow(dead_code)]
 pub fn parse_decision_reply(text: &str) -> Option<(Decision, String)> {
     let text = text.trim();
@@ -337,6 +510,8 @@ pub fn parse_decision_reply(text: &str) -> Option<(Decision, String)> {
         "ALLOW" => Decision::Allow,
         "DENY" => Decision::Deny,
         "ALWAYS" => Decision::AlwaysAllow,
+        "BLOCK" => Decision::DenyAndBlock,
+        "SNOOZE" => Decision::Snooze,
         _ => return None,
     };
 
@@ -349,6 +524,40 @@ pub fn parse_decision_reply(text: &str) -> Option<(Decision, String)> {
 // Device Linking
 // ============================================================================
 
+/// SQLite URL of the Signal store in a data directory.
+///
+/// Windows paths are written with forward slashes, as the URL expects.
+fn sqlite_url(db_path: &Path) -> String {
+    let path = db_path.to_string_lossy();
+    if cfg!(windows) {
+        format!("sqlite://{}", path.replace('\\', "/"))
+    } else {
+        format!("sqlite://{}", path)
+    }
+}
+
+/// Check that the Signal store exists and holds a linked device.
+pub async fn check_store(data_path: &Path) -> Result<(), HookError> {
+    let db_path = data_path.join("signal.db");
+    if !db_path.exists() {
+        return Err(HookError::Signal(format!(
+            "No Signal store at {} (run signal-link first)",
+            db_path.display()
+        )));
+    }
+    let db_url = sqlite_url(&db_path);
+
+    let store = SqliteStore::open(&db_url, presage_store_sqlite::OnNewIdentity::Trust)
+        .await
+        .map_err(|e| HookError::Signal(format!("Failed to open Signal store: {}", e)))?;
+
+    Manager::load_registered(store)
+        .await
+        .map_err(|e| HookError::Signal(format!("Failed to load Signal manager: {}", e)))?;
+
+    Ok(())
+}
+
 /// Link this device as a secondary device to an existing Signal account.
 ///
 /// This will display a QR code that needs to be scanned from the primary device.
@@ -359,7 +568,7 @@ pub async fn link_device(
     use futures_channel::oneshot;
 
     let db_path = data_path.join("signal.db");
-    let db_url = format!("sqlite://{}", db_path.display());
+    let db_url = sqlite_url(&db_path);
 
     let store = SqliteStore::open(&db_url, presage_store_sqlite::OnNewIdentity::Trust)
         .await
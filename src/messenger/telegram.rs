@@ -3,143 +3,778 @@
 //! Implements the Messenger trait for Telegram using inline keyboards
 //! for permission decisions.
 
-use super::{Decision, Messenger, PermissionMessage};
+use super::clients;
+use super::compact;
+use super::edits;
+use super::ledger::{self, Answer, PostedMessage, RequestDetails, RequestState};
+use super::types::SHOWN_EDITS;
+use super::updates::UpdateInbox;
+use super::{cancelled, new_request_id, Decision, Messenger, PermissionMessage, TextFormat};
+use crate::away::telegram_responder;
 use crate::error::HookError;
+use crate::snooze::SNOOZE_MINUTES;
 use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Mutex;
 use std::time::Duration;
 use teloxide::prelude::*;
+use teloxide::requests::{Output, Payload};
 use teloxide::types::{
-    ChatId, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode, UpdateKind,
+    CallbackQuery, ChatId, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId,
+    ParseMode, ThreadId, UpdateKind, User,
 };
-use tokio::time::{interval, timeout};
+use teloxide::RequestError;
+use tokio::time::{interval, sleep, timeout};
 
 /// Telegram messenger for permission requests.
 pub struct TelegramMessenger {
     bot: Bot,
     chat_id: ChatId,
+    thread_id: Option<ThreadId>,
+    /// User IDs allowed to press decision buttons (empty: anyone in the chat)
+    responders: Vec<u64>,
+    /// Who answered the most recent decision request
+    last_responder: Mutex<Option<String>>,
+    /// Send one-line requests
+    compact: bool,
 }
 
 impl TelegramMessenger {
     /// Create a new Telegram messenger.
     pub fn new(bot_token: &str, chat_id: ChatId) -> Self {
         Self {
-            bot: Bot::new(bot_token),
+            bot: clients::shared().telegram_bot(bot_token),
             chat_id,
+            thread_id: None,
+            responders: Vec::new(),
+            last_responder: Mutex::new(None),
+            compact: false,
         }
     }
+
+    /// Post into a forum topic of the chat instead of the main thread.
+    pub fn with_topic(mut self, topic_id: Option<i32>) -> Self {
+        self.thread_id = topic_id.map(|id| ThreadId(MessageId(id)));
+        self
+    }
+
+    /// Send requests as one plain line, for smartwatches and SMS.
+    pub fn with_compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Only honor button presses from these user IDs.
+    pub fn with_responders(mut self, responders: Vec<u64>) -> Self {
+        self.responders = responders;
+        self
+    }
 }
 
-#[async_trait]
-impl Messenger for TelegramMessenger {
-    async fn send_permission_request(
+impl TelegramMessenger {
+    /// Send a message with decision buttons and wait for a button press.
+    ///
+    /// The message is edited with `status(decision)` once answered, or
+    /// marked as denied when the timeout expires. Requests with `details`
+    /// can also be decided by `/allow_all` and `/deny_all`.
+    #[allow(clippy::too_many_arguments)]
+    async fn request_decision(
         &self,
-        message: &PermissionMessage,
+        text: &str,
+        keyboard: InlineKeyboardMarkup,
+        request_id: &str,
         request_timeout: Duration,
+        required_approvals: usize,
+        details: Option<RequestDetails>,
+        status: impl Fn(Decision) -> String + Send,
     ) -> Result<Decision, HookError> {
-        // Send message with inline keyboard
-        let keyboard = create_permission_keyboard(&message.request_id, &message.tool_name);
-        let original_message = format_permission_message(message);
-        let sent = self
+        // Opened first so a press arriving right after sending is not skipped
+        let mut inbox = UpdateInbox::new(self.bot.clone());
+        let mut request = self
             .bot
-            .send_message(self.chat_id, &original_message)
+            .send_message(self.chat_id, text)
             .parse_mode(ParseMode::MarkdownV2)
-            .reply_markup(keyboard)
-            .await?;
+            .reply_markup(keyboard.clone());
+        if let Some(thread_id) = self.thread_id {
+            request = request.message_thread_id(thread_id);
+        }
+        let sent = send_with_retry(request).await?;
 
         let message_id = sent.id;
+        ledger::open_with_details(request_id, request_timeout, details).await;
 
-        // Poll for callback query with timeout
-        let poll_result = timeout(
-            request_timeout,
-            poll_for_callback(&self.bot, &message.request_id, message_id, self.chat_id),
-        )
-        .await;
+        // Poll for callback query with timeout, unless Claude gives up first
+        let poll_result = tokio::select! {
+            result = timeout(
+                request_timeout,
+                self.poll_for_callback(
+                    &mut inbox,
+                    request_id,
+                    message_id,
+                    text,
+                    &keyboard,
+                    required_approvals,
+                ),
+            ) => result,
+            _ = cancelled() => {
+                ledger::transition(request_id, RequestState::Cancelled).await;
+                ledger::keep_message(
+                    request_id,
+                    PostedMessage {
+                        platform: self.platform_name().to_string(),
+                        chat_id: self.chat_id.0.to_string(),
+                        message_id: message_id.0.to_string(),
+                        text: text.to_string(),
+                    },
+                )
+                .await;
+                self.edit(
+                    message_id,
+                    self.bot
+                        .edit_message_text(
+                            self.chat_id,
+                            message_id,
+                            format!("{}\n\n*Status:* 🚫 Cancelled by Claude", text),
+                        )
+                        .parse_mode(ParseMode::MarkdownV2),
+                )
+                .await;
+                return Err(HookError::Cancelled);
+            }
+        };
 
         match poll_result {
-            Ok(Ok(callback_decision)) => {
-                // Determine status text
-                let status = match callback_decision {
-                    Decision::Allow => "✅ Approved".to_string(),
-                    Decision::Deny => "❌ Denied".to_string(),
-                    Decision::AlwaysAllow => format!(
-                        "🔓 Always Allowed \\(`{}` added to list\\)",
-                        escape_markdown(&message.tool_name)
-                    ),
-                };
-
+            Ok(Ok((callback_decision, responder))) => {
+                ledger::transition(request_id, RequestState::Answered).await;
+                *self.last_responder.lock().unwrap() = Some(responder);
                 // Update message with status
-                let new_text = format!("{}\n\n*Status:* {}", original_message, status);
-                let _ = self
-                    .bot
-                    .edit_message_text(self.chat_id, message_id, new_text)
-                    .parse_mode(ParseMode::MarkdownV2)
-                    .await;
+                let new_text = format!("{}\n\n*Status:* {}", text, status(callback_decision));
+                self.edit(
+                    message_id,
+                    self.bot
+                        .edit_message_text(self.chat_id, message_id, new_text)
+                        .parse_mode(ParseMode::MarkdownV2),
+                )
+                .await;
 
                 Ok(callback_decision)
             }
             Ok(Err(e)) => {
                 // Error during polling
-                let _ = self
-                    .bot
-                    .edit_message_text(
-                        self.chat_id,
-                        message_id,
-                        format!("{}\n\n*Status:* ❌ Error", original_message),
-                    )
-                    .parse_mode(ParseMode::MarkdownV2)
-                    .await;
+                ledger::transition(request_id, RequestState::Cancelled).await;
+                self.edit(
+                    message_id,
+                    self.bot
+                        .edit_message_text(
+                            self.chat_id,
+                            message_id,
+                            format!("{}\n\n*Status:* ❌ Error", text),
+                        )
+                        .parse_mode(ParseMode::MarkdownV2),
+                )
+                .await;
                 Err(e)
             }
             Err(_) => {
                 // Timeout - deny by default
-                let _ = self
+                ledger::transition(request_id, RequestState::TimedOut).await;
+                self.edit(
+                    message_id,
+                    self.bot
+                        .edit_message_text(
+                            self.chat_id,
+                            message_id,
+                            format!("{}\n\n*Status:* ⏱️ Timeout \\- Denied", text),
+                        )
+                        .parse_mode(ParseMode::MarkdownV2),
+                )
+                .await;
+                Ok(Decision::Deny)
+            }
+        }
+    }
+
+    /// Edit one of our messages through the shared debouncer, ignoring failures.
+    async fn edit<R>(&self, message_id: MessageId, request: R)
+    where
+        R: Request<Err = RequestError>,
+    {
+        if let Err(e) = edits::shared()
+            .edit(self.chat_id, message_id, request)
+            .await
+        {
+            tracing::warn!("Failed to update Telegram message: {}", e);
+        }
+    }
+
+    /// Poll for button presses on our message until the request is decided.
+    ///
+    /// A Deny from any authorized responder decides at once. Allow needs
+    /// presses from `required_approvals` distinct responders, and the message
+    /// shows the progress in between. Returns the decision and who made it.
+    async fn poll_for_callback(
+        &self,
+        inbox: &mut UpdateInbox,
+        request_id: &str,
+        message_id: MessageId,
+        text: &str,
+        keyboard: &InlineKeyboardMarkup,
+        required_approvals: usize,
+    ) -> Result<(Decision, String), HookError> {
+        let mut poll_interval = interval(Duration::from_millis(500));
+        let mut approvers: Vec<User> = Vec::new();
+
+        loop {
+            poll_interval.tick().await;
+
+            if let Some(resolution) = ledger::resolution(request_id) {
+                let decision = if resolution.allow {
+                    Decision::Allow
+                } else {
+                    Decision::Deny
+                };
+                return Ok((decision, resolution.by));
+            }
+
+            let mut decided: Option<(Decision, String)> = None;
+            for shared in inbox.next_updates().await {
+                // Check if this is a callback query
+                let UpdateKind::CallbackQuery(query) = shared.update.kind else {
+                    continue;
+                };
+
+                // Parse callback data
+                let Some(callback) = query.data.as_deref().and_then(parse_callback_data) else {
+                    continue;
+                };
+
+                // Presses on other messages belong to other, possibly closed, requests
+                let ours = matches!(
+                    &query.message,
+                    Some(msg) if msg.chat().id == self.chat_id && msg.id() == message_id
+                );
+                if !ours || callback.request_id != request_id {
+                    // Every poller sees the press; only the one that fetched it answers
+                    if shared.fetched_here {
+                        answer_stale(&self.bot, &query, &callback.request_id).await;
+                    }
+                    continue;
+                }
+
+                // Presses fetched along with the deciding one came too late
+                if let Some((decision, by)) = &decided {
+                    answer_decided(&self.bot, &query, &Answer::new(*decision, by)).await;
+                    continue;
+                }
+
+                if !may_answer(&self.responders, &query.from) {
+                    reject_unauthorized(
+                        &self.bot,
+                        &query,
+                        request_id,
+                        self.chat_id,
+                        self.thread_id,
+                    )
+                    .await;
+                    continue;
+                }
+
+                let decision = if required_approvals <= 1
+                    || matches!(callback.decision, Decision::Deny | Decision::DenyAndBlock)
+                {
+                    (callback.decision, responder_name(&query.from))
+                } else {
+                    if approvers.iter().any(|user| user.id == query.from.id) {
+                        let _ = send_with_retry(
+                            self.bot
+                                .answer_callback_query(&query.id)
+                                .text("You already approved this request"),
+                        )
+                        .await;
+                        continue;
+                    }
+
+                    approvers.push(query.from.clone());
+                    let names: Vec<String> = approvers.iter().map(responder_name).collect();
+                    if approvers.len() < required_approvals {
+                        self.show_quorum_progress(
+                            &query,
+                            message_id,
+                            text,
+                            keyboard,
+                            &names,
+                            required_approvals,
+                        )
+                        .await;
+                        continue;
+                    }
+                    (Decision::Allow, names.join(", "))
+                };
+
+                // An approval link or bulk decision may have answered first
+                if let Err(first) =
+                    ledger::claim(request_id, Answer::new(decision.0, &decision.1)).await
+                {
+                    answer_decided(&self.bot, &query, &first).await;
+                    continue;
+                }
+                // Answer callback query to remove loading state
+                let _ = send_with_retry(self.bot.answer_callback_query(&query.id)).await;
+                decided = Some(decision);
+            }
+            if let Some(decided) = decided {
+                return Ok(decided);
+            }
+        }
+    }
+
+    /// Acknowledge an approval that does not complete the quorum yet.
+    async fn show_quorum_progress(
+        &self,
+        query: &CallbackQuery,
+        message_id: MessageId,
+        text: &str,
+        keyboard: &InlineKeyboardMarkup,
+        names: &[String],
+        required_approvals: usize,
+    ) {
+        let _ = send_with_retry(self.bot.answer_callback_query(&query.id).text(format!(
+            "Approval recorded ({}/{})",
+            names.len(),
+            required_approvals
+        )))
+        .await;
+        // Not awaited, so the next approval or the final status can replace it
+        let progress = format_quorum_progress(names, required_approvals);
+        let request = self
+            .bot
+            .edit_message_text(
+                self.chat_id,
+                message_id,
+                format!("{}\n\n{}", text, progress),
+            )
+            .parse_mode(ParseMode::MarkdownV2)
+            .reply_markup(keyboard.clone());
+        tokio::spawn(edits::shared().edit(self.chat_id, message_id, request));
+    }
+}
+
+impl TelegramMessenger {
+    /// Poll for presses on a group message until every request is decided.
+    ///
+    /// Each press decides one request, or all undecided ones, and the
+    /// message shows the progress in between. Every request is claimed in
+    /// the ledger, so only the first answer to it counts and later pressers
+    /// are told who decided. Returns everyone who decided.
+    async fn poll_for_group(
+        &self,
+        inbox: &mut UpdateInbox,
+        group_id: &str,
+        message_id: MessageId,
+        messages: &[PermissionMessage],
+        decisions: &mut [Option<Decision>],
+    ) -> Result<String, HookError> {
+        let mut poll_interval = interval(Duration::from_millis(500));
+        let mut deciders: Vec<String> = Vec::new();
+
+        loop {
+            poll_interval.tick().await;
+
+            if let Some(resolution) = ledger::resolution(group_id) {
+                let decision = if resolution.allow {
+                    Decision::Allow
+                } else {
+                    Decision::Deny
+                };
+                for slot in decisions.iter_mut() {
+                    slot.get_or_insert(decision);
+                }
+                return Ok(resolution.by);
+            }
+
+            for shared in inbox.next_updates().await {
+                let UpdateKind::CallbackQuery(query) = shared.update.kind else {
+                    continue;
+                };
+                let Some(callback) = query.data.as_deref().and_then(parse_callback_data) else {
+                    continue;
+                };
+
+                let ours = matches!(
+                    &query.message,
+                    Some(msg) if msg.chat().id == self.chat_id && msg.id() == message_id
+                );
+                if !ours || callback.request_id != group_id {
+                    if shared.fetched_here {
+                        answer_stale(&self.bot, &query, &callback.request_id).await;
+                    }
+                    continue;
+                }
+                if !matches!(callback.decision, Decision::Allow | Decision::Deny) {
+                    continue;
+                }
+
+                if !may_answer(&self.responders, &query.from) {
+                    reject_unauthorized(&self.bot, &query, group_id, self.chat_id, self.thread_id)
+                        .await;
+                    continue;
+                }
+
+                let by = responder_name(&query.from);
+                let answer = Answer::new(callback.decision, &by);
+                // An approval link or bulk decision may have answered the
+                // whole group, or another press this request
+                let claimed = match callback.item {
+                    Some(index) if index < decisions.len() => match ledger::answer(group_id) {
+                        Some(first) => Err(first),
+                        None => ledger::claim(&item_key(group_id, index), answer)
+                            .await
+                            .map(|()| vec![index]),
+                    },
+                    Some(_) => continue,
+                    None => match ledger::claim(group_id, answer.clone()).await {
+                        Ok(()) => {
+                            let mut decided = Vec::new();
+                            for index in 0..decisions.len() {
+                                let key = item_key(group_id, index);
+                                if ledger::claim(&key, answer.clone()).await.is_ok() {
+                                    decided.push(index);
+                                }
+                            }
+                            Ok(decided)
+                        }
+                        Err(first) => Err(first),
+                    },
+                };
+                let decided: Vec<usize> = match claimed {
+                    Ok(decided) => decided,
+                    Err(first) => {
+                        answer_decided(&self.bot, &query, &first).await;
+                        continue;
+                    }
+                };
+                let _ = send_with_retry(self.bot.answer_callback_query(&query.id)).await;
+                for index in decided {
+                    decisions[index].get_or_insert(callback.decision);
+                }
+                if !deciders.contains(&by) {
+                    deciders.push(by);
+                }
+                if decisions.iter().all(Option::is_some) {
+                    return Ok(deciders.join(", "));
+                }
+
+                // Not awaited, so the next press or the final status can replace it
+                let request = self
                     .bot
                     .edit_message_text(
                         self.chat_id,
                         message_id,
-                        format!("{}\n\n*Status:* ⏱️ Timeout \\- Denied", original_message),
+                        format_group_message(group_id, messages, decisions),
                     )
                     .parse_mode(ParseMode::MarkdownV2)
-                    .await;
-                Ok(Decision::Deny)
+                    .reply_markup(create_group_keyboard(group_id, decisions));
+                tokio::spawn(edits::shared().edit(self.chat_id, message_id, request));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Messenger for TelegramMessenger {
+    async fn send_permission_request(
+        &self,
+        message: &PermissionMessage,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let keyboard = create_permission_keyboard(
+            &message.request_id,
+            &message.tool_name,
+            message.required_approvals,
+            &message.suggestions,
+        );
+        let text = if self.compact {
+            escape_markdown(&compact::permission_line(message))
+        } else {
+            format_permission_message(message)
+        };
+
+        self.request_decision(
+            &text,
+            keyboard,
+            &message.request_id,
+            request_timeout,
+            message.required_approvals,
+            Some(RequestDetails::of(message)),
+            |decision| match decision {
+                Decision::Allow => "✅ Approved".to_string(),
+                Decision::Deny => "❌ Denied".to_string(),
+                Decision::DenyAndBlock => format!(
+                    "🚫 Denied and blocked `{}`",
+                    escape_markdown(&message.block_rule().to_string())
+                ),
+                Decision::Snooze => format!("⏰ Snoozed for {} min", SNOOZE_MINUTES),
+                Decision::AlwaysAllow => format!(
+                    "🔓 Always Allowed \\(`{}` added to list\\)",
+                    escape_markdown(&message.always_allow_entry())
+                ),
+                Decision::ApplySuggestion(index) => match message.suggestions.get(index) {
+                    Some(label) => format!("✅ Approved \\+ {}", escape_markdown(label)),
+                    None => "✅ Approved".to_string(),
+                },
+            },
+        )
+        .await
+    }
+
+    async fn send_permission_group(
+        &self,
+        messages: &[PermissionMessage],
+        request_timeout: Duration,
+    ) -> Result<Vec<Decision>, HookError> {
+        let group_id = new_request_id();
+        let mut decisions = vec![None; messages.len()];
+
+        // Opened first so a press arriving right after sending is not skipped
+        let mut inbox = UpdateInbox::new(self.bot.clone());
+        let mut request = self
+            .bot
+            .send_message(
+                self.chat_id,
+                format_group_message(&group_id, messages, &decisions),
+            )
+            .parse_mode(ParseMode::MarkdownV2)
+            .reply_markup(create_group_keyboard(&group_id, &decisions));
+        if let Some(thread_id) = self.thread_id {
+            request = request.message_thread_id(thread_id);
+        }
+        let message_id = send_with_retry(request).await?.id;
+        let details = messages.first().map(|first| RequestDetails {
+            summary: format!("{} requests", messages.len()),
+            ..RequestDetails::of(first)
+        });
+        ledger::open_with_details(&group_id, request_timeout, details).await;
+        for index in 0..messages.len() {
+            ledger::open(&item_key(&group_id, index), request_timeout).await;
+        }
+
+        let poll_result = timeout(
+            request_timeout,
+            self.poll_for_group(&mut inbox, &group_id, message_id, messages, &mut decisions),
+        )
+        .await;
+
+        let (state, status, result) = match poll_result {
+            Ok(Ok(responder)) => {
+                *self.last_responder.lock().unwrap() = Some(responder);
+                (RequestState::Answered, None, Ok(()))
             }
+            Ok(Err(e)) => (RequestState::Cancelled, Some("❌ Error"), Err(e)),
+            // Timeout - deny whatever is left
+            Err(_) => (
+                RequestState::TimedOut,
+                Some("⏱️ Timeout \\- Denied"),
+                Ok(()),
+            ),
+        };
+        ledger::transition(&group_id, state).await;
+        for index in 0..messages.len() {
+            ledger::transition(&item_key(&group_id, index), state).await;
         }
+        let mut text = format_group_message(&group_id, messages, &decisions);
+        if let Some(status) = status {
+            text = format!("{}\n\n*Status:* {}", text, status);
+        }
+        self.edit(
+            message_id,
+            self.bot
+                .edit_message_text(self.chat_id, message_id, text)
+                .parse_mode(ParseMode::MarkdownV2),
+        )
+        .await;
+
+        result.map(|()| {
+            decisions
+                .into_iter()
+                .map(|decision| decision.unwrap_or(Decision::Deny))
+                .collect()
+        })
+    }
+
+    fn supports_grouping(&self) -> bool {
+        true
+    }
+
+    async fn send_question(
+        &self,
+        question: &str,
+        hostname: &str,
+        request_timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        let request_id = new_request_id();
+        let text = if self.compact {
+            escape_markdown(&compact::question_line(hostname, question))
+        } else {
+            format_question_message(&request_id, hostname, question)
+        };
+
+        self.request_decision(
+            &text,
+            create_question_keyboard(&request_id),
+            &request_id,
+            request_timeout,
+            1,
+            None,
+            |decision| match decision {
+                Decision::Deny => "❌ Denied".to_string(),
+                _ => "✅ Approved".to_string(),
+            },
+        )
+        .await
     }
 
     async fn send_notification(&self, text: &str) -> Result<(), HookError> {
-        self.bot
+        let mut request = self
+            .bot
             .send_message(self.chat_id, text)
-            .parse_mode(ParseMode::MarkdownV2)
-            .await?;
+            .parse_mode(ParseMode::MarkdownV2);
+        if let Some(thread_id) = self.thread_id {
+            request = request.message_thread_id(thread_id);
+        }
+        send_with_retry(request).await?;
+        Ok(())
+    }
+
+    async fn send_text(&self, text: &str, format: TextFormat) -> Result<(), HookError> {
+        self.send_notification(&format_text(text, format)).await
+    }
+
+    async fn send_file(&self, path: &Path) -> Result<(), HookError> {
+        let mut request = self
+            .bot
+            .send_document(self.chat_id, InputFile::file(path.to_path_buf()));
+        if let Some(thread_id) = self.thread_id {
+            request = request.message_thread_id(thread_id);
+        }
+        send_with_retry(request).await?;
         Ok(())
     }
 
     async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError> {
-        let text = format_auto_approved_message(message);
+        let text = if self.compact {
+            escape_markdown(&compact::auto_approved_line(message))
+        } else {
+            format_auto_approved_message(message)
+        };
         self.send_notification(&text).await
     }
 
+    fn last_responder(&self) -> Option<String> {
+        self.last_responder.lock().unwrap().clone()
+    }
+
+    async fn mark_handled_locally(&self, message: &PostedMessage) -> Result<(), HookError> {
+        let (Ok(chat_id), Ok(message_id)) = (message.chat_id.parse(), message.message_id.parse())
+        else {
+            return Ok(());
+        };
+        let (chat_id, message_id) = (ChatId(chat_id), MessageId(message_id));
+        let request = self
+            .bot
+            .edit_message_text(
+                chat_id,
+                message_id,
+                format!("{}\n\n*Status:* ✅ Handled locally", message.text),
+            )
+            .parse_mode(ParseMode::MarkdownV2);
+        edits::shared().edit(chat_id, message_id, request).await?;
+        Ok(())
+    }
+
+    fn supports_quorum(&self) -> bool {
+        true
+    }
+
     fn platform_name(&self) -> &'static str {
         "Telegram"
     }
 }
 
 /// Create an inline keyboard for permission requests.
-fn create_permission_keyboard(request_id: &str, tool_name: &str) -> InlineKeyboardMarkup {
-    let buttons = vec![
-        vec![
-            InlineKeyboardButton::callback("✅ Allow", format!("{}:allow", request_id)),
-            InlineKeyboardButton::callback("❌ Deny", format!("{}:deny", request_id)),
-        ],
-        vec![InlineKeyboardButton::callback(
-            "🔓 Always Allow",
-            format!("{}:always_allow:{}", request_id, tool_name),
-        )],
-    ];
+///
+/// Requests needing several approvals get no Always Allow button, since it
+/// would let a single responder skip the quorum on later requests.
+fn create_permission_keyboard(
+    request_id: &str,
+    tool_name: &str,
+    required_approvals: usize,
+    suggestions: &[String],
+) -> InlineKeyboardMarkup {
+    let mut buttons = vec![vec![
+        InlineKeyboardButton::callback("✅ Allow", format!("{}:allow", request_id)),
+        InlineKeyboardButton::callback("❌ Deny", format!("{}:deny", request_id)),
+    ]];
+    if required_approvals <= 1 {
+        buttons.push(vec![
+            InlineKeyboardButton::callback(
+                "🔓 Always Allow",
+                format!("{}:always_allow:{}", request_id, tool_name),
+            ),
+            InlineKeyboardButton::callback("🚫 Deny & Block", format!("{}:block", request_id)),
+        ]);
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("⏰ Snooze {}m", SNOOZE_MINUTES),
+            format!("{}:snooze", request_id),
+        )]);
+        for (index, label) in suggestions.iter().enumerate() {
+            buttons.push(vec![InlineKeyboardButton::callback(
+                format!("📌 {}", label),
+                format!("{}:suggest:{}", request_id, index),
+            )]);
+        }
+    }
 
     InlineKeyboardMarkup::new(buttons)
 }
 
+/// Create an inline keyboard for a group of requests.
+///
+/// Each undecided request gets its own Allow and Deny buttons, followed by
+/// a row deciding all of them at once.
+fn create_group_keyboard(group_id: &str, decisions: &[Option<Decision>]) -> InlineKeyboardMarkup {
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = decisions
+        .iter()
+        .enumerate()
+        .filter(|(_, decision)| decision.is_none())
+        .map(|(index, _)| {
+            vec![
+                InlineKeyboardButton::callback(
+                    format!("✅ {}", index + 1),
+                    format!("{}:allow:{}", group_id, index),
+                ),
+                InlineKeyboardButton::callback(
+                    format!("❌ {}", index + 1),
+                    format!("{}:deny:{}", group_id, index),
+                ),
+            ]
+        })
+        .collect();
+    buttons.push(vec![
+        InlineKeyboardButton::callback("✅ Allow all", format!("{}:allow", group_id)),
+        InlineKeyboardButton::callback("❌ Deny all", format!("{}:deny", group_id)),
+    ]);
+
+    InlineKeyboardMarkup::new(buttons)
+}
+
+/// Create an inline keyboard for yes/no questions.
+fn create_question_keyboard(request_id: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("✅ Allow", format!("{}:allow", request_id)),
+        InlineKeyboardButton::callback("❌ Deny", format!("{}:deny", request_id)),
+    ]])
+}
+
 /// Parsed callback data from a button press.
 #[derive(Debug, Clone)]
 struct CallbackData {
@@ -147,6 +782,8 @@ struct CallbackData {
     decision: Decision,
     #[allow(dead_code)]
     tool_name: Option<String>,
+    /// Request within a group the press is for (none: all of them)
+    item: Option<usize>,
 }
 
 /// Parse callback data from a button press.
@@ -162,80 +799,199 @@ fn parse_callback_data(data: &str) -> Option<CallbackData> {
         "allow" => Decision::Allow,
         "deny" => Decision::Deny,
         "always_allow" => Decision::AlwaysAllow,
+        "block" => Decision::DenyAndBlock,
+        "snooze" => Decision::Snooze,
+        "suggest" => Decision::ApplySuggestion(parts.get(2)?.parse().ok()?),
         _ => return None,
     };
 
-    let tool_name = if parts.len() >= 3 {
+    let tool_name = if parts.len() >= 3 && decision == Decision::AlwaysAllow {
         Some(parts[2].to_string())
     } else {
         None
     };
 
+    let item = match decision {
+        Decision::Allow | Decision::Deny => parts.get(2).and_then(|index| index.parse().ok()),
+        _ => None,
+    };
+
     Some(CallbackData {
         request_id,
         decision,
         tool_name,
+        item,
     })
 }
 
-/// Poll for callback query matching our request.
-async fn poll_for_callback(
-    bot: &Bot,
-    request_id: &str,
-    message_id: MessageId,
-    chat_id: ChatId,
-) -> Result<Decision, HookError> {
-    let mut poll_interval = interval(Duration::from_millis(500));
-    let mut offset: Option<i32> = None;
+/// Attempts per Telegram call before a transient error is returned.
+const MAX_ATTEMPTS: u32 = 4;
 
-    loop {
-        poll_interval.tick().await;
+/// Whether a Telegram method can be repeated without doing anything twice.
+///
+/// A network error may come after Telegram carried out the call, so
+/// repeating a send could post a second prompt with live buttons.
+fn idempotent(method: &str) -> bool {
+    method == "AnswerCallbackQuery"
+        || ["Edit", "Get", "Delete", "Set"]
+            .iter()
+            .any(|prefix| method.starts_with(prefix))
+}
 
-        // Build getUpdates request
-        let mut get_updates = bot.get_updates();
-        if let Some(off) = offset {
-            get_updates = get_updates.offset(off);
+/// How long to wait before retrying a failed Telegram call, or `None` to give up.
+///
+/// Rate limits wait as long as Telegram asks; network errors back off
+/// exponentially from half a second, for idempotent calls only. Other
+/// errors are not retried.
+fn retry_delay(error: &RequestError, attempt: u32, idempotent: bool) -> Option<Duration> {
+    if attempt >= MAX_ATTEMPTS {
+        return None;
+    }
+    match error {
+        RequestError::RetryAfter(wait) => Some(wait.duration()),
+        RequestError::Network(_) if idempotent => {
+            Some(Duration::from_millis(500) * 2u32.pow(attempt - 1))
         }
-        get_updates = get_updates.timeout(5);
-        get_updates =
-            get_updates.allowed_updates(vec![teloxide::types::AllowedUpdate::CallbackQuery]);
+        _ => None,
+    }
+}
 
-        let updates = match get_updates.await {
-            Ok(updates) => updates,
-            Err(_) => continue, // Retry on error
+/// Send a Telegram request, waiting out rate limits, and network hiccups
+/// for calls that are safe to repeat.
+pub async fn send_with_retry<R>(request: R) -> Result<Output<R>, RequestError>
+where
+    R: Request<Err = RequestError>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match request.send_ref().await {
+            Ok(output) => return Ok(output),
+            Err(e) => e,
         };
+        let Some(delay) = retry_delay(&error, attempt, idempotent(R::Payload::NAME)) else {
+            return Err(error);
+        };
+        tracing::warn!(
+            "Telegram request failed ({}), retrying in {:?}",
+            error,
+            delay
+        );
+        sleep(delay).await;
+        attempt += 1;
+    }
+}
 
-        for update in updates {
-            // Update offset for next poll
-            offset = Some((update.id.0 + 1) as i32);
+/// Answer a press fetched by a process that has no request waiting on it.
+///
+/// Presses on closed requests get the stale notice; others are left for the
+/// hook process still waiting on them.
+pub async fn answer_unclaimed_press(bot: &Bot, query: &CallbackQuery) {
+    if let Some(callback) = query.data.as_deref().and_then(parse_callback_data) {
+        answer_stale(bot, query, &callback.request_id).await;
+    }
+}
 
-            // Check if this is a callback query
-            if let UpdateKind::CallbackQuery(query) = update.kind {
-                // Check if callback is for our message
-                if let Some(msg) = &query.message {
-                    if msg.chat().id != chat_id || msg.id() != message_id {
-                        continue; // Not our message
-                    }
-                } else {
-                    continue; // No message info
-                }
+/// Tell whoever pressed a button on a closed request that it no longer counts.
+///
+/// Presses for requests still pending in another hook process are left alone.
+async fn answer_stale(bot: &Bot, query: &CallbackQuery, request_id: &str) {
+    let Some(notice) = ledger::closed_notice(request_id) else {
+        return;
+    };
+    tracing::debug!("Ignoring press on closed request {}", request_id);
 
-                // Parse callback data
-                if let Some(data) = &query.data {
-                    if let Some(callback) = parse_callback_data(data) {
-                        if callback.request_id == request_id {
-                            // Answer callback query to remove loading state
-                            let _ = bot.answer_callback_query(&query.id).await;
+    let _ = send_with_retry(
+        bot.answer_callback_query(&query.id)
+            .text(notice)
+            .show_alert(true),
+    )
+    .await;
 
-                            return Ok(callback.decision);
-                        }
-                    }
-                }
-            }
-        }
+    // Drop the keyboard so the stale buttons cannot be pressed again
+    if let Some(msg) = &query.message {
+        let _ = edits::shared()
+            .edit(
+                msg.chat().id,
+                msg.id(),
+                bot.edit_message_reply_markup(msg.chat().id, msg.id()),
+            )
+            .await;
+    }
+}
+
+/// Tell someone whose press lost the race what was decided.
+async fn answer_decided(bot: &Bot, query: &CallbackQuery, first: &Answer) {
+    tracing::debug!("Press came after the request was decided: {}", first);
+    let _ = send_with_retry(
+        bot.answer_callback_query(&query.id)
+            .text(first.notice())
+            .show_alert(true),
+    )
+    .await;
+}
+
+/// Refuse a button press from someone outside the responder allowlist.
+///
+/// The presser sees an alert, and the chat is told about the attempt.
+async fn reject_unauthorized(
+    bot: &Bot,
+    query: &CallbackQuery,
+    request_id: &str,
+    chat_id: ChatId,
+    thread_id: Option<ThreadId>,
+) {
+    let user = &query.from;
+    tracing::warn!(
+        "Ignoring decision on request {} from unauthorized Telegram user {}",
+        request_id,
+        user.id
+    );
+
+    let _ = send_with_retry(
+        bot.answer_callback_query(&query.id)
+            .text("You are not authorized to answer this request")
+            .show_alert(true),
+    )
+    .await;
+
+    let mut request = bot
+        .send_message(chat_id, format_unauthorized_warning(request_id, user))
+        .parse_mode(ParseMode::MarkdownV2);
+    if let Some(thread_id) = thread_id {
+        request = request.message_thread_id(thread_id);
+    }
+    let _ = send_with_retry(request).await;
+}
+
+/// Human-readable name of a Telegram user: `@username`, else the full name.
+fn display_name(user: &User) -> String {
+    match &user.username {
+        Some(username) => format!("@{}", username),
+        None => user.full_name(),
     }
 }
 
+/// Whether a user may answer requests: on the allowlist, or a delegate.
+pub fn may_answer(responders: &[u64], user: &User) -> bool {
+    telegram_responder(responders, user.id.0)
+}
+
+/// Identify who pressed a button, e.g. `@alice (123456789)`.
+pub fn responder_name(user: &User) -> String {
+    format!("{} ({})", display_name(user), user.id)
+}
+
+/// Format the chat warning about an unauthorized decision attempt.
+fn format_unauthorized_warning(request_id: &str, user: &User) -> String {
+    let name = display_name(user);
+    format!(
+        "⚠️ *Unauthorized decision attempt* on request `\\[{}\\]` by {} \\(ID `{}`\\)",
+        escape_markdown(request_id),
+        escape_markdown(&name),
+        user.id
+    )
+}
+
 /// Escape special characters for Telegram MarkdownV2 format.
 pub fn escape_markdown(text: &str) -> String {
     let special_chars = [
@@ -253,6 +1009,20 @@ pub fn escape_markdown(text: &str) -> String {
     result
 }
 
+/// Escape text for use inside a MarkdownV2 code block.
+pub fn escape_code(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('`', "\\`")
+}
+
+/// Render free-form text as MarkdownV2.
+fn format_text(text: &str, format: TextFormat) -> String {
+    match format {
+        TextFormat::Plain => escape_markdown(text),
+        TextFormat::Markdown => text.to_string(),
+        TextFormat::Code => format!("```\n{}\n```", escape_code(text)),
+    }
+}
+
 /// Format a permission request as a Telegram message.
 fn format_permission_message(message: &PermissionMessage) -> String {
     let mut lines = vec![format!(
@@ -260,14 +1030,17 @@ fn format_permission_message(message: &PermissionMessage) -> String {
         escape_markdown(&message.request_id)
     )];
 
-    lines.push(format!(
-        "🖥️ *Host:* `{}`",
-        escape_markdown(&message.hostname)
-    ));
+    lines.push(format!("🖥️ *Host:* `{}`", escape_markdown(message.host())));
     lines.push(String::new());
-    lines.push(format!("*Tool:* `{}`", escape_markdown(&message.tool_name)));
+    if let Some(context) = &message.context {
+        lines.push(format!("💬 _{}_", escape_markdown(context)));
+        lines.push(String::new());
+    }
+    lines.push(format!("*Tool:* {}", format_tool_name(message)));
 
+    let fields = message.display_fields();
     match message.tool_name.as_str() {
+        _ if !fields.is_empty() => lines.extend(format_display_fields(&fields)),
         "Bash" => {
             if let Some(command) = message.tool_input.get("command").and_then(|v| v.as_str()) {
                 lines.push(format!(
@@ -275,6 +1048,9 @@ fn format_permission_message(message: &PermissionMessage) -> String {
                     escape_markdown(command)
                 ));
             }
+            if let Some(explanation) = &message.explanation {
+                lines.push(format!("ℹ️ _{}_", escape_markdown(explanation)));
+            }
         }
         "Edit" | "Write" => {
             if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
@@ -300,6 +1076,94 @@ fn format_permission_message(message: &PermissionMessage) -> String {
                 }
             }
         }
+        "MultiEdit" => {
+            if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
+                lines.push(format!("*File:* `{}`", escape_markdown(file_path)));
+            }
+            let edits = message.multi_edits();
+            lines.push(format!("*Edits:* {}", edits.len()));
+            for (index, (old_string, new_string)) in edits.iter().take(SHOWN_EDITS).enumerate() {
+                let old: String = old_string.chars().take(200).collect();
+                let new: String = new_string.chars().take(200).collect();
+                lines.push(format!(
+                    "*{}\\. Old:*\n```\n{}\n```\n*{}\\. New:*\n```\n{}\n```",
+                    index + 1,
+                    escape_markdown(&old),
+                    index + 1,
+                    escape_markdown(&new)
+                ));
+            }
+            if edits.len() > SHOWN_EDITS {
+                lines.push(format!(
+                    "_\\.\\.\\. and {} more_",
+                    edits.len() - SHOWN_EDITS
+                ));
+            }
+        }
+        "NotebookEdit" => {
+            if let Some(path) = message
+                .tool_input
+                .get("notebook_path")
+                .and_then(|v| v.as_str())
+            {
+                lines.push(format!("*Notebook:* `{}`", escape_markdown(path)));
+            }
+            lines.push(format!(
+                "*Change:* {}",
+                escape_markdown(&message.notebook_change())
+            ));
+            if let Some(source) = message
+                .tool_input
+                .get("new_source")
+                .and_then(|v| v.as_str())
+            {
+                let truncated: String = source.chars().take(200).collect();
+                lines.push(format!(
+                    "*Source:*\n```\n{}\n```",
+                    escape_markdown(&truncated)
+                ));
+            }
+        }
+        "Task" => {
+            lines.push(format!(
+                "🤖 *{}*",
+                escape_markdown(&message.subagent_line())
+            ));
+            if let Some(prompt) = message.tool_input.get("prompt").and_then(|v| v.as_str()) {
+                let truncated: String = prompt.chars().take(300).collect();
+                lines.push(format!("*Prompt:* {}", escape_markdown(&truncated)));
+            }
+        }
+        "WebFetch" => {
+            if let Some(domain) = message.fetch_domain() {
+                lines.push(format!("🌐 *{}*", escape_markdown(&domain)));
+            }
+            if let Some(url) = message.tool_input.get("url").and_then(|v| v.as_str()) {
+                lines.push(format!("*URL:* `{}`", escape_markdown(url)));
+            }
+            if let Some(prompt) = message.tool_input.get("prompt").and_then(|v| v.as_str()) {
+                let truncated: String = prompt.chars().take(200).collect();
+                lines.push(format!("*Prompt:* {}", escape_markdown(&truncated)));
+            }
+        }
+        "WebSearch" => {
+            if let Some(query) = message.tool_input.get("query").and_then(|v| v.as_str()) {
+                lines.push(format!("🔎 *{}*", escape_markdown(query)));
+            }
+            for (key, label) in [
+                ("allowed_domains", "Only"),
+                ("blocked_domains", "Excluding"),
+            ] {
+                let domains = message.search_domains(key);
+                if !domains.is_empty() {
+                    lines.push(format!(
+                        "*{}:* {}",
+                        label,
+                        escape_markdown(&domains.join(", "))
+                    ));
+                }
+            }
+        }
         _ => {
             let input_str = serde_json::to_string_pretty(&message.tool_input).unwrap_or_default();
             let truncated: String = input_str.chars().take(500).collect();
@@ -310,9 +1174,103 @@ fn format_permission_message(message: &PermissionMessage) -> String {
         }
     }
 
+    if message.required_approvals > 1 {
+        lines.push(String::new());
+        lines.push(format!(
+            "👥 *Requires {} approvals* from different people",
+            message.required_approvals
+        ));
+    }
+
     lines.join("\n")
 }
 
+/// The tool name, after its configured emoji and label if there are any.
+fn format_tool_name(message: &PermissionMessage) -> String {
+    let name = format!("`{}`", escape_markdown(&message.tool_name));
+    if message.has_custom_label() {
+        format!("{} \\({}\\)", escape_markdown(&message.tool_label()), name)
+    } else {
+        name
+    }
+}
+
+/// One line per configured input field, instead of the JSON input.
+fn format_display_fields(fields: &[(&str, String)]) -> Vec<String> {
+    fields
+        .iter()
+        .map(|(field, value)| {
+            let truncated: String = value.chars().take(200).collect();
+            format!(
+                "*{}:* `{}`",
+                escape_markdown(field),
+                escape_markdown(&truncated)
+            )
+        })
+        .collect()
+}
+
+/// Ledger key of one request in a group, claimed by the press deciding it.
+fn item_key(group_id: &str, index: usize) -> String {
+    format!("{}-{}", group_id, index)
+}
+
+/// Format a group of requests, one numbered line each with its status.
+fn format_group_message(
+    group_id: &str,
+    messages: &[PermissionMessage],
+    decisions: &[Option<Decision>],
+) -> String {
+    let hostname = messages
+        .first()
+        .map(|message| message.host())
+        .unwrap_or_default();
+    let mut lines = vec![
+        format!(
+            "🔐 *{} Permission Requests* `\\[{}\\]`",
+            messages.len(),
+            escape_markdown(group_id)
+        ),
+        format!("🖥️ *Host:* `{}`", escape_markdown(hostname)),
+        String::new(),
+    ];
+    for (index, (message, decision)) in messages.iter().zip(decisions).enumerate() {
+        let status = match decision {
+            None => "⏳",
+            Some(Decision::Deny) => "❌",
+            Some(_) => "✅",
+        };
+        lines.push(format!(
+            "{}\\. {} {}",
+            index + 1,
+            status,
+            escape_markdown(&compact::item_line(message))
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Format the approvals collected so far for a pending quorum.
+fn format_quorum_progress(approvers: &[String], required_approvals: usize) -> String {
+    format!(
+        "👥 *Approvals:* {}/{} \\({}\\)",
+        approvers.len(),
+        required_approvals,
+        escape_markdown(&approvers.join(", "))
+    )
+}
+
+/// Format a yes/no question as a Telegram message.
+fn format_question_message(request_id: &str, hostname: &str, question: &str) -> String {
+    [
+        format!("❓ *Question* `\\[{}\\]`", escape_markdown(request_id)),
+        format!("🖥️ *Host:* `{}`", escape_markdown(hostname)),
+        String::new(),
+        escape_markdown(question),
+    ]
+    .join("\n")
+}
+
 /// Format an auto-approved notification.
 fn format_auto_approved_message(message: &PermissionMessage) -> String {
     let mut lines = vec![
@@ -320,15 +1278,17 @@ fn format_auto_approved_message(message: &PermissionMessage) -> String {
             "⚙️ *Auto\\-Approved* `\\[{}\\]`",
             escape_markdown(&message.request_id)
         ),
-        format!("🖥️ *Host:* `{}`", escape_markdown(&message.hostname)),
+        format!("🖥️ *Host:* `{}`", escape_markdown(message.host())),
         String::new(),
         format!(
-            "*Tool:* `{}` _\\(in always\\-allow list\\)_",
-            escape_markdown(&message.tool_name)
+            "*Tool:* {} _\\(in always\\-allow list\\)_",
+            format_tool_name(message)
         ),
     ];
 
+    let fields = message.display_fields();
     match message.tool_name.as_str() {
+        _ if !fields.is_empty() => lines.extend(format_display_fields(&fields)),
         "Bash" => {
             if let Some(command) = message.tool_input.get("command").and_then(|v| v.as_str()) {
                 lines.push(format!(
@@ -337,11 +1297,26 @@ fn format_auto_approved_message(message: &PermissionMessage) -> String {
                 ));
             }
         }
-        "Edit" | "Write" => {
+        "Edit" | "Write" | "MultiEdit" => {
             if let Some(file_path) = message.tool_input.get("file_path").and_then(|v| v.as_str()) {
                 lines.push(format!("*File:* `{}`", escape_markdown(file_path)));
             }
         }
+        "NotebookEdit" => {
+            if let Some(path) = message
+                .tool_input
+                .get("notebook_path")
+                .and_then(|v| v.as_str())
+            {
+                lines.push(format!("*Notebook:* `{}`", escape_markdown(path)));
+            }
+        }
+        "Task" => {
+            lines.push(format!(
+                "🤖 *{}*",
+                escape_markdown(&message.subagent_line())
+            ));
+        }
         _ => {
             let input_str = serde_json::to_string_pretty(&message.tool_input).unwrap_or_default();
             let truncated: String = input_str.chars().take(500).collect();
@@ -358,6 +1333,9 @@ fn format_auto_approved_message(message: &PermissionMessage) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ToolDisplay;
+    use proptest::prelude::*;
+    use teloxide::types::{InlineKeyboardButtonKind, Seconds};
 
     #[test]
     fn test_parse_callback_data_allow() {
@@ -382,10 +1360,66 @@ mod tests {
         assert_eq!(data.tool_name, Some("Bash".to_string()));
     }
 
+    #[test]
+    fn test_group_keyboard_and_callbacks() {
+        let keyboard = create_group_keyboard("grp12345", &[Some(Decision::Allow), None, None]);
+        assert_eq!(keyboard.inline_keyboard.len(), 3);
+        assert_eq!(keyboard.inline_keyboard[0][1].text, "❌ 2");
+        assert_eq!(keyboard.inline_keyboard[2][0].text, "✅ Allow all");
+
+        let data = parse_callback_data("grp12345:deny:1").unwrap();
+        assert_eq!(data.decision, Decision::Deny);
+        assert_eq!(data.item, Some(1));
+        assert_eq!(parse_callback_data("grp12345:allow").unwrap().item, None);
+    }
+
+    #[test]
+    fn test_parse_callback_data_block() {
+        let data = parse_callback_data("abc123:block").unwrap();
+        assert_eq!(data.request_id, "abc123");
+        assert_eq!(data.decision, Decision::DenyAndBlock);
+    }
+
+    #[test]
+    fn test_parse_callback_data_snooze() {
+        let data = parse_callback_data("abc123:snooze").unwrap();
+        assert_eq!(data.request_id, "abc123");
+        assert_eq!(data.decision, Decision::Snooze);
+    }
+
     #[test]
     fn test_parse_callback_data_invalid() {
         assert!(parse_callback_data("invalid").is_none());
         assert!(parse_callback_data("abc123:unknown").is_none());
+        assert!(parse_callback_data("abc123:suggest").is_none());
+        assert!(parse_callback_data("abc123:suggest:x").is_none());
+    }
+
+    #[test]
+    fn test_parse_callback_data_suggestion() {
+        let data = parse_callback_data("abc123:suggest:1").unwrap();
+        assert_eq!(data.request_id, "abc123");
+        assert_eq!(data.decision, Decision::ApplySuggestion(1));
+        assert!(data.tool_name.is_none());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let rate_limited = RequestError::RetryAfter(Seconds::from_seconds(3));
+        assert_eq!(
+            retry_delay(&rate_limited, 1, false),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(retry_delay(&rate_limited, MAX_ATTEMPTS, true), None);
+
+        let api_error = RequestError::Api(teloxide::ApiError::MessageNotModified);
+        assert_eq!(retry_delay(&api_error, 1, true), None);
+
+        // Sends are never repeated after a network error
+        assert!(idempotent("EditMessageText"));
+        assert!(idempotent("AnswerCallbackQuery"));
+        assert!(!idempotent("SendMessage"));
+        assert!(!idempotent("ForwardMessage"));
     }
 
     #[test]
@@ -393,6 +1427,8 @@ mod tests {
         assert_eq!(Decision::Allow.to_behavior(), "allow");
         assert_eq!(Decision::Deny.to_behavior(), "deny");
         assert_eq!(Decision::AlwaysAllow.to_behavior(), "allow");
+        assert_eq!(Decision::DenyAndBlock.to_behavior(), "deny");
+        assert_eq!(Decision::Snooze.to_behavior(), "deny");
     }
 
     #[test]
@@ -405,9 +1441,193 @@ mod tests {
 
     #[test]
     fn test_create_permission_keyboard() {
-        let keyboard = create_permission_keyboard("abc123", "Bash");
-        assert_eq!(keyboard.inline_keyboard.len(), 2);
+        let keyboard = create_permission_keyboard("abc123", "Bash", 1, &[]);
+        assert_eq!(keyboard.inline_keyboard.len(), 3);
         assert_eq!(keyboard.inline_keyboard[0].len(), 2); // Allow, Deny
-        assert_eq!(keyboard.inline_keyboard[1].len(), 1); // Always Allow
+        assert_eq!(keyboard.inline_keyboard[1].len(), 2); // Always Allow, Deny & Block
+        assert_eq!(keyboard.inline_keyboard[2][0].text, "⏰ Snooze 15m");
+
+        let keyboard = create_permission_keyboard("abc123", "Bash", 2, &[]);
+        assert_eq!(keyboard.inline_keyboard.len(), 1);
+
+        let suggestions = vec!["Allow Bash(git *) for this project".to_string()];
+        let keyboard = create_permission_keyboard("abc123", "Bash", 1, &suggestions);
+        assert_eq!(keyboard.inline_keyboard.len(), 4);
+        assert_eq!(
+            keyboard.inline_keyboard[3][0].text,
+            "📌 Allow Bash(git *) for this project"
+        );
+        let keyboard = create_permission_keyboard("abc123", "Bash", 2, &suggestions);
+        assert_eq!(keyboard.inline_keyboard.len(), 1);
+    }
+
+    #[test]
+    fn test_format_quorum_progress() {
+        let approvers = vec!["@alice (42)".to_string()];
+        assert_eq!(
+            format_quorum_progress(&approvers, 2),
+            "👥 *Approvals:* 1/2 \\(@alice \\(42\\)\\)"
+        );
+    }
+
+    #[test]
+    fn test_format_tool_display() {
+        let message = PermissionMessage::new(
+            "abc123".to_string(),
+            "mcp__github__create_issue".to_string(),
+            "my-host".to_string(),
+            serde_json::json!({"title": "Fix login", "body": "long text"}),
+        )
+        .with_display(ToolDisplay {
+            emoji: Some("🐙".to_string()),
+            label: Some("GitHub issue".to_string()),
+            fields: vec!["title".to_string(), "labels".to_string()],
+            color: None,
+        });
+        let text = format_permission_message(&message);
+        assert!(
+            text.contains("*Tool:* 🐙 GitHub issue \\(`mcp\\_\\_github\\_\\_create\\_issue`\\)")
+        );
+        assert!(text.contains("*title:* `Fix login`"));
+        assert!(!text.contains("long text"));
+    }
+
+    #[test]
+    fn test_format_multi_edit_and_notebook() {
+        let edit = |n: u32| serde_json::json!({"old_string": format!("old{}", n), "new_string": format!("new{}", n)});
+        let message = PermissionMessage::new(
+            "abc123".to_string(),
+            "MultiEdit".to_string(),
+            "my-host".to_string(),
+            serde_json::json!({"file_path": "src/lib.rs", "edits": [edit(1), edit(2), edit(3)]}),
+        );
+        let text = format_permission_message(&message);
+        assert!(text.contains("*File:* `src/lib\\.rs`\n*Edits:* 3"));
+        assert!(text.contains("*2\\. New:*\n```\nnew2\n```"));
+        assert!(!text.contains("old3"));
+        assert!(text.contains("_\\.\\.\\. and 1 more_"));
+
+        let message = PermissionMessage::new(
+            "abc123".to_string(),
+            "NotebookEdit".to_string(),
+            "my-host".to_string(),
+            serde_json::json!({
+                "notebook_path": "/work/analysis.ipynb",
+                "cell_id": "3f2a",
+                "cell_type": "markdown",
+                "edit_mode": "insert",
+                "new_source": "# Results"
+            }),
+        );
+        assert_eq!(message.notebook_change(), "insert markdown cell after 3f2a");
+        let text = format_permission_message(&message);
+        assert!(text.contains("*Notebook:* `/work/analysis\\.ipynb`"));
+        assert!(text.contains("*Source:*\n```\n\\# Results\n```"));
+    }
+
+    #[test]
+    fn test_format_task() {
+        let message = PermissionMessage::new(
+            "abc123".to_string(),
+            "Task".to_string(),
+            "my-host".to_string(),
+            serde_json::json!({
+                "subagent_type": "Explore",
+                "description": "Find config loader",
+                "prompt": "Look for where the config file is read"
+            }),
+        );
+        let text = format_permission_message(&message);
+        assert!(text.contains("🤖 *Explore: Find config loader*"));
+        assert!(text.contains("*Prompt:* Look for where the config file is read"));
+        assert_eq!(message.always_allow_entry(), "Task(Explore)");
+    }
+
+    #[test]
+    fn test_format_web_tools() {
+        let message = PermissionMessage::new(
+            "abc123".to_string(),
+            "WebFetch".to_string(),
+            "my-host".to_string(),
+            serde_json::json!({"url": "https://docs.rs/serde", "prompt": "Find derive docs"}),
+        );
+        let text = format_permission_message(&message);
+        assert!(text.contains("🌐 *docs\\.rs*\n*URL:* `https://docs\\.rs/serde`"));
+        assert!(text.contains("*Prompt:* Find derive docs"));
+
+        let message = PermissionMessage::new(
+            "abc123".to_string(),
+            "WebSearch".to_string(),
+            "my-host".to_string(),
+            serde_json::json!({"query": "tokio select", "allowed_domains": ["docs.rs"]}),
+        );
+        let text = format_permission_message(&message);
+        assert!(text.contains("🔎 *tokio select*\n*Only:* docs\\.rs"));
+        assert!(!text.contains("Excluding"));
+    }
+
+    #[test]
+    fn test_format_text() {
+        assert_eq!(
+            format_text("build done.", TextFormat::Plain),
+            "build done\\."
+        );
+        assert_eq!(format_text("*bold*", TextFormat::Markdown), "*bold*");
+        assert_eq!(
+            format_text("a `b` c\\d.", TextFormat::Code),
+            "```\na \\`b\\` c\\\\d.\n```"
+        );
+    }
+
+    #[test]
+    fn test_question_message_and_keyboard() {
+        let keyboard = create_question_keyboard("abc123");
+        assert_eq!(keyboard.inline_keyboard.len(), 1);
+        assert_eq!(keyboard.inline_keyboard[0].len(), 2); // Allow, Deny
+
+        let text = format_question_message("abc123", "my-host", "Deploy to prod?");
+        assert!(text.contains("❓ *Question*"));
+        assert!(text.contains("my\\-host"));
+        assert!(text.ends_with("Deploy to prod?"));
+    }
+    proptest! {
+        #[test]
+        fn prop_parse_callback_data_never_panics(data in any::<String>()) {
+            let _ = parse_callback_data(&data);
+        }
+
+        #[test]
+        fn prop_keyboard_callbacks_parse_back(
+            request_id in "[0-9a-f]{8,16}",
+            tool_name in "[A-Za-z_]{1,32}",
+            suggestions in prop::collection::vec(any::<String>(), 0..3),
+        ) {
+            let keyboard = create_permission_keyboard(&request_id, &tool_name, 1, &suggestions);
+            for button in keyboard.inline_keyboard.iter().flatten() {
+                let InlineKeyboardButtonKind::CallbackData(data) = &button.kind else {
+                    continue;
+                };
+                let parsed = parse_callback_data(data).unwrap();
+                prop_assert_eq!(&parsed.request_id, &request_id);
+            }
+        }
+
+        #[test]
+        fn prop_format_permission_message_never_panics(
+            tool_name in any::<String>(),
+            host in any::<String>(),
+            field in prop::sample::select(&["command", "file_path", "url", "query", "content"][..]),
+            value in any::<String>(),
+            context in prop::option::of(any::<String>()),
+        ) {
+            let message = PermissionMessage::new(
+                "abc123".to_string(),
+                tool_name,
+                host,
+                serde_json::json!({ field: value }),
+            )
+            .with_context(context);
+            format_permission_message(&message);
+        }
     }
 }
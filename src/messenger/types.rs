@@ -1,5 +1,8 @@
 //! Shared types for messenger implementations.
 
+use crate::always_allow::{allow_entry, BlockRule};
+use crate::config::ToolDisplay;
+use crate::tool_timer::{tool_detail, url_domain};
 use serde_json::Value;
 
 /// User decision on a permission request.
@@ -8,22 +11,59 @@ pub enum Decision {
     Allow,
     Deny,
     AlwaysAllow,
+    /// Deny, and deny calls like this one from now on without asking
+    DenyAndBlock,
+    /// Deny for now, and remind the user to retry it later
+    Snooze,
+    /// Allow and apply the permission suggestion at this index
+    #[cfg_attr(not(any(feature = "telegram", feature = "mock")), allow(dead_code))]
+    ApplySuggestion(usize),
 }
 
 impl Decision {
     /// Convert decision to Claude Code hook behavior string.
     pub fn to_behavior(self) -> &'static str {
         match self {
-            Decision::Allow | Decision::AlwaysAllow => "allow",
-            Decision::Deny => "deny",
+            Decision::Allow | Decision::AlwaysAllow | Decision::ApplySuggestion(_) => "allow",
+            Decision::Deny | Decision::DenyAndBlock | Decision::Snooze => "deny",
+        }
+    }
+
+    /// Plain-text status for the decision, e.g. `❌ Denied`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Decision::Allow | Decision::ApplySuggestion(_) => "✅ Approved",
+            Decision::Deny => "❌ Denied",
+            Decision::AlwaysAllow => "🔓 Always Allowed",
+            Decision::DenyAndBlock => "🚫 Denied and blocked",
+            Decision::Snooze => "⏰ Snoozed",
         }
     }
 }
 
+/// How free-form text should be rendered by a messenger.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextFormat {
+    /// Shown verbatim, with markup characters escaped where needed
+    #[default]
+    Plain,
+    /// Passed through as the platform's own markdown
+    Markdown,
+    /// Wrapped in a code block
+    Code,
+}
+
+/// Edits of a MultiEdit request shown in full; the rest are only counted.
+#[cfg_attr(
+    not(any(feature = "telegram", feature = "discord", feature = "signal")),
+    allow(dead_code)
+)]
+pub const SHOWN_EDITS: usize = 2;
+
 /// Permission request message content.
 #[derive(Debug, Clone)]
 pub struct PermissionMessage {
-    /// Unique request identifier (8-char UUID prefix)
+    /// Unique request identifier, 8 to 16 characters
     pub request_id: String,
     /// Tool name (e.g., "Bash", "Edit", "Write")
     pub tool_name: String,
@@ -31,6 +71,22 @@ pub struct PermissionMessage {
     pub hostname: String,
     /// Tool input parameters
     pub tool_input: Value,
+    /// Distinct responders who must press Allow
+    pub required_approvals: usize,
+    /// Labels of permission suggestions offered as extra choices
+    pub suggestions: Vec<String>,
+    /// Configured emoji, label and fields for the tool
+    pub display: ToolDisplay,
+    /// Claude Code session that asked (empty if unknown)
+    pub session_id: String,
+    /// Excerpt of what Claude said before asking
+    pub context: Option<String>,
+    /// Host as shown in messages, if labeled
+    pub host_label: Option<String>,
+    /// Signed link to decide in a browser, for one-line requests
+    pub approval_link: Option<String>,
+    /// One-line description of a Bash command
+    pub explanation: Option<String>,
 }
 
 impl PermissionMessage {
@@ -41,6 +97,214 @@ impl PermissionMessage {
             tool_name,
             hostname,
             tool_input,
+            required_approvals: 1,
+            suggestions: Vec::new(),
+            display: ToolDisplay::default(),
+            session_id: String::new(),
+            context: None,
+            host_label: None,
+            approval_link: None,
+            explanation: None,
         }
     }
+
+    /// Require Allow presses from this many distinct responders.
+    pub fn with_required_approvals(mut self, required_approvals: usize) -> Self {
+        self.required_approvals = required_approvals.max(1);
+        self
+    }
+
+    /// Offer permission suggestions, by label, alongside Allow and Deny.
+    pub fn with_suggestions(mut self, suggestions: Vec<String>) -> Self {
+        self.suggestions = suggestions;
+        self
+    }
+
+    /// Show the tool with a configured emoji, label and input fields.
+    pub fn with_display(mut self, display: ToolDisplay) -> Self {
+        self.display = display;
+        self
+    }
+
+    /// Record which Claude Code session asked.
+    pub fn with_session(mut self, session_id: &str) -> Self {
+        self.session_id = session_id.to_string();
+        self
+    }
+
+    /// Show what Claude said before asking, above the tool details.
+    pub fn with_context(mut self, context: Option<String>) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Show the host under a label, e.g. "🟦 gpu-box", instead of its hostname.
+    pub fn with_host_label(mut self, host_label: Option<String>) -> Self {
+        self.host_label = host_label;
+        self
+    }
+
+    /// Offer a link to decide in a browser where there are no buttons.
+    pub fn with_approval_link(mut self, approval_link: Option<String>) -> Self {
+        self.approval_link = approval_link;
+        self
+    }
+
+    /// Describe the command in plain words under it.
+    pub fn with_explanation(mut self, explanation: Option<String>) -> Self {
+        self.explanation = explanation;
+        self
+    }
+
+    /// Host as shown to people: its label, or the hostname.
+    pub fn host(&self) -> &str {
+        self.host_label.as_deref().unwrap_or(&self.hostname)
+    }
+
+    /// Tool name as shown to people, e.g. "🐙 GitHub" when configured.
+    pub fn tool_label(&self) -> String {
+        let name = self.display.label.as_deref().unwrap_or(&self.tool_name);
+        match &self.display.emoji {
+            Some(emoji) => format!("{} {}", emoji, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// Whether a label or emoji replaces the bare tool name.
+    pub fn has_custom_label(&self) -> bool {
+        self.display.label.is_some() || self.display.emoji.is_some()
+    }
+
+    /// The configured input fields present in the tool input, as text.
+    #[cfg_attr(
+        not(any(feature = "telegram", feature = "discord", feature = "homeassistant")),
+        allow(dead_code)
+    )]
+    pub fn display_fields(&self) -> Vec<(&str, String)> {
+        self.display
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let value = self.tool_input.get(field)?;
+                let text = match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                Some((field.as_str(), text))
+            })
+            .collect()
+    }
+
+    /// The most telling part of the input: the first configured field, or
+    /// the command, path or URL of built-in tools.
+    #[cfg_attr(
+        not(any(feature = "telegram", feature = "discord", feature = "homeassistant")),
+        allow(dead_code)
+    )]
+    pub fn detail(&self) -> Option<String> {
+        self.display_fields()
+            .into_iter()
+            .next()
+            .map(|(_, text)| text)
+            .or_else(|| tool_detail(&self.tool_name, &self.tool_input).map(str::to_string))
+    }
+
+    /// Old and new text of each edit in a MultiEdit request.
+    #[cfg_attr(
+        not(any(feature = "telegram", feature = "discord", feature = "signal")),
+        allow(dead_code)
+    )]
+    pub fn multi_edits(&self) -> Vec<(&str, &str)> {
+        self.tool_input
+            .get("edits")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .map(|edit| {
+                let text = |key| edit.get(key).and_then(|v| v.as_str()).unwrap_or_default();
+                (text("old_string"), text("new_string"))
+            })
+            .collect()
+    }
+
+    /// What a NotebookEdit request does, e.g. `insert markdown cell after 3f2a`.
+    #[cfg_attr(
+        not(any(feature = "telegram", feature = "discord", feature = "signal")),
+        allow(dead_code)
+    )]
+    pub fn notebook_change(&self) -> String {
+        let field = |key| self.tool_input.get(key).and_then(|v| v.as_str());
+        let mode = field("edit_mode").unwrap_or("replace");
+        let mut change = mode.to_string();
+        if let Some(cell_type) = field("cell_type") {
+            change.push_str(&format!(" {}", cell_type));
+        }
+        change.push_str(" cell");
+        if let Some(cell_id) = field("cell_id") {
+            let joiner = if mode == "insert" { " after " } else { " " };
+            change.push_str(&format!("{}{}", joiner, cell_id));
+        }
+        change
+    }
+
+    /// Agent type a Task request would start, and what it is for, e.g.
+    /// `Explore: Find the config loader`.
+    #[cfg_attr(
+        not(any(feature = "telegram", feature = "discord", feature = "signal")),
+        allow(dead_code)
+    )]
+    pub fn subagent_line(&self) -> String {
+        let field = |key| self.tool_input.get(key).and_then(|v| v.as_str());
+        let agent = field("subagent_type")
+            .filter(|agent| !agent.is_empty())
+            .unwrap_or("general-purpose");
+        match field("description") {
+            Some(description) => format!("{}: {}", agent, description),
+            None => agent.to_string(),
+        }
+    }
+
+    /// Site a WebFetch request would fetch from, e.g. `docs.rs`.
+    #[cfg_attr(
+        not(any(feature = "telegram", feature = "discord", feature = "signal")),
+        allow(dead_code)
+    )]
+    pub fn fetch_domain(&self) -> Option<String> {
+        (self.tool_name == "WebFetch")
+            .then(|| tool_detail(&self.tool_name, &self.tool_input))
+            .flatten()
+            .and_then(url_domain)
+    }
+
+    /// Domains listed under `key` of a WebSearch request, such as
+    /// `allowed_domains` or `blocked_domains`.
+    #[cfg_attr(
+        not(any(feature = "telegram", feature = "discord", feature = "signal")),
+        allow(dead_code)
+    )]
+    pub fn search_domains(&self, key: &str) -> Vec<&str> {
+        self.tool_input
+            .get(key)
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str())
+            .collect()
+    }
+
+    /// The entry "Always Allow" adds for this request, e.g. the tool name or
+    /// `WebFetch(domain:docs.rs)`.
+    #[cfg_attr(not(any(feature = "telegram", feature = "discord")), allow(dead_code))]
+    pub fn always_allow_entry(&self) -> String {
+        allow_entry(&self.tool_name, &self.tool_input)
+    }
+
+    /// The rule "Deny & Block" adds for this request.
+    #[cfg_attr(not(any(feature = "telegram", feature = "discord")), allow(dead_code))]
+    pub fn block_rule(&self) -> BlockRule {
+        BlockRule::for_call(
+            &self.tool_name,
+            tool_detail(&self.tool_name, &self.tool_input),
+        )
+    }
 }
@@ -0,0 +1,243 @@
+//! Telegram updates shared by every process polling the same bot.
+//!
+//! Telegram hands pending updates to whichever `getUpdates` call comes first
+//! and drops them once a later call confirms them with a higher offset, so
+//! concurrent hook processes and the `bot` command used to swallow each
+//! other's button presses and commands. Instead, updates go through a spool
+//! file in the state directory: whoever holds its lock fetches from the
+//! stored offset and appends what arrives, and every process reads the
+//! spool for updates it has not seen yet.
+
+use crate::clock::now_secs;
+use crate::config::default_update_spool_path;
+use crate::lockfile::{write_atomic, FileLock};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::AllowedUpdate;
+use teloxide::RequestError;
+
+/// Spooled updates older than this are dropped.
+const RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// Long-poll timeout for `getUpdates`, in seconds.
+const POLL_TIMEOUT_SECS: u32 = 5;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Spool {
+    /// Next update ID to ask Telegram for (0: none confirmed yet)
+    #[serde(default)]
+    offset: u32,
+    /// Unix time before which nobody should poll, after a rate limit
+    #[serde(default)]
+    retry_at: u64,
+    #[serde(default)]
+    updates: Vec<SpooledUpdate>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SpooledUpdate {
+    /// Unix time the update was fetched
+    received_at: u64,
+    /// Process that fetched the update
+    pid: u32,
+    update: Update,
+}
+
+/// An update read from the spool.
+#[derive(Debug)]
+pub struct SharedUpdate {
+    pub update: Update,
+    /// Whether this process fetched it from Telegram
+    pub fetched_here: bool,
+}
+
+/// One process's view of the shared update spool.
+pub struct UpdateInbox {
+    bot: Bot,
+    path: PathBuf,
+    /// Highest update ID already handed out
+    cursor: u32,
+}
+
+impl UpdateInbox {
+    /// Open the default spool for a bot.
+    ///
+    /// Updates already spooled are skipped; open the inbox before sending
+    /// anything whose replies it should see.
+    pub fn new(bot: Bot) -> Self {
+        let path = default_update_spool_path(bot.token());
+        Self::at(bot, path)
+    }
+
+    /// Open a spool at an explicit path.
+    pub fn at(bot: Bot, path: PathBuf) -> Self {
+        let spool = read_spool(&path);
+        let newest = spool.updates.iter().map(|s| s.update.id.0).max();
+        let cursor = newest.unwrap_or(0).max(spool.offset.saturating_sub(1));
+        Self { bot, path, cursor }
+    }
+
+    /// Fetch from Telegram if no other process is, then return unseen updates.
+    pub async fn next_updates(&mut self) -> Vec<SharedUpdate> {
+        if let Err(e) = self.fetch().await {
+            tracing::warn!("Failed to fetch Telegram updates: {}", e);
+        }
+        self.take_new()
+    }
+
+    /// Long-poll Telegram and append the results, while holding the spool lock.
+    async fn fetch(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let Some(_lock) = FileLock::try_acquire(&self.path)? else {
+            return Ok(());
+        };
+
+        let mut spool = read_spool(&self.path);
+        let now = now_secs();
+        if now < spool.retry_at {
+            return Ok(());
+        }
+        let mut request = self
+            .bot
+            .get_updates()
+            .timeout(POLL_TIMEOUT_SECS)
+            .allowed_updates(vec![AllowedUpdate::Message, AllowedUpdate::CallbackQuery]);
+        if spool.offset > 0 {
+            request = request.offset(spool.offset as i32);
+        }
+        let updates = match request.await {
+            Ok(updates) => updates,
+            Err(RequestError::RetryAfter(wait)) => {
+                // Hold every poller off, not just this one
+                spool.retry_at = now + u64::from(wait.seconds());
+                write_spool(&self.path, &spool)?;
+                return Err(RequestError::RetryAfter(wait).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        spool
+            .updates
+            .retain(|s| s.received_at + RETENTION.as_secs() > now);
+        for update in updates {
+            spool.offset = spool.offset.max(update.id.0 + 1);
+            spool.updates.push(SpooledUpdate {
+                received_at: now,
+                pid: std::process::id(),
+                update,
+            });
+        }
+        write_spool(&self.path, &spool)?;
+        Ok(())
+    }
+
+    /// Spooled updates newer than the cursor, oldest first.
+    fn take_new(&mut self) -> Vec<SharedUpdate> {
+        let pid = std::process::id();
+        let mut new: Vec<SharedUpdate> = read_spool(&self.path)
+            .updates
+            .into_iter()
+            .filter(|s| s.update.id.0 > self.cursor)
+            .map(|s| SharedUpdate {
+                update: s.update,
+                fetched_here: s.pid == pid,
+            })
+            .collect();
+        new.sort_by_key(|s| s.update.id);
+        if let Some(last) = new.last() {
+            self.cursor = last.update.id.0;
+        }
+        new
+    }
+}
+
+fn read_spool(path: &Path) -> Spool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Replace the spool atomically so readers never see a partial file.
+fn write_spool(path: &Path, spool: &Spool) -> io::Result<()> {
+    write_atomic(path, &serde_json::to_string(spool)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn callback_update(id: u32, data: &str) -> Update {
+        serde_json::from_value(json!({
+            "update_id": id,
+            "callback_query": {
+                "id": format!("q{}", id),
+                "from": {"id": 42, "is_bot": false, "first_name": "Alice"},
+                "chat_instance": "1",
+                "data": data
+            }
+        }))
+        .unwrap()
+    }
+
+    fn spooled(id: u32, pid: u32) -> SpooledUpdate {
+        SpooledUpdate {
+            received_at: now_secs(),
+            pid,
+            update: callback_update(id, "abc12345:allow"),
+        }
+    }
+
+    #[test]
+    fn test_inbox_skips_updates_spooled_before_opening() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("spool.json");
+        let mut spool = Spool {
+            offset: 11,
+            retry_at: 0,
+            updates: vec![spooled(10, 1)],
+        };
+        write_spool(&path, &spool).unwrap();
+
+        let mut inbox = UpdateInbox::at(Bot::new("123:abc"), path.clone());
+        assert!(inbox.take_new().is_empty());
+
+        spool.updates.push(spooled(11, std::process::id()));
+        spool.updates.push(spooled(12, 1));
+        spool.offset = 13;
+        write_spool(&path, &spool).unwrap();
+
+        let new = inbox.take_new();
+        let ids: Vec<u32> = new.iter().map(|s| s.update.id.0).collect();
+        assert_eq!(ids, [11, 12]);
+        assert!(new[0].fetched_here);
+        assert!(!new[1].fetched_here);
+        assert!(inbox.take_new().is_empty());
+    }
+
+    #[test]
+    fn test_every_inbox_sees_each_update() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("spool.json");
+        let mut first = UpdateInbox::at(Bot::new("123:abc"), path.clone());
+        let mut second = UpdateInbox::at(Bot::new("123:abc"), path.clone());
+
+        let spool = Spool {
+            offset: 6,
+            retry_at: 0,
+            updates: vec![spooled(5, 1)],
+        };
+        write_spool(&path, &spool).unwrap();
+
+        assert_eq!(first.take_new().len(), 1);
+        assert_eq!(second.take_new().len(), 1);
+    }
+}
@@ -0,0 +1,72 @@
+//! Stand-in messenger that decides requests while vacation mode is on.
+//!
+//! Requests that would be sent to chat are decided by `vacation_fallback`
+//! at once; notifications still go to the messenger it stands in for.
+
+use super::{Decision, Messenger, PermissionMessage, TextFormat};
+use crate::config::VacationFallback;
+use crate::error::HookError;
+use async_trait::async_trait;
+use std::path::Path;
+use std::time::Duration;
+
+/// Messenger answering requests by the vacation fallback.
+pub struct VacationMessenger {
+    inner: Box<dyn Messenger>,
+    fallback: VacationFallback,
+}
+
+impl VacationMessenger {
+    /// Stand in for `inner`, deciding requests by `fallback`.
+    pub fn new(inner: Box<dyn Messenger>, fallback: VacationFallback) -> Self {
+        Self { inner, fallback }
+    }
+}
+
+#[async_trait]
+impl Messenger for VacationMessenger {
+    async fn send_permission_request(
+        &self,
+        message: &PermissionMessage,
+        _timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        // Nobody stands in for a second approver
+        match self.fallback {
+            VacationFallback::Allow if message.required_approvals == 1 => Ok(Decision::Allow),
+            _ => Ok(Decision::Deny),
+        }
+    }
+
+    async fn send_question(
+        &self,
+        _question: &str,
+        _hostname: &str,
+        _timeout: Duration,
+    ) -> Result<Decision, HookError> {
+        Ok(Decision::Deny)
+    }
+
+    async fn send_notification(&self, text: &str) -> Result<(), HookError> {
+        self.inner.send_notification(text).await
+    }
+
+    async fn send_text(&self, text: &str, format: TextFormat) -> Result<(), HookError> {
+        self.inner.send_text(text, format).await
+    }
+
+    async fn send_file(&self, path: &Path) -> Result<(), HookError> {
+        self.inner.send_file(path).await
+    }
+
+    async fn send_auto_approved(&self, message: &PermissionMessage) -> Result<(), HookError> {
+        self.inner.send_auto_approved(message).await
+    }
+
+    fn supports_quorum(&self) -> bool {
+        self.inner.supports_quorum()
+    }
+
+    fn platform_name(&self) -> &'static str {
+        "vacation"
+    }
+}
@@ -0,0 +1,144 @@
+//! Migration from the legacy `telegram_hook.json` to `hook_config.json`.
+
+use crate::config::default_timeout_seconds;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// Convert a legacy config document into the new format.
+///
+/// The chat ID keeps its original string or integer form.
+pub fn convert_legacy(legacy: &Value) -> Result<Value> {
+    let bot_token = legacy
+        .get("telegram_bot_token")
+        .and_then(Value::as_str)
+        .filter(|token| !token.is_empty())
+        .context("Legacy config is missing telegram_bot_token")?;
+    let chat_id = legacy
+        .get("telegram_chat_id")
+        .filter(|id| id.is_string() || id.is_i64())
+        .context("Legacy config is missing telegram_chat_id")?;
+
+    Ok(json!({
+        "messengers": {
+            "telegram": {
+                "enabled": true,
+                "bot_token": bot_token,
+                "chat_id": chat_id,
+            }
+        },
+        "preferences": {
+            "messenger_priority": ["telegram"],
+            "timeout_seconds": default_timeout_seconds(),
+        }
+    }))
+}
+
+/// Write a file readable only by the owner, since it holds a bot token.
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(())
+}
+
+/// Migrate `legacy_path` into `new_path`, optionally deleting the legacy file.
+pub fn run(legacy_path: &Path, new_path: &Path, force: bool, delete_legacy: bool) -> Result<()> {
+    if !legacy_path.exists() {
+        anyhow::bail!("Legacy config not found: {}", legacy_path.display());
+    }
+    if new_path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists (use --force to overwrite)",
+            new_path.display()
+        );
+    }
+
+    let content = fs::read_to_string(legacy_path)
+        .with_context(|| format!("Failed to read {}", legacy_path.display()))?;
+    let legacy: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid JSON in {}", legacy_path.display()))?;
+    if legacy.get("messengers").is_some() {
+        anyhow::bail!("{} is already in the new format", legacy_path.display());
+    }
+
+    let converted = convert_legacy(&legacy)?;
+    write_private(
+        new_path,
+        &(serde_json::to_string_pretty(&converted)? + "\n"),
+    )?;
+    println!(
+        "✅ Migrated {} → {}",
+        legacy_path.display(),
+        new_path.display()
+    );
+
+    if delete_legacy {
+        fs::remove_file(legacy_path)
+            .with_context(|| format!("Failed to delete {}", legacy_path.display()))?;
+        println!("🗑️  Deleted {}", legacy_path.display());
+    } else {
+        println!(
+            "The legacy file is no longer read and can be deleted: {}",
+            legacy_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_convert_legacy_preserves_values() {
+        let converted =
+            convert_legacy(&json!({"telegram_bot_token": "tok", "telegram_chat_id": "-100"}))
+                .unwrap();
+        assert_eq!(converted["messengers"]["telegram"]["bot_token"], "tok");
+        assert_eq!(converted["messengers"]["telegram"]["chat_id"], "-100");
+        assert_eq!(converted["preferences"]["timeout_seconds"], 300);
+
+        assert!(convert_legacy(&json!({"telegram_chat_id": 1})).is_err());
+    }
+
+    #[test]
+    fn test_migrate_roundtrip() {
+        let dir = tempdir().unwrap();
+        let legacy = dir.path().join("telegram_hook.json");
+        let new = dir.path().join("hook_config.json");
+        fs::write(
+            &legacy,
+            r#"{"telegram_bot_token":"tok","telegram_chat_id":42}"#,
+        )
+        .unwrap();
+
+        run(&legacy, &new, false, true).unwrap();
+        assert!(!legacy.exists());
+
+        let config = Config::from_json(&new).unwrap();
+        assert_eq!(config.telegram.unwrap().bot_token, "tok");
+        assert_eq!(config.messenger_priority, vec!["telegram"]);
+
+        // Refuses to overwrite without --force
+        fs::write(
+            &legacy,
+            r#"{"telegram_bot_token":"tok","telegram_chat_id":42}"#,
+        )
+        .unwrap();
+        assert!(run(&legacy, &new, false, false).is_err());
+        assert!(run(&legacy, &new, true, false).is_ok());
+    }
+}
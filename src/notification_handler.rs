@@ -5,13 +5,16 @@
 
 use crate::config::Config;
 use crate::error::HookError;
-use crate::messenger::telegram::TelegramMessenger;
-use crate::messenger::Messenger;
+use crate::messenger::{dry_run, notify_primary};
+use crate::prompt_dedup::{prompt_tool, PromptMarks};
 use serde::Deserialize;
-use std::io::{self, Read};
-
-#[cfg(feature = "discord")]
-use crate::messenger::discord::DiscordMessenger;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncReadExt;
+use tokio::time::Instant;
 
 /// Claude Code notification hook input.
 #[derive(Debug, Deserialize)]
@@ -24,11 +27,13 @@ pub struct NotificationInput {
     pub message: String,
     /// Session ID
     #[serde(default)]
-    #[allow(dead_code)]
     pub session_id: String,
     /// Current working directory
     #[serde(default)]
     pub cwd: String,
+    /// Path to the session transcript
+    #[serde(default)]
+    pub transcript_path: String,
 }
 
 /// Format notification for messaging.
@@ -75,55 +80,144 @@ pub async fn send_notification(
     config: &Config,
     input: &NotificationInput,
 ) -> Result<(), HookError> {
-    let text = format_notification(input, &config.hostname);
-
-    // Try Discord if configured as primary
-    #[cfg(feature = "discord")]
-    if config.primary_messenger == "discord" {
-        if let Some(ref discord_config) = config.discord {
-            if discord_config.enabled {
-                let messenger =
-                    DiscordMessenger::new(&discord_config.bot_token, discord_config.user_id);
-                return messenger.send_notification(&text).await;
-            }
-        }
+    let text = format_notification(input, &config.host_label);
+    send_text(config, &text).await
+}
+
+/// Send preformatted text via the configured messenger.
+async fn send_text(config: &Config, text: &str) -> Result<(), HookError> {
+    notify_primary(config, text).await
+}
+
+/// Format an idle reminder; later reminders use more urgent icons.
+fn format_idle_reminder(hostname: &str, cwd: &str, minutes: u64, index: usize) -> String {
+    let icon = match index {
+        0 => "⏰",
+        1 => "⚠️",
+        _ => "🚨",
+    };
+
+    let mut lines = vec![
+        format!("{} **Still Waiting for Input**", icon),
+        format!(
+            "Claude has been waiting {} min on host {}",
+            minutes, hostname
+        ),
+    ];
+
+    if !cwd.is_empty() {
+        let project = cwd.split('/').next_back().unwrap_or(cwd);
+        lines.push(format!("📁 **Project:** {}", project));
     }
 
-    // Try Telegram if configured
-    if let Some(ref telegram_config) = config.telegram {
-        let messenger = TelegramMessenger::new(&telegram_config.bot_token, telegram_config.chat_id);
-        return messenger.send_notification(&text).await;
+    lines.join("\n")
+}
+
+/// Get the last modification time of the transcript, if available.
+fn transcript_modified(transcript_path: &Path) -> Option<SystemTime> {
+    fs::metadata(transcript_path)
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// Spawn a detached `nudge` process so the hook can return immediately.
+fn spawn_idle_nudger(input: &NotificationInput, config_path: Option<&Path>) -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut command = Command::new(exe);
+    if let Some(path) = config_path {
+        command.arg("--config").arg(path);
+    }
+    if dry_run() {
+        command.arg("--dry-run");
     }
+    command
+        .arg("nudge")
+        .arg("--transcript-path")
+        .arg(&input.transcript_path)
+        .arg("--cwd")
+        .arg(&input.cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
 
-    // Try Discord as fallback
-    #[cfg(feature = "discord")]
-    if let Some(ref discord_config) = config.discord {
-        if discord_config.enabled {
-            let messenger =
-                DiscordMessenger::new(&discord_config.bot_token, discord_config.user_id);
-            return messenger.send_notification(&text).await;
+/// Send escalating idle reminders until the session resumes.
+///
+/// The session counts as resumed once the transcript is modified, which
+/// happens as soon as the user replies to Claude.
+pub async fn run_idle_nudges(
+    transcript_path: &Path,
+    cwd: &str,
+    config_path: Option<&Path>,
+) -> Result<(), HookError> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?.for_project(cwd);
+
+    let mut reminder_minutes = config.idle_nudges.reminder_minutes.clone();
+    reminder_minutes.sort_unstable();
+
+    let baseline = transcript_modified(transcript_path);
+    let start = Instant::now();
+
+    for (index, minutes) in reminder_minutes.into_iter().enumerate() {
+        tokio::time::sleep_until(start + Duration::from_secs(minutes * 60)).await;
+
+        if transcript_modified(transcript_path) != baseline {
+            return Ok(());
         }
+
+        let text = format_idle_reminder(&config.host_label, cwd, minutes, index);
+        send_text(&config, &text).await?;
     }
 
-    // No messenger available - silently skip
     Ok(())
 }
 
 /// Read JSON input from stdin.
-fn read_stdin() -> Result<String, io::Error> {
+async fn read_stdin() -> Result<String, io::Error> {
     let mut buffer = String::new();
-    io::stdin().read_to_string(&mut buffer)?;
+    tokio::io::stdin().read_to_string(&mut buffer).await?;
     Ok(buffer)
 }
 
 /// Main entry point for the notification handler.
-pub async fn run() -> Result<(), HookError> {
-    let input_str = read_stdin()?;
-    let input: NotificationInput = serde_json::from_str(&input_str)?;
+pub async fn run(config_path: Option<&Path>) -> Result<(), HookError> {
+    let input_str = read_stdin().await?;
+    run_with_input(config_path, &input_str).await
+}
+
+/// Handle raw notification hook input JSON.
+pub async fn run_with_input(config_path: Option<&Path>, input_str: &str) -> Result<(), HookError> {
+    let input: NotificationInput = serde_json::from_str(input_str)?;
+
+    let config = Config::load(config_path.map(Path::to_path_buf))?.for_project(&input.cwd);
+
+    // The PermissionRequest hook may already have asked about this call
+    if input.notification_type == "permission_prompt"
+        && config.permission_prompt_dedup_seconds > 0
+        && !input.session_id.is_empty()
+    {
+        let window = Duration::from_secs(config.permission_prompt_dedup_seconds);
+        let tool_name = prompt_tool(&input.message);
+        if PromptMarks::new(None)
+            .covered(&input.session_id, tool_name, window)
+            .await
+        {
+            tracing::info!("Permission prompt already sent as a request, not sending it");
+            return Ok(());
+        }
+    }
+
+    send_notification(&config, &input).await?;
 
-    let config = Config::load(None)?;
+    if input.notification_type == "idle_prompt" && config.idle_nudges.enabled {
+        if let Err(e) = spawn_idle_nudger(&input, config_path) {
+            tracing::warn!("Failed to start idle reminders: {}", e);
+        }
+    }
 
-    send_notification(&config, &input).await
+    Ok(())
 }
 
 #[cfg(test)]
@@ -137,6 +231,7 @@ mod tests {
             message: "Claude needs permission to run bash".to_string(),
             session_id: "test123".to_string(),
             cwd: "/home/user/project".to_string(),
+            transcript_path: String::new(),
         };
 
         let result = format_notification(&input, "test-host");
@@ -152,10 +247,23 @@ mod tests {
             message: "Waiting for input".to_string(),
             session_id: "test123".to_string(),
             cwd: "/home/user/myapp".to_string(),
+            transcript_path: String::new(),
         };
 
         let result = format_notification(&input, "my-machine");
         assert!(result.contains("Idle"));
         assert!(result.contains("my-machine"));
     }
+
+    #[test]
+    fn test_format_idle_reminder_escalates() {
+        let first = format_idle_reminder("my-machine", "/home/user/myapp", 10, 0);
+        assert!(first.starts_with("⏰"));
+        assert!(first.contains("waiting 10 min on host my-machine"));
+        assert!(first.contains("myapp"));
+
+        let last = format_idle_reminder("my-machine", "", 60, 2);
+        assert!(last.starts_with("🚨"));
+        assert!(!last.contains("Project"));
+    }
 }
@@ -0,0 +1,157 @@
+//! Notification-only Telegram chat for observing requests and decisions.
+//!
+//! `messengers.observer` names a chat, e.g. a team channel, that is sent a
+//! plain line for each request, decision, timeout and finished session while
+//! approvals stay in the private chat of `messengers.telegram`. It is not a
+//! messenger: nothing is ever asked there, its messages never carry buttons,
+//! config loading rejects a chat that requests are sent to, and the bot
+//! refuses commands and presses from it.
+
+use crate::config::{Config, ObserverConfig, WebhookEvent};
+use crate::messenger::{clients, dry_run};
+use crate::redact::Redactor;
+use crate::tool_timer::describe_tool;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// How long posting one event may take; hooks wait for it before answering.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether a chat is the observer chat, which never decides anything.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn is_observer_chat(config: &Config, chat_id: i64) -> bool {
+    config
+        .observer
+        .as_ref()
+        .is_some_and(|observer| observer.chat_id.0 == chat_id)
+}
+
+/// One line describing an event, e.g. "✅ laptop: Bash `ls` → allow by @ana".
+fn format_event(event: WebhookEvent, host: &str, fields: &Value) -> String {
+    let text = |key: &str| fields.get(key).and_then(Value::as_str).unwrap_or_default();
+    let tool = describe_tool(
+        text("tool_name"),
+        fields.get("tool_input").unwrap_or(&Value::Null),
+    );
+    match event {
+        WebhookEvent::RequestCreated => format!("🔔 {}: {} requested", host, tool),
+        WebhookEvent::DecisionMade => {
+            let decision = text("decision");
+            let icon = if decision.contains("allow") {
+                "✅"
+            } else {
+                "❌"
+            };
+            let mut line = format!("{} {}: {} → {}", icon, host, tool, decision);
+            if !text("responder").is_empty() {
+                line.push_str(&format!(" by {}", text("responder")));
+            }
+            if !text("reason").is_empty() {
+                line.push_str(&format!(" ({})", text("reason")));
+            }
+            line
+        }
+        WebhookEvent::Timeout => format!("⏱️ {}: {} timed out", host, tool),
+        WebhookEvent::SessionCompleted => {
+            let mut line = format!("🏁 {}: session in {} finished", host, text("project"));
+            if !text("error").is_empty() {
+                line.push_str(&format!(" with an error: {}", text("error")));
+            }
+            line
+        }
+    }
+}
+
+/// The `sendMessage` body; it never has a `reply_markup`.
+fn send_message_body(observer: &ObserverConfig, text: &str) -> Value {
+    let mut body = json!({"chat_id": observer.chat_id.0, "text": text});
+    if let Some(topic_id) = observer.topic_id {
+        body["message_thread_id"] = json!(topic_id);
+    }
+    body
+}
+
+/// Post an event to the observer chat, if there is one that wants it.
+///
+/// Delivery is best effort, like webhooks. In dry-run mode the line is
+/// printed to stderr instead.
+pub async fn publish(config: &Config, event: WebhookEvent, fields: &Value) {
+    let Some(observer) = config.observer.as_ref().filter(|o| o.wants(event)) else {
+        return;
+    };
+    let text =
+        Redactor::new(&config.redaction).redact(&format_event(event, &config.host_label, fields));
+    if dry_run() {
+        eprintln!("[dry-run] observer {}: {}", observer.chat_id, text);
+        return;
+    }
+
+    let url = format!(
+        "{}/bot{}/sendMessage",
+        clients::telegram_api_url(),
+        observer.bot_token
+    );
+    let result = clients::shared()
+        .http()
+        .post(url)
+        .timeout(REQUEST_TIMEOUT)
+        .json(&send_message_body(observer, &text))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(e) = result {
+        // Leave the token out of the logged URL
+        tracing::warn!("Failed to post to observer chat: {}", e.without_url());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChatId;
+
+    #[test]
+    fn test_format_event() {
+        let fields = json!({
+            "tool_name": "Bash",
+            "tool_input": {"command": "cargo test"},
+            "decision": "allow",
+            "responder": "@ana (42)",
+        });
+        assert_eq!(
+            format_event(WebhookEvent::RequestCreated, "laptop", &fields),
+            "🔔 laptop: Bash `cargo test` requested"
+        );
+        assert_eq!(
+            format_event(WebhookEvent::DecisionMade, "laptop", &fields),
+            "✅ laptop: Bash `cargo test` → allow by @ana (42)"
+        );
+
+        let fields = json!({"tool_name": "Bash", "decision": "policy_deny", "reason": "no"});
+        assert_eq!(
+            format_event(WebhookEvent::DecisionMade, "laptop", &fields),
+            "❌ laptop: Bash → policy_deny (no)"
+        );
+        let fields = json!({"project": "myapp", "error": null});
+        assert_eq!(
+            format_event(WebhookEvent::SessionCompleted, "laptop", &fields),
+            "🏁 laptop: session in myapp finished"
+        );
+    }
+
+    #[test]
+    fn test_send_message_body_has_no_buttons() {
+        let observer = ObserverConfig {
+            bot_token: "123:abc".to_string(),
+            chat_id: ChatId(-100),
+            topic_id: Some(7),
+            events: vec![WebhookEvent::DecisionMade],
+        };
+        let body = send_message_body(&observer, "hi");
+        assert_eq!(body["chat_id"], -100);
+        assert_eq!(body["message_thread_id"], 7);
+        assert!(body.get("reply_markup").is_none());
+        assert!(observer.wants(WebhookEvent::DecisionMade));
+        assert!(!observer.wants(WebhookEvent::RequestCreated));
+    }
+}
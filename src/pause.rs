@@ -0,0 +1,153 @@
+//! Sessions and hosts paused from chat.
+//!
+//! `/pause <session|host>` in the bot adds an entry to a small file in the
+//! state directory; the hook denies every request matching an entry without
+//! asking, until `/resume` removes it. The agent keeps running and sees the
+//! denials, so it can be stopped without killing the process.
+
+use crate::clock::now_secs;
+use crate::config::default_pauses_path;
+use crate::lockfile::{write_atomic, FileLock};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// A host or session whose requests are denied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pause {
+    /// Hostname, or session ID (or its start)
+    pub target: String,
+    /// Who paused it
+    pub by: String,
+    /// Unix time it was paused
+    #[serde(default)]
+    pub since: u64,
+}
+
+impl Pause {
+    /// Whether a request from this host and session falls under the pause.
+    pub fn matches(&self, hostname: &str, session_id: &str) -> bool {
+        self.target == hostname || (!session_id.is_empty() && session_id.starts_with(&self.target))
+    }
+}
+
+impl fmt::Display for Pause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (by {})", self.target, self.by)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PauseData {
+    #[serde(default)]
+    paused: Vec<Pause>,
+}
+
+/// The list of paused hosts and sessions.
+#[derive(Debug, Clone)]
+pub struct PauseList {
+    storage_path: PathBuf,
+}
+
+impl PauseList {
+    /// Create a list kept at the given path, or the default state file.
+    pub fn new(storage_path: Option<PathBuf>) -> Self {
+        Self {
+            storage_path: storage_path.unwrap_or_else(default_pauses_path),
+        }
+    }
+
+    fn load(&self) -> PauseData {
+        fs::read_to_string(&self.storage_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Read, change and write the list while holding its lock.
+    fn update<T>(&self, change: impl FnOnce(&mut PauseData) -> T) -> io::Result<T> {
+        if let Some(parent) = self.storage_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _lock = FileLock::acquire(&self.storage_path)?;
+        let mut data = self.load();
+        let result = change(&mut data);
+        write_atomic(&self.storage_path, &serde_json::to_string_pretty(&data)?)?;
+        Ok(result)
+    }
+
+    /// Every current pause.
+    #[cfg_attr(not(any(feature = "telegram", unix, windows)), allow(dead_code))]
+    pub fn list(&self) -> Vec<Pause> {
+        self.load().paused
+    }
+
+    /// The pause covering a request from this host and session, if any.
+    pub fn matching(&self, hostname: &str, session_id: &str) -> Option<Pause> {
+        self.load()
+            .paused
+            .into_iter()
+            .find(|pause| pause.matches(hostname, session_id))
+    }
+
+    /// Pause a host or session. Returns false if it already was.
+    #[cfg_attr(not(any(feature = "telegram", unix, windows)), allow(dead_code))]
+    pub fn pause(&self, target: &str, by: &str) -> io::Result<bool> {
+        let since = now_secs();
+        self.update(|data| {
+            if data.paused.iter().any(|pause| pause.target == target) {
+                return false;
+            }
+            data.paused.push(Pause {
+                target: target.to_string(),
+                by: by.to_string(),
+                since,
+            });
+            true
+        })
+    }
+
+    /// Resume a host or session, or everything for an empty target.
+    ///
+    /// Returns the pauses lifted.
+    #[cfg_attr(not(any(feature = "telegram", unix, windows)), allow(dead_code))]
+    pub fn resume(&self, target: &str) -> io::Result<Vec<Pause>> {
+        self.update(|data| {
+            let (lifted, kept) = std::mem::take(&mut data.paused)
+                .into_iter()
+                .partition(|pause| target.is_empty() || pause.target == target);
+            data.paused = kept;
+            lifted
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_pause_and_resume() {
+        let dir = tempdir().unwrap();
+        let pauses = PauseList::new(Some(dir.path().join("paused.json")));
+        assert_eq!(pauses.matching("laptop", "1a2b3c4d-0000"), None);
+
+        assert!(pauses.pause("1a2b3c4d", "@alice").unwrap());
+        assert!(!pauses.pause("1a2b3c4d", "@bob").unwrap());
+        assert!(pauses.pause("build-server", "@alice").unwrap());
+
+        let pause = pauses.matching("laptop", "1a2b3c4d-0000").unwrap();
+        assert_eq!(pause.to_string(), "1a2b3c4d (by @alice)");
+        assert!(pauses.matching("laptop", "").is_none());
+        assert!(pauses.matching("build-server", "ffff").is_some());
+
+        let lifted = pauses.resume("1a2b3c4d").unwrap();
+        assert_eq!(lifted.len(), 1);
+        assert!(pauses.matching("laptop", "1a2b3c4d-0000").is_none());
+        assert_eq!(pauses.resume("").unwrap().len(), 1);
+        assert!(pauses.list().is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+//! Looking at files in project directories from chat.
+//!
+//! `/cat <path>` and `/diff <path>` in the bot show a file's contents or its
+//! uncommitted changes, so an Edit request can be checked against the file
+//! before it is decided. Only files inside the `remote_runs.projects`
+//! directories can be shown, after following symlinks and `..`.
+//!
+//! `/git <session|host>` shows `git status -sb` and the diff stat of a
+//! session's working directory, or of every Claude session running in tmux
+//! on this host, before more destructive commands are approved. A session's
+//! directory is read from its transcript, and like files it must be inside
+//! the projects.
+
+use crate::config::expand_home;
+use crate::stop_handler::ReverseLines;
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Most characters shown, to stay within one Telegram message.
+const MAX_CHARS: usize = 3500;
+
+/// Most bytes read from a file, which is more than one message can show.
+const MAX_READ: u64 = 4 * MAX_CHARS as u64;
+
+/// The file a path names, if it is inside one of the project directories.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn resolve(projects: &BTreeMap<String, PathBuf>, path: &str) -> Result<PathBuf> {
+    let path = expand_home(path.trim());
+    if !path.is_absolute() {
+        bail!("Expected an absolute path");
+    }
+    inside_projects(projects, &path)
+}
+
+/// A path with symlinks and `..` followed, if it is inside one of the
+/// project directories.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn inside_projects(projects: &BTreeMap<String, PathBuf>, path: &Path) -> Result<PathBuf> {
+    let resolved = path
+        .canonicalize()
+        .with_context(|| format!("Cannot open {}", path.display()))?;
+    let inside = projects
+        .values()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| resolved.starts_with(root));
+    if !inside {
+        bail!("{} is outside the configured projects", path.display());
+    }
+    Ok(resolved)
+}
+
+/// Escape text for a message and cut it to `MAX_CHARS`, counting what the
+/// escaping adds, saying when something was left out.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn fit(text: &str, escape: impl Fn(&str) -> String) -> String {
+    let mut shown = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let escaped = escape(c.encode_utf8(&mut [0; 4]));
+        used += escaped.chars().count();
+        if used > MAX_CHARS {
+            return format!("{}\n… cut to fit one message", shown);
+        }
+        shown.push_str(&escaped);
+    }
+    shown
+}
+
+/// The start of a text file, reading no more than one message can show.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn cat(path: &Path) -> Result<String> {
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(MAX_READ).read_to_end(&mut bytes))
+        .with_context(|| format!("Cannot read {}", path.display()))?;
+    if bytes.contains(&0) {
+        bail!("{} is a binary file", path.display());
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Run git in a directory, returning its output.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Uncommitted changes to a file, staged or not.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn diff(path: &Path) -> Result<String> {
+    let dir = path.parent().unwrap_or(path);
+    let file = path.to_string_lossy();
+    let diff = git(dir, &["diff", "--no-color", "HEAD", "--", &file])?;
+    if diff.trim().is_empty() {
+        return Ok("No uncommitted changes".to_string());
+    }
+    Ok(diff)
+}
+
+/// Branch, changed files and diff stat of a working tree.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn git_summary(dir: &Path) -> Result<String> {
+    let status = git(dir, &["status", "-sb"])?;
+    let stat = git(dir, &["diff", "--no-color", "--stat", "HEAD"]).unwrap_or_default();
+    Ok(format!("{}\n{}", status.trim_end(), stat.trim_end())
+        .trim_end()
+        .to_string())
+}
+
+/// The full ID and the transcript of the only session whose ID starts with
+/// `prefix`.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn find_session(projects_dir: &Path, prefix: &str) -> Result<(String, PathBuf)> {
+    if prefix.is_empty() {
+        bail!("Expected the start of a session ID");
+    }
+    let transcripts: Vec<PathBuf> = fs::read_dir(projects_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|project| fs::read_dir(project.path()).ok())
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "jsonl")
+                && path
+                    .file_stem()
+                    .is_some_and(|stem| stem.to_string_lossy().starts_with(prefix))
+        })
+        .collect();
+    let transcript = match transcripts.as_slice() {
+        [] => bail!("No session {}", prefix),
+        [transcript] => transcript.clone(),
+        _ => bail!("{} matches several sessions", prefix),
+    };
+    let id = transcript
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok((id, transcript))
+}
+
+/// Working directory of the only session whose ID starts with `prefix`, from
+/// the last transcript line that records one, if it is inside one of the
+/// project directories.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub fn session_cwd(
+    projects_dir: &Path,
+    projects: &BTreeMap<String, PathBuf>,
+    prefix: &str,
+) -> Result<PathBuf> {
+    let (_, transcript) = find_session(projects_dir, prefix)?;
+    let file =
+        File::open(&transcript).with_context(|| format!("Cannot read {}", transcript.display()))?;
+    // Read from the end, so long sessions are not loaded in full
+    let cwd = ReverseLines::new(file)
+        .filter_map(|line| serde_json::from_str::<Value>(&line).ok())
+        .find_map(|entry| entry.get("cwd")?.as_str().map(PathBuf::from))
+        .with_context(|| format!("Session {} has no working directory", prefix))?;
+    // Transcripts are written by the session, so its directory is checked too
+    inside_projects(projects, &cwd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::{tempdir, TempDir};
+
+    /// A project `app` with a source file and an image, next to a file
+    /// outside it.
+    fn projects() -> (TempDir, PathBuf, BTreeMap<String, PathBuf>) {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("app");
+        fs::create_dir(&project).unwrap();
+        fs::write(project.join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(project.join("logo.png"), b"\x89PNG\0\0").unwrap();
+        fs::write(dir.path().join("secret.txt"), "hunter2").unwrap();
+        let projects = BTreeMap::from([("app".to_string(), project.clone())]);
+        (dir, project, projects)
+    }
+
+    fn path(project: &Path, name: &str) -> String {
+        format!("{}/{}", project.display(), name)
+    }
+
+    /// Transcripts of sessions `abc123-1` (working in `app`, then `app/sub`),
+    /// `abc123-2` and `def456` (working outside the projects).
+    fn transcripts(dir: &Path, project: &Path) -> PathBuf {
+        let projects_dir = dir.join("claude");
+        let transcripts = projects_dir.join("-home-me-app");
+        fs::create_dir_all(&transcripts).unwrap();
+        let sub = project.join("sub");
+        fs::create_dir(&sub).unwrap();
+        let cwd = |dir: &Path| json!({"cwd": dir}).to_string();
+        fs::write(
+            transcripts.join("abc123-1.jsonl"),
+            format!("{}\n{}\n{{}}\n", cwd(project), cwd(&sub)),
+        )
+        .unwrap();
+        fs::write(transcripts.join("abc123-2.jsonl"), "").unwrap();
+        fs::write(transcripts.join("def456.jsonl"), cwd(dir)).unwrap();
+        projects_dir
+    }
+
+    #[test]
+    fn test_cat_reads_project_files() {
+        let (_dir, project, projects) = projects();
+        let file = resolve(&projects, &path(&project, "main.rs")).unwrap();
+        assert_eq!(cat(&file).unwrap(), "fn main() {}\n");
+    }
+
+    #[test]
+    fn test_cat_rejects_binary() {
+        let (_dir, project, projects) = projects();
+        let file = resolve(&projects, &path(&project, "logo.png")).unwrap();
+        assert!(cat(&file).is_err());
+    }
+
+    #[test]
+    fn test_resolve_rejects_outside_projects() {
+        let (_dir, project, projects) = projects();
+        // Escapes through `..` are caught once the path is resolved
+        assert!(resolve(&projects, &path(&project, "../secret.txt")).is_err());
+        assert!(resolve(&projects, "app/main.rs").is_err());
+        assert!(resolve(&projects, &path(&project, "missing.rs")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_symlinks_out_of_projects() {
+        let (dir, project, projects) = projects();
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), project.join("link.txt"))
+            .unwrap();
+        assert!(resolve(&projects, &path(&project, "link.txt")).is_err());
+    }
+
+    #[test]
+    fn test_cat_reads_big_files_in_part() {
+        let (_dir, project, projects) = projects();
+        fs::write(project.join("big.txt"), "x".repeat(MAX_READ as usize * 2)).unwrap();
+        let big = cat(&resolve(&projects, &path(&project, "big.txt")).unwrap()).unwrap();
+        assert_eq!(big.len(), MAX_READ as usize);
+    }
+
+    #[test]
+    fn test_fit_truncates_after_escaping() {
+        let escape = |text: &str| text.replace('`', "\\`");
+        let shown = fit(&"`".repeat(MAX_READ as usize), escape);
+        assert!(shown.ends_with("… cut to fit one message"));
+        assert!(shown.chars().count() <= MAX_CHARS + 30);
+        assert!(shown.starts_with("\\`\\`"));
+        assert_eq!(fit("short", escape), "short");
+    }
+
+    #[test]
+    fn test_find_session_by_prefix() {
+        let (dir, project, _) = projects();
+        let projects_dir = transcripts(dir.path(), &project);
+        assert_eq!(
+            find_session(&projects_dir, "de").unwrap().0,
+            "def456".to_string()
+        );
+        assert!(find_session(&projects_dir, "").is_err());
+    }
+
+    #[test]
+    fn test_session_cwd_uses_last_directory() {
+        let (dir, project, projects) = projects();
+        let projects_dir = transcripts(dir.path(), &project);
+        assert_eq!(
+            session_cwd(&projects_dir, &projects, "abc123-1").unwrap(),
+            project.join("sub").canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_session_cwd_rejects_ambiguous_and_unknown_sessions() {
+        let (dir, project, projects) = projects();
+        let projects_dir = transcripts(dir.path(), &project);
+        let ambiguous = session_cwd(&projects_dir, &projects, "abc123").unwrap_err();
+        assert!(ambiguous.to_string().contains("several sessions"));
+        assert!(session_cwd(&projects_dir, &projects, "fff").is_err());
+    }
+
+    #[test]
+    fn test_session_cwd_rejects_outside_projects() {
+        let (dir, project, projects) = projects();
+        let projects_dir = transcripts(dir.path(), &project);
+        let outside = session_cwd(&projects_dir, &projects, "def").unwrap_err();
+        assert!(outside
+            .to_string()
+            .contains("outside the configured projects"));
+    }
+}
@@ -0,0 +1,156 @@
+//! Organization policy that user configuration cannot override.
+//!
+//! Administrators can place a policy file at a system location (by default
+//! `/etc/claude-code-remote/policy.json`). Its deny rules are checked before
+//! the always-allow list or any messenger, so matching tool calls can never
+//! be approved remotely. A policy file that exists but cannot be read denies
+//! every request, so a broken policy never silently stops applying.
+
+use crate::config::tool_call_matches;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// System-wide policy file location.
+pub fn system_policy_path() -> PathBuf {
+    #[cfg(windows)]
+    {
+        std::env::var_os("ProgramData")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+            .join("claude-code-remote")
+            .join("policy.json")
+    }
+    #[cfg(not(windows))]
+    {
+        PathBuf::from("/etc/claude-code-remote/policy.json")
+    }
+}
+
+/// A tool call the organization forbids approving remotely.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DenyRule {
+    /// Tool names the rule applies to, with `*`/`?` wildcards (empty: every tool)
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// Patterns matched against the command, file path or URL (empty: any input)
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Explanation returned to Claude Code with the denial
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Contents of the policy file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default)]
+    deny: Vec<DenyRule>,
+}
+
+/// Loaded organization policy.
+#[derive(Debug, Default)]
+pub struct Policy {
+    /// Where the policy came from, if a file exists
+    pub path: Option<PathBuf>,
+    pub deny: Vec<DenyRule>,
+    /// Why the policy file could not be used; every request is denied
+    pub error: Option<String>,
+}
+
+impl Policy {
+    /// Load a policy file; a missing file is an empty policy.
+    pub fn load(path: &Path) -> Self {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => return Self::broken(path, e.to_string()),
+        };
+        match serde_json::from_str::<PolicyFile>(&content) {
+            Ok(file) => Self {
+                path: Some(path.to_path_buf()),
+                deny: file.deny,
+                error: None,
+            },
+            Err(e) => Self::broken(path, e.to_string()),
+        }
+    }
+
+    /// Load the system-wide policy file.
+    pub fn load_system() -> Self {
+        Self::load(&system_policy_path())
+    }
+
+    fn broken(path: &Path, error: String) -> Self {
+        tracing::error!("Unusable organization policy {}: {}", path.display(), error);
+        Self {
+            path: Some(path.to_path_buf()),
+            deny: Vec::new(),
+            error: Some(error),
+        }
+    }
+
+    /// Reason a tool call is forbidden, or `None` if the policy allows asking.
+    pub fn denial(&self, tool_name: &str, detail: Option<&str>) -> Option<String> {
+        if let Some(error) = &self.error {
+            return Some(format!("organization policy file is invalid ({})", error));
+        }
+        self.deny
+            .iter()
+            .find(|rule| tool_call_matches(&rule.tools, &rule.patterns, tool_name, detail))
+            .map(|rule| {
+                rule.reason
+                    .clone()
+                    .unwrap_or_else(|| "forbidden by organization policy".to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_policy_denies_matching_calls() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("policy.json");
+        fs::write(
+            &path,
+            r#"{"deny": [
+                {"tools": ["Bash"], "patterns": ["rm -rf /*"], "reason": "No recursive deletes"},
+                {"tools": ["Write", "Edit"], "patterns": ["/etc/*"]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let policy = Policy::load(&path);
+        assert_eq!(
+            policy.denial("Bash", Some("rm -rf /var/lib")).as_deref(),
+            Some("No recursive deletes")
+        );
+        assert_eq!(
+            policy.denial("Edit", Some("/etc/hosts")).as_deref(),
+            Some("forbidden by organization policy")
+        );
+        assert!(policy.denial("Bash", Some("ls")).is_none());
+    }
+
+    #[test]
+    fn test_missing_and_broken_policy() {
+        let dir = tempdir().unwrap();
+        let missing = Policy::load(&dir.path().join("missing.json"));
+        assert!(missing.path.is_none());
+        assert!(missing.denial("Bash", Some("rm -rf /")).is_none());
+
+        let path = dir.path().join("policy.json");
+        fs::write(&path, r#"{"deny": [], "allow": []}"#).unwrap();
+        let broken = Policy::load(&path);
+        assert!(broken
+            .denial("Read", None)
+            .unwrap()
+            .starts_with("organization policy file is invalid"));
+    }
+}
@@ -0,0 +1,199 @@
+//! Custom policies written as WebAssembly modules.
+//!
+//! Modules listed in `preferences.policy_modules` see each permission request
+//! after the organization policy and before any messenger, and may allow or
+//! deny it outright. A module exports its `memory` and two functions:
+//!
+//! - `alloc(len: i32) -> i32` returns where to put `len` bytes of input;
+//! - `evaluate(ptr: i32, len: i32) -> i32` reads the request as JSON
+//!   (`tool_name`, `tool_input`, `host` and `session_id`) and answers 0 to
+//!   ask, 1 to allow or 2 to deny.
+//!
+//! A module may import `ccr.now() -> i64`, the Unix time, e.g. to follow an
+//! on-call rota; it has no other access to the machine. A denial from any
+//! module wins over an allow from another. A module that fails to load, traps
+//! or runs out of fuel is skipped with a warning, so the request is asked.
+//! Modules only run when built with the `wasm` feature.
+
+use anyhow::Result;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Instructions a module may run per request, so a loop can't hang the hook.
+#[cfg(feature = "wasm")]
+const FUEL: u64 = 10_000_000;
+
+/// What a module says about a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Leave it to a messenger
+    Ask,
+    /// Allow without asking
+    Allow,
+    /// Deny without asking
+    Deny,
+}
+
+impl Verdict {
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(Self::Ask),
+            1 => Some(Self::Allow),
+            2 => Some(Self::Deny),
+            _ => None,
+        }
+    }
+}
+
+/// Name of a module as shown in history, e.g. `oncall.wasm`.
+fn module_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Run a module on the request JSON.
+#[cfg(feature = "wasm")]
+fn run(path: &Path, input: &[u8]) -> Result<Verdict> {
+    use crate::clock::now_secs;
+    use anyhow::Context;
+    use wasmi::{Config, Engine, Linker, Module, Store};
+
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, &std::fs::read(path)?)?;
+    let mut store = Store::new(&engine, ());
+    store.set_fuel(FUEL).map_err(wasmi::Error::from)?;
+
+    let mut linker = Linker::<()>::new(&engine);
+    linker.func_wrap("ccr", "now", || -> i64 { now_secs() as i64 })?;
+    let instance = linker.instantiate(&mut store, &module)?.start(&mut store)?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .context("no exported memory")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc")?;
+    let evaluate = instance.get_typed_func::<(i32, i32), i32>(&store, "evaluate")?;
+
+    let len = i32::try_from(input.len())?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory
+        .write(&mut store, ptr as u32 as usize, input)
+        .map_err(wasmi::Error::from)?;
+    let code = evaluate.call(&mut store, (ptr, len))?;
+    Verdict::from_code(code).with_context(|| format!("unknown answer {}", code))
+}
+
+#[cfg(not(feature = "wasm"))]
+fn run(_path: &Path, _input: &[u8]) -> Result<Verdict> {
+    anyhow::bail!("not supported (compile with --features wasm)")
+}
+
+/// Ask each module about a request.
+///
+/// Returns an allow or deny with the module that gave it, or `None` if every
+/// module left the request to be asked.
+pub fn evaluate(modules: &[PathBuf], request: &Value) -> Option<(Verdict, String)> {
+    let input = request.to_string();
+    let mut allowed_by = None;
+    for path in modules {
+        match run(path, input.as_bytes()) {
+            Ok(Verdict::Deny) => return Some((Verdict::Deny, module_name(path))),
+            Ok(Verdict::Allow) => {
+                allowed_by.get_or_insert_with(|| module_name(path));
+            }
+            Ok(Verdict::Ask) => {}
+            Err(e) => tracing::warn!("Policy module {} failed: {:#}", path.display(), e),
+        }
+    }
+    allowed_by.map(|module| (Verdict::Allow, module))
+}
+
+#[cfg(all(test, feature = "wasm"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::tempdir;
+
+    /// A module whose `evaluate` answers `answer` for requests longer than
+    /// `min_len` bytes and asks otherwise, or loops forever if `answer` is
+    /// negative.
+    fn module(answer: i8, min_len: i8) -> Vec<u8> {
+        let body: Vec<u8> = if answer < 0 {
+            // loop br 0 end i32.const 0
+            vec![0x03, 0x40, 0x0c, 0x00, 0x0b, 0x41, 0x00]
+        } else {
+            // local.get 1 i32.const min_len i32.gt_s if i32 ... else 0 end
+            vec![
+                0x20,
+                0x01,
+                0x41,
+                min_len as u8,
+                0x4a,
+                0x04,
+                0x7f,
+                0x41,
+                answer as u8,
+                0x05,
+                0x41,
+                0x00,
+                0x0b,
+            ]
+        };
+        let mut evaluate = vec![0x00];
+        evaluate.extend(body);
+        evaluate.push(0x0b);
+
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        // Types: (i32) -> i32, (i32 i32) -> i32
+        wasm.extend([
+            0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f,
+        ]);
+        // Functions, one page of memory, exports
+        wasm.extend([0x03, 0x03, 0x02, 0x00, 0x01]);
+        wasm.extend([0x05, 0x03, 0x01, 0x00, 0x01]);
+        wasm.extend([
+            0x07, 0x1d, 0x03, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00, 0x05, b'a',
+            b'l', b'l', b'o', b'c', 0x00, 0x00, 0x08, b'e', b'v', b'a', b'l', b'u', b'a', b't',
+            b'e', 0x00, 0x01,
+        ]);
+        // Code: alloc returns 16
+        let alloc = [0x04, 0x00, 0x41, 0x10, 0x0b];
+        let size = 1 + alloc.len() + 1 + evaluate.len();
+        wasm.extend([0x0a, size as u8, 0x02]);
+        wasm.extend(alloc);
+        wasm.push(evaluate.len() as u8);
+        wasm.extend(evaluate);
+        wasm
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let dir = tempdir().unwrap();
+        let path = |name: &str, wasm: Vec<u8>| {
+            let path = dir.path().join(name);
+            fs::write(&path, wasm).unwrap();
+            path
+        };
+        let allow = path("allow.wasm", module(1, 0));
+        let deny_long = path("deny_long.wasm", module(2, 60));
+        let ask = path("ask.wasm", module(0, 0));
+        let spin = path("spin.wasm", module(-1, 0));
+        let broken = path("broken.wasm", b"not wasm".to_vec());
+
+        let short = json!({"tool_name": "Bash", "tool_input": {"command": "ls"}});
+        let long = json!({"tool_name": "Bash", "tool_input": {"command": "rm -rf ~/projects/old"}});
+        let modules = [spin, broken, ask, allow, deny_long];
+        assert_eq!(
+            evaluate(&modules, &short),
+            Some((Verdict::Allow, "allow.wasm".to_string()))
+        );
+        assert_eq!(
+            evaluate(&modules, &long),
+            Some((Verdict::Deny, "deny_long.wasm".to_string()))
+        );
+        assert_eq!(evaluate(&modules[..3], &short), None);
+    }
+}
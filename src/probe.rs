@@ -0,0 +1,155 @@
+//! Connectivity test that sends a probe message through each messenger.
+
+use crate::config::{Config, MESSENGER_NAMES};
+use crate::messenger::build_messenger;
+use anyhow::Result;
+use std::path::Path;
+use std::time::Instant;
+
+/// Suggest a fix for common Telegram API errors.
+fn telegram_hint(error: &str) -> Option<&'static str> {
+    if error.contains("chat not found") {
+        Some("check chat_id and send /start to the bot from that chat")
+    } else if error.contains("Unauthorized") || error.contains("Not Found") {
+        Some("check bot_token")
+    } else if error.contains("bot was blocked") {
+        Some("unblock the bot in Telegram")
+    } else if error.contains("message thread not found") {
+        Some("check topic_id")
+    } else {
+        None
+    }
+}
+
+/// Check whether a messenger is set up in the configuration.
+fn is_configured(config: &Config, name: &str) -> bool {
+    match name {
+        "telegram" => config.telegram.is_some(),
+        #[cfg(feature = "discord")]
+        "discord" => config.discord.as_ref().is_some_and(|d| d.enabled),
+        #[cfg(feature = "signal")]
+        "signal" => config.signal.as_ref().is_some_and(|s| s.enabled),
+        #[cfg(feature = "homeassistant")]
+        "homeassistant" => config.homeassistant.is_some(),
+        _ => config.plugins.iter().any(|plugin| plugin.name == name),
+    }
+}
+
+/// Probe a single messenger, printing the outcome. Returns whether it passed.
+async fn probe(config: &Config, name: &str, text: &str) -> bool {
+    if name == "signal" {
+        return probe_signal(config).await;
+    }
+
+    let Some(messenger) = build_messenger(config, name) else {
+        println!("❌ {}: not configured or not compiled in", name);
+        return false;
+    };
+
+    let start = Instant::now();
+    match messenger.send_notification(text).await {
+        Ok(()) => {
+            println!(
+                "✅ {}: delivered in {} ms",
+                messenger.platform_name(),
+                start.elapsed().as_millis()
+            );
+            true
+        }
+        Err(e) => {
+            let error = e.to_string();
+            println!("❌ {}: {}", messenger.platform_name(), error);
+            if let Some(hint) = telegram_hint(&error).filter(|_| name == "telegram") {
+                println!("   Hint: {}", hint);
+            }
+            false
+        }
+    }
+}
+
+/// Check the Signal store, since Signal cannot send outside a linked session.
+#[cfg(feature = "signal")]
+async fn probe_signal(config: &Config) -> bool {
+    let Some(signal) = config.signal.as_ref().filter(|s| s.enabled) else {
+        println!("❌ signal: not configured");
+        return false;
+    };
+
+    match crate::messenger::signal::check_store(&signal.data_path).await {
+        Ok(()) => {
+            println!(
+                "✅ Signal: store at {} is linked",
+                signal.data_path.display()
+            );
+            true
+        }
+        Err(e) => {
+            println!("❌ Signal: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(not(feature = "signal"))]
+async fn probe_signal(_config: &Config) -> bool {
+    println!("❌ signal: not available (compile with --features signal)");
+    false
+}
+
+/// Send a test message through one messenger, or every configured one.
+///
+/// Returns whether all probes succeeded.
+pub async fn run(config_path: Option<&Path>, messenger: Option<&str>) -> Result<bool> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
+
+    let names: Vec<String> = match messenger {
+        Some(name) => {
+            let plugins = config.plugins.iter().map(|plugin| plugin.name.as_str());
+            let known: Vec<&str> = MESSENGER_NAMES.iter().copied().chain(plugins).collect();
+            if !known.contains(&name) {
+                anyhow::bail!(
+                    "Unknown messenger \"{}\" (expected one of: {})",
+                    name,
+                    known.join(", ")
+                );
+            }
+            vec![name.to_string()]
+        }
+        None => config
+            .messenger_priority
+            .iter()
+            .filter(|name| is_configured(&config, name))
+            .cloned()
+            .collect(),
+    };
+
+    if names.is_empty() {
+        anyhow::bail!("No messenger configured");
+    }
+
+    let text = format!("🧪 Test message from host {}", config.host_label);
+    let mut all_ok = true;
+    for name in &names {
+        all_ok &= probe(&config, name, &text).await;
+    }
+
+    Ok(all_ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_telegram_hint() {
+        assert_eq!(
+            telegram_hint("Telegram error: A request to the Telegram server was unsuccessful: Bad Request: chat not found"),
+            Some("check chat_id and send /start to the bot from that chat")
+        );
+        assert_eq!(
+            telegram_hint("Telegram error: Unauthorized"),
+            Some("check bot_token")
+        );
+        assert_eq!(telegram_hint("network unreachable"), None);
+    }
+}
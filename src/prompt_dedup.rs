@@ -0,0 +1,192 @@
+//! Deduplication of permission prompts that also arrive as requests.
+//!
+//! Claude Code can fire both the Notification hook (`permission_prompt`) and
+//! the PermissionRequest hook for the same tool call. The request hook marks
+//! each call it sends to chat by session and tool in a small file in the
+//! state directory. A permission prompt for a session and tool with a request
+//! still open, or closed within `permission_prompt_dedup_seconds`, is not
+//! sent; one arriving first waits that long for its request.
+
+use crate::clock::now_secs;
+use crate::config::default_prompt_marks_path;
+use crate::lockfile::FileLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Marks untouched for this long are dropped.
+const MARK_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// How often a waiting prompt checks for its request.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Requests sent for one session and tool.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Mark {
+    /// Requests still waiting for a decision
+    #[serde(default)]
+    open: usize,
+    /// Unix time a request was last opened or closed
+    #[serde(default)]
+    last: u64,
+    /// Unix time the last request opened times out, after which open
+    /// requests are taken to belong to a hook that was killed
+    #[serde(default)]
+    expires: u64,
+}
+
+impl Mark {
+    fn covers(&self, window: u64, now: u64) -> bool {
+        (self.open > 0 && now <= self.expires) || now.saturating_sub(self.last) <= window
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MarkData {
+    /// Marks by `session_id/tool`
+    #[serde(default)]
+    requests: BTreeMap<String, Mark>,
+}
+
+fn key(session_id: &str, tool_name: &str) -> String {
+    format!("{}/{}", session_id, tool_name)
+}
+
+/// Permission requests sent to chat, by session and tool.
+#[derive(Debug, Clone)]
+pub struct PromptMarks {
+    storage_path: PathBuf,
+}
+
+impl PromptMarks {
+    /// Create marks kept at the given path, or the default state file.
+    pub fn new(storage_path: Option<PathBuf>) -> Self {
+        Self {
+            storage_path: storage_path.unwrap_or_else(default_prompt_marks_path),
+        }
+    }
+
+    fn load(&self) -> MarkData {
+        fs::read_to_string(&self.storage_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Read, change and write the marks while holding their lock.
+    fn update(&self, change: impl FnOnce(&mut MarkData)) -> io::Result<()> {
+        if let Some(parent) = self.storage_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _lock = FileLock::acquire(&self.storage_path)?;
+        let mut data = self.load();
+        change(&mut data);
+        fs::write(&self.storage_path, serde_json::to_string(&data)?)?;
+        Ok(())
+    }
+
+    /// Mark a request sent for this session and tool, timing out after `timeout`.
+    pub fn open(&self, session_id: &str, tool_name: &str, timeout: Duration) -> io::Result<()> {
+        self.open_at(session_id, tool_name, now_secs(), timeout.as_secs())
+    }
+
+    fn open_at(&self, session_id: &str, tool_name: &str, now: u64, timeout: u64) -> io::Result<()> {
+        self.update(|data| {
+            data.requests
+                .retain(|_, mark| now.saturating_sub(mark.last.max(mark.expires)) < MARK_TTL_SECS);
+            let mark = data.requests.entry(key(session_id, tool_name)).or_default();
+            mark.open += 1;
+            mark.last = now;
+            mark.expires = now + timeout;
+        })
+    }
+
+    /// Mark a request for this session and tool decided.
+    pub fn close(&self, session_id: &str, tool_name: &str) -> io::Result<()> {
+        self.close_at(session_id, tool_name, now_secs())
+    }
+
+    fn close_at(&self, session_id: &str, tool_name: &str, now: u64) -> io::Result<()> {
+        self.update(|data| {
+            if let Some(mark) = data.requests.get_mut(&key(session_id, tool_name)) {
+                mark.open = mark.open.saturating_sub(1);
+                mark.last = now;
+            }
+        })
+    }
+
+    /// Whether a request covers a prompt for this session and tool, or any
+    /// tool of the session if the prompt does not name one.
+    fn covers_at(&self, session_id: &str, tool_name: Option<&str>, window: u64, now: u64) -> bool {
+        let prefix = key(session_id, "");
+        self.load().requests.iter().any(|(key, mark)| {
+            let tool_matches = match tool_name {
+                Some(tool_name) => key.strip_prefix(&prefix) == Some(tool_name),
+                None => key.starts_with(&prefix),
+            };
+            tool_matches && mark.covers(window, now)
+        })
+    }
+
+    /// Wait up to `window` for a request covering a prompt.
+    pub async fn covered(
+        &self,
+        session_id: &str,
+        tool_name: Option<&str>,
+        window: Duration,
+    ) -> bool {
+        let deadline = tokio::time::Instant::now() + window;
+        loop {
+            if self.covers_at(session_id, tool_name, window.as_secs(), now_secs()) {
+                return true;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// The tool a permission prompt names, e.g. `Bash` in
+/// "Claude needs your permission to use Bash".
+pub fn prompt_tool(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once("permission to use ")?;
+    rest.split_whitespace().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_prompt_marks() {
+        assert_eq!(
+            prompt_tool("Claude needs your permission to use Bash"),
+            Some("Bash")
+        );
+        assert_eq!(prompt_tool("Claude needs permission"), None);
+
+        let dir = tempdir().unwrap();
+        let marks = PromptMarks::new(Some(dir.path().join("prompt_marks.json")));
+        let now = 1_700_000_000;
+        assert!(!marks.covers_at("s1", Some("Bash"), 5, now));
+
+        marks.open_at("s1", "Bash", now, 900).unwrap();
+        assert!(marks.covers_at("s1", Some("Bash"), 5, now + 600));
+        assert!(marks.covers_at("s1", None, 5, now + 600));
+        // Requests outlive their timeout only if their hook was killed
+        assert!(!marks.covers_at("s1", Some("Bash"), 5, now + 1_000));
+        assert!(!marks.covers_at("s1", Some("Edit"), 5, now));
+        assert!(!marks.covers_at("s2", Some("Bash"), 5, now));
+
+        // A decided request still covers prompts that trail it briefly
+        marks.close_at("s1", "Bash", now + 600).unwrap();
+        assert!(marks.covers_at("s1", Some("Bash"), 5, now + 603));
+        assert!(!marks.covers_at("s1", Some("Bash"), 5, now + 610));
+    }
+}
@@ -0,0 +1,135 @@
+//! Low-noise mode for read-only tools.
+//!
+//! With `preferences.read_only_tools` enabled, Read, Glob, Grep and LS calls
+//! are allowed without a message. Each one is counted in a small file in the
+//! state directory, and the hook that finds `digest_minutes` have passed
+//! since the last digest sends the counts as one notification instead.
+
+use crate::config::{default_read_only_digest_path, ReadOnlyToolsConfig};
+use crate::lockfile::FileLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Whether a tool call may be allowed without asking in low-noise mode.
+pub fn is_quiet(settings: &ReadOnlyToolsConfig, tool_name: &str) -> bool {
+    settings.enabled && settings.tools.iter().any(|tool| tool == tool_name)
+}
+
+/// Read-only calls allowed since the last digest.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Digest {
+    /// Unix time the first call was counted
+    #[serde(default)]
+    pub since: u64,
+    /// Calls by tool name
+    #[serde(default)]
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl Digest {
+    /// Count a call, returning the digest due to be sent, if any.
+    fn count(&mut self, tool_name: &str, now: u64, every: u64) -> Option<Digest> {
+        if self.counts.is_empty() {
+            self.since = now;
+        }
+        *self.counts.entry(tool_name.to_string()).or_default() += 1;
+        (now.saturating_sub(self.since) >= every).then(|| std::mem::take(self))
+    }
+
+    /// The notification, e.g. `📚 laptop: 42 read-only calls allowed in 30m (Read 30, Grep 12)`.
+    pub fn text(&self, hostname: &str, now: u64) -> String {
+        let total: u64 = self.counts.values().sum();
+        let mut counts: Vec<_> = self.counts.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let counts = counts
+            .iter()
+            .map(|(tool, count)| format!("{} {}", tool, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "📚 {}: {} read-only call{} allowed in {}m ({})",
+            hostname,
+            total,
+            if total == 1 { "" } else { "s" },
+            now.saturating_sub(self.since) / 60,
+            counts
+        )
+    }
+}
+
+/// Count a call allowed in low-noise mode, in the default state file.
+///
+/// Returns the digest to send once `digest_minutes` have passed since the
+/// first call counted; with `digest_minutes` at 0 nothing is counted.
+pub fn record(
+    settings: &ReadOnlyToolsConfig,
+    tool_name: &str,
+    now: u64,
+) -> io::Result<Option<Digest>> {
+    record_at(&default_read_only_digest_path(), settings, tool_name, now)
+}
+
+fn record_at(
+    path: &Path,
+    settings: &ReadOnlyToolsConfig,
+    tool_name: &str,
+    now: u64,
+) -> io::Result<Option<Digest>> {
+    if settings.digest_minutes == 0 {
+        return Ok(None);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _lock = FileLock::acquire(path)?;
+    let mut digest: Digest = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    let due = digest.count(tool_name, now, settings.digest_minutes * 60);
+    fs::write(path, serde_json::to_string(&digest)?)?;
+    Ok(due)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_quiet_tools_and_digest() {
+        let mut settings = ReadOnlyToolsConfig::default();
+        assert!(!is_quiet(&settings, "Read"));
+        settings.enabled = true;
+        assert!(is_quiet(&settings, "Grep"));
+        assert!(!is_quiet(&settings, "Bash"));
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("read_only_digest.json");
+        let now = 1_700_000_000;
+        for (tool, at) in [("Read", 0), ("Grep", 60), ("Read", 600)] {
+            assert_eq!(record_at(&path, &settings, tool, now + at).unwrap(), None);
+        }
+        let digest = record_at(&path, &settings, "Read", now + 1_800)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            digest.text("laptop", now + 1_800),
+            "📚 laptop: 4 read-only calls allowed in 30m (Read 3, Grep 1)"
+        );
+
+        // The next call starts a new digest
+        assert_eq!(
+            record_at(&path, &settings, "LS", now + 1_900).unwrap(),
+            None
+        );
+        settings.digest_minutes = 0;
+        assert_eq!(
+            record_at(&path, &settings, "LS", now + 9_000).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Free-form messages from shell scripts.
+//!
+//! `relay` sends text (from the command line or stdin) and optional file
+//! attachments to one, all, or the first available messenger.
+
+use crate::config::Config;
+use crate::messenger::{build_messenger, enabled_messengers, Messenger, TextFormat};
+use anyhow::{Context, Result};
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Resolve the message argument, reading stdin when it is `-`.
+fn message_text(message: Option<&str>, mut stdin: impl Read) -> Result<Option<String>> {
+    let text = match message {
+        Some("-") => {
+            let mut buffer = String::new();
+            stdin
+                .read_to_string(&mut buffer)
+                .context("Failed to read stdin")?;
+            buffer.trim_end_matches(['\r', '\n']).to_string()
+        }
+        Some(message) => message.to_string(),
+        None => return Ok(None),
+    };
+    Ok(Some(text).filter(|text| !text.trim().is_empty()))
+}
+
+/// Messengers selected by `--to`, or all enabled ones in priority order.
+fn select_messengers(config: &Config, to: Option<&str>) -> Result<Vec<Box<dyn Messenger>>> {
+    match to {
+        None | Some("all") => {
+            let messengers = enabled_messengers(config);
+            if messengers.is_empty() {
+                anyhow::bail!("No messenger configured");
+            }
+            Ok(messengers)
+        }
+        Some(name) => build_messenger(config, name)
+            .map(|messenger| vec![messenger])
+            .with_context(|| format!("{} is not configured", name)),
+    }
+}
+
+/// Send the text and attachment through one messenger.
+async fn deliver(
+    messenger: &dyn Messenger,
+    text: Option<&str>,
+    format: TextFormat,
+    file: Option<&Path>,
+) -> Result<()> {
+    if let Some(text) = text {
+        messenger.send_text(text, format).await?;
+    }
+    if let Some(file) = file {
+        messenger.send_file(file).await?;
+    }
+    Ok(())
+}
+
+/// Relay a message and/or file.
+///
+/// Without `to`, the first messenger in priority order that delivers wins.
+/// With `to`, every selected messenger must deliver.
+pub async fn run(
+    config_path: Option<&Path>,
+    message: Option<&str>,
+    to: Option<&str>,
+    format: TextFormat,
+    file: Option<&Path>,
+) -> Result<()> {
+    let text = message_text(message, io::stdin())?;
+    if text.is_none() && file.is_none() {
+        anyhow::bail!("Nothing to relay: pass a message, `-` to read stdin, or --file");
+    }
+    if let Some(file) = file {
+        if !file.is_file() {
+            anyhow::bail!("{} is not a file", file.display());
+        }
+    }
+
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
+    let messengers = select_messengers(&config, to)?;
+
+    if to.is_none() {
+        let mut last_error = None;
+        for messenger in &messengers {
+            match deliver(messenger.as_ref(), text.as_deref(), format, file).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    tracing::warn!("Failed to relay via {}: {}", messenger.platform_name(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        return last_error.map_or(Ok(()), Err);
+    }
+
+    let mut failures = Vec::new();
+    for messenger in &messengers {
+        if let Err(e) = deliver(messenger.as_ref(), text.as_deref(), format, file).await {
+            failures.push(format!("{}: {:#}", messenger.platform_name(), e));
+        }
+    }
+    if !failures.is_empty() {
+        anyhow::bail!(failures.join("; "));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_text() {
+        let stdin = "build done\n".as_bytes();
+        assert_eq!(
+            message_text(Some("-"), stdin).unwrap().as_deref(),
+            Some("build done")
+        );
+        assert_eq!(
+            message_text(Some("hello"), io::empty()).unwrap().as_deref(),
+            Some("hello")
+        );
+        assert!(message_text(Some("-"), "\n".as_bytes()).unwrap().is_none());
+        assert!(message_text(None, io::empty()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,303 @@
+//! Replay of past permission requests against the current rules.
+//!
+//! `rules replay` checks each request in the decision history against the
+//! organization policy, the block and always-allow lists and low-noise
+//! read-only tools as they are now, and reports which requests would now be
+//! allowed or denied without asking and which would be asked again. Entries
+//! passed with `--allow` count as if they were on the always-allow list, so a
+//! pattern can be tried on past requests before it is trusted.
+//!
+//! Auto-approval limits and time windows are left out, since they depend on
+//! when a call comes in, and history only keeps the command, file path or URL
+//! of each call, redacted if redaction was on.
+
+use crate::always_allow::{entry_allows, AlwaysAllowManager};
+use crate::config::Config;
+use crate::history::{self, ExportRow};
+use crate::policy::Policy;
+use crate::read_only;
+use crate::tool_timer::detail_input;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// How a request was, or would now be, handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Handling {
+    /// Allowed without asking
+    Allowed,
+    /// Denied without asking
+    Denied,
+    /// Sent to a messenger
+    Asked,
+}
+
+impl Handling {
+    /// How a request was handled, from its recorded decision.
+    fn recorded(decision: &str) -> Option<Self> {
+        match decision {
+            "auto_allow" | "read_only_allow" => Some(Self::Allowed),
+            "policy_deny" | "blocked" => Some(Self::Denied),
+            "allow" | "deny" | "deny_block" | "timeout" => Some(Self::Asked),
+            // Paused, snoozed, cancelled and failed requests say nothing about the rules,
+            // and policy modules may decide differently on a replay
+            _ => None,
+        }
+    }
+
+    fn heading(self) -> &'static str {
+        match self {
+            Self::Allowed => "✅ Now allowed without asking",
+            Self::Denied => "❌ Now denied without asking",
+            Self::Asked => "💬 Now asked",
+        }
+    }
+}
+
+/// The rules requests are replayed against.
+struct Rules<'a> {
+    config: &'a Config,
+    policy: Policy,
+    always_allow: AlwaysAllowManager,
+    /// Entries tried out as if they were on the always-allow list
+    trial: Vec<String>,
+}
+
+impl Rules<'_> {
+    /// How a call would be handled now, and the rule that decides it.
+    fn handle(&self, tool: &str, detail: Option<&str>) -> (Handling, String) {
+        if let Some(reason) = self.policy.denial(tool, detail) {
+            return (Handling::Denied, format!("policy: {}", reason));
+        }
+        let required_approvals = self.config.required_approvals(tool, detail);
+        let blocked = self.always_allow.blocked_by(tool, detail);
+        if required_approvals == 1
+            && blocked.is_none()
+            && read_only::is_quiet(&self.config.read_only_tools, tool)
+        {
+            return (Handling::Allowed, "read-only tool".to_string());
+        }
+        if let Some(rule) = blocked {
+            return (Handling::Denied, format!("blocked {}", rule));
+        }
+        if required_approvals > 1 {
+            let rule = format!("needs {} approvals", required_approvals);
+            return (Handling::Asked, rule);
+        }
+        let input = detail_input(tool, detail);
+        if let Some(entry) = self
+            .trial
+            .iter()
+            .find(|entry| entry_allows(entry, tool, &input))
+        {
+            return (Handling::Allowed, format!("--allow {}", entry));
+        }
+        if self.always_allow.allows(tool, &input) {
+            return (Handling::Allowed, "always-allow list".to_string());
+        }
+        (Handling::Asked, "no rule applies".to_string())
+    }
+}
+
+/// Past requests for one call whose handling would change.
+#[derive(Debug, Default, PartialEq)]
+struct Change {
+    /// Allowed from a messenger
+    allowed: usize,
+    /// Denied from a messenger, or timed out
+    denied: usize,
+    /// Decided without asking
+    automatic: usize,
+}
+
+impl Change {
+    fn count(&self) -> usize {
+        self.allowed + self.denied + self.automatic
+    }
+
+    /// e.g. `you allowed 18, you denied 1`
+    fn then(&self) -> String {
+        [
+            (self.allowed, "you allowed"),
+            (self.denied, "you denied"),
+            (self.automatic, "decided without asking"),
+        ]
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", what, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+/// What replaying the history found.
+#[derive(Debug, Default)]
+struct Report {
+    /// Requests replayed
+    total: usize,
+    /// Changed requests by new handling, call and the rule deciding them
+    changes: BTreeMap<(Handling, String, String), Change>,
+}
+
+impl Report {
+    fn lines(&self) -> Vec<String> {
+        let changed: usize = self.changes.values().map(Change::count).sum();
+        let mut lines = vec![format!(
+            "Replayed {} request(s): {} unchanged, {} handled differently",
+            self.total,
+            self.total - changed,
+            changed
+        )];
+        let mut heading = None;
+        for ((handling, call, rule), change) in &self.changes {
+            if heading != Some(*handling) {
+                let count: usize = self
+                    .changes
+                    .iter()
+                    .filter(|((other, _, _), _)| other == handling)
+                    .map(|(_, change)| change.count())
+                    .sum();
+                lines.push(String::new());
+                lines.push(format!("{}: {}", handling.heading(), count));
+                heading = Some(*handling);
+            }
+            // Calls someone said no to deserve a second look before they are allowed
+            let warning = if *handling == Handling::Allowed && change.denied > 0 {
+                "⚠️ "
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "   {}{} ×{} ({}) by {}",
+                warning,
+                call,
+                change.count(),
+                change.then(),
+                rule
+            ));
+        }
+        lines
+    }
+}
+
+/// Replay history rows against the rules.
+fn replay(rows: &[ExportRow], rules: &Rules) -> Report {
+    let mut report = Report::default();
+    for row in rows {
+        let Some(decision) = row.decision.as_deref() else {
+            continue;
+        };
+        let (Some(then), Some(tool)) = (Handling::recorded(decision), row.tool.as_deref()) else {
+            continue;
+        };
+        report.total += 1;
+        let (now, rule) = rules.handle(tool, row.command.as_deref());
+        if now == then {
+            continue;
+        }
+
+        let call = match &row.command {
+            Some(command) => format!("{} `{}`", tool, command),
+            None => tool.to_string(),
+        };
+        let change = report.changes.entry((now, call, rule)).or_default();
+        match decision {
+            "allow" => change.allowed += 1,
+            "deny" | "deny_block" | "timeout" => change.denied += 1,
+            _ => change.automatic += 1,
+        }
+    }
+    report
+}
+
+/// Replay the requests of the last `since` and print the report.
+pub fn run(config_path: Option<&Path>, since: Duration, trial: Vec<String>) -> Result<()> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
+    let rules = Rules {
+        config: &config,
+        policy: Policy::load_system(),
+        always_allow: AlwaysAllowManager::new(None),
+        trial,
+    };
+    for line in replay(&history::recent_rows(since), &rules).lines() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::always_allow::BlockRule;
+    use crate::policy::DenyRule;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_replay() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{"telegram_bot_token":"test_token","telegram_chat_id":"123456"}"#,
+        )
+        .unwrap();
+        let config = Config::from_json(&config_path).unwrap();
+        let always_allow = AlwaysAllowManager::new(Some(dir.path().join("always_allow.json")));
+        always_allow.add_tool("Edit").unwrap();
+        always_allow
+            .block(BlockRule::for_call("Bash", Some("rm -rf build")))
+            .unwrap();
+        let rules = Rules {
+            config: &config,
+            policy: Policy {
+                deny: vec![DenyRule {
+                    tools: vec!["Bash".to_string()],
+                    patterns: vec!["git push --force*".to_string()],
+                    reason: None,
+                }],
+                ..Policy::default()
+            },
+            always_allow,
+            trial: vec!["Bash(cargo test)".to_string()],
+        };
+
+        let row = |tool: &str, command: &str, decision: &str| ExportRow {
+            tool: Some(tool.to_string()),
+            command: Some(command.to_string()),
+            decision: Some(decision.to_string()),
+            ..ExportRow::default()
+        };
+        let rows = [
+            row("Bash", "cargo test", "allow"),
+            row("Bash", "cargo test", "allow"),
+            row("Bash", "cargo test", "deny"),
+            row("Bash", "rm -rf target", "allow"),
+            row("Bash", "git push --force", "auto_allow"),
+            row("Edit", "src/main.rs", "auto_allow"),
+            row("Write", "src/lib.rs", "auto_allow"),
+            row("Bash", "ls", "allow"),
+            row("Bash", "ls", "cancelled"),
+        ];
+
+        let report = replay(&rows, &rules);
+        assert_eq!(report.total, 8);
+        assert_eq!(
+            report.lines(),
+            [
+                "Replayed 8 request(s): 2 unchanged, 6 handled differently",
+                "",
+                "✅ Now allowed without asking: 3",
+                "   ⚠️ Bash `cargo test` ×3 (you allowed 2, you denied 1) by --allow Bash(cargo test)",
+                "",
+                "❌ Now denied without asking: 2",
+                "   Bash `git push --force` ×1 (decided without asking 1) by policy: forbidden by organization policy",
+                "   Bash `rm -rf target` ×1 (you allowed 1) by blocked Bash(rm *)",
+                "",
+                "💬 Now asked: 1",
+                "   Write `src/lib.rs` ×1 (decided without asking 1) by no rule applies",
+            ]
+        );
+    }
+}
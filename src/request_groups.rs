@@ -0,0 +1,401 @@
+//! Grouping of rapid-fire permission requests.
+//!
+//! Claude Code can fire off many tool calls at once, each asking for
+//! permission from its own hook process. Arrivals are recorded in a small
+//! file per session in the state directory; once more than `threshold`
+//! requests from one session arrive within `window_seconds`, further ones
+//! join a group. The process that opens a group waits a moment for others to
+//! join, sends them as one message with per-item buttons and writes each
+//! decision back to the file, where the other processes pick theirs up.
+
+use crate::config::{default_request_groups_path, RequestGroupingConfig, ToolDisplay};
+use crate::error::HookError;
+use crate::lockfile::FileLock;
+use crate::messenger::{new_request_id, Decision, Messenger, PermissionMessage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long the process opening a group waits for more requests to join.
+const GATHER: Duration = Duration::from_secs(1);
+
+/// How often waiting processes look for their decision.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Most requests in one message, so their buttons fit.
+const MAX_GROUP_SIZE: usize = 20;
+
+/// An open group older than this lost its sender and is dropped.
+const STALE_OPEN_GROUP: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionState {
+    /// Unix times in milliseconds of recent requests
+    #[serde(default)]
+    arrivals: Vec<u64>,
+    #[serde(default)]
+    groups: Vec<Group>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Group {
+    id: String,
+    /// Unix time in milliseconds the group was opened
+    opened_at: u64,
+    /// Still taking new members
+    open: bool,
+    /// Sending failed, so members ask on their own
+    #[serde(default)]
+    failed: bool,
+    members: Vec<Member>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Member {
+    request_id: String,
+    tool_name: String,
+    tool_input: Value,
+    #[serde(default)]
+    display: ToolDisplay,
+    #[serde(default)]
+    session_id: String,
+    #[serde(default)]
+    host_label: Option<String>,
+    /// Whether the request was allowed, once decided
+    #[serde(default)]
+    allowed: Option<bool>,
+}
+
+impl Member {
+    fn new(message: &PermissionMessage) -> Self {
+        Self {
+            request_id: message.request_id.clone(),
+            tool_name: message.tool_name.clone(),
+            tool_input: message.tool_input.clone(),
+            display: message.display.clone(),
+            session_id: message.session_id.clone(),
+            host_label: message.host_label.clone(),
+            allowed: None,
+        }
+    }
+
+    fn to_message(&self, hostname: &str) -> PermissionMessage {
+        PermissionMessage::new(
+            self.request_id.clone(),
+            self.tool_name.clone(),
+            hostname.to_string(),
+            self.tool_input.clone(),
+        )
+        .with_display(self.display.clone())
+        .with_session(&self.session_id)
+        .with_host_label(self.host_label.clone())
+    }
+}
+
+/// What a request does after arriving.
+#[derive(Debug, PartialEq, Eq)]
+enum Role {
+    /// Ask on its own
+    Alone,
+    /// Opened the group with this ID and sends it
+    Leader(String),
+    /// Joined the group with this ID and waits for its decision
+    Member(String),
+}
+
+/// Where a member's decision stands.
+#[derive(Debug, PartialEq, Eq)]
+enum Taken {
+    Waiting,
+    Decided(bool),
+    /// The group is gone or failed; ask on its own
+    Unavailable,
+}
+
+impl SessionState {
+    fn join(&mut self, member: Member, settings: &RequestGroupingConfig, now: u64) -> Role {
+        let window = settings.window_seconds * 1000;
+        self.arrivals.retain(|at| now.saturating_sub(*at) < window);
+        self.arrivals.push(now);
+        self.groups.retain(|group| {
+            !group.open || now.saturating_sub(group.opened_at) < STALE_OPEN_GROUP.as_millis() as u64
+        });
+
+        if let Some(group) = self
+            .groups
+            .iter_mut()
+            .find(|group| group.open && group.members.len() < MAX_GROUP_SIZE)
+        {
+            group.members.push(member);
+            return Role::Member(group.id.clone());
+        }
+        if self.arrivals.len() > settings.threshold {
+            let id = new_request_id();
+            self.groups.push(Group {
+                id: id.clone(),
+                opened_at: now,
+                open: true,
+                failed: false,
+                members: vec![member],
+            });
+            return Role::Leader(id);
+        }
+        Role::Alone
+    }
+
+    /// Remove a member once it is decided, or when giving up on it.
+    fn take(&mut self, group_id: &str, request_id: &str, give_up: bool) -> Taken {
+        let Some(index) = self.groups.iter().position(|group| group.id == group_id) else {
+            return Taken::Unavailable;
+        };
+        let group = &mut self.groups[index];
+        let Some(position) = group
+            .members
+            .iter()
+            .position(|member| member.request_id == request_id)
+        else {
+            return Taken::Unavailable;
+        };
+
+        let taken = match (group.failed, group.members[position].allowed) {
+            (true, _) => Taken::Unavailable,
+            (false, Some(allowed)) => Taken::Decided(allowed),
+            (false, None) if give_up => Taken::Decided(false),
+            (false, None) => return Taken::Waiting,
+        };
+        group.members.remove(position);
+        if group.members.is_empty() {
+            self.groups.remove(index);
+        }
+        taken
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Read, change and write a session file while holding its lock.
+fn update<T>(path: &Path, change: impl FnOnce(&mut SessionState) -> T) -> io::Result<T> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _lock = FileLock::acquire(path)?;
+    let mut state: SessionState = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    let result = change(&mut state);
+    fs::write(path, serde_json::to_string(&state)?)?;
+    Ok(result)
+}
+
+/// Groups bursts of permission requests, per Claude Code session.
+pub struct RequestGroups {
+    dir: PathBuf,
+    settings: RequestGroupingConfig,
+}
+
+impl RequestGroups {
+    /// Keep session files in the default state directory.
+    pub fn new(settings: RequestGroupingConfig) -> Self {
+        Self {
+            dir: default_request_groups_path(),
+            settings,
+        }
+    }
+
+    /// Keep session files in `dir` instead.
+    #[allow(dead_code)]
+    pub fn with_dir(mut self, dir: PathBuf) -> Self {
+        self.dir = dir;
+        self
+    }
+
+    /// Session IDs are UUIDs; anything else cannot name a file.
+    fn session_path(&self, session_id: &str) -> Option<PathBuf> {
+        let valid = !session_id.is_empty()
+            && session_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        valid.then(|| self.dir.join(format!("{}.json", session_id)))
+    }
+
+    /// Decide on a request as part of a group, if it arrived in a burst.
+    ///
+    /// Returns `None` when the request should be sent on its own: grouping
+    /// is off or unsupported, the session is unknown, too few requests
+    /// arrived, or the group could not be sent.
+    pub async fn decide<M: Messenger + ?Sized>(
+        &self,
+        messenger: &M,
+        session_id: &str,
+        message: &PermissionMessage,
+        timeout: Duration,
+    ) -> Option<Result<Decision, HookError>> {
+        if !self.settings.enabled || !messenger.supports_grouping() {
+            return None;
+        }
+        let path = self.session_path(session_id)?;
+        let role = update(&path, |state| {
+            state.join(Member::new(message), &self.settings, now_millis())
+        });
+        match role {
+            Ok(Role::Alone) => None,
+            Ok(Role::Leader(group_id)) => lead(&path, &group_id, messenger, message, timeout).await,
+            Ok(Role::Member(group_id)) => {
+                wait(&path, &group_id, &message.request_id, timeout + GATHER).await
+            }
+            Err(e) => {
+                tracing::warn!("Failed to record request for grouping: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Send the group once others had a moment to join, and share the decisions.
+async fn lead<M: Messenger + ?Sized>(
+    path: &Path,
+    group_id: &str,
+    messenger: &M,
+    message: &PermissionMessage,
+    timeout: Duration,
+) -> Option<Result<Decision, HookError>> {
+    tokio::time::sleep(GATHER).await;
+    let messages = update(path, |state| {
+        let group = state.groups.iter_mut().find(|group| group.id == group_id)?;
+        group.open = false;
+        Some(
+            group
+                .members
+                .iter()
+                .map(|member| member.to_message(&message.hostname))
+                .collect::<Vec<_>>(),
+        )
+    });
+    let messages = match messages {
+        Ok(Some(messages)) if messages.len() > 1 => messages,
+        // Nobody joined, so this is an ordinary request after all
+        _ => {
+            let _ = update(path, |state| {
+                state.take(group_id, &message.request_id, true)
+            });
+            return None;
+        }
+    };
+
+    let decisions = messenger.send_permission_group(&messages, timeout).await;
+    let shared = update(path, |state| {
+        let Some(group) = state.groups.iter_mut().find(|group| group.id == group_id) else {
+            return;
+        };
+        match &decisions {
+            Ok(decisions) => {
+                for (message, decision) in messages.iter().zip(decisions) {
+                    if let Some(member) = group
+                        .members
+                        .iter_mut()
+                        .find(|member| member.request_id == message.request_id)
+                    {
+                        member.allowed = Some(decision.to_behavior() == "allow");
+                    }
+                }
+            }
+            Err(_) => group.failed = true,
+        }
+        state.take(group_id, &message.request_id, true);
+    });
+    if let Err(e) = shared {
+        tracing::warn!("Failed to share grouped decisions: {}", e);
+    }
+
+    match decisions {
+        Ok(decisions) => messages
+            .iter()
+            .position(|sent| sent.request_id == message.request_id)
+            .and_then(|index| decisions.get(index).copied())
+            .map(Ok),
+        Err(e) => {
+            tracing::warn!("Failed to send grouped requests, asking one by one: {}", e);
+            None
+        }
+    }
+}
+
+/// Wait for the group's sender to write this request's decision.
+///
+/// Gives up with a denial once `timeout` has passed, like an unanswered
+/// request.
+async fn wait(
+    path: &Path,
+    group_id: &str,
+    request_id: &str,
+    timeout: Duration,
+) -> Option<Result<Decision, HookError>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let give_up = Instant::now() >= deadline;
+        match update(path, |state| state.take(group_id, request_id, give_up)) {
+            Ok(Taken::Waiting) => {}
+            Ok(Taken::Decided(true)) => return Some(Ok(Decision::Allow)),
+            Ok(Taken::Decided(false)) => return Some(Ok(Decision::Deny)),
+            Ok(Taken::Unavailable) => return None,
+            Err(_) if give_up => return Some(Ok(Decision::Deny)),
+            Err(e) => tracing::debug!("Failed to read grouped decision: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn member(request_id: &str) -> Member {
+        Member::new(&PermissionMessage::new(
+            request_id.to_string(),
+            "Bash".to_string(),
+            "laptop".to_string(),
+            json!({"command": "ls"}),
+        ))
+    }
+
+    #[test]
+    fn test_join_and_take() {
+        let settings = RequestGroupingConfig {
+            enabled: true,
+            threshold: 2,
+            window_seconds: 5,
+        };
+        let mut state = SessionState::default();
+        let now = 1_700_000_000_000;
+
+        assert_eq!(state.join(member("a"), &settings, now), Role::Alone);
+        assert_eq!(state.join(member("b"), &settings, now + 100), Role::Alone);
+        let Role::Leader(group_id) = state.join(member("c"), &settings, now + 200) else {
+            panic!("third request should open a group");
+        };
+        assert_eq!(
+            state.join(member("d"), &settings, now + 300),
+            Role::Member(group_id.clone())
+        );
+
+        assert_eq!(state.take(&group_id, "d", false), Taken::Waiting);
+        state.groups[0].members[1].allowed = Some(true);
+        assert_eq!(state.take(&group_id, "d", false), Taken::Decided(true));
+        assert_eq!(state.take(&group_id, "c", true), Taken::Decided(false));
+        assert!(state.groups.is_empty());
+        assert_eq!(state.take(&group_id, "c", false), Taken::Unavailable);
+
+        // Arrivals outside the window no longer count
+        assert_eq!(state.join(member("e"), &settings, now + 6_000), Role::Alone);
+    }
+}
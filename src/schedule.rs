@@ -0,0 +1,163 @@
+//! Time windows limiting when always-allowed tools are auto-approved.
+//!
+//! A window lists tools, days of the week and a time range. Once any window
+//! applies to a tool, that tool is only auto-approved inside one of its
+//! windows; outside them the request is sent for an interactive decision,
+//! e.g. to always ask at night while an agent runs unattended.
+
+use crate::config::{glob_match, AutoApprovalWindow};
+
+const DAY_NAMES: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Parse `HH:MM` into minutes after midnight (`24:00` is the end of the day).
+pub fn parse_clock(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    match (hours, minutes) {
+        (0..=23, 0..=59) | (24, 0) => Some(hours * 60 + minutes),
+        _ => None,
+    }
+}
+
+/// Parse a UTC offset such as `+09:00`, `-05:00` or `Z` into minutes.
+pub fn parse_offset(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("z") || value.eq_ignore_ascii_case("utc") {
+        return Some(0);
+    }
+    let sign = match value.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let minutes = parse_clock(&value[1..])?;
+    (minutes <= 18 * 60).then_some(sign * i64::from(minutes))
+}
+
+/// Parse day names into a Monday-first set; an empty list means every day.
+pub fn parse_days(days: &[String]) -> Option<[bool; 7]> {
+    if days.is_empty() {
+        return Some([true; 7]);
+    }
+    let mut set = [false; 7];
+    for day in days {
+        let day = day.trim().to_ascii_lowercase();
+        match day.as_str() {
+            "weekdays" => set[..5].fill(true),
+            "weekends" => set[5..].fill(true),
+            _ => {
+                let index = DAY_NAMES.iter().position(|name| day.starts_with(name))?;
+                set[index] = true;
+            }
+        }
+    }
+    Some(set)
+}
+
+/// Check whether a window is open at `now` (Unix seconds).
+///
+/// Windows ending before they start run past midnight and belong to the day
+/// they start on. Windows that cannot be parsed are never open.
+pub fn window_open(window: &AutoApprovalWindow, now: u64) -> bool {
+    let (Some(start), Some(end), Some(days)) = (
+        parse_clock(&window.start),
+        parse_clock(&window.end),
+        parse_days(&window.days),
+    ) else {
+        return false;
+    };
+    let Some(offset) = window.utc_offset.as_deref().map_or(Some(0), parse_offset) else {
+        return false;
+    };
+
+    let local = now as i64 + offset * 60;
+    let day = local.div_euclid(86400);
+    let minute = (local.rem_euclid(86400) / 60) as u32;
+    // 1970-01-01 was a Thursday
+    let weekday = (day + 3).rem_euclid(7) as usize;
+    let yesterday = (weekday + 6) % 7;
+
+    if start <= end {
+        days[weekday] && start <= minute && minute < end
+    } else {
+        (days[weekday] && minute >= start) || (days[yesterday] && minute < end)
+    }
+}
+
+/// Check whether a tool may be auto-approved at `now`.
+///
+/// Tools no window applies to are unrestricted.
+pub fn auto_approval_open(windows: &[AutoApprovalWindow], tool_name: &str, now: u64) -> bool {
+    let mut applicable = windows
+        .iter()
+        .filter(|window| {
+            window.tools.is_empty() || window.tools.iter().any(|p| glob_match(p, tool_name))
+        })
+        .peekable();
+    applicable.peek().is_none() || applicable.any(|window| window_open(window, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01 00:00 UTC, a Monday.
+    const MONDAY: u64 = 1_704_067_200;
+    const HOUR: u64 = 3600;
+
+    fn window(tools: &[&str], days: &[&str], start: &str, end: &str) -> AutoApprovalWindow {
+        AutoApprovalWindow {
+            tools: tools.iter().map(|s| s.to_string()).collect(),
+            days: days.iter().map(|s| s.to_string()).collect(),
+            start: start.to_string(),
+            end: end.to_string(),
+            utc_offset: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_schedule_fields() {
+        assert_eq!(parse_clock("09:30"), Some(570));
+        assert_eq!(parse_clock("24:00"), Some(1440));
+        assert_eq!(parse_clock("25:00"), None);
+        assert_eq!(parse_offset("+09:00"), Some(540));
+        assert_eq!(parse_offset("-05:30"), Some(-330));
+        assert_eq!(parse_offset("09:00"), None);
+        assert_eq!(
+            parse_days(&["weekends".to_string(), "Monday".to_string()]),
+            Some([true, false, false, false, false, true, true])
+        );
+        assert_eq!(parse_days(&["someday".to_string()]), None);
+    }
+
+    #[test]
+    fn test_office_hours_window() {
+        let windows = vec![window(&["Bash"], &["weekdays"], "09:00", "18:00")];
+        assert!(auto_approval_open(&windows, "Bash", MONDAY + 10 * HOUR));
+        assert!(!auto_approval_open(&windows, "Bash", MONDAY + 20 * HOUR));
+        // Saturday morning
+        assert!(!auto_approval_open(
+            &windows,
+            "Bash",
+            MONDAY + (5 * 24 + 10) * HOUR
+        ));
+        // No window applies to Edit
+        assert!(auto_approval_open(&windows, "Edit", MONDAY + 20 * HOUR));
+
+        let mut tokyo = windows[0].clone();
+        tokyo.utc_offset = Some("+09:00".to_string());
+        // 01:00 UTC is 10:00 in Tokyo
+        assert!(window_open(&tokyo, MONDAY + HOUR));
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let night = window(&[], &["fri"], "22:00", "06:00");
+        let friday = MONDAY + 4 * 24 * HOUR;
+        assert!(window_open(&night, friday + 23 * HOUR));
+        assert!(window_open(&night, friday + 29 * HOUR));
+        assert!(!window_open(&night, friday + 5 * HOUR));
+        assert!(!window_open(&night, friday + 12 * HOUR));
+    }
+}
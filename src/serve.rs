@@ -0,0 +1,286 @@
+//! JSON-RPC over stdin/stdout for editors and wrapper scripts.
+//!
+//! `serve --stdio` reads one JSON-RPC 2.0 request per line and writes one
+//! response per line. Requests run concurrently, so `list_pending` answers
+//! while approvals are still waiting for a decision. Methods:
+//!
+//! - `request_approval` with `{tool_name, tool_input, request_id,
+//!   required_approvals, suggestions, timeout_seconds}` (only `tool_name` is
+//!   required) returns `{request_id, decision}`, where `decision` is `allow`,
+//!   `deny` or `always_allow`, plus `suggestion` when one was chosen and
+//!   `block` when "Deny & Block" was pressed.
+//! - `notify` with `{text}` returns `null`.
+//! - `list_pending` returns `[{request_id, tool_name, tool_input, waiting_seconds}]`.
+//! - `request_state` with `{request_id}` returns `{request_id, state,
+//!   transitions}` for any request sent from this machine, where `state` is
+//!   `sent`, `answered`, `timed_out`, `cancelled` or `expired` and each
+//!   transition is `{state, at}` in Unix seconds.
+
+use crate::client::ApprovalClient;
+use crate::config::Config;
+use crate::messenger::{ledger, new_request_id, Decision, PermissionMessage};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Delivery failures and other errors from the messenger layer
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApprovalParams {
+    tool_name: String,
+    #[serde(default = "empty_object")]
+    tool_input: Value,
+    request_id: Option<String>,
+    #[serde(default = "one")]
+    required_approvals: usize,
+    #[serde(default)]
+    suggestions: Vec<String>,
+    timeout_seconds: Option<u64>,
+}
+
+fn empty_object() -> Value {
+    json!({})
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+struct NotifyParams {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StateParams {
+    request_id: String,
+}
+
+/// A `request_approval` call waiting for its decision.
+struct Pending {
+    tool_name: String,
+    tool_input: Value,
+    since: Instant,
+}
+
+/// State shared by the requests of one session.
+struct Server {
+    client: ApprovalClient,
+    pending: Mutex<BTreeMap<String, Pending>>,
+}
+
+impl Server {
+    async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "request_approval" => self.request_approval(parse_params(params)?).await,
+            "notify" => {
+                let params: NotifyParams = parse_params(params)?;
+                self.client
+                    .notify(&params.text)
+                    .await
+                    .map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+                Ok(Value::Null)
+            }
+            "list_pending" => Ok(self.list_pending()),
+            "request_state" => {
+                let params: StateParams = parse_params(params)?;
+                Ok(json!({
+                    "request_id": params.request_id,
+                    "state": ledger::state(&params.request_id),
+                    "transitions": ledger::transitions(&params.request_id),
+                }))
+            }
+            other => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {}", other),
+            )),
+        }
+    }
+
+    async fn request_approval(&self, params: ApprovalParams) -> Result<Value, RpcError> {
+        let request_id = params.request_id.unwrap_or_else(new_request_id);
+        let message = PermissionMessage::new(
+            request_id.clone(),
+            params.tool_name.clone(),
+            self.client.hostname().to_string(),
+            params.tool_input.clone(),
+        )
+        .with_required_approvals(params.required_approvals)
+        .with_suggestions(params.suggestions);
+
+        let client = match params.timeout_seconds {
+            Some(seconds) => self
+                .client
+                .clone()
+                .with_timeout(Duration::from_secs(seconds)),
+            None => self.client.clone(),
+        };
+
+        self.lock().insert(
+            request_id.clone(),
+            Pending {
+                tool_name: params.tool_name,
+                tool_input: params.tool_input,
+                since: Instant::now(),
+            },
+        );
+        let decision = client.request_approval(message).await;
+        self.lock().remove(&request_id);
+
+        let decision = decision.map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+        Ok(decision_result(&request_id, decision))
+    }
+
+    fn list_pending(&self) -> Value {
+        let pending = self.lock();
+        Value::Array(
+            pending
+                .iter()
+                .map(|(request_id, pending)| {
+                    json!({
+                        "request_id": request_id,
+                        "tool_name": pending.tool_name,
+                        "tool_input": pending.tool_input,
+                        "waiting_seconds": pending.since.elapsed().as_secs(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Pending>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn decision_result(request_id: &str, decision: Decision) -> Value {
+    match decision {
+        Decision::Allow => json!({"request_id": request_id, "decision": "allow"}),
+        Decision::Deny => json!({"request_id": request_id, "decision": "deny"}),
+        Decision::DenyAndBlock => {
+            json!({"request_id": request_id, "decision": "deny", "block": true})
+        }
+        Decision::Snooze => {
+            json!({"request_id": request_id, "decision": "deny", "snooze": true})
+        }
+        Decision::AlwaysAllow => json!({"request_id": request_id, "decision": "always_allow"}),
+        Decision::ApplySuggestion(index) => {
+            json!({"request_id": request_id, "decision": "allow", "suggestion": index})
+        }
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": error.code, "message": error.message},
+    })
+}
+
+/// Handle one line of input, returning the response to write, if any.
+async fn handle_line(server: &Server, line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(error_response(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, e.to_string()),
+            ))
+        }
+    };
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return Some(error_response(
+            id.unwrap_or(Value::Null),
+            RpcError::new(INVALID_REQUEST, "Missing method"),
+        ));
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let result = server.call(method, params).await;
+
+    // Requests without an id are JSON-RPC notifications and get no response
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(error) => error_response(id, error),
+    })
+}
+
+/// Serve JSON-RPC requests from stdin until it is closed.
+///
+/// Requests still waiting for a decision at that point are answered before
+/// returning.
+pub async fn run_stdio(config_path: Option<&Path>) -> Result<()> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
+    let server = Arc::new(Server {
+        client: ApprovalClient::new(config)?,
+        pending: Mutex::default(),
+    });
+
+    // One writer keeps concurrent responses from interleaving
+    let (responses, mut outgoing) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(response) = outgoing.recv().await {
+            let line = format!("{}\n", response);
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut requests = JoinSet::new();
+    while let Some(line) = lines.next_line().await.context("Failed to read stdin")? {
+        while requests.try_join_next().is_some() {}
+        if line.trim().is_empty() {
+            continue;
+        }
+        let server = server.clone();
+        let responses = responses.clone();
+        requests.spawn(async move {
+            if let Some(response) = handle_line(&server, &line).await {
+                let _ = responses.send(response);
+            }
+        });
+    }
+
+    while requests.join_next().await.is_some() {}
+    drop(responses);
+    writer.await?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! The hook daemon as a Windows service.
+//!
+//! `daemon --service` is what the Service Control Manager starts; run from
+//! a console it fails at once. Register it under the account that runs
+//! Claude Code, since the daemon uses that account's `.claude` directory:
+//!
+//! ```text
+//! sc.exe create ClaudeCodeTelegram start= auto obj= .\ana password= ... ^
+//!     binPath= "C:\tools\claude-code-telegram.exe daemon --service"
+//! ```
+//!
+//! Stopping the service stops the daemon like Ctrl+C does.
+
+use crate::daemon;
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::watch;
+use windows_service::service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::{define_windows_service, service_dispatcher};
+
+/// Name the service is registered under.
+const SERVICE_NAME: &str = "ClaudeCodeTelegram";
+
+/// `--config` of the service command, for the service thread.
+static CONFIG_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Hand this process to the Service Control Manager until the service stops.
+pub fn run(config_path: Option<&Path>) -> Result<()> {
+    let _ = CONFIG_PATH.set(config_path.map(Path::to_path_buf));
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+        .context("Not started by the Service Control Manager; run `daemon` without --service")
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        tracing::error!("Hook daemon service failed: {:#}", e);
+    }
+}
+
+fn run_service() -> Result<()> {
+    let (stop, mut stopped) = watch::channel(false);
+    let status = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let _ = stop.send(true);
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+    let report = |state, controls_accepted| {
+        status.set_service_status(ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted,
+            exit_code: ServiceExitCode::Win32(0),
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        })
+    };
+
+    report(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+    )?;
+    let config_path = CONFIG_PATH.get().cloned().flatten();
+    let stop_requested = async move {
+        let _ = stopped.wait_for(|stopped| *stopped).await;
+    };
+    let result = tokio::runtime::Runtime::new()?
+        .block_on(daemon::run_until(config_path.as_deref(), stop_requested));
+    report(ServiceState::Stopped, ServiceControlAccept::empty())?;
+    result
+}
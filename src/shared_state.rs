@@ -0,0 +1,364 @@
+//! State shared between hosts through a Postgres database.
+//!
+//! Pending requests, always-allow rules and history normally live in files
+//! in the state directory, so each machine has its own. With
+//! `CCR_STATE_DATABASE_URL` set to a `postgres://` URL, they are kept in
+//! that database instead, so several relay server replicas or hosts see one
+//! set of pending requests, answer each only once, and share their rules
+//! and history. Like `CCR_STATE_DIR`, it is read from the environment (or
+//! `.env`) rather than the config file, since state is opened before and
+//! without a config. Only builds with the `postgres` feature can use it.
+//!
+//! The tables are created on first use:
+//!
+//! - `ccr_documents` holds one JSON document per `kind` and `key`, e.g. a
+//!   ledger entry per request ID;
+//! - `ccr_log` holds history, one JSON line per row in insertion order.
+//!
+//! Documents are rewritten under an advisory lock named after them, which
+//! takes the place of the lock files used next to state files.
+
+use std::io;
+use std::sync::OnceLock;
+
+#[cfg(feature = "postgres")]
+pub use postgres::{Lock, SharedState};
+#[cfg(not(feature = "postgres"))]
+pub use unsupported::{Lock, SharedState};
+
+/// Environment variable naming the database.
+const DATABASE_URL_VAR: &str = "CCR_STATE_DATABASE_URL";
+
+static DATABASE: OnceLock<Option<SharedState>> = OnceLock::new();
+
+/// The shared database, if `CCR_STATE_DATABASE_URL` names one.
+///
+/// Connecting is tried once per process; if it can't be set up, state is
+/// kept in local files and a warning is logged.
+pub fn database() -> Option<&'static SharedState> {
+    DATABASE
+        .get_or_init(|| {
+            let url = std::env::var(DATABASE_URL_VAR)
+                .ok()
+                .filter(|url| !url.trim().is_empty())?;
+            match SharedState::connect(url.trim()) {
+                Ok(database) => Some(database),
+                Err(e) => {
+                    tracing::warn!(
+                        "Shared state database unavailable, using local files: {}",
+                        e
+                    );
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use super::*;
+    use crate::clock::now_secs;
+    use sqlx::pool::PoolConnection;
+    use sqlx::postgres::{PgPool, PgPoolOptions};
+    use sqlx::Postgres;
+    use std::future::Future;
+    use std::time::Duration;
+    use tokio::runtime::Runtime;
+
+    /// Longest wait for a connection before a query fails.
+    const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    const SCHEMA: &[&str] = &[
+        "CREATE TABLE IF NOT EXISTS ccr_documents (
+            kind TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at BIGINT NOT NULL,
+            PRIMARY KEY (kind, key)
+        )",
+        "CREATE TABLE IF NOT EXISTS ccr_log (
+            id BIGSERIAL PRIMARY KEY,
+            log TEXT NOT NULL,
+            line TEXT NOT NULL
+        )",
+        "CREATE INDEX IF NOT EXISTS ccr_log_by_log ON ccr_log (log, id)",
+    ];
+
+    /// A connection pool to the shared database.
+    ///
+    /// Stores are read and written from synchronous code, often inside
+    /// another runtime, so queries run on a runtime of their own and the
+    /// caller waits for their result.
+    pub struct SharedState {
+        pool: PgPool,
+        /// Connections holding locks, kept apart so that lock holders can't
+        /// take every connection they need to read and write
+        locks: PgPool,
+        runtime: Runtime,
+    }
+
+    /// Held while a document is read and rewritten; released when dropped.
+    pub struct Lock {
+        connection: Option<PoolConnection<Postgres>>,
+        name: String,
+        runtime: tokio::runtime::Handle,
+    }
+
+    impl Drop for Lock {
+        fn drop(&mut self) {
+            let Some(mut connection) = self.connection.take() else {
+                return;
+            };
+            let name = std::mem::take(&mut self.name);
+            self.runtime.spawn(async move {
+                let _ = sqlx::query("SELECT pg_advisory_unlock(hashtextextended($1, 0))")
+                    .bind(name)
+                    .execute(&mut *connection)
+                    .await;
+            });
+        }
+    }
+
+    impl SharedState {
+        /// Connect to the database and create the tables if needed.
+        pub(super) fn connect(url: &str) -> io::Result<Self> {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("shared-state")
+                .enable_all()
+                .build()?;
+            let (pool, locks) = {
+                let _entered = runtime.enter();
+                let pool = |max_connections| {
+                    PgPoolOptions::new()
+                        .max_connections(max_connections)
+                        .acquire_timeout(ACQUIRE_TIMEOUT)
+                        .connect_lazy(url)
+                        .map_err(io::Error::other)
+                };
+                (pool(4)?, pool(8)?)
+            };
+            let state = Self {
+                pool,
+                locks,
+                runtime,
+            };
+            let pool = state.pool.clone();
+            let created = state.block(async move {
+                // Concurrent creation can still collide, so take turns
+                let mut tx = pool.begin().await?;
+                sqlx::query("SET LOCAL client_min_messages = warning")
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended('ccr_schema', 0))")
+                    .execute(&mut *tx)
+                    .await?;
+                for statement in SCHEMA {
+                    sqlx::query(statement).execute(&mut *tx).await?;
+                }
+                tx.commit().await
+            });
+            match created {
+                Ok(()) => Ok(state),
+                Err(e) => {
+                    // The caller may be on another runtime, where this one
+                    // can't be dropped by waiting for it
+                    let Self {
+                        pool,
+                        locks,
+                        runtime,
+                    } = state;
+                    drop((pool, locks));
+                    runtime.shutdown_background();
+                    Err(e)
+                }
+            }
+        }
+
+        /// Run a query on the database's runtime and wait for it.
+        fn block<T, F>(&self, query: F) -> io::Result<T>
+        where
+            T: Send + 'static,
+            F: Future<Output = Result<T, sqlx::Error>> + Send + 'static,
+        {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            self.runtime.spawn(async move {
+                let _ = sender.send(query.await);
+            });
+            receiver
+                .recv()
+                .map_err(|_| io::Error::other("shared state query was dropped"))?
+                .map_err(io::Error::other)
+        }
+
+        /// Whether the database answers.
+        pub fn ping(&self) -> io::Result<()> {
+            let pool = self.pool.clone();
+            self.block(async move { sqlx::query("SELECT 1").execute(&pool).await.map(drop) })
+        }
+
+        /// A document, if it exists.
+        pub fn get(&self, kind: &str, key: &str) -> io::Result<Option<String>> {
+            let (pool, kind, key) = (self.pool.clone(), kind.to_string(), key.to_string());
+            self.block(async move {
+                sqlx::query_scalar("SELECT value FROM ccr_documents WHERE kind = $1 AND key = $2")
+                    .bind(kind)
+                    .bind(key)
+                    .fetch_optional(&pool)
+                    .await
+            })
+        }
+
+        /// Create or replace a document.
+        pub fn put(&self, kind: &str, key: &str, value: &str) -> io::Result<()> {
+            let (pool, kind, key, value) = (
+                self.pool.clone(),
+                kind.to_string(),
+                key.to_string(),
+                value.to_string(),
+            );
+            self.block(async move {
+                sqlx::query(
+                    "INSERT INTO ccr_documents (kind, key, value, updated_at)
+                     VALUES ($1, $2, $3, $4)
+                     ON CONFLICT (kind, key)
+                     DO UPDATE SET value = EXCLUDED.value, updated_at = EXCLUDED.updated_at",
+                )
+                .bind(kind)
+                .bind(key)
+                .bind(value)
+                .bind(now_secs() as i64)
+                .execute(&pool)
+                .await
+                .map(drop)
+            })
+        }
+
+        /// Every document of a kind, by key.
+        pub fn list(&self, kind: &str) -> io::Result<Vec<(String, String)>> {
+            let (pool, kind) = (self.pool.clone(), kind.to_string());
+            self.block(async move {
+                sqlx::query_as("SELECT key, value FROM ccr_documents WHERE kind = $1 ORDER BY key")
+                    .bind(kind)
+                    .fetch_all(&pool)
+                    .await
+            })
+        }
+
+        /// Remove documents of a kind not written for `max_age`.
+        pub fn prune(&self, kind: &str, max_age: Duration) -> io::Result<()> {
+            let (pool, kind) = (self.pool.clone(), kind.to_string());
+            let cutoff = now_secs().saturating_sub(max_age.as_secs()) as i64;
+            self.block(async move {
+                sqlx::query("DELETE FROM ccr_documents WHERE kind = $1 AND updated_at < $2")
+                    .bind(kind)
+                    .bind(cutoff)
+                    .execute(&pool)
+                    .await
+                    .map(drop)
+            })
+        }
+
+        /// Lock a document against other processes rewriting it, waiting
+        /// while one does.
+        pub fn lock(&self, kind: &str, key: &str) -> io::Result<Lock> {
+            let pool = self.locks.clone();
+            let name = format!("{}/{}", kind, key);
+            let lock_name = name.clone();
+            let connection = self.block(async move {
+                let mut connection = pool.acquire().await?;
+                sqlx::query("SELECT pg_advisory_lock(hashtextextended($1, 0))")
+                    .bind(lock_name)
+                    .execute(&mut *connection)
+                    .await?;
+                Ok(connection)
+            })?;
+            Ok(Lock {
+                connection: Some(connection),
+                name,
+                runtime: self.runtime.handle().clone(),
+            })
+        }
+
+        /// Add a line to the end of a log.
+        pub fn append(&self, log: &str, line: &str) -> io::Result<()> {
+            let (pool, log, line) = (self.pool.clone(), log.to_string(), line.to_string());
+            self.block(async move {
+                sqlx::query("INSERT INTO ccr_log (log, line) VALUES ($1, $2)")
+                    .bind(log)
+                    .bind(line)
+                    .execute(&pool)
+                    .await
+                    .map(drop)
+            })
+        }
+
+        /// Lines of a log added after the one numbered `after`, oldest first,
+        /// with their numbers.
+        pub fn lines_after(&self, log: &str, after: i64) -> io::Result<Vec<(i64, String)>> {
+            let (pool, log) = (self.pool.clone(), log.to_string());
+            self.block(async move {
+                sqlx::query_as(
+                    "SELECT id, line FROM ccr_log WHERE log = $1 AND id > $2 ORDER BY id",
+                )
+                .bind(log)
+                .bind(after)
+                .fetch_all(&pool)
+                .await
+            })
+        }
+    }
+}
+
+#[cfg(not(feature = "postgres"))]
+mod unsupported {
+    use super::*;
+    use std::time::Duration;
+
+    /// Stands in for the shared database in builds without it; never exists.
+    pub enum SharedState {}
+
+    /// Stands in for a document lock in builds without a shared database.
+    pub enum Lock {}
+
+    impl SharedState {
+        pub(super) fn connect(_url: &str) -> io::Result<Self> {
+            Err(io::Error::other(
+                "not supported (compile with --features postgres)",
+            ))
+        }
+
+        pub fn ping(&self) -> io::Result<()> {
+            match *self {}
+        }
+
+        pub fn get(&self, _kind: &str, _key: &str) -> io::Result<Option<String>> {
+            match *self {}
+        }
+
+        pub fn put(&self, _kind: &str, _key: &str, _value: &str) -> io::Result<()> {
+            match *self {}
+        }
+
+        pub fn list(&self, _kind: &str) -> io::Result<Vec<(String, String)>> {
+            match *self {}
+        }
+
+        pub fn prune(&self, _kind: &str, _max_age: Duration) -> io::Result<()> {
+            match *self {}
+        }
+
+        pub fn lock(&self, _kind: &str, _key: &str) -> io::Result<Lock> {
+            match *self {}
+        }
+
+        pub fn append(&self, _log: &str, _line: &str) -> io::Result<()> {
+            match *self {}
+        }
+
+        pub fn lines_after(&self, _log: &str, _after: i64) -> io::Result<Vec<(i64, String)>> {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! End-to-end simulation of Claude Code hook events.
+//!
+//! `simulate` synthesizes realistic hook input and feeds it through the same
+//! handlers Claude Code invokes, so a setup can be verified without a session.
+
+use crate::messenger::new_request_id;
+use crate::{hook_handler, notification_handler, stop_handler};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Current directory as a string, used as the simulated project.
+fn current_dir() -> String {
+    std::env::current_dir()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Build `tool_input` for a tool the way Claude Code would send it.
+fn tool_input(tool: &str, command: Option<&str>, file_path: Option<&str>) -> Value {
+    let file_path = file_path.unwrap_or("src/main.rs");
+    match tool {
+        "Bash" => json!({
+            "command": command.unwrap_or("ls -la"),
+            "description": "Simulated command",
+        }),
+        "Edit" => json!({
+            "file_path": file_path,
+            "old_string": "println!(\"Hello\");",
+            "new_string": "println!(\"Hello, world!\");",
+        }),
+        "Write" => json!({
+            "file_path": file_path,
+            "content": "fn main() {}\n",
+        }),
+        _ => json!({}),
+    }
+}
+
+/// Hook input for a permission request.
+fn permission_input(tool: &str, command: Option<&str>, file_path: Option<&str>) -> Value {
+    json!({
+        "hook_event_name": "PermissionRequest",
+        "session_id": format!("simulate-{}", new_request_id()),
+        "cwd": current_dir(),
+        "tool_name": tool,
+        "tool_input": tool_input(tool, command, file_path),
+    })
+}
+
+/// Transcript lines for a short finished session ending with `message`.
+fn transcript(message: &str) -> String {
+    [
+        json!({
+            "type": "user",
+            "timestamp": "2025-01-31T12:00:00.000Z",
+            "message": {"role": "user", "content": "Run the test suite and fix any failures"},
+        }),
+        json!({
+            "type": "assistant",
+            "timestamp": "2025-01-31T12:05:00.000Z",
+            "message": {"role": "assistant", "content": [{"type": "text", "text": message}]},
+        }),
+    ]
+    .iter()
+    .map(|line| line.to_string() + "\n")
+    .collect()
+}
+
+/// Simulate a PermissionRequest hook and print the hook output.
+pub async fn permission(
+    config_path: Option<&Path>,
+    tool: &str,
+    command: Option<&str>,
+    file_path: Option<&str>,
+) -> Result<()> {
+    let input = permission_input(tool, command, file_path);
+    hook_handler::run_with_input(config_path, &input.to_string()).await?;
+    Ok(())
+}
+
+/// Simulate a Stop hook for a session whose last message is `message`.
+pub async fn stop(config_path: Option<&Path>, message: &str) -> Result<()> {
+    let session_id = format!("simulate-{}", new_request_id());
+    let transcript_path: PathBuf = std::env::temp_dir().join(format!("{}.jsonl", session_id));
+    fs::write(&transcript_path, transcript(message))
+        .with_context(|| format!("Failed to write {}", transcript_path.display()))?;
+
+    let input = json!({
+        "session_id": session_id,
+        "transcript_path": transcript_path,
+        "cwd": current_dir(),
+        "stop_hook_active": false,
+    });
+    let result = stop_handler::run_with_input(config_path, &input.to_string()).await;
+    let _ = fs::remove_file(&transcript_path);
+    result?;
+    Ok(())
+}
+
+/// Simulate a Notification hook of the given type.
+pub async fn notification(
+    config_path: Option<&Path>,
+    notification_type: &str,
+    message: &str,
+) -> Result<()> {
+    let input = json!({
+        "notification_type": notification_type,
+        "message": message,
+        "session_id": format!("simulate-{}", new_request_id()),
+        "cwd": current_dir(),
+    });
+    notification_handler::run_with_input(config_path, &input.to_string()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_input() {
+        let input = permission_input("Bash", Some("rm -rf /tmp/x"), None);
+        assert_eq!(input["tool_name"], "Bash");
+        assert_eq!(input["tool_input"]["command"], "rm -rf /tmp/x");
+
+        let input = permission_input("Edit", None, Some("lib.rs"));
+        assert_eq!(input["tool_input"]["file_path"], "lib.rs");
+    }
+
+    #[test]
+    fn test_transcript_is_valid_jsonl() {
+        let text = transcript("All tests pass.");
+        let lines: Vec<Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["message"]["content"][0]["text"], "All tests pass.");
+    }
+}
@@ -0,0 +1,94 @@
+//! Snoozed permission requests.
+//!
+//! "⏰ Snooze" denies a request for now without stopping Claude, telling it
+//! to leave the call for later. A detached `remind-snoozed` process then
+//! reminds the user after [`SNOOZE_MINUTES`], so they can have Claude retry
+//! the call once they are ready to decide, instead of the request silently
+//! timing out.
+
+use crate::config::Config;
+use crate::error::HookError;
+use crate::messenger::{dry_run, notify_primary};
+use crate::tool_timer::project_name;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// How long a snoozed request waits before the reminder.
+pub const SNOOZE_MINUTES: u64 = 15;
+
+/// What Claude is told when a request is snoozed.
+pub fn snooze_message() -> String {
+    format!(
+        "The user snoozed this request for {} minutes. Don't retry it now; carry on \
+         with other work or wait, and try it again when the user asks.",
+        SNOOZE_MINUTES
+    )
+}
+
+/// Format the reminder sent once the snooze is over.
+fn format_reminder(hostname: &str, label: &str, cwd: &str) -> String {
+    let mut lines = vec![
+        "⏰ **Snoozed Request**".to_string(),
+        format!(
+            "{} on host {} was snoozed {} min ago",
+            label, hostname, SNOOZE_MINUTES
+        ),
+    ];
+    if !cwd.is_empty() {
+        lines.push(format!("📁 **Project:** {}", project_name(cwd)));
+    }
+    lines.push("Ask Claude to try it again when you're ready to decide.".to_string());
+    lines.join("\n")
+}
+
+/// Spawn a detached `remind-snoozed` process so the hook can return now.
+pub fn spawn_reminder(label: &str, cwd: &str, config_path: Option<&Path>) -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut command = Command::new(exe);
+    if let Some(path) = config_path {
+        command.arg("--config").arg(path);
+    }
+    if dry_run() {
+        command.arg("--dry-run");
+    }
+    command
+        .arg("remind-snoozed")
+        .arg("--label")
+        .arg(label)
+        .arg("--cwd")
+        .arg(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// Wait out the snooze, then remind the user of the request.
+pub async fn run_reminder(
+    label: &str,
+    cwd: &str,
+    config_path: Option<&Path>,
+) -> Result<(), HookError> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?.for_project(cwd);
+    tokio::time::sleep(Duration::from_secs(SNOOZE_MINUTES * 60)).await;
+    let text = format_reminder(&config.host_label, label, cwd);
+    notify_primary(&config, &text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_reminder() {
+        let text = format_reminder("laptop", "Bash `cargo publish`", "/home/user/myapp");
+        assert!(text.contains("Bash `cargo publish` on host laptop was snoozed 15 min ago"));
+        assert!(text.contains("📁 **Project:** myapp"));
+
+        let text = format_reminder("laptop", "Bash `ls`", "");
+        assert!(!text.contains("Project"));
+    }
+}
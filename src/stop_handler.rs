@@ -3,17 +3,23 @@
 //! Handles Stop hook events by sending notifications via configured messengers
 //! when Claude Code finishes a task.
 
-use crate::config::Config;
+use crate::config::{glob_match, Config, StopNotificationConfig, TaskPromptSource, WebhookEvent};
 use crate::error::StopError;
-use crate::messenger::telegram::TelegramMessenger;
-use crate::messenger::Messenger;
-use serde::Deserialize;
+use crate::messenger::{enabled_messengers, notify_primary};
+use crate::summary;
+use crate::webhooks;
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+use std::env;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::task::JoinSet;
 
-#[cfg(feature = "discord")]
-use crate::messenger::discord::DiscordMessenger;
+/// Set to the session ID in sessions started with `/run`.
+pub const REMOTE_RUN_ENV: &str = "CCR_REMOTE_RUN";
 
 /// Claude Code stop hook input.
 #[derive(Debug, Deserialize)]
@@ -31,7 +37,6 @@ pub struct StopInput {
 /// Stop event with parsed data.
 #[derive(Debug)]
 pub struct StopEvent {
-    #[allow(dead_code)]
     pub session_id: String,
     pub transcript_path: PathBuf,
     pub cwd: PathBuf,
@@ -50,35 +55,183 @@ impl StopEvent {
     }
 
     /// Get the last assistant message from the transcript.
+    ///
+    /// Reads the transcript backwards so only the tail of large transcripts is
+    /// parsed. Entries holding only thinking or tool_use blocks are skipped.
     pub fn get_last_assistant_message(&self) -> Option<String> {
-        if self.transcript_path.as_os_str().is_empty() {
+        last_assistant_message(&self.transcript_path)
+    }
+
+    /// Get the user prompt that describes the session's task.
+    ///
+    /// Skips tool results, interrupt markers, slash-command wrappers and
+    /// meta entries so only prompts typed by the user are considered. The
+    /// latest prompt is found by reading the transcript backwards.
+    pub fn get_user_prompt(&self, source: TaskPromptSource) -> Option<String> {
+        if source == TaskPromptSource::Off || self.transcript_path.as_os_str().is_empty() {
             return None;
         }
 
-        if !self.transcript_path.exists() {
+        let file = File::open(&self.transcript_path).ok()?;
+        if source == TaskPromptSource::First {
+            BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .find_map(|line| typed_prompt(&line))
+        } else {
+            ReverseLines::new(file).find_map(|line| typed_prompt(&line))
+        }
+    }
+
+    /// Detect whether the session ended on an error.
+    ///
+    /// Looks for API errors, failed tool calls, user interrupts, and error
+    /// results (e.g. max-turn aborts). An error only counts if no regular
+    /// assistant reply follows it, since Claude often recovers from failures
+    /// mid-session, so the transcript is read backwards until either is found.
+    /// Returns a snippet describing the failure.
+    pub fn detect_session_error(&self) -> Option<String> {
+        if self.transcript_path.as_os_str().is_empty() {
             return None;
         }
 
         let file = File::open(&self.transcript_path).ok()?;
-        let reader = BufReader::new(file);
 
-        let mut last_message: Option<String> = None;
+        for line in ReverseLines::new(file) {
+            let Ok(entry) = serde_json::from_str::<TranscriptEntry>(&line) else {
+                continue;
+            };
 
-        for line in reader.lines().map_while(Result::ok) {
-            if let Ok(entry) = serde_json::from_str::<TranscriptEntry>(&line) {
-                if entry.entry_type == "assistant" {
-                    if let Some(message) = entry.message {
-                        for block in message.content {
-                            if let ContentBlock::Text { text } = block {
-                                last_message = Some(text);
+            match entry.role() {
+                "assistant" => {
+                    let is_api_error = entry.is_api_error_message;
+                    let text = entry
+                        .into_blocks()
+                        .into_iter()
+                        .filter_map(|block| match block {
+                            ContentBlock::Text { text } => Some(text),
+                            _ => None,
+                        })
+                        .next_back();
+
+                    if is_api_error {
+                        return Some(text.unwrap_or_else(|| "API error".to_string()));
+                    } else if text.is_some() {
+                        return None;
+                    }
+                }
+                "user" => {
+                    let error = entry
+                        .into_blocks()
+                        .into_iter()
+                        .filter_map(|block| match block {
+                            ContentBlock::ToolResult {
+                                content,
+                                is_error: true,
+                            } => Some(tool_result_text(&content)),
+                            ContentBlock::Text { text }
+                                if text.starts_with("[Request interrupted by user") =>
+                            {
+                                Some("Interrupted by user".to_string())
                             }
-                        }
+                            _ => None,
+                        })
+                        .next_back();
+                    if error.is_some() {
+                        return error;
+                    }
+                }
+                "result" => {
+                    if let Some(subtype) = entry.subtype.filter(|s| s.starts_with("error")) {
+                        return Some(format!("Session aborted ({})", subtype));
                     }
                 }
+                "system" if entry.level.as_deref() == Some("error") => {
+                    return Some(
+                        entry
+                            .content
+                            .as_ref()
+                            .map(tool_result_text)
+                            .unwrap_or_else(|| "System error".to_string()),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Get the conversation text at the end of the transcript.
+    ///
+    /// Renders user and assistant text blocks as `User:`/`Assistant:` lines and
+    /// keeps at most `max_chars` trailing characters. Only as much of the
+    /// transcript as needed is read, starting from the end.
+    pub fn get_transcript_tail(&self, max_chars: usize) -> Option<String> {
+        if self.transcript_path.as_os_str().is_empty() {
+            return None;
+        }
+
+        let file = File::open(&self.transcript_path).ok()?;
+
+        // Rendered lines, newest first
+        let mut lines: Vec<String> = Vec::new();
+        let mut char_count = 0;
+
+        for line in ReverseLines::new(file) {
+            if char_count > max_chars {
+                break;
+            }
+            let Ok(entry) = serde_json::from_str::<TranscriptEntry>(&line) else {
+                continue;
+            };
+            let role = match entry.role() {
+                "user" if !entry.is_meta => "User",
+                "assistant" => "Assistant",
+                _ => continue,
+            };
+
+            for block in entry.into_blocks().into_iter().rev() {
+                if let ContentBlock::Text { text } = block {
+                    let rendered = format!("{}: {}", role, text.trim());
+                    char_count += rendered.chars().count() + 1;
+                    lines.push(rendered);
+                }
             }
         }
 
-        last_message
+        if lines.is_empty() {
+            return None;
+        }
+
+        lines.reverse();
+        let text = lines.join("\n");
+        let char_count = text.chars().count();
+        Some(
+            text.chars()
+                .skip(char_count.saturating_sub(max_chars))
+                .collect(),
+        )
+    }
+
+    /// Get the session duration from the first and last transcript timestamps.
+    ///
+    /// The first timestamp is read from the start and the last from the end,
+    /// so the bulk of the transcript is never parsed.
+    pub fn get_session_duration(&self) -> Option<Duration> {
+        if self.transcript_path.as_os_str().is_empty() {
+            return None;
+        }
+
+        let file = File::open(&self.transcript_path).ok()?;
+        let first = BufReader::new(file.try_clone().ok()?)
+            .lines()
+            .map_while(Result::ok)
+            .find_map(|line| entry_timestamp(&line))?;
+        let last = ReverseLines::new(file).find_map(|line| entry_timestamp(&line))?;
+
+        let elapsed = last - first;
+        Some(Duration::from_secs(elapsed.max(0) as u64))
     }
 
     /// Get the project name from the current working directory.
@@ -90,55 +243,432 @@ impl StopEvent {
     }
 }
 
+/// Get the last assistant message from a transcript file.
+///
+/// See [`StopEvent::get_last_assistant_message`]; permission requests use it
+/// to show what Claude said before asking.
+pub fn last_assistant_message(transcript_path: &Path) -> Option<String> {
+    if transcript_path.as_os_str().is_empty() {
+        return None;
+    }
+
+    let file = File::open(transcript_path).ok()?;
+
+    for line in ReverseLines::new(file) {
+        let Ok(entry) = serde_json::from_str::<TranscriptEntry>(&line) else {
+            continue;
+        };
+        if entry.role() != "assistant" {
+            continue;
+        }
+
+        let last_text = entry
+            .into_blocks()
+            .into_iter()
+            .rev()
+            .find_map(|block| match block {
+                ContentBlock::Text { text } if !text.trim().is_empty() => Some(text),
+                _ => None,
+            });
+        if last_text.is_some() {
+            return last_text;
+        }
+    }
+
+    None
+}
+
+/// Minimal transcript entry used for timing; matches any entry type.
+#[derive(Debug, Deserialize)]
+struct TimestampEntry {
+    #[serde(default)]
+    timestamp: Option<String>,
+}
+
+/// Timestamp of a transcript line, in Unix seconds.
+fn entry_timestamp(line: &str) -> Option<i64> {
+    let entry = serde_json::from_str::<TimestampEntry>(line).ok()?;
+    entry.timestamp.as_deref().and_then(parse_timestamp)
+}
+
+/// Text of a prompt typed by the user, if the transcript line holds one.
+fn typed_prompt(line: &str) -> Option<String> {
+    let entry = serde_json::from_str::<TranscriptEntry>(line).ok()?;
+    if entry.role() != "user" || entry.is_meta {
+        return None;
+    }
+
+    let text = entry
+        .into_blocks()
+        .into_iter()
+        .filter_map(|block| match block {
+            ContentBlock::Text { text } => Some(text),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let text = text.trim();
+
+    if text.is_empty() || text.starts_with('<') || text.starts_with("[Request interrupted") {
+        return None;
+    }
+    Some(text.to_string())
+}
+
+/// Parse an RFC 3339 timestamp (e.g. `2025-01-31T12:34:56.789Z`) into Unix seconds.
+fn parse_timestamp(s: &str) -> Option<i64> {
+    let (date, time) = s.split_once('T')?;
+
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+
+    // Split off the UTC offset ("Z", "+09:00", "-05:00")
+    let (clock, offset_secs) = if let Some(clock) = time.strip_suffix('Z') {
+        (clock, 0)
+    } else if let Some(pos) = time.rfind(['+', '-']) {
+        let (clock, offset) = time.split_at(pos);
+        let sign = if offset.starts_with('-') { -1 } else { 1 };
+        let (oh, om) = offset[1..].split_once(':')?;
+        let (oh, om) = (oh.parse::<i64>().ok()?, om.parse::<i64>().ok()?);
+        if !(0..24).contains(&oh) || !(0..60).contains(&om) {
+            return None;
+        }
+        (clock, sign * (oh * 3600 + om * 60))
+    } else {
+        (time, 0)
+    };
+
+    let mut clock_parts = clock.splitn(3, ':');
+    let hour: i64 = clock_parts.next()?.parse().ok()?;
+    let minute: i64 = clock_parts.next()?.parse().ok()?;
+    let second: i64 = clock_parts.next()?.split('.').next()?.parse().ok()?;
+
+    // Out-of-range fields would overflow the arithmetic below
+    let in_range = (0..=9999).contains(&year)
+        && (1..=12).contains(&month)
+        && (1..=31).contains(&day)
+        && (0..24).contains(&hour)
+        && (0..60).contains(&minute)
+        && (0..=60).contains(&second);
+    if !in_range {
+        return None;
+    }
+
+    // Days since the Unix epoch (civil-from-days inverse, proleptic Gregorian)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    Some(days * 86400 + hour * 3600 + minute * 60 + second - offset_secs)
+}
+
+/// Check whether a stop event passes the configured notification filters.
+fn should_notify(filters: &StopNotificationConfig, event: &StopEvent) -> bool {
+    if filters.min_duration_seconds > 0 {
+        // Sessions without timing information are always reported
+        if let Some(duration) = event.get_session_duration() {
+            if duration.as_secs() < filters.min_duration_seconds {
+                return false;
+            }
+        }
+    }
+
+    if !filters.projects.is_empty() {
+        let project_name = event.get_project_name();
+        let cwd = event.cwd.to_string_lossy();
+        let matched = filters
+            .projects
+            .iter()
+            .any(|pattern| glob_match(pattern, &project_name) || glob_match(pattern, &cwd));
+        if !matched {
+            return false;
+        }
+    }
+
+    true
+}
+
 /// Transcript entry structure.
+///
+/// Accepts both the Claude Code layout (`{"type": "assistant", "message": {...}}`)
+/// and flatter layouts where `role` and `content` sit on the entry itself.
 #[derive(Debug, Deserialize)]
 struct TranscriptEntry {
-    #[serde(rename = "type")]
+    #[serde(default, rename = "type")]
     entry_type: String,
     #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
     message: Option<TranscriptMessage>,
+    #[serde(default, rename = "isApiErrorMessage")]
+    is_api_error_message: bool,
+    #[serde(default, rename = "isMeta")]
+    is_meta: bool,
+    #[serde(default)]
+    subtype: Option<String>,
+    #[serde(default)]
+    level: Option<String>,
+    #[serde(default)]
+    content: Option<Value>,
+}
+
+impl TranscriptEntry {
+    /// Get the speaker of this entry ("user", "assistant") or its entry type.
+    fn role(&self) -> &str {
+        match self.entry_type.as_str() {
+            "user" | "assistant" => &self.entry_type,
+            _ => self
+                .message
+                .as_ref()
+                .and_then(|m| m.role.as_deref())
+                .or(self.role.as_deref())
+                .unwrap_or(&self.entry_type),
+        }
+    }
+
+    /// Take the content blocks from the nested message or the entry itself.
+    fn into_blocks(self) -> Vec<ContentBlock> {
+        match self.message {
+            Some(message) => message.content,
+            None => self.content.map(parse_content).unwrap_or_default(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct TranscriptMessage {
     #[serde(default)]
+    role: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_content")]
     content: Vec<ContentBlock>,
 }
 
+/// Accept message content as either a plain string or a list of blocks.
+fn deserialize_content<'de, D>(deserializer: D) -> Result<Vec<ContentBlock>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(parse_content(Value::deserialize(deserializer)?))
+}
+
+/// Convert raw content into blocks, tolerating unknown or malformed blocks.
+fn parse_content(content: Value) -> Vec<ContentBlock> {
+    match content {
+        Value::String(text) => vec![ContentBlock::Text { text }],
+        Value::Array(blocks) => blocks
+            .into_iter()
+            .map(|block| serde_json::from_value(block).unwrap_or(ContentBlock::Other))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Iterator over the lines of a file from last to first.
+///
+/// Reads fixed-size chunks from the end so large transcripts don't need to be
+/// read in full when only recent entries are of interest.
+pub(crate) struct ReverseLines {
+    file: File,
+    /// Bytes of the file not yet loaded into `buffer`
+    remaining: u64,
+    /// Loaded bytes that have not been returned as lines yet
+    buffer: Vec<u8>,
+}
+
+impl ReverseLines {
+    const CHUNK_SIZE: u64 = 64 * 1024;
+
+    pub(crate) fn new(file: File) -> Self {
+        let remaining = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Self {
+            file,
+            remaining,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Prepend the previous chunk of the file to the buffer.
+    fn load_chunk(&mut self) -> io::Result<()> {
+        let size = self.remaining.min(Self::CHUNK_SIZE);
+        self.remaining -= size;
+
+        let mut chunk = vec![0; size as usize];
+        self.file.seek(SeekFrom::Start(self.remaining))?;
+        self.file.read_exact(&mut chunk)?;
+
+        chunk.append(&mut self.buffer);
+        self.buffer = chunk;
+        Ok(())
+    }
+}
+
+impl Iterator for ReverseLines {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            // Ignore the trailing newline of the buffer when searching
+            let search_end = self.buffer.len().saturating_sub(1);
+            if let Some(pos) = self.buffer[..search_end].iter().rposition(|&b| b == b'\n') {
+                let line = self.buffer.split_off(pos + 1);
+                self.buffer.truncate(pos + 1);
+                return Some(String::from_utf8_lossy(&line).trim_end().to_string());
+            }
+
+            if self.remaining == 0 {
+                if self.buffer.is_empty() {
+                    return None;
+                }
+                let line = std::mem::take(&mut self.buffer);
+                return Some(String::from_utf8_lossy(&line).trim_end().to_string());
+            }
+
+            self.load_chunk().ok()?;
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 enum ContentBlock {
     #[serde(rename = "text")]
-    Text { text: String },
+    Text {
+        #[serde(default)]
+        text: String,
+    },
+    #[serde(rename = "tool_result")]
+    ToolResult {
+        #[serde(default)]
+        content: Value,
+        #[serde(default)]
+        is_error: bool,
+    },
     #[serde(other)]
     Other,
 }
 
+/// Flatten tool result content (a string or a list of text blocks) into text.
+fn tool_result_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => "Tool call failed".to_string(),
+    }
+}
+
+/// Truncate text to a maximum number of characters, adding an ellipsis.
+fn truncate(text: &str, max_chars: usize) -> String {
+    let truncated: String = text.chars().take(max_chars).collect();
+    if text.chars().count() > max_chars {
+        format!("{}...", truncated)
+    } else {
+        truncated
+    }
+}
+
 /// Format job completion message.
-fn format_completion_message(config: &Config, event: &StopEvent) -> String {
+///
+/// Uses the LLM summary when one is provided, otherwise the last assistant message.
+fn format_completion_message(config: &Config, event: &StopEvent, summary: Option<&str>) -> String {
     let project_name = event.get_project_name();
+    let error = event.detect_session_error();
+
+    let title = if error.is_some() {
+        "❌ **Session ended with errors**"
+    } else {
+        "✅ **Job Completed**"
+    };
 
     let mut lines = vec![
-        "✅ **Job Completed**".to_string(),
-        format!("🖥️ **Host:** {}", config.hostname),
+        title.to_string(),
+        format!("🖥️ **Host:** {}", config.host_label),
         format!("📁 **Project:** {}", project_name),
     ];
 
-    // Try to get last assistant message for summary
-    if let Some(last_message) = event.get_last_assistant_message() {
-        let truncated: String = last_message.chars().take(300).collect();
-        let summary = if last_message.len() > 300 {
-            format!("{}...", truncated)
-        } else {
-            truncated
-        };
+    if let Some(prompt) = event.get_user_prompt(config.stop_notifications.task_prompt) {
+        lines.push(format!("📝 **Task:** {}", truncate(&prompt, 200)));
+    }
+
+    if let Some(error) = error {
         lines.push(String::new());
-        lines.push(format!("**Summary:**\n{}", summary));
+        lines.push(format!("**Error:**\n```\n{}\n```", truncate(&error, 300)));
+    } else if let Some(summary) = summary {
+        lines.push(String::new());
+        lines.push(format!("**Summary:**\n{}", truncate(summary, 600)));
+    } else if let Some(last_message) = event.get_last_assistant_message() {
+        // Use the last assistant message as a summary
+        lines.push(String::new());
+        lines.push(format!("**Summary:**\n{}", truncate(&last_message, 300)));
+    }
+
+    if let Some(hint) = format_resume_hint(event) {
+        lines.push(String::new());
+        lines.push(hint);
     }
 
     lines.join("\n")
 }
 
+/// Format a copy-paste command for resuming the session from a terminal.
+fn format_resume_hint(event: &StopEvent) -> Option<String> {
+    if event.session_id.is_empty() {
+        return None;
+    }
+
+    let resume = format!("claude --resume {}", event.session_id);
+    let command = if event.cwd.as_os_str().is_empty() {
+        resume
+    } else {
+        format!(
+            "cd {} && {}",
+            shell_quote(&event.cwd.to_string_lossy()),
+            resume
+        )
+    };
+
+    Some(format!("▶️ **Resume:**\n```\n{}\n```", command))
+}
+
+/// Quote a string for POSIX shells if it contains special characters.
+fn shell_quote(s: &str) -> String {
+    let safe = s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-~+".contains(c));
+    if safe && !s.is_empty() {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+/// Generate an LLM summary of the session if enabled.
+///
+/// Failures are logged and fall back to the last assistant message.
+async fn generate_summary(config: &Config, event: &StopEvent) -> Option<String> {
+    if !config.llm_summary.enabled {
+        return None;
+    }
+
+    let tail = event.get_transcript_tail(config.llm_summary.max_transcript_chars)?;
+    match summary::summarize(&config.llm_summary, &tail).await {
+        Ok(summary) => Some(summary),
+        Err(e) => {
+            tracing::warn!("Failed to generate session summary: {}", e);
+            None
+        }
+    }
+}
+
 /// Send job completion notification via configured messenger.
 pub async fn send_notification(config: &Config, event: &StopEvent) -> Result<(), StopError> {
     // Skip if this is a continuation from a stop hook to prevent loops
@@ -146,70 +676,105 @@ pub async fn send_notification(config: &Config, event: &StopEvent) -> Result<(),
         return Ok(());
     }
 
-    let text = format_completion_message(config, event);
-
-    // Try Discord if configured as primary
-    #[cfg(feature = "discord")]
-    if config.primary_messenger == "discord" {
-        if let Some(ref discord_config) = config.discord {
-            if discord_config.enabled {
-                let messenger =
-                    DiscordMessenger::new(&discord_config.bot_token, discord_config.user_id);
-                messenger.send_notification(&text).await.map_err(|e| {
-                    StopError::TelegramError(teloxide::RequestError::Api(
-                        teloxide::ApiError::Unknown(e.to_string()),
-                    ))
-                })?;
-                return Ok(());
-            }
-        }
+    // Webhooks hear about every session; the filters only apply to chat
+    emit_session_completed(config, event).await;
+    if !should_notify(&config.stop_notifications, event) {
+        return Ok(());
     }
 
-    // Try Telegram if configured
-    if let Some(ref telegram_config) = config.telegram {
-        let messenger = TelegramMessenger::new(&telegram_config.bot_token, telegram_config.chat_id);
-        messenger.send_notification(&text).await.map_err(|e| {
-            StopError::TelegramError(teloxide::RequestError::Api(teloxide::ApiError::Unknown(
-                e.to_string(),
-            )))
-        })?;
-        return Ok(());
+    send_completion_message(config, event).await
+}
+
+/// Send the completion message for a session, without applying Stop filters.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub async fn send_summary(config: &Config, event: &StopEvent) -> Result<(), StopError> {
+    emit_session_completed(config, event).await;
+    send_completion_message(config, event).await
+}
+
+/// Tell webhooks and the observer chat that a session finished.
+async fn emit_session_completed(config: &Config, event: &StopEvent) {
+    if config.webhooks.is_empty() && config.observer.is_none() {
+        return;
     }
+    let fields = json!({
+        "session_id": event.session_id,
+        "project": event.get_project_name(),
+        "cwd": event.cwd,
+        "duration_seconds": event.get_session_duration().map(|d| d.as_secs()),
+        "error": event.detect_session_error(),
+    });
+    webhooks::emit(config, WebhookEvent::SessionCompleted, fields).await;
+}
+
+/// Build the completion message and deliver it to the chat.
+async fn send_completion_message(config: &Config, event: &StopEvent) -> Result<(), StopError> {
+    let summary = generate_summary(config, event).await;
+    let text = format_completion_message(config, event, summary.as_deref());
 
-    // Try Discord as fallback
-    #[cfg(feature = "discord")]
-    if let Some(ref discord_config) = config.discord {
-        if discord_config.enabled {
-            let messenger =
-                DiscordMessenger::new(&discord_config.bot_token, discord_config.user_id);
-            messenger.send_notification(&text).await.map_err(|e| {
-                StopError::TelegramError(teloxide::RequestError::Api(teloxide::ApiError::Unknown(
-                    e.to_string(),
-                )))
-            })?;
-            return Ok(());
+    if config.stop_notifications.broadcast {
+        return broadcast_notification(config, &text).await;
+    }
+
+    notify_primary(config, &text)
+        .await
+        .map_err(|e| StopError::DeliveryFailed(vec![e.to_string()]))
+}
+
+/// Send a notification to every enabled messenger concurrently.
+///
+/// All deliveries are attempted; failures are collected into a single error.
+async fn broadcast_notification(config: &Config, text: &str) -> Result<(), StopError> {
+    let mut tasks = JoinSet::new();
+
+    for messenger in enabled_messengers(config) {
+        let text = text.to_string();
+        tasks.spawn(async move {
+            let result = messenger.send_notification(&text).await;
+            (messenger.platform_name(), result)
+        });
+    }
+
+    let mut failures = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((_, Ok(()))) => {}
+            Ok((platform, Err(e))) => failures.push(format!("{}: {}", platform, e)),
+            Err(e) => failures.push(e.to_string()),
         }
     }
 
-    // No messenger configured - silently skip
-    Ok(())
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(StopError::DeliveryFailed(failures))
+    }
 }
 
 /// Read JSON input from stdin.
-fn read_stdin() -> Result<String, io::Error> {
+async fn read_stdin() -> Result<String, io::Error> {
     let mut buffer = String::new();
-    io::stdin().read_to_string(&mut buffer)?;
+    tokio::io::stdin().read_to_string(&mut buffer).await?;
     Ok(buffer)
 }
 
 /// Main entry point for the stop handler.
-pub async fn run() -> Result<(), StopError> {
-    // Read and parse input
-    let input_str = read_stdin()?;
-    let input: StopInput = serde_json::from_str(&input_str)?;
+pub async fn run(config_path: Option<&Path>) -> Result<(), StopError> {
+    let input_str = read_stdin().await?;
+    run_with_input(config_path, &input_str).await
+}
 
-    // Load config
-    let config = Config::load(None)?;
+/// Handle raw stop hook input JSON.
+pub async fn run_with_input(config_path: Option<&Path>, input_str: &str) -> Result<(), StopError> {
+    let input: StopInput = serde_json::from_str(input_str)?;
+
+    // Sessions started with `/run` are reported by the launcher once they exit
+    if env::var(REMOTE_RUN_ENV).is_ok_and(|id| !id.is_empty() && id == input.session_id) {
+        return Ok(());
+    }
+
+    // Load config, applying any project route
+    let config = Config::load(config_path.map(Path::to_path_buf))?.for_project(&input.cwd);
 
     // Create event and send notification
     let event = StopEvent::from_input(input);
@@ -221,6 +786,7 @@ pub async fn run() -> Result<(), StopError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     use std::io::Write;
     use tempfile::tempdir;
 
@@ -309,4 +875,326 @@ mod tests {
             Some("Final response".to_string())
         );
     }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_timestamp("2025-01-31T12:34:56.789Z"),
+            Some(1738326896)
+        );
+        assert_eq!(
+            parse_timestamp("2025-01-31T21:34:56+09:00"),
+            Some(1738326896)
+        );
+        assert_eq!(parse_timestamp("not a timestamp"), None);
+        assert_eq!(parse_timestamp("99999999999999-01-31T12:34:56Z"), None);
+        assert_eq!(parse_timestamp("2025-01-31T12:34:56+9999999999:00"), None);
+    }
+
+    #[test]
+    fn test_get_session_duration() {
+        let dir = tempdir().unwrap();
+        let transcript_path = dir.path().join("transcript.jsonl");
+
+        let mut file = File::create(&transcript_path).unwrap();
+        writeln!(
+            file,
+            r#"{{"type": "user", "timestamp": "2025-01-31T12:00:00.000Z"}}"#
+        )
+        .unwrap();
+        writeln!(
+            file,
+            r#"{{"type": "assistant", "timestamp": "2025-01-31T12:02:30.000Z"}}"#
+        )
+        .unwrap();
+
+        let event = StopEvent {
+            session_id: String::new(),
+            transcript_path,
+            cwd: PathBuf::new(),
+            stop_hook_active: false,
+        };
+
+        assert_eq!(event.get_session_duration(), Some(Duration::from_secs(150)));
+    }
+
+    #[test]
+    fn test_should_notify_project_filter() {
+        let event = StopEvent {
+            session_id: String::new(),
+            transcript_path: PathBuf::new(),
+            cwd: PathBuf::from("/home/user/work-api"),
+            stop_hook_active: false,
+        };
+
+        let mut filters = StopNotificationConfig::default();
+        assert!(should_notify(&filters, &event));
+
+        filters.projects = vec!["personal-*".to_string()];
+        assert!(!should_notify(&filters, &event));
+
+        filters.projects.push("work-*".to_string());
+        assert!(should_notify(&filters, &event));
+
+        filters.projects = vec!["/home/user/*".to_string()];
+        assert!(should_notify(&filters, &event));
+    }
+
+    #[test]
+    fn test_should_notify_min_duration() {
+        let dir = tempdir().unwrap();
+        let transcript_path = dir.path().join("transcript.jsonl");
+
+        let mut file = File::create(&transcript_path).unwrap();
+        writeln!(
+            file,
+            r#"{{"type": "user", "timestamp": "2025-01-31T12:00:00Z"}}"#
+        )
+        .unwrap();
+        writeln!(
+            file,
+            r#"{{"type": "assistant", "timestamp": "2025-01-31T12:00:20Z"}}"#
+        )
+        .unwrap();
+
+        let event = StopEvent {
+            session_id: String::new(),
+            transcript_path,
+            cwd: PathBuf::new(),
+            stop_hook_active: false,
+        };
+
+        let mut filters = StopNotificationConfig {
+            min_duration_seconds: 60,
+            ..Default::default()
+        };
+        assert!(!should_notify(&filters, &event));
+
+        filters.min_duration_seconds = 10;
+        assert!(should_notify(&filters, &event));
+    }
+
+    fn event_with_transcript(lines: &[&str]) -> (tempfile::TempDir, StopEvent) {
+        let dir = tempdir().unwrap();
+        let transcript_path = dir.path().join("transcript.jsonl");
+
+        let mut file = File::create(&transcript_path).unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+
+        let event = StopEvent {
+            session_id: String::new(),
+            transcript_path,
+            cwd: PathBuf::new(),
+            stop_hook_active: false,
+        };
+        (dir, event)
+    }
+
+    #[test]
+    fn test_detect_session_error_none() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "user", "message": {"content": [{"type": "text", "text": "Hi"}]}}"#,
+            r#"{"type": "assistant", "message": {"content": [{"type": "text", "text": "Done"}]}}"#,
+        ]);
+
+        assert!(event.detect_session_error().is_none());
+    }
+
+    #[test]
+    fn test_detect_session_error_recovered_tool_failure() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "user", "message": {"content": [{"type": "tool_result", "content": "exit 1", "is_error": true}]}}"#,
+            r#"{"type": "assistant", "message": {"content": [{"type": "text", "text": "Fixed it"}]}}"#,
+        ]);
+
+        assert!(event.detect_session_error().is_none());
+    }
+
+    #[test]
+    fn test_detect_session_error_api_error() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "assistant", "message": {"content": [{"type": "text", "text": "Working"}]}}"#,
+            r#"{"type": "assistant", "isApiErrorMessage": true, "message": {"content": [{"type": "text", "text": "API Error: 529 Overloaded"}]}}"#,
+        ]);
+
+        assert_eq!(
+            event.detect_session_error(),
+            Some("API Error: 529 Overloaded".to_string())
+        );
+    }
+
+    #[test]
+    fn test_detect_session_error_tool_failure_and_interrupt() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "user", "message": {"content": [{"type": "tool_result", "content": [{"type": "text", "text": "command not found"}], "is_error": true}]}}"#,
+        ]);
+        assert_eq!(
+            event.detect_session_error(),
+            Some("command not found".to_string())
+        );
+
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "user", "message": {"content": [{"type": "text", "text": "[Request interrupted by user]"}]}}"#,
+        ]);
+        assert_eq!(
+            event.detect_session_error(),
+            Some("Interrupted by user".to_string())
+        );
+    }
+
+    #[test]
+    fn test_detect_session_error_after_recovery() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "user", "message": {"content": [{"type": "tool_result", "content": "first failure", "is_error": true}]}}"#,
+            r#"{"type": "assistant", "message": {"content": [{"type": "text", "text": "Recovered"}]}}"#,
+            r#"{"type": "user", "message": {"content": [{"type": "tool_result", "content": "second failure", "is_error": true}]}}"#,
+            r#"{"type": "assistant", "message": {"content": [{"type": "tool_use", "name": "Bash"}]}}"#,
+        ]);
+
+        assert_eq!(
+            event.detect_session_error(),
+            Some("second failure".to_string())
+        );
+    }
+
+    #[test]
+    fn test_detect_session_error_max_turns() {
+        let (_dir, event) =
+            event_with_transcript(&[r#"{"type": "result", "subtype": "error_max_turns"}"#]);
+
+        assert_eq!(
+            event.detect_session_error(),
+            Some("Session aborted (error_max_turns)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_user_prompt() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "user", "isMeta": true, "message": {"content": "Caveat: meta"}}"#,
+            r#"{"type": "user", "message": {"content": "Fix the login bug"}}"#,
+            r#"{"type": "user", "message": {"content": [{"type": "tool_result", "content": "ok"}]}}"#,
+            r#"{"type": "user", "message": {"content": "<command-name>/clear</command-name>"}}"#,
+            r#"{"type": "user", "message": {"content": [{"type": "text", "text": "Now add tests"}]}}"#,
+            r#"{"type": "user", "message": {"content": [{"type": "text", "text": "[Request interrupted by user]"}]}}"#,
+        ]);
+
+        assert_eq!(
+            event.get_user_prompt(TaskPromptSource::First),
+            Some("Fix the login bug".to_string())
+        );
+        assert_eq!(
+            event.get_user_prompt(TaskPromptSource::Last),
+            Some("Now add tests".to_string())
+        );
+        assert!(event.get_user_prompt(TaskPromptSource::Off).is_none());
+    }
+
+    #[test]
+    fn test_format_resume_hint() {
+        let mut event = StopEvent {
+            session_id: String::new(),
+            transcript_path: PathBuf::new(),
+            cwd: PathBuf::from("/home/user/my project"),
+            stop_hook_active: false,
+        };
+        assert!(format_resume_hint(&event).is_none());
+
+        event.session_id = "abc-123".to_string();
+        let hint = format_resume_hint(&event).unwrap();
+        assert!(hint.contains("cd '/home/user/my project' && claude --resume abc-123"));
+
+        event.cwd = PathBuf::new();
+        let hint = format_resume_hint(&event).unwrap();
+        assert!(hint.contains("\nclaude --resume abc-123\n"));
+    }
+
+    #[test]
+    fn test_get_transcript_tail() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "user", "message": {"content": "Fix the bug"}}"#,
+            r#"{"type": "assistant", "message": {"content": [{"type": "text", "text": "Fixed"}]}}"#,
+        ]);
+
+        assert_eq!(
+            event.get_transcript_tail(1000),
+            Some("User: Fix the bug\nAssistant: Fixed".to_string())
+        );
+        assert_eq!(event.get_transcript_tail(5), Some("Fixed".to_string()));
+    }
+
+    #[test]
+    fn test_get_transcript_tail_of_long_transcript() {
+        let entries: Vec<String> = (0..1000)
+            .map(|i| {
+                format!(
+                    r#"{{"type": "assistant", "message": {{"content": [{{"type": "text", "text": "Step {}"}}, {{"type": "text", "text": "done"}}]}}}}"#,
+                    i
+                )
+            })
+            .collect();
+        let lines: Vec<&str> = entries.iter().map(String::as_str).collect();
+        let (_dir, event) = event_with_transcript(&lines);
+
+        assert_eq!(
+            event.get_transcript_tail(50),
+            Some("ssistant: done\nAssistant: Step 999\nAssistant: done".to_string())
+        );
+    }
+
+    #[test]
+    fn test_reverse_lines() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lines.txt");
+        fs::write(&path, "first\nsecond\n\nthird\n").unwrap();
+
+        let lines: Vec<String> = ReverseLines::new(File::open(&path).unwrap()).collect();
+        assert_eq!(lines, vec!["third", "", "second", "first"]);
+    }
+
+    #[test]
+    fn test_reverse_lines_across_chunks() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lines.txt");
+        let long = "x".repeat(ReverseLines::CHUNK_SIZE as usize + 10);
+        fs::write(&path, format!("{}\nlast", long)).unwrap();
+
+        let lines: Vec<String> = ReverseLines::new(File::open(&path).unwrap()).collect();
+        assert_eq!(lines, vec!["last".to_string(), long]);
+    }
+
+    #[test]
+    fn test_get_last_assistant_message_skips_tool_use_and_thinking() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"type": "assistant", "message": {"content": [{"type": "thinking", "thinking": "hmm"}, {"type": "text", "text": "All done"}]}}"#,
+            r#"{"type": "assistant", "message": {"content": [{"type": "tool_use", "id": "t1", "name": "Bash", "input": {}}]}}"#,
+            r#"{"type": "user", "message": {"content": [{"type": "tool_result", "tool_use_id": "t1", "content": "ok"}]}}"#,
+            r#"{"type": "assistant", "message": {"content": [{"type": "thinking", "thinking": "wrap up"}]}}"#,
+        ]);
+
+        assert_eq!(
+            event.get_last_assistant_message(),
+            Some("All done".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_last_assistant_message_flat_layout() {
+        let (_dir, event) = event_with_transcript(&[
+            r#"{"role": "user", "content": "Hello"}"#,
+            r#"{"type": "message", "role": "assistant", "content": [{"type": "text", "text": "Hi there"}, {"unknown": true}]}"#,
+        ]);
+
+        assert_eq!(
+            event.get_last_assistant_message(),
+            Some("Hi there".to_string())
+        );
+        assert_eq!(
+            event.get_user_prompt(TaskPromptSource::First),
+            Some("Hello".to_string())
+        );
+    }
 }
@@ -0,0 +1,198 @@
+//! Always-allow entries suggested from the decision history.
+//!
+//! A call approved every time it was asked about, at least `min_approvals`
+//! times in the last `days`, is proposed as an always-allow entry: shell
+//! commands exactly as run (`Bash(cargo test)`), fetches by site and other
+//! tools as a whole. `rules suggest` lists the suggestions, and with
+//! `preferences.rule_suggestions.enabled` the bot offers them now and then
+//! with a button to add each.
+
+use crate::always_allow::AlwaysAllowManager;
+use crate::config::{Config, RuleSuggestionsConfig};
+use crate::history::{self, ExportRow};
+use crate::tool_timer::{detail_input, url_domain};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Most suggestions made at once.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// An always-allow entry the history says is safe to add.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// The entry, e.g. `Bash(cargo test)`
+    pub entry: String,
+    /// Times the call was approved, which is every time it was asked
+    pub approved: usize,
+}
+
+impl Suggestion {
+    /// What the entry covers: the command for shell entries, else the entry.
+    fn subject(&self) -> &str {
+        self.entry
+            .strip_prefix("Bash(")
+            .and_then(|rest| rest.strip_suffix(')'))
+            .unwrap_or(&self.entry)
+    }
+
+    /// e.g. ``you approved `cargo test` 19/19 times``.
+    pub fn text(&self) -> String {
+        format!(
+            "you approved `{}` {}/{} times",
+            self.subject(),
+            self.approved,
+            self.approved
+        )
+    }
+}
+
+/// The entry a call would be allowed with, and the input to check it against.
+///
+/// Shell commands with wildcards or line breaks are left out, as are
+/// subagent tasks, whose agent type is not recorded.
+fn candidate(row: &ExportRow) -> Option<(String, Value)> {
+    let tool = row.tool.as_deref()?;
+    let command = row.command.as_deref();
+    let entry = match tool {
+        "Bash" => format!(
+            "Bash({})",
+            command.filter(|command| !command.contains(['*', '?', '\n']))?
+        ),
+        "WebFetch" => format!("WebFetch(domain:{})", url_domain(command?)?),
+        "Task" => return None,
+        tool => tool.to_string(),
+    };
+    Some((entry, detail_input(tool, command)))
+}
+
+/// Suggestions from history rows, most approved first.
+///
+/// Calls already covered by the always-allow list are skipped.
+pub fn suggest(
+    rows: &[ExportRow],
+    settings: &RuleSuggestionsConfig,
+    always_allow: &AlwaysAllowManager,
+) -> Vec<Suggestion> {
+    // Approvals and times asked, by entry
+    let mut tallies: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for row in rows {
+        let approved = match row.decision.as_deref() {
+            Some("allow") => true,
+            Some("deny" | "deny_block" | "timeout") => false,
+            _ => continue,
+        };
+        let Some((entry, input)) = candidate(row) else {
+            continue;
+        };
+        let tool = row.tool.as_deref().unwrap_or_default();
+        if always_allow.allows(tool, &input) {
+            continue;
+        }
+        let tally = tallies.entry(entry).or_default();
+        tally.0 += usize::from(approved);
+        tally.1 += 1;
+    }
+
+    let mut suggestions: Vec<Suggestion> = tallies
+        .into_iter()
+        .filter(|(_, (approved, asked))| approved == asked && *approved >= settings.min_approvals)
+        .map(|(entry, (approved, _))| Suggestion { entry, approved })
+        .collect();
+    suggestions.sort_by(|a, b| b.approved.cmp(&a.approved).then(a.entry.cmp(&b.entry)));
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+/// Suggestions from this machine's history over the configured days.
+pub fn current(config: &Config) -> Vec<Suggestion> {
+    let settings = &config.rule_suggestions;
+    let rows = history::recent_rows(Duration::from_secs(settings.days * 24 * 60 * 60));
+    suggest(&rows, settings, &AlwaysAllowManager::new(None))
+}
+
+/// List the suggestions, adding them to the always-allow list with `apply`.
+pub fn run(config_path: Option<&Path>, apply: bool) -> Result<()> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
+    let settings = &config.rule_suggestions;
+    let suggestions = current(&config);
+    if suggestions.is_empty() {
+        println!(
+            "No suggestions: nothing was approved {} times without a denial in the last {} days",
+            settings.min_approvals, settings.days
+        );
+        return Ok(());
+    }
+
+    println!("💡 Always-allow suggestions (last {} days):", settings.days);
+    for suggestion in &suggestions {
+        println!("   {}  ({})", suggestion.entry, suggestion.text());
+    }
+    if !apply {
+        println!("\nAdd them with `rules suggest --apply`.");
+        return Ok(());
+    }
+    let always_allow = AlwaysAllowManager::new(None);
+    for suggestion in &suggestions {
+        always_allow
+            .add_tool(&suggestion.entry)
+            .with_context(|| format!("Failed to add {}", suggestion.entry))?;
+    }
+    println!("\n✅ Added {} always-allow entries", suggestions.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_suggest() {
+        let row = |tool: &str, command: Option<&str>, decision: &str| ExportRow {
+            tool: Some(tool.to_string()),
+            command: command.map(str::to_string),
+            decision: Some(decision.to_string()),
+            ..ExportRow::default()
+        };
+        let mut rows = Vec::new();
+        for _ in 0..19 {
+            rows.push(row("Bash", Some("cargo test"), "allow"));
+        }
+        for _ in 0..12 {
+            rows.push(row("Bash", Some("git push"), "allow"));
+            rows.push(row("Edit", Some("src/main.rs"), "allow"));
+            rows.push(row("WebFetch", Some("https://docs.rs/serde"), "allow"));
+            rows.push(row("Bash", Some("rm -rf *"), "allow"));
+            rows.push(row("Task", Some("Explore"), "allow"));
+        }
+        rows.push(row("Bash", Some("git push"), "deny"));
+        rows.push(row("Bash", Some("cargo test"), "auto_allow"));
+        for _ in 0..3 {
+            rows.push(row("Bash", Some("ls"), "allow"));
+        }
+
+        let dir = tempdir().unwrap();
+        let always_allow = AlwaysAllowManager::new(Some(dir.path().join("always_allow.json")));
+        always_allow.add_tool("Edit").unwrap();
+        let settings = RuleSuggestionsConfig::default();
+
+        let suggestions = suggest(&rows, &settings, &always_allow);
+        let entries: Vec<&str> = suggestions.iter().map(|s| s.entry.as_str()).collect();
+        assert_eq!(entries, ["Bash(cargo test)", "WebFetch(domain:docs.rs)"]);
+        assert_eq!(
+            suggestions[0].text(),
+            "you approved `cargo test` 19/19 times"
+        );
+
+        always_allow.add_tool("Bash(cargo test)").unwrap();
+        let suggestions = suggest(&rows, &settings, &always_allow);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(
+            suggestions[0].text(),
+            "you approved `WebFetch(domain:docs.rs)` 12/12 times"
+        );
+    }
+}
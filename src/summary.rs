@@ -0,0 +1,147 @@
+//! LLM-generated session summaries for completion notifications.
+//!
+//! Sends the tail of a session transcript to the Anthropic Messages API or an
+//! OpenAI-compatible endpoint and returns a short summary. The same call
+//! writes command explanations, see `explain`.
+
+use crate::config::{LlmProvider, LlmSummaryConfig};
+use crate::error::SummaryError;
+use crate::messenger::clients;
+use serde_json::{json, Value};
+use std::env;
+use std::time::Duration;
+
+const ANTHROPIC_ENDPOINT: &str = "https://api.anthropic.com/v1";
+const OPENAI_ENDPOINT: &str = "https://api.openai.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Build the summarization prompt for a transcript excerpt.
+fn build_prompt(transcript_tail: &str) -> String {
+    format!(
+        "Below is the end of a Claude Code session transcript. Summarize what was \
+        accomplished in 2-3 plain sentences for a phone notification. Mention any \
+        unfinished work or problems. Do not use markdown.\n\n<transcript>\n{}\n</transcript>",
+        transcript_tail
+    )
+}
+
+/// Resolve the API key from config or environment.
+fn resolve_api_key(config: &LlmSummaryConfig) -> Option<String> {
+    config
+        .api_key
+        .clone()
+        .or_else(|| env::var(&config.api_key_env).ok())
+        .filter(|key| !key.is_empty())
+}
+
+/// Summarize a transcript excerpt with the configured LLM provider.
+pub async fn summarize(
+    config: &LlmSummaryConfig,
+    transcript_tail: &str,
+) -> Result<String, SummaryError> {
+    complete(config, &build_prompt(transcript_tail), REQUEST_TIMEOUT).await
+}
+
+/// Send a prompt to the configured LLM provider, returning its answer.
+pub async fn complete(
+    config: &LlmSummaryConfig,
+    prompt: &str,
+    timeout: Duration,
+) -> Result<String, SummaryError> {
+    let client = clients::shared().http();
+    let api_key = resolve_api_key(config);
+
+    let summary = match config.provider {
+        LlmProvider::Anthropic => {
+            let api_key =
+                api_key.ok_or_else(|| SummaryError::MissingApiKey(config.api_key_env.clone()))?;
+            let endpoint = config.endpoint.as_deref().unwrap_or(ANTHROPIC_ENDPOINT);
+
+            let response: Value = client
+                .post(format!("{}/messages", endpoint.trim_end_matches('/')))
+                .header("x-api-key", api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .timeout(timeout)
+                .json(&json!({
+                    "model": config.model,
+                    "max_tokens": 300,
+                    "messages": [{"role": "user", "content": prompt}],
+                }))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            response
+                .get("content")
+                .and_then(|c| c.as_array())
+                .and_then(|blocks| {
+                    blocks
+                        .iter()
+                        .find_map(|b| b.get("text").and_then(|t| t.as_str()))
+                })
+                .map(str::to_string)
+        }
+        LlmProvider::Openai => {
+            let endpoint = config.endpoint.as_deref().unwrap_or(OPENAI_ENDPOINT);
+
+            // Local model servers usually don't need a key
+            let mut request = client
+                .post(format!(
+                    "{}/chat/completions",
+                    endpoint.trim_end_matches('/')
+                ))
+                .timeout(timeout)
+                .json(&json!({
+                    "model": config.model,
+                    "max_tokens": 300,
+                    "messages": [{"role": "user", "content": prompt}],
+                }));
+            if let Some(api_key) = api_key {
+                request = request.bearer_auth(api_key);
+            }
+
+            let response: Value = request.send().await?.error_for_status()?.json().await?;
+
+            response
+                .pointer("/choices/0/message/content")
+                .and_then(|c| c.as_str())
+                .map(str::to_string)
+        }
+    };
+
+    summary
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| SummaryError::InvalidResponse("no text in response".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_prompt_includes_transcript() {
+        let prompt = build_prompt("User: fix the bug");
+        assert!(prompt.contains("<transcript>\nUser: fix the bug\n</transcript>"));
+    }
+
+    #[test]
+    fn test_resolve_api_key_prefers_config() {
+        let config = LlmSummaryConfig {
+            api_key: Some("from-config".to_string()),
+            api_key_env: "CLAUDE_CODE_TELEGRAM_TEST_UNSET_KEY".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(resolve_api_key(&config), Some("from-config".to_string()));
+
+        let config = LlmSummaryConfig {
+            api_key: None,
+            api_key_env: "CLAUDE_CODE_TELEGRAM_TEST_UNSET_KEY".to_string(),
+            ..Default::default()
+        };
+        assert!(resolve_api_key(&config).is_none());
+    }
+}
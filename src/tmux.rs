@@ -0,0 +1,142 @@
+//! Claude Code sessions running in tmux panes, for steering from chat.
+//!
+//! Panes are found with `tmux list-panes -a` by their foreground command and
+//! addressed by tmux pane ID (`%12`, shown as `12`), which stays the same for
+//! the pane's lifetime. Steering is done with `send-keys`: Escape interrupts
+//! Claude, and text is typed literally and submitted with Enter.
+
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Format of one line of `tmux list-panes` output.
+const PANE_FORMAT: &str =
+    "#{pane_id}\t#{pane_current_command}\t#{session_name}:#{window_index}.#{pane_index}\t#{pane_current_path}";
+
+/// A tmux pane running Claude Code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaudePane {
+    /// Pane ID without the leading `%`
+    pub id: String,
+    /// `session:window.pane`
+    pub target: String,
+    pub cwd: PathBuf,
+}
+
+impl ClaudePane {
+    /// Project name, the last component of the working directory.
+    pub fn project(&self) -> String {
+        self.cwd
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.cwd.display().to_string())
+    }
+}
+
+/// Pick the Claude Code panes out of `tmux list-panes` output.
+fn parse_panes(output: &str) -> Vec<ClaudePane> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            let id = fields.next()?.strip_prefix('%')?;
+            let command = fields.next()?;
+            let target = fields.next()?;
+            let cwd = fields.next()?;
+            (command == "claude").then(|| ClaudePane {
+                id: id.to_string(),
+                target: target.to_string(),
+                cwd: PathBuf::from(cwd),
+            })
+        })
+        .collect()
+}
+
+fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to run tmux")?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// List Claude Code panes across all tmux sessions.
+///
+/// No tmux server means no sessions rather than an error.
+pub fn list_claude_panes() -> Vec<ClaudePane> {
+    tmux(&["list-panes", "-a", "-F", PANE_FORMAT])
+        .map(|output| parse_panes(&output))
+        .unwrap_or_default()
+}
+
+/// Find a Claude Code pane by its ID, with or without the `%`.
+fn find_pane(id: &str) -> Result<ClaudePane> {
+    let id = id.trim().trim_start_matches('%');
+    list_claude_panes()
+        .into_iter()
+        .find(|pane| pane.id == id)
+        .with_context(|| format!("No Claude session with ID {}; see /sessions", id))
+}
+
+/// Press Escape in a Claude Code pane to interrupt the current turn.
+pub fn interrupt(id: &str) -> Result<ClaudePane> {
+    let pane = find_pane(id)?;
+    tmux(&["send-keys", "-t", &format!("%{}", pane.id), "Escape"])?;
+    Ok(pane)
+}
+
+/// Arguments that type `text` literally into the pane `target`.
+///
+/// `-l` types the text instead of looking up key names, and `--` keeps a
+/// prompt starting with a dash from being read as a flag.
+fn prompt_args<'a>(target: &'a str, text: &'a str) -> [&'a str; 6] {
+    ["send-keys", "-t", target, "-l", "--", text]
+}
+
+/// Type a prompt into a Claude Code pane and submit it.
+pub fn send_prompt(id: &str, text: &str) -> Result<ClaudePane> {
+    let pane = find_pane(id)?;
+    let target = format!("%{}", pane.id);
+    tmux(&prompt_args(&target, text))?;
+    tmux(&["send-keys", "-t", &target, "Enter"])?;
+    Ok(pane)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_panes() {
+        let output = "%3\tzsh\tmain:0.0\t/home/me\n\
+                      %7\tclaude\tmain:1.0\t/home/me/src/api\n\
+                      %12\tclaude\tccr-0123abcd:0.0\t/home/me/src/web tools\n\
+                      garbage\n";
+        let panes = parse_panes(output);
+        assert_eq!(panes.len(), 2);
+        assert_eq!(panes[0].id, "7");
+        assert_eq!(panes[0].target, "main:1.0");
+        assert_eq!(panes[0].project(), "api");
+        assert_eq!(panes[1].cwd, PathBuf::from("/home/me/src/web tools"));
+    }
+
+    #[test]
+    fn test_prompt_args_end_flags_before_a_dash_prompt() {
+        let args = prompt_args("%7", "-rf is not a flag here");
+        assert_eq!(
+            args,
+            [
+                "send-keys",
+                "-t",
+                "%7",
+                "-l",
+                "--",
+                "-rf is not a flag here"
+            ]
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Long-running tool alerts from PreToolUse/PostToolUse hooks.
+//!
+//! PreToolUse records a start marker and spawns a detached watcher that alerts
+//! if the tool is still running after the configured threshold. PostToolUse
+//! removes the marker and reports the total duration of slow tools.
+//!
+//! PostToolUse also shows when a request sent to chat was answered in the
+//! terminal instead: Claude gives up on the request, and the tool then runs.
+
+use crate::clock::now_secs;
+use crate::config::{default_tool_timers_path, Config};
+use crate::cost_guard;
+use crate::error::HookError;
+use crate::messenger::{dry_run, enabled_messengers, ledger, notify_primary};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+
+/// Claude Code PreToolUse/PostToolUse hook input.
+#[derive(Debug, Deserialize)]
+pub struct ToolHookInput {
+    /// "PreToolUse" or "PostToolUse"
+    #[serde(default)]
+    pub hook_event_name: String,
+    #[serde(default)]
+    pub session_id: String,
+    #[serde(default)]
+    pub tool_use_id: String,
+    #[serde(default)]
+    pub tool_name: String,
+    #[serde(default)]
+    pub tool_input: Value,
+    #[serde(default)]
+    pub cwd: String,
+    #[serde(default)]
+    pub transcript_path: String,
+}
+
+impl ToolHookInput {
+    /// Key identifying this tool call across the Pre and Post hooks.
+    fn timer_key(&self) -> String {
+        let raw = if self.tool_use_id.is_empty() {
+            format!("{}-{}", self.session_id, self.tool_name)
+        } else {
+            self.tool_use_id.clone()
+        };
+        raw.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+}
+
+/// The input that identifies a tool call: its command, file path, URL or task.
+pub fn tool_detail<'a>(tool_name: &str, tool_input: &'a Value) -> Option<&'a str> {
+    match tool_name {
+        "Bash" => tool_input.get("command"),
+        "Edit" | "Write" | "MultiEdit" | "Read" => tool_input.get("file_path"),
+        "NotebookEdit" => tool_input.get("notebook_path"),
+        "WebFetch" => tool_input.get("url"),
+        "WebSearch" => tool_input.get("query"),
+        "Task" => tool_input.get("description"),
+        _ => None,
+    }
+    .and_then(|v| v.as_str())
+}
+
+/// A tool input holding just the detail `tool_detail` would read back.
+///
+/// History keeps only the detail of each call, so this stands in for the
+/// full input when old requests are checked against rules.
+pub fn detail_input(tool_name: &str, detail: Option<&str>) -> Value {
+    let key = match tool_name {
+        "Bash" => "command",
+        "Edit" | "Write" | "MultiEdit" | "Read" => "file_path",
+        "NotebookEdit" => "notebook_path",
+        "WebFetch" => "url",
+        "WebSearch" => "query",
+        "Task" => "description",
+        _ => return json!({}),
+    };
+    match detail {
+        Some(detail) => json!({ key: detail }),
+        None => json!({}),
+    }
+}
+
+/// Lowercased host of a URL, e.g. `docs.rs` for `https://docs.rs/serde`.
+pub fn url_domain(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = host.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+/// Describe a tool call in one line, e.g. "Bash `cargo build --release`".
+pub fn describe_tool(tool_name: &str, tool_input: &Value) -> String {
+    match tool_detail(tool_name, tool_input) {
+        Some(detail) => {
+            let truncated: String = detail.chars().take(80).collect();
+            let ellipsis = if detail.chars().count() > 80 {
+                "..."
+            } else {
+                ""
+            };
+            format!("{} `{}{}`", tool_name, truncated, ellipsis)
+        }
+        None => tool_name.to_string(),
+    }
+}
+
+/// Format a duration as whole minutes, or seconds when under a minute.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{} s", secs)
+    } else {
+        format!("{} min", secs / 60)
+    }
+}
+
+pub(crate) fn project_name(cwd: &str) -> &str {
+    cwd.split('/').next_back().unwrap_or(cwd)
+}
+
+/// Format the alert sent while a tool is still running.
+fn format_running_alert(hostname: &str, label: &str, cwd: &str, elapsed: Duration) -> String {
+    let mut lines = vec![
+        "⏳ **Long-Running Tool**".to_string(),
+        format!(
+            "{} has been running {} on host {}",
+            label,
+            format_duration(elapsed),
+            hostname
+        ),
+    ];
+    if !cwd.is_empty() {
+        lines.push(format!("📁 **Project:** {}", project_name(cwd)));
+    }
+    lines.join("\n")
+}
+
+/// Format the report sent when a slow tool finishes.
+fn format_finished_report(hostname: &str, label: &str, cwd: &str, elapsed: Duration) -> String {
+    let mut lines = vec![
+        "🏁 **Long-Running Tool Finished**".to_string(),
+        format!(
+            "{} finished after {} on host {}",
+            label,
+            format_duration(elapsed),
+            hostname
+        ),
+    ];
+    if !cwd.is_empty() {
+        lines.push(format!("📁 **Project:** {}", project_name(cwd)));
+    }
+    lines.join("\n")
+}
+
+/// Path of the start marker for a tool call.
+fn marker_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.start", key))
+}
+
+/// Read the start time recorded in a marker, if it still exists.
+fn read_marker(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Spawn a detached watcher that alerts if the tool is still running later.
+fn spawn_watcher(
+    key: &str,
+    started: u64,
+    label: &str,
+    cwd: &str,
+    config_path: Option<&Path>,
+) -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let mut command = Command::new(exe);
+    if let Some(path) = config_path {
+        command.arg("--config").arg(path);
+    }
+    if dry_run() {
+        command.arg("--dry-run");
+    }
+    command
+        .arg("watch-tool")
+        .arg("--key")
+        .arg(key)
+        .arg("--started")
+        .arg(started.to_string())
+        .arg("--label")
+        .arg(label)
+        .arg("--cwd")
+        .arg(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// Handle a PreToolUse or PostToolUse event.
+pub async fn handle_tool_event(
+    config: &Config,
+    input: &ToolHookInput,
+    timers_dir: &Path,
+    config_path: Option<&Path>,
+) -> Result<(), HookError> {
+    let key = input.timer_key();
+    let marker = marker_path(timers_dir, &key);
+    let label = describe_tool(&input.tool_name, &input.tool_input);
+
+    match input.hook_event_name.as_str() {
+        "PreToolUse" => {
+            fs::create_dir_all(timers_dir)?;
+            let started = now_secs();
+            fs::write(&marker, started.to_string())?;
+            if let Err(e) = spawn_watcher(&key, started, &label, &input.cwd, config_path) {
+                tracing::warn!("Failed to start tool watcher: {}", e);
+            }
+        }
+        "PostToolUse" => {
+            let Some(started) = read_marker(&marker) else {
+                return Ok(());
+            };
+            let _ = fs::remove_file(&marker);
+
+            let elapsed = Duration::from_secs(now_secs().saturating_sub(started));
+            if elapsed.as_secs() >= config.long_running_tools.threshold_seconds {
+                let text = format_finished_report(&config.host_label, &label, &input.cwd, elapsed);
+                notify_primary(config, &text).await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Wait for the threshold and alert if the tool call has not finished.
+pub async fn run_watcher(
+    key: &str,
+    started: u64,
+    label: &str,
+    cwd: &str,
+    config_path: Option<&Path>,
+) -> Result<(), HookError> {
+    let config = Config::load(config_path.map(Path::to_path_buf))?.for_project(cwd);
+    let marker = marker_path(&default_tool_timers_path(), key);
+
+    let due = started + config.long_running_tools.threshold_seconds;
+    tokio::time::sleep(Duration::from_secs(due.saturating_sub(now_secs()))).await;
+
+    // A missing or replaced marker means the tool already finished
+    if read_marker(&marker) != Some(started) {
+        return Ok(());
+    }
+
+    let elapsed = Duration::from_secs(now_secs().saturating_sub(started));
+    let text = format_running_alert(&config.host_label, label, cwd, elapsed);
+    notify_primary(&config, &text).await
+}
+
+/// Mark the chat message of a request for this tool call as handled locally,
+/// if Claude gave up on it.
+async fn mark_handled_locally(config: &Config, input: &ToolHookInput) {
+    let tool = describe_tool(&input.tool_name, &input.tool_input);
+    let Some(message) = ledger::take_handled_locally(&input.session_id, &tool).await else {
+        return;
+    };
+    for messenger in enabled_messengers(config) {
+        if messenger.platform_name() == message.platform {
+            if let Err(e) = messenger.mark_handled_locally(&message).await {
+                tracing::warn!("Failed to mark request as handled locally: {}", e);
+            }
+        }
+    }
+}
+
+/// Read JSON input from stdin.
+async fn read_stdin() -> Result<String, io::Error> {
+    let mut buffer = String::new();
+    tokio::io::stdin().read_to_string(&mut buffer).await?;
+    Ok(buffer)
+}
+
+/// Main entry point for the tool timer hook.
+pub async fn run(config_path: Option<&Path>) -> Result<(), HookError> {
+    let input_str = read_stdin().await?;
+    let input: ToolHookInput = serde_json::from_str(&input_str)?;
+
+    let config = Config::load(config_path.map(Path::to_path_buf))?.for_project(&input.cwd);
+    if input.hook_event_name == "PostToolUse" {
+        mark_handled_locally(&config, &input).await;
+    }
+    if config.cost_guard.enabled {
+        match input.hook_event_name.as_str() {
+            "PostToolUse" => {
+                cost_guard::track(
+                    &config,
+                    &input.session_id,
+                    &input.transcript_path,
+                    &input.cwd,
+                )
+                .await
+            }
+            "PreToolUse" if config.cost_guard.confirm => {
+                if let Some(reason) =
+                    cost_guard::confirm(&config, &input.session_id, &input.cwd).await
+                {
+                    let output = json!({
+                        "hookSpecificOutput": {
+                            "hookEventName": "PreToolUse",
+                            "permissionDecision": "deny",
+                            "permissionDecisionReason": reason,
+                        }
+                    });
+                    println!("{}", output);
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+    }
+    if !config.long_running_tools.enabled {
+        return Ok(());
+    }
+
+    handle_tool_event(&config, &input, &default_tool_timers_path(), config_path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_describe_tool() {
+        let input = serde_json::json!({"command": "cargo build --release"});
+        assert_eq!(
+            describe_tool("Bash", &input),
+            "Bash `cargo build --release`"
+        );
+
+        let input = serde_json::json!({"file_path": "/src/main.rs"});
+        assert_eq!(describe_tool("Edit", &input), "Edit `/src/main.rs`");
+
+        assert_eq!(describe_tool("Glob", &serde_json::json!({})), "Glob");
+
+        let input = detail_input("Edit", Some("/src/main.rs"));
+        assert_eq!(tool_detail("Edit", &input), Some("/src/main.rs"));
+        assert_eq!(detail_input("Glob", Some("*.rs")), serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_url_domain() {
+        assert_eq!(
+            url_domain("https://Docs.rs/serde/latest").as_deref(),
+            Some("docs.rs")
+        );
+        assert_eq!(
+            url_domain("http://user@localhost:8080?q=1").as_deref(),
+            Some("localhost")
+        );
+        assert_eq!(
+            url_domain("example.com/a#b").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(url_domain("https:///path"), None);
+    }
+
+    #[test]
+    fn test_timer_key_sanitized() {
+        let input = ToolHookInput {
+            hook_event_name: "PreToolUse".to_string(),
+            session_id: "abc/123".to_string(),
+            tool_use_id: String::new(),
+            tool_name: "Bash".to_string(),
+            tool_input: Value::Null,
+            cwd: String::new(),
+            transcript_path: String::new(),
+        };
+        assert_eq!(input.timer_key(), "abc_123_Bash");
+    }
+
+    #[test]
+    fn test_format_running_alert() {
+        let text = format_running_alert(
+            "my-host",
+            "Bash `cargo build --release`",
+            "/home/user/app",
+            Duration::from_secs(720),
+        );
+        assert!(
+            text.contains("Bash `cargo build --release` has been running 12 min on host my-host")
+        );
+        assert!(text.contains("app"));
+    }
+
+    #[test]
+    fn test_marker_roundtrip() {
+        let dir = tempdir().unwrap();
+        let marker = marker_path(dir.path(), "toolu_1");
+        assert!(read_marker(&marker).is_none());
+
+        fs::write(&marker, "1700000000").unwrap();
+        assert_eq!(read_marker(&marker), Some(1700000000));
+    }
+}
@@ -0,0 +1,1139 @@
+//! Configuration file validation with detailed diagnostics.
+//!
+//! Goes beyond `Config::from_json` by reporting every problem at once:
+//! unknown keys, missing fields, invalid IDs, inconsistent preferences and
+//! deprecated legacy fields.
+
+use crate::config::{default_plugin_dir, expand_home, Config, MESSENGER_NAMES as MESSENGERS};
+use crate::schedule;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Known keys for each object in the configuration file, by dotted path.
+///
+/// `routes[]` stands for every element of the `routes` array, and
+/// `messengers.plugins.*` for every plugin.
+const KNOWN_KEYS: &[(&str, &[&str])] = &[
+    (
+        "",
+        &[
+            "messengers",
+            "preferences",
+            "routes",
+            "authorized_responders",
+            "approval_rules",
+        ],
+    ),
+    ("approval_rules[]", &["tools", "patterns", "require_quorum"]),
+    ("authorized_responders", &["telegram", "discord", "signal"]),
+    (
+        "messengers",
+        &[
+            "telegram",
+            "discord",
+            "signal",
+            "homeassistant",
+            "plugins",
+            "observer",
+        ],
+    ),
+    (
+        "messengers.telegram",
+        &[
+            "enabled",
+            "bot_token",
+            "bot_token_file",
+            "bot_token_command",
+            "chat_id",
+            "topic_id",
+            "compact",
+        ],
+    ),
+    (
+        "messengers.discord",
+        &[
+            "enabled",
+            "bot_token",
+            "bot_token_file",
+            "bot_token_command",
+            "user_id",
+            "compact",
+        ],
+    ),
+    (
+        "messengers.signal",
+        &["enabled", "phone_number", "device_name", "data_path"],
+    ),
+    (
+        "messengers.homeassistant",
+        &[
+            "enabled",
+            "mqtt_host",
+            "mqtt_port",
+            "username",
+            "password",
+            "topic_prefix",
+            "presence",
+            "statestream_topic",
+        ],
+    ),
+    ("messengers.plugins.*", &["enabled", "command", "settings"]),
+    (
+        "messengers.observer",
+        &[
+            "enabled",
+            "bot_token",
+            "bot_token_file",
+            "bot_token_command",
+            "chat_id",
+            "topic_id",
+            "events",
+        ],
+    ),
+    (
+        "preferences",
+        &[
+            "primary_messenger",
+            "messenger_priority",
+            "severity_routes",
+            "timeout_seconds",
+            "stop_notifications",
+            "idle_nudges",
+            "llm_summary",
+            "command_explanations",
+            "long_running_tools",
+            "cost_guard",
+            "strict",
+            "redaction",
+            "audit_log",
+            "auto_approval_limits",
+            "auto_approval_windows",
+            "remote_runs",
+            "webhooks",
+            "github_deployments",
+            "web_approvals",
+            "command_api",
+            "policy_modules",
+            "vacation_fallback",
+            "block_in_claude_settings",
+            "tool_display",
+            "request_grouping",
+            "escalation",
+            "request_context_chars",
+            "permission_prompt_dedup_seconds",
+            "read_only_tools",
+            "rule_suggestions",
+            "host_label",
+            "host_emoji",
+            "request_id_length",
+        ],
+    ),
+    (
+        "preferences.auto_approval_windows[]",
+        &["tools", "days", "start", "end", "utc_offset"],
+    ),
+    (
+        "preferences.stop_notifications",
+        &[
+            "min_duration_seconds",
+            "projects",
+            "task_prompt",
+            "broadcast",
+        ],
+    ),
+    ("preferences.idle_nudges", &["enabled", "reminder_minutes"]),
+    (
+        "preferences.llm_summary",
+        &[
+            "enabled",
+            "provider",
+            "endpoint",
+            "model",
+            "api_key",
+            "api_key_env",
+            "max_transcript_chars",
+        ],
+    ),
+    (
+        "preferences.command_explanations",
+        &["enabled", "source", "language"],
+    ),
+    (
+        "preferences.request_grouping",
+        &["enabled", "threshold", "window_seconds"],
+    ),
+    (
+        "preferences.read_only_tools",
+        &["enabled", "tools", "digest_minutes"],
+    ),
+    (
+        "preferences.rule_suggestions",
+        &["enabled", "min_approvals", "days", "interval_hours"],
+    ),
+    (
+        "preferences.escalation",
+        &[
+            "enabled",
+            "after_fraction",
+            "remind",
+            "mention",
+            "messengers",
+        ],
+    ),
+    (
+        "preferences.long_running_tools",
+        &["enabled", "threshold_seconds"],
+    ),
+    (
+        "preferences.cost_guard",
+        &["enabled", "threshold_usd", "confirm", "prices"],
+    ),
+    ("preferences.redaction", &["enabled", "patterns"]),
+    ("preferences.audit_log", &["enabled", "path"]),
+    (
+        "preferences.remote_runs",
+        &["enabled", "projects", "claude_command"],
+    ),
+    ("preferences.webhooks[]", &["url", "events"]),
+    (
+        "preferences.github_deployments",
+        &["enabled", "listen", "secret", "token"],
+    ),
+    (
+        "preferences.web_approvals",
+        &["enabled", "listen", "base_url", "secret", "telegram_login"],
+    ),
+    ("preferences.command_api", &["enabled", "listen", "token"]),
+    (
+        "routes[]",
+        &[
+            "projects",
+            "telegram_chat_id",
+            "telegram_topic_id",
+            "discord_user_id",
+        ],
+    ),
+];
+
+/// Legacy top-level fields superseded by the `messengers` section.
+const LEGACY_KEYS: &[&str] = &["telegram_bot_token", "telegram_chat_id"];
+
+/// Severity of a validation finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single validation finding.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Dotted path of the offending key ("" for the whole file)
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icon = match self.severity {
+            Severity::Error => "❌",
+            Severity::Warning => "⚠️ ",
+        };
+        if self.path.is_empty() {
+            write!(f, "{} {}", icon, self.message)
+        } else {
+            write!(f, "{} {}: {}", icon, self.path, self.message)
+        }
+    }
+}
+
+/// Collects diagnostics while walking the configuration.
+#[derive(Default)]
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn error(&mut self, path: &str, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn warning(&mut self, path: &str, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Report keys not known for the object at `schema_path`, then recurse.
+    fn check_keys(&mut self, object: &Map<String, Value>, path: &str, schema_path: &str) {
+        let Some((_, known)) = KNOWN_KEYS.iter().find(|(p, _)| *p == schema_path) else {
+            return;
+        };
+
+        for (key, value) in object {
+            let child_path = join_path(path, key);
+            if !known.contains(&key.as_str()) {
+                if schema_path.is_empty() && LEGACY_KEYS.contains(&key.as_str()) {
+                    continue;
+                }
+                self.warning(&child_path, "unknown key (ignored)");
+                continue;
+            }
+
+            let child_schema = join_path(schema_path, key);
+            match value {
+                Value::Object(child) => self.check_keys(child, &child_path, &child_schema),
+                Value::Array(items) => {
+                    let item_schema = format!("{}[]", child_schema);
+                    if !KNOWN_KEYS.iter().any(|(p, _)| *p == item_schema) {
+                        continue;
+                    }
+                    for (i, item) in items.iter().enumerate() {
+                        let item_path = format!("{}[{}]", child_path, i);
+                        match item.as_object() {
+                            Some(item) => self.check_keys(item, &item_path, &item_schema),
+                            None => self.error(&item_path, "must be an object"),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Validate the plugin sections; returns the names of enabled plugins.
+    fn check_plugins<'a>(&mut self, plugins: &'a Value) -> Vec<&'a str> {
+        let Some(plugins) = plugins.as_object() else {
+            self.error("messengers.plugins", "must be an object");
+            return Vec::new();
+        };
+
+        let mut enabled = Vec::new();
+        for (name, section) in plugins {
+            let path = join_path("messengers.plugins", name);
+            if MESSENGERS.contains(&name.as_str()) {
+                self.error(&path, "name is taken by a built-in messenger");
+                continue;
+            }
+            let Some(section) = section.as_object() else {
+                self.error(&path, "must be an object");
+                continue;
+            };
+            self.check_keys(section, &path, "messengers.plugins.*");
+            if section.get("enabled").and_then(Value::as_bool) == Some(false) {
+                continue;
+            }
+
+            let command = match section.get("command") {
+                None => default_plugin_dir().join(name),
+                Some(Value::String(command)) => expand_home(command),
+                Some(_) => {
+                    self.error(&join_path(&path, "command"), "must be a string");
+                    continue;
+                }
+            };
+            if !command.is_file() {
+                self.error(
+                    &join_path(&path, "command"),
+                    format!("{} does not exist", command.display()),
+                );
+            }
+            enabled.push(name.as_str());
+        }
+        enabled
+    }
+
+    /// Validate a messenger section; returns whether it is enabled.
+    fn check_messenger(&mut self, name: &str, section: &Value) -> bool {
+        let path = format!("messengers.{}", name);
+        let Some(section) = section.as_object() else {
+            self.error(&path, "must be an object");
+            return false;
+        };
+
+        let enabled = section
+            .get("enabled")
+            .map(|v| v.as_bool().unwrap_or(true))
+            .unwrap_or(true);
+        if !enabled {
+            return false;
+        }
+
+        let required: &[&str] = match name {
+            "telegram" => &["bot_token", "chat_id"],
+            "discord" => &["bot_token", "user_id"],
+            "signal" => &["phone_number"],
+            "homeassistant" => &["mqtt_host"],
+            _ => &[],
+        };
+        let token_sources: Vec<&str> = ["bot_token", "bot_token_file", "bot_token_command"]
+            .into_iter()
+            .filter(|key| section.contains_key(*key))
+            .collect();
+        if token_sources.len() > 1 {
+            self.warning(
+                &path,
+                format!("several token sources set; using `{}`", token_sources[0]),
+            );
+        }
+        if let Some(file) = section.get("bot_token_file").and_then(Value::as_str) {
+            if !expand_home(file).exists() {
+                self.error(&join_path(&path, "bot_token_file"), "file does not exist");
+            }
+        }
+
+        for field in required {
+            if *field == "bot_token" && token_sources.iter().any(|s| *s != "bot_token") {
+                continue;
+            }
+            match section.get(*field) {
+                None => self.error(&path, format!("enabled but missing `{}`", field)),
+                Some(Value::String(s)) if s.is_empty() => {
+                    self.error(&join_path(&path, field), "must not be empty")
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(chat_id) = section.get("chat_id").filter(|_| name == "telegram") {
+            self.check_integer_id(&join_path(&path, "chat_id"), chat_id, true);
+        }
+        if let Some(user_id) = section.get("user_id").filter(|_| name == "discord") {
+            self.check_integer_id(&join_path(&path, "user_id"), user_id, false);
+        }
+        if name == "discord" && !cfg!(feature = "discord") {
+            self.warning(&path, "this binary was built without Discord support");
+        }
+        if name == "signal" && !cfg!(feature = "signal") {
+            self.warning(&path, "this binary was built without Signal support");
+        }
+        if name == "homeassistant" && !cfg!(feature = "homeassistant") {
+            self.warning(
+                &path,
+                "this binary was built without Home Assistant support",
+            );
+        }
+
+        true
+    }
+
+    /// Check that an ID is an integer or a string holding one.
+    fn check_integer_id(&mut self, path: &str, value: &Value, allow_negative: bool) {
+        let valid = match value {
+            Value::Number(n) => n.is_i64() && (allow_negative || n.is_u64()),
+            Value::String(s) => {
+                if allow_negative {
+                    s.parse::<i64>().is_ok()
+                } else {
+                    s.parse::<u64>().is_ok()
+                }
+            }
+            _ => false,
+        };
+        if !valid {
+            self.error(path, format!("invalid ID {}", value));
+        }
+    }
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+/// Validate configuration file content and return all findings.
+pub fn validate_content(content: &str) -> Vec<Diagnostic> {
+    let mut v = Validator::default();
+
+    let root: Value = match serde_json::from_str(content) {
+        Ok(root) => root,
+        Err(e) => {
+            v.error("", format!("invalid JSON: {}", e));
+            return v.diagnostics;
+        }
+    };
+    let Some(object) = root.as_object() else {
+        v.error("", "configuration must be a JSON object");
+        return v.diagnostics;
+    };
+
+    if !object.contains_key("messengers") {
+        if LEGACY_KEYS.iter().any(|k| object.contains_key(*k)) {
+            v.warning(
+                "",
+                "legacy format (telegram_bot_token/telegram_chat_id) is deprecated; \
+                run `config migrate` to convert it",
+            );
+            for key in LEGACY_KEYS {
+                if !object.contains_key(*key) {
+                    v.error("", format!("legacy format missing `{}`", key));
+                }
+            }
+            if let Some(chat_id) = object.get("telegram_chat_id") {
+                v.check_integer_id("telegram_chat_id", chat_id, true);
+            }
+        } else {
+            v.error("", "missing `messengers` section");
+        }
+        v.check_keys(object, "", "");
+        return v.diagnostics;
+    }
+
+    for key in LEGACY_KEYS {
+        if object.contains_key(*key) {
+            v.warning(key, "legacy field is ignored when `messengers` is present");
+        }
+    }
+
+    v.check_keys(object, "", "");
+
+    // Messenger sections
+    let mut enabled = Vec::new();
+    if let Some(messengers) = object.get("messengers").and_then(Value::as_object) {
+        for name in MESSENGERS {
+            if let Some(section) = messengers.get(*name) {
+                if v.check_messenger(name, section) {
+                    enabled.push(*name);
+                }
+            }
+        }
+        if let Some(plugins) = messengers.get("plugins") {
+            enabled.extend(v.check_plugins(plugins));
+        }
+        let chat_id = |section: Option<&Value>| {
+            section
+                .and_then(|section| section.get("chat_id"))
+                .map(|id| id.as_str().map_or_else(|| id.to_string(), str::to_string))
+        };
+        let observer_chat = chat_id(messengers.get("observer"));
+        if observer_chat.is_some() && observer_chat == chat_id(messengers.get("telegram")) {
+            v.error(
+                "messengers.observer.chat_id",
+                "must differ from messengers.telegram.chat_id",
+            );
+        }
+    } else {
+        v.error("messengers", "must be an object");
+    }
+    if enabled.is_empty() {
+        v.error("messengers", "no messenger is enabled");
+    }
+
+    // Preferences
+    let plugins = object
+        .get("messengers")
+        .and_then(|m| m.get("plugins"))
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|plugins| plugins.keys().map(String::as_str));
+    let known: Vec<&str> = MESSENGERS.iter().copied().chain(plugins).collect();
+    let preferences = object.get("preferences");
+    if let Some(primary) = preferences.and_then(|p| p.get("primary_messenger")) {
+        match primary.as_str() {
+            Some(name) if !known.contains(&name) => v.error(
+                "preferences.primary_messenger",
+                format!("unknown messenger \"{}\"", name),
+            ),
+            Some(name) if !enabled.is_empty() && !enabled.contains(&name) => v.warning(
+                "preferences.primary_messenger",
+                format!(
+                    "\"{}\" is not configured or disabled; falling back to {}",
+                    name, enabled[0]
+                ),
+            ),
+            Some(_) => {}
+            None => v.error("preferences.primary_messenger", "must be a string"),
+        }
+    }
+    if let Some(priority) = preferences.and_then(|p| p.get("messenger_priority")) {
+        let path = "preferences.messenger_priority";
+        match priority.as_array() {
+            Some(names) => {
+                let names: Vec<&str> = names.iter().filter_map(Value::as_str).collect();
+                for name in &names {
+                    if !known.contains(name) {
+                        v.error(path, format!("unknown messenger \"{}\"", name));
+                    }
+                }
+                for name in &enabled {
+                    if !names.contains(name) {
+                        v.warning(path, format!("\"{}\" is enabled but never used", name));
+                    }
+                }
+                if !enabled.is_empty() && !names.iter().any(|n| enabled.contains(n)) {
+                    v.error(path, "lists no enabled messenger");
+                }
+            }
+            None => v.error(path, "must be a list of messenger names"),
+        }
+        if preferences
+            .and_then(|p| p.get("primary_messenger"))
+            .is_some()
+        {
+            v.warning(
+                "preferences.primary_messenger",
+                "ignored because messenger_priority is set",
+            );
+        }
+    }
+    if let Some(routes) = preferences.and_then(|p| p.get("severity_routes")) {
+        match routes.as_object() {
+            Some(routes) => {
+                for (severity, names) in routes {
+                    let path = format!("preferences.severity_routes.{}", severity);
+                    if !["high", "normal", "info"].contains(&severity.as_str()) {
+                        v.error(&path, "unknown severity; expected high, normal or info");
+                        continue;
+                    }
+                    let Some(names) = names.as_array() else {
+                        v.error(&path, "must be a list of messenger names");
+                        continue;
+                    };
+                    let names: Vec<&str> = names.iter().filter_map(Value::as_str).collect();
+                    for name in &names {
+                        if !known.contains(name) {
+                            v.error(&path, format!("unknown messenger \"{}\"", name));
+                        }
+                    }
+                    if !enabled.is_empty() && !names.iter().any(|n| enabled.contains(n)) {
+                        v.error(&path, "lists no enabled messenger");
+                    }
+                }
+            }
+            None => v.error(
+                "preferences.severity_routes",
+                "must map severities to messenger lists",
+            ),
+        }
+    }
+    if let Some(timeout) = preferences.and_then(|p| p.get("timeout_seconds")) {
+        if timeout.as_u64().map_or(true, |t| t == 0) {
+            v.error("preferences.timeout_seconds", "must be a positive integer");
+        }
+    }
+    if let Some(github) = preferences
+        .and_then(|p| p.get("github_deployments"))
+        .filter(|github| github.get("enabled").and_then(Value::as_bool) == Some(true))
+    {
+        for field in ["secret", "token"] {
+            if github.get(field).is_none() {
+                v.error(
+                    "preferences.github_deployments",
+                    format!("enabled but missing `{}`", field),
+                );
+            }
+        }
+    }
+    if let Some(web) = preferences
+        .and_then(|p| p.get("web_approvals"))
+        .filter(|web| web.get("enabled").and_then(Value::as_bool) == Some(true))
+    {
+        for field in ["base_url", "secret"] {
+            if web.get(field).is_none() {
+                v.error(
+                    "preferences.web_approvals",
+                    format!("enabled but missing `{}`", field),
+                );
+            }
+        }
+        if web.get("telegram_login").is_some() {
+            if !enabled.contains(&"telegram") {
+                v.error(
+                    "preferences.web_approvals.telegram_login",
+                    "needs messengers.telegram to check logins",
+                );
+            }
+            let responders = object
+                .get("authorized_responders")
+                .and_then(|r| r.get("telegram"))
+                .and_then(Value::as_array);
+            if responders.map_or(true, Vec::is_empty) {
+                v.error(
+                    "preferences.web_approvals.telegram_login",
+                    "needs a non-empty authorized_responders.telegram",
+                );
+            }
+        }
+    }
+    if preferences
+        .and_then(|p| p.get("command_api"))
+        .is_some_and(|api| {
+            api.get("enabled").and_then(Value::as_bool) == Some(true) && api.get("token").is_none()
+        })
+    {
+        v.error("preferences.command_api", "enabled but missing `token`");
+    }
+    if let Some(threshold) = preferences
+        .and_then(|p| p.get("cost_guard"))
+        .and_then(|guard| guard.get("threshold_usd"))
+        .and_then(Value::as_f64)
+    {
+        if threshold <= 0.0 {
+            v.error("preferences.cost_guard.threshold_usd", "must be above 0");
+        }
+    }
+    if let Some(modules) = preferences.and_then(|p| p.get("policy_modules")) {
+        let path = "preferences.policy_modules";
+        match modules.as_array() {
+            Some(modules) => {
+                if !modules.is_empty() && !cfg!(feature = "wasm") {
+                    v.warning(path, "ignored (compile with --features wasm)");
+                }
+                for (i, module) in modules.iter().enumerate() {
+                    let item_path = format!("{}[{}]", path, i);
+                    match module.as_str() {
+                        Some(module) if !expand_home(module).is_file() => {
+                            v.error(&item_path, "file does not exist")
+                        }
+                        Some(_) => {}
+                        None => v.error(&item_path, "must be a path"),
+                    }
+                }
+            }
+            None => v.error(path, "must be a list of paths"),
+        }
+    }
+
+    // Routes
+    if let Some(routes) = object.get("routes").and_then(Value::as_array) {
+        for (i, route) in routes.iter().enumerate() {
+            let path = format!("routes[{}]", i);
+            let projects = route.get("projects").and_then(Value::as_array);
+            if projects.map_or(true, |p| p.is_empty()) {
+                v.error(&path, "missing non-empty `projects` list");
+            }
+            if let Some(chat_id) = route.get("telegram_chat_id") {
+                v.check_integer_id(&join_path(&path, "telegram_chat_id"), chat_id, true);
+            }
+            if let Some(user_id) = route.get("discord_user_id") {
+                v.check_integer_id(&join_path(&path, "discord_user_id"), user_id, false);
+            }
+        }
+    }
+
+    if let Some(patterns) = preferences
+        .and_then(|p| p.pointer("/redaction/patterns"))
+        .and_then(Value::as_array)
+    {
+        for (i, pattern) in patterns.iter().enumerate() {
+            let path = format!("preferences.redaction.patterns[{}]", i);
+            match pattern.as_str().map(regex::Regex::new) {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => v.error(&path, format!("invalid regex: {}", e)),
+                None => v.error(&path, "must be a string"),
+            }
+        }
+    }
+
+    if let Some(limits) = preferences.and_then(|p| p.get("auto_approval_limits")) {
+        match limits.as_object() {
+            Some(limits) => {
+                for (tool, limit) in limits {
+                    if limit.as_u64().is_none() {
+                        let path = format!("preferences.auto_approval_limits.{}", tool);
+                        v.error(&path, "must be a non-negative integer");
+                    }
+                }
+            }
+            None => v.error(
+                "preferences.auto_approval_limits",
+                "must map tool names to hourly limits",
+            ),
+        }
+    }
+
+    if let Some(windows) = preferences
+        .and_then(|p| p.get("auto_approval_windows"))
+        .and_then(Value::as_array)
+    {
+        for (i, window) in windows.iter().enumerate() {
+            let path = format!("preferences.auto_approval_windows[{}]", i);
+            for key in ["start", "end"] {
+                match window.get(key).and_then(Value::as_str) {
+                    Some(time) if schedule::parse_clock(time).is_some() => {}
+                    Some(_) => v.error(&join_path(&path, key), "must be a time like \"09:00\""),
+                    None => v.error(&path, format!("missing `{}` time", key)),
+                }
+            }
+            if let Some(days) = window.get("days") {
+                let days: Option<Vec<String>> = days
+                    .as_array()
+                    .and_then(|days| days.iter().map(|d| d.as_str().map(String::from)).collect());
+                if days.as_deref().and_then(schedule::parse_days).is_none() {
+                    v.error(
+                        &join_path(&path, "days"),
+                        "must list days like \"mon\", \"weekdays\" or \"weekends\"",
+                    );
+                }
+            }
+            if let Some(offset) = window.get("utc_offset") {
+                if offset.as_str().and_then(schedule::parse_offset).is_none() {
+                    v.error(
+                        &join_path(&path, "utc_offset"),
+                        "must be an offset like \"+09:00\"",
+                    );
+                }
+            }
+        }
+    }
+
+    // Authorized responders
+    if let Some(responders) = object.get("authorized_responders") {
+        for platform in ["telegram", "discord"] {
+            let Some(ids) = responders.get(platform) else {
+                continue;
+            };
+            let path = format!("authorized_responders.{}", platform);
+            match ids.as_array() {
+                Some(ids) => {
+                    for (i, id) in ids.iter().enumerate() {
+                        v.check_integer_id(&format!("{}[{}]", path, i), id, false);
+                    }
+                }
+                None => v.error(&path, "must be a list of user IDs"),
+            }
+        }
+        if let Some(uuids) = responders.get("signal") {
+            let valid = uuids.as_array().is_some_and(|uuids| {
+                uuids
+                    .iter()
+                    .all(|u| u.as_str().is_some_and(|s| uuid::Uuid::parse_str(s).is_ok()))
+            });
+            if !valid {
+                v.error("authorized_responders.signal", "must be a list of UUIDs");
+            }
+        }
+    }
+
+    if let Some(rules) = object.get("approval_rules").and_then(Value::as_array) {
+        for (i, rule) in rules.iter().enumerate() {
+            let path = format!("approval_rules[{}].require_quorum", i);
+            match rule.get("require_quorum") {
+                None => {}
+                Some(quorum) if quorum.as_u64().is_some_and(|n| n >= 1) => {}
+                Some(_) => v.error(&path, "must be a positive integer"),
+            }
+        }
+    }
+
+    v.diagnostics
+}
+
+/// Validate a configuration file, print diagnostics, and return the error count.
+pub fn run(path: &Path) -> anyhow::Result<usize> {
+    if !path.exists() {
+        anyhow::bail!("Configuration file not found: {}", path.display());
+    }
+
+    let content = fs::read_to_string(path)?;
+    let mut diagnostics = validate_content(&content);
+
+    // Catch anything the structural checks missed
+    let has_errors = diagnostics.iter().any(|d| d.severity == Severity::Error);
+    if !has_errors {
+        if let Err(e) = Config::from_json(path) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                path: String::new(),
+                message: format!("failed to load: {}", e),
+            });
+        }
+    }
+
+    println!("🔍 Validating {}\n", path.display());
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+
+    if diagnostics.is_empty() {
+        println!("✅ Configuration is valid");
+    } else {
+        println!("\n{} error(s), {} warning(s)", errors, warnings);
+    }
+
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(content: &str) -> Vec<String> {
+        validate_content(content)
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let diagnostics = validate_content(
+            r#"{
+                "messengers": {"telegram": {"bot_token": "t", "chat_id": "-100123"}},
+                "preferences": {"primary_messenger": "telegram", "timeout_seconds": 60}
+            }"#,
+        );
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"telegram": {"bot_token": "t", "chat_id": 1, "chatid": 2}},
+                "preferences": {"timeout": 60}
+            }"#,
+        );
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("messengers.telegram.chatid: unknown key")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("preferences.timeout: unknown key")));
+    }
+
+    #[test]
+    fn test_missing_fields_and_invalid_ids() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"telegram": {"chat_id": "not-a-number"}},
+                "routes": [{"telegram_chat_id": "abc"}]
+            }"#,
+        );
+        assert!(msgs.iter().any(|m| m.contains("missing `bot_token`")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("messengers.telegram.chat_id: invalid ID")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("routes[0]: missing non-empty `projects`")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("routes[0].telegram_chat_id: invalid ID")));
+    }
+
+    #[test]
+    fn test_primary_messenger_checks() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"telegram": {"bot_token": "t", "chat_id": 1}},
+                "preferences": {"primary_messenger": "discord"}
+            }"#,
+        );
+        assert!(msgs.iter().any(|m| m.contains("falling back to telegram")));
+
+        let msgs = messages(
+            r#"{
+                "messengers": {"telegram": {"bot_token": "t", "chat_id": 1}},
+                "preferences": {"primary_messenger": "slack"}
+            }"#,
+        );
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("unknown messenger \"slack\"")));
+    }
+
+    #[test]
+    fn test_messenger_priority_checks() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"telegram": {"bot_token": "t", "chat_id": 1}},
+                "preferences": {"messenger_priority": ["discord", "pager"]}
+            }"#,
+        );
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("unknown messenger \"pager\"")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("\"telegram\" is enabled but never used")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("lists no enabled messenger")));
+    }
+
+    #[test]
+    fn test_severity_routes_checks() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"telegram": {"bot_token": "t", "chat_id": 1}},
+                "preferences": {"severity_routes": {
+                    "high": ["pager"],
+                    "normal": ["telegram"],
+                    "urgent": ["telegram"]
+                }}
+            }"#,
+        );
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("preferences.severity_routes.high: unknown messenger \"pager\"")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("preferences.severity_routes.high: lists no enabled messenger")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("preferences.severity_routes.urgent: unknown severity")));
+        assert!(!msgs
+            .iter()
+            .any(|m| m.contains("preferences.severity_routes.normal")));
+    }
+
+    #[test]
+    fn test_authorized_responders() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"telegram": {"bot_token": "t", "chat_id": 1}},
+                "authorized_responders": {
+                    "telegram": [123, "456"],
+                    "discord": ["abc"],
+                    "signal": ["not-a-uuid"]
+                }
+            }"#,
+        );
+        assert_eq!(msgs.len(), 2, "{:?}", msgs);
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("authorized_responders.discord[0]: invalid ID")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("authorized_responders.signal: must be a list of UUIDs")));
+    }
+
+    #[test]
+    fn test_bot_token_sources() {
+        let msgs = messages(
+            r#"{"messengers": {"telegram": {"bot_token_command": "pass show t", "chat_id": 1}}}"#,
+        );
+        assert!(msgs.is_empty(), "{:?}", msgs);
+
+        let msgs = messages(
+            r#"{"messengers": {"telegram": {
+                "bot_token": "t", "bot_token_file": "/nonexistent/token", "chat_id": 1
+            }}}"#,
+        );
+        assert!(msgs.iter().any(|m| m.contains("several token sources")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("bot_token_file: file does not exist")));
+    }
+
+    #[test]
+    fn test_observer_chat() {
+        let msgs = messages(
+            r#"{"messengers": {
+                "telegram": {"bot_token": "t", "chat_id": "-100123"},
+                "observer": {"chat_id": -100123}
+            }}"#,
+        );
+        assert_eq!(msgs.len(), 1, "{:?}", msgs);
+        assert!(msgs[0].contains("messengers.observer.chat_id: must differ"));
+    }
+
+    #[test]
+    fn test_no_enabled_messenger() {
+        let msgs = messages(
+            r#"{"messengers": {"telegram": {"enabled": false, "bot_token": "t", "chat_id": 1}}}"#,
+        );
+        assert!(msgs.iter().any(|m| m.contains("no messenger is enabled")));
+    }
+
+    #[test]
+    fn test_plugins() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"plugins": {
+                    "sms": {"command": "/bin/sh", "settings": {"to": "+15550100"}},
+                    "pager": {"command": "/nonexistent/pager", "args": []},
+                    "off": {"enabled": false},
+                    "discord": {"command": "/bin/sh"}
+                }},
+                "preferences": {"messenger_priority": ["sms", "pager", "off"]}
+            }"#,
+        );
+        assert_eq!(msgs.len(), 3, "{:?}", msgs);
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("plugins.pager.command: /nonexistent/pager does not exist")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("plugins.pager.args: unknown key")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("plugins.discord: name is taken by a built-in messenger")));
+    }
+
+    #[test]
+    fn test_auto_approval_windows() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"telegram": {"bot_token": "t", "chat_id": 1}},
+                "preferences": {"auto_approval_windows": [
+                    {"tools": ["Bash"], "days": ["weekdays"], "start": "09:00", "end": "18:00"},
+                    {"days": ["someday"], "start": "9am", "utc_offset": "JST", "hours": 8}
+                ]}
+            }"#,
+        );
+        assert_eq!(msgs.len(), 5, "{:?}", msgs);
+        assert!(msgs.iter().any(|m| m.contains("[1].hours: unknown key")));
+        assert!(msgs.iter().any(|m| m.contains("[1].start: must be a time")));
+        assert!(msgs.iter().any(|m| m.contains("missing `end` time")));
+        assert!(msgs.iter().any(|m| m.contains("[1].days: must list days")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("[1].utc_offset: must be an offset")));
+    }
+
+    #[test]
+    fn test_web_approvals_telegram_login() {
+        let msgs = messages(
+            r#"{
+                "messengers": {"plugins": {"sms": {"command": "/bin/sh"}}},
+                "preferences": {"web_approvals": {
+                    "enabled": true, "base_url": "https://a.example.com", "secret": "s",
+                    "telegram_login": "my_bot"
+                }}
+            }"#,
+        );
+        assert_eq!(msgs.len(), 2, "{:?}", msgs);
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("needs messengers.telegram to check logins")));
+        assert!(msgs
+            .iter()
+            .any(|m| m.contains("needs a non-empty authorized_responders.telegram")));
+    }
+
+    #[test]
+    fn test_legacy_format_deprecated() {
+        let diagnostics =
+            validate_content(r#"{"telegram_bot_token": "t", "telegram_chat_id": "123"}"#);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("deprecated"));
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let diagnostics = validate_content("{not json");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(diagnostics[0].message.contains("invalid JSON"));
+    }
+}
@@ -0,0 +1,287 @@
+//! Live streaming of a session transcript.
+//!
+//! `watch` tails a transcript JSONL file and forwards assistant messages and
+//! tool calls to the messenger as they are appended, batching whatever
+//! arrived during each poll interval into one message.
+
+use crate::config::{default_claude_projects_path, Config};
+use crate::messenger::{enabled_messengers, Messenger, TextFormat};
+use crate::redact::Redactor;
+use crate::tool_timer::describe_tool;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Maximum characters per streamed message, below every platform's limit.
+const MAX_MESSAGE_CHARS: usize = 3500;
+
+/// Maximum characters of assistant text quoted per event.
+const MAX_TEXT_CHARS: usize = 500;
+
+/// Reads lines appended to a file since the last call.
+pub struct FileTail {
+    path: PathBuf,
+    offset: u64,
+    /// Trailing bytes of an incomplete line
+    partial: Vec<u8>,
+}
+
+impl FileTail {
+    /// Start tailing at the current end of the file.
+    pub fn at_end(path: PathBuf) -> Self {
+        let offset = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        Self {
+            path,
+            offset,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Read complete lines appended since the last call.
+    ///
+    /// Starts over from the beginning if the file was truncated or replaced.
+    pub fn read_new_lines(&mut self) -> io::Result<Vec<String>> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len == self.offset {
+            return Ok(Vec::new());
+        }
+
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut buffer = std::mem::take(&mut self.partial);
+        let read = file.take(len - self.offset).read_to_end(&mut buffer)?;
+        self.offset += read as u64;
+
+        // Keep an unterminated last line for the next call
+        let complete = buffer
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        self.partial = buffer.split_off(complete);
+
+        Ok(String::from_utf8_lossy(&buffer)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Find the transcript of a session under the Claude Code projects directory.
+pub fn find_session_transcript(projects_dir: &Path, session_id: &str) -> Option<PathBuf> {
+    let file_name = format!("{}.jsonl", session_id);
+    fs::read_dir(projects_dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|project| project.path().join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// Truncate text to a maximum number of characters, adding an ellipsis.
+fn truncate(text: &str, max_chars: usize) -> String {
+    let truncated: String = text.chars().take(max_chars).collect();
+    if text.chars().count() > max_chars {
+        format!("{}...", truncated)
+    } else {
+        truncated
+    }
+}
+
+/// Describe the assistant text and tool calls in one transcript line.
+fn format_entry(line: &str) -> Vec<String> {
+    let Ok(entry) = serde_json::from_str::<Value>(line) else {
+        return Vec::new();
+    };
+    if entry.get("type").and_then(Value::as_str) != Some("assistant") {
+        return Vec::new();
+    }
+
+    let content = entry.pointer("/message/content");
+    let blocks = match content {
+        Some(Value::String(text)) => {
+            return vec![format!("💬 {}", truncate(text.trim(), MAX_TEXT_CHARS))];
+        }
+        Some(Value::Array(blocks)) => blocks,
+        _ => return Vec::new(),
+    };
+
+    blocks
+        .iter()
+        .filter_map(|block| match block.get("type").and_then(Value::as_str)? {
+            "text" => {
+                let text = block.get("text").and_then(Value::as_str)?.trim();
+                (!text.is_empty()).then(|| format!("💬 {}", truncate(text, MAX_TEXT_CHARS)))
+            }
+            "tool_use" => {
+                let name = block.get("name").and_then(Value::as_str)?;
+                let input = block.get("input").unwrap_or(&Value::Null);
+                Some(format!("🔧 {}", describe_tool(name, input)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Group events into messages of at most `max_chars` characters each.
+fn batch_events(header: &str, events: &[String], max_chars: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = header.to_string();
+
+    for event in events {
+        if current.len() > header.len() && current.len() + event.len() + 2 > max_chars {
+            messages.push(std::mem::replace(&mut current, header.to_string()));
+        }
+        current.push_str("\n\n");
+        current.push_str(event);
+    }
+    if current.len() > header.len() {
+        messages.push(current);
+    }
+    messages
+}
+
+/// Send text via the first messenger in priority order that delivers it.
+async fn send(messengers: &[Box<dyn Messenger>], text: &str) {
+    for messenger in messengers {
+        match messenger.send_text(text, TextFormat::Plain).await {
+            Ok(()) => return,
+            Err(e) => tracing::warn!("Failed to stream via {}: {}", messenger.platform_name(), e),
+        }
+    }
+}
+
+/// Stream new transcript activity until interrupted.
+pub async fn run(
+    config_path: Option<&Path>,
+    session: Option<&str>,
+    transcript: Option<&Path>,
+    interval_seconds: u64,
+) -> Result<()> {
+    let path = match (transcript, session) {
+        (Some(path), _) => path.to_path_buf(),
+        (None, Some(session)) => find_session_transcript(&default_claude_projects_path(), session)
+            .with_context(|| format!("No transcript found for session {}", session))?,
+        (None, None) => anyhow::bail!("Pass --session or --transcript"),
+    };
+
+    let config = Config::load(config_path.map(Path::to_path_buf))?;
+    let messengers = enabled_messengers(&config);
+    if messengers.is_empty() {
+        anyhow::bail!("No messenger configured");
+    }
+
+    let label = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let header = format!("📡 {} · {}", config.host_label, truncate(&label, 8));
+    println!("Watching {} (Ctrl-C to stop)", path.display());
+
+    let redactor = Redactor::new(&config.redaction);
+    let mut tail = FileTail::at_end(path);
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval_seconds.max(1)));
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        let events: Vec<String> = tail
+            .read_new_lines()
+            .context("Failed to read transcript")?
+            .iter()
+            .flat_map(|line| format_entry(line))
+            .collect();
+
+        for message in batch_events(&header, &events, MAX_MESSAGE_CHARS) {
+            send(&messengers, &redactor.redact(&message)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tail_reads_only_complete_new_lines() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("session.jsonl");
+        fs::write(&path, "{\"old\":1}\n").unwrap();
+
+        let mut tail = FileTail::at_end(path.clone());
+        assert!(tail.read_new_lines().unwrap().is_empty());
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"a\":1}}\n{{\"b\"").unwrap();
+        assert_eq!(tail.read_new_lines().unwrap(), vec!["{\"a\":1}"]);
+
+        writeln!(file, ":2}}").unwrap();
+        assert_eq!(tail.read_new_lines().unwrap(), vec!["{\"b\":2}"]);
+
+        // Truncation starts over from the beginning
+        fs::write(&path, "{\"c\":3}\n").unwrap();
+        assert_eq!(tail.read_new_lines().unwrap(), vec!["{\"c\":3}"]);
+    }
+
+    #[test]
+    fn test_format_entry() {
+        let line = serde_json::json!({
+            "type": "assistant",
+            "message": {"content": [
+                {"type": "text", "text": "Running the tests."},
+                {"type": "tool_use", "name": "Bash", "input": {"command": "cargo test"}},
+                {"type": "thinking", "thinking": "..."}
+            ]}
+        })
+        .to_string();
+        assert_eq!(
+            format_entry(&line),
+            vec!["💬 Running the tests.", "🔧 Bash `cargo test`"]
+        );
+
+        let user = r#"{"type":"user","message":{"content":"hi"}}"#;
+        assert!(format_entry(user).is_empty());
+        assert!(format_entry("not json").is_empty());
+    }
+
+    #[test]
+    fn test_find_session_transcript() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("-home-user-app");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("abc123.jsonl"), "").unwrap();
+
+        assert_eq!(
+            find_session_transcript(dir.path(), "abc123"),
+            Some(project.join("abc123.jsonl"))
+        );
+        assert!(find_session_transcript(dir.path(), "missing").is_none());
+    }
+
+    #[test]
+    fn test_batch_events() {
+        let events = vec!["a".repeat(30), "b".repeat(30), "c".repeat(30)];
+        let messages = batch_events("H", &events, 70);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("H\n\n") && messages[0].contains(&events[1]));
+        assert!(messages[1].contains(&events[2]));
+
+        assert!(batch_events("H", &[], 70).is_empty());
+    }
+}
@@ -0,0 +1,476 @@
+//! Approving requests from a browser through signed links.
+//!
+//! One-line requests often end up on SMS gateways and watches that drop
+//! buttons. With `preferences.web_approvals` enabled they end with a link to
+//! the daemon, signed with `secret` and valid until the request times out.
+//! Opening it shows the request with Allow and Deny buttons, and the decision
+//! is left in the request ledger for the waiting hook process to pick up,
+//! like `/allow_all` in the bot.
+//!
+//! With `telegram_login` set to the bot's username, the page also asks for a
+//! Telegram login (the bot's domain must be set with BotFather's `/setdomain`)
+//! and only authorized Telegram responders can decide. This needs
+//! `messengers.telegram` and a non-empty `authorized_responders.telegram`;
+//! without them the links are refused.
+
+use crate::away::telegram_responder;
+use crate::clock::now_secs;
+use crate::config::{default_request_ledger_path, Config, WebApprovalConfig};
+use crate::http::{
+    form_value, hex, hex_matches, hmac_sha256, load_config_for_request, read_request,
+    write_response, HttpRequest, READ_TIMEOUT,
+};
+use crate::lockfile::off_reactor;
+use crate::messenger::ledger::{self, Resolution};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Path the links are served under.
+const LINK_PATH: &str = "/approve/";
+
+/// Telegram logins older than this are not accepted.
+const LOGIN_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+
+/// Signature of a link to a request, valid until `expires`.
+fn signature(secret: &str, request_id: &str, expires: u64) -> String {
+    let message = format!("{}:{}", request_id, expires);
+    hex(&hmac_sha256(secret.as_bytes(), message.as_bytes()))
+}
+
+/// The approval link for a request, if links are set up.
+pub fn link(settings: &WebApprovalConfig, request_id: &str, timeout: Duration) -> Option<String> {
+    link_until(settings, request_id, now_secs() + timeout.as_secs())
+}
+
+fn link_until(settings: &WebApprovalConfig, request_id: &str, expires: u64) -> Option<String> {
+    if !settings.enabled {
+        return None;
+    }
+    let base_url = settings.base_url.as_deref()?.trim_end_matches('/');
+    let secret = settings
+        .secret
+        .as_deref()
+        .filter(|secret| !secret.is_empty())?;
+    Some(format!(
+        "{}{}{}?exp={}&sig={}",
+        base_url,
+        LINK_PATH,
+        request_id,
+        expires,
+        signature(secret, request_id, expires)
+    ))
+}
+
+/// Whether a link's signature is valid and it has not expired.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn link_valid(secret: &str, request_id: &str, expires: u64, sig: &str, now: u64) -> bool {
+    now <= expires && hex_matches(&signature(secret, request_id, expires), sig)
+}
+
+/// Check a Telegram Login Widget result, returning the user's ID and name.
+///
+/// The widget signs the user's fields with a key derived from the bot token;
+/// see <https://core.telegram.org/widgets/login#checking-authorization>.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn telegram_user(bot_token: &str, login: &Value, now: u64) -> Option<(u64, String)> {
+    let fields = login.as_object()?;
+    let hash = fields.get("hash")?.as_str()?;
+    let mut check: Vec<String> = fields
+        .iter()
+        .filter(|(key, _)| key.as_str() != "hash")
+        .map(|(key, value)| match value {
+            Value::String(text) => format!("{}={}", key, text),
+            other => format!("{}={}", key, other),
+        })
+        .collect();
+    check.sort();
+    let key = Sha256::digest(bot_token.as_bytes());
+    let expected = hex(&hmac_sha256(&key, check.join("\n").as_bytes()));
+    let auth_date = fields.get("auth_date")?.as_u64()?;
+    if !hex_matches(&expected, hash) || now.saturating_sub(auth_date) > LOGIN_MAX_AGE_SECS {
+        return None;
+    }
+
+    let id = fields.get("id")?.as_u64()?;
+    let name = match fields.get("username").and_then(Value::as_str) {
+        Some(username) => format!("@{}", username),
+        None => fields
+            .get("first_name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+    };
+    Some((id, format!("{} ({})", name, id)))
+}
+
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// A page with a heading and some body HTML.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>{0}</title></head>\n<body style=\"font-family: sans-serif; max-width: 32em; margin: 2em auto; padding: 0 1em\">\
+         \n<h1>{0}</h1>\n{1}\n</body></html>\n",
+        escape_html(title),
+        body
+    )
+}
+
+/// The request and its Allow and Deny buttons.
+///
+/// With a Telegram login the buttons stay hidden until the login widget
+/// hands over the signed user, which is then posted with the decision.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn approval_page(
+    request_id: &str,
+    summary: &str,
+    host: &str,
+    telegram_login: Option<&str>,
+) -> String {
+    let form = |decision: &str, label: &str| {
+        format!(
+            "<form method=\"post\" style=\"display: inline\">\
+             <input type=\"hidden\" name=\"decision\" value=\"{}\">\
+             <input type=\"hidden\" name=\"telegram\" value=\"\">\
+             <button type=\"submit\" style=\"font-size: 1.2em; padding: 0.5em 1em\">{}</button></form>",
+            decision, label
+        )
+    };
+    let buttons = format!("{} {}", form("allow", "✅ Allow"), form("deny", "❌ Deny"));
+    let mut body = format!(
+        "<p>🖥️ {}</p>\n<p><code>{}</code></p>\n<p>Request [{}]</p>\n",
+        escape_html(host),
+        escape_html(summary),
+        escape_html(request_id)
+    );
+    match telegram_login {
+        Some(bot) => body.push_str(&format!(
+            "<script async src=\"https://telegram.org/js/telegram-widget.js?22\" \
+             data-telegram-login=\"{}\" data-size=\"large\" data-onauth=\"login(user)\"></script>\n\
+             <script>function login(user) {{\n\
+             for (const field of document.querySelectorAll('input[name=telegram]')) field.value = JSON.stringify(user);\n\
+             document.getElementById('decide').hidden = false;\n}}</script>\n\
+             <div id=\"decide\" hidden>{}</div>",
+            escape_html(bot),
+            buttons
+        )),
+        None => body.push_str(&buttons),
+    }
+    page("🔐 Permission Request", &body)
+}
+
+/// Decide the response to a request for a link, leaving any decision in
+/// the ledger.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn respond(request: &HttpRequest, config: &Config) -> ((u16, &'static str), String) {
+    let settings = &config.web_approvals;
+    let Some(secret) = settings
+        .secret
+        .as_deref()
+        .filter(|secret| !secret.is_empty())
+    else {
+        return (
+            (503, "Service Unavailable"),
+            page("Approval links are off", ""),
+        );
+    };
+    // Without the bot token the login signature could be made by anyone, and
+    // without an allowlist every Telegram user would be let in
+    let bot_token = config
+        .telegram
+        .as_ref()
+        .map(|telegram| telegram.bot_token.as_str())
+        .filter(|token| !token.is_empty());
+    if settings.telegram_login.is_some()
+        && (bot_token.is_none() || config.authorized_responders.telegram.is_empty())
+    {
+        return (
+            (503, "Service Unavailable"),
+            page("Telegram login is not set up", ""),
+        );
+    }
+    let Some(request_id) = request.path().strip_prefix(LINK_PATH) else {
+        return ((404, "Not Found"), page("Not found", ""));
+    };
+    let expires = request.query("exp").and_then(|exp| exp.parse().ok());
+    let sig = request.query("sig").unwrap_or_default();
+    if !expires.is_some_and(|expires| link_valid(secret, request_id, expires, &sig, now_secs())) {
+        return (
+            (403, "Forbidden"),
+            page("This link is invalid or has expired", ""),
+        );
+    }
+    if let Some(notice) = ledger::closed_notice(request_id) {
+        return ((410, "Gone"), page(&notice, ""));
+    }
+
+    match request.method.as_str() {
+        "GET" => {
+            let details = ledger::details(request_id).unwrap_or_default();
+            let host = if details.host.is_empty() {
+                &config.host_label
+            } else {
+                &details.host
+            };
+            let page = approval_page(
+                request_id,
+                &details.summary,
+                host,
+                settings.telegram_login.as_deref(),
+            );
+            ((200, "OK"), page)
+        }
+        "POST" => {
+            let form = String::from_utf8_lossy(&request.body);
+            let allow = match form_value(&form, "decision").as_deref() {
+                Some("allow") => true,
+                Some("deny") => false,
+                _ => return ((400, "Bad Request"), page("Unknown decision", "")),
+            };
+            let by = if let Some(bot_token) = settings.telegram_login.as_ref().and(bot_token) {
+                let login: Option<Value> = form_value(&form, "telegram")
+                    .and_then(|login| serde_json::from_str(&login).ok());
+                let user = login
+                    .as_ref()
+                    .and_then(|login| telegram_user(bot_token, login, now_secs()));
+                match user {
+                    Some((id, name))
+                        if telegram_responder(&config.authorized_responders.telegram, id) =>
+                    {
+                        name
+                    }
+                    Some((_, name)) => {
+                        tracing::warn!(
+                            "Ignoring decision on request {} from unauthorized Telegram user {}",
+                            request_id,
+                            name
+                        );
+                        return (
+                            (403, "Forbidden"),
+                            page("You are not allowed to answer this request", ""),
+                        );
+                    }
+                    None => {
+                        return (
+                            (403, "Forbidden"),
+                            page("Log in with Telegram to answer this request", ""),
+                        )
+                    }
+                }
+            } else {
+                "approval link".to_string()
+            };
+            // Already off the runtime, so resolve through the blocking call
+            let resolved = ledger::resolve_in(
+                &default_request_ledger_path(),
+                request_id,
+                Resolution { allow, by },
+            )
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to resolve request {}: {}", request_id, e);
+                false
+            });
+            if !resolved {
+                // Someone answered in the meantime
+                let notice = ledger::closed_notice(request_id)
+                    .unwrap_or_else(|| "This request is no longer waiting".to_string());
+                return ((410, "Gone"), page(&notice, ""));
+            }
+            let title = if allow { "✅ Allowed" } else { "❌ Denied" };
+            ((200, "OK"), page(title, ""))
+        }
+        _ => ((405, "Method Not Allowed"), page("Method not allowed", "")),
+    }
+}
+
+/// Bind the link listener, so a busy port fails the daemon at start.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn bind(address: &str) -> Result<TcpListener> {
+    TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to listen for approval links on {}", address))
+}
+
+/// Serve approval links until the task is aborted.
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+pub async fn serve(listener: TcpListener, config_path: Option<PathBuf>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle(stream, config_path.clone()));
+            }
+            Err(e) => tracing::warn!("Failed to accept approval link request: {}", e),
+        }
+    }
+}
+
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+async fn handle(mut stream: TcpStream, config_path: Option<PathBuf>) {
+    let request = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) => {
+            tracing::debug!("Invalid approval link request: {}", e);
+            return;
+        }
+        Err(_) => return,
+    };
+    // Answering takes the ledger lock
+    let (status, body) = off_reactor(move || match load_config_for_request(config_path) {
+        Ok(config) => respond(&request, &config),
+        Err(e) => {
+            tracing::warn!("Failed to load configuration for approval link: {}", e);
+            (
+                (500, "Internal Server Error"),
+                page("Configuration error", ""),
+            )
+        }
+    })
+    .await;
+    if let Err(e) = write_response(&mut stream, status, "text/html; charset=utf-8", &body).await {
+        tracing::debug!("Failed to answer approval link request: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_links() {
+        let mut settings = WebApprovalConfig {
+            base_url: Some("https://approve.example.com/".to_string()),
+            secret: Some("s3cret".to_string()),
+            ..WebApprovalConfig::default()
+        };
+        assert_eq!(link_until(&settings, "abc12345", 1_700_000_600), None);
+
+        settings.enabled = true;
+        let link = link_until(&settings, "abc12345", 1_700_000_600).unwrap();
+        let sig = signature("s3cret", "abc12345", 1_700_000_600);
+        assert_eq!(
+            link,
+            format!(
+                "https://approve.example.com/approve/abc12345?exp=1700000600&sig={}",
+                sig
+            )
+        );
+        assert!(link_valid(
+            "s3cret",
+            "abc12345",
+            1_700_000_600,
+            &sig,
+            1_700_000_000
+        ));
+        assert!(!link_valid(
+            "s3cret",
+            "abc12345",
+            1_700_000_600,
+            &sig,
+            1_700_000_601
+        ));
+        assert!(!link_valid(
+            "s3cret",
+            "abc99999",
+            1_700_000_600,
+            &sig,
+            1_700_000_000
+        ));
+        assert!(!link_valid(
+            "other",
+            "abc12345",
+            1_700_000_600,
+            &sig,
+            1_700_000_000
+        ));
+
+        assert!(approval_page("abc12345", "Bash \"<rm>\"", "laptop", None)
+            .contains("Bash &quot;&lt;rm&gt;&quot;"));
+    }
+
+    #[test]
+    fn test_telegram_login_needs_allowlist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        let write = |responders: &str| {
+            let content = format!(
+                r#"{{
+                    "messengers": {{"telegram": {{"bot_token": "123:token", "chat_id": 1}}}},
+                    "authorized_responders": {{"telegram": [{}]}},
+                    "preferences": {{"web_approvals": {{
+                        "enabled": true, "secret": "s3cret", "telegram_login": "my_bot"
+                    }}}}
+                }}"#,
+                responders
+            );
+            std::fs::write(&path, content).unwrap();
+            Config::from_json(&path).unwrap()
+        };
+        let expires = now_secs() + 600;
+        let request = HttpRequest {
+            method: "POST".to_string(),
+            target: format!(
+                "/approve/abc12345?exp={}&sig={}",
+                expires,
+                signature("s3cret", "abc12345", expires)
+            ),
+            headers: Vec::new(),
+            body: b"decision=allow&telegram=".to_vec(),
+        };
+
+        let ((status, _), _) = respond(&request, &write(""));
+        assert_eq!(status, 503);
+        let ((status, _), _) = respond(&request, &write("42"));
+        assert_ne!(status, 503);
+    }
+
+    #[test]
+    fn test_telegram_login() {
+        let now = 1_700_000_000;
+        let signed = |mut user: Value| {
+            let check = format!(
+                "auth_date={}\nfirst_name=Alice\nid=42\nusername=alice",
+                user["auth_date"]
+            );
+            let key = Sha256::digest(b"123:token");
+            user["hash"] = json!(hex(&hmac_sha256(&key, check.as_bytes())));
+            user
+        };
+        let user = signed(json!({
+            "id": 42,
+            "first_name": "Alice",
+            "username": "alice",
+            "auth_date": now - 60,
+        }));
+        assert_eq!(
+            telegram_user("123:token", &user, now),
+            Some((42, "@alice (42)".to_string()))
+        );
+        assert_eq!(telegram_user("456:other", &user, now), None);
+
+        let mut forged = user.clone();
+        forged["id"] = json!(43);
+        assert_eq!(telegram_user("123:token", &forged, now), None);
+
+        let stale = signed(json!({
+            "id": 42,
+            "first_name": "Alice",
+            "username": "alice",
+            "auth_date": now - 2 * LOGIN_MAX_AGE_SECS,
+        }));
+        assert_eq!(telegram_user("123:token", &stale, now), None);
+    }
+}
@@ -0,0 +1,108 @@
+//! Outgoing event webhooks for automations.
+//!
+//! Every webhook in `preferences.webhooks` receives a JSON POST for each
+//! event it subscribes to, e.g. to log approvals to a spreadsheet through
+//! Zapier, IFTTT or n8n. Delivery is best effort: failures are logged and
+//! never change the hook's answer.
+//!
+//! The same events are posted to the observer chat, if one is configured;
+//! see [`crate::observer`].
+
+use crate::clock::now_secs;
+use crate::config::{Config, WebhookEvent};
+use crate::messenger::{clients, dry_run};
+use crate::observer;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// How long one delivery may take; hooks wait for it before answering.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Event body: `event`, `time` and `host`, followed by the event's fields.
+fn payload(event: WebhookEvent, host: &str, time: u64, fields: Value) -> Value {
+    let mut body = json!({"event": event, "time": time, "host": host});
+    if let (Some(body), Value::Object(fields)) = (body.as_object_mut(), fields) {
+        body.extend(fields);
+    }
+    body
+}
+
+/// Send an event to every webhook subscribed to it, and to the observer chat.
+pub async fn emit(config: &Config, event: WebhookEvent, fields: Value) {
+    tokio::join!(
+        observer::publish(config, event, &fields),
+        deliver(config, event, &fields)
+    );
+}
+
+/// Send an event to every webhook subscribed to it.
+///
+/// Deliveries run concurrently. In dry-run mode the body is printed to
+/// stderr instead.
+async fn deliver(config: &Config, event: WebhookEvent, fields: &Value) {
+    let urls: Vec<String> = config
+        .webhooks
+        .iter()
+        .filter(|webhook| webhook.wants(event))
+        .map(|webhook| webhook.url.clone())
+        .collect();
+    if urls.is_empty() {
+        return;
+    }
+
+    let time = now_secs();
+    let body = payload(event, &config.hostname, time, fields.clone());
+    if dry_run() {
+        for url in &urls {
+            eprintln!("[dry-run] webhook {}:\n{}\n", url, body);
+        }
+        return;
+    }
+
+    let client = clients::shared().http();
+    let mut deliveries = JoinSet::new();
+    for url in urls {
+        let request = client.post(&url).timeout(REQUEST_TIMEOUT).json(&body);
+        deliveries.spawn(async move {
+            let result = request
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            (url, result)
+        });
+    }
+    while let Some(joined) = deliveries.join_next().await {
+        if let Ok((url, Err(e))) = joined {
+            tracing::warn!("Failed to deliver webhook to {}: {}", url, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WebhookConfig;
+
+    #[test]
+    fn test_payload_and_subscriptions() {
+        let body = payload(
+            WebhookEvent::DecisionMade,
+            "laptop",
+            1_700_000_000,
+            json!({"request_id": "abc12345", "decision": "allow"}),
+        );
+        assert_eq!(
+            body.to_string(),
+            r#"{"event":"decision_made","time":1700000000,"host":"laptop","request_id":"abc12345","decision":"allow"}"#
+        );
+
+        let all: WebhookConfig = serde_json::from_value(json!({"url": "https://x"})).unwrap();
+        assert!(all.wants(WebhookEvent::Timeout));
+        let some: WebhookConfig =
+            serde_json::from_value(json!({"url": "https://x", "events": ["session_completed"]}))
+                .unwrap();
+        assert!(some.wants(WebhookEvent::SessionCompleted));
+        assert!(!some.wants(WebhookEvent::RequestCreated));
+    }
+}
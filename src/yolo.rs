@@ -0,0 +1,205 @@
+//! Time-boxed windows in which a session's requests are allowed.
+//!
+//! `/yolo <session> 10m` in the bot, once confirmed, opens a window in a
+//! small file in the state directory; until it ends the hook allows every
+//! request from that session without asking, for supervised bursts of
+//! trusted activity. Windows are capped at `MAX_WINDOW`, end on their own,
+//! and can be closed early with `/yolo off`. Requests needing several
+//! approvals and calls matching a block rule are still asked about.
+
+use crate::clock::now_secs;
+use crate::config::default_yolo_path;
+use crate::lockfile::{write_atomic, FileLock};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Longest a window can stay open.
+#[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+pub const MAX_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// A session whose requests are allowed for a while.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct YoloWindow {
+    /// Full session ID
+    pub session: String,
+    /// Who opened the window
+    pub by: String,
+    /// Unix time the window ends
+    pub until: u64,
+}
+
+impl YoloWindow {
+    /// Whether a request from this session falls in the window.
+    pub fn matches(&self, session_id: &str) -> bool {
+        !self.session.is_empty() && session_id == self.session
+    }
+}
+
+impl fmt::Display for YoloWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let left = self.until.saturating_sub(now_secs());
+        write!(
+            f,
+            "{} for {}m (by {})",
+            self.session,
+            left.div_ceil(60),
+            self.by
+        )
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct YoloData {
+    #[serde(default)]
+    windows: Vec<YoloWindow>,
+}
+
+/// The open yolo windows.
+#[derive(Debug, Clone)]
+pub struct YoloWindows {
+    storage_path: PathBuf,
+}
+
+impl YoloWindows {
+    /// Create the windows kept at the given path, or the default state file.
+    pub fn new(storage_path: Option<PathBuf>) -> Self {
+        Self {
+            storage_path: storage_path.unwrap_or_else(default_yolo_path),
+        }
+    }
+
+    fn load(&self) -> YoloData {
+        fs::read_to_string(&self.storage_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Read, change and write the windows while holding their lock.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    fn update<T>(&self, change: impl FnOnce(&mut YoloData) -> T) -> io::Result<T> {
+        if let Some(parent) = self.storage_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _lock = FileLock::acquire(&self.storage_path)?;
+        let mut data = self.load();
+        let result = change(&mut data);
+        write_atomic(&self.storage_path, &serde_json::to_string_pretty(&data)?)?;
+        Ok(result)
+    }
+
+    /// Windows that have not ended.
+    pub fn list(&self) -> Vec<YoloWindow> {
+        let now = now_secs();
+        self.load()
+            .windows
+            .into_iter()
+            .filter(|window| window.until > now)
+            .collect()
+    }
+
+    /// The open window covering a request from this session, if any.
+    pub fn matching(&self, session_id: &str) -> Option<YoloWindow> {
+        self.list()
+            .into_iter()
+            .find(|window| window.matches(session_id))
+    }
+
+    /// Open a window for a session, at most `MAX_WINDOW` long, replacing an
+    /// earlier one. Returns the window opened.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn open(&self, session: &str, by: &str, length: Duration) -> io::Result<YoloWindow> {
+        let now = now_secs();
+        let window = YoloWindow {
+            session: session.to_string(),
+            by: by.to_string(),
+            until: now + length.min(MAX_WINDOW).as_secs(),
+        };
+        self.update(|data| {
+            data.windows
+                .retain(|open| open.until > now && open.session != session);
+            data.windows.push(window.clone());
+        })?;
+        Ok(window)
+    }
+
+    /// Close the window of the session whose ID starts with `session` early,
+    /// or every window for an empty session.
+    ///
+    /// Returns the open windows closed.
+    #[cfg_attr(not(feature = "telegram"), allow(dead_code))]
+    pub fn close(&self, session: &str) -> io::Result<Vec<YoloWindow>> {
+        let now = now_secs();
+        self.update(|data| {
+            let (closed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut data.windows)
+                .into_iter()
+                .filter(|window| window.until > now)
+                .partition(|window| window.session.starts_with(session));
+            data.windows = kept;
+            closed
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn windows() -> (tempfile::TempDir, YoloWindows) {
+        let dir = tempdir().unwrap();
+        let windows = YoloWindows::new(Some(dir.path().join("yolo.json")));
+        (dir, windows)
+    }
+
+    #[test]
+    fn test_window_covers_only_its_session() {
+        let (_dir, windows) = windows();
+        assert_eq!(windows.matching("1a2b3c4d-0000"), None);
+
+        let window = windows
+            .open("1a2b3c4d-0000", "@alice", Duration::from_secs(10 * 60))
+            .unwrap();
+        assert_eq!(window.to_string(), "1a2b3c4d-0000 for 10m (by @alice)");
+        assert_eq!(windows.matching("1a2b3c4d-0000"), Some(window));
+        // Other sessions starting the same way are not covered
+        assert_eq!(windows.matching("1a2b3c4d-0001"), None);
+        assert_eq!(windows.matching("1a2b"), None);
+        assert_eq!(windows.matching(""), None);
+    }
+
+    #[test]
+    fn test_reopened_window_is_capped_and_replaces_earlier() {
+        let (_dir, windows) = windows();
+        windows
+            .open("1a2b3c4d-0000", "@alice", Duration::from_secs(10 * 60))
+            .unwrap();
+        let window = windows
+            .open("1a2b3c4d-0000", "@bob", Duration::from_secs(24 * 60 * 60))
+            .unwrap();
+        assert!(window.until <= now_secs() + MAX_WINDOW.as_secs());
+        assert_eq!(windows.list(), vec![window]);
+    }
+
+    #[test]
+    fn test_ended_window_does_not_count() {
+        let (_dir, windows) = windows();
+        windows.open("ffff0000", "@bob", Duration::ZERO).unwrap();
+        assert_eq!(windows.matching("ffff0000"), None);
+    }
+
+    #[test]
+    fn test_close_windows() {
+        let (_dir, windows) = windows();
+        windows.open("1a2b3c4d-0000", "@bob", MAX_WINDOW).unwrap();
+        windows.open("eeee0000", "@bob", MAX_WINDOW).unwrap();
+        assert_eq!(windows.close("1a2b3c4d").unwrap().len(), 1);
+        assert_eq!(windows.matching("1a2b3c4d-0000"), None);
+        assert_eq!(windows.close("").unwrap().len(), 1);
+        assert!(windows.list().is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Property tests for hook payloads and transcripts.
+//!
+//! Claude Code, other agents and whatever edits a transcript all feed these
+//! parsers, so arbitrary or adversarial input must come back as an error or a
+//! fallback, never a panic.
+
+use claude_code_telegram::config::TaskPromptSource;
+use claude_code_telegram::hook_handler::{parse_hook_input, PermissionRequest};
+use claude_code_telegram::stop_handler::{last_assistant_message, StopEvent, StopInput};
+use claude_code_telegram::tool_timer::{describe_tool, tool_detail};
+use proptest::prelude::*;
+use serde_json::{json, Map, Value};
+use std::fs;
+use tempfile::tempdir;
+
+/// Any JSON value, a few levels deep.
+fn any_json() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<f64>()
+            .prop_filter("JSON has no NaN or infinity", |f| f.is_finite())
+            .prop_map(Value::from),
+        any::<String>().prop_map(Value::from),
+    ];
+    leaf.prop_recursive(3, 24, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Value::from),
+            prop::collection::btree_map(any::<String>(), inner, 0..4)
+                .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+/// An object using keys that tool inputs and transcript entries really have.
+fn object_with_keys(keys: &'static [&'static str]) -> impl Strategy<Value = Value> {
+    prop::collection::vec((prop::sample::select(keys), any_json()), 0..6).prop_map(|fields| {
+        let map: Map<String, Value> = fields
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect();
+        Value::Object(map)
+    })
+}
+
+const TOOLS: &[&str] = &[
+    "Bash",
+    "Read",
+    "Write",
+    "Edit",
+    "MultiEdit",
+    "NotebookEdit",
+    "WebFetch",
+    "WebSearch",
+    "Task",
+    "Glob",
+    "Grep",
+    "mcp__github__create_issue",
+    "",
+];
+
+const TOOL_INPUT_KEYS: &[&str] = &[
+    "command",
+    "description",
+    "file_path",
+    "content",
+    "old_string",
+    "new_string",
+    "edits",
+    "notebook_path",
+    "new_source",
+    "edit_mode",
+    "url",
+    "prompt",
+    "query",
+    "allowed_domains",
+    "blocked_domains",
+    "subagent_type",
+    "pattern",
+    "path",
+];
+
+/// Hook input shaped like Claude Code's, with arbitrary values.
+fn hook_input() -> impl Strategy<Value = Value> {
+    let tool_name = prop_oneof![
+        prop::sample::select(TOOLS).prop_map(Value::from),
+        any_json()
+    ];
+    let suggestion = prop_oneof![
+        object_with_keys(&["type", "rules", "behavior", "destination", "mode"]),
+        any_json(),
+    ];
+    (
+        tool_name,
+        prop_oneof![object_with_keys(TOOL_INPUT_KEYS), any_json()],
+        prop::collection::vec(suggestion, 0..3),
+        any::<String>(),
+    )
+        .prop_map(|(tool_name, tool_input, suggestions, cwd)| {
+            json!({
+                "tool_name": tool_name,
+                "tool_input": tool_input,
+                "permission_suggestions": suggestions,
+                "cwd": cwd,
+                "session_id": "s1",
+            })
+        })
+}
+
+/// A transcript line: an entry shaped like Claude Code's, or garbage.
+fn transcript_line() -> impl Strategy<Value = String> {
+    let role = prop::sample::select(&["user", "assistant", "system", "result", "summary"][..]);
+    let block = prop_oneof![
+        any::<String>().prop_map(|text| json!({"type": "text", "text": text})),
+        (any_json(), any::<bool>()).prop_map(|(content, is_error)| {
+            json!({"type": "tool_result", "content": content, "is_error": is_error})
+        }),
+        any_json(),
+    ];
+    let content = prop_oneof![
+        any::<String>().prop_map(Value::from),
+        prop::collection::vec(block, 0..4).prop_map(Value::from),
+        any_json(),
+    ];
+    let timestamp = prop_oneof![
+        Just("2025-01-31T12:34:56.789Z".to_string()),
+        "[0-9]{1,20}-[0-9]{1,3}-[0-9]{1,3}T[0-9]{1,3}:[0-9]{1,3}:[0-9]{1,20}(Z|[+-][0-9]{1,20}:[0-9]{1,20})?",
+        any::<String>(),
+    ];
+    let entry = (
+        role.clone(),
+        role,
+        content.clone(),
+        content,
+        timestamp,
+        any::<bool>(),
+        any::<bool>(),
+        prop::option::of(prop::sample::select(
+            &["error_max_turns", "success", "error"][..],
+        )),
+    )
+        .prop_map(
+            |(entry_type, role, message, content, timestamp, meta, api_error, subtype)| {
+                json!({
+                    "type": entry_type,
+                    "message": {"role": role, "content": message},
+                    "content": content,
+                    "timestamp": timestamp,
+                    "isMeta": meta,
+                    "isApiErrorMessage": api_error,
+                    "subtype": subtype,
+                    "level": "error",
+                })
+                .to_string()
+            },
+        );
+    prop_oneof![4 => entry, 1 => any::<String>(), 1 => any_json().prop_map(|v| v.to_string())]
+}
+
+proptest! {
+    #[test]
+    fn hook_input_parsing_never_panics(input in any::<String>()) {
+        let _ = parse_hook_input(&input);
+    }
+
+    #[test]
+    fn any_json_hook_input_is_parsed_or_rejected(input in any_json()) {
+        if let Ok(input) = parse_hook_input(&input.to_string()) {
+            PermissionRequest::from_hook_input(input);
+        }
+    }
+
+    #[test]
+    fn hook_shaped_input_can_be_described(input in hook_input()) {
+        let tool_name_is_text = input["tool_name"].is_string();
+        let parsed = parse_hook_input(&input.to_string());
+        prop_assert_eq!(parsed.is_ok(), tool_name_is_text);
+        let Ok(parsed) = parsed else {
+            return Ok(());
+        };
+
+        describe_tool(&parsed.tool_name, &parsed.tool_input);
+        tool_detail(&parsed.tool_name, &parsed.tool_input);
+        let message = PermissionRequest::from_hook_input(parsed).to_message("host");
+        message.tool_label();
+        message.display_fields();
+        message.detail();
+        message.multi_edits();
+        message.notebook_change();
+        message.subagent_line();
+        message.fetch_domain();
+        message.search_domains("allowed_domains");
+        message.always_allow_entry();
+        message.block_rule();
+    }
+
+    #[test]
+    fn stop_input_parsing_never_panics(input in any::<String>(), value in any_json()) {
+        let _ = serde_json::from_str::<StopInput>(&input);
+        let _ = serde_json::from_str::<StopInput>(&value.to_string());
+    }
+
+    #[test]
+    fn transcripts_never_panic(
+        lines in prop::collection::vec(transcript_line(), 0..12),
+        raw in prop::collection::vec(any::<u8>(), 0..64),
+        max_chars in 0usize..200,
+    ) {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("transcript.jsonl");
+        let mut content = lines.join("\n").into_bytes();
+        content.extend(raw);
+        fs::write(&path, content).unwrap();
+
+        let event = StopEvent::from_input(StopInput {
+            session_id: "s1".to_string(),
+            transcript_path: path.to_string_lossy().to_string(),
+            cwd: "/home/user/project".to_string(),
+            stop_hook_active: false,
+        });
+        last_assistant_message(&path);
+        event.get_last_assistant_message();
+        event.get_user_prompt(TaskPromptSource::First);
+        event.get_user_prompt(TaskPromptSource::Last);
+        event.detect_session_error();
+        event.get_session_duration();
+        if let Some(tail) = event.get_transcript_tail(max_chars) {
+            prop_assert!(tail.chars().count() <= max_chars);
+        }
+    }
+}
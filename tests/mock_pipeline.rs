@@ -0,0 +1,447 @@
+//! Hook pipeline tests against the mock messenger.
+//!
+//! Run with `cargo test --features mock`.
+#![cfg(feature = "mock")]
+
+use claude_code_telegram::always_allow::AlwaysAllowManager;
+use claude_code_telegram::config::{RedactionConfig, RequestGroupingConfig, ToolDisplay};
+use claude_code_telegram::error::HookError;
+use claude_code_telegram::hook_handler::{
+    handle_permission_request_with_messenger, PermissionRequest,
+};
+use claude_code_telegram::messenger::mock::{self, MockMessenger, SentMessage};
+use claude_code_telegram::messenger::{Decision, PermissionMessage};
+use claude_code_telegram::redact::Redactor;
+use claude_code_telegram::request_groups::RequestGroups;
+use claude_code_telegram::{ApprovalClient, Config};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempdir;
+
+fn bash_request(command: &str) -> PermissionRequest {
+    PermissionRequest {
+        tool_name: "Bash".to_string(),
+        tool_input: json!({"command": command}),
+        request_id: "abc12345".to_string(),
+        required_approvals: 1,
+        permission_suggestions: Vec::new(),
+        display: ToolDisplay::default(),
+        session_id: String::new(),
+        context: None,
+        host_label: None,
+        approval_link: None,
+        explanation: None,
+        redactor: None,
+    }
+}
+
+#[tokio::test]
+async fn always_allow_decision_auto_approves_later_requests() {
+    let dir = tempdir().unwrap();
+    let always_allow = AlwaysAllowManager::new(Some(dir.path().join("always_allow.json")));
+    let messenger = MockMessenger::with_decisions([Decision::AlwaysAllow]);
+    let timeout = Duration::from_secs(1);
+
+    for _ in 0..2 {
+        let decision = handle_permission_request_with_messenger(
+            &messenger,
+            &always_allow,
+            &bash_request("ls"),
+            "test-host",
+            timeout,
+        )
+        .await
+        .unwrap();
+        assert_eq!(decision, Decision::Allow);
+    }
+
+    let sent = messenger.sent();
+    assert!(
+        matches!(&sent[0], SentMessage::PermissionRequest { tool_name, .. } if tool_name == "Bash")
+    );
+    assert!(matches!(&sent[1], SentMessage::AutoApproved { .. }));
+}
+
+#[tokio::test]
+async fn always_allow_rule_keeps_the_secret_it_was_approved_with() {
+    let dir = tempdir().unwrap();
+    let always_allow = AlwaysAllowManager::new(Some(dir.path().join("always_allow.json")));
+    let messenger = MockMessenger::with_decisions([Decision::AlwaysAllow, Decision::Deny]);
+    let timeout = Duration::from_secs(1);
+    // Fetches are allowed by site, and this site's name holds a token
+    let redaction = RedactionConfig {
+        patterns: vec!["(?i)token[a-z0-9]+".to_string()],
+        ..RedactionConfig::default()
+    };
+    let fetch = |token: &str| PermissionRequest {
+        tool_name: "WebFetch".to_string(),
+        tool_input: json!({"url": format!("https://{}.example.com/status", token)}),
+        redactor: Some(Redactor::new(&redaction)),
+        ..bash_request("")
+    };
+
+    let mut decisions = Vec::new();
+    for token in ["tokenAAA111", "tokenBBB222"] {
+        decisions.push(
+            handle_permission_request_with_messenger(
+                &messenger,
+                &always_allow,
+                &fetch(token),
+                "test-host",
+                timeout,
+            )
+            .await
+            .unwrap(),
+        );
+    }
+    assert_eq!(decisions, [Decision::Allow, Decision::Deny]);
+
+    // Chat only saw masked URLs, while the rule holds the real site
+    let sent = messenger.sent();
+    assert_eq!(sent.len(), 2);
+    for message in &sent {
+        let SentMessage::PermissionRequest { tool_input, .. } = message else {
+            panic!("expected a permission request, got {:?}", message);
+        };
+        assert!(!tool_input.to_string().to_lowercase().contains("token"));
+    }
+    let rules = fs::read_to_string(dir.path().join("always_allow.json")).unwrap();
+    assert!(rules.to_lowercase().contains("tokenaaa111"));
+}
+
+#[tokio::test]
+async fn auto_approval_limit_falls_back_to_prompting() {
+    let dir = tempdir().unwrap();
+    let always_allow = AlwaysAllowManager::new(Some(dir.path().join("always_allow.json")))
+        .with_limits(BTreeMap::from([("Bash".to_string(), 1)]));
+    always_allow.add_tool("Bash").unwrap();
+    let messenger = MockMessenger::with_decisions([Decision::Deny]);
+
+    let mut decisions = Vec::new();
+    for _ in 0..2 {
+        decisions.push(
+            handle_permission_request_with_messenger(
+                &messenger,
+                &always_allow,
+                &bash_request("ls"),
+                "test-host",
+                Duration::from_secs(1),
+            )
+            .await
+            .unwrap(),
+        );
+    }
+    assert_eq!(decisions, [Decision::Allow, Decision::Deny]);
+
+    let sent = messenger.sent();
+    assert!(matches!(&sent[0], SentMessage::AutoApproved { .. }));
+    assert!(matches!(&sent[1], SentMessage::Text { text } if text.contains("(1 per hour)")));
+    assert!(matches!(&sent[2], SentMessage::PermissionRequest { .. }));
+}
+
+#[tokio::test]
+async fn deny_and_block_denies_later_matching_calls() {
+    let dir = tempdir().unwrap();
+    let always_allow = AlwaysAllowManager::new(Some(dir.path().join("always_allow.json")));
+    let messenger = MockMessenger::with_decisions([Decision::DenyAndBlock, Decision::Allow]);
+    let timeout = Duration::from_secs(1);
+
+    let decision = handle_permission_request_with_messenger(
+        &messenger,
+        &always_allow,
+        &bash_request("curl -X DELETE https://api.example.com/items/1"),
+        "test-host",
+        timeout,
+    )
+    .await
+    .unwrap();
+    assert_eq!(decision, Decision::DenyAndBlock);
+
+    let blocked = handle_permission_request_with_messenger(
+        &messenger,
+        &always_allow,
+        &bash_request("curl -X DELETE https://api.example.com/items/2"),
+        "test-host",
+        timeout,
+    )
+    .await;
+    assert!(matches!(blocked, Err(HookError::Blocked(rule)) if rule == "Bash(curl *)"));
+
+    let decision = handle_permission_request_with_messenger(
+        &messenger,
+        &always_allow,
+        &bash_request("ls"),
+        "test-host",
+        timeout,
+    )
+    .await
+    .unwrap();
+    assert_eq!(decision, Decision::Allow);
+    assert_eq!(messenger.sent().len(), 2);
+}
+
+#[tokio::test]
+async fn burst_of_requests_is_asked_as_one_group() {
+    let dir = tempdir().unwrap();
+    let groups = RequestGroups::new(RequestGroupingConfig {
+        enabled: true,
+        threshold: 1,
+        window_seconds: 5,
+    })
+    .with_dir(dir.path().to_path_buf());
+    let messenger = MockMessenger::with_decisions([Decision::Allow, Decision::Deny]);
+    let message = |request_id: &str| {
+        PermissionMessage::new(
+            request_id.to_string(),
+            "Bash".to_string(),
+            "test-host".to_string(),
+            json!({"command": "ls"}),
+        )
+    };
+    let (first, second, third) = (
+        message("req00001"),
+        message("req00002"),
+        message("req00003"),
+    );
+    let timeout = Duration::from_secs(5);
+
+    let (first, second, third) = tokio::join!(
+        groups.decide(&messenger, "session-1", &first, timeout),
+        groups.decide(&messenger, "session-1", &second, timeout),
+        groups.decide(&messenger, "session-1", &third, timeout),
+    );
+    // Up to the threshold, requests are sent on their own
+    assert!(first.is_none());
+    assert_eq!(second.unwrap().unwrap(), Decision::Allow);
+    assert_eq!(third.unwrap().unwrap(), Decision::Deny);
+
+    assert_eq!(
+        messenger.sent(),
+        [SentMessage::PermissionGroup {
+            request_ids: vec!["req00002".to_string(), "req00003".to_string()]
+        }]
+    );
+}
+
+#[tokio::test]
+async fn exhausted_script_denies() {
+    let dir = tempdir().unwrap();
+    let always_allow = AlwaysAllowManager::new(Some(dir.path().join("always_allow.json")));
+    let messenger = MockMessenger::with_decisions([]);
+
+    let decision = handle_permission_request_with_messenger(
+        &messenger,
+        &always_allow,
+        &bash_request("rm -rf /tmp/x"),
+        "test-host",
+        Duration::from_secs(1),
+    )
+    .await
+    .unwrap();
+    assert_eq!(decision, Decision::Deny);
+}
+
+#[tokio::test]
+async fn approval_client_redacts_and_returns_decision() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("config.json");
+    fs::write(
+        &config_path,
+        json!({
+            "messengers": {
+                "telegram": {"enabled": true, "bot_token": "123:abc", "chat_id": "1"}
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let config = Config::from_json(&config_path).unwrap();
+    let messenger = Arc::new(MockMessenger::with_decisions([Decision::Allow]));
+    let client = ApprovalClient::with_messenger(config, messenger.clone());
+
+    let message = PermissionMessage::new(
+        "abc12345".to_string(),
+        "Deploy".to_string(),
+        client.hostname().to_string(),
+        json!({"token": "Bearer abcdef123456"}),
+    );
+    assert_eq!(
+        client.request_approval(message).await.unwrap(),
+        Decision::Allow
+    );
+    client.notify("Deployed").await.unwrap();
+
+    let sent = messenger.sent();
+    let SentMessage::PermissionRequest { tool_input, .. } = &sent[0] else {
+        panic!("expected a permission request, got {:?}", sent[0]);
+    };
+    assert!(!tool_input.to_string().contains("abcdef123456"));
+    assert!(matches!(&sent[1], SentMessage::Notification { text } if text == "Deployed"));
+
+    // Multi-person approval needs a messenger that can count responders
+    let quorum = PermissionMessage::new(
+        "def67890".to_string(),
+        "Deploy".to_string(),
+        client.hostname().to_string(),
+        json!({}),
+    )
+    .with_required_approvals(2);
+    assert!(client.request_approval(quorum).await.is_err());
+}
+
+/// Run the hook binary against the mock messenger, returning its response and what it sent.
+fn run_hook(decisions: &str, input: &Value) -> (Value, Vec<SentMessage>) {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("hook_config.json");
+    fs::write(
+        &config_path,
+        json!({
+            "messengers": {
+                "telegram": {"enabled": true, "bot_token": "123:abc", "chat_id": "1"}
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let log_path = dir.path().join("mock.jsonl");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_claude-code-telegram"))
+        .arg("--config")
+        .arg(&config_path)
+        .arg("hook")
+        .env("HOME", dir.path())
+        .env("XDG_STATE_HOME", dir.path())
+        .env(mock::DECISIONS_ENV, decisions)
+        .env(mock::LOG_ENV, &log_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.to_string().as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let response = serde_json::from_slice(&output.stdout).unwrap();
+    (response, mock::read_log(&log_path))
+}
+
+#[test]
+fn hook_binary_uses_scripted_decision() {
+    let (response, sent) = run_hook(
+        "allow",
+        &json!({"tool_name": "Bash", "tool_input": {"command": "cargo test"}}),
+    );
+    assert_eq!(
+        response["hookSpecificOutput"]["decision"]["behavior"],
+        "allow"
+    );
+
+    assert_eq!(sent.len(), 1);
+    assert!(matches!(
+        &sent[0],
+        SentMessage::PermissionRequest { tool_input, .. } if tool_input["command"] == "cargo test"
+    ));
+}
+
+#[test]
+fn hook_binary_writes_back_chosen_suggestion() {
+    let suggestion = json!({
+        "type": "addRules",
+        "rules": [{"toolName": "Bash", "ruleContent": "git *"}],
+        "behavior": "allow",
+        "destination": "localSettings"
+    });
+    let (response, _) = run_hook(
+        "suggest:0",
+        &json!({
+            "tool_name": "Bash",
+            "tool_input": {"command": "git status"},
+            "permission_suggestions": [suggestion]
+        }),
+    );
+
+    let decision = &response["hookSpecificOutput"]["decision"];
+    assert_eq!(decision["behavior"], "allow");
+    assert_eq!(decision["updatedPermissions"], json!([suggestion]));
+}
+
+#[test]
+fn serve_stdio_answers_json_rpc() {
+    let dir = tempdir().unwrap();
+    let config_path = dir.path().join("hook_config.json");
+    fs::write(
+        &config_path,
+        json!({
+            "messengers": {
+                "telegram": {"enabled": true, "bot_token": "123:abc", "chat_id": "1"}
+            }
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let log_path = dir.path().join("mock.jsonl");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_claude-code-telegram"))
+        .arg("--config")
+        .arg(&config_path)
+        .args(["serve", "--stdio"])
+        .env("HOME", dir.path())
+        .env("XDG_STATE_HOME", dir.path())
+        .env(mock::DECISIONS_ENV, "deny")
+        .env(mock::LOG_ENV, &log_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let requests = [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "request_approval",
+            "params": {"tool_name": "Deploy", "tool_input": {"target": "prod"}, "request_id": "req00001"}}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "notify", "params": {"text": "hello"}}),
+        json!({"jsonrpc": "2.0", "method": "notify", "params": {"text": "no reply"}}),
+        json!({"jsonrpc": "2.0", "id": 3, "method": "list_pending"}),
+        json!({"jsonrpc": "2.0", "id": 4, "method": "reboot"}),
+        json!({"jsonrpc": "2.0", "id": 5, "method": "notify", "params": {}}),
+    ];
+    let mut input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+    input.push_str("not json\n");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let responses: BTreeMap<String, Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let response: Value = serde_json::from_str(line).unwrap();
+            (response["id"].to_string(), response)
+        })
+        .collect();
+    assert_eq!(responses.len(), 6);
+    assert_eq!(
+        responses["1"]["result"],
+        json!({"request_id": "req00001", "decision": "deny"})
+    );
+    assert_eq!(responses["2"]["result"], Value::Null);
+    assert!(responses["3"]["result"].is_array());
+    assert_eq!(responses["4"]["error"]["code"], -32601);
+    assert_eq!(responses["5"]["error"]["code"], -32602);
+    assert_eq!(responses["null"]["error"]["code"], -32700);
+
+    let sent = mock::read_log(&log_path);
+    assert_eq!(sent.len(), 3);
+}
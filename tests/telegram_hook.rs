@@ -0,0 +1,166 @@
+//! End-to-end `hook` runs against a mock Telegram Bot API server.
+//!
+//! The hook binary is pointed at [`telegram_mock::TelegramMock`] through
+//! `CCR_TELEGRAM_API_URL`, so these cover the real Telegram client: the
+//! keyboard it sends, how it handles presses, and the edits it makes.
+#![cfg(feature = "telegram")]
+
+mod telegram_mock;
+
+use serde_json::{json, Value};
+use std::process::Stdio;
+use tempfile::{tempdir, TempDir};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use telegram_mock::{keyboard_rows, TelegramMock};
+
+/// Write a config for chat 1 with extra top-level settings merged in.
+fn write_config(dir: &TempDir, extra: Value) -> std::path::PathBuf {
+    let mut config = json!({
+        "messengers": {
+            "telegram": {"enabled": true, "bot_token": "123:abc", "chat_id": "1"}
+        }
+    });
+    if let (Some(config), Some(extra)) = (config.as_object_mut(), extra.as_object()) {
+        config.extend(extra.clone());
+    }
+    let path = dir.path().join("hook_config.json");
+    std::fs::write(&path, config.to_string()).unwrap();
+    path
+}
+
+/// Run the hook binary for a Bash call, returning its response.
+async fn run_hook(telegram: &TelegramMock, extra: Value) -> Value {
+    let dir = tempdir().unwrap();
+    let config_path = write_config(&dir, extra);
+    let input = json!({"tool_name": "Bash", "tool_input": {"command": "cargo test"}});
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_claude-code-telegram"))
+        .arg("--config")
+        .arg(&config_path)
+        .arg("hook")
+        .env("HOME", dir.path())
+        .env("XDG_STATE_HOME", dir.path())
+        .env("CCR_TELEGRAM_API_URL", telegram.uri())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.to_string().as_bytes()).await.unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().await.unwrap();
+    assert!(output.status.success());
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+fn behavior(response: &Value) -> &str {
+    response["hookSpecificOutput"]["decision"]["behavior"]
+        .as_str()
+        .unwrap_or_default()
+}
+
+fn edited_text(telegram: &TelegramMock) -> String {
+    let edits = telegram.edits();
+    assert_eq!(edits.len(), 1, "expected one edit, got {:?}", edits);
+    assert_eq!(edits[0]["message_id"], 1);
+    edits[0]["text"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn allow_press_approves_and_edits_status() {
+    let telegram = TelegramMock::start().await;
+    telegram.press("✅ Allow", 7);
+
+    let response = run_hook(&telegram, json!({})).await;
+    assert_eq!(behavior(&response), "allow");
+
+    let sent = telegram.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0]["chat_id"], 1);
+    assert_eq!(sent[0]["parse_mode"], "MarkdownV2");
+    assert!(sent[0]["text"].as_str().unwrap().contains("cargo test"));
+    assert_eq!(
+        keyboard_rows(&sent[0]),
+        vec![
+            vec!["✅ Allow", "❌ Deny"],
+            vec!["🔓 Always Allow", "🚫 Deny & Block"],
+            vec!["⏰ Snooze 15m"],
+        ]
+    );
+
+    assert_eq!(telegram.answers().len(), 1);
+    assert!(edited_text(&telegram).ends_with("*Status:* ✅ Approved"));
+}
+
+#[tokio::test]
+async fn deny_press_denies_and_edits_status() {
+    let telegram = TelegramMock::start().await;
+    telegram.press("❌ Deny", 7);
+
+    let response = run_hook(&telegram, json!({})).await;
+    assert_eq!(behavior(&response), "deny");
+    assert!(edited_text(&telegram).ends_with("*Status:* ❌ Denied"));
+}
+
+#[tokio::test]
+async fn unauthorized_press_is_rejected() {
+    let telegram = TelegramMock::start().await;
+    telegram.press("✅ Allow", 7);
+    telegram.press("❌ Deny", 42);
+
+    let response = run_hook(
+        &telegram,
+        json!({"authorized_responders": {"telegram": [42]}}),
+    )
+    .await;
+    assert_eq!(behavior(&response), "deny");
+
+    let answers = telegram.answers();
+    assert_eq!(answers.len(), 2);
+    assert_eq!(answers[0]["show_alert"], true);
+    assert_eq!(
+        answers[0]["text"],
+        "You are not authorized to answer this request"
+    );
+
+    // The request, then the warning about user 7
+    let sent = telegram.sent();
+    assert_eq!(sent.len(), 2);
+    assert!(sent[1]["text"].as_str().unwrap().contains("user7"));
+    assert!(sent[1].get("reply_markup").is_none());
+}
+
+#[tokio::test]
+async fn first_of_two_racing_presses_wins() {
+    let telegram = TelegramMock::start().await;
+    telegram.press("❌ Deny", 7);
+    telegram.press("✅ Allow", 8);
+
+    let response = run_hook(&telegram, json!({})).await;
+    assert_eq!(behavior(&response), "deny");
+    assert!(edited_text(&telegram).ends_with("*Status:* ❌ Denied"));
+
+    let answers = telegram.answers();
+    assert_eq!(answers.len(), 2);
+    assert_eq!(answers[1]["show_alert"], true);
+    assert_eq!(
+        answers[1]["text"],
+        "Already decided: ❌ Denied by @user7 (7)"
+    );
+}
+
+#[tokio::test]
+async fn unanswered_request_times_out_as_deny() {
+    let telegram = TelegramMock::start().await;
+
+    let response = run_hook(
+        &telegram,
+        json!({"preferences": {"timeout_seconds": 2, "escalation": {"remind": false}}}),
+    )
+    .await;
+    assert_eq!(behavior(&response), "deny");
+    assert!(telegram.answers().is_empty());
+    assert!(edited_text(&telegram).ends_with("*Status:* ⏱️ Timeout \\- Denied"));
+}
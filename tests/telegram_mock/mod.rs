@@ -0,0 +1,203 @@
+//! `telegram-mock`: a stand-in for the Telegram Bot API, built on wiremock.
+//!
+//! Point the hook at it with `CCR_TELEGRAM_API_URL`. It records every
+//! `sendMessage`, `editMessageText` and `answerCallbackQuery` call and hands
+//! out scripted button presses through `getUpdates`: a queued press is
+//! delivered once a message showing that button has been sent, as a callback
+//! query on that message carrying the button's `callback_data`. Every press
+//! that can be delivered goes out in the same batch.
+
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use wiremock::matchers::{method, path_regex};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+/// A scripted button press.
+struct Press {
+    button: String,
+    user_id: u64,
+}
+
+#[derive(Default)]
+struct State {
+    sent: Vec<Value>,
+    edits: Vec<Value>,
+    answers: Vec<Value>,
+    presses: VecDeque<Press>,
+    next_update_id: u64,
+}
+
+impl State {
+    /// Turn the first queued press into an update, if its button is showing.
+    fn next_update(&mut self) -> Option<Value> {
+        let press = self.presses.front()?;
+        let (message_id, message, data) =
+            self.sent.iter().enumerate().rev().find_map(|(i, body)| {
+                let data = button_data(body, &press.button)?;
+                Some((i as i64 + 1, body, data))
+            })?;
+
+        self.next_update_id += 1;
+        let update = json!({
+            "update_id": self.next_update_id,
+            "callback_query": {
+                "id": format!("query-{}", self.next_update_id),
+                "from": user(press.user_id),
+                "chat_instance": "1",
+                "data": data,
+                "message": message_json(message_id, message),
+            }
+        });
+        self.presses.pop_front();
+        Some(update)
+    }
+}
+
+/// The `callback_data` of the button with the given text on a sent message.
+fn button_data(body: &Value, text: &str) -> Option<String> {
+    body["reply_markup"]["inline_keyboard"]
+        .as_array()?
+        .iter()
+        .flat_map(|row| row.as_array().into_iter().flatten())
+        .find(|button| button["text"] == text)
+        .and_then(|button| button["callback_data"].as_str())
+        .map(str::to_string)
+}
+
+fn user(id: u64) -> Value {
+    json!({"id": id, "is_bot": false, "first_name": format!("User {}", id), "username": format!("user{}", id)})
+}
+
+/// A Bot API `Message` for a `sendMessage` or `editMessageText` body.
+///
+/// The date must not be 0, which marks a message the bot can no longer see.
+fn message_json(message_id: i64, body: &Value) -> Value {
+    json!({
+        "message_id": message_id,
+        "date": 1_700_000_000,
+        "chat": {"id": body["chat_id"], "type": "private", "first_name": "Test"},
+        "text": body["text"],
+    })
+}
+
+fn ok(result: Value) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({"ok": true, "result": result}))
+}
+
+fn body(request: &Request) -> Value {
+    serde_json::from_slice(&request.body).unwrap_or(Value::Null)
+}
+
+/// Handles one Bot API method given the JSON request body.
+type Handler = fn(&mut State, Value) -> ResponseTemplate;
+
+/// Answers one Bot API method from the shared state.
+struct Method {
+    state: Arc<Mutex<State>>,
+    handle: Handler,
+}
+
+impl Respond for Method {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        (self.handle)(&mut self.state.lock().unwrap(), body(request))
+    }
+}
+
+fn send_message(state: &mut State, body: Value) -> ResponseTemplate {
+    state.sent.push(body.clone());
+    ok(message_json(state.sent.len() as i64, &body))
+}
+
+fn edit_message_text(state: &mut State, body: Value) -> ResponseTemplate {
+    state.edits.push(body.clone());
+    let message_id = body["message_id"].as_i64().unwrap_or_default();
+    ok(message_json(message_id, &body))
+}
+
+fn answer_callback_query(state: &mut State, body: Value) -> ResponseTemplate {
+    state.answers.push(body);
+    ok(json!(true))
+}
+
+/// Presses queued together arrive in the same batch, as when two people
+/// press at nearly the same time.
+fn get_updates(state: &mut State, _body: Value) -> ResponseTemplate {
+    ok(json!(
+        std::iter::from_fn(|| state.next_update()).collect::<Vec<_>>()
+    ))
+}
+
+/// A running mock Bot API server.
+pub struct TelegramMock {
+    server: MockServer,
+    state: Arc<Mutex<State>>,
+}
+
+impl TelegramMock {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        let state = Arc::new(Mutex::new(State::default()));
+        let methods: [(&str, Handler); 4] = [
+            ("sendMessage", send_message),
+            ("editMessageText", edit_message_text),
+            ("answerCallbackQuery", answer_callback_query),
+            ("getUpdates", get_updates),
+        ];
+        for (name, handle) in methods {
+            Mock::given(method("POST"))
+                .and(path_regex(format!("(?i)^/bot[^/]+/{}$", name)))
+                .respond_with(Method {
+                    state: state.clone(),
+                    handle,
+                })
+                .mount(&server)
+                .await;
+        }
+        Self { server, state }
+    }
+
+    /// The base URL to put in `CCR_TELEGRAM_API_URL`.
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Queue a press of the button labelled `button` by the given user.
+    pub fn press(&self, button: &str, user_id: u64) {
+        self.state.lock().unwrap().presses.push_back(Press {
+            button: button.to_string(),
+            user_id,
+        });
+    }
+
+    /// Bodies of every `sendMessage` call, in order.
+    pub fn sent(&self) -> Vec<Value> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// Bodies of every `editMessageText` call, in order.
+    pub fn edits(&self) -> Vec<Value> {
+        self.state.lock().unwrap().edits.clone()
+    }
+
+    /// Bodies of every `answerCallbackQuery` call, in order.
+    pub fn answers(&self) -> Vec<Value> {
+        self.state.lock().unwrap().answers.clone()
+    }
+}
+
+/// Button labels of a sent message, row by row.
+pub fn keyboard_rows(body: &Value) -> Vec<Vec<String>> {
+    body["reply_markup"]["inline_keyboard"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|row| {
+            row.as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|button| button["text"].as_str().map(str::to_string))
+                .collect()
+        })
+        .collect()
+}